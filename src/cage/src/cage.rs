@@ -2,23 +2,33 @@
 //! definitions, a global variables that handles cage management, and cage initialization and
 //! finialization required by wasmtime
 use crate::memory::vmmap::*;
+use crate::signal::IntervalTimer;
 use fdtables;
 pub use once_cell::sync::Lazy;
 /// Uses spinlocks first (for short waits) and parks threads when blocking to reduce kernel
 /// interaction and increases efficiency.
-pub use parking_lot::RwLock;
+pub use parking_lot::{Condvar, Mutex, RwLock};
 pub use std::collections::HashMap;
 use std::ffi::CString;
 pub use std::path::{Path, PathBuf};
+use std::sync::atomic::Ordering::SeqCst;
 pub use std::sync::atomic::{AtomicI32, AtomicU64};
 pub use std::sync::Arc;
 use sysdefs::constants::err_const::VERBOSE;
 use sysdefs::constants::fs_const::*;
+use sysdefs::data::fs_struct::{SigactionStruct, SigsetType};
+
+/// Cage id of the init cage, which adopts the children of exited cages
+pub const INIT_CAGEID: u64 = 1;
 
 #[derive(Debug, Clone, Copy)]
 pub struct Zombie {
     pub cageid: u64,
+    // Process group of the cage when it exited, used by waitpid() with pid 0 or < -1
+    pub pgid: u64,
     pub exit_code: i32,
+    // Signal that terminated the cage, 0 if it exited by itself
+    pub term_signal: i32,
 }
 
 /// I only kept required fields for cage struct
@@ -26,14 +36,21 @@ pub struct Zombie {
 pub struct Cage {
     // Identifying ID number for this cage
     pub cageid: u64,
-    pub parent: u64,
+    // Changes when the parent exits, as the cage is then re-parented to the init cage
+    pub parent: AtomicU64,
+    // Process group of the cage, inherited from the parent
+    pub pgid: AtomicU64,
     // Current working directory of cage, must be able to be unique from other cages
     pub cwd: RwLock<Arc<PathBuf>>,
-    // Identifiers for gid/uid/egid/euid
+    // Real, effective and saved user and group ids of the cage, read by getuid() and friends and
+    // changed by setuid() and friends. They are only modified while holding `CREDENTIALS_LOCK` in
+    // rawposix, so a cage never observes a half-applied change
     pub gid: AtomicI32,
     pub uid: AtomicI32,
     pub egid: AtomicI32,
     pub euid: AtomicI32,
+    pub sgid: AtomicI32,
+    pub suid: AtomicI32,
     // The kernel thread id of the main thread of current cage, used because when we want to send signals,
     // we want to send to the main thread
     pub main_threadid: AtomicU64,
@@ -42,9 +59,25 @@ pub struct Cage {
     // When a cage exits, shared memory segments are detached, file descriptors are removed from fdtable,
     // and cage struct is cleaned up, but its exit status are inserted along with its cage id into the end of
     // its parent cage's zombies list
-    pub zombies: RwLock<Vec<Zombie>>,
+    pub zombies: Mutex<Vec<Zombie>>,
+    // Notified each time a zombie is inserted into `zombies`, wakes up the cage blocked in wait()
+    pub zombie_cv: Condvar,
+    // Number of running children, only modified while holding `zombies`
     pub child_num: AtomicU64,
     pub vmmap: RwLock<Vmmap>,
+    // Signal handlers registered by sigaction(), keyed by signal number. Signals without an entry use
+    // the default action
+    pub signalhandler: RwLock<HashMap<i32, SigactionStruct>>,
+    // Signal masks of each thread in the cage, keyed by kernel thread id. The mask of the main thread
+    // is stored under key 0 (see `signal::sigmask_key`)
+    pub sigset: RwLock<HashMap<u64, SigsetType>>,
+    // Signals sent to this cage but not delivered yet, bit (signo - 1) is set for a pending signal
+    pub pending_signals: AtomicU64,
+    // ITIMER_REAL timer of the cage, raises SIGALRM when it expires
+    pub interval_timer: IntervalTimer,
+    // Signal that terminated the cage, 0 while it runs or when it exits by itself. Copied into its
+    // zombie so that waitpid() reports it apart from the exit code
+    pub term_signal: AtomicI32,
 }
 
 /// We achieve an O(1) complexity for our cage map implementation through the following three approaches:
@@ -119,3 +152,85 @@ pub fn cagetable_clear() -> Vec<usize> {
 
     exitvec
 }
+
+/// Removes an exiting cage from `CAGE_MAP` and inserts its exit status into the zombie list of its
+/// parent, waking up the parent if it is blocked in wait().
+///
+/// Both happen while the parent's zombie list is locked, so a parent in waitpid() always sees the child
+/// either running (in `CAGE_MAP`) or as a zombie. The parent may change concurrently: it re-parents its
+/// children to init when it exits, and it is replaced in `CAGE_MAP` when it calls exec(). So the parent
+/// is checked again once its zombie list is locked, and the insertion is retried otherwise.
+pub fn remove_cage_to_zombie(cage: &Cage, exit_code: i32) {
+    loop {
+        let parentid = cage.parent.load(SeqCst);
+        // cage 0 and init are their own parents, nobody waits for them
+        if parentid == cage.cageid {
+            remove_cage(cage.cageid);
+            return;
+        }
+        let parent = match get_cage(parentid) {
+            Some(parent) => parent,
+            None => {
+                // Only happens when the init cage has already exited, the exit status is dropped
+                remove_cage(cage.cageid);
+                return;
+            }
+        };
+
+        let mut zombies = parent.zombies.lock();
+        let replaced = match get_cage(parentid) {
+            Some(current) => !Arc::ptr_eq(&current, &parent),
+            None => true,
+        };
+        if replaced || cage.parent.load(SeqCst) != parentid {
+            continue;
+        }
+
+        remove_cage(cage.cageid);
+        zombies.push(Zombie {
+            cageid: cage.cageid,
+            pgid: cage.pgid.load(SeqCst),
+            exit_code,
+            term_signal: cage.term_signal.load(SeqCst),
+        });
+        parent.child_num.fetch_sub(1, SeqCst);
+        parent.zombie_cv.notify_all();
+        return;
+    }
+}
+
+/// Re-parents the children of an exiting cage to the init cage, running children as well as zombies
+/// that were not waited for, so that init can reap them. Must be called before the exiting cage is
+/// removed with `remove_cage_to_zombie`.
+pub fn reparent_children(cage: &Cage) {
+    let init = match get_cage(INIT_CAGEID) {
+        Some(init) => init,
+        None => return,
+    };
+    if cage.cageid == INIT_CAGEID {
+        return;
+    }
+
+    // Children exiting concurrently either already moved to our zombie list, or see their new parent
+    // once they get the lock. Locks are always taken in this order (exiting cage, then init) and init
+    // never re-parents its children, so this can't deadlock
+    let mut zombies = cage.zombies.lock();
+    let mut init_zombies = init.zombies.lock();
+
+    let mut adopted = 0;
+    for child in CAGE_MAP.read().iter().flatten() {
+        if child
+            .parent
+            .compare_exchange(cage.cageid, INIT_CAGEID, SeqCst, SeqCst)
+            .is_ok()
+        {
+            adopted += 1;
+        }
+    }
+    init.child_num.fetch_add(adopted, SeqCst);
+
+    if !zombies.is_empty() {
+        init_zombies.append(&mut zombies);
+        init.zombie_cv.notify_all();
+    }
+}
@@ -4,8 +4,12 @@
 //! initializing vmmap, helper functions for handling vmmap during a fork syscall, and
 //! address translation and validation related to vmmap
 use crate::cage::{get_cage, Cage};
-use crate::memory::{MemoryBackingType, Vmmap, VmmapOps};
+use crate::memory::{MemoryBackingType, MemorySnapshot, Vmmap, VmmapOps};
 use libc::c_void;
+use nodit::interval::ie;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::sync::Arc;
 use sysdefs::constants::err_const::{syscall_error, Errno};
 use sysdefs::constants::fs_const::{
     F_GETFL, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MAP_SHARED, MREMAP_FIXED, MREMAP_MAYMOVE,
@@ -30,6 +34,11 @@ pub fn round_up_page(length: u64) -> u64 {
     }
 }
 
+/// Bits of an entry of `/proc/self/pagemap`, see "man 5 proc_pid_pagemap"
+const PAGEMAP_PRESENT: u64 = 1 << 63;
+const PAGEMAP_SWAPPED: u64 = 1 << 62;
+const PAGEMAP_FILE: u64 = 1 << 61;
+
 /// Copies the memory regions from parent to child based on the provided `vmmap` memory layout.
 ///
 /// This function is designed to replicate the parent's memory space into the child immediately after
@@ -41,27 +50,37 @@ pub fn round_up_page(length: u64) -> u64 {
 ///    - No action is taken, as memory regions are already configured with `PROT_NONE` by default.
 /// 2. **Shared memory regions**:
 ///    - The function uses the `mremap` syscall to replicate shared memory efficiently. Refer to `man 2 mremap` for details.
+///    - Attached System V shared memory segments are handled the same way, so the child shares the segment's
+///      pages with the parent instead of getting a copy. Their attach counts are updated in `fork_syscall`.
 /// 3. **Private memory regions**:
-///    - The function uses `std::ptr::copy_nonoverlapping` to copy the memory contents directly.
-///    - **TODO**: Investigate whether using `writev` could improve performance for this case.
+///    - The region is mapped into the child from a `MemorySnapshot` with `MAP_PRIVATE`, so the host kernel
+///      copies a page only once the parent or the child writes to it.
+///    - The first fork of a region copies it into a new snapshot, which the parent maps the region from as
+///      well. Later forks only copy the pages the parent wrote since, found in `/proc/self/pagemap`, and
+///      the pages of the region outside the snapshot, e.g. when the heap grew.
+///    - The parent's pages are replaced when the region is copied into a snapshot, so writes of other
+///      threads of the parent during the fork can be lost.
+///    - The region is copied with `std::ptr::copy_nonoverlapping` if no snapshot can be created.
 ///
 /// # Arguments
 /// * `parent_vmmap` - vmmap struct of parent
 /// * `child_vmmap` - vmmap struct of child
-pub fn fork_vmmap(parent_vmmap: &Vmmap, child_vmmap: &Vmmap) {
+pub fn fork_vmmap(parent_vmmap: &mut Vmmap, child_vmmap: &mut Vmmap) {
     let parent_base = parent_vmmap.base_address.unwrap();
     let child_base = child_vmmap.base_address.unwrap();
+    let pagemap = File::open("/proc/self/pagemap").ok();
 
     // iterate through each vmmap entry
-    for (_interval, entry) in parent_vmmap.entries.iter() {
+    for (_interval, entry) in parent_vmmap.entries.iter_mut() {
         // translate page number to user address
         let addr_st = (entry.page_num << PAGESHIFT) as u32;
         let addr_len = (entry.npages << PAGESHIFT) as usize;
 
         // translate user address to system address
-        let parent_st = parent_vmmap.user_to_sys(addr_st);
-        let child_st = child_vmmap.user_to_sys(addr_st);
-        if entry.flags & (MAP_SHARED as i32) > 0 {
+        let parent_st = parent_base + addr_st as usize;
+        let child_st = child_base + addr_st as usize;
+        let is_shm = matches!(entry.backing, MemoryBackingType::SharedMemory(_));
+        if is_shm || entry.flags & (MAP_SHARED as i32) > 0 {
             // for shared memory, we are using mremap to fork shared memory
             // See "man 2 mremap" for description of what MREMAP_MAYMOVE does with old_size=0
             // when old_address points to a shared mapping
@@ -74,26 +93,256 @@ pub fn fork_vmmap(parent_vmmap: &Vmmap, child_vmmap: &Vmmap) {
                     child_st as *mut libc::c_void,
                 )
             };
-        } else {
-            unsafe {
-                // temporarily enable write on child's memory region to write parent data
-                libc::mprotect(
-                    child_st as *mut libc::c_void,
-                    addr_len,
-                    PROT_READ | PROT_WRITE,
-                );
-
-                // write parent data
-                // TODO: replace copy_nonoverlapping with writev for potential performance boost
-                std::ptr::copy_nonoverlapping(
-                    parent_st as *const u8,
-                    child_st as *mut u8,
-                    addr_len,
-                );
+            continue;
+        }
 
-                // revert child's memory region prot
-                libc::mprotect(child_st as *mut libc::c_void, addr_len, entry.prot)
+        let snapshot = match &entry.snapshot {
+            Some(snapshot) => snapshot.clone(),
+            None => {
+                // copy the region into a new snapshot, and map the parent's region from it
+                match take_snapshot(parent_st, entry.page_num, entry.npages, entry.prot) {
+                    Some(snapshot) => {
+                        map_snapshot(
+                            &snapshot,
+                            parent_st,
+                            entry.page_num,
+                            entry.npages,
+                            entry.prot,
+                        );
+                        entry.snapshot = Some(snapshot.clone());
+                        snapshot
+                    }
+                    None => {
+                        copy_pages(
+                            parent_st,
+                            child_st,
+                            &vec![true; entry.npages as usize],
+                            entry.prot,
+                        );
+                        continue;
+                    }
+                }
+            }
+        };
+
+        // map the pages of the region inside the snapshot from it
+        let first_page = entry.page_num.max(snapshot.page_num);
+        let end_page = (entry.page_num + entry.npages).min(snapshot.page_num + snapshot.npages);
+        if first_page < end_page {
+            map_snapshot(
+                &snapshot,
+                child_base + (first_page << PAGESHIFT) as usize,
+                first_page,
+                end_page - first_page,
+                entry.prot,
+            );
+        }
+
+        // then copy the pages that the parent wrote since the snapshot was taken, and those outside it
+        let mut pages = written_pages(pagemap.as_ref(), parent_st, entry.npages as usize);
+        for (page_num, page) in (entry.page_num..).zip(pages.iter_mut()) {
+            *page |= !snapshot.contains(page_num);
+        }
+        copy_pages(parent_st, child_st, &pages, entry.prot);
+
+        if let Some(child_entry) = child_vmmap.entries.get_at_point_mut(entry.page_num) {
+            child_entry.snapshot = Some(snapshot);
+        }
+    }
+}
+
+/// Copies the `npages` pages at host address `sys_st`, which hold the cage memory from page `page_num`
+/// on, into a new memfd. Returns `None` if the memfd can't be created or written.
+fn take_snapshot(
+    sys_st: usize,
+    page_num: u32,
+    npages: u32,
+    prot: i32,
+) -> Option<Arc<MemorySnapshot>> {
+    let fd = unsafe {
+        libc::memfd_create(
+            b"lind_snapshot\0".as_ptr() as *const libc::c_char,
+            libc::MFD_CLOEXEC,
+        )
+    };
+    if fd < 0 {
+        return None;
+    }
+    // from now on, the memfd is closed when the snapshot is dropped
+    let snapshot = Arc::new(MemorySnapshot {
+        fd,
+        page_num,
+        npages,
+    });
+
+    let len = (npages as usize) << PAGESHIFT;
+    if unsafe { libc::ftruncate(fd, len as i64) } < 0 {
+        return None;
+    }
+
+    // temporarily enable read on the region if needed
+    if prot & PROT_READ == 0 {
+        unsafe { libc::mprotect(sys_st as *mut c_void, len, PROT_READ) };
+    }
+    let mut written = 0;
+    while written < len {
+        let ret = unsafe {
+            libc::pwrite(
+                fd,
+                (sys_st + written) as *const c_void,
+                len - written,
+                written as i64,
+            )
+        };
+        if ret <= 0 {
+            break;
+        }
+        written += ret as usize;
+    }
+    if prot & PROT_READ == 0 {
+        unsafe { libc::mprotect(sys_st as *mut c_void, len, prot) };
+    }
+
+    if written < len {
+        return None;
+    }
+    Some(snapshot)
+}
+
+/// Maps the `npages` pages of the cage memory from page `page_num` on at host address `sys_st`, from the
+/// snapshot holding them, with `MAP_PRIVATE`
+fn map_snapshot(snapshot: &MemorySnapshot, sys_st: usize, page_num: u32, npages: u32, prot: i32) {
+    let result = unsafe {
+        libc::mmap(
+            sys_st as *mut c_void,
+            (npages as usize) << PAGESHIFT,
+            prot,
+            (MAP_PRIVATE | MAP_FIXED) as i32,
+            snapshot.fd,
+            snapshot.offset(page_num),
+        ) as usize
+    };
+    if result != sys_st {
+        panic!("MAP_FIXED not fixed");
+    }
+}
+
+/// Returns whether each of the `npages` pages at host address `sys_st` was written since it was mapped
+/// from a file, i.e. whether the page is a private copy rather than a page of the file. All the pages are
+/// considered written if the pagemap can't be read.
+fn written_pages(pagemap: Option<&File>, sys_st: usize, npages: usize) -> Vec<bool> {
+    let mut entries = vec![0u8; npages * 8];
+    let offset = (sys_st >> PAGESHIFT) as u64 * 8;
+    match pagemap.map(|pagemap| pagemap.read_exact_at(&mut entries, offset)) {
+        Some(Ok(())) => entries
+            .chunks_exact(8)
+            .map(|entry| {
+                let entry = u64::from_ne_bytes(entry.try_into().unwrap());
+                // a private copy is not a file page, and it may be swapped out
+                (entry & PAGEMAP_PRESENT != 0 && entry & PAGEMAP_FILE == 0)
+                    || entry & PAGEMAP_SWAPPED != 0
+            })
+            .collect(),
+        _ => vec![true; npages],
+    }
+}
+
+/// Copies the pages of a parent's region selected by `pages` to the same pages of the child's region,
+/// whose protection is `prot`
+fn copy_pages(parent_st: usize, child_st: usize, pages: &[bool], prot: i32) {
+    if !pages.contains(&true) {
+        return;
+    }
+    let len = pages.len() << PAGESHIFT;
+    unsafe {
+        // temporarily enable read on parent's and write on child's memory region
+        if prot & PROT_READ == 0 {
+            libc::mprotect(parent_st as *mut c_void, len, PROT_READ);
+        }
+        libc::mprotect(child_st as *mut c_void, len, PROT_READ | PROT_WRITE);
+
+        // write parent data, one run of selected pages at a time
+        let mut page = 0;
+        while page < pages.len() {
+            if !pages[page] {
+                page += 1;
+                continue;
+            }
+            let start = page;
+            while page < pages.len() && pages[page] {
+                page += 1;
+            }
+            std::ptr::copy_nonoverlapping(
+                (parent_st + (start << PAGESHIFT)) as *const u8,
+                (child_st + (start << PAGESHIFT)) as *mut u8,
+                (page - start) << PAGESHIFT,
+            );
+        }
+
+        // revert prots
+        if prot & PROT_READ == 0 {
+            libc::mprotect(parent_st as *mut c_void, len, prot);
+        }
+        libc::mprotect(child_st as *mut c_void, len, prot);
+    }
+}
+
+/// Releases the host memory of the `npages` pages of the cage memory from page `page_num` on, which are
+/// left inaccessible.
+///
+/// Private anonymous pages are discarded with `MADV_DONTNEED`, which keeps their host mapping. The other
+/// pages are replaced with a new anonymous mapping, so that the host also drops the file, shared memory
+/// segment or snapshot they are mapped from. The vmmap entries of the pages are left unchanged.
+///
+/// # Arguments
+/// * `vmmap` - vmmap of the cage
+/// * `page_num` - first page to release
+/// * `npages` - number of pages to release
+pub fn release_pages(vmmap: &Vmmap, page_num: u32, npages: u32) {
+    let end_page = page_num + npages;
+
+    // split the pages into ranges released the same way, pages without entry are remapped
+    let mut ranges = Vec::new();
+    let mut next_page = page_num;
+    for (interval, entry) in vmmap.entries.overlapping(ie(page_num, end_page)) {
+        let start = interval.start().max(page_num);
+        let end = (interval.end() + 1).min(end_page); // `end()` is inclusive
+        if next_page < start {
+            ranges.push((next_page, start, false));
+        }
+        let private_anonymous = entry.flags & MAP_SHARED as i32 == 0
+            && entry.flags & MAP_ANONYMOUS as i32 != 0
+            && entry.backing == MemoryBackingType::Anonymous
+            && entry.snapshot.is_none();
+        ranges.push((start, end, private_anonymous));
+        next_page = end;
+    }
+    if next_page < end_page {
+        ranges.push((next_page, end_page, false));
+    }
+
+    for (start, end, private_anonymous) in ranges {
+        let sysaddr = vmmap.user_to_sys(start << PAGESHIFT);
+        let len = ((end - start) as usize) << PAGESHIFT;
+        if private_anonymous {
+            unsafe {
+                libc::madvise(sysaddr as *mut c_void, len, libc::MADV_DONTNEED);
+                libc::mprotect(sysaddr as *mut c_void, len, PROT_NONE);
+            }
+        } else {
+            let result = unsafe {
+                libc::mmap(
+                    sysaddr as *mut c_void,
+                    len,
+                    PROT_NONE,
+                    (MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED) as i32,
+                    -1,
+                    0,
+                ) as usize
             };
+            if result != sysaddr {
+                panic!("MAP_FIXED not fixed");
+            }
         }
     }
 }
@@ -113,14 +362,12 @@ pub fn init_vmmap_helper(cageid: u64, base_address: usize, program_break: Option
 pub fn fork_vmmap_helper(parent_cageid: u64, child_cageid: u64) {
     let parent_cage = get_cage(parent_cageid).unwrap();
     let child_cage = get_cage(child_cageid).unwrap();
-    let parent_vmmap = parent_cage.vmmap.read();
-    let child_vmmap = child_cage.vmmap.read();
+    let mut parent_vmmap = parent_cage.vmmap.write();
+    let mut child_vmmap = child_cage.vmmap.write();
 
-    fork_vmmap(&parent_vmmap, &child_vmmap);
+    fork_vmmap(&mut parent_vmmap, &mut child_vmmap);
 
     // update program break for child
-    drop(child_vmmap);
-    let mut child_vmmap = child_cage.vmmap.write();
     child_vmmap.set_program_break(parent_vmmap.program_break);
 }
 
@@ -139,7 +386,7 @@ pub fn fork_vmmap_helper(parent_cageid: u64, child_cageid: u64) {
 ///
 /// # Returns
 /// * `Ok(u64)` - Physical memory address if validation succeeds
-/// * `Err(Errno)` - EFAULT if memory access would be invalid
+/// * `Err(Errno)` - EFAULT if memory access would be invalid, or the cage doesn't exist
 ///
 /// # Memory Safety
 /// This is a critical security function that prevents invalid memory accesses by:
@@ -153,15 +400,13 @@ pub fn check_and_convert_addr_ext(
     prot: i32,
 ) -> Result<u64, Errno> {
     // search from the table and get the item from
-    let cage = get_cage(cageid).unwrap();
+    let cage = get_cage(cageid).ok_or(Errno::EFAULT)?;
 
     // Get read lock on virtual memory map
     let mut vmmap = cage.vmmap.write();
 
     // Calculate page numbers for start and end of region
-    let page_num = (arg >> PAGESHIFT) as u32; // Starting page number
-    let end_page = ((arg + length as u64 + PAGESIZE as u64 - 1) >> PAGESHIFT) as u32; // Ending page number (rounded up)
-    let npages = end_page - page_num; // Total number of pages spanned
+    let (page_num, npages) = _addr_to_pages(arg, length)?;
 
     // Validate memory mapping and permissions
     if vmmap.check_addr_mapping(page_num, npages, prot).is_none() {
@@ -169,20 +414,33 @@ pub fn check_and_convert_addr_ext(
     }
 
     // Convert to physical address by adding base address
-    Ok(vmmap.base_address.unwrap() as u64 + arg)
+    vmmap
+        .base_address
+        .map(|base| base as u64 + arg)
+        .ok_or(Errno::EFAULT)
+}
+
+/// Returns the first page and the number of pages spanned by the region of `length` bytes at `arg`, or
+/// EFAULT if the region doesn't fit in the 32-bit linear memory of a cage
+fn _addr_to_pages(arg: u64, length: usize) -> Result<(u32, u32), Errno> {
+    let end = match arg.checked_add(length as u64) {
+        Some(end) if end <= 1 << 32 => end,
+        _ => return Err(Errno::EFAULT),
+    };
+    let page_num = (arg >> PAGESHIFT) as u32; // Starting page number
+    let end_page = ((end + PAGESIZE as u64 - 1) >> PAGESHIFT) as u32; // Ending page number (rounded up)
+    Ok((page_num, end_page - page_num)) // Total number of pages spanned
 }
 
 pub fn check_addr(cageid: u64, arg: u64, length: usize, prot: i32) -> Result<bool, Errno> {
     // search from the table and get the item from
-    let cage = get_cage(cageid).unwrap();
+    let cage = get_cage(cageid).ok_or(Errno::EFAULT)?;
 
     // Get read lock on virtual memory map
     let mut vmmap = cage.vmmap.write();
 
     // Calculate page numbers for start and end of region
-    let page_num = (arg >> PAGESHIFT) as u32; // Starting page number
-    let end_page = ((arg + length as u64 + PAGESIZE as u64 - 1) >> PAGESHIFT) as u32; // Ending page number (rounded up)
-    let npages = end_page - page_num; // Total number of pages spanned
+    let (page_num, npages) = _addr_to_pages(arg, length)?;
 
     // Validate memory mapping and permissions
     if vmmap.check_addr_mapping(page_num, npages, prot).is_none() {
@@ -199,8 +457,12 @@ pub fn check_addr(cageid: u64, arg: u64, length: usize, prot: i32) -> Result<boo
 ///
 /// # Returns
 /// * `Ok(u64)` - Translated physical memory address
+/// * `Err(Errno)` - EFAULT if the memory of the cage isn't set up yet
 pub fn translate_vmmap_addr(cage: &Cage, arg: u64) -> Result<u64, Errno> {
     // Get read lock on virtual memory map
     let vmmap = cage.vmmap.read();
-    Ok(vmmap.base_address.unwrap() as u64 + arg)
+    vmmap
+        .base_address
+        .map(|base| base as u64 + arg)
+        .ok_or(Errno::EFAULT)
 }
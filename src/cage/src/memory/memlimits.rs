@@ -0,0 +1,203 @@
+//! Memory accounting and limits of the cages
+//!
+//! This file provides the counters of the pages committed by the mappings of a cage and the limits they
+//! are checked against. Every `Vmmap` counts the pages of its entries by type of backing in
+//! `CommittedPages`, which also keeps the count of the pages committed by all cages up to date. A cage
+//! has its own RLIMIT_AS and RLIMIT_DATA limits, kept in its `Vmmap` so that they are inherited on fork,
+//! and the whole runtime has a limit on the pages committed by all cages, so that one cage can't take the
+//! memory of its siblings.
+//!
+//! Mappings check the limits before they are made with `Vmmap::reserve_memory`, which counts the new
+//! pages in the global count until the returned `MemoryReservation` is dropped. The global count is then
+//! never below the pages actually committed, even while other cages map memory concurrently.
+use crate::memory::MemoryBackingType;
+use std::sync::atomic::{AtomicU64, Ordering};
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::PAGESHIFT;
+use sysdefs::constants::sys_const::RLIM_INFINITY;
+
+/// Most bytes which may be committed across all cages, and how many pages are now
+static TOTAL_MEMORY_LIMIT: AtomicU64 = AtomicU64::new(RLIM_INFINITY);
+static TOTAL_COMMITTED_PAGES: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the most bytes which may be committed across all cages
+pub fn get_total_memory_limit() -> u64 {
+    TOTAL_MEMORY_LIMIT.load(Ordering::SeqCst)
+}
+
+/// Sets the most bytes which may be committed across all cages. Once they are, mappings fail with
+/// ENOMEM. Memory already committed above the new limit stays mapped.
+pub fn set_total_memory_limit(limit: u64) {
+    TOTAL_MEMORY_LIMIT.store(limit, Ordering::SeqCst);
+}
+
+/// Returns the number of pages now committed across all cages
+pub fn get_total_committed_pages() -> u64 {
+    TOTAL_COMMITTED_PAGES.load(Ordering::SeqCst)
+}
+
+/// Returns the number of bytes in `npages` pages
+fn pages_to_bytes(npages: u64) -> u64 {
+    npages << PAGESHIFT
+}
+
+/// The (soft, hard) memory limits of a cage, in bytes, as set by `setrlimit`. The soft limits are the
+/// ones enforced, the hard limits are the most the soft limits may be raised to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct MemoryLimits {
+    pub address_space: (u64, u64), // RLIMIT_AS, all the pages committed by the cage
+    pub data: (u64, u64),          // RLIMIT_DATA, the anonymous pages, the heap included
+}
+
+impl MemoryLimits {
+    /// Limits that are never reached
+    pub const UNLIMITED: MemoryLimits = MemoryLimits {
+        address_space: (RLIM_INFINITY, RLIM_INFINITY),
+        data: (RLIM_INFINITY, RLIM_INFINITY),
+    };
+}
+
+impl Default for MemoryLimits {
+    fn default() -> Self {
+        MemoryLimits::UNLIMITED
+    }
+}
+
+/// Number of pages committed by the entries of a vmmap, by type of backing
+///
+/// Any change of the counts is applied to the count of pages committed by all cages too. A copy of the
+/// counts, made when a vmmap is cloned for a forked cage, adds its pages to the global count, and the
+/// counts remove their pages from it when they are dropped with their vmmap.
+#[derive(PartialEq, Eq, Debug, Default)]
+pub struct CommittedPages {
+    anonymous: u32, // Anonymous mappings, shared or private, and the heap
+    file: u32,      // Mappings of files
+    shared: u32,    // Attached System V shared memory segments
+}
+
+impl CommittedPages {
+    /// Returns the number of pages of anonymous mappings
+    pub fn anonymous(&self) -> u32 {
+        self.anonymous
+    }
+
+    /// Returns the number of pages of file mappings
+    pub fn file(&self) -> u32 {
+        self.file
+    }
+
+    /// Returns the number of pages of attached shared memory segments
+    pub fn shared(&self) -> u32 {
+        self.shared
+    }
+
+    /// Returns the number of pages of all the mappings
+    pub fn total(&self) -> u64 {
+        self.anonymous as u64 + self.file as u64 + self.shared as u64
+    }
+
+    /// Returns the count of the pages with backing `backing`, `None` for the placeholder backing which
+    /// no mapping has
+    fn count_mut(&mut self, backing: MemoryBackingType) -> Option<&mut u32> {
+        match backing {
+            MemoryBackingType::Anonymous => Some(&mut self.anonymous),
+            MemoryBackingType::FileDescriptor(_) => Some(&mut self.file),
+            MemoryBackingType::SharedMemory(_) => Some(&mut self.shared),
+            MemoryBackingType::None => None,
+        }
+    }
+
+    /// Counts `npages` more pages with backing `backing`
+    pub fn add(&mut self, backing: MemoryBackingType, npages: u32) {
+        if let Some(count) = self.count_mut(backing) {
+            *count += npages;
+            TOTAL_COMMITTED_PAGES.fetch_add(npages as u64, Ordering::SeqCst);
+        }
+    }
+
+    /// Counts `npages` pages with backing `backing` as no longer committed
+    pub fn sub(&mut self, backing: MemoryBackingType, npages: u32) {
+        if let Some(count) = self.count_mut(backing) {
+            *count -= npages;
+            TOTAL_COMMITTED_PAGES.fetch_sub(npages as u64, Ordering::SeqCst);
+        }
+    }
+
+    /// Checks that committing `npages` pages with backing `backing`, in place of the committed pages
+    /// `replaced` given as (backing, number of pages), keeps the cage within `limits` and all the cages
+    /// within the global limit
+    ///
+    /// Returns:
+    /// - A reservation of the pages that are added to the global count, to keep until the pages are
+    ///   counted here
+    /// - Err(Errno::ENOMEM) if a limit would be exceeded
+    pub fn reserve(
+        &self,
+        limits: &MemoryLimits,
+        backing: MemoryBackingType,
+        npages: u32,
+        replaced: impl IntoIterator<Item = (MemoryBackingType, u32)>,
+    ) -> Result<MemoryReservation, Errno> {
+        let mut total = self.total() + npages as u64;
+        let mut anonymous = self.anonymous as u64;
+        if backing == MemoryBackingType::Anonymous {
+            anonymous += npages as u64;
+        }
+        for (replaced_backing, replaced_npages) in replaced {
+            if replaced_backing == MemoryBackingType::None {
+                continue;
+            }
+            total -= replaced_npages as u64;
+            if replaced_backing == MemoryBackingType::Anonymous {
+                anonymous -= replaced_npages as u64;
+            }
+        }
+        if pages_to_bytes(total) > limits.address_space.0
+            || pages_to_bytes(anonymous) > limits.data.0
+        {
+            return Err(Errno::ENOMEM);
+        }
+
+        // Only the pages beyond those the cage already committed count against the global limit
+        let growth = total.saturating_sub(self.total());
+        let limit = TOTAL_MEMORY_LIMIT.load(Ordering::SeqCst);
+        match TOTAL_COMMITTED_PAGES.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+            Some(current + growth).filter(|newcount| pages_to_bytes(*newcount) <= limit)
+        }) {
+            Ok(_) => Ok(MemoryReservation { npages: growth }),
+            Err(_) => Err(Errno::ENOMEM),
+        }
+    }
+}
+
+impl Clone for CommittedPages {
+    fn clone(&self) -> Self {
+        TOTAL_COMMITTED_PAGES.fetch_add(self.total(), Ordering::SeqCst);
+        CommittedPages {
+            anonymous: self.anonymous,
+            file: self.file,
+            shared: self.shared,
+        }
+    }
+}
+
+impl Drop for CommittedPages {
+    fn drop(&mut self) {
+        TOTAL_COMMITTED_PAGES.fetch_sub(self.total(), Ordering::SeqCst);
+    }
+}
+
+/// Pages counted in the global count ahead of a mapping, so that other cages can't commit them while the
+/// mapping is made. They are removed from the count when the reservation is dropped, which must happen
+/// once the mapping is counted in the vmmap, or failed.
+#[must_use]
+#[derive(Debug)]
+pub struct MemoryReservation {
+    npages: u64,
+}
+
+impl Drop for MemoryReservation {
+    fn drop(&mut self) {
+        TOTAL_COMMITTED_PAGES.fetch_sub(self.npages, Ordering::SeqCst);
+    }
+}
@@ -1,6 +1,10 @@
 //! This module is VMMAP specific
 pub mod mem_helper;
+pub mod memlimits;
+pub mod shm;
 pub mod vmmap;
 
 pub use mem_helper::*;
+pub use memlimits::*;
+pub use shm::*;
 pub use vmmap::*;
@@ -0,0 +1,240 @@
+//! System V shared memory segments
+//!
+//! This file provides the global table of System V shared memory segments used by the
+//! `shmget`/`shmat`/`shmdt`/`shmctl` syscalls. Every segment is backed by a host memfd, and attaching
+//! a segment maps that memfd `MAP_SHARED` into the cage's linear memory, so all attachments, in any
+//! cage, refer to the same host pages. Attachments are recorded in the cage's `vmmap` as entries
+//! backed by `MemoryBackingType::SharedMemory(shmid)`, which is how they are found again on fork,
+//! exit and exec.
+use crate::memory::{MemoryBackingType, Vmmap};
+use once_cell::sync::Lazy;
+use parking_lot::Mutex;
+use std::collections::HashMap;
+use std::time::{SystemTime, UNIX_EPOCH};
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    IPC_PRIVATE, MAP_FIXED, MAP_SHARED, PAGESHIFT, PAGESIZE, SHMMNI, SHM_DEST,
+};
+use sysdefs::constants::sys_const::{DEFAULT_GID, DEFAULT_UID};
+use sysdefs::data::fs_struct::{IpcPermStruct, ShmidsStruct};
+
+/// A shared memory segment and its `shmid_ds` bookkeeping
+pub struct ShmSegment {
+    pub size: usize,           // Size requested at creation, in bytes
+    pub memfd: i32,            // Host memfd holding the segment's pages
+    pub shminfo: ShmidsStruct, // What IPC_STAT reports, including the attach count
+    pub rmid: bool,            // IPC_RMID was requested, destroy once the last attachment is gone
+}
+
+impl ShmSegment {
+    /// Creates the memfd backing a new segment of `size` bytes, rounded up to whole pages.
+    /// Returns `None` if the host could not provide the memory.
+    fn new(key: i32, size: usize, mode: u16, cageid: u64) -> Option<Self> {
+        let memfd = unsafe { libc::memfd_create(c"lind_shm".as_ptr(), libc::MFD_CLOEXEC) };
+        if memfd < 0 {
+            return None;
+        }
+        let rounded_size = ((size + PAGESIZE as usize - 1) >> PAGESHIFT) << PAGESHIFT;
+        if unsafe { libc::ftruncate(memfd, rounded_size as i64) } < 0 {
+            unsafe { libc::close(memfd) };
+            return None;
+        }
+
+        let mut shminfo = ShmidsStruct::default();
+        shminfo.shm_perm.__key = key;
+        shminfo.shm_perm.uid = DEFAULT_UID;
+        shminfo.shm_perm.gid = DEFAULT_GID;
+        shminfo.shm_perm.cuid = DEFAULT_UID;
+        shminfo.shm_perm.cgid = DEFAULT_GID;
+        shminfo.shm_perm.mode = mode & 0o777;
+        shminfo.shm_segsz = size as u32;
+        shminfo.shm_ctime = now();
+        shminfo.shm_cpid = cageid as u32;
+
+        Some(ShmSegment {
+            size,
+            memfd,
+            shminfo,
+            rmid: false,
+        })
+    }
+
+    /// Updates the owner and permission bits from `perm`, as IPC_SET does
+    pub fn set_perm(&mut self, perm: &IpcPermStruct) {
+        self.shminfo.shm_perm.uid = perm.uid;
+        self.shminfo.shm_perm.gid = perm.gid;
+        self.shminfo.shm_perm.mode = (self.shminfo.shm_perm.mode & !0o777) | (perm.mode & 0o777);
+        self.shminfo.shm_ctime = now();
+    }
+
+    /// Number of pages an attachment of this segment spans
+    pub fn npages(&self) -> u32 {
+        ((self.size + PAGESIZE as usize - 1) >> PAGESHIFT) as u32
+    }
+
+    /// Maps the segment's pages at the host address `sysaddr` with protection `prot`.
+    /// Returns the address mapped, or `libc::MAP_FAILED`.
+    pub fn map_at(&self, sysaddr: usize, prot: i32) -> *mut libc::c_void {
+        unsafe {
+            libc::mmap(
+                sysaddr as *mut libc::c_void,
+                (self.npages() as usize) << PAGESHIFT,
+                prot,
+                (MAP_SHARED | MAP_FIXED) as i32,
+                self.memfd,
+                0,
+            )
+        }
+    }
+}
+
+impl Drop for ShmSegment {
+    fn drop(&mut self) {
+        // pages still mapped somewhere keep their own reference to the memfd
+        unsafe { libc::close(self.memfd) };
+    }
+}
+
+/// Table of all shared memory segments, indexed by shmid
+pub struct ShmTable {
+    pub segments: HashMap<i32, ShmSegment>,
+    keys: HashMap<i32, i32>, // key -> shmid, for segments not created with IPC_PRIVATE
+    next_shmid: i32,
+}
+
+impl ShmTable {
+    fn new() -> Self {
+        ShmTable {
+            segments: HashMap::new(),
+            keys: HashMap::new(),
+            next_shmid: 1,
+        }
+    }
+
+    /// Looks up the segment created with `key`. Segments marked for removal can't be found by
+    /// key anymore.
+    pub fn find_key(&self, key: i32) -> Option<i32> {
+        self.keys.get(&key).copied()
+    }
+
+    /// Creates a segment of `size` bytes and returns its shmid.
+    ///
+    /// Returns `ENOSPC` if the system wide segment limit is reached, `ENOMEM` if the host could not
+    /// provide the memory.
+    pub fn create(&mut self, key: i32, size: usize, mode: u16, cageid: u64) -> Result<i32, Errno> {
+        if self.segments.len() >= SHMMNI as usize {
+            return Err(Errno::ENOSPC);
+        }
+        let segment = ShmSegment::new(key, size, mode, cageid).ok_or(Errno::ENOMEM)?;
+
+        let shmid = self.next_shmid;
+        self.next_shmid += 1;
+        self.segments.insert(shmid, segment);
+        if key != IPC_PRIVATE {
+            self.keys.insert(key, shmid);
+        }
+        Ok(shmid)
+    }
+
+    /// Counts a new attachment of `shmid` by `cageid`
+    pub fn attach(&mut self, shmid: i32, cageid: u64) {
+        if let Some(segment) = self.segments.get_mut(&shmid) {
+            segment.shminfo.shm_nattch += 1;
+            segment.shminfo.shm_atime = now();
+            segment.shminfo.shm_lpid = cageid as u32;
+        }
+    }
+
+    /// Drops an attachment of `shmid` by `cageid`, destroying the segment if it was the last one
+    /// and the segment is marked for removal
+    pub fn detach(&mut self, shmid: i32, cageid: u64) {
+        if let Some(segment) = self.segments.get_mut(&shmid) {
+            segment.shminfo.shm_nattch = segment.shminfo.shm_nattch.saturating_sub(1);
+            segment.shminfo.shm_dtime = now();
+            segment.shminfo.shm_lpid = cageid as u32;
+            if segment.rmid && segment.shminfo.shm_nattch == 0 {
+                self.segments.remove(&shmid);
+            }
+        }
+    }
+
+    /// Marks `shmid` for removal. The segment is destroyed right away if nothing is attached,
+    /// otherwise after the last detach.
+    pub fn remove(&mut self, shmid: i32) {
+        let Some(segment) = self.segments.get_mut(&shmid) else {
+            return;
+        };
+        if !segment.rmid {
+            segment.rmid = true;
+            segment.shminfo.shm_perm.mode |= SHM_DEST as u16;
+            // the key is released, a later shmget() with it creates a new segment
+            let key = segment.shminfo.shm_perm.__key;
+            if self.keys.get(&key) == Some(&shmid) {
+                self.keys.remove(&key);
+            }
+            segment.shminfo.shm_perm.__key = IPC_PRIVATE;
+        }
+        if segment.shminfo.shm_nattch == 0 {
+            self.segments.remove(&shmid);
+        }
+    }
+}
+
+/// Global table of shared memory segments, shared by all cages.
+///
+/// Lock ordering: a cage's `vmmap` lock is always taken before `SHM_TABLE`.
+pub static SHM_TABLE: Lazy<Mutex<ShmTable>> = Lazy::new(|| Mutex::new(ShmTable::new()));
+
+/// Current time in seconds, as stored in `shmid_ds`
+fn now() -> isize {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs() as isize)
+        .unwrap_or(0)
+}
+
+/// Returns the shmid of every segment attached in `vmmap`, once per attachment.
+///
+/// An attachment may have been split into several `vmmap` entries (by `mprotect` for instance), so
+/// entries of the same segment that lie within the pages of the attachment they follow are not
+/// counted again.
+pub fn shm_attachments(vmmap: &Vmmap, table: &ShmTable) -> Vec<i32> {
+    let mut attachments = Vec::new();
+    let mut current: Option<(i32, u32)> = None; // (shmid, end page of the attachment)
+
+    for (_interval, entry) in vmmap.entries.iter() {
+        let MemoryBackingType::SharedMemory(shmid) = entry.backing else {
+            continue;
+        };
+        let shmid = shmid as i32;
+        if let Some((cur_shmid, end_page)) = current {
+            if cur_shmid == shmid && entry.page_num < end_page {
+                continue;
+            }
+        }
+        let npages = table
+            .segments
+            .get(&shmid)
+            .map_or(entry.npages, |s| s.npages());
+        current = Some((shmid, entry.page_num + npages));
+        attachments.push(shmid);
+    }
+    attachments
+}
+
+/// Counts the attachments inherited by a child whose `vmmap` was just cloned from its parent
+pub fn shm_fork_attachments(child_vmmap: &Vmmap, child_cageid: u64) {
+    let mut table = SHM_TABLE.lock();
+    for shmid in shm_attachments(child_vmmap, &table) {
+        table.attach(shmid, child_cageid);
+    }
+}
+
+/// Detaches every segment attached in `vmmap`, for a cage that exits or execs. The host pages are
+/// left to be released along with the rest of the cage memory.
+pub fn shm_detach_all(vmmap: &Vmmap, cageid: u64) {
+    let mut table = SHM_TABLE.lock();
+    for shmid in shm_attachments(vmmap, &table) {
+        table.detach(shmid, cageid);
+    }
+}
@@ -7,12 +7,16 @@
 //! and searching for memory regions, ensuring proper alignment, protection, and handling of shared
 //! and file-backed memory.
 //! This file defines `vmmap` data structures.
+use crate::memory::memlimits::{CommittedPages, MemoryLimits, MemoryReservation};
 use fdtables;
 use nodit::NoditMap;
 use nodit::{interval::ie, Interval};
 use std::io;
+use std::sync::Arc;
 use sysdefs::constants::err_const::{syscall_error, Errno};
-use sysdefs::constants::fs_const::{PAGESHIFT, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE};
+use sysdefs::constants::fs_const::{
+    MAP_SHARED, PAGESHIFT, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE,
+};
 
 const DEFAULT_VMMAP_SIZE: u32 = 1 << (32 - PAGESHIFT);
 
@@ -33,6 +37,37 @@ pub enum MemoryBackingType {
     FileDescriptor(u64), // stores file descriptor addr
 }
 
+/// Frozen copy of the contents of private memory, kept in a memfd that is never written once created.
+///
+/// Private regions are mapped from it with `MAP_PRIVATE` by `fork_vmmap`, so the pages are shared by the
+/// parent and the child until one of them writes to a page, which the host kernel then copies. The memfd
+/// is closed when the last entry referring to the snapshot is dropped, the host mappings keep its pages
+/// alive.
+#[derive(PartialEq, Eq, Debug)]
+pub struct MemorySnapshot {
+    pub fd: i32,       // The memfd, its first page holds page `page_num` of the cage memory
+    pub page_num: u32, // First page of the cage memory in the snapshot
+    pub npages: u32,   // Number of pages in the snapshot
+}
+
+impl MemorySnapshot {
+    /// Returns whether the snapshot holds the contents of page `page_num` of the cage memory
+    pub fn contains(&self, page_num: u32) -> bool {
+        self.page_num <= page_num && page_num < self.page_num + self.npages
+    }
+
+    /// Returns the offset in the memfd of page `page_num` of the cage memory
+    pub fn offset(&self, page_num: u32) -> i64 {
+        ((page_num - self.page_num) as i64) << PAGESHIFT
+    }
+}
+
+impl Drop for MemorySnapshot {
+    fn drop(&mut self) {
+        unsafe { libc::close(self.fd) };
+    }
+}
+
 /// An entry in the virtual memory map that contains fields such as page number, number of pages,
 /// permissions, file offset, file size, shared memory ID, and backing fields to distinguish memory types.
 #[derive(Clone, PartialEq, Eq, Debug)]
@@ -47,6 +82,9 @@ pub struct VmmapEntry {
     pub file_size: i64,   // Size of the backing store
     pub cage_id: u64,     // Identifier for the security cage
     pub backing: MemoryBackingType, // Type of memory backing for this region
+    // Snapshot the private pages of the region are mapped from, see `MemorySnapshot`. The pages of the
+    // region inside the snapshot that were not written since are those of the snapshot
+    pub snapshot: Option<Arc<MemorySnapshot>>,
 }
 
 // Implement methods for VmmapEntry
@@ -92,9 +130,23 @@ impl VmmapEntry {
             file_size,
             cage_id,
             backing,
+            snapshot: None,
         };
     }
 
+    /// Returns a copy of the entry restricted to the pages from `start` to `end` (excluded), which must
+    /// lie within the entry. The page number, number of pages and file offset of the copy describe the
+    /// restricted range.
+    pub fn slice(&self, start: u32, end: u32) -> VmmapEntry {
+        let mut slice = self.clone();
+        slice.page_num = start;
+        slice.npages = end - start;
+        if let MemoryBackingType::FileDescriptor(_) = self.backing {
+            slice.file_offset += ((start - self.page_num) as i64) << PAGESHIFT;
+        }
+        slice
+    }
+
     // Gets the maximum protection flags allowed for file-backed memory mappings
     //
     // Arguments:
@@ -179,6 +231,9 @@ pub trait VmmapOps {
     // Method to check address mapping
     fn check_addr_mapping(&mut self, page_num: u32, npages: u32, prot: i32) -> Option<u32>;
 
+    /// Checks that no mapping overlaps the page range
+    fn is_range_free(&self, page_num: u32, npages: u32) -> bool;
+
     // Method to find a page in the memory map
     fn find_page(&self, page_num: u32) -> Option<&VmmapEntry>;
 
@@ -239,6 +294,8 @@ pub trait VmmapOps {
 /// - entries: NoditMap storing the memory regions indexed by page number
 /// - cached_entry: Optional cached entry for performance optimization
 /// - base_address: Optional base address for WASM memory
+/// - committed: Pages of the entries, by type of backing
+/// - limits: RLIMIT_AS and RLIMIT_DATA limits of the cage, checked by `reserve_memory`
 #[derive(Clone, PartialEq, Eq, Debug)]
 pub struct Vmmap {
     pub entries: NoditMap<u32, Interval<u32>, VmmapEntry>, // Keyed by `page_num`
@@ -249,6 +306,9 @@ pub struct Vmmap {
     pub start_address: u32, // start address of valid vmmap address range
     pub end_address: u32,   // end address of valid vmmap address range
     pub program_break: u32, // program break (i.e. heap bottom) of the memory
+
+    pub committed: CommittedPages, // kept up to date by `add_entry` and `update`
+    pub limits: MemoryLimits,      // inherited on fork and kept across exec
 }
 
 #[allow(dead_code)]
@@ -263,6 +323,8 @@ impl Vmmap {
             start_address: 0,
             end_address: DEFAULT_VMMAP_SIZE,
             program_break: 0,
+            committed: CommittedPages::default(),
+            limits: MemoryLimits::default(),
         }
     }
 
@@ -329,25 +391,180 @@ impl Vmmap {
         (address as usize - self.base_address.unwrap()) as u32
     }
 
-    // Visits each entry in the vmmap, applying a visitor function to each entry
-    //
-    // The visitor function should be used to:
-    // - Validate memory map consistency
-    // - Gather statistics about memory usage
-    // - Perform operations across all entries
-    // - Support debugging and auditing features
-    fn visit() {}
-
-    // Prints detailed debug information about the vmmap's current state
-    //
-    // Should output information including:
-    // - Page ranges and sizes for each mapping
-    // - Protection flags (current and maximum)
-    // - Mapping flags
-    // - Backing store information (Anonymous, File, or Shared Memory)
-    // - File information (offset and size) when applicable
-    // - Any gaps in the address space
-    fn debug() {}
+    /// Checks that mapping `npages` pages at `page_num` with backing `backing`, in place of whatever is
+    /// mapped there, keeps the cage within its memory limits and all the cages within the global limit
+    ///
+    /// Arguments:
+    /// - page_num: Starting page number of the mapping
+    /// - npages: Number of pages of the mapping
+    /// - backing: Type of memory backing of the mapping
+    ///
+    /// Returns:
+    /// - A reservation of the new pages in the global count, to keep until the entry is added, so that
+    ///   other cages can't commit them in the meantime
+    /// - Err(Errno::ENOMEM) if a limit would be exceeded
+    pub fn reserve_memory(
+        &self,
+        page_num: u32,
+        npages: u32,
+        backing: MemoryBackingType,
+    ) -> Result<MemoryReservation, Errno> {
+        let end_page = page_num + npages;
+        let replaced = self
+            .entries
+            .overlapping(ie(page_num, end_page))
+            .map(|(interval, entry)| {
+                let start = interval.start().max(page_num);
+                let end = (interval.end() + 1).min(end_page); // `end()` is inclusive
+                (entry.backing, end - start)
+            });
+        self.committed
+            .reserve(&self.limits, backing, npages, replaced)
+    }
+
+    /// Visits each entry in the vmmap in address order, applying a visitor function to each entry
+    ///
+    /// Arguments:
+    /// - visitor: Called with each entry and the interval it is stored under
+    ///
+    /// Returns:
+    /// - Ok(()) if the visitor succeeded for every entry
+    /// - The first error returned by the visitor otherwise, the remaining entries are not visited
+    pub fn visit<E>(
+        &self,
+        mut visitor: impl FnMut(&Interval<u32>, &VmmapEntry) -> Result<(), E>,
+    ) -> Result<(), E> {
+        for (interval, entry) in self.entries.iter() {
+            visitor(interval, entry)?;
+        }
+        Ok(())
+    }
+
+    /// Checks that the vmmap is consistent, so that the operations on it kept it exact
+    ///
+    /// Checked for each entry:
+    /// - Its page number and number of pages match the interval it is stored under
+    /// - It is not empty, and lies within the valid address range
+    /// - Its protection doesn't exceed its maximum protection, and it is not marked as removed
+    /// - The file offset of a file-backed entry is page aligned
+    /// - Shared entries are not mapped from a snapshot
+    /// The heap entry must also end at the program break, when one is set, and the committed pages must
+    /// be those of the entries.
+    ///
+    /// Returns:
+    /// - Ok(()) if the vmmap is consistent
+    /// - Err(String) describing the first inconsistency found otherwise
+    pub fn check_consistency(&self) -> Result<(), String> {
+        let (mut anonymous, mut file, mut shared) = (0, 0, 0);
+        self.visit(|interval, entry| {
+            let end = interval.end() + 1; // `end()` is inclusive
+            let error =
+                if entry.page_num != interval.start() || entry.npages != end - interval.start() {
+                    "does not match its interval"
+                } else if entry.npages == 0 {
+                    "is empty"
+                } else if entry.page_num < self.start_address || end > self.end_address {
+                    "is out of the valid address range"
+                } else if entry.prot & !entry.maxprot != 0 {
+                    "has a protection exceeding its maximum protection"
+                } else if entry.removed {
+                    "is marked as removed"
+                } else if matches!(entry.backing, MemoryBackingType::FileDescriptor(_))
+                    && entry.file_offset & ((1 << PAGESHIFT) - 1) != 0
+                {
+                    "has an unaligned file offset"
+                } else if entry.flags & MAP_SHARED as i32 != 0 && entry.snapshot.is_some() {
+                    "is shared but mapped from a snapshot"
+                } else {
+                    match entry.backing {
+                        MemoryBackingType::Anonymous => anonymous += entry.npages,
+                        MemoryBackingType::FileDescriptor(_) => file += entry.npages,
+                        MemoryBackingType::SharedMemory(_) => shared += entry.npages,
+                        MemoryBackingType::None => {}
+                    }
+                    return Ok(());
+                };
+            Err(format!(
+                "entry for pages {}..{} {}: {:?}",
+                interval.start(),
+                end,
+                error,
+                entry
+            ))
+        })?;
+
+        if self.program_break > 0 {
+            match self.entries.get_at_point(0) {
+                Some(heap) if heap.page_num == 0 && heap.npages == self.program_break => {}
+                heap => {
+                    return Err(format!(
+                        "heap does not end at the program break {}: {:?}",
+                        self.program_break, heap
+                    ))
+                }
+            }
+        }
+
+        let committed = (
+            self.committed.anonymous(),
+            self.committed.file(),
+            self.committed.shared(),
+        );
+        if committed != (anonymous, file, shared) {
+            return Err(format!(
+                "committed pages {:?} are not those of the entries {:?}",
+                committed,
+                (anonymous, file, shared)
+            ));
+        }
+        Ok(())
+    }
+
+    /// Prints detailed debug information about the vmmap's current state
+    ///
+    /// For each mapping, prints its page range, current and maximum protection, mapping flags and
+    /// backing, with the file offset and size of file-backed mappings and the page range of the
+    /// snapshot it is mapped from. Gaps in the address space are printed between mappings.
+    pub fn debug(&self) {
+        println!(
+            "vmmap: base address {:?}, pages {}..{}, program break {}",
+            self.base_address, self.start_address, self.end_address, self.program_break
+        );
+        let mut next_page = self.start_address;
+        let _ = self.visit(|interval, entry| -> Result<(), ()> {
+            if next_page < interval.start() {
+                println!("  {:#x}..{:#x} unmapped", next_page, interval.start());
+            }
+            next_page = interval.end() + 1;
+            let mut line = format!(
+                "  {:#x}..{:#x} prot {:#x} maxprot {:#x} flags {:#x} {:?}",
+                interval.start(),
+                next_page,
+                entry.prot,
+                entry.maxprot,
+                entry.flags,
+                entry.backing
+            );
+            if let MemoryBackingType::FileDescriptor(_) = entry.backing {
+                line += &format!(
+                    " offset {:#x} size {:#x}",
+                    entry.file_offset, entry.file_size
+                );
+            }
+            if let Some(snapshot) = &entry.snapshot {
+                line += &format!(
+                    " snapshot {:#x}..{:#x}",
+                    snapshot.page_num,
+                    snapshot.page_num + snapshot.npages
+                );
+            }
+            println!("{}", line);
+            Ok(())
+        });
+        if next_page < self.end_address {
+            println!("  {:#x}..{:#x} unmapped", next_page, self.end_address);
+        }
+    }
 }
 
 impl VmmapOps for Vmmap {
@@ -363,15 +580,23 @@ impl VmmapOps for Vmmap {
     /// - Start: vmmap_entry_ref.page_num
     /// - End: vmmap_entry_ref.page_num + vmmap_entry_ref.npages (inclusive)
     fn add_entry(&mut self, vmmap_entry_ref: VmmapEntry) {
+        let backing = vmmap_entry_ref.backing;
+        let npages = vmmap_entry_ref.npages;
         // Create interval from page range and insert entry with strict bounds checking
-        let _ = self.entries.insert_strict(
-            // pages x to y, y included
-            ie(
-                vmmap_entry_ref.page_num,
-                vmmap_entry_ref.page_num + vmmap_entry_ref.npages,
-            ),
-            vmmap_entry_ref,
-        );
+        if self
+            .entries
+            .insert_strict(
+                // pages x to y, y included
+                ie(
+                    vmmap_entry_ref.page_num,
+                    vmmap_entry_ref.page_num + vmmap_entry_ref.npages,
+                ),
+                vmmap_entry_ref,
+            )
+            .is_ok()
+        {
+            self.committed.add(backing, npages);
+        }
     }
 
     /// Adds a new entry to the virtual memory map with overwrite capability
@@ -509,12 +734,41 @@ impl VmmapOps for Vmmap {
             file_size,
             removed: false,
             cage_id,
+            snapshot: None,
         };
 
+        // The parts of the existing entries cut by the range keep describing their own pages
+        let mut remainders = Vec::new();
+        for (interval, entry) in self
+            .entries
+            .overlapping(ie(new_region_start_page, new_region_end_page))
+        {
+            let ent_start = interval.start();
+            let ent_end = interval.end() + 1; // `end()` is inclusive
+
+            // The pages inside the range are replaced, or removed
+            self.committed.sub(
+                entry.backing,
+                ent_end.min(new_region_end_page) - ent_start.max(new_region_start_page),
+            );
+            if ent_start < new_region_start_page {
+                remainders.push(entry.slice(ent_start, new_region_start_page));
+            }
+            if ent_end > new_region_end_page {
+                remainders.push(entry.slice(new_region_end_page, ent_end));
+            }
+        }
+
         // Insert new entry, overwriting any existing entries in the range
         let _ = self
             .entries
             .insert_overwrite(ie(new_region_start_page, new_region_end_page), new_entry);
+        for remainder in remainders {
+            let _ = self.entries.insert_overwrite(
+                ie(remainder.page_num, remainder.page_num + remainder.npages),
+                remainder,
+            );
+        }
 
         // If removing, delete the entry after insertion
         if remove {
@@ -522,6 +776,8 @@ impl VmmapOps for Vmmap {
             let _ = self
                 .entries
                 .remove_overlapping(ie(new_region_start_page, new_region_end_page));
+        } else {
+            self.committed.add(backing, npages);
         }
 
         Ok(())
@@ -538,48 +794,45 @@ impl VmmapOps for Vmmap {
     /// - new_prot: New protection flags to apply
     ///
     /// Implementation details:
-    /// - Handles partial overlaps by splitting entries
-    /// - Maintains mapping consistency during protection changes
-    /// - Updates protection flags for fully contained pages
+    /// - Entries partially inside the region are split at the region boundaries, the parts outside
+    ///   keep their protection
+    /// - Pages of the region that are not mapped stay unmapped
     fn change_prot(&mut self, page_num: u32, npages: u32, new_prot: i32) {
         // Calculate page range
         let new_region_end_page = page_num + npages;
         let new_region_start_page = page_num;
 
-        // Store intervals that need to be inserted after iteration
+        // Collect the parts of the overlapping entries inside the region
         let mut to_insert = Vec::new();
-
-        // Iterate over overlapping entries
         for (overlap_interval, entry) in self
             .entries
-            .overlapping_mut(ie(new_region_start_page, new_region_end_page))
+            .overlapping(ie(new_region_start_page, new_region_end_page))
         {
-            let mut ent_start = overlap_interval.start();
-            let ent_end = overlap_interval.end();
-
-            // Case 1: Entry starts before region but extends into it
-            if ent_start < new_region_start_page && ent_end > new_region_start_page {
-                to_insert.push(ie(new_region_start_page, ent_end));
-                ent_start = new_region_start_page;
-            }
-
-            // Case 2: Entry extends beyond region end
-            if ent_start < new_region_end_page && ent_end > new_region_end_page {
-                to_insert.push(ie(ent_start, new_region_end_page));
-            } else {
-                // Case 3: Entry is fully contained - update protection
-                entry.prot = new_prot;
-            }
+            let start = overlap_interval.start().max(new_region_start_page);
+            let end = (overlap_interval.end() + 1).min(new_region_end_page); // `end()` is inclusive
+            let mut slice = entry.slice(start, end);
+            slice.prot = new_prot;
+            to_insert.push(slice);
         }
 
-        // Insert new intervals with updated protection
-        for interval in to_insert {
-            // Get and clone the entry at the start of the interval
-            let mut interval_val = self.entries.get_at_point(interval.start()).unwrap().clone();
-            // Update protection
-            interval_val.prot = new_prot;
-            // Insert the new interval
-            let _ = self.entries.insert_overwrite(interval, interval_val);
+        // Insert them with the updated protection, which splits the entries at the region boundaries
+        for slice in to_insert {
+            let _ = self.update(
+                slice.page_num,
+                slice.npages,
+                slice.prot,
+                slice.maxprot,
+                slice.flags,
+                slice.backing,
+                false, // Not removing
+                slice.file_offset,
+                slice.file_size,
+                slice.cage_id,
+            );
+            // The host pages were not remapped, so they are still those of the snapshot
+            if let Some(entry) = self.entries.get_at_point_mut(slice.page_num) {
+                entry.snapshot = slice.snapshot;
+            }
         }
     }
 
@@ -710,6 +963,19 @@ impl VmmapOps for Vmmap {
         None
     }
 
+    /// Checks that no mapping overlaps the page range
+    ///
+    /// Arguments:
+    /// - page_num: Starting page number
+    /// - npages: Number of pages to check
+    ///
+    /// Returns:
+    /// - true if none of the pages is mapped
+    /// - false otherwise
+    fn is_range_free(&self, page_num: u32, npages: u32) -> bool {
+        npages == 0 || !self.entries.overlaps(ie(page_num, page_num + npages))
+    }
+
     /// Finds a page entry in the memory map
     ///
     /// Arguments:
@@ -894,7 +1160,9 @@ impl VmmapOps for Vmmap {
         for gap in self.entries.gaps_trimmed(ie(start, end)) {
             let aligned_start_page =
                 self.trunc_page_num_down_to_map_multiple(gap.start(), pages_per_map);
-            let aligned_end_page = self.round_page_num_up_to_map_multiple(gap.end(), pages_per_map);
+            // `end()` is inclusive
+            let aligned_end_page =
+                self.round_page_num_up_to_map_multiple(gap.end() + 1, pages_per_map);
 
             let gap_size = aligned_end_page - aligned_start_page;
             if gap_size >= rounded_num_pages {
@@ -937,7 +1205,9 @@ impl VmmapOps for Vmmap {
         for gap in self.entries.gaps_trimmed(ie(start, end)) {
             let aligned_start_page =
                 self.trunc_page_num_down_to_map_multiple(gap.start(), pages_per_map);
-            let aligned_end_page = self.round_page_num_up_to_map_multiple(gap.end(), pages_per_map);
+            // `end()` is inclusive
+            let aligned_end_page =
+                self.round_page_num_up_to_map_multiple(gap.end() + 1, pages_per_map);
 
             let gap_size = aligned_end_page - aligned_start_page;
             if gap_size >= rounded_num_pages {
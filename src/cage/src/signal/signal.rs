@@ -1 +1,361 @@
+//! Signal implementation for cages
+//!
+//! Signals in Lind are emulated per cage instead of using host signals, since all cages run inside the
+//! same host process. Each cage keeps its handler table, per-thread signal masks, a pending signal set
+//! and an interval timer (see `Cage`). `kill` only marks a signal as pending on the target cage; the
+//! signal is then delivered by wasmtime at the next safe point of the target cage (on return from a
+//! syscall), where `lind_get_deliverable_signal` is polled and the guest handler is invoked.
+use crate::cage::{get_cage, Cage, CAGE_MAP};
+use parking_lot::{Mutex, RwLock};
+use std::sync::atomic::Ordering::*;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use sysdefs::constants::sys_const::*;
+use sysdefs::data::fs_struct::{SigactionStruct, SigsetType};
 
+/// Default action taken when a signal is delivered with `SIG_DFL` as its handler.
+/// Reference: https://man7.org/linux/man-pages/man7/signal.7.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignalDefault {
+    Terminate,
+    Ignore,
+    // Job control is not supported in Lind, so stop/continue are treated as no-ops
+    Stop,
+    Continue,
+}
+
+pub fn signal_default_action(signo: i32) -> SignalDefault {
+    match signo {
+        SIGCHLD | SIGURG | SIGWINCH => SignalDefault::Ignore,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => SignalDefault::Stop,
+        SIGCONT => SignalDefault::Continue,
+        _ => SignalDefault::Terminate,
+    }
+}
+
+/// Returns the bit of `signo` in a `SigsetType`. Signal numbers start from 1, so signal `n` is stored
+/// in bit `n - 1`, same as the kernel's sigset layout.
+pub fn sigmask_bit(signo: i32) -> SigsetType {
+    1 << (signo - 1)
+}
+
+/// Returns true if `signo` is a valid signal number
+pub fn is_valid_signal(signo: i32) -> bool {
+    signo > 0 && signo <= SIGNAL_MAX
+}
+
+/// SIGKILL and SIGSTOP cannot be caught, blocked, or ignored
+pub fn is_unblockable_signal(signo: i32) -> bool {
+    signo == SIGKILL || signo == SIGSTOP
+}
+
+/// Real time interval timer of a cage, used by `setitimer(ITIMER_REAL)` / `alarm()`.
+///
+/// The timer doesn't own a host timer or thread: the deadline is stored, and expiration is checked
+/// lazily at each safe point (`lind_check_timer`), which then raises SIGALRM on the cage.
+#[derive(Debug, Default)]
+pub struct IntervalTimer {
+    inner: Mutex<IntervalTimerState>,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+struct IntervalTimerState {
+    // When the timer expires next, None if the timer is disarmed
+    deadline: Option<Instant>,
+    // Period used to re-arm the timer after expiration, zero for a one-shot timer
+    interval: Duration,
+}
+
+impl IntervalTimer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns (remaining time until expiration, interval) of the timer. The remaining time is zero
+    /// when the timer is disarmed.
+    pub fn get_itimer(&self) -> (Duration, Duration) {
+        let state = self.inner.lock();
+        let remaining = match state.deadline {
+            Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+            None => Duration::ZERO,
+        };
+        (remaining, state.interval)
+    }
+
+    /// Arms the timer to expire after `value` and then every `interval`. A zero `value` disarms the
+    /// timer. Returns the previous (remaining time, interval).
+    pub fn set_itimer(&self, value: Duration, interval: Duration) -> (Duration, Duration) {
+        let old = self.get_itimer();
+        let mut state = self.inner.lock();
+        state.deadline = if value.is_zero() {
+            None
+        } else {
+            Some(Instant::now() + value)
+        };
+        state.interval = interval;
+        old
+    }
+
+    /// Returns true if the timer expired since the last check, re-arming it when it is periodic
+    pub fn check_expired(&self) -> bool {
+        let mut state = self.inner.lock();
+        match state.deadline {
+            Some(deadline) if deadline <= Instant::now() => {
+                state.deadline = if state.interval.is_zero() {
+                    None
+                } else {
+                    Some(deadline + state.interval)
+                };
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Copies the timer state, used when a cage is forked/exec-ed. Note that fork() does not inherit
+    /// timers on Linux, while exec() does.
+    pub fn clone_timer(&self) -> Self {
+        IntervalTimer {
+            inner: Mutex::new(*self.inner.lock()),
+        }
+    }
+}
+
+/// Returns the kernel thread id of the calling thread. Every wasm thread runs on its own host thread,
+/// so this identifies the guest thread.
+pub fn current_threadid() -> u64 {
+    unsafe { libc::gettid() as u64 }
+}
+
+/// Signal masks are stored per thread, keyed by the kernel thread id. The main thread of a cage is
+/// always stored under key 0: its kernel thread id is not known when the cage is created (fork/exec)
+/// so the mask it inherits is put there beforehand.
+fn sigmask_key(cage: &Cage, threadid: u64) -> u64 {
+    // The first thread that touches the signal state of a cage is its main thread
+    let _ = cage
+        .main_threadid
+        .compare_exchange(0, threadid, SeqCst, SeqCst);
+    if cage.main_threadid.load(Relaxed) == threadid {
+        0
+    } else {
+        threadid
+    }
+}
+
+/// Returns the signal mask of the thread `threadid` in the cage
+pub fn get_sigmask(cage: &Cage, threadid: u64) -> SigsetType {
+    let key = sigmask_key(cage, threadid);
+    *cage.sigset.read().get(&key).unwrap_or(&0)
+}
+
+/// Replaces the signal mask of the thread `threadid` in the cage. SIGKILL and SIGSTOP are silently
+/// removed from the mask as they cannot be blocked.
+pub fn set_sigmask(cage: &Cage, threadid: u64, mask: SigsetType) {
+    let key = sigmask_key(cage, threadid);
+    let mask = mask & !(sigmask_bit(SIGKILL) | sigmask_bit(SIGSTOP));
+    cage.sigset.write().insert(key, mask);
+}
+
+/// Functions run each time a signal is sent, registered by the syscall implementations that block on
+/// their own condition variables (pipes, in-memory sockets)
+static SIGNAL_WAKERS: RwLock<Vec<fn()>> = RwLock::new(Vec::new());
+
+/// Registers `waker` to be run each time a signal is sent. The waker must wake up every thread
+/// blocked in the syscalls it covers, which then check `lind_signal_interrupts` and fail with EINTR.
+pub fn lind_register_signal_waker(waker: fn()) {
+    let mut wakers = SIGNAL_WAKERS.write();
+    if !wakers.contains(&waker) {
+        wakers.push(waker);
+    }
+}
+
+/// Wakes up the threads of the cage blocked in waitpid, and the ones blocked in the syscalls covered
+/// by the registered wakers, after a signal was marked pending on the cage
+fn _wake_blocked(cage: &Cage) {
+    // the lock is taken so that a waiter can't miss the notification between its check and its sleep
+    drop(cage.zombies.lock());
+    cage.zombie_cv.notify_all();
+    for waker in SIGNAL_WAKERS.read().iter() {
+        waker();
+    }
+}
+
+/// Marks `signo` pending on the cage `cageid`. The signal is delivered by the target cage itself at
+/// its next safe point, blocking syscalls of the cage are woken up to let them return EINTR.
+///
+/// Return:
+///     - false if the cage doesn't exist
+pub fn lind_send_signal(cageid: u64, signo: i32) -> bool {
+    match get_cage(cageid) {
+        Some(cage) => {
+            cage.pending_signals.fetch_or(sigmask_bit(signo), SeqCst);
+            _wake_blocked(&cage);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Marks `signo` pending on every cage selected by `selected`, the way `kill` signals a process group.
+/// A `signo` of 0 only checks that a cage is selected.
+///
+/// Return:
+///     - false if no cage was selected
+pub fn lind_send_signal_to_cages<F: Fn(&Cage) -> bool>(signo: i32, selected: F) -> bool {
+    // the selected cages are woken up once the cage table is released, since waitpid looks up the
+    // cage table while holding its zombie list
+    let signaled: Vec<Arc<Cage>> = CAGE_MAP
+        .read()
+        .iter()
+        .flatten()
+        .filter(|cage| selected(cage))
+        .cloned()
+        .collect();
+    if signo != 0 {
+        for cage in &signaled {
+            cage.pending_signals.fetch_or(sigmask_bit(signo), SeqCst);
+            _wake_blocked(cage);
+        }
+    }
+    !signaled.is_empty()
+}
+
+/// Raises SIGALRM on the cage if its interval timer has expired
+pub fn lind_check_timer(cage: &Cage) {
+    if cage.interval_timer.check_expired() {
+        cage.pending_signals.fetch_or(sigmask_bit(SIGALRM), SeqCst);
+    }
+}
+
+/// Fast path checked by wasmtime on every syscall return.
+///
+/// Return:
+///     - true if no signal is pending on the cage (the timer is checked first)
+pub fn lind_check_no_pending_signal(cageid: u64) -> bool {
+    match get_cage(cageid) {
+        Some(cage) => {
+            lind_check_timer(&cage);
+            cage.pending_signals.load(SeqCst) == 0
+        }
+        None => true,
+    }
+}
+
+/// Checked by blocking syscalls when they are woken up, a syscall interrupted by a signal returns EINTR
+/// and the signal is then delivered on its return.
+///
+/// Return:
+///     - true if a signal that is not blocked by the calling thread, nor ignored, is pending on the cage
+pub fn lind_signal_interrupts(cageid: u64) -> bool {
+    let Some(cage) = get_cage(cageid) else {
+        return false;
+    };
+    let deliverable = cage.pending_signals.load(SeqCst) & !get_sigmask(&cage, current_threadid());
+    if deliverable == 0 {
+        return false;
+    }
+    let handlers = cage.signalhandler.read();
+    (1..=SIGNAL_MAX)
+        .filter(|&signo| deliverable & sigmask_bit(signo) != 0)
+        .any(|signo| {
+            if is_unblockable_signal(signo) {
+                return true;
+            }
+            let action = handlers.get(&signo).copied().unwrap_or_default();
+            match action.sa_handler {
+                SIG_IGN => false,
+                SIG_DFL => signal_default_action(signo) == SignalDefault::Terminate,
+                _ => true,
+            }
+        })
+}
+
+/// Records `signo` as the signal terminating the cage, called by wasmtime right before it exits the
+/// cage on `SignalDelivery::Terminate`. The parent's waitpid() then sees the cage killed by `signo`.
+pub fn lind_set_termination_signal(cageid: u64, signo: i32) {
+    if let Some(cage) = get_cage(cageid) {
+        cage.term_signal.store(signo, SeqCst);
+    }
+}
+
+/// Action wasmtime has to take to deliver a signal to the guest
+#[derive(Debug, Clone, Copy)]
+pub enum SignalDelivery {
+    /// Call the guest handler (index in the guest function table) with the signal number. `oldmask`
+    /// must be restored with `lind_signal_handler_return` once the handler returns.
+    Handler {
+        signo: i32,
+        handler: u32,
+        oldmask: SigsetType,
+    },
+    /// Terminate the cage because of the signal
+    Terminate { signo: i32 },
+}
+
+/// Picks the lowest numbered pending signal that is not blocked by the calling thread and removes it
+/// from the pending set. Ignored signals (explicitly, or by default) are discarded on the way.
+///
+/// When a handler is returned, the thread's mask is extended with `sa_mask` (and the signal itself
+/// unless `SA_NODEFER`) for the duration of the handler, and `SA_RESETHAND` is applied.
+///
+/// Return:
+///     - None if there is nothing to deliver to the calling thread
+pub fn lind_get_deliverable_signal(cageid: u64) -> Option<SignalDelivery> {
+    let cage = get_cage(cageid)?;
+    let threadid = current_threadid();
+    let mask = get_sigmask(&cage, threadid);
+
+    loop {
+        let pending = cage.pending_signals.load(SeqCst);
+        let deliverable = pending & !mask;
+        if deliverable == 0 {
+            return None;
+        }
+        let signo = deliverable.trailing_zeros() as i32 + 1;
+        // Another thread of the cage may have taken the signal in the meantime
+        if cage.pending_signals.fetch_and(!sigmask_bit(signo), SeqCst) & sigmask_bit(signo) == 0 {
+            continue;
+        }
+
+        let action = cage
+            .signalhandler
+            .read()
+            .get(&signo)
+            .copied()
+            .unwrap_or_default();
+
+        if action.sa_handler == SIG_IGN && !is_unblockable_signal(signo) {
+            continue;
+        }
+        if action.sa_handler == SIG_DFL || is_unblockable_signal(signo) {
+            match signal_default_action(signo) {
+                SignalDefault::Terminate => return Some(SignalDelivery::Terminate { signo }),
+                _ => continue,
+            }
+        }
+
+        if action.sa_flags & SA_RESETHAND != 0 {
+            cage.signalhandler
+                .write()
+                .insert(signo, SigactionStruct::default());
+        }
+        let mut handler_mask = mask | action.sa_mask;
+        if action.sa_flags & SA_NODEFER == 0 {
+            handler_mask |= sigmask_bit(signo);
+        }
+        set_sigmask(&cage, threadid, handler_mask);
+
+        return Some(SignalDelivery::Handler {
+            signo,
+            handler: action.sa_handler,
+            oldmask: mask,
+        });
+    }
+}
+
+/// Restores the signal mask of the calling thread after a guest handler returned
+pub fn lind_signal_handler_return(cageid: u64, oldmask: SigsetType) {
+    if let Some(cage) = get_cage(cageid) {
+        set_sigmask(&cage, current_threadid(), oldmask);
+    }
+}
@@ -1,5 +1,6 @@
 /* Benchmarks for fdtables.  This does a few basic operations related to
- * virtual fd -> real fd translation */
+ * virtual fd -> real fd translation.  Every backend is benchmarked, so one
+ * `cargo bench` compares all of them. */
 
 use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
 
@@ -9,7 +10,22 @@ use std::thread;
 
 use std::time::Duration;
 
+// Empties out the tables of backend B.  The benchmarks create far more fds
+// than a real system would (e.g., forking a cage with 1024 fds many times),
+// so this also lifts the global fd limit which refresh puts back.
+fn refresh<B: FdTableBackend>() {
+    B::refresh();
+    set_total_fd_limit(u64::MAX);
+}
+
 pub fn run_benchmark(c: &mut Criterion) {
+    run_backend_benchmark::<VanillaGlobal>(c);
+    run_backend_benchmark::<MutHashMaxGlobal>(c);
+    run_backend_benchmark::<DashMapArrayGlobal>(c);
+    run_backend_benchmark::<DashMapVecGlobal>(c);
+}
+
+fn run_backend_benchmark<B: FdTableBackend>(c: &mut Criterion) {
     // I'm going to do some simple calls using fdtables in this file
     let mut group = c.benchmark_group("fdtables basics");
 
@@ -20,66 +36,70 @@ pub fn run_benchmark(c: &mut Criterion) {
     // Shorten the warm up time as well from 3s to this...
     group.warm_up_time(Duration::from_secs(1));
 
-    let fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 10, true, 100).unwrap();
-    let fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 20, true, 1).unwrap();
-    let fd3 = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, true, 10).unwrap();
+    refresh::<B>();
+
+    let fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, true, 100).unwrap();
+    let fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 20, true, 1).unwrap();
+    let fd3 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 10).unwrap();
 
     // I'm going to insert three items, then do 10000 queries, then clean up...
-    group.bench_function(format!("{}/st: trans (10K)", ALGONAME), |b| {
+    group.bench_function(format!("{}/st: trans (10K)", B::ALGONAME), |b| {
         b.iter(|| {
             for _ in 0..1000 {
-                translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
-                translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                B::translate_virtual_fd(threei::TESTING_CAGEID, fd1).unwrap();
             }
         })
     });
 
-    refresh();
+    refresh::<B>();
 
     // only do 1000 because 1024 is a common lower bound
-    group.bench_function(format!("{}/st: getvirt (1K)", ALGONAME), |b| {
+    group.bench_function(format!("{}/st: getvirt (1K)", B::ALGONAME), |b| {
         b.iter(|| {
             for _ in 0..1000 {
-                _ = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, true, 10).unwrap();
+                _ = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 10).unwrap();
             }
             // unfortunately, we need to clean up, or else we will
             // get an exception due to the table being full...
-            refresh();
+            refresh::<B>();
         })
     });
 
-    // Check get_optionalinfo...
-    let fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, true, 10).unwrap();
-    group.bench_function(format!("{}/st: get_opt (10K)", ALGONAME), |b| {
+    // Check reading the perfdinfo...
+    let fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 10).unwrap();
+    group.bench_function(format!("{}/st: get_perfdinfo (10K)", B::ALGONAME), |b| {
         b.iter(|| {
             for _ in 0..10000 {
-                _ = get_optionalinfo(threei::TESTING_CAGEID, fd).unwrap();
+                _ = B::translate_virtual_fd(threei::TESTING_CAGEID, fd)
+                    .unwrap()
+                    .perfdinfo;
             }
         })
     });
 
-    refresh();
+    refresh::<B>();
 
-    // flip the set_optionalinfo data...
-    let fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, true, 10).unwrap();
-    group.bench_function(format!("{}/st: set_opt (10K)", ALGONAME), |b| {
+    // flip the perfdinfo data...
+    let fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 10).unwrap();
+    group.bench_function(format!("{}/st: set_perfdinfo (10K)", B::ALGONAME), |b| {
         b.iter(|| {
             for _ in 0..5000 {
-                _ = set_optionalinfo(threei::TESTING_CAGEID, fd, 100).unwrap();
-                _ = set_optionalinfo(threei::TESTING_CAGEID, fd, 200).unwrap();
+                B::set_perfdinfo(threei::TESTING_CAGEID, fd, 100).unwrap();
+                B::set_perfdinfo(threei::TESTING_CAGEID, fd, 200).unwrap();
             }
         })
     });
 
-    refresh();
+    refresh::<B>();
 
     // TODO: I'd love to count memory use in these tests too.  It really
     // varies widely...
@@ -88,64 +108,68 @@ pub fn run_benchmark(c: &mut Criterion) {
     for fdcount in [1, 4, 16, 64, 256, 1024].iter() {
         // Setup the fds up front, outside of the benchmark...
         for _ in 0..*fdcount {
-            let _fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, false, 10).unwrap();
+            let _fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, false, 10).unwrap();
         }
         let mut cagenumtouse = 1;
         group.bench_with_input(
-            BenchmarkId::new(format!("{}/st: fork (fds:{})", ALGONAME, fdcount), fdcount),
+            BenchmarkId::new(
+                format!("{}/st: fork (fds:{})", B::ALGONAME, fdcount),
+                fdcount,
+            ),
             fdcount,
             |b, _fdcount| {
                 b.iter({
                     || {
-                        copy_fdtable_for_cage(threei::TESTING_CAGEID, cagenumtouse).unwrap();
+                        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, cagenumtouse).unwrap();
                         // Get a new cage each time...
                         cagenumtouse += 1;
                         // The number of cages may grow large and this could
                         // also skew the results...  Reset after 100...
-                        //
-                        // Also, if I ever get around to limiting the global
-                        // fds, this will panic...
                         if cagenumtouse % 100 == 0 {
-                            refresh();
+                            refresh::<B>();
                         }
                     }
                 })
             },
         );
-        refresh();
+        refresh::<B>();
     }
-    refresh();
+    refresh::<B>();
 
     // check remove_cage_from_fdtable (exit) time...
     for fdcount in [1, 4, 16, 64, 256, 1024].iter() {
         group.bench_with_input(
-            BenchmarkId::new(format!("{}/st: exit (fds:{})", ALGONAME, fdcount), fdcount),
+            BenchmarkId::new(
+                format!("{}/st: exit (fds:{})", B::ALGONAME, fdcount),
+                fdcount,
+            ),
             fdcount,
             |b, _fdcount| {
                 b.iter({
                     || {
                         // BUG: Is there a better way to do this?  I really
-                        // only want to check the empty_fds_for_exec() call
+                        // only want to check the B::empty_fds_for_exec() call
                         // time...
                         for _ in 0..*fdcount {
-                            let _fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, false, 10)
-                                .unwrap();
+                            let _fd =
+                                B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, false, 10)
+                                    .unwrap();
                         }
-                        remove_cage_from_fdtable(threei::TESTING_CAGEID);
+                        B::remove_cage_from_fdtable(threei::TESTING_CAGEID);
                         // need to re-add the cage...
-                        refresh();
+                        refresh::<B>();
                     }
                 })
             },
         );
     }
-    refresh();
+    refresh::<B>();
 
     // check on empty_fds_for_exec with the flag set to false...
     for fdcount in [1, 4, 16, 64, 256, 1024].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/st: exec (false) (fds:{})", ALGONAME, fdcount),
+                format!("{}/st: exec (false) (fds:{})", B::ALGONAME, fdcount),
                 fdcount,
             ),
             fdcount,
@@ -153,26 +177,27 @@ pub fn run_benchmark(c: &mut Criterion) {
                 b.iter({
                     || {
                         // BUG: Is there a better way to do this?  I really
-                        // only want to check the empty_fds_for_exec() call
+                        // only want to check the B::empty_fds_for_exec() call
                         // time...
                         for _ in 0..*fdcount {
-                            let _fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, false, 10)
-                                .unwrap(); // Notice the false here!
+                            let _fd =
+                                B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, false, 10)
+                                    .unwrap(); // Notice the false here!
                         }
-                        empty_fds_for_exec(threei::TESTING_CAGEID);
-                        refresh();
+                        B::empty_fds_for_exec(threei::TESTING_CAGEID);
+                        refresh::<B>();
                     }
                 })
             },
         );
     }
-    refresh();
+    refresh::<B>();
 
     // Now, check on empty_fds_for_exec with the flag set to true...
     for fdcount in [1, 4, 16, 64, 256, 1024].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/st: exec (true) (fds:{})", ALGONAME, fdcount),
+                format!("{}/st: exec (true) (fds:{})", B::ALGONAME, fdcount),
                 fdcount,
             ),
             fdcount,
@@ -180,34 +205,35 @@ pub fn run_benchmark(c: &mut Criterion) {
                 b.iter({
                     || {
                         // BUG: Is there a better way to do this?  I really
-                        // only want to check the empty_fds_for_exec() call
+                        // only want to check the B::empty_fds_for_exec() call
                         // time...
                         for _ in 0..*fdcount {
-                            let _fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, true, 10)
-                                .unwrap(); // Notice the true here!
+                            let _fd =
+                                B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 10)
+                                    .unwrap(); // Notice the true here!
                         }
-                        empty_fds_for_exec(threei::TESTING_CAGEID);
-                        //refresh(); <- Don't need this because the prior
+                        B::empty_fds_for_exec(threei::TESTING_CAGEID);
+                        //refresh::<B>(); <- Don't need this because the prior
                         // line cleans up for me!
                     }
                 })
             },
         );
     }
-    refresh();
+    refresh::<B>();
 
     // ---------------- MULTI-THREADED / 1 cage TESTS ------------------  //
 
     // -- Multithreaded benchmark 1: 100K translate calls --
 
-    let fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 10, true, 100).unwrap();
-    let fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 20, true, 200).unwrap();
-    let fd3 = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, true, 300).unwrap();
+    let fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, true, 100).unwrap();
+    let fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 20, true, 200).unwrap();
+    let fd3 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 300).unwrap();
 
     for threadcount in [1, 2, 4, 8, 16].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/[mt1c:{}] trans_virtfd (100K)", ALGONAME, threadcount),
+                format!("{}/[mt1c:{}] trans_virtfd (100K)", B::ALGONAME, threadcount),
                 threadcount,
             ),
             threadcount,
@@ -223,16 +249,16 @@ pub fn run_benchmark(c: &mut Criterion) {
                             thread_handle_vec.push(thread::spawn(move || {
                                 // Do 10K / threadcount of 10 requests each.  100K total
                                 for _ in 0..10000 / thisthreadcount {
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
                                 }
                             }));
                         }
@@ -244,7 +270,7 @@ pub fn run_benchmark(c: &mut Criterion) {
             },
         );
     }
-    refresh();
+    refresh::<B>();
 
     // -- Multithreaded benchmark 2: get / translate interleaved --
 
@@ -253,7 +279,7 @@ pub fn run_benchmark(c: &mut Criterion) {
     for threadcount in [1, 2, 4, 8, 16].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/[mt1c:{}] get_trans (1K per)", ALGONAME, threadcount),
+                format!("{}/[mt1c:{}] get_trans (1K per)", B::ALGONAME, threadcount),
                 threadcount,
             ),
             threadcount,
@@ -269,21 +295,22 @@ pub fn run_benchmark(c: &mut Criterion) {
                             thread_handle_vec.push(thread::spawn(move || {
                                 // Do 1K / threadcount
                                 for _ in 0..1000 / thisthreadcount {
-                                    let fd = get_unused_virtual_fd(
+                                    let fd = B::get_unused_virtual_fd(
                                         threei::TESTING_CAGEID,
+                                        0,
                                         10,
                                         true,
                                         100,
                                     )
                                     .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                                    B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
                                 }
                             }));
                         }
                         for handle in thread_handle_vec {
                             handle.join().unwrap();
                         }
-                        refresh();
+                        refresh::<B>();
                     }
                 })
             },
@@ -297,7 +324,7 @@ pub fn run_benchmark(c: &mut Criterion) {
     for threadcount in [1, 2, 4, 8, 16].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/[mt1c:{}] get_close (10K)", ALGONAME, threadcount),
+                format!("{}/[mt1c:{}] get_close (10K)", B::ALGONAME, threadcount),
                 threadcount,
             ),
             threadcount,
@@ -313,14 +340,15 @@ pub fn run_benchmark(c: &mut Criterion) {
                             thread_handle_vec.push(thread::spawn(move || {
                                 // Do 100K / threadcount each
                                 for _ in 0..10000 / thisthreadcount {
-                                    let fd = get_unused_virtual_fd(
+                                    let fd = B::get_unused_virtual_fd(
                                         threei::TESTING_CAGEID,
+                                        0,
                                         10,
                                         true,
                                         100,
                                     )
                                     .unwrap();
-                                    close_virtualfd(threei::TESTING_CAGEID, fd).unwrap();
+                                    B::close_virtualfd(threei::TESTING_CAGEID, fd).unwrap();
                                 }
                             }));
                         }
@@ -337,18 +365,18 @@ pub fn run_benchmark(c: &mut Criterion) {
 
     // -- Multithreaded benchmark 1: 100K translate calls --
 
-    let fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 10, true, 100).unwrap();
-    let fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 20, true, 200).unwrap();
-    let fd3 = get_unused_virtual_fd(threei::TESTING_CAGEID, 30, true, 300).unwrap();
+    let fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, true, 100).unwrap();
+    let fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 20, true, 200).unwrap();
+    let fd3 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 300).unwrap();
     for val in 1..16 {
         // I'm just going to assume I can increment these...
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID + val).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID + val).unwrap();
     }
 
     for threadcount in [1, 2, 4, 8, 16].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/[mtmc:{}] trans_virtfd (100K)", ALGONAME, threadcount),
+                format!("{}/[mtmc:{}] trans_virtfd (100K)", B::ALGONAME, threadcount),
                 threadcount,
             ),
             threadcount,
@@ -364,26 +392,56 @@ pub fn run_benchmark(c: &mut Criterion) {
                             thread_handle_vec.push(thread::spawn(move || {
                                 // Do 10K / threadcount of 10 requests each.  100K total
                                 for _ in 0..10000 / thisthreadcount {
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd2)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd2)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd2)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd3)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd3)
-                                        .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd3)
-                                        .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd2,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd2,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd2,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd3,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd3,
+                                    )
+                                    .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd3,
+                                    )
+                                    .unwrap();
                                 }
                             }));
                         }
@@ -395,7 +453,7 @@ pub fn run_benchmark(c: &mut Criterion) {
             },
         );
     }
-    refresh();
+    refresh::<B>();
 
     // -- Multithreaded benchmark 2: get / translate interleaved --
 
@@ -404,7 +462,7 @@ pub fn run_benchmark(c: &mut Criterion) {
     for threadcount in [1, 2, 4, 8, 16].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/[mtmc:{}] get_trans (1K per)", ALGONAME, threadcount),
+                format!("{}/[mtmc:{}] get_trans (1K per)", B::ALGONAME, threadcount),
                 threadcount,
             ),
             threadcount,
@@ -413,7 +471,7 @@ pub fn run_benchmark(c: &mut Criterion) {
                     || {
                         // setup the empty cages
                         for numthreads in 1..*threadcount {
-                            copy_fdtable_for_cage(
+                            B::copy_fdtable_for_cage(
                                 threei::TESTING_CAGEID,
                                 threei::TESTING_CAGEID + numthreads,
                             )
@@ -430,36 +488,40 @@ pub fn run_benchmark(c: &mut Criterion) {
                             thread_handle_vec.push(thread::spawn(move || {
                                 // Do 1K / threadcount
                                 for _ in 0..1000 / thisthreadcount {
-                                    let fd = get_unused_virtual_fd(
+                                    let fd = B::get_unused_virtual_fd(
                                         threei::TESTING_CAGEID + numthreads,
+                                        0,
                                         10,
                                         true,
                                         100,
                                     )
                                     .unwrap();
-                                    translate_virtual_fd(threei::TESTING_CAGEID + numthreads, fd)
-                                        .unwrap();
+                                    B::translate_virtual_fd(
+                                        threei::TESTING_CAGEID + numthreads,
+                                        fd,
+                                    )
+                                    .unwrap();
                                 }
                             }));
                         }
                         for handle in thread_handle_vec {
                             handle.join().unwrap();
                         }
-                        refresh();
+                        refresh::<B>();
                     }
                 })
             },
         );
     }
 
-    refresh();
+    refresh::<B>();
 
     // -- Multithreaded benchmark 3: get / close interleaved --
 
     // dup the cage tables as this is different cages for each...
     for val in 1..16 {
         // I'm just going to assume I can increment these...
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID + val).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID + val).unwrap();
     }
 
     // I will always do 100K requests (split amongst some number of threads)
@@ -467,7 +529,7 @@ pub fn run_benchmark(c: &mut Criterion) {
     for threadcount in [1, 2, 4, 8, 16].iter() {
         group.bench_with_input(
             BenchmarkId::new(
-                format!("{}/[mtmc:{}] get_close (10K)", ALGONAME, threadcount),
+                format!("{}/[mtmc:{}] get_close (10K)", B::ALGONAME, threadcount),
                 threadcount,
             ),
             threadcount,
@@ -483,14 +545,15 @@ pub fn run_benchmark(c: &mut Criterion) {
                             thread_handle_vec.push(thread::spawn(move || {
                                 // Do 100K / threadcount each
                                 for _ in 0..10000 / thisthreadcount {
-                                    let fd = get_unused_virtual_fd(
+                                    let fd = B::get_unused_virtual_fd(
                                         threei::TESTING_CAGEID,
+                                        0,
                                         10,
                                         true,
                                         100,
                                     )
                                     .unwrap();
-                                    close_virtualfd(threei::TESTING_CAGEID, fd).unwrap();
+                                    B::close_virtualfd(threei::TESTING_CAGEID, fd).unwrap();
                                 }
                             }));
                         }
@@ -0,0 +1,448 @@
+// The FdTableBackend trait, which every implementation of the fdtable
+// provides.  Each implementation keeps its state in globals, so a backend is
+// a unit struct and all of the trait functions are associated functions.
+// This lets the tests and the benchmarks be written once and run against
+// every backend, while the crate API re-exports the free functions of the
+// backend chosen by the cargo features.
+//
+// The trait functions just call the free functions of the module, so the
+// doc strings live with those.  They are the same for every backend.
+
+use crate::threei;
+
+use super::commonconstants::{epoll_event, FDTableEntry};
+
+use libc::fd_set;
+
+use std::collections::{HashMap, HashSet};
+
+/// An implementation of the fdtable.  See the function of the same name at
+/// the top of the crate for the details of each call.
+///
+/// All of the backends share the per-cage and global fd limits.  Only one of
+/// them should be used at a time outside of testing and benchmarking.
+// The docs (errors, panics, etc.) are on the free functions.
+#[allow(missing_docs, clippy::missing_errors_doc)]
+pub trait FdTableBackend: 'static {
+    /// The name of the algorithm, used when benchmarking.
+    const ALGONAME: &'static str;
+
+    fn init_empty_cage(cageid: u64);
+
+    fn translate_virtual_fd(cageid: u64, virtualfd: u64) -> Result<FDTableEntry, threei::RetVal>;
+
+    fn get_unused_virtual_fd(
+        cageid: u64,
+        fdkind: u32,
+        underfd: u64,
+        should_cloexec: bool,
+        perfdinfo: u64,
+    ) -> Result<u64, threei::RetVal>;
+
+    fn get_unused_virtual_fd_from_startfd(
+        cageid: u64,
+        fdkind: u32,
+        underfd: u64,
+        should_cloexec: bool,
+        perfdinfo: u64,
+        startfd: u64,
+    ) -> Result<u64, threei::RetVal>;
+
+    fn get_specific_virtual_fd(
+        cageid: u64,
+        requested_virtualfd: u64,
+        fdkind: u32,
+        underfd: u64,
+        should_cloexec: bool,
+        perfdinfo: u64,
+    ) -> Result<(), threei::RetVal>;
+
+    fn set_cloexec(cageid: u64, virtualfd: u64, is_cloexec: bool) -> Result<(), threei::RetVal>;
+
+    fn set_perfdinfo(cageid: u64, virtualfd: u64, perfdinfo: u64) -> Result<(), threei::RetVal>;
+
+    fn copy_fdtable_for_cage(srccageid: u64, newcageid: u64) -> Result<(), threei::Errno>;
+
+    fn remove_cage_from_fdtable(cageid: u64);
+
+    fn empty_fds_for_exec(cageid: u64);
+
+    #[must_use]
+    fn return_fdtable_copy(cageid: u64) -> HashMap<u64, FDTableEntry>;
+
+    fn close_virtualfd(cageid: u64, virtfd: u64) -> Result<(), threei::RetVal>;
+
+    fn register_close_handlers(
+        fdkind: u32,
+        intermediate: fn(FDTableEntry, u64),
+        last: fn(FDTableEntry, u64),
+    );
+
+    #[allow(clippy::type_complexity)]
+    fn get_bitmask_for_select(
+        cageid: u64,
+        nfds: u64,
+        bits: Option<fd_set>,
+        fdkinds: &HashSet<u32>,
+    ) -> Result<
+        (
+            HashMap<u32, (u64, fd_set)>,
+            HashMap<u32, HashSet<FDTableEntry>>,
+            HashMap<(u32, u64), u64>,
+        ),
+        threei::RetVal,
+    >;
+
+    #[allow(clippy::type_complexity)]
+    fn prepare_bitmasks_for_select(
+        cageid: u64,
+        nfds: u64,
+        rbits: Option<fd_set>,
+        wbits: Option<fd_set>,
+        ebits: Option<fd_set>,
+        fdkinds: &HashSet<u32>,
+    ) -> Result<
+        (
+            [HashMap<u32, (u64, fd_set)>; 3],
+            [HashMap<u32, HashSet<FDTableEntry>>; 3],
+            HashMap<(u32, u64), u64>,
+        ),
+        threei::RetVal,
+    >;
+
+    #[must_use]
+    fn get_one_virtual_bitmask_from_select_result(
+        fdkind: u32,
+        nfds: u64,
+        bits: Option<fd_set>,
+        unprocessedset: HashSet<u64>,
+        startingbits: Option<fd_set>,
+        mappingtable: &HashMap<(u32, u64), u64>,
+    ) -> (u64, Option<fd_set>);
+
+    #[allow(clippy::type_complexity)]
+    #[must_use]
+    fn convert_virtualfds_for_poll(
+        cageid: u64,
+        virtualfds: HashSet<u64>,
+    ) -> (
+        HashMap<u32, HashSet<(u64, FDTableEntry)>>,
+        HashMap<(u32, u64), u64>,
+    );
+
+    #[must_use]
+    fn convert_poll_result_back_to_virtual(
+        fdkind: u32,
+        underfd: u64,
+        mappingtable: &HashMap<(u32, u64), u64>,
+    ) -> Option<u64>;
+
+    fn epoll_create_empty(cageid: u64, should_cloexec: bool) -> Result<u64, threei::RetVal>;
+
+    fn epoll_add_underfd(
+        cageid: u64,
+        virtepollfd: u64,
+        fdkind: u32,
+        underfd: u64,
+    ) -> Result<(), threei::RetVal>;
+
+    fn epoll_get_underfd_hashmap(
+        cageid: u64,
+        virtepollfd: u64,
+    ) -> Result<HashMap<u32, u64>, threei::RetVal>;
+
+    fn virtualize_epoll_ctl(
+        cageid: u64,
+        epfd: u64,
+        op: i32,
+        virtfd: u64,
+        event: epoll_event,
+    ) -> Result<(), threei::RetVal>;
+
+    fn get_virtual_epoll_wait_data(
+        cageid: u64,
+        epfd: u64,
+    ) -> Result<HashMap<u32, HashMap<u64, epoll_event>>, threei::RetVal>;
+
+    #[doc(hidden)]
+    fn refresh();
+}
+
+// Implements FdTableBackend for $backend by calling the free functions in
+// $module.  Every backend has exactly the same free functions.
+macro_rules! impl_fdtable_backend {
+    ($backend:ident, $module:ident) => {
+        impl FdTableBackend for $backend {
+            const ALGONAME: &'static str = crate::$module::ALGONAME;
+
+            fn init_empty_cage(cageid: u64) {
+                crate::$module::init_empty_cage(cageid);
+            }
+
+            fn translate_virtual_fd(
+                cageid: u64,
+                virtualfd: u64,
+            ) -> Result<FDTableEntry, threei::RetVal> {
+                crate::$module::translate_virtual_fd(cageid, virtualfd)
+            }
+
+            fn get_unused_virtual_fd(
+                cageid: u64,
+                fdkind: u32,
+                underfd: u64,
+                should_cloexec: bool,
+                perfdinfo: u64,
+            ) -> Result<u64, threei::RetVal> {
+                crate::$module::get_unused_virtual_fd(
+                    cageid,
+                    fdkind,
+                    underfd,
+                    should_cloexec,
+                    perfdinfo,
+                )
+            }
+
+            fn get_unused_virtual_fd_from_startfd(
+                cageid: u64,
+                fdkind: u32,
+                underfd: u64,
+                should_cloexec: bool,
+                perfdinfo: u64,
+                startfd: u64,
+            ) -> Result<u64, threei::RetVal> {
+                crate::$module::get_unused_virtual_fd_from_startfd(
+                    cageid,
+                    fdkind,
+                    underfd,
+                    should_cloexec,
+                    perfdinfo,
+                    startfd,
+                )
+            }
+
+            fn get_specific_virtual_fd(
+                cageid: u64,
+                requested_virtualfd: u64,
+                fdkind: u32,
+                underfd: u64,
+                should_cloexec: bool,
+                perfdinfo: u64,
+            ) -> Result<(), threei::RetVal> {
+                crate::$module::get_specific_virtual_fd(
+                    cageid,
+                    requested_virtualfd,
+                    fdkind,
+                    underfd,
+                    should_cloexec,
+                    perfdinfo,
+                )
+            }
+
+            fn set_cloexec(
+                cageid: u64,
+                virtualfd: u64,
+                is_cloexec: bool,
+            ) -> Result<(), threei::RetVal> {
+                crate::$module::set_cloexec(cageid, virtualfd, is_cloexec)
+            }
+
+            fn set_perfdinfo(
+                cageid: u64,
+                virtualfd: u64,
+                perfdinfo: u64,
+            ) -> Result<(), threei::RetVal> {
+                crate::$module::set_perfdinfo(cageid, virtualfd, perfdinfo)
+            }
+
+            fn copy_fdtable_for_cage(srccageid: u64, newcageid: u64) -> Result<(), threei::Errno> {
+                crate::$module::copy_fdtable_for_cage(srccageid, newcageid)
+            }
+
+            fn remove_cage_from_fdtable(cageid: u64) {
+                crate::$module::remove_cage_from_fdtable(cageid);
+            }
+
+            fn empty_fds_for_exec(cageid: u64) {
+                crate::$module::empty_fds_for_exec(cageid);
+            }
+
+            fn return_fdtable_copy(cageid: u64) -> HashMap<u64, FDTableEntry> {
+                crate::$module::return_fdtable_copy(cageid)
+            }
+
+            fn close_virtualfd(cageid: u64, virtfd: u64) -> Result<(), threei::RetVal> {
+                crate::$module::close_virtualfd(cageid, virtfd)
+            }
+
+            fn register_close_handlers(
+                fdkind: u32,
+                intermediate: fn(FDTableEntry, u64),
+                last: fn(FDTableEntry, u64),
+            ) {
+                crate::$module::register_close_handlers(fdkind, intermediate, last);
+            }
+
+            fn get_bitmask_for_select(
+                cageid: u64,
+                nfds: u64,
+                bits: Option<fd_set>,
+                fdkinds: &HashSet<u32>,
+            ) -> Result<
+                (
+                    HashMap<u32, (u64, fd_set)>,
+                    HashMap<u32, HashSet<FDTableEntry>>,
+                    HashMap<(u32, u64), u64>,
+                ),
+                threei::RetVal,
+            > {
+                crate::$module::get_bitmask_for_select(cageid, nfds, bits, fdkinds)
+            }
+
+            fn prepare_bitmasks_for_select(
+                cageid: u64,
+                nfds: u64,
+                rbits: Option<fd_set>,
+                wbits: Option<fd_set>,
+                ebits: Option<fd_set>,
+                fdkinds: &HashSet<u32>,
+            ) -> Result<
+                (
+                    [HashMap<u32, (u64, fd_set)>; 3],
+                    [HashMap<u32, HashSet<FDTableEntry>>; 3],
+                    HashMap<(u32, u64), u64>,
+                ),
+                threei::RetVal,
+            > {
+                crate::$module::prepare_bitmasks_for_select(
+                    cageid, nfds, rbits, wbits, ebits, fdkinds,
+                )
+            }
+
+            fn get_one_virtual_bitmask_from_select_result(
+                fdkind: u32,
+                nfds: u64,
+                bits: Option<fd_set>,
+                unprocessedset: HashSet<u64>,
+                startingbits: Option<fd_set>,
+                mappingtable: &HashMap<(u32, u64), u64>,
+            ) -> (u64, Option<fd_set>) {
+                crate::$module::get_one_virtual_bitmask_from_select_result(
+                    fdkind,
+                    nfds,
+                    bits,
+                    unprocessedset,
+                    startingbits,
+                    mappingtable,
+                )
+            }
+
+            fn convert_virtualfds_for_poll(
+                cageid: u64,
+                virtualfds: HashSet<u64>,
+            ) -> (
+                HashMap<u32, HashSet<(u64, FDTableEntry)>>,
+                HashMap<(u32, u64), u64>,
+            ) {
+                crate::$module::convert_virtualfds_for_poll(cageid, virtualfds)
+            }
+
+            fn convert_poll_result_back_to_virtual(
+                fdkind: u32,
+                underfd: u64,
+                mappingtable: &HashMap<(u32, u64), u64>,
+            ) -> Option<u64> {
+                crate::$module::convert_poll_result_back_to_virtual(fdkind, underfd, mappingtable)
+            }
+
+            fn epoll_create_empty(
+                cageid: u64,
+                should_cloexec: bool,
+            ) -> Result<u64, threei::RetVal> {
+                crate::$module::epoll_create_empty(cageid, should_cloexec)
+            }
+
+            fn epoll_add_underfd(
+                cageid: u64,
+                virtepollfd: u64,
+                fdkind: u32,
+                underfd: u64,
+            ) -> Result<(), threei::RetVal> {
+                crate::$module::epoll_add_underfd(cageid, virtepollfd, fdkind, underfd)
+            }
+
+            fn epoll_get_underfd_hashmap(
+                cageid: u64,
+                virtepollfd: u64,
+            ) -> Result<HashMap<u32, u64>, threei::RetVal> {
+                crate::$module::epoll_get_underfd_hashmap(cageid, virtepollfd)
+            }
+
+            fn virtualize_epoll_ctl(
+                cageid: u64,
+                epfd: u64,
+                op: i32,
+                virtfd: u64,
+                event: epoll_event,
+            ) -> Result<(), threei::RetVal> {
+                crate::$module::virtualize_epoll_ctl(cageid, epfd, op, virtfd, event)
+            }
+
+            fn get_virtual_epoll_wait_data(
+                cageid: u64,
+                epfd: u64,
+            ) -> Result<HashMap<u32, HashMap<u64, epoll_event>>, threei::RetVal> {
+                crate::$module::get_virtual_epoll_wait_data(cageid, epfd)
+            }
+
+            fn refresh() {
+                crate::$module::refresh();
+            }
+        }
+    };
+}
+
+/// A global `Mutex<HashMap>` of cages, each with a `HashMap` of fds.  The
+/// simplest implementation.
+pub struct VanillaGlobal;
+impl_fdtable_backend!(VanillaGlobal, vanillaglobal);
+
+/// Like [`VanillaGlobal`], but also tracks the highest fd ever handed out to
+/// speed up finding an unused fd.
+pub struct MutHashMaxGlobal;
+impl_fdtable_backend!(MutHashMaxGlobal, muthashmaxglobal);
+
+/// A `DashMap` of cages, each with a fixed size array of fds.
+pub struct DashMapArrayGlobal;
+impl_fdtable_backend!(DashMapArrayGlobal, dashmaparrayglobal);
+
+/// A `DashMap` of cages, each with a `Vec` of fds.
+pub struct DashMapVecGlobal;
+impl_fdtable_backend!(DashMapVecGlobal, dashmapvecglobal);
+
+// The backend whose free functions the crate re-exports.  dashmaparray is the
+// default.  If more than one backend feature is enabled (e.g., a crate forgot
+// to turn off the default features), the first in the order vanilla,
+// muthashmax, dashmapvec, dashmaparray wins.
+
+/// The backend selected by the cargo features.  The free functions at the top
+/// of the crate are the ones of this backend.
+#[cfg(feature = "vanilla")]
+pub type DefaultBackend = VanillaGlobal;
+
+/// The backend selected by the cargo features.  The free functions at the top
+/// of the crate are the ones of this backend.
+#[cfg(all(feature = "muthashmax", not(feature = "vanilla")))]
+pub type DefaultBackend = MutHashMaxGlobal;
+
+/// The backend selected by the cargo features.  The free functions at the top
+/// of the crate are the ones of this backend.
+#[cfg(all(
+    feature = "dashmapvec",
+    not(any(feature = "vanilla", feature = "muthashmax"))
+))]
+pub type DefaultBackend = DashMapVecGlobal;
+
+/// The backend selected by the cargo features.  The free functions at the top
+/// of the crate are the ones of this backend.
+#[cfg(not(any(feature = "vanilla", feature = "muthashmax", feature = "dashmapvec")))]
+pub type DefaultBackend = DashMapArrayGlobal;
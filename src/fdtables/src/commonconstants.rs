@@ -1,7 +1,8 @@
 // This file exists to make it easier to vary a single file of constants
 // instead of editing each implementation...
 
-/// Per-process maximum number of fds...
+/// Per-process maximum number of fds...  Also the largest hard limit a cage
+/// may have.
 pub const FD_PER_PROCESS_MAX: u64 = 1024;
 
 // /// Use this to indicate there isn't a real fd backing an item
@@ -48,9 +49,8 @@ pub struct FDTableEntry {
 /// It is the default if no close handlers are defined
 pub const fn NULL_FUNC(_: FDTableEntry, _: u64) {}
 
-// BUG / TODO: Use this in some sane way...
-#[allow(dead_code)]
-/// Global maximum number of fds... (checks may not be implemented)
+/// Default global maximum number of fds open across all cages.  See
+/// [`set_total_fd_limit`](crate::set_total_fd_limit) to change it.
 pub const TOTAL_FD_MAX: u64 = 4096;
 
 // replicating these constants here so this can compile on systems other than
@@ -58,9 +58,9 @@ pub const TOTAL_FD_MAX: u64 = 4096;
 /// copied from libc
 pub const EPOLL_CTL_ADD: i32 = 1;
 /// copied from libc
-pub const EPOLL_CTL_MOD: i32 = 2;
+pub const EPOLL_CTL_DEL: i32 = 2;
 /// copied from libc
-pub const EPOLL_CTL_DEL: i32 = 3;
+pub const EPOLL_CTL_MOD: i32 = 3;
 
 #[allow(non_camel_case_types)]
 /// i32 copied from libc.  used in EPOLL event flags even though events are u32
@@ -18,15 +18,21 @@ use std::sync::Mutex;
 // Get constants about the fd table sizes, etc.
 pub use super::commonconstants::*;
 
+// The per-cage and global fd limits are tracked for all implementations here.
+use super::fdlimits::{
+    copy_fd_limits, get_fd_limits, release_fds, remove_fd_limits, reserve_fds, reset_fd_limits,
+};
+
 // algorithm name.  Need not be listed.  Used in benchmarking output
 #[doc(hidden)]
 pub const ALGONAME: &str = "DashMapVecGlobal";
 
 // It's fairly easy to check the fd count on a per-process basis (I just check
-// when I would add a new fd).
+// when I would add a new fd).  Fds are only handed out below the cage's soft
+// limit.
 //
-// TODO: I will ignore the total limit for now.  I would ideally do this on
-// every creation, close, fork, etc. but it's a PITA to track this.
+// The total limit is checked through fdlimits.rs.  I reserve a slot whenever
+// an entry is added to a table and release it whenever one is removed.
 
 // We will raise a panic anywhere we receive an unknown cageid.  This frankly
 // should not be possible and indicates some sort of internal error in our
@@ -123,12 +129,59 @@ pub fn get_unused_virtual_fd(
         perfdinfo,
     };
 
+    let softlimit = get_fd_limits(cageid).0;
     let mut myfdrow = FDTABLE.get_mut(&cageid).unwrap();
 
     // Check the fds in order.
-    for fdcandidate in 0..FD_PER_PROCESS_MAX {
+    for fdcandidate in 0..softlimit {
         // FIXME: This is likely very slow.  Should do something smarter...
         if myfdrow[fdcandidate as usize].is_none() {
+            // return ENFILE if no more fds may be open at all...
+            reserve_fds(1)?;
+            // I just checked.  Should not be there...
+            myfdrow[fdcandidate as usize] = Some(myentry);
+            _increment_fdcount(myentry);
+            return Ok(fdcandidate);
+        }
+    }
+
+    // I must have checked all fds and failed to find one open.  Fail!
+    Err(threei::Errno::EMFILE as u64)
+}
+
+// This is used for things like fcntl's F_DUPFD, which needs the lowest fd
+// that is at least startfd...
+#[doc = include_str!("../docs/get_unused_virtual_fd_from_startfd.md")]
+pub fn get_unused_virtual_fd_from_startfd(
+    cageid: u64,
+    fdkind: u32,
+    underfd: u64,
+    should_cloexec: bool,
+    perfdinfo: u64,
+    startfd: u64,
+) -> Result<u64, threei::RetVal> {
+
+    assert!(FDTABLE.contains_key(&cageid),"Unknown cageid in fdtable access");
+
+    let softlimit = get_fd_limits(cageid).0;
+    if startfd >= softlimit {
+        return Err(threei::Errno::EINVAL as u64);
+    }
+
+    let myentry = FDTableEntry {
+        fdkind,
+        underfd,
+        should_cloexec,
+        perfdinfo,
+    };
+
+    let mut myfdrow = FDTABLE.get_mut(&cageid).unwrap();
+
+    // Check the fds in order, starting at startfd.
+    for fdcandidate in startfd..softlimit {
+        if myfdrow[fdcandidate as usize].is_none() {
+            // return ENFILE if no more fds may be open at all...
+            reserve_fds(1)?;
             // I just checked.  Should not be there...
             myfdrow[fdcandidate as usize] = Some(myentry);
             _increment_fdcount(myentry);
@@ -155,10 +208,9 @@ pub fn get_specific_virtual_fd(
     assert!(FDTABLE.contains_key(&cageid),"Unknown cageid in fdtable access");
 
     // If you ask for a FD number that is too large, I'm going to reject it.
-    // Note that, I need to use the FD_PER_PROCESS_MAX setting because this
-    // is also how I'm tracking how many values you have open.  If this
-    // changed, then these constants could be decoupled...
-    if requested_virtualfd > FD_PER_PROCESS_MAX {
+    // The soft limit is never above FD_PER_PROCESS_MAX, which is the size of
+    // the row.
+    if requested_virtualfd >= get_fd_limits(cageid).0 {
         return Err(threei::Errno::EBADF as u64);
     }
 
@@ -172,10 +224,16 @@ pub fn get_specific_virtual_fd(
         perfdinfo,
     };
 
+    let myoptionentry = FDTABLE.get(&cageid).unwrap()[requested_virtualfd as usize];
+    // Replacing an entry doesn't change the number of open fds, but filling
+    // an empty one does.
+    if myoptionentry.is_none() {
+        reserve_fds(1)?;
+    }
+
     // This is before the FDTABLE action, so if I decrement the same fd, it
     // calls the intermediate handler instead of the last one.
     _increment_fdcount(myentry);
-    let myoptionentry = FDTABLE.get(&cageid).unwrap()[requested_virtualfd as usize];
     // always add the new entry.  I'm doing this first, before I close
     // the old one because I need to ensure I've cleaned up state correctly
     // before calling the close handlers...
@@ -234,6 +292,13 @@ pub fn copy_fdtable_for_cage(srccageid: u64, newcageid: u64) -> Result<(), three
     // Insert a copy and ensure it didn't exist...
     let hmcopy = FDTABLE.get(&srccageid).unwrap().clone();
 
+    // The copied fds count against the global limit too.  If they don't fit,
+    // the new cage isn't created.
+    let numfds = hmcopy.iter().flatten().count() as u64;
+    if reserve_fds(numfds).is_err() {
+        return Err(threei::Errno::ENFILE);
+    }
+
     // Increment copied items
     for entry in FDTABLE.get(&srccageid).unwrap().iter() {
         if entry.is_some() {
@@ -242,9 +307,9 @@ pub fn copy_fdtable_for_cage(srccageid: u64, newcageid: u64) -> Result<(), three
     }
 
     assert!(FDTABLE.insert(newcageid, hmcopy).is_none());
-    
-    // I'm not going to bother to check the number of fds used overall yet...
-    //    Err(threei::Errno::EMFILE as u64),
+    // The child inherits the parent's limits, as with fork()
+    copy_fd_limits(srccageid, newcageid);
+
     Ok(())
 }
 
@@ -259,6 +324,8 @@ pub fn remove_cage_from_fdtable(cageid: u64) {
     // remove the item first and then we clean up and call their close
     // handlers.
     let myfdrow = FDTABLE.remove(&cageid).unwrap().1;
+    remove_fd_limits(cageid);
+    release_fds(myfdrow.iter().flatten().count() as u64);
 
     // Take only the Some items in here (clippy suggested)
     for entry in myfdrow.into_iter().flatten() {
@@ -291,6 +358,7 @@ pub fn empty_fds_for_exec(cageid: u64) {
 
     // Need to drop the lock, before calling the handlers.
     drop(myfdrow);
+    release_fds(closevec.len() as u64);
 
     // Now, we can call the close handlers!
     for entry in closevec {
@@ -353,20 +421,17 @@ pub fn close_virtualfd(cageid:u64, virtfd:u64) -> Result<(),threei::RetVal> {
 
     assert!(FDTABLE.contains_key(&cageid),"Unknown cageid in fdtable access");
 
-    // cloning this so I don't hold a lock and deadlock close handlers
-    let mut myfdrow = FDTABLE.get_mut(&cageid).unwrap().clone();
-
-
-    if myfdrow[virtfd as usize].is_some() {
-        let entry = myfdrow[virtfd as usize];
+    // Zero out this entry before calling the close handler.  I take it out
+    // while holding the lock, so a concurrent get / close of another fd in
+    // this cage isn't lost, and release the lock before calling the close
+    // handlers so they can't deadlock.
+    let entry = FDTABLE.get_mut(&cageid).unwrap().get_mut(virtfd as usize).and_then(Option::take);
 
-        // Zero out this entry before calling the close handler...
-        myfdrow[virtfd as usize] = None;
-
-        FDTABLE.insert(cageid, myfdrow.clone());
+    if let Some(entry) = entry {
+        release_fds(1);
 
         // always _decrement last as it may call the user handler...
-        _decrement_fdcount(entry.unwrap());
+        _decrement_fdcount(entry);
         return Ok(());
     }
     Err(threei::Errno::EBADFD as u64)
@@ -993,5 +1058,6 @@ pub fn refresh() {
         e.into_inner()
     });
     closehandlers.clear();
+    reset_fd_limits();
     // Note, it doesn't seem that Dashmaps can be poisoned...
 }
@@ -0,0 +1,117 @@
+// Limits on the number of open fds, shared by all of the implementations.
+//
+// Linux has two of these.  A per-process limit (RLIMIT_NOFILE), which bounds
+// the fd numbers a process may be handed out and returns EMFILE, and a
+// system-wide limit on the total number of open files, which returns ENFILE.
+// I track both here so every implementation enforces them the same way.
+//
+// The per-cage limits are a (soft, hard) pair like a struct rlimit.  A cage
+// only has an entry here once its limits differ from the defaults, so the
+// implementations don't need to do anything when a cage is created.  The
+// hard limit can never be above FD_PER_PROCESS_MAX because that is how large
+// the tables are.
+//
+// The global count is the number of virtual fds in use across all cages.  An
+// implementation must reserve a slot before it adds an entry to a table and
+// release it once the entry is gone.
+
+use crate::threei;
+
+use dashmap::DashMap;
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+
+use super::commonconstants::{FD_PER_PROCESS_MAX, TOTAL_FD_MAX};
+
+// cageid -> (soft limit, hard limit).  Missing cages use the defaults.
+static FDLIMITS: LazyLock<DashMap<u64, (u64, u64)>> = LazyLock::new(DashMap::new);
+
+// The most fds which may be open across all cages and how many are now.
+static TOTAL_FD_LIMIT: AtomicU64 = AtomicU64::new(TOTAL_FD_MAX);
+static TOTAL_FD_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Returns the (soft, hard) limit on the number of fds of a cage.  Fd numbers
+/// handed out to the cage are always below the soft limit.
+#[must_use] // must use the return value if you call it.
+pub fn get_fd_limits(cageid: u64) -> (u64, u64) {
+    match FDLIMITS.get(&cageid) {
+        Some(limits) => *limits,
+        None => (FD_PER_PROCESS_MAX, FD_PER_PROCESS_MAX),
+    }
+}
+
+/// Sets the (soft, hard) limit on the number of fds of a cage, as
+/// `setrlimit(RLIMIT_NOFILE)` does.  Fds already open above the new soft
+/// limit stay open.
+///
+/// # Errors
+/// Returns EINVAL if `soft` is above `hard` and EPERM if `hard` is above the
+/// cage's current hard limit.
+pub fn set_fd_limits(cageid: u64, soft: u64, hard: u64) -> Result<(), threei::RetVal> {
+    if soft > hard {
+        return Err(threei::Errno::EINVAL as u64);
+    }
+    // Like an unprivileged process, a cage may lower its hard limit, but
+    // never raise it again.
+    if hard > get_fd_limits(cageid).1 {
+        return Err(threei::Errno::EPERM as u64);
+    }
+    FDLIMITS.insert(cageid, (soft, hard));
+    Ok(())
+}
+
+/// Returns the most fds which may be open across all cages.
+#[must_use] // must use the return value if you call it.
+pub fn get_total_fd_limit() -> u64 {
+    TOTAL_FD_LIMIT.load(Ordering::SeqCst)
+}
+
+/// Sets the most fds which may be open across all cages.  Once this many are
+/// open, creating another returns ENFILE.  Fds already open above the new
+/// limit stay open.
+pub fn set_total_fd_limit(limit: u64) {
+    TOTAL_FD_LIMIT.store(limit, Ordering::SeqCst);
+}
+
+/// Returns the number of fds now open across all cages.
+#[must_use] // must use the return value if you call it.
+pub fn get_total_fd_count() -> u64 {
+    TOTAL_FD_COUNT.load(Ordering::SeqCst)
+}
+
+// Counts `count` more open fds, unless that would go over the global limit.
+pub(crate) fn reserve_fds(count: u64) -> Result<(), threei::RetVal> {
+    let limit = TOTAL_FD_LIMIT.load(Ordering::SeqCst);
+    match TOTAL_FD_COUNT.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |current| {
+        current.checked_add(count).filter(|newcount| *newcount <= limit)
+    }) {
+        Ok(_) => Ok(()),
+        Err(_) => Err(threei::Errno::ENFILE as u64),
+    }
+}
+
+// Counts `count` fds as closed.
+pub(crate) fn release_fds(count: u64) {
+    TOTAL_FD_COUNT.fetch_sub(count, Ordering::SeqCst);
+}
+
+// Gives a forked cage the limits of its parent.
+pub(crate) fn copy_fd_limits(srccageid: u64, newcageid: u64) {
+    if let Some(limits) = FDLIMITS.get(&srccageid).map(|limits| *limits) {
+        FDLIMITS.insert(newcageid, limits);
+    }
+}
+
+// Forgets the limits of a cage which is gone.
+pub(crate) fn remove_fd_limits(cageid: u64) {
+    FDLIMITS.remove(&cageid);
+}
+
+// Puts all of the limits and the count back as they started.  Only used by
+// refresh() when testing...
+pub(crate) fn reset_fd_limits() {
+    FDLIMITS.clear();
+    TOTAL_FD_LIMIT.store(TOTAL_FD_MAX, Ordering::SeqCst);
+    TOTAL_FD_COUNT.store(0, Ordering::SeqCst);
+}
@@ -14,9 +14,11 @@
 //!
 //! Note that the code re-exports an implementation from a specific submodule.
 //! This was done to make the algorithmic options easier to benchmark and
-//! compare.  You, the caller, should only use the base `fdtables` API and
-//! not `fdtables::algorithmname` directly, as the latter will not be stable
-//! over time.
+//! compare.  You, the caller, should only use the base `fdtables` API.  The
+//! implementation is chosen with a cargo feature (`vanilla`, `muthashmax`,
+//! `dashmaparray` or `dashmapvec`), with `dashmaparray` as the default.
+//! Every implementation also provides the [`FdTableBackend`] trait, which is
+//! how the tests and benchmarks run against all of them.
 
 // ********************** CLIPPY DISCUSSION **************************** //
 // Copied from Tom Buckley-Houston
@@ -76,27 +78,21 @@
 
 // ********************* END CLIPPY DISCUSSION ************************* //
 
-// NOTE: This setup is a bit odd, I know.  I'm creating different
-// implementations of the same algorithm and I'd like to test them.  Originally
-// I was going to have a struct interface where I switched between them by
-// swapping out structs with the same trait.  This was a pain-in-the-butt, but
-// it worked for single threaded things or multi-threaded readable things.
-// However, I couldn't figure out how to make this work with having threads
-// share a struct where the underlying things which were mutable (even though
-// the underlying items were locked appropriately in a generic way).
+// NOTE: There are several implementations of the same algorithm so that I
+// can test and benchmark them against each other.  Each one keeps its state
+// in globals, so I couldn't swap between them by swapping out structs which
+// share a trait (I couldn't figure out how to make threads share a struct
+// whose underlying items were mutable).  Instead, every implementation is a
+// module with the same free functions, and the one chosen by the cargo
+// features is re-exported here.
 //
 // This makes things like the doc strings very odd as well.  I am extracting
 // these out to separate files instead of having them in-line, since the
 // different implementations will have the same doc strings.
 //
-// How this works is that I will import a single implementation as a mod here
-// and this is what the benchmarker will use.  If you want to change the
-// implementation you benchmark / test / use, you need to change the lines
-// below...
-//
-// I've looked at traits and patterns.  It's possible there is a better way to
-// do this which I'm currently unable to devise given my unfamliarity with
-// Rust...
+// The FdTableBackend trait in backend.rs wraps each module in a unit struct,
+// so the tests and benchmarks can be written once and run on every
+// implementation.
 
 // Please see the doc strings for more information about the implementations.
 
@@ -110,10 +106,9 @@
 //              descriptors has been reached.
 //
 //       ENFILE The system-wide limit on the total number of open files
-//              has been reached. (mostly unimplemented)
-
-// This includes the specific implementation of the algorithm chosen.
-include!("current_impl");
+//              has been reached.
+//
+// Both are kept in fdlimits.rs so all of the implementations share them.
 
 // This includes general constants and definitions for things that are
 // needed everywhere, like FDTableEntry.  I use the * import here to flatten
@@ -121,6 +116,35 @@ include!("current_impl");
 mod commonconstants;
 pub use commonconstants::*;
 
+// The implementations.  All of them are always built so they can be tested
+// and benchmarked side by side.
+mod dashmaparrayglobal;
+mod dashmapvecglobal;
+mod muthashmaxglobal;
+mod vanillaglobal;
+
+// The FdTableBackend trait and a unit struct for each implementation.
+mod backend;
+pub use backend::*;
+
+// This re-exports the implementation chosen by the cargo features.  See
+// DefaultBackend in backend.rs for which one wins if several are enabled.
+#[cfg(feature = "vanilla")]
+pub use crate::vanillaglobal::*;
+#[cfg(all(feature = "muthashmax", not(feature = "vanilla")))]
+pub use crate::muthashmaxglobal::*;
+#[cfg(all(
+    feature = "dashmapvec",
+    not(any(feature = "vanilla", feature = "muthashmax"))
+))]
+pub use crate::dashmapvecglobal::*;
+#[cfg(not(any(feature = "vanilla", feature = "muthashmax", feature = "dashmapvec")))]
+pub use crate::dashmaparrayglobal::*;
+
+// The per-cage (RLIMIT_NOFILE) and global limits on the number of fds.
+mod fdlimits;
+pub use fdlimits::*;
+
 // This is used everywhere...  Should I re-export more of these symbols?
 pub mod threei;
 /// Error values (matching errno in Linux) for the various call Results
@@ -159,52 +183,50 @@ mod tests {
         panic!("do_panic!");
     }
 
-    #[test]
     // Basic test to ensure that I can get a virtual fd and the info back
     // find the value in the table afterwards...
-    fn get_and_translate_work() {
+    fn get_and_translate_work<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FDKIND: u32 = 0;
         const UNDERFD: u64 = 10;
         // Acquire a virtual fd...
         let my_virt_fd =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
-        let _ = get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
-        let _ = get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
-        let _ = get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
+        let _ = B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
+        let _ = B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
+        let _ = B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
         assert_eq!(
             UNDERFD,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd)
                 .unwrap()
                 .underfd
         );
         assert_eq!(
             FDKIND,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd)
                 .unwrap()
                 .fdkind
         );
     }
 
-    #[test]
     // Do more complex things work with get and translate?
-    fn more_complex_get_and_translate() {
+    fn more_complex_get_and_translate<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // Acquire a virtual fd...
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 2, false, 3).unwrap();
-        let my_virt_fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 7, 8, true, 9).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 2, false, 3).unwrap();
+        let my_virt_fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 7, 8, true, 9).unwrap();
         assert_eq!(
             FDTableEntry {
                 fdkind: 1,
@@ -212,7 +234,7 @@ mod tests {
                 should_cloexec: false,
                 perfdinfo: 3
             },
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd).unwrap()
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd).unwrap()
         );
         assert_eq!(
             FDTableEntry {
@@ -221,23 +243,22 @@ mod tests {
                 should_cloexec: true,
                 perfdinfo: 9
             },
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2).unwrap()
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2).unwrap()
         );
     }
 
-    #[test]
     // Let's see if I can change the cloexec flag...
-    fn try_set_cloexec() {
+    fn try_set_cloexec<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // Acquire a virtual fd...
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 2, false, 3).unwrap();
-        set_cloexec(threei::TESTING_CAGEID, my_virt_fd, true).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 2, false, 3).unwrap();
+        B::set_cloexec(threei::TESTING_CAGEID, my_virt_fd, true).unwrap();
 
         assert_eq!(
             FDTableEntry {
@@ -246,98 +267,94 @@ mod tests {
                 should_cloexec: true, // Should be set now...
                 perfdinfo: 3
             },
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd).unwrap()
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd).unwrap()
         );
     }
 
-    #[test]
     // Set perfdinfo
-    fn try_set_perfdinfo() {
+    fn try_set_perfdinfo<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // Acquire two virtual fds with the same fdkind and underfd...
-        let my_virt_fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 3, 4, false, 150).unwrap();
-        let my_virt_fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 3, 4, true, 250).unwrap();
-        set_perfdinfo(threei::TESTING_CAGEID, my_virt_fd1, 500).unwrap();
+        let my_virt_fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 3, 4, false, 150).unwrap();
+        let my_virt_fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 3, 4, true, 250).unwrap();
+        B::set_perfdinfo(threei::TESTING_CAGEID, my_virt_fd1, 500).unwrap();
         assert_eq!(
             500,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
                 .unwrap()
                 .perfdinfo
         );
         // Changing one should not have changed the other...
         assert_eq!(
             250,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2)
                 .unwrap()
                 .perfdinfo
         );
     }
 
-    #[test]
-    fn test_remove_cage_from_fdtable() {
+    fn test_remove_cage_from_fdtable<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // Acquire two virtual fds...
         let _my_virt_fd1 =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
         let _my_virt_fd2 =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, 4, 13, false, 150).unwrap();
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, 4, 13, false, 150).unwrap();
 
         // let's drop this fdtable...
-        remove_cage_from_fdtable(threei::TESTING_CAGEID);
+        B::remove_cage_from_fdtable(threei::TESTING_CAGEID);
         // Likely should have a better test, but everything will panic...
     }
 
-    #[test]
-    fn test_empty_fds_for_exec() {
+    fn test_empty_fds_for_exec<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // Acquire two virtual fds...
-        let my_virt_fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
-        let my_virt_fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 4, true, 250).unwrap();
+        let my_virt_fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
+        let my_virt_fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 4, true, 250).unwrap();
 
-        empty_fds_for_exec(threei::TESTING_CAGEID);
+        B::empty_fds_for_exec(threei::TESTING_CAGEID);
 
         assert_eq!(
             150,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
                 .unwrap()
                 .perfdinfo
         );
         // Should be missing...
-        assert!(translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2).is_err());
+        assert!(B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2).is_err());
     }
 
-    #[test]
-    fn return_fdtable_copy_test() {
+    fn return_fdtable_copy_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
         // Acquire two virtual fds...
-        let my_virt_fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
-        let my_virt_fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 4, true, 250).unwrap();
+        let my_virt_fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
+        let my_virt_fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 4, true, 250).unwrap();
 
         // Copy the fdtable over to a new cage...
-        let mut myhm = return_fdtable_copy(threei::TESTING_CAGEID);
+        let mut myhm = B::return_fdtable_copy(threei::TESTING_CAGEID);
 
         // Check we got what we expected...
         assert_eq!(
@@ -384,85 +401,83 @@ mod tests {
         // Check to make sure the actual table is still intact...
         assert_eq!(
             150,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
                 .unwrap()
                 .perfdinfo
         );
         assert_eq!(
             250,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2)
                 .unwrap()
                 .perfdinfo
         );
     }
 
-    #[test]
-    fn test_copy_fdtable_for_cage() {
+    fn test_copy_fdtable_for_cage<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // Acquire two virtual fds...
-        let my_virt_fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
-        let my_virt_fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 4, true, 250).unwrap();
+        let my_virt_fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
+        let my_virt_fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 4, true, 250).unwrap();
 
         assert_eq!(
             150,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
                 .unwrap()
                 .perfdinfo
         );
         assert_eq!(
             250,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd2)
                 .unwrap()
                 .perfdinfo
         );
 
         // Copy the fdtable over to a new cage...
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID1).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID1).unwrap();
 
         // Check the elements exist...
         assert_eq!(
             150,
-            translate_virtual_fd(threei::TESTING_CAGEID1, my_virt_fd1)
+            B::translate_virtual_fd(threei::TESTING_CAGEID1, my_virt_fd1)
                 .unwrap()
                 .perfdinfo
         );
         assert_eq!(
             250,
-            translate_virtual_fd(threei::TESTING_CAGEID1, my_virt_fd2)
+            B::translate_virtual_fd(threei::TESTING_CAGEID1, my_virt_fd2)
                 .unwrap()
                 .perfdinfo
         );
         // ... and are independent...
-        set_perfdinfo(threei::TESTING_CAGEID, my_virt_fd1, 500).unwrap();
+        B::set_perfdinfo(threei::TESTING_CAGEID, my_virt_fd1, 500).unwrap();
         assert_eq!(
             150,
-            translate_virtual_fd(threei::TESTING_CAGEID1, my_virt_fd1)
+            B::translate_virtual_fd(threei::TESTING_CAGEID1, my_virt_fd1)
                 .unwrap()
                 .perfdinfo
         );
         assert_eq!(
             500,
-            translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd1)
                 .unwrap()
                 .perfdinfo
         );
     }
 
-    #[test]
-    // Do close_virtualfd(...) testing...
-    fn test_close_virtualfd_with_fdkind_0() {
+    // Do B::close_virtualfd(...) testing...
+    fn test_close_virtualfd_with_fdkind_0<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FD1: u64 = 57;
 
@@ -471,52 +486,51 @@ mod tests {
         const SPECIFICVIRTUALFD: u64 = 15;
 
         // None of my closes (until the end) will be the last...
-        register_close_handlers(0, NULL_FUNC, do_panic);
+        B::register_close_handlers(0, NULL_FUNC, do_panic);
 
         // use the same fd a few times in different ways...
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD1, false, 10).unwrap();
-        get_specific_virtual_fd(threei::TESTING_CAGEID, SPECIFICVIRTUALFD, 0, FD1, false, 10)
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD1, false, 10).unwrap();
+        B::get_specific_virtual_fd(threei::TESTING_CAGEID, SPECIFICVIRTUALFD, 0, FD1, false, 10)
             .unwrap();
-        let cloexecfd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD1, true, 10).unwrap();
+        let cloexecfd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD1, true, 10).unwrap();
         // and a different fd
         let _my_virt_fd3 =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD2, false, 10).unwrap();
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD2, false, 10).unwrap();
 
         // let's close one (should have two left...)
-        close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
 
         // Let's fork (to double the count)!
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID7).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID7).unwrap();
 
         // let's simulate exec, which should close one of these...
-        empty_fds_for_exec(threei::TESTING_CAGEID7);
+        B::empty_fds_for_exec(threei::TESTING_CAGEID7);
 
         // but the copy in the original cage table should remain, so this
         // shouldn't error...
-        translate_virtual_fd(threei::TESTING_CAGEID, cloexecfd).unwrap();
+        B::translate_virtual_fd(threei::TESTING_CAGEID, cloexecfd).unwrap();
 
         // However, the other should be gone and should error...
-        assert!(translate_virtual_fd(threei::TESTING_CAGEID7, cloexecfd).is_err());
+        assert!(B::translate_virtual_fd(threei::TESTING_CAGEID7, cloexecfd).is_err());
 
         // Let's simulate exit on the initial cage, to close two of them...
-        remove_cage_from_fdtable(threei::TESTING_CAGEID);
+        B::remove_cage_from_fdtable(threei::TESTING_CAGEID);
 
         // panic if this isn't the last one (from now on)
-        register_close_handlers(0, do_panic, NULL_FUNC);
+        B::register_close_handlers(0, do_panic, NULL_FUNC);
 
         // Now this is the last one!
-        close_virtualfd(threei::TESTING_CAGEID7, SPECIFICVIRTUALFD).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID7, SPECIFICVIRTUALFD).unwrap();
     }
 
-    #[test]
-    // Do close_virtualfd(...) testing on different fdkinds...
-    fn test_close_virtualfd_with_varied_fdkinds() {
+    // Do B::close_virtualfd(...) testing on different fdkinds...
+    fn test_close_virtualfd_with_varied_fdkinds<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FDKIND1: u32 = 57;
         const FD1: u64 = 57;
@@ -527,12 +541,12 @@ mod tests {
         const SPECIFICVIRTUALFD: u64 = 15;
 
         // Should not be called because I'm doing different fds...
-        register_close_handlers(0, do_panic, do_panic);
+        B::register_close_handlers(0, do_panic, do_panic);
 
         // use the same fd a few times in different ways...
         let my_virt_fd =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND1, FD1, false, 10).unwrap();
-        get_specific_virtual_fd(
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND1, FD1, false, 10).unwrap();
+        B::get_specific_virtual_fd(
             threei::TESTING_CAGEID,
             SPECIFICVIRTUALFD,
             FDKIND1,
@@ -542,141 +556,135 @@ mod tests {
         )
         .unwrap();
         let cloexecfd =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND1, FD1, true, 10).unwrap();
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND1, FD1, true, 10).unwrap();
         // and a different fd
         let _my_virt_fd3 =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND2, FD2, false, 10).unwrap();
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND2, FD2, false, 10).unwrap();
 
         // let's close one (should have two left...)
-        close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
 
         // Let's fork (to double the count)!
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID7).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID7).unwrap();
 
         // let's simulate exec, which should close one of these...
-        empty_fds_for_exec(threei::TESTING_CAGEID7);
+        B::empty_fds_for_exec(threei::TESTING_CAGEID7);
 
         // but the copy in the original cage table should remain, so this
         // shouldn't error...
-        translate_virtual_fd(threei::TESTING_CAGEID, cloexecfd).unwrap();
+        B::translate_virtual_fd(threei::TESTING_CAGEID, cloexecfd).unwrap();
 
         // However, the other should be gone and should error...
-        assert!(translate_virtual_fd(threei::TESTING_CAGEID7, cloexecfd).is_err());
+        assert!(B::translate_virtual_fd(threei::TESTING_CAGEID7, cloexecfd).is_err());
 
         // Let's simulate exit on the initial cage, to close two of them...
-        remove_cage_from_fdtable(threei::TESTING_CAGEID);
+        B::remove_cage_from_fdtable(threei::TESTING_CAGEID);
 
         // Now this is the last one!
-        close_virtualfd(threei::TESTING_CAGEID7, SPECIFICVIRTUALFD).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID7, SPECIFICVIRTUALFD).unwrap();
     }
 
-    #[test]
-    #[should_panic]
     // Check for duplicate uses of the same fd...
-    fn test_dup_close() {
+    fn test_dup_close<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // get the fd...  I tested this in the test above, so should not
         // panic...
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 10).unwrap();
-        close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 10).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
 
         // Panic on this one...
-        register_close_handlers(0, do_panic, NULL_FUNC);
+        B::register_close_handlers(0, do_panic, NULL_FUNC);
 
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 10).unwrap();
-        close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 10).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
     }
 
     // Helper for the close handler recursion tests...
-    fn _test_close_handler_recursion_helper(_: FDTableEntry, _: u64) {
+    fn _test_close_handler_recursion_helper<B: FdTableBackend>(_: FDTableEntry, _: u64) {
         // reset helpers
-        register_close_handlers(0, NULL_FUNC, NULL_FUNC);
+        B::register_close_handlers(0, NULL_FUNC, NULL_FUNC);
 
         const FD: u64 = 57;
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 10).unwrap();
-        close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 10).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
     }
 
-    #[test]
     // check to see what happens if close handlers call other operations...
-    fn test_close_handler_recursion() {
+    fn test_close_handler_recursion<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FD: u64 = 57;
 
         // Register my helper to be called when I call close...
-        register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper);
+        B::register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper::<B>);
 
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 10).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 10).unwrap();
         // Call this which calls the close handler
-        close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
     }
 
-    #[test]
     // get_specific_virtual_fd closehandler recursion... likely deadlock on
     // fail.
-    fn test_gsvfd_handler_recursion() {
+    fn test_gsvfd_handler_recursion<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FD: u64 = 57;
 
         // Register my helper to be called when I call close...
-        register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper);
+        B::register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper::<B>);
 
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 10).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 10).unwrap();
         // Call this which calls the close handler
-        get_specific_virtual_fd(threei::TESTING_CAGEID, my_virt_fd, 0, 123, true, 0).unwrap();
+        B::get_specific_virtual_fd(threei::TESTING_CAGEID, my_virt_fd, 0, 123, true, 0).unwrap();
     }
 
-    #[test]
     // remove_cage_from_fdtable closehandler recursion... likely deadlock on
     // fail.
-    fn test_rcffdt_handler_recursion() {
+    fn test_rcffdt_handler_recursion<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FD: u64 = 57;
         // Since I'm removing a cage here, yet doing operations afterwards,
         // I need to have an empty cage first.
-        init_empty_cage(threei::TESTING_CAGEID5);
+        B::init_empty_cage(threei::TESTING_CAGEID5);
 
         // Register my helper to be called when I call close...
-        register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper);
+        B::register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper::<B>);
 
-        let _my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID5, 0, FD, false, 10).unwrap();
+        let _my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID5, 0, FD, false, 10).unwrap();
         // Call this which calls the close handler
-        remove_cage_from_fdtable(threei::TESTING_CAGEID5);
+        B::remove_cage_from_fdtable(threei::TESTING_CAGEID5);
     }
 
-    #[test]
     // empty_fds_for_exec closehandler recursion...  likely deadlock on fail.
-    fn test_effe_handler_recursion() {
+    fn test_effe_handler_recursion<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // Use a different fdkind...
         const FDKIND: u32 = 1000;
@@ -684,16 +692,15 @@ mod tests {
 
         // Register my helper to be called when I call close on only FDKIND
         // 0.  This should not be called because FDKIND is different...
-        register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper);
+        B::register_close_handlers(0, NULL_FUNC, _test_close_handler_recursion_helper::<B>);
 
         let _my_virt_fd =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, FD, true, 10).unwrap();
-        empty_fds_for_exec(threei::TESTING_CAGEID);
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, FD, true, 10).unwrap();
+        B::empty_fds_for_exec(threei::TESTING_CAGEID);
     }
 
-    #[test]
     // check some common poll cases...
-    fn check_poll_helpers() {
+    fn check_poll_helpers<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
         loop {
             match TESTMUTEX.lock() {
@@ -706,16 +713,16 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
         let cage_id = threei::TESTING_CAGEID;
 
-        get_specific_virtual_fd(cage_id, 3, 0, 7, false, 10).unwrap();
-        get_specific_virtual_fd(cage_id, 5, 100, 32, false, 123).unwrap();
-        get_specific_virtual_fd(cage_id, 9, 0, 20, true, 0).unwrap();
+        B::get_specific_virtual_fd(cage_id, 3, 0, 7, false, 10).unwrap();
+        B::get_specific_virtual_fd(cage_id, 5, 100, 32, false, 123).unwrap();
+        B::get_specific_virtual_fd(cage_id, 9, 0, 20, true, 0).unwrap();
 
         let (pollhashmap, mappingtable) =
-            convert_virtualfds_for_poll(cage_id, HashSet::from([1, 3, 5, 9]));
+            B::convert_virtualfds_for_poll(cage_id, HashSet::from([1, 3, 5, 9]));
 
         assert_eq!(pollhashmap.len(), 3); // 3 different keys for fdkinds
         assert_eq!(pollhashmap.get(&0).unwrap().len(), 2);
@@ -723,14 +730,13 @@ mod tests {
         assert_eq!(pollhashmap.get(&FDT_INVALID_FD).unwrap().len(), 1);
 
         // poll(...)  // let's pretend that fd 7 had its event triggered...
-        let newfds = convert_poll_result_back_to_virtual(0, 7, &mappingtable);
+        let newfds = B::convert_poll_result_back_to_virtual(0, 7, &mappingtable);
         // virtfd 3 should be returned
         assert_eq!(newfds, Some(3));
     }
 
-    #[test]
     // check some common epoll cases...
-    fn check_epoll_helpers() {
+    fn check_epoll_helpers<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
         loop {
             match TESTMUTEX.lock() {
@@ -743,7 +749,7 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
         let cage_id = threei::TESTING_CAGEID;
 
@@ -753,15 +759,15 @@ mod tests {
         let virtfd2 = 6;
         let virtfd3 = 10;
         let epollunderfd = 100;
-        // get_specific_virtual_fd(cage_id, VIRTFD, REALFD, CLOEXEC, OPTINFO)
-        get_specific_virtual_fd(cage_id, virtfd1, EMULFDKIND, 10, false, 123).unwrap();
-        get_specific_virtual_fd(cage_id, virtfd2, EMULFDKIND, 11, false, 456).unwrap();
-        get_specific_virtual_fd(cage_id, virtfd3, FDKIND, 20, true, 0).unwrap();
+        // B::get_specific_virtual_fd(cage_id, VIRTFD, REALFD, CLOEXEC, OPTINFO)
+        B::get_specific_virtual_fd(cage_id, virtfd1, EMULFDKIND, 10, false, 123).unwrap();
+        B::get_specific_virtual_fd(cage_id, virtfd2, EMULFDKIND, 11, false, 456).unwrap();
+        B::get_specific_virtual_fd(cage_id, virtfd3, FDKIND, 20, true, 0).unwrap();
 
         // get an epollfd...
-        let epollfd = epoll_create_empty(cage_id, false).unwrap();
+        let epollfd = B::epoll_create_empty(cage_id, false).unwrap();
         // ... set the underfd ...
-        epoll_add_underfd(cage_id, epollfd, FDKIND, epollunderfd).unwrap();
+        B::epoll_add_underfd(cage_id, epollfd, FDKIND, epollunderfd).unwrap();
 
         let myevent1 = epoll_event {
             events: (EPOLLIN + EPOLLOUT) as u32,
@@ -774,19 +780,19 @@ mod tests {
 
         // try to add the epollfd, which should fail
         assert_eq!(
-            virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd3, myevent1.clone())
+            B::virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd3, myevent1.clone())
                 .unwrap(),
             ()
         );
 
         // Only one key,
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
+            B::get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
             1
         );
         // ...with a value
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd)
+            B::get_virtual_epoll_wait_data(cage_id, epollfd)
                 .unwrap()
                 .get(&FDKIND)
                 .unwrap()
@@ -796,54 +802,54 @@ mod tests {
 
         // Add in one fd...
         assert_eq!(
-            virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd1, myevent1.clone())
+            B::virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd1, myevent1.clone())
                 .unwrap(),
             ()
         );
 
         // Should have two keys now
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
+            B::get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
             2
         );
 
         // Delete an item...
         assert_eq!(
-            virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_DEL, virtfd1, myevent1.clone())
+            B::virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_DEL, virtfd1, myevent1.clone())
                 .unwrap(),
             ()
         );
 
         // Only one key,
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
+            B::get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
             1
         );
 
         // Add in two EMULFDKINDS
         assert_eq!(
-            virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd1, myevent1.clone())
+            B::virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd1, myevent1.clone())
                 .unwrap(),
             ()
         );
         assert_eq!(
-            virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd2, myevent2.clone())
+            B::virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_ADD, virtfd2, myevent2.clone())
                 .unwrap(),
             ()
         );
         // Should have two kinds...
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
+            B::get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
             2
         );
         // ...and two values of kind EMULFDKIND
 
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
+            B::get_virtual_epoll_wait_data(cage_id, epollfd).unwrap().len(),
             2
         );
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd)
+            B::get_virtual_epoll_wait_data(cage_id, epollfd)
                 .unwrap()
                 .get(&EMULFDKIND)
                 .unwrap()
@@ -853,7 +859,7 @@ mod tests {
 
         // Check their event types are correct...
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd)
+            B::get_virtual_epoll_wait_data(cage_id, epollfd)
                 .unwrap()
                 .get(&EMULFDKIND)
                 .unwrap()
@@ -863,7 +869,7 @@ mod tests {
             myevent1.events
         );
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd)
+            B::get_virtual_epoll_wait_data(cage_id, epollfd)
                 .unwrap()
                 .get(&EMULFDKIND)
                 .unwrap()
@@ -875,7 +881,7 @@ mod tests {
 
         // Let's switch one of them...
         assert_eq!(
-            virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_MOD, virtfd1, myevent2.clone())
+            B::virtualize_epoll_ctl(cage_id, epollfd, EPOLL_CTL_MOD, virtfd1, myevent2.clone())
                 .unwrap(),
             ()
         );
@@ -883,7 +889,7 @@ mod tests {
         // Check their event types are correct...
         // not anymore!
         assert_ne!(
-            get_virtual_epoll_wait_data(cage_id, epollfd)
+            B::get_virtual_epoll_wait_data(cage_id, epollfd)
                 .unwrap()
                 .get(&EMULFDKIND)
                 .unwrap()
@@ -894,7 +900,7 @@ mod tests {
         );
         // still the same...
         assert_eq!(
-            get_virtual_epoll_wait_data(cage_id, epollfd)
+            B::get_virtual_epoll_wait_data(cage_id, epollfd)
                 .unwrap()
                 .get(&EMULFDKIND)
                 .unwrap()
@@ -905,13 +911,11 @@ mod tests {
         );
     }
 
-    #[test]
-    #[ignore]
     // Add these if I do the complete epoll later.  These tests are amazing!
     // https://github.com/heiher/epoll-wakeup
     // Right now, just check, did I implement epoll of epoll fds?
     #[allow(non_snake_case)]
-    fn check_SHOULD_FAIL_FOR_NOW_if_we_support_epoll_of_epoll() {
+    fn check_SHOULD_FAIL_FOR_NOW_if_we_support_epoll_of_epoll<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
         loop {
             match TESTMUTEX.lock() {
@@ -924,13 +928,13 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
         let cage_id = threei::TESTING_CAGEID;
 
         // get two epollfds...
-        let epollfd1 = epoll_create_empty(cage_id, false).unwrap();
-        let epollfd2 = epoll_create_empty(cage_id, false).unwrap();
+        let epollfd1 = B::epoll_create_empty(cage_id, false).unwrap();
+        let epollfd2 = B::epoll_create_empty(cage_id, false).unwrap();
 
         let myevent1 = epoll_event {
             events: (EPOLLIN + EPOLLOUT) as u32,
@@ -939,15 +943,14 @@ mod tests {
 
         // try to add an epollfd to an epollfd
         assert_eq!(
-            virtualize_epoll_ctl(cage_id, epollfd1, EPOLL_CTL_ADD, epollfd2, myevent1.clone())
+            B::virtualize_epoll_ctl(cage_id, epollfd1, EPOLL_CTL_ADD, epollfd2, myevent1.clone())
                 .unwrap(),
             ()
         );
     }
 
-    #[test]
     // check some common select cases...
-    fn check_basic_select() {
+    fn check_basic_select<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
         loop {
             match TESTMUTEX.lock() {
@@ -960,22 +963,22 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
         let cage_id = threei::TESTING_CAGEID;
 
-        get_specific_virtual_fd(cage_id, 3, 0, 7, false, 10).unwrap();
-        get_specific_virtual_fd(cage_id, 5, 1, 123, false, 123).unwrap();
+        B::get_specific_virtual_fd(cage_id, 3, 0, 7, false, 10).unwrap();
+        B::get_specific_virtual_fd(cage_id, 5, 1, 123, false, 123).unwrap();
 
         let mut bad_fds_to_check = _init_fd_set();
 
         // check all "None" is okay...
         assert!(
-            prepare_bitmasks_for_select(cage_id, 6, None, None, None, &HashSet::from([0])).is_ok()
+            B::prepare_bitmasks_for_select(cage_id, 6, None, None, None, &HashSet::from([0])).is_ok()
         );
 
         // check a few different "empty" bitmask cases too...
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             6,
             Some(bad_fds_to_check),
@@ -984,7 +987,7 @@ mod tests {
             &HashSet::from([0])
         )
         .is_ok());
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             6,
             None,
@@ -993,7 +996,7 @@ mod tests {
             &HashSet::from([0])
         )
         .is_ok());
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             6,
             Some(bad_fds_to_check),
@@ -1007,7 +1010,7 @@ mod tests {
         _fd_set(2, &mut bad_fds_to_check);
 
         // check all of the positions!
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             6,
             Some(bad_fds_to_check),
@@ -1016,7 +1019,7 @@ mod tests {
             &HashSet::from([0])
         )
         .is_err());
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             6,
             None,
@@ -1025,7 +1028,7 @@ mod tests {
             &HashSet::from([0])
         )
         .is_err());
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             6,
             None,
@@ -1036,7 +1039,7 @@ mod tests {
         .is_err());
 
         // but if I drop the nfds too low, it is okay...
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             2,
             None,
@@ -1047,7 +1050,7 @@ mod tests {
         .is_ok());
 
         // too high also errors...
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             1024,
             None,
@@ -1062,7 +1065,7 @@ mod tests {
         _fd_set(3, &mut actual_fds_to_check);
         _fd_set(5, &mut actual_fds_to_check);
 
-        assert!(prepare_bitmasks_for_select(
+        assert!(B::prepare_bitmasks_for_select(
             cage_id,
             6,
             Some(actual_fds_to_check),
@@ -1073,7 +1076,7 @@ mod tests {
         .is_ok());
 
         // let's peek closer at an actual call...
-        let (selectbittables, unparsedtables, mappingtable) = prepare_bitmasks_for_select(
+        let (selectbittables, unparsedtables, mappingtable) = B::prepare_bitmasks_for_select(
             cage_id,
             6,
             Some(actual_fds_to_check),
@@ -1094,9 +1097,8 @@ mod tests {
         assert_eq!(mappingtable.len(), 2);
     }
 
-    #[test]
     // Let's test to see our functions error gracefully with badfds...
-    fn get_specific_virtual_fd_tests() {
+    fn get_specific_virtual_fd_tests<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
 
         loop {
@@ -1110,9 +1112,9 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
-        let my_virt_fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
+        let my_virt_fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, false, 150).unwrap();
 
         // Choose an unused new_fd
         let my_new_fd: u64;
@@ -1121,22 +1123,22 @@ mod tests {
         } else {
             my_new_fd = 0;
         }
-        get_specific_virtual_fd(threei::TESTING_CAGEID, my_new_fd, 0, 1, true, 5).unwrap();
+        B::get_specific_virtual_fd(threei::TESTING_CAGEID, my_new_fd, 0, 1, true, 5).unwrap();
         assert_eq!(
-            translate_virtual_fd(threei::TESTING_CAGEID, my_new_fd)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_new_fd)
                 .unwrap()
                 .perfdinfo,
             5
         );
         assert_eq!(
-            translate_virtual_fd(threei::TESTING_CAGEID, my_new_fd)
+            B::translate_virtual_fd(threei::TESTING_CAGEID, my_new_fd)
                 .unwrap()
                 .underfd,
             1
         );
 
         // Check if I get an error going out of range...
-        assert!(get_specific_virtual_fd(
+        assert!(B::get_specific_virtual_fd(
             threei::TESTING_CAGEID,
             FD_PER_PROCESS_MAX + 1,
             0,
@@ -1147,36 +1149,34 @@ mod tests {
         .is_err());
     }
 
-    #[test]
     // Let's test to see our functions error gracefully with badfds...
-    fn badfd_test() {
+    fn badfd_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         // some made up number...
         let my_virt_fd = 135;
-        assert!(translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd).is_err());
-        assert!(set_cloexec(threei::TESTING_CAGEID, my_virt_fd, true).is_err());
-        assert!(set_perfdinfo(threei::TESTING_CAGEID, my_virt_fd, 37).is_err());
+        assert!(B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd).is_err());
+        assert!(B::set_cloexec(threei::TESTING_CAGEID, my_virt_fd, true).is_err());
+        assert!(B::set_perfdinfo(threei::TESTING_CAGEID, my_virt_fd, 37).is_err());
     }
 
-    #[test]
     // Let's do a multithreaded test...
-    fn multithreaded_test() {
+    fn multithreaded_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
 
-        refresh();
-        let fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, true, 100).unwrap();
-        let fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 20, true, 200).unwrap();
-        let fd3 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 300).unwrap();
+        B::refresh();
+        let fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, true, 100).unwrap();
+        let fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 20, true, 200).unwrap();
+        let fd3 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 30, true, 300).unwrap();
         for threadcount in [1, 2, 4, 8, 16].iter() {
             let mut thread_handle_vec: Vec<thread::JoinHandle<()>> = Vec::new();
             for _numthreads in 0..*threadcount {
@@ -1185,16 +1185,16 @@ mod tests {
                 thread_handle_vec.push(thread::spawn(move || {
                     // Do 10K / threadcount of 10 requests each.  100K total
                     for _ in 0..10000 / thisthreadcount {
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd2).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd3).unwrap();
                     }
                 }));
             }
@@ -1204,16 +1204,15 @@ mod tests {
         }
     }
 
-    #[test]
     // Let's do a multithreaded test...
-    fn multithreaded_write_test() {
+    fn multithreaded_write_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
 
-        refresh();
+        B::refresh();
         for threadcount in [1, 2, 4, 8, 16].iter() {
             let mut thread_handle_vec: Vec<thread::JoinHandle<()>> = Vec::new();
             for _numthreads in 0..*threadcount {
@@ -1222,28 +1221,27 @@ mod tests {
                 thread_handle_vec.push(thread::spawn(move || {
                     // Do 1000 writes, then flush it out...
                     for _ in 0..1000 / thisthreadcount {
-                        let fd = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, true, 100)
+                        let fd = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, 10, true, 100)
                             .unwrap();
-                        translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
+                        B::translate_virtual_fd(threei::TESTING_CAGEID, fd).unwrap();
                     }
                 }));
             }
             for handle in thread_handle_vec {
                 handle.join().unwrap();
             }
-            refresh();
+            B::refresh();
         }
     }
 
     // Let's use up all the fds and verify we get an error...
-    #[test]
-    fn use_all_fds_test() {
+    fn use_all_fds_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FD: u64 = 10;
         for _current in 0..FD_PER_PROCESS_MAX {
@@ -1266,21 +1264,20 @@ mod tests {
                 current as usize
             ); */
 
-            let _ = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap();
+            let _ = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap();
         }
         // If the test is failing by not triggering here, we're not stopping
         // at the limit...
-        if get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).is_err() {
-            refresh();
+        if B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).is_err() {
+            B::refresh();
         } else {
             panic!("Should have raised an error...");
         }
     }
 
-    #[test]
     // Do we close a virtualfd when we select it?  (Do nothing, but see the
     // next test.)
-    fn check_get_specific_virtual_fd_close_ok_test() {
+    fn check_get_specific_virtual_fd_close_ok_test<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
 
         loop {
@@ -1294,19 +1291,17 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID10).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID10).unwrap();
 
-        let virtfd = get_unused_virtual_fd(threei::TESTING_CAGEID10, 0, 10, false, 100).unwrap();
+        let virtfd = B::get_unused_virtual_fd(threei::TESTING_CAGEID10, 0, 10, false, 100).unwrap();
         // Do nothing.  See next test...
-        get_specific_virtual_fd(threei::TESTING_CAGEID10, virtfd, 0, 10, false, 100).unwrap();
+        B::get_specific_virtual_fd(threei::TESTING_CAGEID10, virtfd, 0, 10, false, 100).unwrap();
     }
 
-    #[test]
-    #[should_panic]
     // checks that init correctly panics
-    fn check_init_panics() {
+    fn check_init_panics<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
 
         loop {
@@ -1320,17 +1315,15 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID11).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID11).unwrap();
         // panic!
-        init_empty_cage(threei::TESTING_CAGEID11);
+        B::init_empty_cage(threei::TESTING_CAGEID11);
     }
 
-    #[test]
-    #[should_panic]
     // Do we close a virtualfd when we call get_specific on it?
-    fn check_get_specific_virtual_fd_close_panic_test() {
+    fn check_get_specific_virtual_fd_close_panic_test<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
 
         loop {
@@ -1344,60 +1337,52 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
-        copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID11).unwrap();
+        B::copy_fdtable_for_cage(threei::TESTING_CAGEID, threei::TESTING_CAGEID11).unwrap();
         // panic in a moment!
-        register_close_handlers(0, do_panic, do_panic);
-        let virtfd = get_unused_virtual_fd(threei::TESTING_CAGEID11, 0, 234, false, 100).unwrap();
+        B::register_close_handlers(0, do_panic, do_panic);
+        let virtfd = B::get_unused_virtual_fd(threei::TESTING_CAGEID11, 0, 234, false, 100).unwrap();
         // panic!!!
-        get_specific_virtual_fd(threei::TESTING_CAGEID11, virtfd, 0, 10, false, 100).unwrap();
+        B::get_specific_virtual_fd(threei::TESTING_CAGEID11, virtfd, 0, 10, false, 100).unwrap();
     }
 
-    #[test]
-    #[should_panic]
     // Let's check to make sure we panic with an invalid cageid
-    fn translate_panics_on_bad_cageid_test() {
+    fn translate_panics_on_bad_cageid_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
 
-        let _ = translate_virtual_fd(threei::INVALID_CAGEID, 10);
+        let _ = B::translate_virtual_fd(threei::INVALID_CAGEID, 10);
     }
 
-    #[test]
-    #[should_panic]
     // Let's check to make sure we panic with an invalid cageid
-    fn get_unused_virtual_fd_panics_on_bad_cageid_test() {
+    fn get_unused_virtual_fd_panics_on_bad_cageid_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
 
-        let _ = get_unused_virtual_fd(threei::INVALID_CAGEID, 0, 10, false, 100);
+        let _ = B::get_unused_virtual_fd(threei::INVALID_CAGEID, 0, 10, false, 100);
     }
 
-    #[test]
-    #[should_panic]
     // Let's check to make sure we panic with an invalid cageid
-    fn set_cloexec_panics_on_bad_cageid_test() {
+    fn set_cloexec_panics_on_bad_cageid_test<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
 
-        let _ = set_cloexec(threei::INVALID_CAGEID, 10, true);
+        let _ = B::set_cloexec(threei::INVALID_CAGEID, 10, true);
     }
 
-    #[test]
-    #[should_panic]
     // Let's check that our callback for close is working correctly by having
     // it panic
-    fn test_intermediate_handler() {
+    fn test_intermediate_handler<B: FdTableBackend>() {
         // Get the guard in a way that if we unpoison it, we don't end up
         // with multiple runners...
         let mut _thelock: MutexGuard<bool>;
@@ -1414,24 +1399,22 @@ mod tests {
             }
         }
 
-        refresh();
+        B::refresh();
 
         const FD: u64 = 132;
         // I'm using unwrap_or because I don't want a panic here to be
         // considered passing the test
-        let fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap_or(1);
-        let _fd2 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap_or(1);
+        let fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap_or(1);
+        let _fd2 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap_or(1);
 
-        register_close_handlers(0, do_panic, NULL_FUNC);
+        B::register_close_handlers(0, do_panic, NULL_FUNC);
 
         // should panic here...
-        close_virtualfd(threei::TESTING_CAGEID, fd1).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, fd1).unwrap();
     }
 
-    #[test]
-    #[should_panic]
     // Check final_handler
-    fn test_final_handler() {
+    fn test_final_handler<B: FdTableBackend>() {
         // Get the guard in a way that if we unpoison it, we don't end up
         // with multiple runners...
         let mut _thelock: MutexGuard<bool>;
@@ -1447,22 +1430,21 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
         const FD: u64 = 109;
         // I'm using unwrap_or because I don't want a panic here to be
         // considered passing the test
-        let fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap_or(1);
+        let fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 0, FD, false, 100).unwrap_or(1);
 
-        register_close_handlers(0, NULL_FUNC, do_panic);
+        B::register_close_handlers(0, NULL_FUNC, do_panic);
 
         // should panic here...
-        close_virtualfd(threei::TESTING_CAGEID, fd1).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, fd1).unwrap();
     }
 
-    #[test]
     // No panics.  Just call a function...
-    fn test_close_handlers() {
+    fn test_close_handlers<B: FdTableBackend>() {
         let mut _thelock: MutexGuard<bool>;
 
         loop {
@@ -1476,45 +1458,313 @@ mod tests {
                 }
             }
         }
-        refresh();
+        B::refresh();
 
         // I'm using unwrap_or because I don't want a panic here to be
         // considered passing the test
-        let fd1 = get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 123, false, 100).unwrap_or(1);
+        let fd1 = B::get_unused_virtual_fd(threei::TESTING_CAGEID, 1, 123, false, 100).unwrap_or(1);
 
         fn myfunc(_: FDTableEntry, _: u64) {}
 
-        register_close_handlers(0, myfunc, myfunc);
+        B::register_close_handlers(0, myfunc, myfunc);
 
         // should panic here...
-        close_virtualfd(threei::TESTING_CAGEID, fd1).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, fd1).unwrap();
     }
 
-    #[test]
     // To check if item has been removed successfully after close
-    fn test_close_fdtable_update() {
+    fn test_close_fdtable_update<B: FdTableBackend>() {
         let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
-            refresh();
+            B::refresh();
             TESTMUTEX.clear_poison();
             e.into_inner()
         });
-        refresh();
+        B::refresh();
 
         const FDKIND: u32 = 0;
         const UNDERFD: u64 = 10;
         // Acquire a virtual fd...
         let my_virt_fd =
-            get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
+            B::get_unused_virtual_fd(threei::TESTING_CAGEID, FDKIND, UNDERFD, false, 100).unwrap();
 
-        close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
+        B::close_virtualfd(threei::TESTING_CAGEID, my_virt_fd).unwrap();
 
         // translate_virtual_fd should return error, because there should have
         // no requested my_virt_fd after close
-        match translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd) {
+        match B::translate_virtual_fd(threei::TESTING_CAGEID, my_virt_fd) {
             Ok(_) => panic!("translate_virtual_fd should return error!!"),
             Err(_e) => {
                 TESTMUTEX.clear_poison();
             }
         }
     }
+
+    // Does the soft limit bound the fds handed out and is it checked
+    // correctly when set?
+    fn test_per_cage_fd_limits<B: FdTableBackend>() {
+        let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
+            B::refresh();
+            TESTMUTEX.clear_poison();
+            e.into_inner()
+        });
+        B::refresh();
+
+        let cage_id = threei::TESTING_CAGEID;
+        assert_eq!(
+            get_fd_limits(cage_id),
+            (FD_PER_PROCESS_MAX, FD_PER_PROCESS_MAX)
+        );
+
+        set_fd_limits(cage_id, 3, 16).unwrap();
+        assert_eq!(get_fd_limits(cage_id), (3, 16));
+        for expected in 0..3 {
+            assert_eq!(
+                B::get_unused_virtual_fd(cage_id, 0, 10, false, 0).unwrap(),
+                expected
+            );
+        }
+        assert_eq!(
+            B::get_unused_virtual_fd(cage_id, 0, 10, false, 0),
+            Err(threei::Errno::EMFILE as u64)
+        );
+        // dup2 onto a fd at or above the soft limit fails, below is fine
+        assert_eq!(
+            B::get_specific_virtual_fd(cage_id, 3, 0, 10, false, 0),
+            Err(threei::Errno::EBADF as u64)
+        );
+        B::get_specific_virtual_fd(cage_id, 2, 0, 11, false, 0).unwrap();
+
+        // raising the soft limit up to the hard one makes room again
+        set_fd_limits(cage_id, 16, 16).unwrap();
+        assert_eq!(B::get_unused_virtual_fd(cage_id, 0, 10, false, 0).unwrap(), 3);
+
+        assert_eq!(
+            set_fd_limits(cage_id, 17, 16),
+            Err(threei::Errno::EINVAL as u64)
+        );
+        // the hard limit may only go down
+        assert_eq!(
+            set_fd_limits(cage_id, 16, 17),
+            Err(threei::Errno::EPERM as u64)
+        );
+        set_fd_limits(cage_id, 8, 8).unwrap();
+        assert_eq!(
+            set_fd_limits(cage_id, 8, 16),
+            Err(threei::Errno::EPERM as u64)
+        );
+        assert_eq!(
+            set_fd_limits(threei::TESTING_CAGEID1, 0, FD_PER_PROCESS_MAX + 1),
+            Err(threei::Errno::EPERM as u64)
+        );
+    }
+
+    // Are the limits inherited by a forked cage and forgotten when it exits?
+    fn test_fd_limits_inherited_by_copy<B: FdTableBackend>() {
+        let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
+            B::refresh();
+            TESTMUTEX.clear_poison();
+            e.into_inner()
+        });
+        B::refresh();
+
+        let src_cage_id = threei::TESTING_CAGEID;
+        let new_cage_id = threei::TESTING_CAGEID1;
+        set_fd_limits(src_cage_id, 2, 4).unwrap();
+        let _ = B::get_unused_virtual_fd(src_cage_id, 0, 10, false, 0).unwrap();
+        B::copy_fdtable_for_cage(src_cage_id, new_cage_id).unwrap();
+
+        assert_eq!(get_fd_limits(new_cage_id), (2, 4));
+        assert_eq!(get_total_fd_count(), 2);
+        assert_eq!(
+            B::get_unused_virtual_fd(new_cage_id, 0, 10, false, 0).unwrap(),
+            1
+        );
+        assert_eq!(
+            B::get_unused_virtual_fd(new_cage_id, 0, 10, false, 0),
+            Err(threei::Errno::EMFILE as u64)
+        );
+
+        // changing the child's limits leaves the parent's alone
+        set_fd_limits(new_cage_id, 1, 1).unwrap();
+        assert_eq!(get_fd_limits(src_cage_id), (2, 4));
+
+        B::remove_cage_from_fdtable(new_cage_id);
+        assert_eq!(get_total_fd_count(), 1);
+        B::init_empty_cage(new_cage_id);
+        assert_eq!(
+            get_fd_limits(new_cage_id),
+            (FD_PER_PROCESS_MAX, FD_PER_PROCESS_MAX)
+        );
+    }
+
+    // Is ENFILE returned once the global limit is reached, and are closed
+    // fds given back?
+    fn test_total_fd_limit<B: FdTableBackend>() {
+        let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
+            B::refresh();
+            TESTMUTEX.clear_poison();
+            e.into_inner()
+        });
+        B::refresh();
+
+        let cage_id = threei::TESTING_CAGEID;
+        let new_cage_id = threei::TESTING_CAGEID2;
+        assert_eq!(get_total_fd_limit(), TOTAL_FD_MAX);
+        set_total_fd_limit(4);
+
+        for _ in 0..3 {
+            let _ = B::get_unused_virtual_fd(cage_id, 0, 10, true, 0).unwrap();
+        }
+        // the copy doesn't fit, so the new cage isn't created
+        assert_eq!(
+            B::copy_fdtable_for_cage(cage_id, new_cage_id),
+            Err(threei::Errno::ENFILE)
+        );
+        B::init_empty_cage(new_cage_id);
+
+        assert_eq!(B::get_unused_virtual_fd(new_cage_id, 0, 10, false, 0).unwrap(), 0);
+        assert_eq!(get_total_fd_count(), 4);
+        assert_eq!(
+            B::get_unused_virtual_fd(cage_id, 0, 10, false, 0),
+            Err(threei::Errno::ENFILE as u64)
+        );
+        assert_eq!(
+            B::get_specific_virtual_fd(cage_id, 100, 0, 10, false, 0),
+            Err(threei::Errno::ENFILE as u64)
+        );
+        assert_eq!(B::epoll_create_empty(cage_id, false), Err(threei::Errno::ENFILE as u64));
+        // replacing an open fd doesn't need another one
+        B::get_specific_virtual_fd(cage_id, 1, 0, 11, true, 0).unwrap();
+
+        B::close_virtualfd(new_cage_id, 0).unwrap();
+        assert_eq!(B::get_unused_virtual_fd(cage_id, 0, 10, false, 0).unwrap(), 3);
+        // the three cloexec fds are given back on exec
+        B::empty_fds_for_exec(cage_id);
+        assert_eq!(get_total_fd_count(), 1);
+        B::copy_fdtable_for_cage(cage_id, threei::TESTING_CAGEID3).unwrap();
+        assert_eq!(get_total_fd_count(), 2);
+    }
+
+    // Every test above is generic over the backend.  These macros make a
+    // module per backend with a #[test] which runs each of them, so all of
+    // the backends must pass the exact same tests.
+    macro_rules! backend_tests {
+        ($modname:ident, $backend:ty, { $($(#[$attr:meta])* fn $name:ident;)* }) => {
+            mod $modname {
+                $(
+                    #[test]
+                    $(#[$attr])*
+                    fn $name() {
+                        super::$name::<$backend>();
+                    }
+                )*
+            }
+        };
+    }
+
+    macro_rules! conformance_suite {
+        ($tests:tt) => {
+            backend_tests!(vanilla, crate::VanillaGlobal, $tests);
+            backend_tests!(muthashmax, crate::MutHashMaxGlobal, $tests);
+            backend_tests!(dashmaparray, crate::DashMapArrayGlobal, $tests);
+            backend_tests!(dashmapvec, crate::DashMapVecGlobal, $tests);
+        };
+    }
+
+    // Does F_DUPFD style allocation honor the lower bound and the limits?
+    fn test_get_unused_virtual_fd_from_startfd<B: FdTableBackend>() {
+        let mut _thelock = TESTMUTEX.lock().unwrap_or_else(|e| {
+            B::refresh();
+            TESTMUTEX.clear_poison();
+            e.into_inner()
+        });
+        B::refresh();
+
+        let cage_id = threei::TESTING_CAGEID;
+        assert_eq!(
+            B::get_unused_virtual_fd_from_startfd(cage_id, 0, 10, false, 0, 5).unwrap(),
+            5
+        );
+        let fd = B::get_unused_virtual_fd_from_startfd(cage_id, 1, 11, true, 7, 5).unwrap();
+        assert_eq!(fd, 6);
+        let entry = B::translate_virtual_fd(cage_id, fd).unwrap();
+        assert_eq!((entry.fdkind, entry.underfd), (1, 11));
+        assert!(entry.should_cloexec);
+        assert_eq!(entry.perfdinfo, 7);
+
+        // the fds below startfd are still free
+        assert_eq!(
+            B::get_unused_virtual_fd_from_startfd(cage_id, 0, 10, false, 0, 0).unwrap(),
+            0
+        );
+        assert_eq!(B::get_unused_virtual_fd(cage_id, 0, 10, false, 0).unwrap(), 1);
+
+        // startfd must be below the soft limit, fds above it are never used
+        set_fd_limits(cage_id, 8, 16).unwrap();
+        assert_eq!(
+            B::get_unused_virtual_fd_from_startfd(cage_id, 0, 10, false, 0, 8),
+            Err(threei::Errno::EINVAL as u64)
+        );
+        assert_eq!(
+            B::get_unused_virtual_fd_from_startfd(cage_id, 0, 10, false, 0, 7).unwrap(),
+            7
+        );
+        assert_eq!(
+            B::get_unused_virtual_fd_from_startfd(cage_id, 0, 10, false, 0, 5),
+            Err(threei::Errno::EMFILE as u64)
+        );
+        // get_unused_virtual_fd still finds the gap below
+        assert_eq!(B::get_unused_virtual_fd(cage_id, 0, 10, false, 0).unwrap(), 2);
+    }
+
+    conformance_suite!({
+        fn get_and_translate_work;
+        fn more_complex_get_and_translate;
+        fn try_set_cloexec;
+        fn try_set_perfdinfo;
+        fn test_remove_cage_from_fdtable;
+        fn test_empty_fds_for_exec;
+        fn return_fdtable_copy_test;
+        fn test_copy_fdtable_for_cage;
+        fn test_close_virtualfd_with_fdkind_0;
+        fn test_close_virtualfd_with_varied_fdkinds;
+        #[should_panic]
+        fn test_dup_close;
+        fn test_close_handler_recursion;
+        fn test_gsvfd_handler_recursion;
+        fn test_rcffdt_handler_recursion;
+        fn test_effe_handler_recursion;
+        fn check_poll_helpers;
+        fn check_epoll_helpers;
+        #[ignore]
+        #[allow(non_snake_case)]
+        fn check_SHOULD_FAIL_FOR_NOW_if_we_support_epoll_of_epoll;
+        fn check_basic_select;
+        fn get_specific_virtual_fd_tests;
+        fn badfd_test;
+        fn multithreaded_test;
+        fn multithreaded_write_test;
+        fn use_all_fds_test;
+        fn check_get_specific_virtual_fd_close_ok_test;
+        #[should_panic]
+        fn check_init_panics;
+        #[should_panic]
+        fn check_get_specific_virtual_fd_close_panic_test;
+        #[should_panic]
+        fn translate_panics_on_bad_cageid_test;
+        #[should_panic]
+        fn get_unused_virtual_fd_panics_on_bad_cageid_test;
+        #[should_panic]
+        fn set_cloexec_panics_on_bad_cageid_test;
+        #[should_panic]
+        fn test_intermediate_handler;
+        #[should_panic]
+        fn test_final_handler;
+        fn test_close_handlers;
+        fn test_close_fdtable_update;
+        fn test_per_cage_fd_limits;
+        fn test_fd_limits_inherited_by_copy;
+        fn test_total_fd_limit;
+        fn test_get_unused_virtual_fd_from_startfd;
+    });
 }
@@ -13,16 +13,21 @@ use std::collections::HashMap;
 // Get constants about the fd table sizes, etc.
 pub use super::commonconstants::*;
 
+// The per-cage and global fd limits are tracked for all implementations here.
+use super::fdlimits::{
+    copy_fd_limits, get_fd_limits, release_fds, remove_fd_limits, reserve_fds, reset_fd_limits,
+};
+
 // algorithm name.  Need not be listed in the docs.
 #[doc(hidden)]
 pub const ALGONAME: &str = "VanillaGlobal";
 
 // It's fairly easy to check the fd count on a per-process basis (I just check
 // when I would
-// add a new fd).
+// add a new fd).  Fds are only handed out below the cage's soft limit.
 //
-// BUG: I will ignore the total limit for now.  I would ideally do this on
-// every creation, close, fork, etc. but it's a PITA to track this.
+// The total limit is checked through fdlimits.rs.  I reserve a slot whenever
+// an entry is added to a table and release it whenever one is removed.
 
 // We will raise a panic anywhere we receive an unknown cageid.  This frankly
 // should not be possible and indicates some sort of internal error in our
@@ -31,8 +36,7 @@ pub const ALGONAME: &str = "VanillaGlobal";
 
 // In order to store this information, I'm going to use a HashMap which
 // has keys of (cageid:u64) and values that are another HashMap.  The second
-// HashMap has keys of (virtualfd:64) and values of (realfd:u64,
-// should_cloexec:bool, optionalinfo:u64).
+// HashMap has keys of (virtualfd:64) and values of FDTableEntry.
 //
 // To speed up lookups, I could have used arrays instead of HashMaps.  In
 // theory, that space is far too large, but likely each could be bounded to
@@ -48,7 +52,7 @@ lazy_static! {
 
     #[derive(Debug)]
     static ref GLOBALFDTABLE: Mutex<HashMap<u64, HashMap<u64,FDTableEntry>>> = {
-        let mut m = HashMap::new();
+        let m = HashMap::new();
         // Insert a cage so that I have something to fork / test later, if need
         // be. Otherwise, I'm not sure how I get this started. I think this
         // should be invalid from a 3i standpoint, etc. Could this mask an
@@ -60,19 +64,30 @@ lazy_static! {
 
 lazy_static! {
     // This is needed for close and similar functionality.  I need track the
-    // number of times a realfd is open
+    // number of times a (fdkind,underfd) is open.  Note that this is across
+    // cages in order to enable a library to have  situations where two cages
+    // have the same fd open.  The (fdkind,underfd) tuple is the key and the
+    // number of times it appears is the value.  If it reaches 0, the entry
+    // is removed.
     #[derive(Debug)]
-    static ref GLOBALREALFDCOUNT: Mutex<HashMap<u64, u64>> = {
+    static ref GLOBALREALFDCOUNT: Mutex<HashMap<(u32,u64), u64>> = {
         Mutex::new(HashMap::new())
     };
 
 }
 
-// Internal helper to hold the close handlers...
+// Internal helper to hold the close handlers...  These indicate what
+// functions should be called upon a virtualfd closing.
+// The handler which is called depends on number of (fdkind,underfd) tuples
+// that are used across *all instances managed by this library including in
+// other cages*.
 struct CloseHandlers {
-    intermediate_handler: fn(u64),
-    final_handler: fn(u64),
-    unreal_handler: fn(u64),
+    // Called when close is called, but at least one (fdkind,underfd)
+    // reference still remains.  Called with (entry,count)
+    intermediate: fn(FDTableEntry,u64),
+    // Called when the last (fdkind,underfd) reference is closed.  Called with
+    // (entry,0)
+    last: fn(FDTableEntry,u64),
 }
 
 lazy_static! {
@@ -80,13 +95,8 @@ lazy_static! {
     // a close occurs.  I did this rather than return messy data structures
     // from the close, exec, and exit handlers because it seemed cleaner...
     #[derive(Debug)]
-    static ref CLOSEHANDLERTABLE: Mutex<CloseHandlers> = {
-        let c = CloseHandlers {
-            intermediate_handler:NULL_FUNC,
-            final_handler:NULL_FUNC,
-            unreal_handler:NULL_FUNC,
-        };
-        Mutex::new(c)
+    static ref CLOSEHANDLERTABLE: Mutex<HashMap<u32,CloseHandlers>> = {
+        Mutex::new(HashMap::new())
     };
 }
 
@@ -103,7 +113,7 @@ pub fn init_empty_cage(cageid: u64) {
 }
 
 #[doc = include_str!("../docs/translate_virtual_fd.md")]
-pub fn translate_virtual_fd(cageid: u64, virtualfd: u64) -> Result<u64, threei::RetVal> {
+pub fn translate_virtual_fd(cageid: u64, virtualfd: u64) -> Result<FDTableEntry, threei::RetVal> {
     // Get the lock on the fdtable...  I'm not handling "poisoned locks" now
     // where a thread holding the lock died...
     let fdtable = GLOBALFDTABLE.lock().unwrap();
@@ -122,7 +132,7 @@ pub fn translate_virtual_fd(cageid: u64, virtualfd: u64) -> Result<u64, threei::
     }
 
     return match fdtable.get(&cageid).unwrap().get(&virtualfd) {
-        Some(tableentry) => Ok(tableentry.realfd),
+        Some(tableentry) => Ok(*tableentry),
         None => Err(threei::Errno::EBADFD as u64),
     };
 }
@@ -137,9 +147,10 @@ pub fn translate_virtual_fd(cageid: u64, virtualfd: u64) -> Result<u64, threei::
 #[doc = include_str!("../docs/get_unused_virtual_fd.md")]
 pub fn get_unused_virtual_fd(
     cageid: u64,
-    realfd: u64,
+    fdkind: u32,
+    underfd: u64,
     should_cloexec: bool,
-    optionalinfo: u64,
+    perfdinfo: u64,
 ) -> Result<u64, threei::RetVal> {
     let mut fdtable = GLOBALFDTABLE.lock().unwrap();
 
@@ -150,20 +161,70 @@ pub fn get_unused_virtual_fd(
     // Note, a HashMap stores its data on the heap!  No need to box it...
     // https://doc.rust-lang.org/book/ch08-03-hash-maps.html#creating-a-new-hash-map
     let myentry = FDTableEntry {
-        realfd,
+        fdkind,
+        underfd,
         should_cloexec,
-        optionalinfo,
+        perfdinfo,
     };
 
+    let softlimit = get_fd_limits(cageid).0;
     let myfdmap = fdtable.get_mut(&cageid).unwrap();
 
     // Check the fds in order.
-    for fdcandidate in 0..FD_PER_PROCESS_MAX {
+    for fdcandidate in 0..softlimit {
+        // Get the entry if it's Vacant and assign it to e (so I can fill
+        // it in).
+        if let std::collections::hash_map::Entry::Vacant(e) = myfdmap.entry(fdcandidate) {
+            // return ENFILE if no more fds may be open at all...
+            reserve_fds(1)?;
+            e.insert(myentry);
+            _increment_fdcount(myentry);
+            return Ok(fdcandidate);
+        }
+    }
+
+    // I must have checked all fds and failed to find one open.  Fail!
+    Err(threei::Errno::EMFILE as u64)
+}
+
+// This is used for things like fcntl's F_DUPFD, which needs the lowest fd
+// that is at least startfd...
+#[doc = include_str!("../docs/get_unused_virtual_fd_from_startfd.md")]
+pub fn get_unused_virtual_fd_from_startfd(
+    cageid: u64,
+    fdkind: u32,
+    underfd: u64,
+    should_cloexec: bool,
+    perfdinfo: u64,
+    startfd: u64,
+) -> Result<u64, threei::RetVal> {
+    let mut fdtable = GLOBALFDTABLE.lock().unwrap();
+
+    assert!(fdtable.contains_key(&cageid),"Unknown cageid in fdtable access");
+
+    let softlimit = get_fd_limits(cageid).0;
+    if startfd >= softlimit {
+        return Err(threei::Errno::EINVAL as u64);
+    }
+
+    let myentry = FDTableEntry {
+        fdkind,
+        underfd,
+        should_cloexec,
+        perfdinfo,
+    };
+
+    let myfdmap = fdtable.get_mut(&cageid).unwrap();
+
+    // Check the fds in order, starting at startfd.
+    for fdcandidate in startfd..softlimit {
         // Get the entry if it's Vacant and assign it to e (so I can fill
         // it in).
         if let std::collections::hash_map::Entry::Vacant(e) = myfdmap.entry(fdcandidate) {
+            // return ENFILE if no more fds may be open at all...
+            reserve_fds(1)?;
             e.insert(myentry);
-            _increment_realfd(realfd);
+            _increment_fdcount(myentry);
             return Ok(fdcandidate);
         }
     }
@@ -178,9 +239,10 @@ pub fn get_unused_virtual_fd(
 pub fn get_specific_virtual_fd(
     cageid: u64,
     requested_virtualfd: u64,
-    realfd: u64,
+    fdkind: u32,
+    underfd: u64,
     should_cloexec: bool,
-    optionalinfo: u64,
+    perfdinfo: u64,
 ) -> Result<(), threei::RetVal> {
     let mut fdtable = GLOBALFDTABLE.lock().unwrap();
 
@@ -189,10 +251,8 @@ pub fn get_specific_virtual_fd(
     }
 
     // If you ask for a FD number that is too large, I'm going to reject it.
-    // Note that, I need to use the FD_PER_PROCESS_MAX setting because this
-    // is also how I'm tracking how many values you have open.  If this
-    // changed, then these constants could be decoupled...
-    if requested_virtualfd > FD_PER_PROCESS_MAX {
+    // The soft limit is never above FD_PER_PROCESS_MAX.
+    if requested_virtualfd >= get_fd_limits(cageid).0 {
         return Err(threei::Errno::EBADF as u64);
     }
 
@@ -200,29 +260,29 @@ pub fn get_specific_virtual_fd(
     // Note, a HashMap stores its data on the heap!  No need to box it...
     // https://doc.rust-lang.org/book/ch08-03-hash-maps.html#creating-a-new-hash-map
     let myentry = FDTableEntry {
-        realfd,
+        fdkind,
+        underfd,
         should_cloexec,
-        optionalinfo,
+        perfdinfo,
     };
 
-    // I moved this up so that if I decrement the same realfd, it calls
-    // the intermediate handler instead of the final one.
-    _increment_realfd(realfd);
+    // Replacing an entry doesn't change the number of open fds, but filling
+    // an empty one does.
+    if !fdtable.get(&cageid).unwrap().contains_key(&requested_virtualfd) {
+        reserve_fds(1)?;
+    }
+
+    // I moved this up so that if I decrement the same (fdkind,underfd), it
+    // calls the intermediate handler instead of the last one.
+    _increment_fdcount(myentry);
 
     // always add the new entry.  insert returns the old entry.
     let myoptionentry = fdtable.get_mut(&cageid).unwrap().insert(requested_virtualfd,myentry);
     drop(fdtable);
 
-    // Close the old entry, if I need to...
+    // Update the fdcount / close the old entry, if existed
     if let Some(entry) = myoptionentry {
-        if entry.realfd != NO_REAL_FD {
-            _decrement_realfd(entry.realfd);
-        }
-        else {
-            // Let their code know this has been closed...
-            let unrealclosehandler = (*CLOSEHANDLERTABLE.lock().unwrap()).unreal_handler;
-            (unrealclosehandler)(entry.optionalinfo);
-        }
+        _decrement_fdcount(entry);
     }
 
     Ok(())
@@ -244,29 +304,15 @@ pub fn set_cloexec(cageid: u64, virtualfd: u64, is_cloexec: bool) -> Result<(),
             Ok(())
         }
         None => Err(threei::Errno::EBADFD as u64),
-    };
-}
-
-// Super easy, just return the optionalinfo field...
-#[doc = include_str!("../docs/get_optionalinfo.md")]
-pub fn get_optionalinfo(cageid: u64, virtualfd: u64) -> Result<u64, threei::RetVal> {
-    let fdtable = GLOBALFDTABLE.lock().unwrap();
-    if !fdtable.contains_key(&cageid) {
-        panic!("Unknown cageid in fdtable access");
     }
-
-    return match fdtable.get(&cageid).unwrap().get(&virtualfd) {
-        Some(tableentry) => Ok(tableentry.optionalinfo),
-        None => Err(threei::Errno::EBADFD as u64),
-    };
 }
 
 // We're setting an opaque value here. This should be pretty straightforward.
-#[doc = include_str!("../docs/set_optionalinfo.md")]
-pub fn set_optionalinfo(
+#[doc = include_str!("../docs/set_perfdinfo.md")]
+pub fn set_perfdinfo(
     cageid: u64,
     virtualfd: u64,
-    optionalinfo: u64,
+    perfdinfo: u64,
 ) -> Result<(), threei::RetVal> {
     let mut fdtable = GLOBALFDTABLE.lock().unwrap();
 
@@ -274,10 +320,10 @@ pub fn set_optionalinfo(
         panic!("Unknown cageid in fdtable access");
     }
 
-    // Set optionalinfo or return EBADFD, if that's missing...
+    // Set perfdinfo or return EBADFD, if that's missing...
     return match fdtable.get_mut(&cageid).unwrap().get_mut(&virtualfd) {
         Some(tableentry) => {
-            tableentry.optionalinfo = optionalinfo;
+            tableentry.perfdinfo = perfdinfo;
             Ok(())
         }
         None => Err(threei::Errno::EBADFD as u64),
@@ -299,18 +345,22 @@ pub fn copy_fdtable_for_cage(srccageid: u64, newcageid: u64) -> Result<(), three
     // Insert a copy and ensure it didn't exist...
     let hmcopy = fdtable.get(&srccageid).unwrap().clone();
 
+    // The copied fds count against the global limit too.  If they don't fit,
+    // the new cage isn't created.
+    if reserve_fds(hmcopy.len() as u64).is_err() {
+        return Err(threei::Errno::ENFILE);
+    }
+
     // increment the reference to items in the fdtable appropriately...
-    for v in fdtable.get(&srccageid).unwrap().values() {
-        if v.realfd != NO_REAL_FD {
-            _increment_realfd(v.realfd);
-        }
+    for v in hmcopy.values() {
+        _increment_fdcount(*v);
     }
 
     // insert the new table...
     assert!(fdtable.insert(newcageid, hmcopy).is_none());
+    // The child inherits the parent's limits, as with fork()
+    copy_fd_limits(srccageid, newcageid);
     Ok(())
-    // I'm not going to bother to check the number of fds used overall yet...
-    //    Err(threei::Errno::EMFILE as u64),
 }
 
 // This is mostly used in handling exit, etc.  Returns the HashMap
@@ -325,17 +375,12 @@ pub fn remove_cage_from_fdtable(cageid: u64) {
 
     let cagetable = fdtable.remove(&cageid).unwrap();
     drop(fdtable);
+    remove_fd_limits(cageid);
+    release_fds(cagetable.len() as u64);
 
     // decrement the reference to items in the fdtable appropriately...
     for v in cagetable.values() {
-        if v.realfd != NO_REAL_FD {
-            _decrement_realfd(v.realfd);
-        }
-        else {
-            // Let their code know this has been closed...
-            let unrealclosehandler = CLOSEHANDLERTABLE.lock().unwrap().unreal_handler;
-            (unrealclosehandler)(v.optionalinfo);
-        }
+        _decrement_fdcount(*v);
     }
 
 }
@@ -384,20 +429,12 @@ pub fn empty_fds_for_exec(cageid: u64) {
     fdtable.insert(cageid,without_cloexec_hm);
     // Release the lock...
     drop(fdtable);
+    release_fds(with_cloexec_vec.len() as u64);
 
     // Now call the close handlers on the others...
     for v in with_cloexec_vec {
-        if v.realfd == NO_REAL_FD {
-            // Let their code know this has been closed...  I get the handler
-            // repeatedly in case they change it during execution of another
-            // handler...
-            let closehandlerunreal = CLOSEHANDLERTABLE.lock().unwrap().unreal_handler;
-            (closehandlerunreal)(v.optionalinfo);
-        }
-        else {
-            // Let the helper tell the user and decrement the count
-            _decrement_realfd(v.realfd);
-        }
+        // Let the helper tell the user and decrement the count
+        _decrement_fdcount(v);
     }
 
 }
@@ -433,18 +470,12 @@ pub fn close_virtualfd(cageid:u64, virtfd:u64) -> Result<(),threei::RetVal> {
     drop(fdtable);
 
     match thisoption {
-        Some(entry) =>
-            if entry.realfd == NO_REAL_FD {
-                // Let their code know this has been closed...
-                let closehandlerunreal = CLOSEHANDLERTABLE.lock().unwrap().unreal_handler;
-                let optionalinfo = entry.optionalinfo;
-                (closehandlerunreal)(optionalinfo);
-                Ok(())
-            }
-            else {
-                _decrement_realfd(entry.realfd);
-                Ok(())
-            }
+        Some(entry) => {
+            release_fds(1);
+            // always _decrement last as it may call the user handler...
+            _decrement_fdcount(entry);
+            Ok(())
+        }
         None => Err(threei::Errno::EBADFD as u64),
     }
 }
@@ -453,72 +484,72 @@ pub fn close_virtualfd(cageid:u64, virtfd:u64) -> Result<(),threei::RetVal> {
 // Register a series of helpers to be called for close.  Can be called
 // multiple times to override the older helpers.
 #[doc = include_str!("../docs/register_close_handlers.md")]
-pub fn register_close_handlers(intermediate_handler: fn(u64), final_handler: fn(u64), unreal_handler: fn(u64)) {
+pub fn register_close_handlers(fdkind:u32, intermediate: fn(FDTableEntry,u64), last: fn(FDTableEntry,u64)) {
     // Unlock the table and set the handlers...
-    // TODO: I made a serious attempt to try to keep closehandlers that call
-    // close, etc. from deadlocking the system.  More testing, etc. is needed.
-    let mut closehandlers = CLOSEHANDLERTABLE.lock().unwrap();
-    closehandlers.intermediate_handler = intermediate_handler;
-    closehandlers.final_handler = final_handler;
-    closehandlers.unreal_handler = unreal_handler;
+    let mut closehandlertable = CLOSEHANDLERTABLE.lock().unwrap();
+    let closehandler = CloseHandlers {
+        intermediate,
+        last,
+    };
+    // overwrite whatever is in there...
+    closehandlertable.insert(fdkind,closehandler);
 }
 
-// Helpers to track the count of times each realfd is used
+// Helpers to track the count of times each (fdkind,underfd) is used
 #[doc(hidden)]
-fn _decrement_realfd(realfd:u64) -> u64 {
-    // Do nothing if it's not a realfd...
-    assert!(realfd != NO_REAL_FD, "Called _decrement_realfd with NO_REAL_FD");
+fn _decrement_fdcount(entry:FDTableEntry) {
+
+    let mytuple = (entry.fdkind, entry.underfd);
 
     // Get this table's lock...
-    let mut realfdcount = GLOBALREALFDCOUNT.lock().unwrap();
+    let mut fdcount = GLOBALREALFDCOUNT.lock().unwrap();
+
+    let newcount:u64 = fdcount.get(&mytuple).unwrap() - 1;
 
-    let newcount:u64 = realfdcount.get(&realfd).unwrap() - 1;
+    // Update before calling their close handler in case they do operations
+    // inside the close handler which create / close fds...
+    if newcount > 0 {
+        fdcount.insert(mytuple,newcount);
+    }
+    else {
+        fdcount.remove(&mytuple);
+    }
+    // Need to drop locks to call the handlers or else will deadlock...
+    drop(fdcount);
+
+    let intermediatech;
+    let lastch;
     let closehandlers = CLOSEHANDLERTABLE.lock().unwrap();
-    let intermediateclosehandler = closehandlers.intermediate_handler;
-    let finalclosehandler = closehandlers.final_handler;
+    if let Some(closehandlerentry) = closehandlers.get(&entry.fdkind) {
+        intermediatech = closehandlerentry.intermediate;
+        lastch = closehandlerentry.last;
+    }
+    else {
+        intermediatech = NULL_FUNC;
+        lastch = NULL_FUNC;
+    }
     // release the lock...
     drop(closehandlers);
 
     if newcount > 0 {
-        realfdcount.insert(realfd,newcount);
-        // Need to drop locks to call the handlers or else will deadlock...
-        drop(realfdcount);
-
-        (intermediateclosehandler)(realfd);
+        (intermediatech)(entry,newcount);
     }
     else {
-        // Remove before calling their close handler in case they do operations
-        // inside the close handler which create / close fds...
-        realfdcount.remove(&realfd);
-        // Need to drop locks to call the handlers or else will deadlock...
-        drop(realfdcount);
-
-        (finalclosehandler)(realfd);
+        (lastch)(entry,0);
     }
-    newcount
 }
 
-// Helpers to track the count of times each realfd is used
+// Helpers to track the count of times each (fdkind,underfd) is used
 #[doc(hidden)]
-fn _increment_realfd(realfd:u64) -> u64 {
-    if realfd == NO_REAL_FD {
-        return 0
-    }
+fn _increment_fdcount(entry:FDTableEntry) {
+
+    let mytuple = (entry.fdkind, entry.underfd);
 
     // Get this table's lock...
-    let mut realfdcount = GLOBALREALFDCOUNT.lock().unwrap();
+    let mut fdcount = GLOBALREALFDCOUNT.lock().unwrap();
 
     // Get a mutable reference to the entry so we can update it.
-    return match realfdcount.get_mut(&realfd) {
-        Some(count) => {
-            *count += 1;
-            *count
-        }
-        None => {
-            realfdcount.insert(realfd, 1);
-            1
-        }
-    }
+    *fdcount.entry(mytuple).or_insert(0) += 1;
 }
 
 /***************   Code for handling select() ****************/
@@ -551,96 +582,124 @@ pub fn _fd_isset(fd:u64, thisfdset:&fd_set) -> bool {
     unsafe{libc::FD_ISSET(fd as i32,thisfdset)}
 }
 
-// Computes the bitmodifications and returns a (maxnfds, unrealset) tuple...
-#[doc(hidden)]
-fn _do_bitmods(myfdmap:HashMap<u64,FDTableEntry>, nfds:u64, infdset: fd_set, thisfdset: &mut fd_set, mappingtable: &mut HashMap<u64,u64>) -> Result<(u64,HashSet<(u64,u64)>),threei::RetVal> {
-    let mut unrealhashset:HashSet<(u64,u64)> = HashSet::new();
-    // Iterate through the infdset and set those values as is appropriate
-    let mut highestpos = 0;
-
-    // Clippy is somehow missing how pos is using bit.
-    #[allow(clippy::needless_range_loop)]
-    for bit in 0..nfds as usize {
-        let pos = bit as u64;
-        if _fd_isset(pos,&infdset) {
-            if let Some(entry) = myfdmap.get(&pos) {
-                if entry.realfd == NO_REAL_FD {
-                    unrealhashset.insert((pos,entry.optionalinfo));
-                }
-                else {
-                    mappingtable.insert(entry.realfd, pos);
-                    _fd_set(entry.realfd,thisfdset);
-                    // I add one because select expects nfds to be the max+1
-                    highestpos = cmp::max(highestpos, entry.realfd+1);
-                }
-            }
-            else {
-                return Err(threei::Errno::EINVAL as u64);
-            }
-        }
-    }
-    Ok((highestpos,unrealhashset))
-}
+// This is a helper that just does a single type (r/w/e) and returns:
+//    bithashmap: HashMap<fdkind, (nfds, fd_set)>
+//    unhandledhashmap: HashMap<fdkind, HashSet<FDTableEntry>>
+//    mappingtable: HashMap<FDTableEntry, virt_fd>
+//
+// With this we trivially build the whole function...
 
 // helper to call before calling select beneath you.  Translates your virtfds
-// to realfds.
+// into a bitmask you may use for select.
 // See: https://man7.org/linux/man-pages/man2/select.2.html for details /
 // corner cases about the arguments.
 
-// I hate doing these, but don't know how to make this interface better...
+// I hate doing this, but don't know how to make this interface better...
 #[allow(clippy::type_complexity)]
-#[allow(clippy::too_many_arguments)]
-#[doc = include_str!("../docs/get_real_bitmasks_for_select.md")]
-pub fn get_real_bitmasks_for_select(cageid:u64, nfds:u64, readbits:Option<fd_set>, writebits:Option<fd_set>, exceptbits:Option<fd_set>) -> Result<(u64, Option<fd_set>, Option<fd_set>, Option<fd_set>, [HashSet<(u64,u64)>;3], HashMap<u64,u64>),threei::RetVal> {
+#[allow(clippy::implicit_hasher)]
+#[doc = include_str!("../docs/get_bitmask_for_select.md")]
+pub fn get_bitmask_for_select(cageid:u64, nfds:u64, bits:Option<fd_set>, fdkinds:&HashSet<u32>) -> Result<(HashMap<u32,(u64, fd_set)>, HashMap<u32,HashSet<FDTableEntry>>, HashMap<(u32,u64),u64>),threei::RetVal> {
 
     if nfds >= FD_PER_PROCESS_MAX {
         return Err(threei::Errno::EINVAL as u64);
     }
 
-    let globfdtable = GLOBALFDTABLE.lock().unwrap();
+    // The three things I will return...
+    let mut retbittable:HashMap<u32,(u64,fd_set)> = HashMap::new();
+    let mut retunparsedtable:HashMap<u32,HashSet<FDTableEntry>> = HashMap::new();
+    let mut mappingtable:HashMap<(u32,u64),u64> = HashMap::new();
 
+    // copy the cage's table so I don't hold the lock for the whole call
+    let globfdtable = GLOBALFDTABLE.lock().unwrap();
     if !globfdtable.contains_key(&cageid) {
         panic!("Unknown cageid in fdtable access");
     }
+    let myfdmap = globfdtable.get(&cageid).unwrap().clone();
+    drop(globfdtable);
+
+    // If we were asked to do this on nothing, return empty mappings...
+    let Some(infdset) = bits else {
+        return Ok((retbittable, retunparsedtable, mappingtable));
+    };
+
+    // iterate through the set bits...
+    for bit in 0..nfds {
+        let pos = bit;
+        if _fd_isset(pos,&infdset) {
+            if let Some(entry) = myfdmap.get(&pos) {
+
+                // I like to do the shorter case first rather than having
+                // it later.
+                #[allow(clippy::if_not_else)]
+                // Which return set do I go in?
+                if !fdkinds.contains(&entry.fdkind) {
+                    // Is unparsed...  Clippy's suggestion to insert if missing
+                    retunparsedtable.entry(entry.fdkind).or_default().insert(*entry);
+                    // and update the mappingtable to have the bit from the
+                    // original fd...
+                    mappingtable.insert((entry.fdkind,entry.underfd),pos);
+                }
+                else {
 
-    let mut unrealarray:[HashSet<(u64,u64)>;3] = [HashSet::new(),HashSet::new(),HashSet::new()];
-    let mut mappingtable:HashMap<u64,u64> = HashMap::new();
-    let mut newnfds = 0;
+                    // Either initialize it or use what exists.  I don't init
+                    // the fd_set up front because it is a large data
+                    // structure and would be costly.
+                    let (startingnfds, mut startingfdset) = match retbittable.get(&entry.fdkind) {
+                        Some(existing) => *existing,
+                        None => (1, _init_fd_set()),
+                    };
 
-    // putting results in a vec was the cleanest way I found to do this..
-    let mut resultvec = Vec::new();
+                    // Update the table and the nfds
+                    _fd_set(entry.underfd,&mut startingfdset);
+                    let newnfds = cmp::max(startingnfds, entry.underfd+1);
 
-    for (unrealoffset, inset) in [readbits,writebits, exceptbits].into_iter().enumerate() {
-        match inset {
-            Some(virtualbits) => {
-                let mut retset = _init_fd_set();
-                let (thisnfds,myunrealhashset) = _do_bitmods(globfdtable.get(&cageid).unwrap().clone(),nfds,virtualbits, &mut retset,&mut mappingtable)?;
-                resultvec.push(Some(retset));
-                newnfds = cmp::max(thisnfds, newnfds);
-                unrealarray[unrealoffset] = myunrealhashset;
+                    // and update the mappingtable to have the bit from the
+                    // original fd...
+                    mappingtable.insert((entry.fdkind,entry.underfd),pos);
+
+                    // insert the item
+                    retbittable.insert(entry.fdkind,(newnfds,startingfdset));
+                }
             }
-            None => {
-                // This item is null.  No unreal items
-                resultvec.push(None);
-                unrealarray[unrealoffset] = HashSet::new();
+            else {
+                return Err(threei::Errno::EBADF as u64);
             }
         }
     }
+    Ok((retbittable, retunparsedtable, mappingtable))
+
+}
 
-    Ok((newnfds, resultvec[0], resultvec[1], resultvec[2], unrealarray, mappingtable))
+
+#[allow(clippy::type_complexity)]
+#[allow(clippy::implicit_hasher)]
+#[doc = include_str!("../docs/prepare_bitmasks_for_select.md")]
+pub fn prepare_bitmasks_for_select(cageid:u64, nfds:u64, rbits:Option<fd_set>, wbits:Option<fd_set>, ebits:Option<fd_set>, fdkinds:&HashSet<u32>) -> Result<([HashMap<u32,(u64, fd_set)>;3], [HashMap<u32,HashSet<FDTableEntry>>;3], HashMap<(u32,u64),u64>),threei::RetVal> {
+    // This is a pretty simple function.  Calls get_bitmask_for_select
+    // repeatedly and combines the results...
+
+    // return the error, if need be
+    let rresult = get_bitmask_for_select(cageid, nfds, rbits, fdkinds)?;
+    let wresult = get_bitmask_for_select(cageid, nfds, wbits, fdkinds)?;
+    let eresult = get_bitmask_for_select(cageid, nfds, ebits, fdkinds)?;
+
+    let mut mappingtable = rresult.2;
+    mappingtable.extend(wresult.2);
+    mappingtable.extend(eresult.2);
+
+    Ok(([rresult.0,wresult.0,eresult.0],[rresult.1,wresult.1,eresult.1],mappingtable))
 
 }
 
 
-// helper to call after calling select beneath you.  returns the fd_sets you
+// helper to call after calling select beneath you.  returns the fd_set you
 // need for your return from a select call and the number of unique flags
 // set...
 
-// I hate doing these, but don't know how to make this interface better...
-#[allow(clippy::type_complexity)]
-#[allow(clippy::too_many_arguments)]
-#[doc = include_str!("../docs/get_virtual_bitmasks_from_select_result.md")]
-pub fn get_virtual_bitmasks_from_select_result(nfds:u64, readbits:Option<fd_set>, writebits:Option<fd_set>, exceptbits:Option<fd_set>,unrealreadset:HashSet<u64>, unrealwriteset:HashSet<u64>, unrealexceptset:HashSet<u64>, mappingtable:&HashMap<u64,u64>) -> Result<(u64, Option<fd_set>, Option<fd_set>, Option<fd_set>),threei::RetVal> {
+// I given them the hashmap, so don't need flexibility in what they return...
+#[allow(clippy::implicit_hasher)]
+#[doc = include_str!("../docs/get_one_virtual_bitmask_from_select_result.md")]
+pub fn get_one_virtual_bitmask_from_select_result(fdkind:u32, nfds:u64, bits:Option<fd_set>, unprocessedset:HashSet<u64>, startingbits:Option<fd_set>,mappingtable:&HashMap<(u32,u64),u64>) -> (u64, Option<fd_set>) {
 
     // Note, I don't need the cage_id here because I have the mappingtable...
 
@@ -649,35 +708,32 @@ pub fn get_virtual_bitmasks_from_select_result(nfds:u64, readbits:Option<fd_set>
     }
 
     let mut flagsset = 0;
-    let mut retvec = Vec::new();
 
-    for (insetoption,unrealset) in [(readbits,unrealreadset), (writebits,unrealwriteset), (exceptbits,unrealexceptset)] {
-        // If I don't have any data, just return None (NULL) and skip...
-        if insetoption.is_none()&&unrealset.is_empty() {
-            retvec.push(None);
-            continue;
-        }
+    if bits.is_none() && unprocessedset.is_empty() {
+        return (flagsset,None);
+    }
 
-        let mut retbits = _init_fd_set();
-        if let Some(inset) = insetoption {
-            for bit in 0..nfds as usize {
-                let pos = bit as u64;
-                if _fd_isset(pos,&inset)&& !_fd_isset(*mappingtable.get(&pos).unwrap(),&retbits) {
-                    flagsset+=1;
-                    _fd_set(*mappingtable.get(&pos).unwrap(),&mut retbits);
-                }
-            }
-        }
-        for virtfd in unrealset {
-            if !_fd_isset(virtfd,&retbits) {
+    let mut retbits = match startingbits {
+        Some(val) => val,
+        None => _init_fd_set(),
+    };
+
+    if let Some(inset) = bits {
+        for pos in 0..nfds {
+            if _fd_isset(pos,&inset)&& !_fd_isset(*mappingtable.get(&(fdkind,pos)).unwrap(),&retbits) {
                 flagsset+=1;
-                _fd_set(virtfd,&mut retbits);
+                _fd_set(*mappingtable.get(&(fdkind,pos)).unwrap(),&mut retbits);
             }
         }
-        retvec.push(Some(retbits));
+    }
+    for virtfd in unprocessedset {
+        if !_fd_isset(virtfd,&retbits) {
+            flagsset+=1;
+            _fd_set(virtfd,&mut retbits);
+        }
     }
 
-    Ok((flagsset,retvec[0],retvec[1],retvec[2]))
+    (flagsset,Some(retbits))
 
 }
 
@@ -688,9 +744,10 @@ pub fn get_virtual_bitmasks_from_select_result(nfds:u64, readbits:Option<fd_set>
 // helper to call before calling poll beneath you.  replaces the fds in
 // the poll struct with virtual versions and returns the items you need
 // to check yourself...
+#[allow(clippy::implicit_hasher)]
 #[allow(clippy::type_complexity)]
-#[doc = include_str!("../docs/convert_virtualfds_to_real.md")]
-pub fn convert_virtualfds_to_real(cageid:u64, virtualfds:Vec<u64>) -> (Vec<u64>, Vec<(u64,u64)>, Vec<u64>, HashMap<u64,u64>) {
+#[doc = include_str!("../docs/convert_virtualfds_for_poll.md")]
+pub fn convert_virtualfds_for_poll(cageid:u64, virtualfds:HashSet<u64>) -> (HashMap<u32,HashSet<(u64,FDTableEntry)>>, HashMap<(u32,u64),u64>) {
 
     let globfdtable = GLOBALFDTABLE.lock().unwrap();
 
@@ -698,69 +755,64 @@ pub fn convert_virtualfds_to_real(cageid:u64, virtualfds:Vec<u64>) -> (Vec<u64>,
         panic!("Unknown cageid in fdtable access");
     }
 
-    let mut unrealvec = Vec::new();
-    let mut realvec = Vec::new();
-    let mut invalidvec = Vec::new();
     let thefdhm = globfdtable.get(&cageid).unwrap();
-    let mut mappingtable:HashMap<u64,u64> = HashMap::new();
+    let mut mappingtable:HashMap<(u32,u64),u64> = HashMap::new();
+    let mut rethashmap:HashMap<u32,HashSet<(u64,FDTableEntry)>> = HashMap::new();
 
     // BUG?: I'm ignoring the fact that virtualfds can show up multiple times.
     // I'm not sure this actually matters, but I didn't think hard about it.
     for virtfd in virtualfds {
-        match thefdhm.get(&virtfd) {
-            Some(entry) => {
-                // always append the value here.  NO_REAL_FD will be added
-                // in the appropriate places to tell them to handle those calls
-                // themself.
-                realvec.push(entry.realfd);
-                if entry.realfd == NO_REAL_FD {
-                    unrealvec.push((virtfd,entry.optionalinfo));
-                }
-                else{
-                    mappingtable.insert(entry.realfd, virtfd);
-                }
-            }
-            None => {
-                // Add this because they need to handle it if POLLNVAL is set.
-                // An exception should not be raised!!!
-                realvec.push(INVALID_FD);
-                invalidvec.push(virtfd);
-            }
+        if let Some(entry) = thefdhm.get(&virtfd) {
+            // Insert an empty HashSet, if needed
+            rethashmap.entry(entry.fdkind).or_default().insert((virtfd,*entry));
+            mappingtable.insert((entry.fdkind,entry.underfd), virtfd);
+        }
+        else {
+            let myentry = FDTableEntry {
+                fdkind:FDT_INVALID_FD,
+                underfd:virtfd,
+                should_cloexec:false,
+                perfdinfo:u64::from(FDT_INVALID_FD),
+            };
+
+            // Add this because they need to handle it if POLLNVAL is set.
+            // An exception should not be raised!!!
+            rethashmap.entry(FDT_INVALID_FD).or_default().insert((virtfd,myentry));
+
+            // I will add this to the mapping table, because I do think they
+            // may want to raise an exception, etc. based upon this and signal
+            // back.  I am setting the underfd to be the virtfd, so I can
+            // reverse this process, if multiple entries like this occur.
+            mappingtable.insert((FDT_INVALID_FD,virtfd), virtfd);
         }
     }
 
-    (realvec, unrealvec, invalidvec, mappingtable)
+    (rethashmap, mappingtable)
 }
 
 
 
-// helper to call after calling poll.  replaces the realfds the vector
+// helper to call after calling poll.  replaces the fds in the vector
 // with virtual ones...
-#[doc = include_str!("../docs/convert_realfds_back_to_virtual.md")]
-pub fn convert_realfds_back_to_virtual(realfds:Vec<u64>, mappingtable:&HashMap<u64,u64>) -> Vec<u64> {
+#[doc = include_str!("../docs/convert_poll_result_back_to_virtual.md")]
+// I give them the hashmap, so don't need flexibility in what they return...
+#[allow(clippy::implicit_hasher)]
+pub fn convert_poll_result_back_to_virtual(fdkind:u32,underfd:u64, mappingtable:&HashMap<(u32,u64),u64>) -> Option<u64> {
 
     // I don't care what cage was used, and don't need to lock anything...
     // I have the mappingtable!
-
-    let mut virtvec = Vec::new();
-
-    for realfd in realfds {
-        virtvec.push(*mappingtable.get(&realfd).unwrap());
-    }
-
-    virtvec
+    mappingtable.get(&(fdkind,underfd)).copied()
 }
 
 /********************** EPOLL SPECIFIC FUNCTIONS **********************/
 
 
-// Okay this adds a big wrinkle, epollfds.  The reason these are complex is
-// multi-fold:
-// 1) they themselves are file descriptors and take up a slot.
-// 2) a epollfd can point to any number of other fds
+// Supporting epollfds is done by a fdkind which is not set by the user.
+// There are a few complexities here:
+// 1) an epollfd gets a virtual file descriptor
+// 2) a epollfd can point to any number of other fds of different kinds
 // 3) an epollfd can point to epollfds, which can point to other epollfds, etc.
 //    and possibly cause a loop to occur (which is an error)
-// 4) an epollfd can point to a mix of virtual and realfds.
 //
 // My thinking is this is handled as similarly to poll as possible.  We push
 // off the problem of understanding what the event types are to the implementer
@@ -771,24 +823,10 @@ pub fn convert_realfds_back_to_virtual(realfds:Vec<u64>, mappingtable:&HashMap<u
 // types, which they may need to poll themselves.  After this, they handle the
 // call.
 //
-// epoll_create makes a new fd type, which really is unfortunate.  To this
-// point, I haven't had to care about anything except in-memory fds (unreal),
-// and doing the virtual <-> real mappings.  The caller can decide whether to
-// create an underlying epollfd when this is called, when epoll_ctl is called
-// to add a realfd, etc.
-//
-// epoll_ctl is complex, but really has the same fundamental problem as
-// epoll_create: the epollfd.
-//
-// What if I just ignore the epollfd problem by just making another table
-// for epoll information?  Then what I do is set the realfd to EPOLLFD and
-// have optionalinfo point into the epollfd table.  If I do this, then when
-// epoll_create is called, if it contains realfds, those need to be passed
-// down to the underlying epoll_create.  Similarly, when epoll_ctl is called,
-// we either modify our data or return the realfd...
-//
-// Interestingly, this actually would be just as easy to build on top of the
-// fdtables library as into it.
+// I'll create a new fdkind for epoll.  When epoll_create is called, the
+// caller can decide which fdkinds need to be passed down to the underlying
+// epoll_create call(s).  Similarly, when epoll_ctl is called, one either
+// handles the call internally or uses the underfd for the fdkind...
 //
 // Each epollfd will have some virtual fds associated with it.  Each of those
 // will have an event mask.  So I'll have a mutex around an EPollTable struct.
@@ -799,9 +837,21 @@ pub fn convert_realfds_back_to_virtual(realfds:Vec<u64>, mappingtable:&HashMap<u
 // them on systems that don't support epoll and I want to be able to build
 // the code anywhere.  See commonconstants.rs for more info.
 
-// Design notes: I'm not adding realfds.  I return them when you do a epoll_ctl
-// operation that tries to add them.  So, I only have unrealfds in my epoll
-// structures.
+// Lock ordering: EPOLLTABLE is always taken before GLOBALFDTABLE.
+
+
+// a structure that exists for each epoll descriptor to track the underfd(s)
+// and parts the user will handle
+#[derive(Clone, Debug, Default)]
+struct EPollDescriptorInfo {
+    underfdhashmap: HashMap<u32,u64>, // The underfd for a specific fdkind.
+                                      // Used only when an epoll call will
+                                      // call down beneath it.
+    userhandledhashmap: HashMap<u32,HashMap<u64,epoll_event>>,
+                                      // This has all of the things the user
+                                      // will virtualize and handle.  The key
+                                      // is the fdkind.
+}
 
 // TODO: I don't clean up this table yet.  I probably should when the last
 // reference to a fd is closed, but this bookkeeping seems excessive at this
@@ -810,12 +860,7 @@ pub fn convert_realfds_back_to_virtual(realfds:Vec<u64>, mappingtable:&HashMap<u
 struct EPollTable {
     highestneverusedentry: u64, // Never resets (even after close).  Used to
                                 // let us quickly get an unused entry
-    thisepolltable: HashMap<u64,HashMap<u64,epoll_event>>, // the epollentry ->
-                                                           // virtfd ->
-                                                           // event map
-    realfdtable: HashMap<u64,u64>, // the epollentry -> realfd map.  I need 
-                                   // this because the realfd field in the
-                                   // main data structure is EPOLLFD
+    thisepolltable: HashMap<u64,EPollDescriptorInfo>,
 }
 
 lazy_static! {
@@ -823,40 +868,95 @@ lazy_static! {
     #[derive(Debug)]
     static ref EPOLLTABLE: Mutex<EPollTable> = {
         let newetable = HashMap::new();
-        let newrealfdtable = HashMap::new();
         let m = EPollTable {
             highestneverusedentry:0,
-            realfdtable:newrealfdtable,
             thisepolltable:newetable,
         };
         Mutex::new(m)
     };
 }
 
+fn _get_epoll_entrynum_or_error(cageid:u64, epfd:u64) -> Result<u64,threei::RetVal> {
+    // Is the epfd ok?
+    match GLOBALFDTABLE.lock().unwrap().get(&cageid).unwrap().get(&epfd) {
+        None => {
+            Err(threei::Errno::EBADF as u64)
+        },
+        Some(tableentry) => {
+            // You must call this on an epoll fd
+            if tableentry.fdkind == FDT_KINDEPOLL {
+                Ok(tableentry.underfd)
+            }
+            else {
+                Err(threei::Errno::EINVAL as u64)
+            }
+        },
+    }
+}
+
 
-#[doc = include_str!("../docs/epoll_create_helper.md")]
-pub fn epoll_create_helper(cageid:u64, realfd:u64, should_cloexec:bool) -> Result<u64,threei::RetVal> {
+#[doc = include_str!("../docs/epoll_create_empty.md")]
+pub fn epoll_create_empty(cageid:u64, should_cloexec:bool) -> Result<u64,threei::RetVal> {
 
     let mut ept = EPOLLTABLE.lock().unwrap();
 
-    // I'll use my other functions to make this easier.
     // return the same errno (EMFile), if we get one
-    let newepollfd = get_unused_virtual_fd(cageid, EPOLLFD, should_cloexec, ept.highestneverusedentry)?;
+    let newepollfd = get_unused_virtual_fd(cageid, FDT_KINDEPOLL, ept.highestneverusedentry, should_cloexec, 0)?;
 
-    let newentry = ept.highestneverusedentry;
+    let newentrynum = ept.highestneverusedentry;
     ept.highestneverusedentry+=1;
-    // add in my realfd.
-    ept.realfdtable.insert(newentry,realfd);
-    // if it errored out above that is okay. I haven't changed any state yet.
-    ept.thisepolltable.insert(newentry, HashMap::new());
+
+    // Create a new entry with empty values
+    ept.thisepolltable.entry(newentrynum).or_default();
     Ok(newepollfd)
 
 }
 
+#[doc = include_str!("../docs/epoll_add_underfd.md")]
+pub fn epoll_add_underfd(cageid:u64, virtepollfd:u64, fdkind:u32, underfd:u64) -> Result<(),threei::RetVal> {
 
+    if !GLOBALFDTABLE.lock().unwrap().contains_key(&cageid) {
+        panic!("Unknown cageid in fdtable access");
+    }
+
+    let mut ept = EPOLLTABLE.lock().unwrap();
+
+    // get this or error out...
+    let epentrynum = _get_epoll_entrynum_or_error(cageid, virtepollfd)?;
+
+    let myhm = &mut ept.thisepolltable.get_mut(&epentrynum).unwrap().underfdhashmap;
 
-#[doc = include_str!("../docs/try_epoll_ctl.md")]
-pub fn try_epoll_ctl(cageid:u64, epfd:u64, op:i32, virtfd:u64, event:epoll_event) -> Result<(u64,u64),threei::RetVal> {
+    if myhm.contains_key(&fdkind) {
+        panic!("Adding duplicate underfd to epollfd");
+    }
+
+    myhm.insert(fdkind,underfd);
+
+    Ok(())
+
+}
+
+
+#[doc = include_str!("../docs/epoll_get_underfd_hashmap.md")]
+pub fn epoll_get_underfd_hashmap(cageid:u64, virtepollfd:u64) -> Result<HashMap<u32,u64>,threei::RetVal> {
+
+    if !GLOBALFDTABLE.lock().unwrap().contains_key(&cageid) {
+        panic!("Unknown cageid in fdtable access");
+    }
+
+    let ept = EPOLLTABLE.lock().unwrap();
+
+    // get this or error out...
+    let epentrynum = _get_epoll_entrynum_or_error(cageid, virtepollfd)?;
+
+    Ok(ept.thisepolltable.get(&epentrynum).unwrap().underfdhashmap.clone())
+
+}
+
+
+
+#[doc = include_str!("../docs/virtualize_epoll_ctl.md")]
+pub fn virtualize_epoll_ctl(cageid:u64, epfd:u64, op:i32, virtfd:u64, event:epoll_event) -> Result<(),threei::RetVal> {
 
     if !GLOBALFDTABLE.lock().unwrap().contains_key(&cageid) {
         panic!("Unknown cageid in fdtable access");
@@ -865,101 +965,83 @@ pub fn try_epoll_ctl(cageid:u64, epfd:u64, op:i32, virtfd:u64, event:epoll_event
     if epfd == virtfd {
         return Err(threei::Errno::EINVAL as u64);
     }
-    // Is the epfd ok?
-    let epollentrynum:u64 = match GLOBALFDTABLE.lock().unwrap().get(&cageid).unwrap().get(&epfd) {
-        None => {
-            return Err(threei::Errno::EBADF as u64);
-        },
-        // Do I need to have EPOLLFDs here too?
-        Some(tableentry) => {
-            if tableentry.realfd != EPOLLFD {
-                return Err(threei::Errno::EINVAL as u64);
-            }
-            tableentry.optionalinfo
-        },
-    };
-
-    // Okay, I know which table entry and verified the virtfd...
 
-    let mut epttable = EPOLLTABLE.lock().unwrap();
-    let realepollfd = epttable.realfdtable.get(&epollentrynum).unwrap().clone();
-    let eptentry = epttable.thisepolltable.get_mut(&epollentrynum).unwrap();
+    // get this or error out...
+    let epentrynum = _get_epoll_entrynum_or_error(cageid, epfd)?;
 
-    // check if the virtfd is real and error...
-    // I don't care about its contents except to ensure it isn't real...
-    match GLOBALFDTABLE.lock().unwrap().get(&cageid).unwrap().get(&virtfd) {
-        // Do I need to have EPOLLFDs here too?
+    // Okay, I know which table entry, now verify the virtfd...
+    // (the lock on the fdtable is released at the end of the statement, so
+    // I don't hold it while taking EPOLLTABLE below)
+    let virtfdkind:u32 = match GLOBALFDTABLE.lock().unwrap().get(&cageid).unwrap().get(&virtfd) {
         Some(tableentry) => {
-            if tableentry.realfd != NO_REAL_FD {
-                // Return realfds because the caller should handle them instead
-                // I only track unrealfds.
-                if tableentry.realfd == EPOLLFD {
-                    // BUG: How should I be doing this, really!?!
-                    println!("epollfds acting on epollfds is not supported!");
-                }
-                return Ok((realepollfd,tableentry.realfd));
+            // Right now, I don't support this, so error...
+            if tableentry.fdkind == FDT_KINDEPOLL {
+                // TODO: support EPOLLFDs...
+                return Err(threei::Errno::ENOSYS as u64);
             }
+            tableentry.fdkind
         },
         None => {
+            // The virtual Fd doesn't exist -- error...
             return Err(threei::Errno::EBADF as u64);
         },
     };
 
-    // okay, virtfd is real...
+    let mut eptable = EPOLLTABLE.lock().unwrap();
+    let userhm = &mut eptable.thisepolltable.get_mut(&epentrynum).unwrap().userhandledhashmap;
 
     match op {
         EPOLL_CTL_ADD => {
-            if eptentry.contains_key(&virtfd) {
+            let thisuserhm = userhm.entry(virtfdkind).or_default();
+            if thisuserhm.contains_key(&virtfd) {
                 return Err(threei::Errno::EEXIST as u64);
             }
-            // BUG: Need to check for ELOOP here...
+            // BUG: Need to check for ELOOP here once I support EPOLLFDs
+            // referencing each other...
 
-            eptentry.insert(virtfd, event);
+            thisuserhm.insert(virtfd, event);
         },
         EPOLL_CTL_MOD => {
-            if !eptentry.contains_key(&virtfd) {
+            let Some(thisuserhm) = userhm.get_mut(&virtfdkind) else {
+                return Err(threei::Errno::ENOENT as u64);
+            };
+            if !thisuserhm.contains_key(&virtfd) {
                 return Err(threei::Errno::ENOENT as u64);
             }
-            eptentry.insert(virtfd, event);
+            thisuserhm.insert(virtfd, event);
         },
         EPOLL_CTL_DEL => {
-            if !eptentry.contains_key(&virtfd) {
+            let Some(thisuserhm) = userhm.get_mut(&virtfdkind) else {
+                return Err(threei::Errno::ENOENT as u64);
+            };
+            if thisuserhm.remove(&virtfd).is_none() {
                 return Err(threei::Errno::ENOENT as u64);
             }
-            eptentry.remove(&virtfd);
+            // If this was the last entry, delete the key altogether...
+            if thisuserhm.is_empty() {
+                userhm.remove(&virtfdkind);
+            }
         },
         _ => {
             return Err(threei::Errno::EINVAL as u64);
         },
     };
-    Ok((realepollfd,NO_REAL_FD))
+    Ok(())
 }
 
 
-#[doc = include_str!("../docs/get_epoll_wait_data.md")]
-pub fn get_epoll_wait_data(cageid:u64, epfd:u64) -> Result<(u64,HashMap<u64,epoll_event>),threei::RetVal> {
+#[doc = include_str!("../docs/get_virtual_epoll_wait_data.md")]
+pub fn get_virtual_epoll_wait_data(cageid:u64, epfd:u64) -> Result<HashMap<u32,HashMap<u64,epoll_event>>,threei::RetVal> {
 
     if !GLOBALFDTABLE.lock().unwrap().contains_key(&cageid) {
         panic!("Unknown cageid in fdtable access");
     }
 
-    // Note that because I don't track realfds or deal with epollfds, I just
-    // return the epolltable...
-    let epollentrynum:u64 = match GLOBALFDTABLE.lock().unwrap().get(&cageid).unwrap().get(&epfd) {
-        None => {
-            return Err(threei::Errno::EBADF as u64);
-        },
-        // Do I need to have EPOLLFDs here too?
-        Some(tableentry) => {
-            if tableentry.realfd != EPOLLFD {
-                return Err(threei::Errno::EINVAL as u64);
-            }
-            tableentry.optionalinfo
-        },
-    };
+    // get this or error out...
+    let epentrynum = _get_epoll_entrynum_or_error(cageid, epfd)?;
 
-    let epttable = EPOLLTABLE.lock().unwrap();
-    Ok((*epttable.realfdtable.get(&epollentrynum).unwrap(),epttable.thisepolltable[&epollentrynum].clone()))
+    let eptable = EPOLLTABLE.lock().unwrap();
+    Ok(eptable.thisepolltable.get(&epentrynum).unwrap().userhandledhashmap.clone())
 }
 
 
@@ -982,12 +1064,10 @@ pub fn refresh() {
         CLOSEHANDLERTABLE.clear_poison();
         e.into_inner()
     });
-    closehandlers.intermediate_handler = NULL_FUNC;
-    closehandlers.final_handler = NULL_FUNC;
-    closehandlers.unreal_handler = NULL_FUNC;
-    let mut _realfdcount = GLOBALREALFDCOUNT.lock().unwrap_or_else(|e| {
+    closehandlers.clear();
+    let mut _fdcount = GLOBALREALFDCOUNT.lock().unwrap_or_else(|e| {
         GLOBALREALFDCOUNT.clear_poison();
         e.into_inner()
     });
+    reset_fd_limits();
 }
-
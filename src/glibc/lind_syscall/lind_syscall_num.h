@@ -28,12 +28,14 @@
 #define FXSTAT_SYSCALL 17
 #define FTRUNCATE_SYSCALL 18
 #define FSTATFS_SYSCALL 19
+#define MREMAP_SYSCALL 20
 #define MMAP_SYSCALL 21
 #define MUNMAP_SYSCALL 22
 #define GETDENTS_SYSCALL 23
 #define DUP_SYSCALL 24
 #define DUP2_SYSCALL 25
 #define STATFS_SYSCALL 26
+#define MPROTECT_SYSCALL 27
 #define FCNTL_SYSCALL 28
 
 #define GETPPID_SYSCALL 29
@@ -48,6 +50,8 @@
 #define CONNECT_SYSCALL 38
 #define LISTEN_SYSCALL 39
 #define ACCEPT_SYSCALL 40
+#define SENDMSG_SYSCALL 41
+#define RECVMSG_SYSCALL 42
 
 #define GETSOCKOPT_SYSCALL 43
 #define SETSOCKOPT_SYSCALL 44
@@ -80,8 +84,18 @@
 #define COND_TIMEDWAIT_SYSCALL 80
 
 #define SEM_TIMEDWAIT_SYSCALL 94
+#define GETRLIMIT_SYSCALL 97
 #define FUTEX_SYSCALL 98
 
+#define SETUID_SYSCALL 100
+#define SETGID_SYSCALL 101
+#define SETREUID_SYSCALL 102
+#define SETREGID_SYSCALL 103
+#define SETRESUID_SYSCALL 104
+#define SETRESGID_SYSCALL 105
+#define GETRESUID_SYSCALL 106
+#define GETRESGID_SYSCALL 107
+
 #define GETHOSTNAME_SYSCALL 125
 #define PREAD_SYSCALL 126
 #define PWRITE_SYSCALL 127
@@ -101,6 +115,7 @@
 #define SIGPROCMASK_SYSCALL 149
 #define SETITIMER_SYSCALL 150
 
+#define SETRLIMIT_SYSCALL 160
 #define FCHDIR_SYSCALL 161
 #define FSYNC_SYSCALL 162
 #define FDATASYNC_SYSCALL 163
@@ -120,4 +135,9 @@
 #define NANOSLEEP_TIME64_SYSCALL 181
 #define CLOCK_GETTIME_SYSCALL 191
 
+/* 3i calls, made by grates through the syscall table so they can be interposed */
+#define COPY_DATA_BETWEEN_CAGES_SYSCALL 252
+#define COPY_HANDLER_TABLE_TO_CAGE_SYSCALL 253
+#define HARSH_CAGE_EXIT_SYSCALL 254
+
 #endif /* _LIND_SYSCALL_NUM_H */
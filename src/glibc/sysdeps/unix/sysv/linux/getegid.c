@@ -1,9 +1,12 @@
 #include <unistd.h>
+#include <sysdep.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
-__gid_t
+/* Get the effective group ID of the calling process.  */
+gid_t
 __getegid (void)
 {
-  return -1;
+  return MAKE_SYSCALL(GETEGID_SYSCALL, "syscall|getegid", NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
-
-
+weak_alias (__getegid, getegid)
@@ -1,8 +1,12 @@
 #include <unistd.h>
+#include <sysdep.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
-pid_t
+/* Get the effective user ID of the calling process.  */
+uid_t
 __geteuid (void)
 {
-  return 0;
+  return MAKE_SYSCALL(GETEUID_SYSCALL, "syscall|geteuid", NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
-
+weak_alias (__geteuid, geteuid)
@@ -1,13 +1,12 @@
 #include <unistd.h>
+#include <sysdep.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
+/* Get the real group ID of the calling process.  */
 gid_t
 __getgid (void)
 {
-  return -1;
-}
-
-gid_t
-getgid (void)
-{
-  return -1;
+  return MAKE_SYSCALL(GETGID_SYSCALL, "syscall|getgid", NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
+weak_alias (__getgid, getgid)
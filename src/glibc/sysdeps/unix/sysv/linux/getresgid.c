@@ -0,0 +1,14 @@
+#include <unistd.h>
+#include <sysdep.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
+
+/* Fetch the real group ID, effective group ID, and saved-set group ID,
+   of the calling process.  */
+int
+__getresgid (gid_t *rgid, gid_t *egid, gid_t *sgid)
+{
+  return MAKE_SYSCALL(GETRESGID_SYSCALL, "syscall|getresgid", (uint64_t)(uintptr_t) rgid, (uint64_t)(uintptr_t) egid, (uint64_t)(uintptr_t) sgid, NOTUSED, NOTUSED, NOTUSED);
+}
+libc_hidden_def (__getresgid)
+weak_alias (__getresgid, getresgid)
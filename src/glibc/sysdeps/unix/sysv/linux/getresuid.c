@@ -0,0 +1,14 @@
+#include <unistd.h>
+#include <sysdep.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
+
+/* Fetch the real user ID, effective user ID, and saved-set user ID,
+   of the calling process.  */
+int
+__getresuid (uid_t *ruid, uid_t *euid, uid_t *suid)
+{
+  return MAKE_SYSCALL(GETRESUID_SYSCALL, "syscall|getresuid", (uint64_t)(uintptr_t) ruid, (uint64_t)(uintptr_t) euid, (uint64_t)(uintptr_t) suid, NOTUSED, NOTUSED, NOTUSED);
+}
+libc_hidden_def (__getresuid)
+weak_alias (__getresuid, getresuid)
@@ -19,6 +19,7 @@
 #include <sys/resource.h>
 #include <sysdep.h>
 #include <shlib-compat.h>
+#include <syscall-template.h>
 
 #if !__RLIM_T_MATCHES_RLIM64_T
 
@@ -34,7 +35,17 @@
 int
 __new_getrlimit (enum __rlimit_resource resource, struct rlimit *rlim)
 {
-  return INLINE_SYSCALL_CALL (ugetrlimit, resource, rlim);
+  /* Lind only has the 64-bit syscall, limits which don't fit are
+     reported as infinite.  */
+  struct rlimit64 rlim64;
+
+  if (__getrlimit64 (resource, &rlim64) < 0)
+    return -1;
+  rlim->rlim_cur = (rlim64.rlim_cur >= RLIM_INFINITY
+		    ? RLIM_INFINITY : rlim64.rlim_cur);
+  rlim->rlim_max = (rlim64.rlim_max >= RLIM_INFINITY
+		    ? RLIM_INFINITY : rlim64.rlim_max);
+  return 0;
 }
 weak_alias (__new_getrlimit, __getrlimit)
 hidden_weak (__getrlimit)
@@ -20,6 +20,7 @@
 #include <sys/types.h>
 #include <sysdep.h>
 #include <shlib-compat.h>
+#include <syscall-template.h>
 
 /* Add this redirection so the strong_alias for __RLIM_T_MATCHES_RLIM64_T
    linking getrlimit64 to {__}getrlimit does not throw a type error.  */
@@ -36,7 +37,9 @@
 int
 __getrlimit64 (enum __rlimit_resource resource, struct rlimit64 *rlimits)
 {
-  return INLINE_SYSCALL_CALL (prlimit64, 0, resource, NULL, rlimits);
+  /* struct rlimit64 has the same layout as the Rlimit struct in rawposix,
+     so the limits are filled in place.  */
+  return MAKE_SYSCALL(97, "syscall|getrlimit", (uint64_t) resource, (uint64_t)(uintptr_t) rlimits, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
 libc_hidden_def (__getrlimit64)
 
@@ -0,0 +1,12 @@
+#include <unistd.h>
+#include <sysdep.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
+
+/* Get the real user ID of the calling process.  */
+uid_t
+__getuid (void)
+{
+  return MAKE_SYSCALL(GETUID_SYSCALL, "syscall|getuid", NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
+}
+weak_alias (__getuid, getuid)
@@ -70,8 +70,32 @@ __libc_sigaction (int sig, const struct sigaction *act, struct sigaction *oact)
   //   }
   // return result;
 
-   // Dennis Edit
-  return 0;
-  // return MAKE_SYSCALL(147, "syscall|sigaction", (uint64_t) sig, (uint64_t)(uintptr_t) act, (uint64_t)(uintptr_t) oact, NOTUSED, NOTUSED, NOTUSED);
+  /* Layout of the sigaction struct expected by rawposix: the handler is an
+     index in the function table, and only the first 64 signals can be
+     masked.  */
+  struct lind_sigaction
+    {
+      uint32_t sa_handler;
+      uint64_t sa_mask;
+      int32_t sa_flags;
+    } lact, loact;
+
+  if (act)
+    {
+      lact.sa_handler = (uint32_t)(uintptr_t) act->sa_handler;
+      memcpy (&lact.sa_mask, &act->sa_mask, sizeof (lact.sa_mask));
+      lact.sa_flags = act->sa_flags;
+    }
+
+  int result = MAKE_SYSCALL(147, "syscall|sigaction", (uint64_t) sig, (uint64_t)(uintptr_t) (act ? &lact : NULL), (uint64_t)(uintptr_t) (oact ? &loact : NULL), NOTUSED, NOTUSED, NOTUSED);
+
+  if (oact && result >= 0)
+    {
+      oact->sa_handler = (__sighandler_t)(uintptr_t) loact.sa_handler;
+      memset (&oact->sa_mask, 0, sizeof (sigset_t));
+      memcpy (&oact->sa_mask, &loact.sa_mask, sizeof (loact.sa_mask));
+      oact->sa_flags = loact.sa_flags;
+    }
+  return result;
 }
 libc_hidden_def (__libc_sigaction)
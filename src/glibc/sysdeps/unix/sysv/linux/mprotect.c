@@ -1,11 +1,16 @@
-#include <unistd.h>
-#include <sysdep-cancel.h>
+#include <sys/mman.h>
+#include <sysdep.h>
+#include <stdint.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
+/* Change the memory protection of the region starting at ADDR and
+   extending LEN bytes to PROT.  Returns 0 if successful, -1 for errors
+   (and sets errno).  */
 int
-__GI___mprotect (int fd, const void *buf, size_t nbytes)
+__mprotect (void *addr, size_t len, int prot)
 {
-
-  return 0;
+  return MAKE_SYSCALL(MPROTECT_SYSCALL, "syscall|mprotect", (uint64_t)(uintptr_t) addr, (uint64_t) len, (uint64_t) prot, NOTUSED, NOTUSED, NOTUSED);
 }
-
-weak_alias(__GI___mprotect, __mprotect)
\ No newline at end of file
+libc_hidden_def (__mprotect)
+weak_alias (__mprotect, mprotect)
@@ -18,6 +18,9 @@
 
 #include <sys/mman.h>
 #include <sysdep.h>
+#include <stdint.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 #include <stdarg.h>
 #include <stddef.h>
 
@@ -34,8 +37,7 @@ __mremap (void *addr, size_t old_len, size_t new_len, int flags, ...)
       va_end (va);
     }
 
-  return (void *) INLINE_SYSCALL_CALL (mremap, addr, old_len, new_len, flags,
-				       new_addr);
+  return (void *) MAKE_SYSCALL(MREMAP_SYSCALL, "syscall|mremap", (uint64_t)(uintptr_t) addr, (uint64_t) old_len, (uint64_t) new_len, (uint64_t) flags, (uint64_t)(uintptr_t) new_addr, NOTUSED);
 }
 libc_hidden_def (__mremap)
 weak_alias (__mremap, mremap)
@@ -19,15 +19,13 @@
 #include <sys/socket.h>
 #include <sysdep-cancel.h>
 #include <socketcall.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 static int
 __recvmsg_syscall (int fd, struct msghdr *msg, int flags)
 {
-#ifdef __ASSUME_RECVMSG_SYSCALL
-  return SYSCALL_CANCEL (recvmsg, fd, msg, flags);
-#else
-  return SOCKETCALL_CANCEL (recvmsg, fd, msg, flags);
-#endif
+  return MAKE_SYSCALL(RECVMSG_SYSCALL, "syscall|recvmsg", (uint64_t) fd, (uint64_t) msg, (uint64_t) flags, NOTUSED, NOTUSED, NOTUSED);
 }
 
 ssize_t
@@ -17,7 +17,8 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 
 int
@@ -28,11 +29,7 @@ setegid (gid_t gid)
   if (gid == (gid_t) ~0)
     return INLINE_SYSCALL_ERROR_RETURN_VALUE (EINVAL);
 
-#ifdef __NR_setresgid32
-  result = INLINE_SETXID_SYSCALL (setresgid32, 3, -1, gid, -1);
-#else
-  result = INLINE_SETXID_SYSCALL (setresgid, 3, -1, gid, -1);
-#endif
+  result = MAKE_SYSCALL(SETRESGID_SYSCALL, "syscall|setresgid", (uint64_t) -1, (uint64_t) gid, (uint64_t) -1, NOTUSED, NOTUSED, NOTUSED);
 
   return result;
 }
@@ -17,7 +17,8 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 
 int
@@ -28,11 +29,7 @@ seteuid (uid_t uid)
   if (uid == (uid_t) ~0)
     return INLINE_SYSCALL_ERROR_RETURN_VALUE (EINVAL);
 
-#ifdef __NR_setresuid32
-  result = INLINE_SETXID_SYSCALL (setresuid32, 3, -1, uid, -1);
-#else
-  result = INLINE_SETXID_SYSCALL (setresuid, 3, -1, uid, -1);
-#endif
+  result = MAKE_SYSCALL(SETRESUID_SYSCALL, "syscall|setresuid", (uint64_t) -1, (uint64_t) uid, (uint64_t) -1, NOTUSED, NOTUSED, NOTUSED);
 
   return result;
 }
@@ -17,17 +17,14 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 
 int
 __setgid (gid_t gid)
 {
-#ifdef __NR_setgid32
-  return INLINE_SETXID_SYSCALL (setgid32, 1, gid);
-#else
-  return INLINE_SETXID_SYSCALL (setgid, 1, gid);
-#endif
+  return MAKE_SYSCALL(SETGID_SYSCALL, "syscall|setgid", (uint64_t) gid, NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
 #ifndef __setgid
 weak_alias (__setgid, setgid)
@@ -21,42 +21,17 @@
 #include <sys/types.h>
 #include <sysdep.h>
 #include <tv32-compat.h>
+#include <syscall-template.h>
 
 int
 __setitimer64 (__itimer_which_t which,
                const struct __itimerval64 *restrict new_value,
                struct __itimerval64 *restrict old_value)
 {
-#if __KERNEL_OLD_TIMEVAL_MATCHES_TIMEVAL64
-  return INLINE_SYSCALL_CALL (setitimer, which, new_value, old_value);
-#else
-  struct __itimerval32 new_value_32;
-
-  if (! in_int32_t_range (new_value->it_interval.tv_sec)
-      || ! in_int32_t_range (new_value->it_value.tv_sec))
-    {
-      __set_errno (EOVERFLOW);
-      return -1;
-    }
-  new_value_32.it_interval
-    = valid_timeval64_to_timeval32 (new_value->it_interval);
-  new_value_32.it_value
-    = valid_timeval64_to_timeval32 (new_value->it_value);
-
-  if (old_value == NULL)
-    return INLINE_SYSCALL_CALL (setitimer, which, &new_value_32, NULL);
-
-  struct __itimerval32 old_value_32;
-  if (INLINE_SYSCALL_CALL (setitimer, which, &new_value_32, &old_value_32)
-      == -1)
-    return -1;
-
-  old_value->it_interval
-     = valid_timeval32_to_timeval64 (old_value_32.it_interval);
-  old_value->it_value
-     = valid_timeval32_to_timeval64 (old_value_32.it_value);
-  return 0;
-#endif
+  /* Lind only supports ITIMER_REAL. struct __itimerval64 has the same
+     layout as the timer struct in rawposix (two 64-bit fields per timeval),
+     so the values are passed through without conversion.  */
+  return MAKE_SYSCALL(150, "syscall|setitimer", (uint64_t) which, (uint64_t)(uintptr_t) new_value, (uint64_t)(uintptr_t) old_value, NOTUSED, NOTUSED, NOTUSED);
 }
 
 #if __TIMESIZE != 64
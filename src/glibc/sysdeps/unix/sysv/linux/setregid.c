@@ -17,17 +17,14 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 
 int
 __setregid (gid_t rgid, gid_t egid)
 {
-#ifdef __NR_setregid32
-  return INLINE_SETXID_SYSCALL (setregid32, 2, rgid, egid);
-#else
-  return INLINE_SETXID_SYSCALL (setregid, 2, rgid, egid);
-#endif
+  return MAKE_SYSCALL(SETREGID_SYSCALL, "syscall|setregid", (uint64_t) rgid, (uint64_t) egid, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
 #ifndef __setregid
 weak_alias (__setregid, setregid)
@@ -17,17 +17,14 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 
 int
 __setresgid (gid_t rgid, gid_t egid, gid_t sgid)
 {
-#ifdef __NR_setresgid32
-  return INLINE_SETXID_SYSCALL (setresgid32, 3, rgid, egid, sgid);
-#else
-  return INLINE_SETXID_SYSCALL (setresgid, 3, rgid, egid, sgid);
-#endif
+  return MAKE_SYSCALL(SETRESGID_SYSCALL, "syscall|setresgid", (uint64_t) rgid, (uint64_t) egid, (uint64_t) sgid, NOTUSED, NOTUSED, NOTUSED);
 }
 libc_hidden_def (__setresgid)
 #ifndef __setresgid
@@ -17,17 +17,14 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 
 int
 __setresuid (uid_t ruid, uid_t euid, uid_t suid)
 {
-#ifdef __NR_setresuid32
-  return INLINE_SETXID_SYSCALL (setresuid32, 3, ruid, euid, suid);
-#else
-  return INLINE_SETXID_SYSCALL (setresuid, 3, ruid, euid, suid);
-#endif
+  return MAKE_SYSCALL(SETRESUID_SYSCALL, "syscall|setresuid", (uint64_t) ruid, (uint64_t) euid, (uint64_t) suid, NOTUSED, NOTUSED, NOTUSED);
 }
 libc_hidden_def (__setresuid)
 #ifndef __setresuid
@@ -17,17 +17,14 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 
 int
 __setreuid (uid_t ruid, uid_t euid)
 {
-#ifdef __NR_setreuid32
-  return INLINE_SETXID_SYSCALL (setreuid32, 2, ruid, euid);
-#else
-  return INLINE_SETXID_SYSCALL (setreuid, 2, ruid, euid);
-#endif
+  return MAKE_SYSCALL(SETREUID_SYSCALL, "syscall|setreuid", (uint64_t) ruid, (uint64_t) euid, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
 #ifndef __setreuid
 weak_alias (__setreuid, setreuid)
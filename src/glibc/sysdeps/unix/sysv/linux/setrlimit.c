@@ -19,6 +19,7 @@
 #include <sys/resource.h>
 #include <sysdep.h>
 #include <shlib-compat.h>
+#include <syscall-template.h>
 
 #if !__RLIM_T_MATCHES_RLIM64_T
 
@@ -44,7 +45,7 @@ __setrlimit (enum __rlimit_resource resource, const struct rlimit *rlim)
   else
     rlim64.rlim_max = rlim->rlim_max;
 
-  return INLINE_SYSCALL_CALL (prlimit64, 0, resource, &rlim64, NULL);
+  return MAKE_SYSCALL(160, "syscall|setrlimit", (uint64_t) resource, (uint64_t)(uintptr_t) &rlim64, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
 
 libc_hidden_def (__setrlimit)
@@ -17,16 +17,13 @@
 
 #include <errno.h>
 #include <unistd.h>
-#include <setxid.h>
+#include <syscall-template.h>
+#include <lind_syscall_num.h>
 
 int
 __setuid (uid_t uid)
 {
-#ifdef __NR_setuid32
-  return INLINE_SETXID_SYSCALL (setuid32, 1, uid);
-#else
-  return INLINE_SETXID_SYSCALL (setuid, 1, uid);
-#endif
+  return MAKE_SYSCALL(SETUID_SYSCALL, "syscall|setuid", (uint64_t) uid, NOTUSED, NOTUSED, NOTUSED, NOTUSED, NOTUSED);
 }
 #ifndef __setuid
 weak_alias (__setuid, setuid)
@@ -0,0 +1,147 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use cage::memory::mem_helper::{fork_vmmap_helper, init_vmmap_helper};
+use cage::{get_cage, remove_cage};
+use common::{init_test_cage, reserve_memory, INIT_CAGEID};
+use rawposix::syscalls::sys_calls::fork_syscall;
+use std::time::{Duration, Instant};
+use sysdefs::constants::fs_const::{MAX_CAGEID, PAGESHIFT, PAGESIZE, PROT_READ, PROT_WRITE};
+
+/// Heap sizes of the forked cages, in MiB
+const HEAP_SIZES: [usize; 4] = [1, 16, 64, 256];
+/// Pages the parent writes to between two forks
+const DIRTY_PAGES: usize = 16;
+/// Cage id of the children, which are removed once forked so the id can be reused. It is well above the
+/// ids `common` hands out to the parents.
+const CHILD_CAGEID: u64 = MAX_CAGEID as u64 - 1;
+
+/// Forks a cage from the init cage, with a heap of `heap_pages` pages that are all written to. Returns
+/// the cage id and the host base address of the cage memory.
+fn init_bench_cage(heap_pages: u32) -> (u64, *mut u8) {
+    let (cageid, base) = init_test_cage(INIT_CAGEID, heap_pages);
+    unsafe { std::ptr::write_bytes(base, 1, (heap_pages as usize) << PAGESHIFT) };
+    (cageid, base)
+}
+
+/// Forks `parentid` like wasmtime does, and returns the time `copy_memory` takes to copy the parent
+/// memory into the child
+fn time_fork(parentid: u64, heap_pages: u32, copy_memory: fn(u64, u64)) -> Duration {
+    assert_eq!(
+        fork_syscall(
+            parentid,
+            CHILD_CAGEID,
+            parentid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    let base = reserve_memory(heap_pages);
+    init_vmmap_helper(CHILD_CAGEID, base as usize, None);
+
+    let start = Instant::now();
+    copy_memory(parentid, CHILD_CAGEID);
+    let elapsed = start.elapsed();
+
+    remove_cage(CHILD_CAGEID);
+    fdtables::remove_cage_from_fdtable(CHILD_CAGEID);
+    unsafe {
+        libc::munmap(
+            base as *mut libc::c_void,
+            (heap_pages as usize) << PAGESHIFT,
+        )
+    };
+    elapsed
+}
+
+/// Copies the private memory of the parent the way fork did before memory snapshots, with one
+/// `copy_nonoverlapping` per region
+fn eager_copy(parentid: u64, childid: u64) {
+    let parent = get_cage(parentid).unwrap();
+    let child = get_cage(childid).unwrap();
+    let parent_vmmap = parent.vmmap.read();
+    let child_vmmap = child.vmmap.read();
+    for (_interval, entry) in parent_vmmap.entries.iter() {
+        let addr_st = entry.page_num << PAGESHIFT;
+        let addr_len = (entry.npages << PAGESHIFT) as usize;
+        let parent_st = parent_vmmap.user_to_sys(addr_st);
+        let child_st = child_vmmap.user_to_sys(addr_st);
+        unsafe {
+            libc::mprotect(
+                child_st as *mut libc::c_void,
+                addr_len,
+                PROT_READ | PROT_WRITE,
+            );
+            std::ptr::copy_nonoverlapping(parent_st as *const u8, child_st as *mut u8, addr_len);
+            libc::mprotect(child_st as *mut libc::c_void, addr_len, entry.prot);
+        }
+    }
+}
+
+/// Fork latency when the whole heap is copied, as fork did before memory snapshots
+fn benchmark_fork_eager_copy(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fork_eager_copy");
+    group.sample_size(20);
+
+    for &size in HEAP_SIZES.iter() {
+        let heap_pages = ((size << 20) / PAGESIZE as usize) as u32;
+        let (parentid, _) = init_bench_cage(heap_pages);
+
+        group.throughput(Throughput::Bytes((size << 20) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| time_fork(parentid, heap_pages, eager_copy))
+                    .sum()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+/// Fork latency when the heap is mapped from a snapshot, and the parent wrote to `DIRTY_PAGES` pages
+/// since its previous fork
+fn benchmark_fork_snapshot(c: &mut Criterion) {
+    let mut group = c.benchmark_group("fork_snapshot");
+    group.sample_size(20);
+
+    for &size in HEAP_SIZES.iter() {
+        let heap_pages = ((size << 20) / PAGESIZE as usize) as u32;
+        let (parentid, base) = init_bench_cage(heap_pages);
+        // the first fork takes the snapshot
+        time_fork(parentid, heap_pages, fork_vmmap_helper);
+
+        group.throughput(Throughput::Bytes((size << 20) as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, _| {
+            let mut next_page = 0;
+            b.iter_custom(|iters| {
+                (0..iters)
+                    .map(|_| {
+                        for _ in 0..DIRTY_PAGES {
+                            unsafe { *base.add(next_page << PAGESHIFT) += 1 };
+                            next_page = (next_page + 1) % heap_pages as usize;
+                        }
+                        time_fork(parentid, heap_pages, fork_vmmap_helper)
+                    })
+                    .sum()
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_fork_eager_copy, benchmark_fork_snapshot);
+criterion_main!(benches);
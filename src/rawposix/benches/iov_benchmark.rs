@@ -0,0 +1,114 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{init_test_cage, store, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{write_syscall, writev_syscall};
+use std::os::fd::IntoRawFd;
+use sysdefs::constants::err_const::VERBOSE;
+use sysdefs::constants::fs_const::{FDKIND_KERNEL, PAGESIZE};
+use sysdefs::data::fs_struct::WasmIovec;
+
+/// Size of the linear memory of the cage
+const MEMORY_PAGES: u32 = 64;
+/// Size of every buffer written, in bytes
+const SEGMENT_SIZE: u32 = 64;
+/// Guest address of the iovec array
+const IOV_ADDR: u64 = 64;
+
+/// Sets up a cage with a linear memory and a virtual fd pointing to /dev/null. Returns the cage id, the
+/// virtual fd and the host base address of the cage memory.
+fn init_bench_cage() -> (u64, u64, *mut u8) {
+    let _ = VERBOSE.set(0);
+    let (cageid, base) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+
+    let devnull = std::fs::OpenOptions::new()
+        .write(true)
+        .open("/dev/null")
+        .unwrap();
+    let fd = fdtables::get_unused_virtual_fd(
+        cageid,
+        FDKIND_KERNEL,
+        devnull.into_raw_fd() as u64,
+        false,
+        0,
+    )
+    .unwrap();
+
+    (cageid, fd, base)
+}
+
+/// Writes `nsegs` iovec entries at `IOV_ADDR`, pointing to consecutive buffers that start at the
+/// second page.
+fn fill_iovecs(base: *mut u8, nsegs: u32) {
+    for i in 0..nsegs {
+        store(
+            base,
+            IOV_ADDR + (i as usize * std::mem::size_of::<WasmIovec>()) as u64,
+            WasmIovec {
+                iov_base: PAGESIZE + i * SEGMENT_SIZE,
+                iov_len: SEGMENT_SIZE,
+            },
+        );
+    }
+}
+
+/// writev of N segments vs N calls to write_syscall, both writing to /dev/null
+fn benchmark_writev(c: &mut Criterion) {
+    let (cageid, fd, base) = init_bench_cage();
+    let mut group = c.benchmark_group("writev_vs_write");
+
+    for nsegs in [1u32, 4, 16, 64] {
+        fill_iovecs(base, nsegs);
+
+        group.bench_with_input(BenchmarkId::new("writev", nsegs), &nsegs, |b, &nsegs| {
+            b.iter(|| {
+                let ret = writev_syscall(
+                    cageid,
+                    fd,
+                    cageid,
+                    IOV_ADDR,
+                    cageid,
+                    nsegs as u64,
+                    cageid,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                );
+                assert_eq!(ret, (nsegs * SEGMENT_SIZE) as i32);
+            });
+        });
+
+        group.bench_with_input(BenchmarkId::new("write_loop", nsegs), &nsegs, |b, &nsegs| {
+            b.iter(|| {
+                for i in 0..nsegs {
+                    let ret = write_syscall(
+                        cageid,
+                        fd,
+                        cageid,
+                        (PAGESIZE + i * SEGMENT_SIZE) as u64,
+                        cageid,
+                        SEGMENT_SIZE as u64,
+                        cageid,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                    );
+                    assert_eq!(ret, SEGMENT_SIZE as i32);
+                }
+            });
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_writev);
+criterion_main!(benches);
@@ -0,0 +1,86 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+#[path = "../tests/common/mod.rs"]
+mod common;
+
+use common::{init_test_cage, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{pipe_syscall, read_syscall, write_syscall};
+use rawposix::syscalls::pipe::set_inmemory_pipes;
+use sysdefs::constants::err_const::VERBOSE;
+use sysdefs::constants::fs_const::PAGESIZE;
+use sysdefs::data::fs_struct::PipeArray;
+
+/// Size of the linear memory of the cage
+const MEMORY_PAGES: u32 = 64;
+/// Guest address of the buffer written to and read from the pipes, after the first page
+const BUF_ADDR: u32 = PAGESIZE;
+
+/// Creates a pipe, in memory or on the host kernel, and returns its read and write fds
+fn create_pipe(cageid: u64, base: *mut u8, inmemory: bool) -> (u64, u64) {
+    set_inmemory_pipes(inmemory);
+    // pipe() takes a host pointer to the fd array
+    let pipefd = base as *mut PipeArray;
+    assert_eq!(
+        pipe_syscall(cageid, pipefd as u64, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    let fds = unsafe { &*pipefd };
+    (fds.readfd as u64, fds.writefd as u64)
+}
+
+/// A write of `size` bytes followed by a read of the same bytes, through an in-memory pipe vs a
+/// host kernel pipe
+fn benchmark_pipe_roundtrip(c: &mut Criterion) {
+    let _ = VERBOSE.set(0);
+    let (cageid, base) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+    let mut group = c.benchmark_group("pipe_write_read");
+
+    for (name, inmemory) in [("inmemory", true), ("kernel", false)] {
+        let (readfd, writefd) = create_pipe(cageid, base, inmemory);
+
+        for size in [64u64, 1024, 4096, 32768] {
+            group.throughput(Throughput::Bytes(size));
+            group.bench_with_input(BenchmarkId::new(name, size), &size, |b, &size| {
+                b.iter(|| {
+                    let ret = write_syscall(
+                        cageid,
+                        writefd,
+                        cageid,
+                        BUF_ADDR as u64,
+                        cageid,
+                        size,
+                        cageid,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                    );
+                    assert_eq!(ret, size as i32);
+                    let ret = read_syscall(
+                        cageid,
+                        readfd,
+                        cageid,
+                        BUF_ADDR as u64,
+                        cageid,
+                        size,
+                        cageid,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                        0,
+                    );
+                    assert_eq!(ret, size as i32);
+                });
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, benchmark_pipe_roundtrip);
+criterion_main!(benches);
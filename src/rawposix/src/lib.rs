@@ -1,3 +1,6 @@
 pub mod syscalls;
 
-pub use syscalls::{lindrustfinalize, lindrustinit};
+pub use syscalls::{
+    lindrustfinalize, lindrustinit, set_initial_credentials, set_initial_memory_limits,
+    set_inmemory_pipes, set_loopback_policy, LoopbackPolicy,
+};
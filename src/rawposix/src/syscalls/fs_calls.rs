@@ -3,21 +3,35 @@
 //! This file provides all system related syscall implementation in RawPOSIX
 use cage::get_cage;
 use cage::memory::mem_helper::*;
+use cage::memory::shm::SHM_TABLE;
 use cage::memory::vmmap::{VmmapOps, *};
 use fdtables;
 use libc::*;
 use parking_lot::RwLock;
-use std::sync::atomic::{AtomicI32, AtomicU64};
+use std::ffi::CString;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicI32, AtomicU64, Ordering};
 use std::sync::Arc;
 use sysdefs::constants::err_const::{get_errno, handle_errno, syscall_error, Errno};
-use sysdefs::constants::sys_const::DEFAULT_GID;
+use sysdefs::data::fs_struct::{
+    ClippedDirent, FSData, FlockStruct, ShmidsStruct, StatData, WasmIovec, WinSize,
+    CLIPPED_DIRENT_SIZE,
+};
 use sysdefs::constants::fs_const;
 use sysdefs::constants::fs_const::{
-    F_GETFL, F_GETOWN, F_SETOWN, MAP_ANONYMOUS, MAP_FAILED, MAP_FIXED, MAP_PRIVATE, MAP_SHARED,
-    PAGESHIFT, PAGESIZE, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE, MAXFD, 
+    F_DUPFD_CLOEXEC, F_GETFL, F_GETLK, F_GETOWN, F_RDLCK, F_SETFL, F_SETLK, F_SETLKW, F_SETOWN,
+    F_UNLCK, F_WRLCK, FIOCLEX, FIONBIO, FIONCLEX, FIONREAD, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN,
+    TIOCGWINSZ, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MAP_SHARED, MREMAP_FIXED,
+    MREMAP_MAYMOVE, PAGESHIFT, PAGESIZE, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE, MAXFD, PATH_MAX,
+    IPC_CREAT, IPC_EXCL, IPC_PRIVATE, IPC_RMID, IPC_SET, IPC_STAT, SHMMAX, SHMMIN, SHM_RDONLY, SHM_RND,
 };
+use typemap::path_conv::{add_lind_root, strip_lind_root};
 use typemap::syscall_conv::*;
 use typemap::type_conv::get_pipearray;
+use crate::syscalls::locks;
+use crate::syscalls::loopback;
+use crate::syscalls::pipe;
+use crate::syscalls::sys_calls::initial_credentials;
 
 /// Lind-WASM is running as same Linux-Process from host kernel perspective, so standard fds shouldn't
 /// be closed in Lind-WASM execution, which preventing issues where other threads might reassign these
@@ -25,6 +39,7 @@ use typemap::type_conv::get_pipearray;
 pub fn kernel_close(fdentry: fdtables::FDTableEntry, _count: u64) {
     let kernel_fd = fdentry.underfd as i32;
     println!("kernel close: {}", kernel_fd);
+    locks::close_description(fdentry.fdkind, fdentry.underfd);
     // TODO:
     // Need to update once we merge with vmmap-alice
     if kernel_fd == STDIN_FILENO || kernel_fd == STDOUT_FILENO || kernel_fd == STDERR_FILENO {
@@ -102,7 +117,7 @@ pub fn open_syscall(
         0,
     ) {
         Ok(virtual_fd) => virtual_fd as i32,
-        Err(_) => syscall_error(Errno::EMFILE, "open_syscall", "Too many files opened"),
+        Err(e) => syscall_error(_fd_alloc_errno(e), "open_syscall", "Too many files opened"),
     }
 }
 
@@ -133,6 +148,24 @@ pub fn read_syscall(
     arg6: u64,
     arg6_cageid: u64,
 ) -> i32 {
+    // In-memory sockets have no kernel fd, reading one is receiving from it without flags
+    if let Some(sockid) = loopback::loopback_id(virtual_fd, vfd_cageid) {
+        let buf = sc_convert_buf(buf_arg, buf_cageid, cageid);
+        let count = sc_convert_sysarg_to_usize(count_arg, count_cageid, cageid);
+        let data = unsafe { std::slice::from_raw_parts_mut(buf as *mut u8, count) };
+        return match loopback::recv(cageid, sockid, data, 0) {
+            Ok((len, _, _, _)) => len as i32,
+            Err(e) => e,
+        };
+    }
+
+    if let Some(pipeend) = pipe::pipe_end(virtual_fd, vfd_cageid) {
+        let buf = sc_convert_buf(buf_arg, buf_cageid, cageid);
+        let count = sc_convert_sysarg_to_usize(count_arg, count_cageid, cageid);
+        let data = unsafe { std::slice::from_raw_parts_mut(buf as *mut u8, count) };
+        return pipe::read(cageid, pipeend, data);
+    }
+
     // Convert the virtual fd to the underlying kernel file descriptor.
     let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
     if kernel_fd == -1 {
@@ -210,6 +243,17 @@ pub fn close_syscall(
         return syscall_error(Errno::EBADF, "close", "Bad File Descriptor");
     }
 
+    // Closing any fd of a file drops every record lock the cage holds on it
+    if locks::holds_record_locks(cageid) {
+        if let Ok(vfd) = fdtables::translate_virtual_fd(cageid, virtual_fd) {
+            if vfd.fdkind == fs_const::FDKIND_KERNEL {
+                if let Ok(file) = _lock_file_id(vfd.underfd as i32) {
+                    locks::release_record_locks(cageid, Some(file));
+                }
+            }
+        }
+    }
+
     match fdtables::close_virtualfd(cageid, virtual_fd) {
         Ok(()) => 0,
         Err(e) => {
@@ -317,6 +361,17 @@ pub fn pipe2_syscall(
         Ok(p) => p,
         Err(e) => return e,
     };
+    // In-memory pipes don't need a kernel pipe
+    if pipe::inmemory_pipes() {
+        return match pipe::pipe2(cageid, flags) {
+            Ok([read_vfd, write_vfd]) => {
+                pipefd.readfd = read_vfd;
+                pipefd.writefd = write_vfd;
+                0
+            }
+            Err(e) => e,
+        };
+    }
     // Create an array to hold the two kernel file descriptors.
     let mut kernel_fds: [i32; 2] = [0; 2];
     let ret = unsafe { libc::pipe2(kernel_fds.as_mut_ptr(), flags) };
@@ -336,12 +391,12 @@ pub fn pipe2_syscall(
         0,
     ) {
         Ok(fd) => fd as i32,
-        Err(_) => {
+        Err(e) => {
             unsafe {
                 libc::close(kernel_fds[0]);
                 libc::close(kernel_fds[1]);
             }
-            return syscall_error(Errno::EMFILE, "pipe2_syscall", "Too many files opened");
+            return syscall_error(_fd_alloc_errno(e), "pipe2_syscall", "Too many files opened");
         }
     };
 
@@ -354,12 +409,12 @@ pub fn pipe2_syscall(
         0,
     ) {
         Ok(fd) => fd as i32,
-        Err(_) => {
+        Err(e) => {
             unsafe {
                 libc::close(kernel_fds[0]);
                 libc::close(kernel_fds[1]);
             }
-            return syscall_error(Errno::EMFILE, "pipe2_syscall", "Too many files opened");
+            return syscall_error(_fd_alloc_errno(e), "pipe2_syscall", "Too many files opened");
         }
     };
 
@@ -451,6 +506,20 @@ pub fn write_syscall(
     arg6: u64,
     arg6_cageid: u64,
 ) -> i32 {
+    // In-memory sockets have no kernel fd, writing one is sending on it without flags
+    if let Some(sockid) = loopback::loopback_id(virtual_fd, vfd_cageid) {
+        let buf = sc_convert_buf(buf_arg, buf_cageid, cageid);
+        let count = sc_convert_sysarg_to_usize(count_arg, count_cageid, cageid);
+        let data = unsafe { std::slice::from_raw_parts(buf, count) };
+        return loopback::send(cageid, sockid, data, 0, std::ptr::null());
+    }
+    if let Some(pipeend) = pipe::pipe_end(virtual_fd, vfd_cageid) {
+        let buf = sc_convert_buf(buf_arg, buf_cageid, cageid);
+        let count = sc_convert_sysarg_to_usize(count_arg, count_cageid, cageid);
+        let data = unsafe { std::slice::from_raw_parts(buf, count) };
+        return pipe::write(cageid, pipeend, data);
+    }
+
     let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
 
     if kernel_fd == -1 {
@@ -515,10 +584,30 @@ pub fn dup_syscall(
         return syscall_error(Errno::EBADF, "dup", "Bad File Descriptor");
     }
     let vfd = wrappedvfd.unwrap();
-    let ret_kernelfd = unsafe { libc::dup(vfd.underfd as i32) };
-    let ret_virtualfd =
-        fdtables::get_unused_virtual_fd(cageid, vfd.fdkind, ret_kernelfd as u64, false, 0).unwrap();
-    return ret_virtualfd as i32;
+    // In-memory sockets and pipes have no kernel fd to duplicate, both fds refer to the same one
+    let inmemory =
+        vfd.fdkind == fs_const::FDKIND_LOOPBACK || vfd.fdkind == fs_const::FDKIND_PIPE;
+    let ret_kernelfd = if inmemory {
+        vfd.underfd as i32
+    } else {
+        unsafe { libc::dup(vfd.underfd as i32) }
+    };
+    match fdtables::get_unused_virtual_fd(cageid, vfd.fdkind, ret_kernelfd as u64, false, 0) {
+        Ok(ret_virtualfd) => {
+            if !inmemory {
+                locks::dup_description(vfd.fdkind, vfd.underfd, ret_kernelfd as u64);
+            }
+            ret_virtualfd as i32
+        }
+        Err(e) => {
+            if !inmemory {
+                unsafe {
+                    libc::close(ret_kernelfd);
+                }
+            }
+            syscall_error(_fd_alloc_errno(e), "dup", "Too many files opened")
+        }
+    }
 }
 
 pub fn dup2_syscall(
@@ -550,10 +639,27 @@ pub fn dup2_syscall(
     }
 
     match fdtables::translate_virtual_fd(cageid, old_virtualfd) {
+        Ok(old_vfd)
+            if old_vfd.fdkind == fs_const::FDKIND_LOOPBACK
+                || old_vfd.fdkind == fs_const::FDKIND_PIPE =>
+        {
+            // In-memory sockets and pipes have no kernel fd to duplicate, both fds refer to the same one
+            let _ = fdtables::get_specific_virtual_fd(
+                cageid,
+                new_virtualfd,
+                old_vfd.fdkind,
+                old_vfd.underfd,
+                false,
+                old_vfd.perfdinfo,
+            )
+            .unwrap();
+            return new_virtualfd as i32;
+        }
         Ok(old_vfd) => {
             let new_kernelfd = unsafe { libc::dup(old_vfd.underfd as i32) };
             // Map new kernel fd with provided kernel fd
             let _ret_kernelfd = unsafe { libc::dup2(old_vfd.underfd as i32, new_kernelfd) };
+            locks::dup_description(old_vfd.fdkind, old_vfd.underfd, new_kernelfd as u64);
             let _ = fdtables::get_specific_virtual_fd(
                 cageid,
                 new_virtualfd,
@@ -579,8 +685,10 @@ pub fn dup2_syscall(
 /// 2. Disallow `PROT_EXEC`; return `EINVAL` if the `prot` argument includes `PROT_EXEC`.
 /// 3. If `MAP_FIXED` is not specified, query the `vmmap` structure to locate an available memory region.
 ///    Otherwise, use the address provided by the user.
-/// 4. Invoke the actual `mmap` syscall with the `MAP_FIXED` flag to configure the memory region's protections.
-/// 5. Update the corresponding `vmmap` entry.
+/// 4. Check that the new pages keep the cage within its RLIMIT_AS and RLIMIT_DATA limits, and all the cages
+///    within the global memory limit; return `ENOMEM` otherwise.
+/// 5. Invoke the actual `mmap` syscall with the `MAP_FIXED` flag to configure the memory region's protections.
+/// 6. Update the corresponding `vmmap` entry.
 ///
 /// # Arguments
 /// * `cageid` - Identifier of the cage that initiated the `mmap` syscall.
@@ -617,7 +725,7 @@ pub fn mmap_syscall(
     let mut len = sc_convert_sysarg_to_usize(len_arg, len_cageid, cageid);
     let mut prot = sc_convert_sysarg_to_i32(prot_arg, prot_cageid, cageid);
     let mut flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
-    let mut fildes = convert_fd_to_host(virtual_fd_arg, vfd_cageid, cageid);
+    let fildes = convert_fd_to_host(virtual_fd_arg, vfd_cageid, cageid);
     let mut off = sc_convert_sysarg_to_i64(off_arg, off_cageid, cageid);
 
     let cage = get_cage(cageid).unwrap();
@@ -692,73 +800,73 @@ pub fn mmap_syscall(
     drop(vmmap);
 
     if rounded_length > 0 {
-        if flags & MAP_ANONYMOUS as i32 > 0 {
-            fildes = -1;
+        let anonymous = flags & MAP_ANONYMOUS as i32 > 0;
+        if !anonymous {
+            if fildes < 0 {
+                return syscall_error(Errno::EBADF, "mmap", "Bad File Descriptor");
+            }
+            // the maximum protection of a file-backed mapping depends on the access mode of the file: a
+            // shared mapping can only be made writable if the file is open for reading and writing
+            let fdflags = unsafe { libc::fcntl(fildes, F_GETFL) };
+            if fdflags < 0 {
+                let errno = get_errno();
+                return handle_errno(errno, "mmap");
+            }
+            if flags & MAP_SHARED as i32 > 0 && fdflags & O_ACCMODE != O_RDWR {
+                maxprot &= !PROT_WRITE;
+            }
         }
 
+        let backing = if anonymous {
+            MemoryBackingType::Anonymous
+        } else {
+            MemoryBackingType::FileDescriptor(virtual_fd_arg)
+        };
+
+        // the vmmap stays locked until the new entry is added, so that the memory limits of the cage
+        // are checked against the pages it has committed
+        let mut vmmap = cage.vmmap.write();
+        let _reservation = match vmmap.reserve_memory(
+            useraddr >> PAGESHIFT,
+            (rounded_length >> PAGESHIFT) as u32,
+            backing,
+        ) {
+            Ok(reservation) => reservation,
+            Err(_) => {
+                return syscall_error(Errno::ENOMEM, "mmap", "memory limit exceeded");
+            }
+        };
+
         let result = mmap_inner(
             cageid,
             sysaddr as *mut u8,
             rounded_length as usize,
             prot,
             flags,
-            fildes,
+            if anonymous { -1 } else { virtual_fd_arg as i32 },
             off,
         );
+        // mmap_inner returns a negative errno on failure
+        if (result as isize) < 0 {
+            return result as i32;
+        }
 
-        let vmmap = cage.vmmap.read();
-        let result = vmmap.sys_to_user(result);
-        drop(vmmap);
-
-        // if mmap addr is positive, that would mean the mapping is successful and we need to update the vmmap entry
-        if result >= 0 {
-            if result != useraddr {
-                panic!("MAP_FIXED not fixed");
-            }
-
-            let mut vmmap = cage.vmmap.write();
-            let backing = {
-                if flags as u32 & MAP_ANONYMOUS > 0 {
-                    MemoryBackingType::Anonymous
-                } else {
-                    // if we are doing file-backed mapping, we need to set maxprot to the file permission
-                    let flags = fcntl_syscall(
-                        cageid,
-                        fildes as u64,
-                        vfd_cageid,
-                        F_GETFL as u64,
-                        flags_cageid,
-                        0,
-                        0,
-                        0,
-                        0,
-                        0,
-                        0,
-                        0,
-                        0,
-                    );
-                    if flags < 0 {
-                        return syscall_error(Errno::EINVAL, "mmap", "invalid file descriptor")
-                            as i32;
-                    }
-                    maxprot &= flags;
-                    MemoryBackingType::FileDescriptor(fildes as u64)
-                }
-            };
-
-            // update vmmap entry
-            let _ = vmmap.add_entry_with_overwrite(
-                useraddr >> PAGESHIFT,
-                (rounded_length >> PAGESHIFT) as u32,
-                prot,
-                maxprot,
-                flags,
-                backing,
-                off,
-                len as i64,
-                cageid,
-            );
+        if vmmap.sys_to_user(result) != useraddr {
+            panic!("MAP_FIXED not fixed");
         }
+
+        // update vmmap entry
+        let _ = vmmap.add_entry_with_overwrite(
+            useraddr >> PAGESHIFT,
+            (rounded_length >> PAGESHIFT) as u32,
+            prot,
+            maxprot,
+            flags,
+            backing,
+            off,
+            len as i64,
+            cageid,
+        );
     }
 
     useraddr as i32
@@ -820,8 +928,9 @@ pub fn mmap_inner(
 ///
 /// This function processes the `munmap_syscall` by updating the `vmmap` entries and managing
 /// the unmap operation. Instead of invoking the actual `munmap` syscall, the unmap operation
-/// is simulated by setting the specified region to `PROT_NONE`. The memory remains valid but
-/// becomes inaccessible due to the `PROT_NONE` setting.
+/// is simulated by releasing the pages of the region (see `release_pages`) and setting it to
+/// `PROT_NONE`. The memory remains valid but becomes inaccessible due to the `PROT_NONE` setting.
+/// The length is rounded up to a multiple of pages, like the range removed from `vmmap`.
 ///
 /// # Arguments
 /// * `cageid` - Identifier of the cage that calls the `munmap`
@@ -867,34 +976,347 @@ pub fn munmap_syscall(
         return syscall_error(Errno::EINVAL, "munmap", "address it not aligned");
     }
 
-    let vmmap = cage.vmmap.read();
-    let sysaddr = vmmap.user_to_sys(rounded_addr as u32);
-    drop(vmmap);
-
     let rounded_length = round_up_page(len as u64) as usize;
+    let page_num = rounded_addr as u32 >> PAGESHIFT;
+    let npages = (rounded_length >> PAGESHIFT) as u32;
+
+    // we do not really deallocate the memory region, as it is part of the cage linear memory. Instead its
+    // pages are released and the region is set back to PROT_NONE
+    let mut vmmap = cage.vmmap.write();
+    if page_num as u64 + npages as u64 > vmmap.end_address as u64 {
+        return syscall_error(Errno::EINVAL, "munmap", "range is out of the cage memory");
+    }
+    release_pages(&vmmap, page_num, npages);
+    let _ = vmmap.remove_entry(page_num, npages);
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/mprotect.2.html
+///
+/// Linux `mprotect()` changes the protection of the pages in the given range. The host pages and the
+/// `vmmap` entries are updated together while holding the `vmmap` lock, and entries only partially
+/// covered by the range are split. The new protection can't exceed the `maxprot` of the mappings, e.g.
+/// a shared mapping of a file opened read-only can't be made writable.
+///
+/// Input:
+///     - cageid: current cage identifier.
+///     - addr_arg: page aligned start address of the range, in the cage address space
+///     - len_arg: length of the range, rounded up to a multiple of pages
+///     - prot_arg: new protection of the range
+///
+/// Return:
+///     - On success, 0 is returned. Otherwise, errors or panics are returned for different scenarios.
+pub fn mprotect_syscall(
+    cageid: u64,
+    addr_arg: u64,
+    addr_cageid: u64,
+    len_arg: u64,
+    len_cageid: u64,
+    prot_arg: u64,
+    prot_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let addr = addr_arg as u32;
+    let len = sc_convert_sysarg_to_usize(len_arg, len_cageid, cageid);
+    let prot = sc_convert_sysarg_to_i32(prot_arg, prot_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "mprotect", "Invalide Cage ID");
+    }
+
+    if round_up_page(addr as u64) != addr as u64 {
+        return syscall_error(Errno::EINVAL, "mprotect", "address it not aligned");
+    }
+    if prot & PROT_EXEC > 0 {
+        return syscall_error(Errno::EINVAL, "mprotect", "PROT_EXEC is not allowed");
+    }
+    if prot & !(PROT_READ | PROT_WRITE) != 0 {
+        return syscall_error(Errno::EINVAL, "mprotect", "invalid protection flags");
+    }
+    if len == 0 {
+        return 0;
+    }
+
+    let cage = get_cage(addr_cageid).unwrap();
+    let rounded_length = round_up_page(len as u64);
+    let page_num = addr >> PAGESHIFT;
+    let npages = (rounded_length >> PAGESHIFT) as u32;
+
+    let mut vmmap = cage.vmmap.write();
+    if page_num as u64 + npages as u64 > vmmap.end_address as u64
+        || !vmmap.check_existing_mapping(page_num, npages, 0)
+    {
+        return syscall_error(Errno::ENOMEM, "mprotect", "range is not fully mapped");
+    }
+    if !vmmap.check_existing_mapping(page_num, npages, prot) {
+        return syscall_error(Errno::EACCES, "mprotect", "protection exceeds maxprot");
+    }
+
+    let sysaddr = vmmap.user_to_sys(addr);
+    let ret = unsafe { libc::mprotect(sysaddr as *mut c_void, rounded_length as usize, prot) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "mprotect");
+    }
+    vmmap.change_prot(page_num, npages, prot);
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/mremap.2.html
+///
+/// Linux `mremap()` resizes a mapping, and moves it if it can't be resized in place. The range to remap
+/// must lie within a single mapping. Shrinking releases the tail of the range the same way `munmap`
+/// does. Growing extends the mapping in place when the pages after it are free, otherwise the mapping
+/// is moved to free space found with `find_map_space` if `MREMAP_MAYMOVE` is set, or to `new_addr` if
+/// `MREMAP_FIXED` is also set. A moved range is replaced by an inaccessible reservation so that the
+/// cage linear memory stays reserved. The host pages and the `vmmap` entries are updated together
+/// while holding the `vmmap` lock. Growing fails with `ENOMEM` if the new pages would exceed the memory
+/// limits of the cage or the global memory limit.
+///
+/// Input:
+///     - cageid: current cage identifier.
+///     - old_addr_arg: page aligned start address of the range to remap, in the cage address space
+///     - old_len_arg: length of the range to remap
+///     - new_len_arg: new length of the range
+///     - flags_arg: 0 or `MREMAP_MAYMOVE`, optionally with `MREMAP_FIXED`
+///     - new_addr_arg: page aligned destination of the range when `MREMAP_FIXED` is set
+///
+/// Return:
+///     - On success, the new address of the range is returned. Otherwise, errors or panics are returned
+///     for different scenarios.
+pub fn mremap_syscall(
+    cageid: u64,
+    old_addr_arg: u64,
+    old_addr_cageid: u64,
+    old_len_arg: u64,
+    old_len_cageid: u64,
+    new_len_arg: u64,
+    new_len_cageid: u64,
+    flags_arg: u64,
+    flags_cageid: u64,
+    new_addr_arg: u64,
+    new_addr_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let old_addr = old_addr_arg as u32;
+    let old_len = sc_convert_sysarg_to_usize(old_len_arg, old_len_cageid, cageid);
+    let new_len = sc_convert_sysarg_to_usize(new_len_arg, new_len_cageid, cageid);
+    let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid) as u32;
+    let new_addr = new_addr_arg as u32;
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !sc_unusedarg(arg6, arg6_cageid) {
+        return syscall_error(Errno::EFAULT, "mremap", "Invalide Cage ID");
+    }
+
+    if flags & !(MREMAP_MAYMOVE | MREMAP_FIXED) != 0 {
+        return syscall_error(Errno::EINVAL, "mremap", "invalid flags");
+    }
+    let fixed = flags & MREMAP_FIXED != 0;
+    let maymove = flags & MREMAP_MAYMOVE != 0;
+    if fixed && !maymove {
+        return syscall_error(
+            Errno::EINVAL,
+            "mremap",
+            "MREMAP_FIXED requires MREMAP_MAYMOVE",
+        );
+    }
+    // a range only moves within the address space of the cage it belongs to
+    if fixed && (!validate_cageid(new_addr_cageid, cageid) || new_addr_cageid != old_addr_cageid) {
+        return syscall_error(Errno::EFAULT, "mremap", "Invalide Cage ID");
+    }
+    if round_up_page(old_addr as u64) != old_addr as u64 {
+        return syscall_error(Errno::EINVAL, "mremap", "address it not aligned");
+    }
+    if old_len == 0 || new_len == 0 {
+        return syscall_error(Errno::EINVAL, "mremap", "length cannot be zero");
+    }
+
+    let cage = get_cage(old_addr_cageid).unwrap();
+    let old_page = old_addr >> PAGESHIFT;
+    let old_npages = (round_up_page(old_len as u64) >> PAGESHIFT) as u32;
+    let new_npages = (round_up_page(new_len as u64) >> PAGESHIFT) as u32;
+
+    let mut vmmap = cage.vmmap.write();
+    if old_page as u64 + old_npages as u64 > vmmap.end_address as u64 {
+        return syscall_error(Errno::EFAULT, "mremap", "range is not mapped");
+    }
+    let entry = match vmmap.find_page(old_page) {
+        Some(entry) if old_page + old_npages <= entry.page_num + entry.npages => {
+            entry.slice(old_page, old_page + old_npages)
+        }
+        _ => {
+            return syscall_error(
+                Errno::EFAULT,
+                "mremap",
+                "range is not within a single mapping",
+            )
+        }
+    };
+    if let MemoryBackingType::SharedMemory(_) = entry.backing {
+        return syscall_error(
+            Errno::EINVAL,
+            "mremap",
+            "cannot remap shared memory segments",
+        );
+    }
+    let old_sys = vmmap.user_to_sys(old_addr);
+
+    if !fixed {
+        // shrink in place, releasing the tail of the range like munmap does
+        if new_npages <= old_npages {
+            if new_npages < old_npages {
+                release_pages(&vmmap, old_page + new_npages, old_npages - new_npages);
+                let _ = vmmap.remove_entry(old_page + new_npages, old_npages - new_npages);
+            }
+            return old_addr as i32;
+        }
+
+        // grow in place by mapping the pages after the range. The extra pages of a shared anonymous
+        // mapping would not be shared with the existing ones, so those are always moved
+        let extra_page = old_page + old_npages;
+        let extra_npages = new_npages - old_npages;
+        let shared_anonymous =
+            entry.flags & MAP_SHARED as i32 != 0 && entry.flags & MAP_ANONYMOUS as i32 != 0;
+        if !shared_anonymous
+            && extra_page as u64 + extra_npages as u64 <= vmmap.end_address as u64
+            && vmmap.is_range_free(extra_page, extra_npages)
+        {
+            let (virtual_fd, off) = match entry.backing {
+                MemoryBackingType::FileDescriptor(virtual_fd) => (
+                    virtual_fd as i32,
+                    entry.file_offset + ((old_npages as i64) << PAGESHIFT),
+                ),
+                _ => (-1, 0),
+            };
+            let _reservation = match vmmap.reserve_memory(extra_page, extra_npages, entry.backing) {
+                Ok(reservation) => reservation,
+                Err(_) => return syscall_error(Errno::ENOMEM, "mremap", "memory limit exceeded"),
+            };
+            let extra_sys = vmmap.user_to_sys(extra_page << PAGESHIFT);
+            let result = mmap_inner(
+                cageid,
+                extra_sys as *mut u8,
+                (extra_npages as usize) << PAGESHIFT,
+                entry.prot,
+                entry.flags | MAP_FIXED as i32,
+                virtual_fd,
+                off,
+            );
+            // mmap_inner returns a negative errno on failure
+            if (result as isize) < 0 {
+                return result as i32;
+            }
+            if result != extra_sys {
+                panic!("MAP_FIXED not fixed");
+            }
+            let _ = vmmap.add_entry_with_overwrite(
+                old_page,
+                new_npages,
+                entry.prot,
+                entry.maxprot,
+                entry.flags,
+                entry.backing,
+                entry.file_offset,
+                new_len as i64,
+                entry.cage_id,
+            );
+            return old_addr as i32;
+        }
+
+        if !maymove {
+            return syscall_error(Errno::ENOMEM, "mremap", "cannot grow the mapping in place");
+        }
+    }
 
-    // we are replacing munmap with mmap because we do not want to really deallocate the memory region
-    // we just want to set the prot of the memory region back to PROT_NONE
-    // Directly call libc::mmap to improve performance
+    // pick the destination of the move
+    let new_page = if fixed {
+        if round_up_page(new_addr as u64) != new_addr as u64 {
+            return syscall_error(Errno::EINVAL, "mremap", "new address it not aligned");
+        }
+        let new_page = new_addr >> PAGESHIFT;
+        if new_page as u64 + new_npages as u64 > vmmap.end_address as u64 {
+            return syscall_error(Errno::EINVAL, "mremap", "new address is out of range");
+        }
+        if new_page < old_page + old_npages && old_page < new_page + new_npages {
+            return syscall_error(Errno::EINVAL, "mremap", "old and new ranges overlap");
+        }
+        new_page
+    } else {
+        match vmmap.find_map_space(new_npages, 1) {
+            Some(space) => space.start(),
+            None => return syscall_error(Errno::ENOMEM, "mremap", "no memory"),
+        }
+    };
+
+    // the old range is released by the move, so only the pages beyond its length are committed
+    let _reservation = if new_npages > old_npages {
+        match vmmap.reserve_memory(
+            new_page + old_npages,
+            new_npages - old_npages,
+            entry.backing,
+        ) {
+            Ok(reservation) => Some(reservation),
+            Err(_) => return syscall_error(Errno::ENOMEM, "mremap", "memory limit exceeded"),
+        }
+    } else {
+        None
+    };
+
+    // move the host pages, then reserve the old range again so it stays part of the cage memory
+    let new_sys = vmmap.user_to_sys(new_page << PAGESHIFT);
+    let old_len = (old_npages as usize) << PAGESHIFT;
+    let result = unsafe {
+        libc::mremap(
+            old_sys as *mut c_void,
+            old_len,
+            (new_npages as usize) << PAGESHIFT,
+            (MREMAP_MAYMOVE | MREMAP_FIXED) as i32,
+            new_sys as *mut c_void,
+        )
+    };
+    if result == libc::MAP_FAILED {
+        let errno = get_errno();
+        return handle_errno(errno, "mremap");
+    }
     let result = unsafe {
         libc::mmap(
-            sysaddr as *mut c_void,
-            rounded_length,
+            old_sys as *mut c_void,
+            old_len,
             PROT_NONE,
             (MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED) as i32,
             -1,
             0,
         ) as usize
     };
-    if result != sysaddr {
+    if result != old_sys {
         panic!("MAP_FIXED not fixed");
     }
 
-    let mut vmmap = cage.vmmap.write();
-
-    vmmap.remove_entry(rounded_addr as u32 >> PAGESHIFT, len as u32 >> PAGESHIFT);
+    let _ = vmmap.remove_entry(old_page, old_npages);
+    let _ = vmmap.add_entry_with_overwrite(
+        new_page,
+        new_npages,
+        entry.prot,
+        entry.maxprot,
+        entry.flags,
+        entry.backing,
+        entry.file_offset,
+        new_len as i64,
+        entry.cage_id,
+    );
 
-    0
+    (new_page << PAGESHIFT) as i32
 }
 
 /// Handles the `brk_syscall`, interacting with the `vmmap` structure.
@@ -902,7 +1324,8 @@ pub fn munmap_syscall(
 /// This function processes the `brk_syscall` by updating the `vmmap` entries and performing
 /// the necessary operations to adjust the program break. Specifically, it updates the program
 /// break by modifying the end of the heap entry (the first entry in `vmmap`) and invokes `mmap`
-/// to adjust the memory protection as needed.
+/// to adjust the memory protection as needed. The program break is not raised if the new heap pages
+/// would exceed the memory limits of the cage or the global memory limit.
 ///
 /// # Arguments
 /// * `cageid` - Identifier of the cage that initiated the `brk` syscall.
@@ -949,11 +1372,18 @@ pub fn brk_syscall(
     let brk_page = (round_up_page(brk as u64) >> PAGESHIFT) as u32;
 
     // if we are incrementing program break, we need to check if we have enough space
-    if brk_page > old_brk_page {
+    // and that the new pages are within the memory limits
+    let _reservation = if brk_page > old_brk_page {
         if vmmap.check_existing_mapping(old_brk_page, brk_page - old_brk_page, 0) {
             return syscall_error(Errno::ENOMEM, "brk", "no memory");
         }
-    }
+        match vmmap.reserve_memory(old_brk_page, brk_page - old_brk_page, heap.backing) {
+            Ok(reservation) => Some(reservation),
+            Err(_) => return syscall_error(Errno::ENOMEM, "brk", "memory limit exceeded"),
+        }
+    } else {
+        None
+    };
 
     // update vmmap entry
     vmmap.add_entry_with_overwrite(
@@ -967,6 +1397,22 @@ pub fn brk_syscall(
         heap.file_size,
         heap.cage_id,
     );
+    // the pages above the new break are no longer part of the heap
+    if brk_page < old_brk_page {
+        let _ = vmmap.remove_entry(brk_page, old_brk_page - brk_page);
+    }
+    // The heap pages below the old break are not remapped, so the heap is still mapped from its snapshot
+    // unless the snapshot holds some of the new pages, which are mapped anonymously below
+    let snapshot_valid = heap.snapshot.as_ref().map_or(false, |snapshot| {
+        brk_page <= old_brk_page
+            || snapshot.page_num + snapshot.npages <= old_brk_page
+            || snapshot.page_num >= brk_page
+    });
+    if snapshot_valid {
+        if let Some(entry) = vmmap.find_page_mut(HEAP_ENTRY_INDEX) {
+            entry.snapshot = heap.snapshot.clone();
+        }
+    }
 
     let old_heap_end_usr = (old_brk_page * PAGESIZE) as u32;
     let old_heap_end_sys = vmmap.user_to_sys(old_heap_end_usr) as *mut u8;
@@ -1021,7 +1467,8 @@ pub fn brk_syscall(
 ///
 /// This function processes the `sbrk_syscall` by updating the `vmmap` entries and managing
 /// the program break. It calculates the target program break after applying the specified
-/// increment and delegates further processing to the `brk_handler`.
+/// increment and delegates further processing to the `brk_handler`, which also enforces the memory
+/// limits, so an increment exceeding them fails with `ENOMEM`.
 ///
 /// # Arguments
 /// * `cageid` - Identifier of the cage that initiated the `sbrk` syscall.
@@ -1105,39 +1552,540 @@ pub fn sbrk_syscall(
     (PAGESIZE * heap.npages) as i32
 }
 
-//------------------------------------FCNTL SYSCALL------------------------------------
-/// This function will be different in new code base (when splitting out type conversion function)
-/// since the conversion from u64 -> i32 in negative number will be different. These lines are repeated
-/// in 5 out of 6 fcntl_syscall cases, so wrapped these loc into helper functions to make code cleaner.
+//------------------------------------SHM SYSCALLS------------------------------------
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/shmget.2.html
 ///
-/// ## Arguments
-/// cageid: cage ID associate with virtual file descriptor
-/// virtual_fd: virtual file descriptor
+/// `shmget_syscall` returns the identifier of the System V shared memory segment associated with `key`,
+/// creating it if `key` is `IPC_PRIVATE` or if `IPC_CREAT` is set and no segment exists for `key`. Segments
+/// live in the global `SHM_TABLE`, so they are visible to every cage.
 ///
-/// ## Return Type
-/// On success:
-/// Return corresponding FDTableEntry that contains
-/// (1) underlying kernel fd.
-/// (2) file descriptor kind.
-/// (3) O_CLOEXEC flag.
-/// (4) file descriptor specific extra information.
+/// Input:
+///     - cageid: current cage
+///     - key_arg: key of the segment, or `IPC_PRIVATE` for a new segment nobody else can look up
+///     - size_arg: size of the segment in bytes, rounded up to whole pages when attached
+///     - shmflg_arg: `IPC_CREAT` and `IPC_EXCL`, and the permission bits of a new segment
 ///
-/// On error:
-/// Return error num EBADF(Bad File Descriptor)
-pub fn _fcntl_helper(cageid: u64, virtual_fd: u64) -> Result<fdtables::FDTableEntry, Errno> {
-    if virtual_fd > MAXFD as u64 {
-        return Err(Errno::EBADF);
+/// Return:
+///     - On success, the shmid of the segment
+///     - On failure, `EEXIST`, `ENOENT`, `EINVAL`, `ENOSPC` or `ENOMEM`
+pub fn shmget_syscall(
+    cageid: u64,
+    key_arg: u64,
+    key_cageid: u64,
+    size_arg: u64,
+    size_cageid: u64,
+    shmflg_arg: u64,
+    shmflg_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let key = sc_convert_sysarg_to_i32(key_arg, key_cageid, cageid);
+    let size = sc_convert_sysarg_to_usize(size_arg, size_cageid, cageid);
+    let shmflg = sc_convert_sysarg_to_i32(shmflg_arg, shmflg_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "shmget", "Invalide Cage ID");
     }
-    // Get underlying kernel fd
-    let wrappedvfd = fdtables::translate_virtual_fd(cageid, virtual_fd);
-    if wrappedvfd.is_err() {
-        return Err(Errno::EBADF);
+
+    let mut table = SHM_TABLE.lock();
+
+    if key != IPC_PRIVATE {
+        if let Some(shmid) = table.find_key(key) {
+            if shmflg & IPC_CREAT != 0 && shmflg & IPC_EXCL != 0 {
+                return syscall_error(
+                    Errno::EEXIST,
+                    "shmget",
+                    "key already exists and IPC_CREAT and IPC_EXCL were used",
+                );
+            }
+            if size > table.segments[&shmid].size {
+                return syscall_error(
+                    Errno::EINVAL,
+                    "shmget",
+                    "size is greater than the size of the existing segment",
+                );
+            }
+            return shmid;
+        }
+
+        if shmflg & IPC_CREAT == 0 {
+            return syscall_error(
+                Errno::ENOENT,
+                "shmget",
+                "no segment exists for the given key and IPC_CREAT was not specified",
+            );
+        }
     }
-    Ok(wrappedvfd.unwrap())
-}
 
-/// Reference: https://man7.org/linux/man-pages/man2/fcntl.2.html
-///
+    if size < SHMMIN as usize || size > SHMMAX as usize {
+        return syscall_error(
+            Errno::EINVAL,
+            "shmget",
+            "size is less than SHMMIN or greater than SHMMAX",
+        );
+    }
+
+    match table.create(key, size, (shmflg & 0o777) as u16, cageid) {
+        Ok(shmid) => shmid,
+        Err(Errno::ENOSPC) => syscall_error(
+            Errno::ENOSPC,
+            "shmget",
+            "all possible shared memory IDs have been taken",
+        ),
+        Err(errno) => syscall_error(errno, "shmget", "could not allocate memory for the segment"),
+    }
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/shmat.2.html
+///
+/// `shmat_syscall` attaches the segment `shmid` to the cage's memory. The segment's host memfd is mapped
+/// `MAP_SHARED` at the chosen address of the linear memory, so every cage attaching the segment sees the same
+/// pages, and the region is recorded in `vmmap` as backed by `SharedMemory(shmid)`.
+///
+/// Input:
+///     - cageid: current cage
+///     - shmid_arg: identifier returned by `shmget`
+///     - shmaddr_arg: address to attach at, or 0 to let `vmmap` pick one
+///     - shmflg_arg: `SHM_RDONLY` to attach read-only, `SHM_RND` to round `shmaddr` down to a page
+///
+/// Return:
+///     - On success, the address the segment is attached at
+///     - On failure, `EINVAL` or `ENOMEM`
+pub fn shmat_syscall(
+    cageid: u64,
+    shmid_arg: u64,
+    shmid_cageid: u64,
+    shmaddr_arg: u64,
+    shmaddr_cageid: u64,
+    shmflg_arg: u64,
+    shmflg_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let shmid = sc_convert_sysarg_to_i32(shmid_arg, shmid_cageid, cageid);
+    let shmaddr = shmaddr_arg as u32;
+    let shmflg = sc_convert_sysarg_to_i32(shmflg_arg, shmflg_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "shmat", "Invalide Cage ID");
+    }
+
+    let prot = if shmflg & SHM_RDONLY != 0 {
+        PROT_READ
+    } else {
+        PROT_READ | PROT_WRITE
+    };
+
+    let cage = get_cage(shmaddr_cageid).unwrap();
+    // vmmap is locked before SHM_TABLE, see `SHM_TABLE`
+    let mut vmmap = cage.vmmap.write();
+    let mut table = SHM_TABLE.lock();
+
+    let Some(segment) = table.segments.get(&shmid) else {
+        return syscall_error(Errno::EINVAL, "shmat", "invalid shmid");
+    };
+    let npages = segment.npages();
+
+    let useraddr = if shmaddr == 0 {
+        // pick an address of appropriate size, anywhere
+        match vmmap.find_map_space(npages, 1) {
+            Some(space) => (space.start() << PAGESHIFT) as u32,
+            None => return syscall_error(Errno::ENOMEM, "shmat", "no memory"),
+        }
+    } else if shmflg & SHM_RND != 0 {
+        // SHMLBA is a page
+        shmaddr & !(PAGESIZE - 1)
+    } else if shmaddr % PAGESIZE != 0 {
+        return syscall_error(
+            Errno::EINVAL,
+            "shmat",
+            "address is not page aligned and SHM_RND was not specified",
+        );
+    } else {
+        shmaddr
+    };
+
+    let sysaddr = vmmap.user_to_sys(useraddr);
+    if segment.map_at(sysaddr, prot) as usize != sysaddr {
+        return syscall_error(Errno::ENOMEM, "shmat", "could not map the segment");
+    }
+
+    let _ = vmmap.add_entry_with_overwrite(
+        useraddr >> PAGESHIFT,
+        npages,
+        prot,
+        prot,
+        MAP_SHARED as i32,
+        MemoryBackingType::SharedMemory(shmid as u64),
+        0,
+        segment.size as i64,
+        cageid,
+    );
+    table.attach(shmid, cageid);
+
+    useraddr as i32
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/shmdt.2.html
+///
+/// `shmdt_syscall` detaches the segment attached at `shmaddr`. As with `munmap`, the pages are not released
+/// but set back to `PROT_NONE`. Once the last attachment of a segment marked with `IPC_RMID` is gone, the
+/// segment is destroyed.
+///
+/// Input:
+///     - cageid: current cage
+///     - shmaddr_arg: address returned by `shmat`
+///
+/// Return:
+///     - On success, 0
+///     - On failure, `EINVAL` if no segment is attached at `shmaddr`
+pub fn shmdt_syscall(
+    cageid: u64,
+    shmaddr_arg: u64,
+    shmaddr_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let shmaddr = shmaddr_arg as u32;
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "shmdt", "Invalide Cage ID");
+    }
+
+    if shmaddr % PAGESIZE != 0 {
+        return syscall_error(Errno::EINVAL, "shmdt", "address is not page aligned");
+    }
+
+    let cage = get_cage(shmaddr_cageid).unwrap();
+    // vmmap is locked before SHM_TABLE, see `SHM_TABLE`
+    let mut vmmap = cage.vmmap.write();
+    let page_num = shmaddr >> PAGESHIFT;
+    let shmid = match vmmap.find_page(page_num).map(|entry| entry.backing) {
+        Some(MemoryBackingType::SharedMemory(shmid)) => shmid as i32,
+        _ => {
+            return syscall_error(
+                Errno::EINVAL,
+                "shmdt",
+                "no shared memory segment is attached at the address",
+            )
+        }
+    };
+
+    let mut table = SHM_TABLE.lock();
+    // an attached segment can't be destroyed, so it is still in the table
+    let npages = table.segments[&shmid].npages();
+
+    let sysaddr = vmmap.user_to_sys(shmaddr);
+    // like munmap, the pages are only made inaccessible
+    let result = unsafe {
+        libc::mmap(
+            sysaddr as *mut c_void,
+            (npages as usize) << PAGESHIFT,
+            PROT_NONE,
+            (MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED) as i32,
+            -1,
+            0,
+        ) as usize
+    };
+    if result != sysaddr {
+        panic!("MAP_FIXED not fixed");
+    }
+
+    let _ = vmmap.remove_entry(page_num, npages);
+    table.detach(shmid, cageid);
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/shmctl.2.html
+///
+/// `shmctl_syscall` performs the control operation `cmd` on the segment `shmid`. `IPC_STAT` copies the
+/// segment's `shmid_ds` to `buf`, `IPC_SET` updates its owner and permission bits from `buf`, and `IPC_RMID`
+/// marks it for destruction, which happens once nothing is attached to it anymore.
+///
+/// Input:
+///     - cageid: current cage
+///     - shmid_arg: identifier returned by `shmget`
+///     - cmd_arg: `IPC_STAT`, `IPC_SET` or `IPC_RMID`
+///     - buf_arg: `shmid_ds` read or written by `IPC_STAT` and `IPC_SET`, unused by `IPC_RMID`
+///
+/// Return:
+///     - On success, 0
+///     - On failure, `EINVAL` or `EFAULT`
+pub fn shmctl_syscall(
+    cageid: u64,
+    shmid_arg: u64,
+    shmid_cageid: u64,
+    cmd_arg: u64,
+    cmd_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let shmid = sc_convert_sysarg_to_i32(shmid_arg, shmid_cageid, cageid);
+    let cmd = sc_convert_sysarg_to_i32(cmd_arg, cmd_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "shmctl", "Invalide Cage ID");
+    }
+
+    let mut table = SHM_TABLE.lock();
+    let Some(segment) = table.segments.get_mut(&shmid) else {
+        return syscall_error(Errno::EINVAL, "shmctl", "invalid shmid");
+    };
+
+    match cmd {
+        IPC_STAT => {
+            if buf_arg == 0 {
+                return syscall_error(Errno::EFAULT, "shmctl", "buf is null");
+            }
+            let size = std::mem::size_of::<ShmidsStruct>();
+            let buf = match sc_convert_addr_to_host_checked(
+                buf_arg, buf_cageid, cageid, size, PROT_WRITE,
+            ) {
+                Ok(addr) => addr as *mut ShmidsStruct,
+                Err(e) => return syscall_error(e, "shmctl", "Invalid address"),
+            };
+            unsafe { *buf = segment.shminfo };
+        }
+        IPC_SET => {
+            if buf_arg == 0 {
+                return syscall_error(Errno::EFAULT, "shmctl", "buf is null");
+            }
+            let size = std::mem::size_of::<ShmidsStruct>();
+            let buf =
+                match sc_convert_addr_to_host_checked(buf_arg, buf_cageid, cageid, size, PROT_READ)
+                {
+                    Ok(addr) => addr as *const ShmidsStruct,
+                    Err(e) => return syscall_error(e, "shmctl", "Invalid address"),
+                };
+            segment.set_perm(unsafe { &(*buf).shm_perm });
+        }
+        IPC_RMID => table.remove(shmid),
+        _ => {
+            return syscall_error(Errno::EINVAL, "shmctl", "invalid command");
+        }
+    }
+
+    0
+}
+
+//------------------------------------FCNTL SYSCALL------------------------------------
+/// This function will be different in new code base (when splitting out type conversion function)
+/// since the conversion from u64 -> i32 in negative number will be different. These lines are repeated
+/// in 5 out of 6 fcntl_syscall cases, so wrapped these loc into helper functions to make code cleaner.
+///
+/// ## Arguments
+/// cageid: cage ID associate with virtual file descriptor
+/// virtual_fd: virtual file descriptor
+///
+/// ## Return Type
+/// On success:
+/// Return corresponding FDTableEntry that contains
+/// (1) underlying kernel fd.
+/// (2) file descriptor kind.
+/// (3) O_CLOEXEC flag.
+/// (4) file descriptor specific extra information.
+///
+/// On error:
+/// Return error num EBADF(Bad File Descriptor)
+pub fn _fcntl_helper(cageid: u64, virtual_fd: u64) -> Result<fdtables::FDTableEntry, Errno> {
+    if virtual_fd > MAXFD as u64 {
+        return Err(Errno::EBADF);
+    }
+    // Get underlying kernel fd
+    let wrappedvfd = fdtables::translate_virtual_fd(cageid, virtual_fd);
+    if wrappedvfd.is_err() {
+        return Err(Errno::EBADF);
+    }
+    Ok(wrappedvfd.unwrap())
+}
+
+/// Maps an error from allocating a virtual fd to the errno to return. `fdtables` returns ENFILE
+/// once the open fds across all cages hit the global limit, anything else means the cage ran out
+/// of fds below its RLIMIT_NOFILE soft limit.
+pub(crate) fn _fd_alloc_errno(e: u64) -> Errno {
+    if e == Errno::ENFILE as u64 {
+        Errno::ENFILE
+    } else {
+        Errno::EMFILE
+    }
+}
+
+/// Maps an error of `F_DUPFD` and `F_DUPFD_CLOEXEC`. Unlike `dup`, a lower bound at or above the
+/// RLIMIT_NOFILE soft limit (or a negative one) is EINVAL.
+fn _fcntl_dupfd_error(e: u64) -> i32 {
+    if e == Errno::EINVAL as u64 {
+        return syscall_error(Errno::EINVAL, "fcntl", "Lower bound is out of range");
+    }
+    syscall_error(_fd_alloc_errno(e), "fcntl", "Too many files opened")
+}
+
+/// Returns the host (device, inode) of the file behind `kernel_fd`, which identifies it in the
+/// lock table of `locks`
+fn _lock_file_id(kernel_fd: i32) -> Result<locks::FileId, i32> {
+    let mut statbuf: stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(kernel_fd, &mut statbuf) } < 0 {
+        let errno = get_errno();
+        return Err(handle_errno(errno, "fcntl"));
+    }
+    Ok((statbuf.st_dev as u64, statbuf.st_ino as u64))
+}
+
+/// Handles `F_GETLK`, `F_SETLK` and `F_SETLKW` of `fcntl_syscall` on `vfd`, with `flock_arg`
+/// the address of a `struct flock` in the cage.
+///
+/// As on Linux, the range of the lock is relative to the `l_whence` of the request, a zero
+/// `l_len` locks up to the end of the file however far it grows, and a negative one locks the
+/// bytes before `l_start`. `F_GETLK` reports the first conflicting lock with `l_pid` set to the
+/// cage holding it, or sets `l_type` to `F_UNLCK` if there is none.
+fn _fcntl_record_lock(
+    cageid: u64,
+    vfd: fdtables::FDTableEntry,
+    cmd: i32,
+    flock_arg: u64,
+    flock_cageid: u64,
+) -> i32 {
+    if flock_arg == 0 {
+        return syscall_error(Errno::EFAULT, "fcntl", "flock is NULL");
+    }
+    // In-memory sockets and pipes are not files there is anything to lock in
+    if vfd.fdkind != fs_const::FDKIND_KERNEL {
+        return syscall_error(Errno::EINVAL, "fcntl", "Record locks need a file");
+    }
+    let kernel_fd = vfd.underfd as i32;
+    // F_GETLK writes the conflicting lock back into the struct
+    let size = std::mem::size_of::<FlockStruct>();
+    let prot = if cmd == F_GETLK {
+        PROT_READ | PROT_WRITE
+    } else {
+        PROT_READ
+    };
+    let flock = match sc_convert_addr_to_host_checked(flock_arg, flock_cageid, cageid, size, prot) {
+        Ok(addr) => unsafe { &mut *(addr as *mut FlockStruct) },
+        Err(e) => return syscall_error(e, "fcntl", "Invalid address"),
+    };
+
+    let ltype = flock.l_type;
+    if ltype != F_RDLCK && ltype != F_WRLCK && (ltype != F_UNLCK || cmd == F_GETLK) {
+        return syscall_error(Errno::EINVAL, "fcntl", "Invalid lock type");
+    }
+    // A read lock needs the fd open for reading, and a write lock for writing
+    if cmd != F_GETLK && ltype != F_UNLCK {
+        let flags = unsafe { libc::fcntl(kernel_fd, F_GETFL) };
+        if flags < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "fcntl");
+        }
+        let accmode = flags & O_ACCMODE;
+        if (ltype == F_RDLCK && accmode == O_WRONLY) || (ltype == F_WRLCK && accmode == O_RDONLY) {
+            return syscall_error(Errno::EBADF, "fcntl", "fd not open for this lock type");
+        }
+    }
+
+    let base = match flock.l_whence as i32 {
+        fs_const::SEEK_SET => 0,
+        fs_const::SEEK_CUR => unsafe { libc::lseek(kernel_fd, 0, fs_const::SEEK_CUR) },
+        fs_const::SEEK_END => {
+            let mut statbuf: stat = unsafe { std::mem::zeroed() };
+            if unsafe { libc::fstat(kernel_fd, &mut statbuf) } < 0 {
+                -1
+            } else {
+                statbuf.st_size
+            }
+        }
+        _ => return syscall_error(Errno::EINVAL, "fcntl", "Invalid l_whence"),
+    };
+    if base < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "fcntl");
+    }
+    let start = match base.checked_add(flock.l_start) {
+        Some(start) => start,
+        None => return syscall_error(Errno::EOVERFLOW, "fcntl", "Lock start overflows"),
+    };
+    let (start, end) = if flock.l_len > 0 {
+        match start.checked_add(flock.l_len) {
+            Some(end) => (start, end as u64),
+            None => return syscall_error(Errno::EOVERFLOW, "fcntl", "Lock end overflows"),
+        }
+    } else if flock.l_len == 0 {
+        (start, locks::LOCK_TO_EOF)
+    } else {
+        (start.saturating_add(flock.l_len), start as u64)
+    };
+    if start < 0 {
+        return syscall_error(Errno::EINVAL, "fcntl", "Lock starts before the file");
+    }
+    let start = start as u64;
+
+    let file = match _lock_file_id(kernel_fd) {
+        Ok(file) => file,
+        Err(e) => return e,
+    };
+    if cmd == F_GETLK {
+        match locks::get_record_lock(cageid, file, ltype, start, end) {
+            Some(lock) => {
+                flock.l_type = lock.ltype;
+                flock.l_whence = fs_const::SEEK_SET as i16;
+                flock.l_start = lock.start as i64;
+                flock.l_len = if lock.end == locks::LOCK_TO_EOF {
+                    0
+                } else {
+                    (lock.end - lock.start) as i64
+                };
+                flock.l_pid = lock.owner as i32;
+            }
+            None => flock.l_type = F_UNLCK,
+        }
+        return 0;
+    }
+    match locks::set_record_lock(cageid, file, ltype, start, end, cmd == F_SETLKW) {
+        Ok(()) => 0,
+        Err(Errno::EDEADLK) => {
+            syscall_error(Errno::EDEADLK, "fcntl", "Resource deadlock would occur")
+        }
+        Err(e) => syscall_error(e, "fcntl", "Conflicting lock is held by another cage"),
+    }
+}
+
+/// Reference: https://man7.org/linux/man-pages/man2/fcntl.2.html
+///
 /// Due to the design of `fdtables` library, different virtual fds created by `dup`/`dup2` are
 /// actually refer to the same underlying kernel fd. Therefore, in `fcntl_syscall` we need to
 /// handle the cases of `F_DUPFD`, `F_DUPFD_CLOEXEC`, `F_GETFD`, and `F_SETFD` separately.
@@ -1173,7 +2121,10 @@ pub fn _fcntl_helper(cageid: u64, virtual_fd: u64) -> Result<fdtables::FDTableEn
 /// All other commands: Zero.
 /// On error, -1 is returned
 ///
-/// TODO: `F_GETOWN`, `F_SETOWN`, `F_GETOWN_EX`, `F_SETOWN_EX`, `F_GETSIG`, and `F_SETSIG` are used to manage I/O availability signals.
+/// Record locks (`F_GETLK`, `F_SETLK` and `F_SETLKW`) and the owner set by `F_SETOWN` are kept by the
+/// runtime rather than the host, see `locks`.
+///
+/// TODO: `F_GETOWN_EX`, `F_SETOWN_EX`, `F_GETSIG`, and `F_SETSIG` are used to manage I/O availability signals.
 pub fn fcntl_syscall(
     cageid: u64,
     virtual_fd: u64,
@@ -1216,11 +2167,11 @@ pub fn fcntl_syscall(
                 vfd.fdkind,
                 vfd.underfd,
                 false,
-                0,
+                vfd.perfdinfo,
                 arg as u64,
             ) {
                 Ok(new_vfd) => return new_vfd as i32,
-                Err(_) => return syscall_error(Errno::EBADF, "fcntl", "Bad File Descriptor"),
+                Err(e) => return _fcntl_dupfd_error(e),
             }
         }
         // As for `F_DUPFD`, but additionally set the close-on-exec flag
@@ -1238,11 +2189,11 @@ pub fn fcntl_syscall(
                 vfd.fdkind,
                 vfd.underfd,
                 true,
-                0,
+                vfd.perfdinfo,
                 arg as u64,
             ) {
                 Ok(new_vfd) => return new_vfd as i32,
-                Err(_) => return syscall_error(Errno::EBADF, "fcntl", "Bad File Descriptor"),
+                Err(e) => return _fcntl_dupfd_error(e),
             }
         }
         // Return (as the function result) the file descriptor flags.
@@ -1261,11 +2212,13 @@ pub fn fcntl_syscall(
                 Ok(entry) => entry,
                 Err(e) => return syscall_error(e, "fcntl", "Bad File Descriptor"),
             };
-            // Set underlying kernel fd flag
-            let ret = unsafe { libc::fcntl(vfd.underfd as i32, cmd, arg) };
-            if ret < 0 {
-                let errno = get_errno();
-                return handle_errno(errno, "fcntl");
+            // Set underlying kernel fd flag, in-memory sockets and pipes only have the virtual one
+            if vfd.fdkind == fs_const::FDKIND_KERNEL {
+                let ret = unsafe { libc::fcntl(vfd.underfd as i32, cmd, arg) };
+                if ret < 0 {
+                    let errno = get_errno();
+                    return handle_errno(errno, "fcntl");
+                }
             }
             // Set virtual fd flag
             let cloexec_flag: bool = arg != 0;
@@ -1277,17 +2230,65 @@ pub fn fcntl_syscall(
         // Return (as the function result) the process ID or process
         // group ID currently receiving SIGIO and SIGURG signals for
         // events on file descriptor fd.
-        (F_GETOWN, ..) => DEFAULT_GID as i32,
+        //
+        // The host only knows the lind runtime as a whole, so the owner is
+        // kept by the runtime, with the open file description like Linux.
+        (F_GETOWN, ..) => {
+            let vfd = match _fcntl_helper(cageid, virtual_fd) {
+                Ok(entry) => entry,
+                Err(e) => return syscall_error(e, "fcntl", "Bad File Descriptor"),
+            };
+            locks::get_owner(vfd.fdkind, vfd.underfd)
+        }
         // Set the process ID or process group ID that will receive
         // SIGIO and SIGURG signals for events on the file descriptor
-        // fd.
-        (F_SETOWN, arg) if arg >= 0 => 0,
+        // fd. A negative `arg` is a process group ID.
+        (F_SETOWN, arg) => {
+            let vfd = match _fcntl_helper(cageid, virtual_fd) {
+                Ok(entry) => entry,
+                Err(e) => return syscall_error(e, "fcntl", "Bad File Descriptor"),
+            };
+            locks::set_owner(vfd.fdkind, vfd.underfd, arg);
+            0
+        }
+        // Acquire, release or test for the existence of record locks. Every
+        // cage shares one host process, whose locks never conflict with each
+        // other, so the locks are kept by the runtime, see `locks`.
+        (F_GETLK, ..) | (F_SETLK, ..) | (F_SETLKW, ..) => {
+            let vfd = match _fcntl_helper(cageid, virtual_fd) {
+                Ok(entry) => entry,
+                Err(e) => return syscall_error(e, "fcntl", "Bad File Descriptor"),
+            };
+            _fcntl_record_lock(cageid, vfd, cmd, arg_arg, arg_cageid)
+        }
         _ => {
             // Get fdtable entry
             let vfd = match _fcntl_helper(cageid, virtual_fd) {
                 Ok(entry) => entry,
                 Err(e) => return syscall_error(e, "fcntl", "Bad File Descriptor"),
             };
+            // The status flags of in-memory sockets are kept by the socket itself
+            if vfd.fdkind == fs_const::FDKIND_LOOPBACK {
+                return match cmd {
+                    F_GETFL => loopback::get_status_flags(vfd.underfd),
+                    F_SETFL => {
+                        loopback::set_status_flags(vfd.underfd, arg);
+                        0
+                    }
+                    _ => syscall_error(Errno::EINVAL, "fcntl", "Invalid command for a socket"),
+                };
+            }
+            // Same for in-memory pipes, whose ends keep their own flags
+            if vfd.fdkind == fs_const::FDKIND_PIPE {
+                return match cmd {
+                    F_GETFL => pipe::get_status_flags(vfd.underfd),
+                    F_SETFL => {
+                        pipe::set_status_flags(vfd.underfd, arg);
+                        0
+                    }
+                    _ => syscall_error(Errno::EINVAL, "fcntl", "Invalid command for a pipe"),
+                };
+            }
             let ret = unsafe { libc::fcntl(vfd.underfd as i32, cmd, arg) };
             if ret < 0 {
                 let errno = get_errno();
@@ -1298,7 +2299,218 @@ pub fn fcntl_syscall(
     }
 }
 
-pub fn clock_gettime_syscall(
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/flock.2.html
+///
+/// The Linux `flock()` syscall applies or removes an advisory lock on the open file associated
+/// with `fd`. The host kernel would already keep the locks of different cages apart, but a call
+/// blocked in the host can't be woken by the runtime, so the locks are emulated by `locks`: they
+/// belong to the open file description, which fds made by `dup()` and fork share, and are released
+/// once its last fd is closed.
+///
+/// ## Arguments
+/// virtual_fd: virtual file descriptor
+/// operation: `LOCK_SH`, `LOCK_EX` or `LOCK_UN`, optionally or'ed with `LOCK_NB` to fail with
+/// `EWOULDBLOCK` instead of blocking on a conflicting lock
+///
+/// ## Return Type
+/// On success, zero is returned. On error, -1 is returned
+pub fn flock_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    operation_arg: u64,
+    operation_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let operation = sc_convert_sysarg_to_i32(operation_arg, operation_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "flock", "Invalide Cage ID");
+    }
+
+    let vfd = match _fcntl_helper(cageid, virtual_fd) {
+        Ok(entry) => entry,
+        Err(e) => return syscall_error(e, "flock", "Bad File Descriptor"),
+    };
+    let exclusive = match operation & !LOCK_NB {
+        LOCK_SH => Some(false),
+        LOCK_EX => Some(true),
+        LOCK_UN => None,
+        _ => return syscall_error(Errno::EINVAL, "flock", "Invalid operation"),
+    };
+    // In-memory sockets and pipes are not files there is anything to lock in
+    if vfd.fdkind != fs_const::FDKIND_KERNEL {
+        return syscall_error(Errno::EINVAL, "flock", "Locks need a file");
+    }
+    let file = match _lock_file_id(vfd.underfd as i32) {
+        Ok(file) => file,
+        Err(e) => return e,
+    };
+
+    match locks::flock(
+        vfd.fdkind,
+        vfd.underfd,
+        file,
+        exclusive,
+        (operation & LOCK_NB) != 0,
+    ) {
+        Ok(()) => 0,
+        Err(e) => syscall_error(e, "flock", "Conflicting lock is held"),
+    }
+}
+
+/// Direction and size of the argument of an ioctl request
+#[derive(Clone, Copy)]
+enum IoctlArg {
+    /// The argument is not used
+    None,
+    /// Points to a value of the given size that the request reads
+    In(usize),
+    /// Points to a value of the given size that the request writes
+    Out(usize),
+}
+
+/// The ioctl requests `ioctl_syscall` supports, with the argument each of them takes
+const IOCTL_REQUESTS: [(u32, IoctlArg); 5] = [
+    (FIONBIO, IoctlArg::In(std::mem::size_of::<i32>())),
+    (FIONREAD, IoctlArg::Out(std::mem::size_of::<i32>())),
+    (TIOCGWINSZ, IoctlArg::Out(std::mem::size_of::<WinSize>())),
+    (FIOCLEX, IoctlArg::None),
+    (FIONCLEX, IoctlArg::None),
+];
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/ioctl.2.html
+///
+/// The Linux `ioctl()` syscall manipulates the underlying device parameters of special files. The
+/// meaning and the size of its argument depend on the request, so only the requests listed in
+/// `IOCTL_REQUESTS` are supported: their argument is checked against the cage's vmmap and
+/// translated before the request runs, any other request fails with ENOTTY instead of reaching the
+/// host with an untranslated pointer.
+///
+/// `FIOCLEX` and `FIONCLEX` only change the close-on-exec flag kept by fdtables. In-memory sockets
+/// and pipes answer `FIONBIO` and `FIONREAD` themselves, and are not terminals. Requests on kernel
+/// fds go to the host.
+///
+/// ## Arguments
+/// virtual_fd: virtual file descriptor
+/// request: the device-dependent request code
+/// ptr: address of the argument of the request in the cage
+///
+/// ## Return Type
+/// On success, zero is returned. On error, -1 is returned
+pub fn ioctl_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    request_arg: u64,
+    request_cageid: u64,
+    ptr_arg: u64,
+    ptr_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let request = sc_convert_sysarg_to_u32(request_arg, request_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "ioctl", "Invalide Cage ID");
+    }
+
+    let vfd = match _fcntl_helper(cageid, virtual_fd) {
+        Ok(entry) => entry,
+        Err(e) => return syscall_error(e, "ioctl", "Bad File Descriptor"),
+    };
+    let argtype = match IOCTL_REQUESTS.iter().find(|(known, _)| *known == request) {
+        Some(&(_, argtype)) => argtype,
+        None => return syscall_error(Errno::ENOTTY, "ioctl", "Unsupported ioctl request"),
+    };
+    let (size, prot) = match argtype {
+        IoctlArg::None => (0, PROT_NONE),
+        IoctlArg::In(size) => (size, PROT_READ),
+        IoctlArg::Out(size) => (size, PROT_WRITE),
+    };
+    let ptr = if size == 0 {
+        std::ptr::null_mut()
+    } else {
+        if ptr_arg == 0 {
+            return syscall_error(Errno::EFAULT, "ioctl", "Argument is NULL");
+        }
+        match check_and_convert_addr_ext(ptr_cageid, ptr_arg, size, prot) {
+            Ok(addr) => addr as *mut u8,
+            Err(e) => return syscall_error(e, "ioctl", "Argument is outside of the cage memory"),
+        }
+    };
+
+    if request == FIOCLEX || request == FIONCLEX {
+        return match fdtables::set_cloexec(cageid, virtual_fd, request == FIOCLEX) {
+            Ok(()) => 0,
+            Err(_e) => syscall_error(Errno::EBADF, "ioctl", "Bad File Descriptor"),
+        };
+    }
+    if vfd.fdkind == fs_const::FDKIND_LOOPBACK || vfd.fdkind == fs_const::FDKIND_PIPE {
+        let is_pipe = vfd.fdkind == fs_const::FDKIND_PIPE;
+        return match request {
+            FIONBIO => {
+                let nonblocking = unsafe { *(ptr as *const i32) } != 0;
+                let flags = if is_pipe {
+                    pipe::get_status_flags(vfd.underfd)
+                } else {
+                    loopback::get_status_flags(vfd.underfd)
+                };
+                let flags = if nonblocking {
+                    flags | O_NONBLOCK
+                } else {
+                    flags & !O_NONBLOCK
+                };
+                if is_pipe {
+                    pipe::set_status_flags(vfd.underfd, flags);
+                } else {
+                    loopback::set_status_flags(vfd.underfd, flags);
+                }
+                0
+            }
+            FIONREAD => {
+                let available = if is_pipe {
+                    pipe::bytes_available(vfd.underfd)
+                } else {
+                    loopback::bytes_available(vfd.underfd)
+                };
+                if available < 0 {
+                    return available;
+                }
+                unsafe { *(ptr as *mut i32) = available };
+                0
+            }
+            _ => syscall_error(Errno::ENOTTY, "ioctl", "Not a terminal"),
+        };
+    }
+
+    let ret = unsafe { libc::ioctl(vfd.underfd as i32, request as c_ulong, ptr) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "ioctl");
+    }
+    ret
+}
+
+pub fn clock_gettime_syscall(
     cageid: u64,
     clockid_arg: u64,
     clockid_cageid: u64,
@@ -1367,8 +2579,13 @@ pub fn futex_syscall(
     uaddr2_cageid: u64,
     val3_arg: u64,
     val3_cageid: u64,
-) -> i32{
-    let uaddr = sc_convert_uaddr_to_host(uaddr_arg, uaddr_cageid, cageid);
+) -> i32 {
+    let size = std::mem::size_of::<u32>();
+    let uaddr =
+        match sc_convert_uaddr_to_host_checked(uaddr_arg, uaddr_cageid, cageid, size, PROT_READ) {
+            Ok(addr) => addr,
+            Err(e) => return syscall_error(e, "futex", "Invalid address"),
+        };
     let futex_op = sc_convert_sysarg_to_u32(futex_op_arg, futex_op_cageid, cageid);
     let val = sc_convert_sysarg_to_u32(val_arg, val_cageid, cageid);
     let val2 = sc_convert_sysarg_to_u32(val2_arg, val2_cageid, cageid);
@@ -1414,3 +2631,1662 @@ pub fn nanosleep_time64_syscall(
     }
     ret
 }
+
+/// Copies the fields of a host `stat` structure into the `StatData` layout expected by glibc in the cage.
+pub fn convert_statdata_to_user(statbuf: &mut StatData, libc_statbuf: &stat) {
+    statbuf.st_dev = libc_statbuf.st_dev;
+    statbuf.st_ino = libc_statbuf.st_ino as usize;
+    statbuf.st_mode = libc_statbuf.st_mode;
+    statbuf.st_nlink = libc_statbuf.st_nlink as u32;
+    statbuf.st_uid = libc_statbuf.st_uid;
+    statbuf.st_gid = libc_statbuf.st_gid;
+    statbuf.st_rdev = libc_statbuf.st_rdev;
+    statbuf.st_size = libc_statbuf.st_size as usize;
+    statbuf.st_blksize = libc_statbuf.st_blksize as i32;
+    statbuf.st_blocks = libc_statbuf.st_blocks as u32;
+    statbuf.st_atim = (
+        libc_statbuf.st_atime as u64,
+        libc_statbuf.st_atime_nsec as u64,
+    );
+    statbuf.st_mtim = (
+        libc_statbuf.st_mtime as u64,
+        libc_statbuf.st_mtime_nsec as u64,
+    );
+    statbuf.st_ctim = (
+        libc_statbuf.st_ctime as u64,
+        libc_statbuf.st_ctime_nsec as u64,
+    );
+}
+
+/// Copies the fields of a host `statfs` structure into the `FSData` layout expected by glibc in the cage.
+pub fn convert_fsdata_to_user(databuf: &mut FSData, libc_databuf: &statfs) {
+    databuf.f_type = libc_databuf.f_type as u64;
+    databuf.f_bsize = libc_databuf.f_bsize as u64;
+    databuf.f_blocks = libc_databuf.f_blocks;
+    databuf.f_bfree = libc_databuf.f_bfree;
+    databuf.f_bavail = libc_databuf.f_bavail;
+    databuf.f_files = libc_databuf.f_files;
+    databuf.f_ffiles = libc_databuf.f_ffree;
+    // `fsid_t` keeps its two 32-bit words private, so copy it as a whole
+    databuf.f_fsid = unsafe { std::mem::transmute::<fsid_t, u64>(libc_databuf.f_fsid) };
+    databuf.f_namelen = libc_databuf.f_namelen as u64;
+    databuf.f_frsize = libc_databuf.f_frsize as u64;
+    databuf.f_spare = [0; 32];
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/stat.2.html
+///
+/// Linux `stat()` syscall retrieves information about the file pointed to by `path`. RawPOSIX converts the path
+/// to the host's perspective, calls the kernel with a host `stat` structure, and then copies the result into
+/// the user buffer using the `StatData` layout that glibc expects.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: pointer to the pathname of the file (user's perspective)
+///     - statbuf_arg: pointer to the user buffer that will receive the `StatData` structure
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn stat_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    statbuf_arg: u64,
+    statbuf_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "stat", "Invalide Cage ID");
+    }
+    if statbuf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "stat", "Buffer is null");
+    }
+    // Type conversion
+    let path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+    let size = std::mem::size_of::<StatData>();
+    let statbuf = match sc_convert_addr_to_host_checked(
+        statbuf_arg,
+        statbuf_cageid,
+        cageid,
+        size,
+        PROT_WRITE,
+    ) {
+        Ok(addr) => addr as *mut StatData,
+        Err(e) => return syscall_error(e, "stat", "Invalid address"),
+    };
+
+    let mut libc_statbuf: stat = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::stat(path.as_ptr(), &mut libc_statbuf) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "stat");
+    }
+
+    convert_statdata_to_user(unsafe { &mut *statbuf }, &libc_statbuf);
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/fstat.2.html
+///
+/// Linux `fstat()` syscall is identical to `stat()`, except that the file to be stat-ed is specified by a file
+/// descriptor. RawPOSIX translates the virtual fd to the kernel fd first, then copies the host result into
+/// the user buffer using the `StatData` layout.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - statbuf_arg: pointer to the user buffer that will receive the `StatData` structure
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn fstat_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    statbuf_arg: u64,
+    statbuf_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "fstat", "Invalide Cage ID");
+    }
+    // In-memory pipes have no inode on the host, describe them as an anonymous FIFO
+    if pipe::pipe_end(virtual_fd, vfd_cageid).is_some() {
+        if statbuf_arg == 0 {
+            return syscall_error(Errno::EFAULT, "fstat", "Buffer is null");
+        }
+        let size = std::mem::size_of::<StatData>();
+        let statbuf = match sc_convert_addr_to_host_checked(
+            statbuf_arg,
+            statbuf_cageid,
+            cageid,
+            size,
+            PROT_WRITE,
+        ) {
+            Ok(addr) => addr as *mut StatData,
+            Err(e) => return syscall_error(e, "fstat", "Invalid address"),
+        };
+        let mut libc_statbuf: stat = unsafe { std::mem::zeroed() };
+        libc_statbuf.st_mode = fs_const::S_IFIFO as u32 | fs_const::S_IRUSR | fs_const::S_IWUSR;
+        libc_statbuf.st_nlink = 1;
+        libc_statbuf.st_blksize = fs_const::PAGESIZE as i64;
+        convert_statdata_to_user(unsafe { &mut *statbuf }, &libc_statbuf);
+        return 0;
+    }
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "fstat", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "fstat", "Bad File Descriptor");
+    }
+    if statbuf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "fstat", "Buffer is null");
+    }
+    let size = std::mem::size_of::<StatData>();
+    let statbuf = match sc_convert_addr_to_host_checked(
+        statbuf_arg,
+        statbuf_cageid,
+        cageid,
+        size,
+        PROT_WRITE,
+    ) {
+        Ok(addr) => addr as *mut StatData,
+        Err(e) => return syscall_error(e, "fstat", "Invalid address"),
+    };
+
+    let mut libc_statbuf: stat = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::fstat(kernel_fd, &mut libc_statbuf) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "fstat");
+    }
+
+    convert_statdata_to_user(unsafe { &mut *statbuf }, &libc_statbuf);
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/statfs.2.html
+///
+/// Linux `statfs()` syscall returns information about a mounted filesystem. `path` is the pathname of any file
+/// within the mounted filesystem. The result is copied into the user buffer using the `FSData` layout.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: pointer to the pathname of any file within the filesystem (user's perspective)
+///     - databuf_arg: pointer to the user buffer that will receive the `FSData` structure
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn statfs_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    databuf_arg: u64,
+    databuf_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "statfs", "Invalide Cage ID");
+    }
+    if databuf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "statfs", "Buffer is null");
+    }
+    // Type conversion
+    let path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+    let size = std::mem::size_of::<FSData>();
+    let databuf = match sc_convert_addr_to_host_checked(
+        databuf_arg,
+        databuf_cageid,
+        cageid,
+        size,
+        PROT_WRITE,
+    ) {
+        Ok(addr) => addr as *mut FSData,
+        Err(e) => return syscall_error(e, "statfs", "Invalid address"),
+    };
+
+    let mut libc_databuf: statfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::statfs(path.as_ptr(), &mut libc_databuf) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "statfs");
+    }
+
+    convert_fsdata_to_user(unsafe { &mut *databuf }, &libc_databuf);
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/fstatfs.2.html
+///
+/// Linux `fstatfs()` syscall returns the same information as `statfs()` about an open file referenced by a file
+/// descriptor. RawPOSIX translates the virtual fd to the kernel fd before calling the kernel.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - databuf_arg: pointer to the user buffer that will receive the `FSData` structure
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn fstatfs_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    databuf_arg: u64,
+    databuf_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "fstatfs", "Invalide Cage ID");
+    }
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "fstatfs", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "fstatfs", "Bad File Descriptor");
+    }
+    if databuf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "fstatfs", "Buffer is null");
+    }
+    let size = std::mem::size_of::<FSData>();
+    let databuf = match sc_convert_addr_to_host_checked(
+        databuf_arg,
+        databuf_cageid,
+        cageid,
+        size,
+        PROT_WRITE,
+    ) {
+        Ok(addr) => addr as *mut FSData,
+        Err(e) => return syscall_error(e, "fstatfs", "Invalid address"),
+    };
+
+    let mut libc_databuf: statfs = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::fstatfs(kernel_fd, &mut libc_databuf) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "fstatfs");
+    }
+
+    convert_fsdata_to_user(unsafe { &mut *databuf }, &libc_databuf);
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/access.2.html
+///
+/// Linux `access()` syscall checks whether the calling process can access the file `path` with the given `mode`.
+/// RawPOSIX converts the path to the host's perspective and lets the kernel check the access first. As the
+/// kernel only knows the credentials of the host process, the permission bits of the file are then checked
+/// again against the real user and group ids of the cage.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: pointer to the pathname of the file (user's perspective)
+///     - amode_arg: accessibility check to perform (F_OK, or a mask of R_OK, W_OK and X_OK)
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn access_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    amode_arg: u64,
+    amode_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // Type conversion
+    let path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+    let amode = sc_convert_sysarg_to_i32(amode_arg, amode_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "access", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::access(path.as_ptr(), amode) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "access");
+    }
+
+    // The host allows the access, it must also be allowed to the cage
+    if amode != F_OK {
+        let mut statbuf: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::stat(path.as_ptr(), &mut statbuf) } < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "access");
+        }
+        if !_cage_may_access(cageid, &statbuf, amode) {
+            return syscall_error(Errno::EACCES, "access", "Permission denied");
+        }
+    }
+    ret
+}
+
+/// Checks `amode` against the permission bits of a file like `access()` does, but with the real user
+/// and group ids of the cage instead of those of the host process. Files created by cages are owned by
+/// the host user running Lind, so files owned by that user are treated as owned by the credentials the
+/// first cage was given. Supplementary groups are not modeled.
+fn _cage_may_access(cageid: u64, statbuf: &libc::stat, amode: i32) -> bool {
+    let cage = get_cage(cageid).unwrap();
+    let uid = cage.uid.load(Ordering::SeqCst) as u32;
+    let gid = cage.gid.load(Ordering::SeqCst) as u32;
+
+    let (initial_uid, initial_gid) = initial_credentials();
+    let owner = if statbuf.st_uid == unsafe { libc::geteuid() } {
+        initial_uid
+    } else {
+        statbuf.st_uid
+    };
+    let group = if statbuf.st_gid == unsafe { libc::getegid() } {
+        initial_gid
+    } else {
+        statbuf.st_gid
+    };
+
+    let mode = statbuf.st_mode;
+    if uid == 0 {
+        // Root may read and write anything, and execute files with any execute bit set
+        return amode & X_OK == 0 || mode & S_IFMT == S_IFDIR || mode & 0o111 != 0;
+    }
+    let granted = if owner == uid {
+        (mode >> 6) & 0o7
+    } else if group == gid {
+        (mode >> 3) & 0o7
+    } else {
+        mode & 0o7
+    };
+    (amode as u32) & !granted & 0o7 == 0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/lseek.2.html
+///
+/// Linux `lseek()` syscall repositions the file offset of the open file description associated with the file
+/// descriptor `fd` to `offset` according to `whence`. RawPOSIX translates the virtual fd to the kernel fd first.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - offset_arg: new offset, interpreted according to `whence`
+///     - whence_arg: SEEK_SET, SEEK_CUR, SEEK_END, SEEK_DATA or SEEK_HOLE
+///
+/// Return:
+///     - the resulting offset location from the beginning of the file on success. On error, negative errno is returned.
+///       EOVERFLOW is returned if the resulting offset doesn't fit in the return value, as with the 32-bit Linux
+///       `lseek()`. The offset is still moved in that case.
+pub fn lseek_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    offset_arg: u64,
+    offset_cageid: u64,
+    whence_arg: u64,
+    whence_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "lseek", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "lseek", "Bad File Descriptor");
+    }
+    // Type conversion
+    let offset = sc_convert_sysarg_to_i64(offset_arg, offset_cageid, cageid);
+    let whence = sc_convert_sysarg_to_i32(whence_arg, whence_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "lseek", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::lseek(kernel_fd, offset, whence) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "lseek");
+    }
+    match i32::try_from(ret) {
+        Ok(offset) => offset,
+        Err(_) => syscall_error(
+            Errno::EOVERFLOW,
+            "lseek",
+            "Resulting offset doesn't fit in the return value",
+        ),
+    }
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/truncate.2.html
+///
+/// Linux `truncate()` syscall causes the regular file named by `path` to be truncated (or extended with null
+/// bytes) to a size of precisely `length` bytes. RawPOSIX only converts the path to the host's perspective.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: pointer to the pathname of the file (user's perspective)
+///     - length_arg: the new size of the file in bytes
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn truncate_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    length_arg: u64,
+    length_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // Type conversion
+    let path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+    let length = sc_convert_sysarg_to_i64(length_arg, length_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "truncate", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::truncate(path.as_ptr(), length) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "truncate");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/ftruncate.2.html
+///
+/// Linux `ftruncate()` syscall truncates (or extends) the regular file referenced by `fd` to a size of precisely
+/// `length` bytes. RawPOSIX translates the virtual fd to the kernel fd first.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - length_arg: the new size of the file in bytes
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn ftruncate_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    length_arg: u64,
+    length_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "ftruncate", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "ftruncate", "Bad File Descriptor");
+    }
+    // Type conversion
+    let length = sc_convert_sysarg_to_i64(length_arg, length_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "ftruncate", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::ftruncate(kernel_fd, length) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "ftruncate");
+    }
+    ret
+}
+
+/// Returns the path from user's perspective of the file or directory referenced by a kernel fd. The host
+/// path is read from `/proc/self/fd` and `LIND_ROOT` is stripped, so fds pointing outside of the lind root
+/// are reported as `EACCES`.
+fn _kernel_fd_to_cage_path(kernel_fd: i32) -> Result<PathBuf, Errno> {
+    let host_path = match std::fs::read_link(format!("/proc/self/fd/{}", kernel_fd)) {
+        Ok(path) => path,
+        Err(_) => return Err(Errno::EBADF),
+    };
+    match host_path.to_str().and_then(strip_lind_root) {
+        Some(path) => Ok(path),
+        None => Err(Errno::EACCES),
+    }
+}
+
+/// Resolves the `(dirfd, path)` pair taken by the `*at` family of syscalls into a single path from host's
+/// perspective. Absolute paths and `AT_FDCWD` are resolved like any other path argument (against the
+/// cage's cwd); relative paths are resolved against the directory referenced by `dirfd`. Resolving to a
+/// full path (instead of passing the kernel dirfd through) keeps `..` from escaping `LIND_ROOT`.
+fn _at_path_helper(
+    cageid: u64,
+    dirfd_arg: u64,
+    dirfd_cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    syscall_name: &str,
+) -> Result<CString, i32> {
+    let dirfd = sc_convert_sysarg_to_i32(dirfd_arg, dirfd_cageid, cageid);
+    // Only the first byte of the path is checked, its length isn't known before it is read
+    let raw_path =
+        match sc_convert_addr_to_host_checked(path_arg, path_cageid, cageid, 1, PROT_READ)
+            .map(|addr| get_cstr(addr as u64))
+        {
+            Ok(Ok(path)) => path,
+            _ => return Err(syscall_error(Errno::EFAULT, syscall_name, "Invalid path")),
+        };
+
+    if dirfd == AT_FDCWD || raw_path.starts_with('/') {
+        return Ok(sc_convert_path_to_host(path_arg, path_cageid, cageid));
+    }
+
+    let kernel_fd = convert_fd_to_host(dirfd_arg, dirfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return Err(syscall_error(Errno::EFAULT, syscall_name, "Invalid Cage ID"));
+    } else if kernel_fd == -9 {
+        return Err(syscall_error(Errno::EBADF, syscall_name, "Bad File Descriptor"));
+    }
+    let dirpath = match _kernel_fd_to_cage_path(kernel_fd) {
+        Ok(path) => path,
+        Err(e) => return Err(syscall_error(e, syscall_name, "Cannot resolve dirfd")),
+    };
+    Ok(add_lind_root(cageid, dirpath.join(raw_path).to_str().unwrap()))
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/unlink.2.html
+///
+/// Linux `unlink()` syscall deletes a name from the filesystem. Since path seen by user is different from actual
+/// path on host, we need to convert the path first. All other operations are handled by the host.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: This argument points to a pathname naming the file. User's perspective.
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn unlink_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // Type conversion
+    let path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "unlink", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::unlink(path.as_ptr()) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "unlink");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/unlinkat.2.html
+///
+/// Linux `unlinkat()` syscall operates in exactly the same way as either `unlink()` or `rmdir()` (depending on
+/// whether `flags` includes the `AT_REMOVEDIR` flag), except that a relative `path` is interpreted relative to
+/// the directory referred to by `dirfd`.
+///
+/// Input:
+///     - cageid: current cageid
+///     - dirfd_arg: virtual fd of the directory that a relative path is resolved against, or AT_FDCWD
+///     - path_arg: This argument points to a pathname naming the file. User's perspective.
+///     - flags_arg: either 0 or AT_REMOVEDIR
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn unlinkat_syscall(
+    cageid: u64,
+    dirfd_arg: u64,
+    dirfd_cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    flags_arg: u64,
+    flags_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "unlinkat", "Invalide Cage ID");
+    }
+    let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
+    if flags & !AT_REMOVEDIR != 0 {
+        return syscall_error(Errno::EINVAL, "unlinkat", "Invalid flags");
+    }
+    let path = match _at_path_helper(cageid, dirfd_arg, dirfd_cageid, path_arg, path_cageid, "unlinkat") {
+        Ok(path) => path,
+        Err(e) => return e,
+    };
+
+    // The path has already been resolved against dirfd, so the host call is relative to nothing
+    let ret = unsafe { libc::unlinkat(AT_FDCWD, path.as_ptr(), flags) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "unlinkat");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/link.2.html
+///
+/// Linux `link()` syscall creates a new link (also known as a hard link) to an existing file. Both paths are
+/// converted to host's perspective before calling the kernel.
+///
+/// Input:
+///     - cageid: current cageid
+///     - oldpath_arg: pointer to the existing pathname. User's perspective.
+///     - newpath_arg: pointer to the pathname of the new link. User's perspective.
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn link_syscall(
+    cageid: u64,
+    oldpath_arg: u64,
+    oldpath_cageid: u64,
+    newpath_arg: u64,
+    newpath_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // Type conversion
+    let oldpath = sc_convert_path_to_host(oldpath_arg, oldpath_cageid, cageid);
+    let newpath = sc_convert_path_to_host(newpath_arg, newpath_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "link", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::link(oldpath.as_ptr(), newpath.as_ptr()) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "link");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/rename.2.html
+///
+/// Linux `rename()` syscall renames a file, moving it between directories if required. Both paths are converted
+/// to host's perspective before calling the kernel.
+///
+/// Input:
+///     - cageid: current cageid
+///     - oldpath_arg: pointer to the existing pathname. User's perspective.
+///     - newpath_arg: pointer to the new pathname. User's perspective.
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn rename_syscall(
+    cageid: u64,
+    oldpath_arg: u64,
+    oldpath_cageid: u64,
+    newpath_arg: u64,
+    newpath_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // Type conversion
+    let oldpath = sc_convert_path_to_host(oldpath_arg, oldpath_cageid, cageid);
+    let newpath = sc_convert_path_to_host(newpath_arg, newpath_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "rename", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::rename(oldpath.as_ptr(), newpath.as_ptr()) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "rename");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/rmdir.2.html
+///
+/// Linux `rmdir()` syscall deletes a directory, which must be empty. Since path seen by user is different from
+/// actual path on host, we need to convert the path first.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: This argument points to a pathname naming the directory. User's perspective.
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn rmdir_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // Type conversion
+    let path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "rmdir", "Invalide Cage ID");
+    }
+
+    let ret = unsafe { libc::rmdir(path.as_ptr()) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "rmdir");
+    }
+    ret
+}
+
+/// Checks that `host_path` is a directory the cage is allowed to enter, then records `cage_path` as the new
+/// working directory of the cage. The host process' own cwd is never changed because all cages share it.
+fn _chdir_helper(cageid: u64, cage_path: PathBuf, host_path: &CString, syscall_name: &str) -> i32 {
+    let mut statbuf: stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::stat(host_path.as_ptr(), &mut statbuf) } < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, syscall_name);
+    }
+    if (statbuf.st_mode & S_IFMT) != S_IFDIR {
+        return syscall_error(Errno::ENOTDIR, syscall_name, "Not a directory");
+    }
+    if unsafe { libc::access(host_path.as_ptr(), X_OK) } < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, syscall_name);
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    *cage.cwd.write() = Arc::new(cage_path);
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/chdir.2.html
+///
+/// Linux `chdir()` syscall changes the current working directory of the calling process. In Lind-WASM all cages
+/// run in the same host process, so instead of changing the host cwd we update `Cage::cwd`, which is used by
+/// `normpath` to resolve every relative path argument of that cage.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: This argument points to a pathname naming the new working directory. User's perspective.
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn chdir_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "chdir", "Invalide Cage ID");
+    }
+    // Type conversion
+    let cage_path = sc_convert_path_to_cage(path_arg, path_cageid, cageid);
+    let host_path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+
+    _chdir_helper(cageid, cage_path, &host_path, "chdir")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/fchdir.2.html
+///
+/// Linux `fchdir()` syscall is identical to `chdir()`; the only difference is that the directory is given as an
+/// open file descriptor. The directory path is recovered from the kernel fd and stored in `Cage::cwd` from
+/// user's perspective.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor of the new working directory
+///
+/// Return:
+///     - return zero on success. On error, negative errno is returned to indicate the error.
+pub fn fchdir_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "fchdir", "Invalide Cage ID");
+    }
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "fchdir", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "fchdir", "Bad File Descriptor");
+    }
+
+    let cage_path = match _kernel_fd_to_cage_path(kernel_fd) {
+        Ok(path) => path,
+        Err(e) => return syscall_error(e, "fchdir", "Cannot resolve directory of fd"),
+    };
+    let host_path = add_lind_root(cageid, cage_path.to_str().unwrap());
+
+    _chdir_helper(cageid, cage_path, &host_path, "fchdir")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getcwd.2.html
+///
+/// Linux `getcwd()` syscall copies the absolute pathname of the current working directory to the buffer `buf`.
+/// The working directory of a cage is kept in `Cage::cwd` from user's perspective, so `LIND_ROOT` is never
+/// exposed to the cage.
+///
+/// Input:
+///     - cageid: current cageid
+///     - buf_arg: pointer to the user buffer that will receive the null-terminated path
+///     - size_arg: size of the user buffer
+///
+/// Return:
+///     - the length of the path including the terminating null byte on success. `ERANGE` is returned if the
+///       buffer is too small.
+pub fn getcwd_syscall(
+    cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    size_arg: u64,
+    size_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getcwd", "Invalide Cage ID");
+    }
+    if buf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "getcwd", "Buffer is null");
+    }
+    let size = sc_convert_sysarg_to_usize(size_arg, size_cageid, cageid);
+    if size == 0 {
+        return syscall_error(Errno::EINVAL, "getcwd", "Size is zero");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    let cwd = cage.cwd.read().clone();
+    let bytes = cwd.to_str().unwrap().as_bytes();
+    // The path needs room for the terminating null byte
+    if bytes.len() + 1 > size {
+        return syscall_error(Errno::ERANGE, "getcwd", "Buffer too small");
+    }
+
+    let buf = match sc_convert_addr_to_host_checked(
+        buf_arg,
+        buf_cageid,
+        cageid,
+        bytes.len() + 1,
+        PROT_WRITE,
+    ) {
+        Ok(addr) => addr,
+        Err(e) => return syscall_error(e, "getcwd", "Invalid address"),
+    };
+    unsafe {
+        std::ptr::copy_nonoverlapping(bytes.as_ptr(), buf, bytes.len());
+        *buf.add(bytes.len()) = 0;
+    }
+    (bytes.len() + 1) as i32
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getdents.2.html
+///
+/// Linux `getdents()` syscall reads several directory entries from the directory referred to by `fd` into the
+/// buffer. RawPOSIX reads `linux_dirent64` records from the kernel into a host buffer and then repacks each
+/// record into the `ClippedDirent` layout expected by glibc: the `ClippedDirent` header, the null-terminated
+/// name, padding up to an 8-byte boundary, and `d_type` in the last byte of the record (same as the kernel's
+/// `linux_dirent`).
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor of an open directory
+///     - buf_arg: pointer to the user buffer that will receive the directory entries
+///     - nbytes_arg: size of the user buffer
+///
+/// Return:
+///     - number of bytes written into the user buffer on success, zero at the end of the directory. On error,
+///       negative errno is returned to indicate the error.
+pub fn getdents_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    nbytes_arg: u64,
+    nbytes_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getdents", "Invalide Cage ID");
+    }
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "getdents", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "getdents", "Bad File Descriptor");
+    }
+    if buf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "getdents", "Buffer is null");
+    }
+    let nbytes = sc_convert_sysarg_to_u32(nbytes_arg, nbytes_cageid, cageid) as usize;
+    let buf = match sc_convert_addr_to_host_checked(buf_arg, buf_cageid, cageid, nbytes, PROT_WRITE)
+    {
+        Ok(addr) => addr,
+        Err(e) => return syscall_error(e, "getdents", "Invalid address"),
+    };
+
+    let mut kernel_buf = vec![0u8; nbytes];
+    let ret = unsafe {
+        libc::syscall(
+            SYS_getdents64,
+            kernel_fd,
+            kernel_buf.as_mut_ptr() as *mut c_void,
+            nbytes,
+        ) as i32
+    };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "getdents");
+    }
+
+    // linux_dirent64: d_ino (8) | d_off (8) | d_reclen (2) | d_type (1) | d_name
+    let header_size = CLIPPED_DIRENT_SIZE as usize;
+    let mut kernel_offset = 0;
+    let mut user_offset = 0;
+    let mut last_d_off: Option<u64> = None;
+    while kernel_offset < ret as usize {
+        let record = &kernel_buf[kernel_offset..];
+        let d_ino = u64::from_ne_bytes(record[0..8].try_into().unwrap());
+        let d_off = u64::from_ne_bytes(record[8..16].try_into().unwrap());
+        let d_reclen = u16::from_ne_bytes(record[16..18].try_into().unwrap()) as usize;
+        let d_type = record[18];
+        let name_field = &record[19..d_reclen];
+        let name_len = name_field.iter().position(|&c| c == 0).unwrap_or(name_field.len());
+
+        // header + name + null byte + d_type, rounded up to 8 bytes
+        let user_reclen = (header_size + name_len + 2 + 7) & !7;
+        if user_offset + user_reclen > nbytes {
+            // Rewind the directory so the entries that did not fit are returned by the next call
+            match last_d_off {
+                Some(off) => unsafe {
+                    libc::lseek(kernel_fd, off as i64, SEEK_SET);
+                },
+                None => return syscall_error(Errno::EINVAL, "getdents", "Result buffer is too small"),
+            }
+            break;
+        }
+
+        unsafe {
+            let entry = buf.add(user_offset);
+            std::ptr::write_bytes(entry, 0, user_reclen);
+            std::ptr::write_unaligned(
+                entry as *mut ClippedDirent,
+                ClippedDirent {
+                    d_ino,
+                    d_off,
+                    d_reclen: user_reclen as u16,
+                },
+            );
+            std::ptr::copy_nonoverlapping(name_field.as_ptr(), entry.add(header_size), name_len);
+            *entry.add(user_reclen - 1) = d_type;
+        }
+
+        last_d_off = Some(d_off);
+        kernel_offset += d_reclen;
+        user_offset += user_reclen;
+    }
+    user_offset as i32
+}
+
+/// Copies the target of a symbolic link read from the host into the user buffer. Targets that point inside
+/// `LIND_ROOT` are converted back to user's perspective. Like Linux, the result is truncated to `bufsiz` and
+/// is not null-terminated.
+fn _readlink_to_user(
+    cageid: u64,
+    host_path: &CString,
+    buf_arg: u64,
+    buf_cageid: u64,
+    bufsiz: usize,
+    syscall_name: &str,
+) -> i32 {
+    let mut target = vec![0u8; PATH_MAX];
+    let len = unsafe {
+        libc::readlink(
+            host_path.as_ptr(),
+            target.as_mut_ptr() as *mut c_char,
+            target.len(),
+        )
+    };
+    if len < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, syscall_name);
+    }
+    target.truncate(len as usize);
+
+    let stripped = std::str::from_utf8(&target).ok().and_then(strip_lind_root);
+    let target = match stripped {
+        Some(path) => path.into_os_string().into_string().unwrap().into_bytes(),
+        None => target,
+    };
+
+    let count = std::cmp::min(target.len(), bufsiz);
+    let buf = match sc_convert_addr_to_host_checked(buf_arg, buf_cageid, cageid, count, PROT_WRITE)
+    {
+        Ok(addr) => addr,
+        Err(e) => return syscall_error(e, syscall_name, "Invalid address"),
+    };
+    unsafe {
+        std::ptr::copy_nonoverlapping(target.as_ptr(), buf, count);
+    }
+    count as i32
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/readlink.2.html
+///
+/// Linux `readlink()` syscall places the contents of the symbolic link `path` in the buffer `buf`. Link targets
+/// that resolve inside `LIND_ROOT` are converted back to user's perspective, so `LIND_ROOT` is never exposed.
+///
+/// Input:
+///     - cageid: current cageid
+///     - path_arg: pointer to the pathname of the symbolic link. User's perspective.
+///     - buf_arg: pointer to the user buffer that will receive the link target
+///     - bufsiz_arg: size of the user buffer
+///
+/// Return:
+///     - the number of bytes placed in `buf` on success. On error, negative errno is returned.
+pub fn readlink_syscall(
+    cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    bufsiz_arg: u64,
+    bufsiz_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "readlink", "Invalide Cage ID");
+    }
+    if buf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "readlink", "Buffer is null");
+    }
+    let bufsiz = sc_convert_sysarg_to_usize(bufsiz_arg, bufsiz_cageid, cageid);
+    if bufsiz == 0 {
+        return syscall_error(Errno::EINVAL, "readlink", "Buffer size is zero");
+    }
+    let path = sc_convert_path_to_host(path_arg, path_cageid, cageid);
+
+    _readlink_to_user(cageid, &path, buf_arg, buf_cageid, bufsiz, "readlink")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/readlinkat.2.html
+///
+/// Linux `readlinkat()` syscall operates in exactly the same way as `readlink()`, except that a relative `path` is
+/// interpreted relative to the directory referred to by `dirfd`.
+///
+/// Input:
+///     - cageid: current cageid
+///     - dirfd_arg: virtual fd of the directory that a relative path is resolved against, or AT_FDCWD
+///     - path_arg: pointer to the pathname of the symbolic link. User's perspective.
+///     - buf_arg: pointer to the user buffer that will receive the link target
+///     - bufsiz_arg: size of the user buffer
+///
+/// Return:
+///     - the number of bytes placed in `buf` on success. On error, negative errno is returned.
+pub fn readlinkat_syscall(
+    cageid: u64,
+    dirfd_arg: u64,
+    dirfd_cageid: u64,
+    path_arg: u64,
+    path_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    bufsiz_arg: u64,
+    bufsiz_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg5, arg5_cageid) && sc_unusedarg(arg6, arg6_cageid)) {
+        return syscall_error(Errno::EFAULT, "readlinkat", "Invalide Cage ID");
+    }
+    if buf_arg == 0 {
+        return syscall_error(Errno::EFAULT, "readlinkat", "Buffer is null");
+    }
+    let bufsiz = sc_convert_sysarg_to_usize(bufsiz_arg, bufsiz_cageid, cageid);
+    if bufsiz == 0 {
+        return syscall_error(Errno::EINVAL, "readlinkat", "Buffer size is zero");
+    }
+    let path = match _at_path_helper(cageid, dirfd_arg, dirfd_cageid, path_arg, path_cageid, "readlinkat") {
+        Ok(path) => path,
+        Err(e) => return e,
+    };
+
+    _readlink_to_user(cageid, &path, buf_arg, buf_cageid, bufsiz, "readlinkat")
+}
+
+/// Translates a guest `iovec` array into host `iovec`s for `readv()` / `writev()`.
+///
+/// Unlike the plain base + offset translation used for single buffers, both the iovec array itself and every
+/// buffer it describes are validated against the cage's vmmap (`check_and_convert_addr_ext`), so one bad entry
+/// fails the whole call with `EFAULT` before anything reaches the host, same as Linux.
+///
+/// Input:
+///     - iov_arg: pointer to the guest `WasmIovec` array
+///     - iov_cageid: cage that owns the iovec array and the buffers it points to
+///     - iovcnt: number of entries in the array
+///     - prot: protection the buffers must have (PROT_READ for writev, PROT_WRITE for readv)
+///     - syscall_name: used for error reporting
+///
+/// Return:
+///     - host iovecs ready to be passed to the kernel, or the negative errno to return to the cage
+pub(crate) fn _iovec_to_host(
+    iov_arg: u64,
+    iov_cageid: u64,
+    iovcnt: i32,
+    prot: i32,
+    syscall_name: &str,
+) -> Result<Vec<iovec>, i32> {
+    if iovcnt < 0 || iovcnt > fs_const::IOV_MAX {
+        return Err(syscall_error(Errno::EINVAL, syscall_name, "Invalid iovcnt"));
+    }
+    if iovcnt == 0 {
+        return Ok(Vec::new());
+    }
+
+    let array_len = iovcnt as usize * std::mem::size_of::<WasmIovec>();
+    let host_array = match check_and_convert_addr_ext(iov_cageid, iov_arg, array_len, PROT_READ) {
+        Ok(addr) => addr as *const WasmIovec,
+        Err(e) => return Err(syscall_error(e, syscall_name, "Invalid iovec array")),
+    };
+    let guest_iovs = unsafe { std::slice::from_raw_parts(host_array, iovcnt as usize) };
+
+    let mut total_len: usize = 0;
+    let mut host_iovs = Vec::with_capacity(iovcnt as usize);
+    for guest_iov in guest_iovs {
+        let len = guest_iov.iov_len as usize;
+        // The total length must fit into the return value
+        total_len = match total_len.checked_add(len) {
+            Some(total) if total <= i32::MAX as usize => total,
+            _ => return Err(syscall_error(Errno::EINVAL, syscall_name, "Total length overflows")),
+        };
+        let base = if len == 0 {
+            // Zero-length entries are never dereferenced by the kernel
+            std::ptr::null_mut()
+        } else {
+            match check_and_convert_addr_ext(iov_cageid, guest_iov.iov_base as u64, len, prot) {
+                Ok(addr) => addr as *mut c_void,
+                Err(e) => return Err(syscall_error(e, syscall_name, "Invalid iovec buffer")),
+            }
+        };
+        host_iovs.push(iovec {
+            iov_base: base,
+            iov_len: len,
+        });
+    }
+    Ok(host_iovs)
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/readv.2.html
+///
+/// Linux `readv()` syscall reads `iovcnt` buffers from the file associated with `fd` into the buffers described
+/// by `iov` ("scatter input"). Every iovec entry is validated and translated through the cage's vmmap before the
+/// host call. glibc does not route `readv` through lind yet (there is no `READV_SYSCALL` in
+/// `lind_syscall_num.h`), so this is currently reachable by grates only.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - iov_arg: pointer to the guest iovec array
+///     - iovcnt_arg: number of entries in the iovec array
+///
+/// Return:
+///     - number of bytes read on success. On error, negative errno is returned to indicate the error.
+pub fn readv_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    iov_arg: u64,
+    iov_cageid: u64,
+    iovcnt_arg: u64,
+    iovcnt_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // In-memory sockets have no kernel fd, the buffers are handed over as a single message
+    if let Some(sockid) = loopback::loopback_id(virtual_fd, vfd_cageid) {
+        let iovcnt = sc_convert_sysarg_to_i32(iovcnt_arg, iovcnt_cageid, cageid);
+        let iovs = match _iovec_to_host(iov_arg, iov_cageid, iovcnt, PROT_WRITE, "readv") {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        return match loopback::recvv(cageid, sockid, &iovs, 0) {
+            Ok((len, _, _, _)) => len as i32,
+            Err(e) => e,
+        };
+    }
+    if let Some(pipeend) = pipe::pipe_end(virtual_fd, vfd_cageid) {
+        let iovcnt = sc_convert_sysarg_to_i32(iovcnt_arg, iovcnt_cageid, cageid);
+        let iovs = match _iovec_to_host(iov_arg, iov_cageid, iovcnt, PROT_WRITE, "readv") {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        return pipe::readv(cageid, pipeend, &iovs);
+    }
+
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "readv", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "readv", "Bad File Descriptor");
+    }
+    let iovcnt = sc_convert_sysarg_to_i32(iovcnt_arg, iovcnt_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "readv", "Invalide Cage ID");
+    }
+
+    let iovs = match _iovec_to_host(iov_arg, iov_cageid, iovcnt, PROT_WRITE, "readv") {
+        Ok(iovs) => iovs,
+        Err(e) => return e,
+    };
+
+    let ret = unsafe { libc::readv(kernel_fd, iovs.as_ptr(), iovs.len() as i32) as i32 };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "readv");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/writev.2.html
+///
+/// Linux `writev()` syscall writes `iovcnt` buffers of data described by `iov` to the file associated with `fd`
+/// ("gather output"). Every iovec entry is validated and translated through the cage's vmmap before the host
+/// call. The host `writev()` is issued once for the whole array, so a short write is returned to the cage as
+/// is, exactly like Linux.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - iov_arg: pointer to the guest iovec array
+///     - iovcnt_arg: number of entries in the iovec array
+///
+/// Return:
+///     - number of bytes written on success. On error, negative errno is returned to indicate the error.
+pub fn writev_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    iov_arg: u64,
+    iov_cageid: u64,
+    iovcnt_arg: u64,
+    iovcnt_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // In-memory sockets have no kernel fd, the buffers are handed over as a single message
+    if let Some(sockid) = loopback::loopback_id(virtual_fd, vfd_cageid) {
+        let iovcnt = sc_convert_sysarg_to_i32(iovcnt_arg, iovcnt_cageid, cageid);
+        let iovs = match _iovec_to_host(iov_arg, iov_cageid, iovcnt, PROT_READ, "writev") {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        return loopback::sendv(cageid, sockid, &iovs, 0, std::ptr::null(), &mut None);
+    }
+    if let Some(pipeend) = pipe::pipe_end(virtual_fd, vfd_cageid) {
+        let iovcnt = sc_convert_sysarg_to_i32(iovcnt_arg, iovcnt_cageid, cageid);
+        let iovs = match _iovec_to_host(iov_arg, iov_cageid, iovcnt, PROT_READ, "writev") {
+            Ok(iovs) => iovs,
+            Err(e) => return e,
+        };
+        return pipe::writev(cageid, pipeend, &iovs);
+    }
+
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "writev", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "writev", "Bad File Descriptor");
+    }
+    let iovcnt = sc_convert_sysarg_to_i32(iovcnt_arg, iovcnt_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "writev", "Invalide Cage ID");
+    }
+
+    let iovs = match _iovec_to_host(iov_arg, iov_cageid, iovcnt, PROT_READ, "writev") {
+        Ok(iovs) => iovs,
+        Err(e) => return e,
+    };
+
+    let ret = unsafe { libc::writev(kernel_fd, iovs.as_ptr(), iovs.len() as i32) as i32 };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "writev");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/pread.2.html
+///
+/// Linux `pread()` syscall reads up to `count` bytes from file descriptor `fd` at offset `offset` (from the start of
+/// the file) into the buffer. The file offset is not changed. RawPOSIX translates the virtual fd and the buffer
+/// pointer before calling the kernel; `EFAULT` is returned if the buffer isn't mapped writable in the cage.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - buf_arg: pointer to the buffer that will receive the data
+///     - count_arg: maximum number of bytes to read
+///     - offset_arg: file offset to read from
+///
+/// Return:
+///     - number of bytes read on success. On error, negative errno is returned to indicate the error.
+pub fn pread_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    count_arg: u64,
+    count_cageid: u64,
+    offset_arg: u64,
+    offset_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "pread", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "pread", "Bad File Descriptor");
+    }
+
+    let count = sc_convert_sysarg_to_usize(count_arg, count_cageid, cageid);
+    let offset = sc_convert_sysarg_to_i64(offset_arg, offset_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg5, arg5_cageid) && sc_unusedarg(arg6, arg6_cageid)) {
+        return syscall_error(Errno::EFAULT, "pread", "Invalide Cage ID");
+    }
+    // The whole buffer is checked against the vmmap, the same way `_iovec_to_host` checks each iovec
+    let buf = if count == 0 {
+        std::ptr::null_mut()
+    } else {
+        match check_and_convert_addr_ext(buf_cageid, buf_arg, count, PROT_WRITE) {
+            Ok(addr) => addr as *mut u8,
+            Err(e) => return syscall_error(e, "pread", "Invalid buffer"),
+        }
+    };
+
+    let ret = unsafe { libc::pread(kernel_fd, buf as *mut c_void, count, offset) as i32 };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "pread");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/pwrite.2.html
+///
+/// Linux `pwrite()` syscall writes up to `count` bytes from the buffer to the file descriptor `fd` at offset
+/// `offset`. The file offset is not changed. RawPOSIX translates the virtual fd and the buffer pointer before
+/// calling the kernel; `EFAULT` is returned if the buffer isn't mapped readable in the cage.
+///
+/// Input:
+///     - cageid: current cageid
+///     - virtual_fd: virtual file descriptor, needs to be translated to kernel fd
+///     - buf_arg: pointer to the buffer that stores the data
+///     - count_arg: number of bytes to write
+///     - offset_arg: file offset to write to
+///
+/// Return:
+///     - number of bytes written on success. On error, negative errno is returned to indicate the error.
+pub fn pwrite_syscall(
+    cageid: u64,
+    virtual_fd: u64,
+    vfd_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    count_arg: u64,
+    count_cageid: u64,
+    offset_arg: u64,
+    offset_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let kernel_fd = convert_fd_to_host(virtual_fd, vfd_cageid, cageid);
+    if kernel_fd == -1 {
+        return syscall_error(Errno::EFAULT, "pwrite", "Invalid Cage ID");
+    } else if kernel_fd == -9 {
+        return syscall_error(Errno::EBADF, "pwrite", "Bad File Descriptor");
+    }
+
+    let count = sc_convert_sysarg_to_usize(count_arg, count_cageid, cageid);
+    let offset = sc_convert_sysarg_to_i64(offset_arg, offset_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg5, arg5_cageid) && sc_unusedarg(arg6, arg6_cageid)) {
+        return syscall_error(Errno::EFAULT, "pwrite", "Invalide Cage ID");
+    }
+    // The whole buffer is checked against the vmmap, the same way `_iovec_to_host` checks each iovec
+    let buf = if count == 0 {
+        std::ptr::null_mut()
+    } else {
+        match check_and_convert_addr_ext(buf_cageid, buf_arg, count, PROT_READ) {
+            Ok(addr) => addr as *mut u8,
+            Err(e) => return syscall_error(e, "pwrite", "Invalid buffer"),
+        }
+    };
+
+    let ret = unsafe { libc::pwrite(kernel_fd, buf as *const c_void, count, offset) as i32 };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "pwrite");
+    }
+    ret
+}
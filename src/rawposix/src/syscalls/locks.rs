@@ -0,0 +1,379 @@
+//! Advisory file locks
+//!
+//! All cages run in one host process, so the host kernel can't tell their POSIX record locks
+//! apart: a record lock taken by one cage never conflicts with a lock of another cage. Record
+//! locks (`fcntl()` with `F_SETLK`, `F_SETLKW` and `F_GETLK`) and `flock()` locks are therefore
+//! kept here, in one table shared by every cage, keyed by the host device and inode of the file.
+//!
+//! A record lock is owned by a cage. As on Linux, it isn't inherited by a forked child, and a
+//! cage loses all its record locks on a file when it closes any of its fds for that file, or
+//! when it exits.
+//!
+//! A `flock()` lock is owned by an open file description, which every fd made from it by `dup`,
+//! `fcntl(F_DUPFD)` or fork shares. fdtables gives those fds the same `underfd`, except `dup()`
+//! and `dup2()` which duplicate the kernel fd, so they call `dup_description` for the new kernel
+//! fd to join the description of the old one. A description, and its lock, is gone once the last
+//! of its fds is closed. The owner set by `fcntl(F_SETOWN)` belongs to the description as well.
+use once_cell::sync::Lazy;
+use parking_lot::{Condvar, Mutex};
+use std::collections::HashMap;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{F_UNLCK, F_WRLCK};
+
+/// Host (device, inode) of a locked file
+pub type FileId = (u64, u64);
+
+/// `(fdkind, underfd)` of the fds referring to an open file description
+type DescKey = (u32, u64);
+
+/// End of a record lock that extends to the end of the file, however far it grows
+pub const LOCK_TO_EOF: u64 = u64::MAX;
+
+/// A record lock on the bytes `start..end` of a file
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RecordLock {
+    /// Cage holding the lock
+    pub owner: u64,
+    /// `F_RDLCK` or `F_WRLCK`
+    pub ltype: i16,
+    pub start: u64,
+    pub end: u64,
+}
+
+impl RecordLock {
+    fn overlaps(&self, start: u64, end: u64) -> bool {
+        self.start < end && start < self.end
+    }
+
+    /// Whether this lock keeps `owner` from taking a lock of type `ltype` on `start..end`
+    fn conflicts(&self, owner: u64, ltype: i16, start: u64, end: u64) -> bool {
+        self.owner != owner
+            && self.overlaps(start, end)
+            && (self.ltype == F_WRLCK || ltype == F_WRLCK)
+    }
+}
+
+#[derive(Default)]
+struct FileLocks {
+    records: Vec<RecordLock>,
+    /// `flock()` locks as (description, exclusive)
+    flocks: Vec<(u64, bool)>,
+}
+
+impl FileLocks {
+    fn is_empty(&self) -> bool {
+        self.records.is_empty() && self.flocks.is_empty()
+    }
+}
+
+struct Description {
+    /// Number of `DescKey`s referring to the description
+    refs: usize,
+    /// Process (or process group, when negative) set by `F_SETOWN`
+    owner: i32,
+    /// File the description holds a `flock()` lock on
+    flocked: Option<FileId>,
+}
+
+#[derive(Default)]
+struct LockTable {
+    files: HashMap<FileId, FileLocks>,
+    /// Descriptions are only tracked once they have state, i.e. a `flock()` lock or an owner, or
+    /// once `dup()` gives them a second kernel fd
+    desc_ids: HashMap<DescKey, u64>,
+    descriptions: HashMap<u64, Description>,
+    next_descid: u64,
+    /// Cages sleeping in `F_SETLKW`, and the cages holding the locks they wait for
+    waiting: HashMap<u64, Vec<u64>>,
+}
+
+impl LockTable {
+    /// Returns the id of the description behind `key`, which is created if it has no state yet
+    fn description(&mut self, key: DescKey) -> u64 {
+        if let Some(&id) = self.desc_ids.get(&key) {
+            return id;
+        }
+        let id = self.next_descid;
+        self.next_descid += 1;
+        self.desc_ids.insert(key, id);
+        self.descriptions.insert(
+            id,
+            Description {
+                refs: 1,
+                owner: 0,
+                flocked: None,
+            },
+        );
+        id
+    }
+
+    /// Whether `cageid` waiting for `holders` would wait, through other sleeping cages, for
+    /// itself
+    fn would_deadlock(&self, cageid: u64, holders: &[u64]) -> bool {
+        let mut stack = holders.to_vec();
+        let mut seen = vec![];
+        while let Some(holder) = stack.pop() {
+            if holder == cageid {
+                return true;
+            }
+            if seen.contains(&holder) {
+                continue;
+            }
+            seen.push(holder);
+            if let Some(next) = self.waiting.get(&holder) {
+                stack.extend_from_slice(next);
+            }
+        }
+        false
+    }
+
+    /// Forgets `key`, dropping its description once nothing refers to it anymore
+    fn forget(&mut self, key: DescKey) {
+        let Some(id) = self.desc_ids.remove(&key) else {
+            return;
+        };
+        let desc = self.descriptions.get_mut(&id).unwrap();
+        desc.refs -= 1;
+        if desc.refs == 0 {
+            self.unflock(id);
+            self.descriptions.remove(&id);
+        }
+    }
+
+    /// Drops the `flock()` lock of description `id`, if it holds one
+    fn unflock(&mut self, id: u64) {
+        let Some(file) = self
+            .descriptions
+            .get_mut(&id)
+            .and_then(|desc| desc.flocked.take())
+        else {
+            return;
+        };
+        if let Some(locks) = self.files.get_mut(&file) {
+            locks.flocks.retain(|&(holder, _)| holder != id);
+            if locks.is_empty() {
+                self.files.remove(&file);
+            }
+        }
+        LOCKS_CV.notify_all();
+    }
+}
+
+static LOCKS: Lazy<Mutex<LockTable>> = Lazy::new(|| Mutex::new(LockTable::default()));
+/// Signalled whenever a lock is released
+static LOCKS_CV: Condvar = Condvar::new();
+
+/// Returns the first lock keeping `cageid` from taking a lock of type `ltype` on `start..end` of
+/// `file`, if any
+pub fn get_record_lock(
+    cageid: u64,
+    file: FileId,
+    ltype: i16,
+    start: u64,
+    end: u64,
+) -> Option<RecordLock> {
+    let table = LOCKS.lock();
+    table.files.get(&file).and_then(|locks| {
+        locks
+            .records
+            .iter()
+            .find(|lock| lock.conflicts(cageid, ltype, start, end))
+            .copied()
+    })
+}
+
+/// Sets the lock `cageid` holds on `start..end` of `file` to `ltype`, `F_UNLCK` unlocks the range.
+/// The locks of the cage are split or merged as needed, like Linux does.
+///
+/// If another cage holds a conflicting lock, returns EAGAIN unless `wait` is set, in which case
+/// the call sleeps until it can take the lock, or returns EDEADLK if it would wait forever.
+pub fn set_record_lock(
+    cageid: u64,
+    file: FileId,
+    ltype: i16,
+    start: u64,
+    end: u64,
+    wait: bool,
+) -> Result<(), Errno> {
+    let mut table = LOCKS.lock();
+    if ltype != F_UNLCK {
+        loop {
+            let holders: Vec<u64> = match table.files.get(&file) {
+                Some(locks) => locks
+                    .records
+                    .iter()
+                    .filter(|lock| lock.conflicts(cageid, ltype, start, end))
+                    .map(|lock| lock.owner)
+                    .collect(),
+                None => vec![],
+            };
+            if holders.is_empty() {
+                break;
+            }
+            if !wait {
+                return Err(Errno::EAGAIN);
+            }
+            if table.would_deadlock(cageid, &holders) {
+                return Err(Errno::EDEADLK);
+            }
+            table.waiting.insert(cageid, holders);
+            LOCKS_CV.wait(&mut table);
+            table.waiting.remove(&cageid);
+        }
+    }
+
+    let locks = table.files.entry(file).or_default();
+    // Take the range out of the locks of the cage, keeping what lies on either side
+    let mut kept = Vec::with_capacity(locks.records.len() + 1);
+    for lock in locks.records.drain(..) {
+        if lock.owner != cageid || !lock.overlaps(start, end) {
+            kept.push(lock);
+            continue;
+        }
+        if lock.start < start {
+            kept.push(RecordLock { end: start, ..lock });
+        }
+        if lock.end > end {
+            kept.push(RecordLock { start: end, ..lock });
+        }
+    }
+    locks.records = kept;
+
+    if ltype != F_UNLCK {
+        // Merge with the adjacent locks of the cage of the same type
+        let mut new = RecordLock {
+            owner: cageid,
+            ltype,
+            start,
+            end,
+        };
+        locks.records.retain(|lock| {
+            let adjacent = lock.owner == cageid
+                && lock.ltype == ltype
+                && (lock.end == new.start || lock.start == new.end);
+            if adjacent {
+                new.start = new.start.min(lock.start);
+                new.end = new.end.max(lock.end);
+            }
+            !adjacent
+        });
+        locks.records.push(new);
+    }
+
+    if locks.is_empty() {
+        table.files.remove(&file);
+    }
+    // Unlocking or downgrading may let a waiter go
+    LOCKS_CV.notify_all();
+    Ok(())
+}
+
+/// Whether `cageid` holds any record lock, so callers can skip looking up the file of an fd
+pub fn holds_record_locks(cageid: u64) -> bool {
+    let table = LOCKS.lock();
+    table
+        .files
+        .values()
+        .any(|locks| locks.records.iter().any(|lock| lock.owner == cageid))
+}
+
+/// Drops the record locks `cageid` holds on `file`, or on every file when `file` is None
+pub fn release_record_locks(cageid: u64, file: Option<FileId>) {
+    let mut table = LOCKS.lock();
+    table.files.retain(|id, locks| {
+        if file.map_or(true, |file| file == *id) {
+            locks.records.retain(|lock| lock.owner != cageid);
+        }
+        !locks.is_empty()
+    });
+    LOCKS_CV.notify_all();
+}
+
+/// Applies a `flock()` operation of the description behind `(fdkind, underfd)` on `file`:
+/// `exclusive` is Some(false) for `LOCK_SH`, Some(true) for `LOCK_EX` and None for `LOCK_UN`.
+///
+/// If another description holds a conflicting lock, returns EAGAIN when `nonblocking` is set,
+/// otherwise the call sleeps until it can take the lock.
+pub fn flock(
+    fdkind: u32,
+    underfd: u64,
+    file: FileId,
+    exclusive: Option<bool>,
+    nonblocking: bool,
+) -> Result<(), Errno> {
+    let mut table = LOCKS.lock();
+    let Some(exclusive) = exclusive else {
+        if let Some(&id) = table.desc_ids.get(&(fdkind, underfd)) {
+            table.unflock(id);
+        }
+        return Ok(());
+    };
+
+    let id = table.description((fdkind, underfd));
+    if table
+        .files
+        .get(&file)
+        .map_or(false, |locks| locks.flocks.contains(&(id, exclusive)))
+    {
+        return Ok(());
+    }
+    // As on Linux, converting a lock first drops the one held, so two descriptions upgrading
+    // their shared locks at once don't wait for each other forever
+    table.unflock(id);
+    loop {
+        let conflict = table.files.get(&file).map_or(false, |locks| {
+            locks
+                .flocks
+                .iter()
+                .any(|&(holder, held_exclusive)| holder != id && (held_exclusive || exclusive))
+        });
+        if !conflict {
+            break;
+        }
+        if nonblocking {
+            return Err(Errno::EAGAIN);
+        }
+        LOCKS_CV.wait(&mut table);
+    }
+
+    table
+        .files
+        .entry(file)
+        .or_default()
+        .flocks
+        .push((id, exclusive));
+    table.descriptions.get_mut(&id).unwrap().flocked = Some(file);
+    Ok(())
+}
+
+/// Returns the owner set by `F_SETOWN` on the description behind `(fdkind, underfd)`, 0 if none
+pub fn get_owner(fdkind: u32, underfd: u64) -> i32 {
+    let table = LOCKS.lock();
+    table
+        .desc_ids
+        .get(&(fdkind, underfd))
+        .map_or(0, |id| table.descriptions[id].owner)
+}
+
+pub fn set_owner(fdkind: u32, underfd: u64, owner: i32) {
+    let mut table = LOCKS.lock();
+    let id = table.description((fdkind, underfd));
+    table.descriptions.get_mut(&id).unwrap().owner = owner;
+}
+
+/// Makes `new_underfd`, a duplicate of `old_underfd` made by the host, refer to the same open file
+/// description in this table
+pub fn dup_description(fdkind: u32, old_underfd: u64, new_underfd: u64) {
+    let mut table = LOCKS.lock();
+    // The description has no state yet, but must know both fds once it gets some
+    let id = table.description((fdkind, old_underfd));
+    table.forget((fdkind, new_underfd));
+    table.desc_ids.insert((fdkind, new_underfd), id);
+    table.descriptions.get_mut(&id).unwrap().refs += 1;
+}
+
+/// Called once the last fd with `(fdkind, underfd)` is closed. The description goes away, along
+/// with its `flock()` lock, when nothing else refers to it.
+pub fn close_description(fdkind: u32, underfd: u64) {
+    LOCKS.lock().forget((fdkind, underfd));
+}
@@ -0,0 +1,1301 @@
+//! In-memory loopback sockets
+//!
+//! Sockets created while the loopback policy covers their domain are not backed by a host kernel
+//! socket. They live in a table shared by every cage of the lind runtime and are registered in
+//! `fdtables` with `FDKIND_LOOPBACK`, `underfd` being the id of the socket in that table. Cages
+//! talking through them never reach the host network stack: AF_INET sockets can only reach the
+//! loopback addresses, and AF_UNIX paths live in a namespace of their own instead of as socket files
+//! under `LIND_ROOT`.
+//!
+//! The net syscalls in `net_calls` check the fd kind of the socket they are given and hand in-memory
+//! sockets over to this module. Blocking calls wait on a condition variable that is signalled on
+//! every state change and every signal sent to a cage, and `poll_events` reports the readiness of a
+//! socket to the poll engine shared by select, poll and epoll.
+//!
+//! Fds passed with SCM_RIGHTS over AF_UNIX sockets travel with the data they were sent with, held
+//! open by `InflightFds` until the receiving cage gets its own virtual fds for them.
+use crate::syscalls::fs_calls::_fd_alloc_errno;
+use crate::syscalls::locks;
+use cage::signal::lind_signal_interrupts;
+use fdtables;
+use once_cell::sync::Lazy;
+use parking_lot::{Condvar, Mutex, MutexGuard};
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU8, Ordering};
+use sysdefs::constants::err_const::{syscall_error, Errno};
+use sysdefs::constants::fs_const::{FDKIND_LOOPBACK, O_NONBLOCK, O_RDWR};
+use sysdefs::constants::net_const::{
+    AF_INET, AF_UNIX, AF_UNSPEC, IPPROTO_IP, IPPROTO_TCP, IPPROTO_UDP, MSG_DONTWAIT, MSG_PEEK,
+    MSG_TRUNC, POLLERR, POLLHUP, POLLIN, POLLNVAL, POLLOUT, SHUT_RD, SHUT_RDWR, SHUT_WR,
+    SOCK_CLOEXEC, SOCK_DGRAM, SOCK_NONBLOCK, SOCK_STREAM, SOL_SOCKET, SOMAXCONN, SO_ACCEPTCONN,
+    SO_DOMAIN, SO_ERROR, SO_PROTOCOL, SO_RCVBUF, SO_SNDBUF, SO_TYPE,
+};
+
+/// Which sockets `socket()` and `socketpair()` create in memory instead of on the host kernel.
+/// The policy only applies to sockets created after it is set.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum LoopbackPolicy {
+    /// Every socket is a host kernel socket
+    Kernel = 0,
+    /// AF_UNIX sockets are in-memory, AF_INET sockets are host kernel sockets
+    Unix = 1,
+    /// AF_UNIX and AF_INET sockets are in-memory. AF_INET sockets can only reach 127.0.0.0/8
+    All = 2,
+}
+
+static POLICY: AtomicU8 = AtomicU8::new(LoopbackPolicy::Kernel as u8);
+
+/// Sets which sockets the runtime creates in memory, see `LoopbackPolicy`
+pub fn set_loopback_policy(policy: LoopbackPolicy) {
+    POLICY.store(policy as u8, Ordering::SeqCst);
+}
+
+pub fn loopback_policy() -> LoopbackPolicy {
+    match POLICY.load(Ordering::SeqCst) {
+        1 => LoopbackPolicy::Unix,
+        2 => LoopbackPolicy::All,
+        _ => LoopbackPolicy::Kernel,
+    }
+}
+
+/// Whether sockets of `domain` are created in memory under the current policy
+pub(crate) fn is_loopback_domain(domain: i32) -> bool {
+    match loopback_policy() {
+        LoopbackPolicy::Kernel => false,
+        LoopbackPolicy::Unix => domain == AF_UNIX,
+        LoopbackPolicy::All => domain == AF_UNIX || domain == AF_INET,
+    }
+}
+
+/// Capacity of the receive buffer of a socket, the Linux default `rmem_default`
+pub(crate) const LOOPBACK_BUFSIZE: usize = 212992;
+
+/// Range of the ports picked for sockets that are not explicitly bound, the Linux default
+/// `ip_local_port_range`
+const EPHEMERAL_PORTS: std::ops::RangeInclusive<u16> = 32768..=60999;
+
+const INADDR_ANY: [u8; 4] = [0, 0, 0, 0];
+const INADDR_LOOPBACK: [u8; 4] = [127, 0, 0, 1];
+
+/// Address of an in-memory socket
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub(crate) enum LoopbackAddr {
+    /// IPv4 address and port, the port in host byte order
+    Inet([u8; 4], u16),
+    /// UNIX path without its null terminator, empty for unnamed sockets
+    Unix(Vec<u8>),
+}
+
+impl LoopbackAddr {
+    /// The address of a socket of `domain` that is not bound
+    fn unbound(domain: i32) -> Self {
+        if domain == AF_INET {
+            LoopbackAddr::Inet(INADDR_ANY, 0)
+        } else {
+            LoopbackAddr::Unix(Vec::new())
+        }
+    }
+
+    /// The address a socket bound to `local` is seen from by its peers: the wildcard address
+    /// stands for the local host
+    fn source(local: Option<&LoopbackAddr>, domain: i32) -> Self {
+        match local {
+            Some(LoopbackAddr::Inet(INADDR_ANY, port)) => {
+                LoopbackAddr::Inet(INADDR_LOOPBACK, *port)
+            }
+            Some(local) => local.clone(),
+            None => LoopbackAddr::unbound(domain),
+        }
+    }
+}
+
+struct Listener {
+    /// How many connections can be waiting for `accept`
+    backlog: usize,
+    /// Server side of the connections not accepted yet
+    pending: VecDeque<u64>,
+}
+
+struct Socket {
+    domain: i32,
+    socktype: i32,
+    /// `O_NONBLOCK`, shared by every fd referring to the socket as for an open file description
+    nonblocking: bool,
+    /// Address the socket is bound to
+    local: Option<LoopbackAddr>,
+    /// Address of the connected peer, for datagram sockets the default destination set by connect
+    peer_addr: Option<LoopbackAddr>,
+    /// The connected peer socket, None once it is closed
+    peer: Option<u64>,
+    /// Whether a stream socket was connected, it stays set once the peer is gone
+    connected: bool,
+    listener: Option<Listener>,
+    shut_rd: bool,
+    shut_wr: bool,
+    /// Set when the stream peer closed or shut down writing: once the buffered data is read, reads
+    /// return end of file
+    eof: bool,
+    stream: VecDeque<u8>,
+    /// Fds passed with SCM_RIGHTS on the stream, keyed by the offset in `stream` of the first byte
+    /// of the message they came with
+    stream_rights: VecDeque<(usize, InflightFds)>,
+    /// Received datagrams, their source address and the fds passed with them
+    dgrams: VecDeque<(LoopbackAddr, Vec<u8>, Option<InflightFds>)>,
+    dgram_bytes: usize,
+    /// Values set with setsockopt, read back by getsockopt
+    options: HashMap<(i32, i32), Vec<u8>>,
+}
+
+impl Socket {
+    fn new(domain: i32, socktype: i32, nonblocking: bool) -> Self {
+        Socket {
+            domain,
+            socktype,
+            nonblocking,
+            local: None,
+            peer_addr: None,
+            peer: None,
+            connected: false,
+            listener: None,
+            shut_rd: false,
+            shut_wr: false,
+            eof: false,
+            stream: VecDeque::new(),
+            stream_rights: VecDeque::new(),
+            dgrams: VecDeque::new(),
+            dgram_bytes: 0,
+            options: HashMap::new(),
+        }
+    }
+
+    fn protocol(&self) -> i32 {
+        match (self.domain, self.socktype) {
+            (AF_INET, SOCK_STREAM) => IPPROTO_TCP,
+            (AF_INET, SOCK_DGRAM) => IPPROTO_UDP,
+            _ => 0,
+        }
+    }
+}
+
+struct LoopbackNet {
+    sockets: HashMap<u64, Socket>,
+    next_id: u64,
+    /// Bound addresses and the socket bound to them, keyed with the socket type
+    bound: HashMap<(i32, LoopbackAddr), u64>,
+    next_port: u16,
+}
+
+impl LoopbackNet {
+    fn socket(&mut self, id: u64) -> &mut Socket {
+        // a socket is only removed once its last fd is closed
+        self.sockets
+            .get_mut(&id)
+            .expect("in-memory socket of an open fd is missing")
+    }
+
+    fn insert(&mut self, socket: Socket) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.sockets.insert(id, socket);
+        id
+    }
+
+    fn in_use(&self, socktype: i32, addr: &LoopbackAddr) -> bool {
+        match addr {
+            LoopbackAddr::Inet(ip, port) => self.bound.keys().any(|(boundtype, bound)| {
+                matches!(bound, LoopbackAddr::Inet(boundip, boundport)
+                    if *boundtype == socktype
+                        && boundport == port
+                        && (boundip == ip || *boundip == INADDR_ANY || *ip == INADDR_ANY))
+            }),
+            // UNIX paths are shared by every socket type, as socket files are
+            LoopbackAddr::Unix(_) => self.bound.keys().any(|(_, bound)| bound == addr),
+        }
+    }
+
+    /// Finds the socket bound to `addr`, a socket bound to the wildcard address is reached through
+    /// any loopback address
+    fn lookup(&self, socktype: i32, addr: &LoopbackAddr) -> Option<u64> {
+        if let Some(&id) = self.bound.get(&(socktype, addr.clone())) {
+            return Some(id);
+        }
+        match addr {
+            LoopbackAddr::Inet(_, port) => self
+                .bound
+                .get(&(socktype, LoopbackAddr::Inet(INADDR_ANY, *port)))
+                .copied(),
+            LoopbackAddr::Unix(_) => None,
+        }
+    }
+
+    fn bind(&mut self, id: u64, addr: LoopbackAddr) {
+        let socket = self.socket(id);
+        socket.local = Some(addr.clone());
+        let socktype = socket.socktype;
+        self.bound.insert((socktype, addr), id);
+    }
+
+    /// Binds an AF_INET socket that is not bound yet to a free port, as the kernel does when an
+    /// unbound socket is first used
+    fn autobind(&mut self, id: u64, ip: [u8; 4], syscall_name: &str) -> Result<(), i32> {
+        let socket = self.socket(id);
+        if socket.domain != AF_INET || socket.local.is_some() {
+            return Ok(());
+        }
+        let socktype = socket.socktype;
+        let nports = EPHEMERAL_PORTS.len();
+        for _ in 0..nports {
+            let port = self.next_port;
+            self.next_port = if port == *EPHEMERAL_PORTS.end() {
+                *EPHEMERAL_PORTS.start()
+            } else {
+                port + 1
+            };
+            let addr = LoopbackAddr::Inet(ip, port);
+            if !self.in_use(socktype, &addr) {
+                self.bind(id, addr);
+                return Ok(());
+            }
+        }
+        Err(syscall_error(
+            Errno::EADDRINUSE,
+            syscall_name,
+            "no ephemeral port left",
+        ))
+    }
+
+    /// Drops a socket whose last fd was closed, the peer of a stream socket sees end of file.
+    ///
+    /// Return:
+    ///     - the fds that were in flight to the socket, to be dropped once `LOOPBACK` is unlocked
+    fn close(&mut self, id: u64) -> Vec<InflightFds> {
+        let mut socket = match self.sockets.remove(&id) {
+            Some(socket) => socket,
+            None => return Vec::new(),
+        };
+        let mut inflight: Vec<InflightFds> = socket
+            .stream_rights
+            .drain(..)
+            .map(|(_, rights)| rights)
+            .chain(socket.dgrams.drain(..).filter_map(|(_, _, rights)| rights))
+            .collect();
+        if let Some(local) = socket.local {
+            let key = (socket.socktype, local);
+            if self.bound.get(&key) == Some(&id) {
+                self.bound.remove(&key);
+            }
+        }
+        if let Some(peer) = socket.peer.and_then(|peer| self.sockets.get_mut(&peer)) {
+            peer.peer = None;
+            peer.eof = true;
+        }
+        // connections nobody accepted are closed with the listener
+        if let Some(listener) = socket.listener {
+            for pending in listener.pending {
+                inflight.extend(self.close(pending));
+            }
+        }
+        inflight
+    }
+}
+
+static LOOPBACK: Lazy<Mutex<LoopbackNet>> = Lazy::new(|| {
+    Mutex::new(LoopbackNet {
+        sockets: HashMap::new(),
+        next_id: 0,
+        bound: HashMap::new(),
+        next_port: *EPHEMERAL_PORTS.start(),
+    })
+});
+
+/// Signalled whenever an in-memory socket changes state, blocked calls then check their socket again
+static LOOPBACK_CV: Condvar = Condvar::new();
+
+fn _notify() {
+    LOOPBACK_CV.notify_all();
+}
+
+/// Signal waker of the in-memory sockets, wakes up every blocked call so that it can check for an
+/// interrupting signal
+pub(crate) fn wake_all() {
+    let _net = LOOPBACK.lock();
+    _notify();
+}
+
+/// Waits for a state change of the in-memory sockets, or fails with EAGAIN for non-blocking calls
+/// and with EINTR once a signal interrupts the cage `cageid`
+fn _wait(
+    net: &mut MutexGuard<LoopbackNet>,
+    cageid: u64,
+    nonblocking: bool,
+    syscall_name: &str,
+) -> Result<(), i32> {
+    if nonblocking {
+        return Err(syscall_error(
+            Errno::EAGAIN,
+            syscall_name,
+            "operation would block",
+        ));
+    }
+    if lind_signal_interrupts(cageid) {
+        return Err(syscall_error(
+            Errno::EINTR,
+            syscall_name,
+            "Interrupted system call",
+        ));
+    }
+    LOOPBACK_CV.wait(net);
+    Ok(())
+}
+
+/// Returns the id of the in-memory socket behind `virtual_fd`, None if the fd is of another kind
+pub(crate) fn loopback_id(virtual_fd: u64, vfd_cageid: u64) -> Option<u64> {
+    match fdtables::translate_virtual_fd(vfd_cageid, virtual_fd) {
+        Ok(entry) if entry.fdkind == FDKIND_LOOPBACK => Some(entry.underfd),
+        _ => None,
+    }
+}
+
+/// Allocates a virtual fd of `cageid` for the in-memory socket `id`. The socket is dropped if no
+/// fd is left, as nothing else refers to it.
+///
+/// Return:
+///     - On success: the new virtual fd
+///     - On failure: a negative errno value
+pub(crate) fn install_fd(cageid: u64, id: u64, should_cloexec: bool, syscall_name: &str) -> i32 {
+    match fdtables::get_unused_virtual_fd(cageid, FDKIND_LOOPBACK, id, should_cloexec, 0) {
+        Ok(virtualfd) => virtualfd as i32,
+        Err(e) => {
+            close(id);
+            syscall_error(_fd_alloc_errno(e), syscall_name, "too many open files")
+        }
+    }
+}
+
+/// Drops the in-memory socket `id`, for sockets that no fd refers to
+pub(crate) fn close(id: u64) {
+    let inflight = LOOPBACK.lock().close(id);
+    _notify();
+    // the fds in flight may be the last reference to another in-memory socket, whose close handler
+    // takes `LOOPBACK` again
+    drop(inflight);
+}
+
+/// Cage whose fdtable keeps the fds passed with SCM_RIGHTS open while they are in flight, from
+/// `sendmsg` until a cage receives them. The utility cage 0 never runs, so its fds are never used.
+const INFLIGHT_CAGEID: u64 = 0;
+
+/// Fds passed with SCM_RIGHTS on an in-memory socket, as virtual fds of `INFLIGHT_CAGEID` referring
+/// to the same open files as the fds of the sender. Each one counts as a reference for fdtables, so
+/// the file stays open even when the sender closes its fd before the message is received.
+///
+/// Dropping it closes the fds that were not received. This may run the close handler of the last
+/// reference of an in-memory socket, so it must never be dropped while `LOOPBACK` is locked.
+pub(crate) struct InflightFds(Vec<u64>);
+
+impl InflightFds {
+    /// Takes a reference to the open files behind the virtual fds `virtualfds` of `cageid`
+    ///
+    /// Return:
+    ///     - On success: the fds in flight
+    ///     - On failure: a negative errno value, EBADF if an fd is not open
+    pub(crate) fn hold(cageid: u64, virtualfds: &[i32]) -> Result<Self, i32> {
+        let mut held = InflightFds(Vec::with_capacity(virtualfds.len()));
+        for &virtualfd in virtualfds {
+            let entry = match u64::try_from(virtualfd)
+                .ok()
+                .and_then(|fd| fdtables::translate_virtual_fd(cageid, fd).ok())
+            {
+                Some(entry) => entry,
+                None => {
+                    return Err(syscall_error(
+                        Errno::EBADF,
+                        "sendmsg",
+                        "invalid fd in SCM_RIGHTS",
+                    ))
+                }
+            };
+            match fdtables::get_unused_virtual_fd(
+                INFLIGHT_CAGEID,
+                entry.fdkind,
+                entry.underfd,
+                false,
+                entry.perfdinfo,
+            ) {
+                Ok(fd) => held.0.push(fd),
+                Err(_) => {
+                    return Err(syscall_error(
+                        Errno::ETOOMANYREFS,
+                        "sendmsg",
+                        "too many fds in flight",
+                    ))
+                }
+            }
+        }
+        Ok(held)
+    }
+
+    /// Installs the first `max` fds in flight into the fdtable of `cageid`, the others are closed
+    ///
+    /// Return:
+    ///     - the new virtual fds of `cageid`, and whether some fds were dropped (no room in the
+    ///       control buffer, or no free fd left in the receiving cage)
+    pub(crate) fn install(self, cageid: u64, should_cloexec: bool, max: usize) -> (Vec<i32>, bool) {
+        let mut virtualfds = Vec::new();
+        for &fd in self.0.iter().take(max) {
+            let installed = fdtables::translate_virtual_fd(INFLIGHT_CAGEID, fd)
+                .ok()
+                .and_then(|entry| {
+                    fdtables::get_unused_virtual_fd(
+                        cageid,
+                        entry.fdkind,
+                        entry.underfd,
+                        should_cloexec,
+                        entry.perfdinfo,
+                    )
+                    .ok()
+                });
+            match installed {
+                Some(virtualfd) => virtualfds.push(virtualfd as i32),
+                None => break,
+            }
+        }
+        let dropped = virtualfds.len() < self.0.len();
+        (virtualfds, dropped)
+    }
+}
+
+impl Drop for InflightFds {
+    fn drop(&mut self) {
+        for fd in self.0.drain(..) {
+            let _ = fdtables::close_virtualfd(INFLIGHT_CAGEID, fd);
+        }
+    }
+}
+
+/// Close handler of `FDKIND_LOOPBACK`, called by fdtables once the last fd of a socket is closed
+pub fn loopback_close(fdentry: fdtables::FDTableEntry, _count: u64) {
+    locks::close_description(FDKIND_LOOPBACK, fdentry.underfd);
+    close(fdentry.underfd);
+}
+
+/// Reads the guest socket address at `addr`, which must be of the family of `domain`
+fn _read_addr(domain: i32, addr: *const u8, syscall_name: &str) -> Result<LoopbackAddr, i32> {
+    if addr.is_null() {
+        return Err(syscall_error(
+            Errno::EFAULT,
+            syscall_name,
+            "address is NULL",
+        ));
+    }
+    let family = unsafe { (addr as *const libc::sa_family_t).read_unaligned() } as i32;
+    if family != domain {
+        return Err(syscall_error(
+            Errno::EAFNOSUPPORT,
+            syscall_name,
+            "address family does not match the socket",
+        ));
+    }
+    if domain == AF_INET {
+        let sin = unsafe { (addr as *const libc::sockaddr_in).read_unaligned() };
+        Ok(LoopbackAddr::Inet(
+            sin.sin_addr.s_addr.to_ne_bytes(),
+            u16::from_be(sin.sin_port),
+        ))
+    } else {
+        let sun = unsafe { (addr as *const libc::sockaddr_un).read_unaligned() };
+        let path = sun
+            .sun_path
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        Ok(LoopbackAddr::Unix(path))
+    }
+}
+
+/// Copies `addr` to the guest buffer `buf`, truncated to the buffer size in `*buflen` as Linux does.
+/// `*buflen` is updated with the actual length of the address.
+pub(crate) fn copy_out_addr(buf: *mut u8, buflen: *mut u32, addr: &LoopbackAddr) {
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+    let len = match addr {
+        LoopbackAddr::Inet(ip, port) => {
+            let sin = &mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in;
+            unsafe {
+                (*sin).sin_family = AF_INET as libc::sa_family_t;
+                (*sin).sin_port = port.to_be();
+                (*sin).sin_addr.s_addr = u32::from_ne_bytes(*ip);
+            }
+            std::mem::size_of::<libc::sockaddr_in>()
+        }
+        LoopbackAddr::Unix(path) => {
+            let sun = &mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_un;
+            unsafe {
+                (*sun).sun_family = AF_UNIX as libc::sa_family_t;
+                for (dst, &src) in (*sun).sun_path.iter_mut().zip(path.iter()) {
+                    *dst = src as libc::c_char;
+                }
+            }
+            let family_len = std::mem::size_of::<libc::sa_family_t>();
+            // unnamed sockets only have a family, paths are followed by their null terminator
+            if path.is_empty() {
+                family_len
+            } else {
+                family_len + path.len() + 1
+            }
+        }
+    };
+    unsafe {
+        let copylen = (*buflen as usize).min(len);
+        std::ptr::copy_nonoverlapping(
+            &storage as *const libc::sockaddr_storage as *const u8,
+            buf,
+            copylen,
+        );
+        *buflen = len as u32;
+    }
+}
+
+/// Checks that an AF_INET address is reachable from in-memory sockets
+fn _check_reachable(addr: &LoopbackAddr, syscall_name: &str) -> Result<(), i32> {
+    match addr {
+        LoopbackAddr::Inet(ip, _) if ip[0] != 127 && *ip != INADDR_ANY => Err(syscall_error(
+            Errno::ENETUNREACH,
+            syscall_name,
+            "in-memory sockets only reach the loopback addresses",
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Creates an in-memory socket, `socktype` may carry `SOCK_NONBLOCK` and `SOCK_CLOEXEC`
+///
+/// Return:
+///     - On success: the id of the socket
+///     - On failure: a negative errno value
+pub(crate) fn socket(domain: i32, socktype: i32, protocol: i32) -> Result<u64, i32> {
+    let basetype = socktype & !(SOCK_NONBLOCK | SOCK_CLOEXEC);
+    if basetype != SOCK_STREAM && basetype != SOCK_DGRAM {
+        return Err(syscall_error(
+            Errno::ESOCKTNOSUPPORT,
+            "socket",
+            "in-memory sockets are stream or datagram sockets",
+        ));
+    }
+    let socket = Socket::new(domain, basetype, (socktype & SOCK_NONBLOCK) != 0);
+    if protocol != IPPROTO_IP && protocol != socket.protocol() {
+        return Err(syscall_error(
+            Errno::EPROTONOSUPPORT,
+            "socket",
+            "protocol not supported",
+        ));
+    }
+    Ok(LOOPBACK.lock().insert(socket))
+}
+
+/// Creates a pair of connected AF_UNIX in-memory sockets
+///
+/// Return:
+///     - On success: the ids of both sockets
+///     - On failure: a negative errno value
+pub(crate) fn socketpair(domain: i32, socktype: i32, protocol: i32) -> Result<(u64, u64), i32> {
+    if domain != AF_UNIX {
+        return Err(syscall_error(
+            Errno::EOPNOTSUPP,
+            "socketpair",
+            "only AF_UNIX sockets can be created in pairs",
+        ));
+    }
+    let sock1 = socket(domain, socktype, protocol)?;
+    let sock2 = socket(domain, socktype, protocol)?;
+    let mut net = LOOPBACK.lock();
+    for (id, peer) in [(sock1, sock2), (sock2, sock1)] {
+        let socket = net.socket(id);
+        socket.peer = Some(peer);
+        socket.peer_addr = Some(LoopbackAddr::Unix(Vec::new()));
+        socket.connected = socket.socktype == SOCK_STREAM;
+    }
+    Ok((sock1, sock2))
+}
+
+/// Binds the socket `id` to the guest address at `addr`. AF_INET sockets can only be bound to the
+/// wildcard or a loopback address, port 0 picking a free port.
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value
+pub(crate) fn bind(id: u64, addr: *const u8) -> i32 {
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    let (domain, socktype) = (socket.domain, socket.socktype);
+    if socket.local.is_some() {
+        return syscall_error(Errno::EINVAL, "bind", "socket is already bound");
+    }
+    let addr = match _read_addr(domain, addr, "bind") {
+        Ok(addr) => addr,
+        Err(e) => return e,
+    };
+    match addr {
+        LoopbackAddr::Inet(ip, _) if ip[0] != 127 && ip != INADDR_ANY => {
+            return syscall_error(
+                Errno::EADDRNOTAVAIL,
+                "bind",
+                "in-memory sockets can only bind loopback addresses",
+            );
+        }
+        LoopbackAddr::Inet(ip, 0) => {
+            if let Err(e) = net.autobind(id, ip, "bind") {
+                return e;
+            }
+        }
+        LoopbackAddr::Unix(ref path) if path.is_empty() => {
+            return syscall_error(Errno::EINVAL, "bind", "empty path");
+        }
+        addr => {
+            if net.in_use(socktype, &addr) {
+                return syscall_error(Errno::EADDRINUSE, "bind", "address already in use");
+            }
+            net.bind(id, addr);
+        }
+    }
+    0
+}
+
+/// Marks the stream socket `id` as accepting connections
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value
+pub(crate) fn listen(id: u64, backlog: i32) -> i32 {
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    if socket.socktype != SOCK_STREAM {
+        return syscall_error(Errno::EOPNOTSUPP, "listen", "not a stream socket");
+    }
+    if socket.connected {
+        return syscall_error(Errno::EINVAL, "listen", "socket is connected");
+    }
+    if socket.local.is_none() {
+        if socket.domain == AF_UNIX {
+            return syscall_error(Errno::EINVAL, "listen", "socket is not bound");
+        }
+        if let Err(e) = net.autobind(id, INADDR_ANY, "listen") {
+            return e;
+        }
+    }
+    // as in Linux, one more connection than the backlog can be waiting
+    let backlog = backlog.clamp(0, SOMAXCONN) as usize + 1;
+    let socket = net.socket(id);
+    match socket.listener {
+        Some(ref mut listener) => listener.backlog = backlog,
+        None => {
+            socket.listener = Some(Listener {
+                backlog,
+                pending: VecDeque::new(),
+            })
+        }
+    }
+    _notify();
+    0
+}
+
+/// Connects the socket `id` to the guest address at `addr`. A stream socket is connected to a new
+/// socket queued on the listener, ready for `accept`. A datagram socket only records the default
+/// destination, an `AF_UNSPEC` address clearing it.
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value
+pub(crate) fn connect(cageid: u64, id: u64, addr: *const u8) -> i32 {
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    let (domain, socktype, nonblocking) = (socket.domain, socket.socktype, socket.nonblocking);
+
+    if socktype == SOCK_DGRAM {
+        if !addr.is_null()
+            && unsafe { (addr as *const libc::sa_family_t).read_unaligned() } as i32 == AF_UNSPEC
+        {
+            socket.peer_addr = None;
+            return 0;
+        }
+        let dest = match _read_addr(domain, addr, "connect") {
+            Ok(dest) => dest,
+            Err(e) => return e,
+        };
+        if let Err(e) = _check_reachable(&dest, "connect") {
+            return e;
+        }
+        if let Err(e) = net.autobind(id, INADDR_ANY, "connect") {
+            return e;
+        }
+        let socket = net.socket(id);
+        socket.peer = None;
+        socket.peer_addr = Some(dest);
+        return 0;
+    }
+
+    let dest = match _read_addr(domain, addr, "connect") {
+        Ok(LoopbackAddr::Inet(ip, port)) => {
+            // the wildcard address stands for the local host
+            let ip = if ip == INADDR_ANY {
+                INADDR_LOOPBACK
+            } else {
+                ip
+            };
+            LoopbackAddr::Inet(ip, port)
+        }
+        Ok(dest) => dest,
+        Err(e) => return e,
+    };
+    if let Err(e) = _check_reachable(&dest, "connect") {
+        return e;
+    }
+
+    let listenerid = loop {
+        let socket = net.socket(id);
+        if socket.listener.is_some() {
+            return syscall_error(Errno::EINVAL, "connect", "socket is listening");
+        }
+        if socket.connected {
+            return syscall_error(Errno::EISCONN, "connect", "socket is already connected");
+        }
+        let listenerid = match net.lookup(SOCK_STREAM, &dest) {
+            Some(listenerid) => listenerid,
+            None if domain == AF_UNIX => {
+                return syscall_error(Errno::ENOENT, "connect", "no socket bound to the path")
+            }
+            None => return syscall_error(Errno::ECONNREFUSED, "connect", "connection refused"),
+        };
+        match net.socket(listenerid).listener {
+            Some(ref listener) if listener.pending.len() < listener.backlog => break listenerid,
+            Some(_) => {}
+            None => return syscall_error(Errno::ECONNREFUSED, "connect", "connection refused"),
+        }
+        // the backlog is full, wait for the listener to accept
+        if let Err(e) = _wait(&mut net, cageid, nonblocking, "connect") {
+            return e;
+        }
+    };
+
+    if let LoopbackAddr::Inet(ip, _) = dest {
+        if let Err(e) = net.autobind(id, ip, "connect") {
+            return e;
+        }
+    }
+    // the new socket has the address the client connected to, and inherits the listener options
+    let mut server = Socket::new(domain, SOCK_STREAM, false);
+    server.local = Some(dest.clone());
+    server.options = net.socket(listenerid).options.clone();
+    server.peer_addr = Some(LoopbackAddr::source(net.socket(id).local.as_ref(), domain));
+    server.peer = Some(id);
+    server.connected = true;
+    let serverid = net.insert(server);
+
+    let socket = net.socket(id);
+    socket.peer = Some(serverid);
+    socket.peer_addr = Some(dest);
+    socket.connected = true;
+    net.socket(listenerid)
+        .listener
+        .as_mut()
+        .unwrap()
+        .pending
+        .push_back(serverid);
+    _notify();
+    0
+}
+
+/// Takes the next connection waiting on the listening socket `id`
+///
+/// Return:
+///     - On success: the id of the new socket and the address of its peer
+///     - On failure: a negative errno value
+pub(crate) fn accept(cageid: u64, id: u64) -> Result<(u64, LoopbackAddr), i32> {
+    let mut net = LOOPBACK.lock();
+    loop {
+        let socket = net.socket(id);
+        let nonblocking = socket.nonblocking;
+        let listener = match socket.listener {
+            Some(ref mut listener) => listener,
+            None => {
+                return Err(syscall_error(
+                    Errno::EINVAL,
+                    "accept",
+                    "socket is not listening",
+                ))
+            }
+        };
+        if let Some(serverid) = listener.pending.pop_front() {
+            let peer_addr = net.socket(serverid).peer_addr.clone().unwrap();
+            _notify();
+            return Ok((serverid, peer_addr));
+        }
+        _wait(&mut net, cageid, nonblocking, "accept")?;
+    }
+}
+
+/// Sends `data` on the socket `id`, to the guest address `dest` if it is not NULL. A blocking stream
+/// socket waits until all of `data` fits in the peer's buffer.
+///
+/// Return:
+///     - On success: the number of bytes sent
+///     - On failure: a negative errno value
+pub(crate) fn send(cageid: u64, id: u64, data: &[u8], flags: i32, dest: *const u8) -> i32 {
+    _send(cageid, id, data, flags, dest, &mut None)
+}
+
+/// `send` with the fds passed with SCM_RIGHTS, only AF_UNIX sockets can pass fds. The fds are taken
+/// out of `rights` once they are attached to the message, those of a message that could not be sent
+/// are left for the caller to drop after `LOOPBACK` is unlocked.
+fn _send(
+    cageid: u64,
+    id: u64,
+    data: &[u8],
+    flags: i32,
+    dest: *const u8,
+    rights: &mut Option<InflightFds>,
+) -> i32 {
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    let nonblocking = socket.nonblocking || (flags & MSG_DONTWAIT) != 0;
+    if rights.is_some() && socket.domain != AF_UNIX {
+        return syscall_error(
+            Errno::EINVAL,
+            "sendmsg",
+            "only AF_UNIX sockets can pass fds",
+        );
+    }
+    if socket.socktype == SOCK_DGRAM {
+        return _send_datagram(&mut net, cageid, id, data, nonblocking, dest, rights);
+    }
+    if !socket.connected {
+        return syscall_error(Errno::ENOTCONN, "send", "socket is not connected");
+    }
+
+    let mut sent = 0;
+    loop {
+        let socket = net.socket(id);
+        let peer = match socket.peer {
+            Some(peer) if !socket.shut_wr => peer,
+            _ if sent > 0 => return sent as i32,
+            _ => return syscall_error(Errno::EPIPE, "send", "connection is closed"),
+        };
+        let peer = net.socket(peer);
+        if peer.shut_rd {
+            if sent > 0 {
+                return sent as i32;
+            }
+            return syscall_error(Errno::EPIPE, "send", "peer shut down reading");
+        }
+        let len = (LOOPBACK_BUFSIZE - peer.stream.len()).min(data.len() - sent);
+        if len > 0 {
+            // the fds come with the first byte of the message
+            if let Some(rights) = rights.take() {
+                let offset = peer.stream.len();
+                peer.stream_rights.push_back((offset, rights));
+            }
+            peer.stream.extend(&data[sent..sent + len]);
+            sent += len;
+            _notify();
+        }
+        if sent == data.len() {
+            return sent as i32;
+        }
+        if nonblocking && sent > 0 {
+            return sent as i32;
+        }
+        if let Err(e) = _wait(&mut net, cageid, nonblocking, "send") {
+            return if sent > 0 { sent as i32 } else { e };
+        }
+    }
+}
+
+fn _send_datagram(
+    net: &mut MutexGuard<LoopbackNet>,
+    cageid: u64,
+    id: u64,
+    data: &[u8],
+    nonblocking: bool,
+    dest: *const u8,
+    rights: &mut Option<InflightFds>,
+) -> i32 {
+    let socket = net.socket(id);
+    let domain = socket.domain;
+    if socket.shut_wr {
+        return syscall_error(Errno::EPIPE, "sendto", "socket is shut down");
+    }
+    if data.len() > LOOPBACK_BUFSIZE {
+        return syscall_error(Errno::EMSGSIZE, "sendto", "message too long");
+    }
+    let dest = if dest.is_null() {
+        socket.peer_addr.clone()
+    } else {
+        match _read_addr(domain, dest, "sendto") {
+            Ok(dest) => Some(dest),
+            Err(e) => return e,
+        }
+    };
+    // the peer of a socket pair has no address, it is reached directly
+    let pairpeer = socket.peer;
+    if dest.is_none() && pairpeer.is_none() {
+        if domain == AF_UNIX {
+            return syscall_error(Errno::ENOTCONN, "sendto", "socket is not connected");
+        }
+        return syscall_error(Errno::EDESTADDRREQ, "sendto", "no destination address");
+    }
+    if let Some(ref dest) = dest {
+        if let Err(e) = _check_reachable(dest, "sendto") {
+            return e;
+        }
+    }
+    if let Err(e) = net.autobind(id, INADDR_ANY, "sendto") {
+        return e;
+    }
+    let source = LoopbackAddr::source(net.socket(id).local.as_ref(), domain);
+
+    loop {
+        let target = match dest {
+            Some(ref dest) => net.lookup(SOCK_DGRAM, dest),
+            None => pairpeer.filter(|peer| net.sockets.contains_key(peer)),
+        };
+        let target = match target {
+            Some(target) => net.socket(target),
+            // like UDP, datagrams to a port nobody is bound to are lost
+            None if domain == AF_INET => return data.len() as i32,
+            None if dest.is_none() => {
+                return syscall_error(Errno::ECONNREFUSED, "sendto", "peer is closed")
+            }
+            None => return syscall_error(Errno::ENOENT, "sendto", "no socket bound to the path"),
+        };
+        if target.dgrams.is_empty() || target.dgram_bytes + data.len() <= LOOPBACK_BUFSIZE {
+            target.dgram_bytes += data.len();
+            target
+                .dgrams
+                .push_back((source, data.to_vec(), rights.take()));
+            _notify();
+            return data.len() as i32;
+        }
+        // UDP drops datagrams when the receiver is full, AF_UNIX waits for room
+        if domain == AF_INET {
+            return data.len() as i32;
+        }
+        if let Err(e) = _wait(net, cageid, nonblocking, "sendto") {
+            return e;
+        }
+    }
+}
+
+/// What a receive returns: the number of bytes received, the source address for datagram sockets,
+/// the flags describing the message and the fds passed with it
+pub(crate) type Received = (usize, Option<LoopbackAddr>, i32, Option<InflightFds>);
+
+/// Receives into `buf` from the socket `id`. `MSG_PEEK`, `MSG_DONTWAIT` and, for datagrams,
+/// `MSG_TRUNC` are supported. The fds passed with a message are received along with its first byte,
+/// a peek leaves them on the message. Callers that can't hand them to the cage drop them, which
+/// closes them as Linux does.
+///
+/// Return:
+///     - On success: the number of bytes received, the source address for datagram sockets, the
+///       flags describing the message (`MSG_TRUNC` if a datagram didn't fit in `buf`), and the fds
+///       passed with SCM_RIGHTS
+///     - On failure: a negative errno value
+pub(crate) fn recv(cageid: u64, id: u64, buf: &mut [u8], flags: i32) -> Result<Received, i32> {
+    let mut net = LOOPBACK.lock();
+    let peek = (flags & MSG_PEEK) != 0;
+    loop {
+        let socket = net.socket(id);
+        let nonblocking = socket.nonblocking || (flags & MSG_DONTWAIT) != 0;
+        if socket.socktype == SOCK_DGRAM {
+            if let Some((source, datagram, _)) = socket.dgrams.front() {
+                let len = buf.len().min(datagram.len());
+                buf[..len].copy_from_slice(&datagram[..len]);
+                let (fulllen, source) = (datagram.len(), source.clone());
+                let msgflags = if fulllen > buf.len() { MSG_TRUNC } else { 0 };
+                let mut rights = None;
+                if !peek {
+                    rights = socket.dgrams.pop_front().and_then(|(_, _, rights)| rights);
+                    socket.dgram_bytes -= fulllen;
+                    _notify();
+                }
+                let ret = if (flags & MSG_TRUNC) != 0 {
+                    fulllen
+                } else {
+                    len
+                };
+                return Ok((ret, Some(source), msgflags, rights));
+            }
+            if socket.shut_rd {
+                return Ok((0, None, 0, None));
+            }
+        } else {
+            if !socket.connected {
+                return Err(syscall_error(
+                    Errno::ENOTCONN,
+                    "recv",
+                    "socket is not connected",
+                ));
+            }
+            if !socket.stream.is_empty() || buf.is_empty() {
+                // a read stops at the next message carrying fds, so that they are received with
+                // the first byte of their message
+                let boundary = socket
+                    .stream_rights
+                    .iter()
+                    .map(|&(offset, _)| offset)
+                    .find(|&offset| offset > 0)
+                    .unwrap_or(socket.stream.len());
+                let len = buf.len().min(boundary);
+                for (dst, src) in buf[..len].iter_mut().zip(socket.stream.iter()) {
+                    *dst = *src;
+                }
+                let mut rights = None;
+                if !peek && len > 0 {
+                    if socket.stream_rights.front().map(|&(offset, _)| offset) == Some(0) {
+                        rights = socket.stream_rights.pop_front().map(|(_, rights)| rights);
+                    }
+                    socket.stream.drain(..len);
+                    for (offset, _) in socket.stream_rights.iter_mut() {
+                        *offset -= len;
+                    }
+                    _notify();
+                }
+                return Ok((len, None, 0, rights));
+            }
+            if socket.eof || socket.shut_rd {
+                return Ok((0, None, 0, None));
+            }
+        }
+        _wait(&mut net, cageid, nonblocking, "recv")?;
+    }
+}
+
+/// Sends the data of the host iovecs `iovs` as a single message, along with the fds of `rights`,
+/// see `send`. The fds are taken out of `rights` once attached to the message, the caller drops the
+/// fds of a message that could not be sent.
+pub(crate) fn sendv(
+    cageid: u64,
+    id: u64,
+    iovs: &[libc::iovec],
+    flags: i32,
+    dest: *const u8,
+    rights: &mut Option<InflightFds>,
+) -> i32 {
+    let mut data = Vec::with_capacity(iovs.iter().map(|iov| iov.iov_len).sum());
+    for iov in iovs.iter().filter(|iov| iov.iov_len > 0) {
+        data.extend_from_slice(unsafe {
+            std::slice::from_raw_parts(iov.iov_base as *const u8, iov.iov_len)
+        });
+    }
+    _send(cageid, id, &data, flags, dest, rights)
+}
+
+/// Receives a single message into the host iovecs `iovs`, see `recv`
+pub(crate) fn recvv(
+    cageid: u64,
+    id: u64,
+    iovs: &[libc::iovec],
+    flags: i32,
+) -> Result<Received, i32> {
+    let mut data = vec![0u8; iovs.iter().map(|iov| iov.iov_len).sum()];
+    let (len, source, msgflags, rights) = recv(cageid, id, &mut data, flags)?;
+    // with MSG_TRUNC, the returned length can be larger than what was received
+    let mut remaining = &data[..len.min(data.len())];
+    for iov in iovs {
+        let copylen = iov.iov_len.min(remaining.len());
+        if copylen == 0 {
+            continue;
+        }
+        unsafe {
+            std::ptr::copy_nonoverlapping(remaining.as_ptr(), iov.iov_base as *mut u8, copylen)
+        };
+        remaining = &remaining[copylen..];
+    }
+    Ok((len, source, msgflags, rights))
+}
+
+/// Shuts down part of a connection, the peer of a stream socket sees end of file once writing is
+/// shut down
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value
+pub(crate) fn shutdown(id: u64, how: i32) -> i32 {
+    if how != SHUT_RD && how != SHUT_WR && how != SHUT_RDWR {
+        return syscall_error(Errno::EINVAL, "shutdown", "invalid how");
+    }
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    let connected = if socket.socktype == SOCK_STREAM {
+        socket.connected
+    } else {
+        socket.peer_addr.is_some()
+    };
+    if !connected {
+        return syscall_error(Errno::ENOTCONN, "shutdown", "socket is not connected");
+    }
+    if how != SHUT_WR {
+        socket.shut_rd = true;
+    }
+    if how != SHUT_RD {
+        socket.shut_wr = true;
+        if let (Some(peer), SOCK_STREAM) = (socket.peer, socket.socktype) {
+            net.socket(peer).eof = true;
+        }
+    }
+    _notify();
+    0
+}
+
+/// Returns the address the socket `id` is bound to, the unbound address if it isn't
+pub(crate) fn getsockname(id: u64) -> Result<LoopbackAddr, i32> {
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    Ok(socket
+        .local
+        .clone()
+        .unwrap_or_else(|| LoopbackAddr::unbound(socket.domain)))
+}
+
+/// Returns the address of the peer of the socket `id`
+pub(crate) fn getpeername(id: u64) -> Result<LoopbackAddr, i32> {
+    let mut net = LOOPBACK.lock();
+    net.socket(id)
+        .peer_addr
+        .clone()
+        .ok_or_else(|| syscall_error(Errno::ENOTCONN, "getpeername", "socket is not connected"))
+}
+
+/// Reads a socket option. The type, domain, protocol, pending error, listening state and buffer
+/// sizes describe the in-memory socket. Other options of the socket and IP levels hold what was
+/// last set with `setsockopt`, 0 if they were never set.
+///
+/// Return:
+///     - On success: the value of the option
+///     - On failure: a negative errno value
+pub(crate) fn getsockopt(id: u64, level: i32, optname: i32) -> Result<Vec<u8>, i32> {
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    let value = match (level, optname) {
+        (SOL_SOCKET, SO_TYPE) => socket.socktype,
+        (SOL_SOCKET, SO_DOMAIN) => socket.domain,
+        (SOL_SOCKET, SO_PROTOCOL) => socket.protocol(),
+        (SOL_SOCKET, SO_ERROR) => 0,
+        (SOL_SOCKET, SO_ACCEPTCONN) => socket.listener.is_some() as i32,
+        (SOL_SOCKET, SO_RCVBUF) | (SOL_SOCKET, SO_SNDBUF) => LOOPBACK_BUFSIZE as i32,
+        _ => {
+            _check_level(level, "getsockopt")?;
+            return Ok(socket
+                .options
+                .get(&(level, optname))
+                .cloned()
+                .unwrap_or_else(|| 0i32.to_ne_bytes().to_vec()));
+        }
+    };
+    Ok(value.to_ne_bytes().to_vec())
+}
+
+/// Sets a socket option. Options don't change how in-memory sockets behave, they are only recorded
+/// to be read back by `getsockopt`.
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value
+pub(crate) fn setsockopt(id: u64, level: i32, optname: i32, optval: &[u8]) -> i32 {
+    if let Err(e) = _check_level(level, "setsockopt") {
+        return e;
+    }
+    let mut net = LOOPBACK.lock();
+    net.socket(id)
+        .options
+        .insert((level, optname), optval.to_vec());
+    0
+}
+
+fn _check_level(level: i32, syscall_name: &str) -> Result<(), i32> {
+    match level {
+        SOL_SOCKET | IPPROTO_IP | IPPROTO_TCP | IPPROTO_UDP => Ok(()),
+        _ => Err(syscall_error(
+            Errno::ENOPROTOOPT,
+            syscall_name,
+            "unknown option level",
+        )),
+    }
+}
+
+/// File status flags of the socket `id`, as returned by `fcntl(F_GETFL)`
+pub(crate) fn get_status_flags(id: u64) -> i32 {
+    let mut net = LOOPBACK.lock();
+    if net.socket(id).nonblocking {
+        O_RDWR | O_NONBLOCK
+    } else {
+        O_RDWR
+    }
+}
+
+/// Sets the file status flags of the socket `id` with `fcntl(F_SETFL)`, only `O_NONBLOCK` is
+/// meaningful
+pub(crate) fn set_status_flags(id: u64, flags: i32) {
+    let mut net = LOOPBACK.lock();
+    net.socket(id).nonblocking = (flags & O_NONBLOCK) != 0;
+}
+
+/// Returns the bytes a read of the socket `id` could return right away, as `ioctl(FIONREAD)` does:
+/// the buffered bytes of a stream socket, or the size of the next datagram
+pub(crate) fn bytes_available(id: u64) -> i32 {
+    let mut net = LOOPBACK.lock();
+    let socket = net.socket(id);
+    if socket.socktype == SOCK_DGRAM {
+        socket
+            .dgrams
+            .front()
+            .map_or(0, |(_, data, _)| data.len() as i32)
+    } else {
+        socket.stream.len() as i32
+    }
+}
+
+/// Returns the poll events of the socket `id` among `events`, plus the error conditions that are
+/// always reported
+pub(crate) fn poll_events(id: u64, events: i16) -> i16 {
+    let net = LOOPBACK.lock();
+    let socket = match net.sockets.get(&id) {
+        Some(socket) => socket,
+        None => return POLLNVAL,
+    };
+    let mut revents = 0;
+    if socket.socktype == SOCK_DGRAM {
+        if !socket.dgrams.is_empty() || socket.shut_rd {
+            revents |= POLLIN;
+        }
+        if !socket.shut_wr {
+            revents |= POLLOUT;
+        }
+    } else if let Some(ref listener) = socket.listener {
+        if !listener.pending.is_empty() {
+            revents |= POLLIN;
+        }
+    } else if !socket.connected {
+        // as a TCP socket that was never connected
+        revents |= POLLOUT | POLLHUP;
+    } else {
+        if !socket.stream.is_empty() || socket.eof || socket.shut_rd {
+            revents |= POLLIN;
+        }
+        match socket.peer.and_then(|peer| net.sockets.get(&peer)) {
+            Some(peer) if !socket.shut_wr => {
+                if peer.shut_rd {
+                    revents |= POLLOUT | POLLERR;
+                } else if peer.stream.len() < LOOPBACK_BUFSIZE {
+                    revents |= POLLOUT;
+                }
+            }
+            // writing fails right away with EPIPE
+            _ => revents |= POLLOUT,
+        }
+        if socket.peer.is_none() || (socket.eof && socket.shut_wr) {
+            revents |= POLLHUP;
+        }
+    }
+    revents & (events | POLLERR | POLLHUP)
+}
@@ -1,6 +1,13 @@
 //! This module contains actual syscall implementation in RawPOSIX
 pub mod fs_calls;
+pub mod locks;
+pub mod loopback;
 pub mod net_calls;
+pub mod pipe;
 pub mod sys_calls;
 
-pub use sys_calls::{lindrustfinalize, lindrustinit};
+pub use loopback::{set_loopback_policy, LoopbackPolicy};
+pub use pipe::set_inmemory_pipes;
+pub use sys_calls::{
+    lindrustfinalize, lindrustinit, set_initial_credentials, set_initial_memory_limits,
+};
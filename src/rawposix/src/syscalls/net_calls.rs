@@ -2,10 +2,79 @@ use typemap::syscall_conv::*;
 use typemap::type_conv::*;
 use fdtables;
 use sysdefs::constants::err_const::{get_errno, handle_errno, syscall_error, Errno};
+use sysdefs::constants::fs_const::{FDKIND_LOOPBACK, FDKIND_PIPE, PROT_READ, PROT_WRITE};
+use sysdefs::constants::net_const::{
+    EPOLLONESHOT, EPOLL_CTL_DEL, EPOLL_CTL_MOD, FD_SET_MAX_FD, MSG_CMSG_CLOEXEC, MSG_CTRUNC,
+    POLLERR, POLLHUP, POLLIN, POLLNVAL, POLLOUT, POLLPRI, SCM_MAX_FD, SCM_RIGHTS, SOCK_CLOEXEC,
+    SOL_SOCKET,
+};
+use sysdefs::data::fs_struct::{EpollEvent, SockPair, TimeVal, WasmCmsghdr, WasmMsghdr};
+use crate::syscalls::fs_calls::{_fd_alloc_errno, _iovec_to_host};
+use crate::syscalls::loopback::{self, InflightFds, LoopbackAddr};
+use crate::syscalls::pipe;
+use crate::syscalls::sys_calls::{_duration_to_timeval, _timeval_to_duration};
 use libc::*;
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
 
 const FDKIND_KERNEL: u32 = 0;
 
+/// Translates a guest pointer to `len` bytes the syscall accesses with `prot`. NULL stays NULL, so
+/// that the loopback module and the host kernel can fail with EFAULT. Returns the host pointer, or the
+/// negative errno to return to the cage if the cage can't access the memory.
+fn _nullable_addr(
+    addr_arg: u64,
+    addr_cageid: u64,
+    cageid: u64,
+    len: usize,
+    prot: i32,
+    syscall_name: &str,
+) -> Result<*mut u8, i32> {
+    if addr_arg == 0 {
+        return Ok(std::ptr::null_mut());
+    }
+    sc_convert_addr_to_host_checked(addr_arg, addr_cageid, cageid, len, prot)
+        .map_err(|e| syscall_error(e, syscall_name, "Invalid address"))
+}
+
+/// Translates the address a syscall sends to or binds, NULL staying NULL. `get_sockaddr` copies a
+/// whole `sockaddr_un` whatever the family, so that much must be readable.
+fn _sockaddr_to_host(
+    addr_arg: u64,
+    addr_cageid: u64,
+    cageid: u64,
+    syscall_name: &str,
+) -> Result<*mut u8, i32> {
+    let size = std::mem::size_of::<sockaddr_un>();
+    _nullable_addr(addr_arg, addr_cageid, cageid, size, PROT_READ, syscall_name)
+}
+
+/// Translates a buffer the syscall writes (an address or an option value) together with the pointer
+/// to its length, which the syscall reads and updates. The buffer must have room for as many bytes
+/// as the length says, a NULL buffer stays NULL.
+fn _outbuf_to_host(
+    buf_arg: u64,
+    buf_cageid: u64,
+    len_arg: u64,
+    len_cageid: u64,
+    cageid: u64,
+    syscall_name: &str,
+) -> Result<(*mut u8, *mut u32), i32> {
+    let lenptr = match sc_convert_addr_to_host_checked(
+        len_arg,
+        len_cageid,
+        cageid,
+        std::mem::size_of::<u32>(),
+        PROT_READ | PROT_WRITE,
+    ) {
+        Ok(addr) => addr as *mut u32,
+        Err(e) => return Err(syscall_error(e, syscall_name, "Invalid length address")),
+    };
+    let len = unsafe { *lenptr } as usize;
+    let buf = _nullable_addr(buf_arg, buf_cageid, cageid, len, PROT_WRITE, syscall_name)?;
+    Ok((buf, lenptr))
+}
+
 /// Reference to Linux: https://man7.org/linux/man-pages/man2/socket.2.html
 ///
 /// The Linux `socket()` syscall creates an endpoint for communication and returns a file descriptor
@@ -50,6 +119,14 @@ pub fn socket_syscall(
         return syscall_error(Errno::EFAULT, "socket_syscall", "Invalide Cage ID");
     }
 
+    if loopback::is_loopback_domain(domain) {
+        let sockid = match loopback::socket(domain, socktype, protocol) {
+            Ok(sockid) => sockid,
+            Err(e) => return e,
+        };
+        return loopback::install_fd(cageid, sockid, (socktype & SOCK_CLOEXEC) != 0, "socket");
+    }
+
     let kernel_fd = unsafe { libc::socket(domain, socktype, protocol) };
        
         if kernel_fd < 0 {
@@ -93,7 +170,6 @@ pub fn connect_syscall(
 ) -> i32 {
 
     let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
-    let addr = sc_convert_addr_to_host(addr_arg, addr_cageid, cageid);
     
     if !(sc_unusedarg(arg3, arg3_cageid)
         &&sc_unusedarg(arg4, arg4_cageid)
@@ -102,6 +178,15 @@ pub fn connect_syscall(
     {
         return syscall_error(Errno::EFAULT, "connect_syscall", "Invalide Cage ID");
     }
+
+    let addr = match _sockaddr_to_host(addr_arg, addr_cageid, cageid, "connect") {
+        Ok(addr) => addr,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        return loopback::connect(cageid, sockid, addr);
+    }
     
     let (finalsockaddr, addrlen) = get_sockaddr(addr);
 
@@ -145,7 +230,6 @@ pub fn bind_syscall(
     arg6_cageid: u64,
 ) -> i32 {
     let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
-    let addr = sc_convert_addr_to_host(addr_arg, addr_cageid, cageid);
 
     if !(sc_unusedarg(arg3, arg3_cageid)
     &&sc_unusedarg(arg4, arg4_cageid)
@@ -155,6 +239,15 @@ pub fn bind_syscall(
         return syscall_error(Errno::EFAULT, "bind_syscall", "Invalide Cage ID");
     }
 
+    let addr = match _sockaddr_to_host(addr_arg, addr_cageid, cageid, "bind") {
+        Ok(addr) => addr,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        return loopback::bind(sockid, addr);
+    }
+
     let (finalsockaddr, addrlen) = get_sockaddr(addr);
 
     let ret = unsafe { libc::bind(fd, finalsockaddr, addrlen) };
@@ -205,6 +298,10 @@ pub fn listen_syscall(
         return syscall_error(Errno::EFAULT, "listen_syscall", "Invalide Cage ID");
     }
 
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        return loopback::listen(sockid, backlog);
+    }
+
     let ret = unsafe { libc::listen(fd, backlog) };
     if ret < 0 {
         let errno = get_errno();
@@ -248,7 +345,6 @@ pub fn accept_syscall(
     arg6_cageid: u64,
 ) -> i32{
     let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
-    let addr = sc_convert_addr_to_host(addr_arg, addr_cageid, cageid);
 
     if !(sc_unusedarg(arg4, arg4_cageid)
     && sc_unusedarg(arg5, arg5_cageid)
@@ -257,14 +353,41 @@ pub fn accept_syscall(
         return syscall_error(Errno::EFAULT, "accept_syscall", "Invalide Cage ID");
     }
 
-    let (finalsockaddr, mut addrlen) = get_sockaddr(addr);
+    // The peer address is only returned when both the address and its length are given
+    let (addr, len) = if addr_arg != 0 && len_arg != 0 {
+        match _outbuf_to_host(addr_arg, addr_cageid, len_arg, len_cageid, cageid, "accept") {
+            Ok(outbuf) => outbuf,
+            Err(e) => return e,
+        }
+    } else {
+        (std::ptr::null_mut(), std::ptr::null_mut())
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let (newid, peer) = match loopback::accept(cageid, sockid) {
+            Ok(accepted) => accepted,
+            Err(e) => return e,
+        };
+        let virtualfd = loopback::install_fd(cageid, newid, false, "accept");
+        if virtualfd >= 0 && !addr.is_null() {
+            loopback::copy_out_addr(addr, len, &peer);
+        }
+        return virtualfd;
+    }
 
-    let ret_kernelfd = unsafe { libc::accept(fd, finalsockaddr, &mut addrlen as *mut u32) };
+    let mut hostaddr: sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut hostlen = std::mem::size_of::<sockaddr_storage>() as u32;
+    let ret_kernelfd = unsafe {
+        libc::accept(fd, &mut hostaddr as *mut sockaddr_storage as *mut sockaddr, &mut hostlen)
+    };
 
     if ret_kernelfd < 0 {
         let errno = get_errno();
         return handle_errno(errno, "accept");
     }
+    if !addr.is_null() {
+        copy_out_sockaddr(addr, len, &hostaddr, hostlen);
+    }
 
     let ret_virtualfd = fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, ret_kernelfd as u64, false, 0).unwrap();
     
@@ -308,13 +431,33 @@ pub fn setsockopt_syscall(
     let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
     let level = sc_convert_sysarg_to_i32(level_arg, level_cageid, cageid);
     let optname = sc_convert_sysarg_to_i32(optname_arg, optname_cageid, cageid);
-    let optval = sc_convert_addr_to_host(optval_arg, optval_cageid, cageid);
     let optlen = sc_convert_sysarg_to_u32(optlen_arg, optlen_cageid, cageid);
 
     if !(sc_unusedarg(arg6, arg6_cageid))
     {
         return syscall_error(Errno::EFAULT, "setsockopt_syscall", "Invalide Cage ID");
     }
+
+    let optval = match _nullable_addr(
+        optval_arg,
+        optval_cageid,
+        cageid,
+        optlen as usize,
+        PROT_READ,
+        "setsockopt",
+    ) {
+        Ok(optval) => optval,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let optval = if optval_arg == 0 {
+            &[][..]
+        } else {
+            unsafe { std::slice::from_raw_parts(optval, optlen as usize) }
+        };
+        return loopback::setsockopt(sockid, level, optname, optval);
+    }
     let ret = unsafe { 
         libc::setsockopt(fd, level, optname, optval as *mut c_void, optlen)
     };
@@ -357,7 +500,6 @@ pub fn send_syscall(
     arg6_cageid: u64,
 ) -> i32{
     let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
-    let buf = sc_convert_buf_to_host(buf_arg, buf_cageid, cageid);
     let buflen = sc_convert_sysarg_to_usize(buflen_arg, buflen_cageid, cageid);
     let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
 
@@ -367,6 +509,16 @@ pub fn send_syscall(
         return syscall_error(Errno::EFAULT, "send_syscall", "Invalide Cage ID");
     }
 
+    let buf = match sc_convert_buf_to_host_checked(buf_arg, buf_cageid, cageid, buflen) {
+        Ok(buf) => buf,
+        Err(e) => return syscall_error(e, "send", "Invalid buffer"),
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let data = unsafe { std::slice::from_raw_parts(buf, buflen) };
+        return loopback::send(cageid, sockid, data, flags, std::ptr::null());
+    }
+
     let ret = unsafe { libc::send(fd as i32, buf as *const c_void, buflen, flags) as i32};
     if ret < 0 {
         let errno = get_errno();
@@ -407,7 +559,6 @@ pub fn recv_syscall(
     arg6_cageid: u64,
 ) -> i32{
     let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
-    let buf = sc_convert_buf_to_host(buf_arg, buf_cageid, cageid);
     let buflen = sc_convert_sysarg_to_usize(buflen_arg, buflen_cageid, cageid);
     let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
 
@@ -417,6 +568,20 @@ pub fn recv_syscall(
         return syscall_error(Errno::EFAULT, "recv_syscall", "Invalide Cage ID");
     }
 
+    let buf = match sc_convert_addr_to_host_checked(buf_arg, buf_cageid, cageid, buflen, PROT_WRITE)
+    {
+        Ok(buf) => buf,
+        Err(e) => return syscall_error(e, "recv", "Invalid buffer"),
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let data = unsafe { std::slice::from_raw_parts_mut(buf, buflen) };
+        return match loopback::recv(cageid, sockid, data, flags) {
+            Ok((len, _, _, _)) => len as i32,
+            Err(e) => e,
+        };
+    }
+
     let ret = unsafe { libc::recv(fd, buf as *mut c_void, buflen, flags) as i32 };
     if ret < 0 {
         let errno = get_errno();
@@ -424,3 +589,1764 @@ pub fn recv_syscall(
     }
     ret
 }
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/sendto.2.html
+///
+/// The Linux `sendto()` syscall transmits a message to the socket, optionally to the given destination
+/// address. This implementation translates the virtual file descriptor and the buffer of the current cage.
+/// If a destination address is given and it is a UNIX domain socket (AF_UNIX), the path is rewritten to
+/// include `LIND_ROOT`, the same way `connect_syscall` does.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor of the socket
+///     - buf_arg: pointer to the message buffer in user memory
+///     - buflen_arg: length of the message to be sent
+///     - flags_arg: bitmask of flags influencing message transmission behavior
+///     - addr_arg: pointer to the destination address, may be NULL for connected sockets
+///     - addrlen_arg: not used in this implementation, the length is derived from the address family
+///
+/// Return:
+///     - On success: number of bytes sent
+///     - On failure: a negative errno value indicating the syscall error
+pub fn sendto_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    buflen_arg: u64,
+    buflen_cageid: u64,
+    flags_arg: u64,
+    flags_cageid: u64,
+    addr_arg: u64,
+    addr_cageid: u64,
+    _addrlen_arg: u64,
+    _addrlen_cageid: u64,
+) -> i32 {
+    let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
+    let buflen = sc_convert_sysarg_to_usize(buflen_arg, buflen_cageid, cageid);
+    let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
+    let buf = match sc_convert_buf_to_host_checked(buf_arg, buf_cageid, cageid, buflen) {
+        Ok(buf) => buf,
+        Err(e) => return syscall_error(e, "sendto", "Invalid buffer"),
+    };
+    let addr = match _sockaddr_to_host(addr_arg, addr_cageid, cageid, "sendto") {
+        Ok(addr) => addr,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let data = unsafe { std::slice::from_raw_parts(buf, buflen) };
+        return loopback::send(cageid, sockid, data, flags, addr);
+    }
+
+    let (finalsockaddr, addrlen) = get_sockaddr(addr);
+
+    let ret = unsafe {
+        libc::sendto(fd, buf as *const c_void, buflen, flags, finalsockaddr, addrlen) as i32
+    };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "sendto");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/recvfrom.2.html
+///
+/// The Linux `recvfrom()` syscall receives a message from a socket and optionally the address of its
+/// sender. The sender address is received into a host buffer and copied back with `copy_out_sockaddr`,
+/// which strips the `LIND_ROOT` prefix from UNIX domain socket paths.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor of the socket
+///     - buf_arg: pointer to the buffer in user memory to store received data
+///     - buflen_arg: size of the buffer
+///     - flags_arg: flags controlling message reception behavior
+///     - addr_arg: pointer to the buffer receiving the sender address, may be NULL
+///     - addrlen_arg: pointer to the size of the address buffer, updated with the address length
+///
+/// Return:
+///     - On success: number of bytes received
+///     - On failure: a negative errno value indicating the syscall error
+pub fn recvfrom_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    buf_arg: u64,
+    buf_cageid: u64,
+    buflen_arg: u64,
+    buflen_cageid: u64,
+    flags_arg: u64,
+    flags_cageid: u64,
+    addr_arg: u64,
+    addr_cageid: u64,
+    addrlen_arg: u64,
+    addrlen_cageid: u64,
+) -> i32 {
+    let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
+    let buflen = sc_convert_sysarg_to_usize(buflen_arg, buflen_cageid, cageid);
+    let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
+
+    if addr_arg != 0 && addrlen_arg == 0 {
+        return syscall_error(Errno::EFAULT, "recvfrom_syscall", "addrlen is NULL");
+    }
+
+    let buf = match sc_convert_addr_to_host_checked(buf_arg, buf_cageid, cageid, buflen, PROT_WRITE)
+    {
+        Ok(buf) => buf,
+        Err(e) => return syscall_error(e, "recvfrom", "Invalid buffer"),
+    };
+    let (addr, addrlen) = if addr_arg != 0 {
+        match _outbuf_to_host(
+            addr_arg,
+            addr_cageid,
+            addrlen_arg,
+            addrlen_cageid,
+            cageid,
+            "recvfrom",
+        ) {
+            Ok(outbuf) => outbuf,
+            Err(e) => return e,
+        }
+    } else {
+        (std::ptr::null_mut(), std::ptr::null_mut())
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let data = unsafe { std::slice::from_raw_parts_mut(buf, buflen) };
+        let (len, source) = match loopback::recv(cageid, sockid, data, flags) {
+            Ok((len, source, _, _)) => (len, source),
+            Err(e) => return e,
+        };
+        if !addr.is_null() {
+            match source {
+                Some(source) => loopback::copy_out_addr(addr, addrlen, &source),
+                // stream sockets don't report the sender
+                None => unsafe { *addrlen = 0 },
+            }
+        }
+        return len as i32;
+    }
+
+    if addr.is_null() {
+        let ret = unsafe {
+            libc::recvfrom(fd, buf as *mut c_void, buflen, flags, std::ptr::null_mut(), std::ptr::null_mut()) as i32
+        };
+        if ret < 0 {
+            let errno = get_errno();
+            return handle_errno(errno, "recvfrom");
+        }
+        return ret;
+    }
+
+    let mut hostaddr: sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut hostlen = std::mem::size_of::<sockaddr_storage>() as u32;
+    let ret = unsafe {
+        libc::recvfrom(
+            fd,
+            buf as *mut c_void,
+            buflen,
+            flags,
+            &mut hostaddr as *mut sockaddr_storage as *mut sockaddr,
+            &mut hostlen,
+        ) as i32
+    };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "recvfrom");
+    }
+    copy_out_sockaddr(addr, addrlen, &hostaddr, hostlen);
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getsockopt.2.html
+///
+/// The Linux `getsockopt()` syscall retrieves the value of an option of a socket. This implementation
+/// translates the virtual file descriptor and the option buffers of the current cage before calling
+/// the host kernel, which writes the option value and its length directly into the cage memory.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor representing the socket
+///     - level_arg: protocol level at which the option resides (e.g., SOL_SOCKET)
+///     - optname_arg: option name to be retrieved (e.g., SO_ERROR)
+///     - optval_arg: pointer to the buffer receiving the option value
+///     - optlen_arg: pointer to the size of the option buffer, updated with the option length
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value indicating the syscall error
+pub fn getsockopt_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    level_arg: u64,
+    level_cageid: u64,
+    optname_arg: u64,
+    optname_cageid: u64,
+    optval_arg: u64,
+    optval_cageid: u64,
+    optlen_arg: u64,
+    optlen_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
+    let level = sc_convert_sysarg_to_i32(level_arg, level_cageid, cageid);
+    let optname = sc_convert_sysarg_to_i32(optname_arg, optname_cageid, cageid);
+
+    if !(sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getsockopt_syscall", "Invalide Cage ID");
+    }
+
+    if optlen_arg == 0 {
+        return syscall_error(Errno::EFAULT, "getsockopt_syscall", "optlen is NULL");
+    }
+    let (optval, optlen) = match _outbuf_to_host(
+        optval_arg,
+        optval_cageid,
+        optlen_arg,
+        optlen_cageid,
+        cageid,
+        "getsockopt",
+    ) {
+        Ok(outbuf) => outbuf,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let value = match loopback::getsockopt(sockid, level, optname) {
+            Ok(value) => value,
+            Err(e) => return e,
+        };
+        // as in Linux, the value is truncated to the buffer
+        unsafe {
+            let len = (*optlen as usize).min(value.len());
+            if !optval.is_null() {
+                std::ptr::copy_nonoverlapping(value.as_ptr(), optval, len);
+            }
+            *optlen = len as u32;
+        }
+        return 0;
+    }
+
+    let ret = unsafe { libc::getsockopt(fd, level, optname, optval as *mut c_void, optlen) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "getsockopt");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/shutdown.2.html
+///
+/// The Linux `shutdown()` syscall shuts down all or part of a full-duplex connection on a socket.
+/// This implementation translates the virtual file descriptor and forwards the call to the host kernel.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor of the socket
+///     - how_arg: SHUT_RD, SHUT_WR or SHUT_RDWR
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value indicating the syscall error
+pub fn shutdown_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    how_arg: u64,
+    how_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
+    let how = sc_convert_sysarg_to_i32(how_arg, how_cageid, cageid);
+
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "shutdown_syscall", "Invalide Cage ID");
+    }
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        return loopback::shutdown(sockid, how);
+    }
+
+    let ret = unsafe { libc::shutdown(fd, how) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "shutdown");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/socketpair.2.html
+///
+/// The Linux `socketpair()` syscall creates a pair of connected sockets. The host sockets are created by
+/// the kernel and both ends are registered in the virtual file descriptor table (`fdtables`) of the
+/// current cage. As for `socket_syscall`, `SOCK_NONBLOCK` is handled by the kernel, while `SOCK_CLOEXEC`
+/// is recorded in `fdtables` so that it is honored on exec.
+///
+/// Input:
+///     - cageid: current cageid
+///     - domain_arg: communication domain (usually AF_UNIX)
+///     - socktype_arg: socket type, possibly or'ed with SOCK_NONBLOCK and SOCK_CLOEXEC
+///     - protocol_arg: protocol to be used (usually 0)
+///     - sv_arg: pointer to the `SockPair` receiving the two virtual file descriptors
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value indicating the syscall error
+pub fn socketpair_syscall(
+    cageid: u64,
+    domain_arg: u64,
+    domain_cageid: u64,
+    socktype_arg: u64,
+    socktype_cageid: u64,
+    protocol_arg: u64,
+    protocol_cageid: u64,
+    sv_arg: u64,
+    sv_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let domain = sc_convert_sysarg_to_i32(domain_arg, domain_cageid, cageid);
+    let socktype = sc_convert_sysarg_to_i32(socktype_arg, socktype_cageid, cageid);
+    let protocol = sc_convert_sysarg_to_i32(protocol_arg, protocol_cageid, cageid);
+
+    if !(sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "socketpair_syscall", "Invalide Cage ID");
+    }
+
+    if sv_arg == 0 {
+        return syscall_error(Errno::EFAULT, "socketpair_syscall", "sv is NULL");
+    }
+    let svsize = std::mem::size_of::<SockPair>();
+    let sv = match sc_convert_addr_to_host_checked(sv_arg, sv_cageid, cageid, svsize, PROT_WRITE) {
+        Ok(addr) => match get_sockpair(addr as u64) {
+            Ok(sv) => sv,
+            Err(e) => return e,
+        },
+        Err(e) => return syscall_error(e, "socketpair", "Invalid address"),
+    };
+
+    if loopback::is_loopback_domain(domain) {
+        let (sockid1, sockid2) = match loopback::socketpair(domain, socktype, protocol) {
+            Ok(sockids) => sockids,
+            Err(e) => return e,
+        };
+        let should_cloexec = (socktype & SOCK_CLOEXEC) != 0;
+        let sock1 = loopback::install_fd(cageid, sockid1, should_cloexec, "socketpair");
+        if sock1 < 0 {
+            loopback::close(sockid2);
+            return sock1;
+        }
+        let sock2 = loopback::install_fd(cageid, sockid2, should_cloexec, "socketpair");
+        if sock2 < 0 {
+            let _ = fdtables::close_virtualfd(cageid, sock1 as u64);
+            return sock2;
+        }
+        sv.sock1 = sock1;
+        sv.sock2 = sock2;
+        return 0;
+    }
+
+    let mut kernel_socks: [i32; 2] = [0; 2];
+    let ret = unsafe { libc::socketpair(domain, socktype, protocol, kernel_socks.as_mut_ptr()) };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "socketpair");
+    }
+
+    let should_cloexec = (socktype & SOCK_CLOEXEC) != 0;
+    let sock1 = fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, kernel_socks[0] as u64, should_cloexec, 0);
+    let sock2 = fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, kernel_socks[1] as u64, should_cloexec, 0);
+    match (sock1, sock2) {
+        (Ok(sock1), Ok(sock2)) => {
+            sv.sock1 = sock1 as i32;
+            sv.sock2 = sock2 as i32;
+            0
+        }
+        (sock1, sock2) => {
+            let errno = match (&sock1, &sock2) {
+                (Err(e), _) | (_, Err(e)) => _fd_alloc_errno(*e),
+                _ => unreachable!(),
+            };
+            // out of virtual fds, undo whatever was registered
+            for (virtualfd, kernelfd) in [(sock1, kernel_socks[0]), (sock2, kernel_socks[1])] {
+                match virtualfd {
+                    Ok(virtualfd) => {
+                        let _ = fdtables::close_virtualfd(cageid, virtualfd);
+                    }
+                    Err(_) => unsafe {
+                        libc::close(kernelfd);
+                    },
+                }
+            }
+            syscall_error(errno, "socketpair_syscall", "too many open files")
+        }
+    }
+}
+
+/// Shared implementation of getsockname and getpeername, which only differ by the host call used to
+/// retrieve the address, and its in-memory socket counterpart. The address is copied back with
+/// `copy_out_sockaddr`, which strips the `LIND_ROOT` prefix from UNIX domain socket paths.
+fn _get_socket_address(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    addr_arg: u64,
+    addr_cageid: u64,
+    addrlen_arg: u64,
+    addrlen_cageid: u64,
+    syscall_name: &str,
+    getaddr: unsafe extern "C" fn(c_int, *mut sockaddr, *mut socklen_t) -> c_int,
+    loopback_getaddr: fn(u64) -> Result<LoopbackAddr, i32>,
+) -> i32 {
+    let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
+
+    if addr_arg == 0 || addrlen_arg == 0 {
+        return syscall_error(Errno::EFAULT, syscall_name, "addr or addrlen is NULL");
+    }
+    let (addr, addrlen) = match _outbuf_to_host(
+        addr_arg,
+        addr_cageid,
+        addrlen_arg,
+        addrlen_cageid,
+        cageid,
+        syscall_name,
+    ) {
+        Ok(outbuf) => outbuf,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        return match loopback_getaddr(sockid) {
+            Ok(sockaddr) => {
+                loopback::copy_out_addr(addr, addrlen, &sockaddr);
+                0
+            }
+            Err(e) => e,
+        };
+    }
+
+    let mut hostaddr: sockaddr_storage = unsafe { std::mem::zeroed() };
+    let mut hostlen = std::mem::size_of::<sockaddr_storage>() as u32;
+    let ret = unsafe {
+        getaddr(fd, &mut hostaddr as *mut sockaddr_storage as *mut sockaddr, &mut hostlen)
+    };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, syscall_name);
+    }
+    copy_out_sockaddr(addr, addrlen, &hostaddr, hostlen);
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getsockname.2.html
+///
+/// The Linux `getsockname()` syscall returns the address the socket is bound to.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor of the socket
+///     - addr_arg: pointer to the buffer receiving the address
+///     - addrlen_arg: pointer to the size of the address buffer, updated with the address length
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value indicating the syscall error
+pub fn getsockname_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    addr_arg: u64,
+    addr_cageid: u64,
+    addrlen_arg: u64,
+    addrlen_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getsockname_syscall", "Invalide Cage ID");
+    }
+
+    _get_socket_address(
+        cageid,
+        fd_arg,
+        fd_cageid,
+        addr_arg,
+        addr_cageid,
+        addrlen_arg,
+        addrlen_cageid,
+        "getsockname",
+        libc::getsockname,
+        loopback::getsockname,
+    )
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getpeername.2.html
+///
+/// The Linux `getpeername()` syscall returns the address of the peer connected to the socket.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor of the socket
+///     - addr_arg: pointer to the buffer receiving the address
+///     - addrlen_arg: pointer to the size of the address buffer, updated with the address length
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value indicating the syscall error
+pub fn getpeername_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    addr_arg: u64,
+    addr_cageid: u64,
+    addrlen_arg: u64,
+    addrlen_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getpeername_syscall", "Invalide Cage ID");
+    }
+
+    _get_socket_address(
+        cageid,
+        fd_arg,
+        fd_cageid,
+        addr_arg,
+        addr_cageid,
+        addrlen_arg,
+        addrlen_cageid,
+        "getpeername",
+        libc::getpeername,
+        loopback::getpeername,
+    )
+}
+
+/// Size of the guest `cmsghdr`, the data of a control message starts right after it
+const GUEST_CMSG_HDRLEN: usize = std::mem::size_of::<WasmCmsghdr>();
+
+/// Guest version of `CMSG_ALIGN`: control messages are aligned to the 4 bytes of the guest `size_t`
+fn _guest_cmsg_align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+/// A guest control message: its level, its type and its data
+type GuestCmsg<'a> = (i32, i32, &'a [u8]);
+
+/// Splits the guest control buffer of `sendmsg` into its control messages
+///
+/// Return:
+///     - the control messages, or the negative errno to return to the cage
+fn _guest_cmsgs<'a>(control: *const u8, controllen: usize) -> Result<Vec<GuestCmsg<'a>>, i32> {
+    let mut cmsgs = Vec::new();
+    let mut offset = 0;
+    while offset + GUEST_CMSG_HDRLEN <= controllen {
+        let hdr = unsafe { std::ptr::read_unaligned(control.add(offset) as *const WasmCmsghdr) };
+        let cmsg_len = hdr.cmsg_len as usize;
+        if cmsg_len < GUEST_CMSG_HDRLEN || offset + cmsg_len > controllen {
+            return Err(syscall_error(Errno::EINVAL, "sendmsg", "invalid control message length"));
+        }
+        let data = unsafe {
+            std::slice::from_raw_parts(control.add(offset + GUEST_CMSG_HDRLEN), cmsg_len - GUEST_CMSG_HDRLEN)
+        };
+        cmsgs.push((hdr.cmsg_level, hdr.cmsg_type, data));
+        offset += _guest_cmsg_align(cmsg_len);
+    }
+    Ok(cmsgs)
+}
+
+/// Translates the guest control messages of `sendmsg` into a host control buffer. The virtual fds
+/// passed with `SCM_RIGHTS` are translated into the kernel fds they stand for, so that the kernel
+/// can duplicate them into the receiver. Other control messages are passed through unchanged.
+///
+/// Pipes and in-memory sockets have no kernel fd the kernel could pass, they can only be passed over
+/// in-memory AF_UNIX sockets (see `LoopbackPolicy::Unix`). Passing one over a kernel socket fails
+/// with EOPNOTSUPP.
+///
+/// Return:
+///     - the host control buffer, as u64s to get the alignment of the host `cmsghdr`, or the
+///       negative errno to return to the cage
+fn _cmsgs_to_host(cageid: u64, control: *const u8, controllen: usize) -> Result<Vec<u64>, i32> {
+    let mut cmsgs = Vec::new();
+    for (level, cmsgtype, data) in _guest_cmsgs(control, controllen)? {
+        let hostdata = if level == SOL_SOCKET && cmsgtype == SCM_RIGHTS {
+            let mut kernelfds = Vec::with_capacity(data.len());
+            for virtualfd in data.chunks_exact(4) {
+                let virtualfd = i32::from_ne_bytes(virtualfd.try_into().unwrap());
+                let entry = if virtualfd < 0 {
+                    None
+                } else {
+                    fdtables::translate_virtual_fd(cageid, virtualfd as u64).ok()
+                };
+                match entry {
+                    Some(entry) if entry.fdkind == FDKIND_KERNEL => {
+                        kernelfds.extend_from_slice(&(entry.underfd as i32).to_ne_bytes());
+                    }
+                    Some(_) => {
+                        return Err(syscall_error(
+                            Errno::EOPNOTSUPP,
+                            "sendmsg",
+                            "only kernel fds can be passed over a kernel socket",
+                        ))
+                    }
+                    None => return Err(syscall_error(Errno::EBADF, "sendmsg", "invalid fd in SCM_RIGHTS")),
+                }
+            }
+            kernelfds
+        } else {
+            data.to_vec()
+        };
+        cmsgs.push((level, cmsgtype, hostdata));
+    }
+
+    let space: usize = cmsgs
+        .iter()
+        .map(|(_, _, data)| unsafe { CMSG_SPACE(data.len() as u32) } as usize)
+        .sum();
+    let mut hostcontrol = vec![0u64; space.div_ceil(8)];
+    let mut offset = 0;
+    for (level, cmsgtype, data) in cmsgs {
+        unsafe {
+            let hdr = (hostcontrol.as_mut_ptr() as *mut u8).add(offset) as *mut cmsghdr;
+            (*hdr).cmsg_len = CMSG_LEN(data.len() as u32) as usize;
+            (*hdr).cmsg_level = level;
+            (*hdr).cmsg_type = cmsgtype;
+            std::ptr::copy_nonoverlapping(data.as_ptr(), CMSG_DATA(hdr), data.len());
+            offset += CMSG_SPACE(data.len() as u32) as usize;
+        }
+    }
+    Ok(hostcontrol)
+}
+
+/// Collects the virtual fds passed with `SCM_RIGHTS` in the guest control messages of a `sendmsg` on
+/// an in-memory socket, and holds their open files until the peer receives them. As in Linux, control
+/// messages of other levels are ignored and other `SOL_SOCKET` ones are rejected.
+///
+/// Return:
+///     - the fds in flight, or the negative errno to return to the cage
+fn _loopback_rights(cageid: u64, control: *const u8, controllen: usize) -> Result<InflightFds, i32> {
+    let mut virtualfds = Vec::new();
+    for (level, cmsgtype, data) in _guest_cmsgs(control, controllen)? {
+        if level != SOL_SOCKET {
+            continue;
+        }
+        if cmsgtype != SCM_RIGHTS {
+            return Err(syscall_error(
+                Errno::EINVAL,
+                "sendmsg",
+                "unsupported control message on an in-memory socket",
+            ));
+        }
+        for virtualfd in data.chunks_exact(4) {
+            virtualfds.push(i32::from_ne_bytes(virtualfd.try_into().unwrap()));
+        }
+        if virtualfds.len() > SCM_MAX_FD {
+            return Err(syscall_error(Errno::EINVAL, "sendmsg", "too many fds in SCM_RIGHTS"));
+        }
+    }
+    InflightFds::hold(cageid, &virtualfds)
+}
+
+/// Writes the fds received on an in-memory socket into the guest control buffer of `recvmsg`, as a
+/// single `SCM_RIGHTS` control message. As in Linux, the fds that don't fit are closed and
+/// `MSG_CTRUNC` is reported.
+///
+/// Return:
+///     - the length of the guest control message, and the flags to add to `msg_flags`
+fn _loopback_rights_to_guest(
+    cageid: u64,
+    rights: InflightFds,
+    control: *mut u8,
+    controllen: usize,
+    should_cloexec: bool,
+) -> (usize, i32) {
+    let max = if control.is_null() {
+        0
+    } else {
+        controllen.saturating_sub(GUEST_CMSG_HDRLEN) / 4
+    };
+    let (virtualfds, dropped) = rights.install(cageid, should_cloexec, max);
+    let ctrunc = if dropped { MSG_CTRUNC } else { 0 };
+    if virtualfds.is_empty() {
+        return (0, ctrunc);
+    }
+    let datalen = virtualfds.len() * 4;
+    let hdr = WasmCmsghdr {
+        cmsg_len: (GUEST_CMSG_HDRLEN + datalen) as u32,
+        cmsg_level: SOL_SOCKET,
+        cmsg_type: SCM_RIGHTS,
+    };
+    unsafe {
+        std::ptr::write_unaligned(control as *mut WasmCmsghdr, hdr);
+        std::ptr::copy_nonoverlapping(
+            virtualfds.as_ptr() as *const u8,
+            control.add(GUEST_CMSG_HDRLEN),
+            datalen,
+        );
+    }
+    let controllen = _guest_cmsg_align(GUEST_CMSG_HDRLEN + datalen).min(controllen);
+    (controllen, ctrunc)
+}
+
+/// Translates the host control messages received by `recvmsg` back into the guest control buffer.
+/// The kernel fds received with `SCM_RIGHTS` are registered in the fdtable of the receiving cage, and
+/// the guest sees the new virtual fds. As in Linux, whatever doesn't fit in the guest buffer is dropped
+/// and `MSG_CTRUNC` is reported, received fds that are dropped are closed.
+///
+/// Return:
+///     - the length of the guest control messages, and the flags to add to `msg_flags`
+fn _cmsgs_to_guest(
+    cageid: u64,
+    hosthdr: &msghdr,
+    control: *mut u8,
+    controllen: usize,
+    should_cloexec: bool,
+) -> (usize, i32) {
+    let mut flags = 0;
+    let mut offset = 0;
+    let mut cmsg = unsafe { CMSG_FIRSTHDR(hosthdr) };
+    while !cmsg.is_null() {
+        let (level, cmsgtype, datalen, data) = unsafe {
+            let datalen = (*cmsg).cmsg_len - CMSG_LEN(0) as usize;
+            ((*cmsg).cmsg_level, (*cmsg).cmsg_type, datalen, CMSG_DATA(cmsg) as *const u8)
+        };
+        let data = unsafe { std::slice::from_raw_parts(data, datalen) };
+        let room = controllen.saturating_sub(offset + GUEST_CMSG_HDRLEN);
+
+        let guestdata = if level == SOL_SOCKET && cmsgtype == SCM_RIGHTS {
+            let mut virtualfds = Vec::new();
+            for kernelfd in data.chunks_exact(4) {
+                let kernelfd = i32::from_ne_bytes(kernelfd.try_into().unwrap());
+                let virtualfd = if virtualfds.len() + 4 <= room {
+                    fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, kernelfd as u64, should_cloexec, 0).ok()
+                } else {
+                    None
+                };
+                match virtualfd {
+                    Some(virtualfd) => virtualfds.extend_from_slice(&(virtualfd as i32).to_ne_bytes()),
+                    None => {
+                        unsafe { libc::close(kernelfd) };
+                        flags |= MSG_CTRUNC;
+                    }
+                }
+            }
+            virtualfds
+        } else {
+            if data.len() > room {
+                flags |= MSG_CTRUNC;
+            }
+            data[..data.len().min(room)].to_vec()
+        };
+
+        if offset + GUEST_CMSG_HDRLEN <= controllen && (!guestdata.is_empty() || data.is_empty()) {
+            let hdr = WasmCmsghdr {
+                cmsg_len: (GUEST_CMSG_HDRLEN + guestdata.len()) as u32,
+                cmsg_level: level,
+                cmsg_type: cmsgtype,
+            };
+            unsafe {
+                std::ptr::write_unaligned(control.add(offset) as *mut WasmCmsghdr, hdr);
+                std::ptr::copy_nonoverlapping(
+                    guestdata.as_ptr(),
+                    control.add(offset + GUEST_CMSG_HDRLEN),
+                    guestdata.len(),
+                );
+            }
+            offset = (offset + _guest_cmsg_align(GUEST_CMSG_HDRLEN + guestdata.len())).min(controllen);
+        } else {
+            flags |= MSG_CTRUNC;
+        }
+        cmsg = unsafe { CMSG_NXTHDR(hosthdr, cmsg) };
+    }
+    (offset, flags)
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/sendmsg.2.html
+///
+/// The Linux `sendmsg()` syscall transmits a message described by a `msghdr`, made of a destination
+/// address, a vector of buffers and ancillary data. The guest `msghdr` uses 32-bit pointers, so every
+/// part is translated into its host counterpart: the address as in `sendto_syscall`, the buffers as in
+/// `writev_syscall` and the control messages by `_cmsgs_to_host`, which turns the virtual fds passed
+/// with `SCM_RIGHTS` into kernel fds. On an in-memory socket the fds passed with `SCM_RIGHTS` travel
+/// with the message instead, see `_loopback_rights`.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor of the socket
+///     - msg_arg: pointer to the guest `WasmMsghdr`
+///     - flags_arg: bitmask of flags influencing message transmission behavior
+///
+/// Return:
+///     - On success: number of bytes sent
+///     - On failure: a negative errno value indicating the syscall error
+pub fn sendmsg_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    msg_arg: u64,
+    msg_cageid: u64,
+    flags_arg: u64,
+    flags_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
+    let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
+
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "sendmsg_syscall", "Invalide Cage ID");
+    }
+
+    if msg_arg == 0 {
+        return syscall_error(Errno::EFAULT, "sendmsg_syscall", "msg is NULL");
+    }
+    let msgsize = std::mem::size_of::<WasmMsghdr>();
+    let msg = match sc_convert_addr_to_host_checked(msg_arg, msg_cageid, cageid, msgsize, PROT_READ)
+    {
+        Ok(addr) => unsafe { std::ptr::read_unaligned(addr as *const WasmMsghdr) },
+        Err(e) => return syscall_error(e, "sendmsg", "Invalid msghdr"),
+    };
+
+    let name = match _sockaddr_to_host(msg.msg_name as u64, msg_cageid, cageid, "sendmsg") {
+        Ok(name) => name,
+        Err(e) => return e,
+    };
+    let (finalsockaddr, addrlen) = get_sockaddr(name);
+
+    let mut iovs = match _iovec_to_host(msg.msg_iov as u64, msg_cageid, msg.msg_iovlen as i32, PROT_READ, "sendmsg") {
+        Ok(iovs) => iovs,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let mut rights = None;
+        if msg.msg_controllen != 0 {
+            let control = match sc_convert_addr_to_host_checked(
+                msg.msg_control as u64,
+                msg_cageid,
+                cageid,
+                msg.msg_controllen as usize,
+                PROT_READ,
+            ) {
+                Ok(control) => control,
+                Err(e) => return syscall_error(e, "sendmsg", "Invalid control buffer"),
+            };
+            match _loopback_rights(cageid, control, msg.msg_controllen as usize) {
+                Ok(held) => rights = Some(held),
+                Err(e) => return e,
+            }
+        }
+        // the fds of a message that wasn't sent are dropped with `rights`, once the socket is unlocked
+        return loopback::sendv(cageid, sockid, &iovs, flags, name, &mut rights);
+    }
+
+    let mut hostcontrol = if msg.msg_controllen == 0 {
+        Vec::new()
+    } else {
+        let control = match sc_convert_addr_to_host_checked(
+            msg.msg_control as u64,
+            msg_cageid,
+            cageid,
+            msg.msg_controllen as usize,
+            PROT_READ,
+        ) {
+            Ok(control) => control,
+            Err(e) => return syscall_error(e, "sendmsg", "Invalid control buffer"),
+        };
+        match _cmsgs_to_host(cageid, control, msg.msg_controllen as usize) {
+            Ok(hostcontrol) => hostcontrol,
+            Err(e) => return e,
+        }
+    };
+
+    let mut hosthdr: msghdr = unsafe { std::mem::zeroed() };
+    hosthdr.msg_name = finalsockaddr as *mut c_void;
+    hosthdr.msg_namelen = addrlen;
+    hosthdr.msg_iov = iovs.as_mut_ptr();
+    hosthdr.msg_iovlen = iovs.len();
+    if !hostcontrol.is_empty() {
+        hosthdr.msg_control = hostcontrol.as_mut_ptr() as *mut c_void;
+        hosthdr.msg_controllen = hostcontrol.len() * std::mem::size_of::<u64>();
+    }
+
+    let ret = unsafe { libc::sendmsg(fd, &hosthdr, flags) as i32 };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "sendmsg");
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/recvmsg.2.html
+///
+/// The Linux `recvmsg()` syscall receives a message into the buffers described by a `msghdr`, along
+/// with the sender address and ancillary data. The message is received into host counterparts of the
+/// guest `msghdr` parts, which are then translated back: the address with `copy_out_sockaddr` and the
+/// control messages by `_cmsgs_to_guest`, which registers the fds passed with `SCM_RIGHTS` in the
+/// fdtable of the receiving cage. The fds received on an in-memory socket are registered the same way
+/// by `_loopback_rights_to_guest`. `msg_namelen`, `msg_controllen` and `msg_flags` are updated in the
+/// guest `msghdr`.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fd_arg: virtual file descriptor of the socket
+///     - msg_arg: pointer to the guest `WasmMsghdr`
+///     - flags_arg: flags controlling message reception behavior, MSG_CMSG_CLOEXEC sets close-on-exec
+///       on the received fds
+///
+/// Return:
+///     - On success: number of bytes received
+///     - On failure: a negative errno value indicating the syscall error
+pub fn recvmsg_syscall(
+    cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    msg_arg: u64,
+    msg_cageid: u64,
+    flags_arg: u64,
+    flags_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let fd = convert_fd_to_host(fd_arg, fd_cageid, cageid);
+    let flags = sc_convert_sysarg_to_i32(flags_arg, flags_cageid, cageid);
+
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "recvmsg_syscall", "Invalide Cage ID");
+    }
+
+    if msg_arg == 0 {
+        return syscall_error(Errno::EFAULT, "recvmsg_syscall", "msg is NULL");
+    }
+    let msgsize = std::mem::size_of::<WasmMsghdr>();
+    let msgptr = match sc_convert_addr_to_host_checked(
+        msg_arg,
+        msg_cageid,
+        cageid,
+        msgsize,
+        PROT_READ | PROT_WRITE,
+    ) {
+        Ok(addr) => addr as *mut WasmMsghdr,
+        Err(e) => return syscall_error(e, "recvmsg", "Invalid msghdr"),
+    };
+    let mut msg = unsafe { std::ptr::read_unaligned(msgptr) };
+
+    // The address and control buffers are checked before anything is received into them
+    let namelen = msg.msg_namelen as usize;
+    let name = match _nullable_addr(
+        msg.msg_name as u64,
+        msg_cageid,
+        cageid,
+        namelen,
+        PROT_WRITE,
+        "recvmsg",
+    ) {
+        Ok(name) => name,
+        Err(e) => return e,
+    };
+    let controllen = msg.msg_controllen as usize;
+    let control = if controllen == 0 {
+        std::ptr::null_mut()
+    } else {
+        match sc_convert_addr_to_host_checked(
+            msg.msg_control as u64,
+            msg_cageid,
+            cageid,
+            controllen,
+            PROT_WRITE,
+        ) {
+            Ok(control) => control,
+            Err(e) => return syscall_error(e, "recvmsg", "Invalid control buffer"),
+        }
+    };
+
+    let mut iovs = match _iovec_to_host(msg.msg_iov as u64, msg_cageid, msg.msg_iovlen as i32, PROT_WRITE, "recvmsg") {
+        Ok(iovs) => iovs,
+        Err(e) => return e,
+    };
+
+    if let Some(sockid) = loopback::loopback_id(fd_arg, fd_cageid) {
+        let (len, source, msgflags, rights) = match loopback::recvv(cageid, sockid, &iovs, flags) {
+            Ok(received) => received,
+            Err(e) => return e,
+        };
+        if !name.is_null() {
+            match source {
+                Some(source) => loopback::copy_out_addr(name, &mut msg.msg_namelen, &source),
+                None => msg.msg_namelen = 0,
+            }
+        }
+        msg.msg_controllen = 0;
+        msg.msg_flags = msgflags;
+        if let Some(rights) = rights {
+            let should_cloexec = (flags & MSG_CMSG_CLOEXEC) != 0;
+            let (controllen, ctrunc) =
+                _loopback_rights_to_guest(cageid, rights, control, controllen, should_cloexec);
+            msg.msg_controllen = controllen as u32;
+            msg.msg_flags |= ctrunc;
+        }
+        unsafe { std::ptr::write_unaligned(msgptr, msg) };
+        return len as i32;
+    }
+
+    let mut hostaddr: sockaddr_storage = unsafe { std::mem::zeroed() };
+    // host control messages have a larger header and alignment than the guest ones, twice the guest
+    // buffer is always enough to hold whatever fits in the guest buffer
+    let mut hostcontrol = vec![0u64; (msg.msg_controllen as usize * 2).div_ceil(8)];
+
+    let mut hosthdr: msghdr = unsafe { std::mem::zeroed() };
+    if !name.is_null() {
+        hosthdr.msg_name = &mut hostaddr as *mut sockaddr_storage as *mut c_void;
+        hosthdr.msg_namelen = std::mem::size_of::<sockaddr_storage>() as u32;
+    }
+    hosthdr.msg_iov = iovs.as_mut_ptr();
+    hosthdr.msg_iovlen = iovs.len();
+    if !hostcontrol.is_empty() {
+        hosthdr.msg_control = hostcontrol.as_mut_ptr() as *mut c_void;
+        hosthdr.msg_controllen = hostcontrol.len() * std::mem::size_of::<u64>();
+    }
+
+    let ret = unsafe { libc::recvmsg(fd, &mut hosthdr, flags) as i32 };
+    if ret < 0 {
+        let errno = get_errno();
+        return handle_errno(errno, "recvmsg");
+    }
+
+    if !name.is_null() {
+        copy_out_sockaddr(name, &mut msg.msg_namelen, &hostaddr, hosthdr.msg_namelen);
+    }
+
+    msg.msg_flags = hosthdr.msg_flags;
+    if !control.is_null() {
+        let should_cloexec = (flags & MSG_CMSG_CLOEXEC) != 0;
+        let (controllen, ctrunc) =
+            _cmsgs_to_guest(cageid, &hosthdr, control, msg.msg_controllen as usize, should_cloexec);
+        msg.msg_controllen = controllen as u32;
+        msg.msg_flags |= ctrunc;
+    }
+
+    unsafe { std::ptr::write_unaligned(msgptr, msg) };
+    ret
+}
+
+/// A virtual fd waited on by select, poll or epoll_wait, together with the poll events it is
+/// waited for and the events found on it
+struct PollEntry {
+    entry: fdtables::FDTableEntry,
+    events: i16,
+    revents: i16,
+}
+
+/// How long to sleep between two rounds of polling when the wait set has fds that the kernel
+/// can't wait on
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Converts a wait duration into a poll timeout in milliseconds, None meaning wait forever. The
+/// duration is rounded up so we never return before the guest asked us to
+fn _duration_to_poll_timeout(timeout: Option<Duration>) -> i32 {
+    match timeout {
+        None => -1,
+        Some(duration) => {
+            let ms = duration.as_micros().div_ceil(1000);
+            ms.min(i32::MAX as u128) as i32
+        }
+    }
+}
+
+/// Polls the kernel fds of the wait set once, filling in their `revents`
+fn _poll_kernel_fds(entries: &mut [PollEntry], timeout_ms: i32) -> Result<(), i32> {
+    let kernel_idx: Vec<usize> = (0..entries.len())
+        .filter(|&i| entries[i].entry.fdkind == FDKIND_KERNEL)
+        .collect();
+    let mut pollfds: Vec<pollfd> = kernel_idx
+        .iter()
+        .map(|&i| pollfd {
+            fd: entries[i].entry.underfd as i32,
+            events: entries[i].events,
+            revents: 0,
+        })
+        .collect();
+
+    let ret = unsafe { libc::poll(pollfds.as_mut_ptr(), pollfds.len() as nfds_t, timeout_ms) };
+    if ret < 0 {
+        let errno = get_errno();
+        return Err(handle_errno(errno, "poll"));
+    }
+    for (pfd, &i) in pollfds.iter().zip(kernel_idx.iter()) {
+        entries[i].revents = pfd.revents;
+    }
+    Ok(())
+}
+
+/// Waits until at least one entry of the wait set is ready or the timeout expires, and fills in
+/// `revents` for every entry. This is the common engine of select, poll and epoll_wait.
+///
+/// Kernel fds are handed to the host `poll`. Fds that are not backed by a kernel fd can't be
+/// waited on by the kernel, so when the wait set mixes them in, we poll the kernel fds without
+/// blocking and check the other kinds ourselves until something is ready. In-memory sockets report
+/// and pipes report their readiness through `loopback::poll_events` and `pipe::poll_events`, closed fds (`FDT_INVALID_FD`) are reported as
+/// `POLLNVAL` and other fd kinds are never ready for now.
+///
+/// Return:
+///     - On success: the number of entries with a non zero `revents`
+///     - On failure: a negative errno value from the host poll
+fn _poll_virtual_fds(entries: &mut [PollEntry], timeout: Option<Duration>) -> i32 {
+    let all_kernel = entries
+        .iter()
+        .all(|pe| pe.entry.fdkind == FDKIND_KERNEL);
+
+    if all_kernel {
+        if let Err(e) = _poll_kernel_fds(entries, _duration_to_poll_timeout(timeout)) {
+            return e;
+        }
+    } else {
+        let deadline = timeout.map(|t| Instant::now() + t);
+        loop {
+            if let Err(e) = _poll_kernel_fds(entries, 0) {
+                return e;
+            }
+            for pe in entries.iter_mut() {
+                if pe.entry.fdkind == FDKIND_LOOPBACK {
+                    pe.revents = loopback::poll_events(pe.entry.underfd, pe.events);
+                } else if pe.entry.fdkind == FDKIND_PIPE {
+                    pe.revents = pipe::poll_events(pe.entry.underfd, pe.events);
+                } else if pe.entry.fdkind == fdtables::FDT_INVALID_FD {
+                    pe.revents = POLLNVAL;
+                }
+            }
+            if entries.iter().any(|pe| pe.revents != 0) {
+                break;
+            }
+            match deadline {
+                Some(deadline) if Instant::now() >= deadline => break,
+                _ => std::thread::sleep(POLL_INTERVAL),
+            }
+        }
+    }
+
+    entries.iter().filter(|pe| pe.revents != 0).count() as i32
+}
+
+/// Translates a guest `fd_set`, which is read and written back, NULL staying NULL
+fn _fdset_to_host(fdset_arg: u64, fdset_cageid: u64, cageid: u64) -> Result<*mut fd_set, i32> {
+    let size = std::mem::size_of::<fd_set>();
+    _nullable_addr(
+        fdset_arg,
+        fdset_cageid,
+        cageid,
+        size,
+        PROT_READ | PROT_WRITE,
+        "select",
+    )
+    .map(|addr| addr as *mut fd_set)
+}
+
+/// Reads a guest `fd_set`, returns None for a NULL pointer
+fn _get_fdset(fdset: *const fd_set) -> Option<fd_set> {
+    if fdset.is_null() {
+        return None;
+    }
+    Some(unsafe { std::ptr::read_unaligned(fdset) })
+}
+
+/// Writes `fdset` back to the guest, a NULL pointer is ignored
+fn _set_fdset(ptr: *mut fd_set, fdset: fd_set) {
+    if ptr.is_null() {
+        return;
+    }
+    unsafe { std::ptr::write_unaligned(ptr, fdset) };
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/select.2.html
+///
+/// The Linux `select()` syscall waits until one or more of the file descriptors in the given sets
+/// become ready for the corresponding class of I/O. The guest sets hold virtual fds, which are
+/// translated through `fdtables::prepare_bitmasks_for_select`. The resulting fds can be of any fd
+/// kind, so instead of the host `select` (whose fd_set can't hold kernel fds above 1024) they are
+/// waited on with the poll engine shared with `poll` and `epoll_wait`. Ready fds are translated back
+/// into virtual fd sets with `fdtables::get_one_virtual_bitmask_from_select_result`.
+///
+/// Input:
+///     - cageid: current cageid
+///     - nfds_arg: highest-numbered virtual fd in any of the sets, plus 1
+///     - readfds_arg: pointer to the guest fd_set checked for reading, may be NULL
+///     - writefds_arg: pointer to the guest fd_set checked for writing, may be NULL
+///     - exceptfds_arg: pointer to the guest fd_set checked for exceptional conditions, may be NULL
+///     - timeout_arg: pointer to the guest `TimeVal`, NULL to block indefinitely. It is updated
+///       with the time not slept, as Linux does
+///
+/// Return:
+///     - On success: the total number of fds set in the three returned sets, 0 on timeout
+///     - On failure: a negative errno value indicating the syscall error
+pub fn select_syscall(
+    cageid: u64,
+    nfds_arg: u64,
+    nfds_cageid: u64,
+    readfds_arg: u64,
+    readfds_cageid: u64,
+    writefds_arg: u64,
+    writefds_cageid: u64,
+    exceptfds_arg: u64,
+    exceptfds_cageid: u64,
+    timeout_arg: u64,
+    timeout_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let nfds = sc_convert_sysarg_to_i32(nfds_arg, nfds_cageid, cageid);
+
+    if !(sc_unusedarg(arg6, arg6_cageid)) {
+        return syscall_error(Errno::EFAULT, "select_syscall", "Invalide Cage ID");
+    }
+
+    if !(0..=FD_SET_MAX_FD).contains(&nfds) {
+        return syscall_error(Errno::EINVAL, "select_syscall", "nfds is negative or too large");
+    }
+
+    // The timeout and the sets are all written back when select returns
+    let tvsize = std::mem::size_of::<TimeVal>();
+    let tv = match _nullable_addr(
+        timeout_arg,
+        timeout_cageid,
+        cageid,
+        tvsize,
+        PROT_READ | PROT_WRITE,
+        "select",
+    ) {
+        Ok(tv) => tv as *mut TimeVal,
+        Err(e) => return e,
+    };
+    let mut fdsets = [std::ptr::null_mut(); 3];
+    let fdsetargs = [
+        (readfds_arg, readfds_cageid),
+        (writefds_arg, writefds_cageid),
+        (exceptfds_arg, exceptfds_cageid),
+    ];
+    for (fdset, (fdset_arg, fdset_cageid)) in fdsets.iter_mut().zip(fdsetargs) {
+        *fdset = match _fdset_to_host(fdset_arg, fdset_cageid, cageid) {
+            Ok(ptr) => ptr,
+            Err(e) => return e,
+        };
+    }
+
+    let timeout = if tv.is_null() {
+        None
+    } else {
+        match _timeval_to_duration(unsafe { &*tv }) {
+            Some(duration) => Some(duration),
+            None => return syscall_error(Errno::EINVAL, "select_syscall", "invalid timeout"),
+        }
+    };
+
+    let inputsets = fdsets.map(|fdset| _get_fdset(fdset));
+
+    // No fd kind is handed down as a bitmask, every fd ends up in the unparsed sets
+    let (_, unparsedsets, mappingtable) = match fdtables::prepare_bitmasks_for_select(
+        cageid,
+        nfds as u64,
+        inputsets[0],
+        inputsets[1],
+        inputsets[2],
+        &HashSet::new(),
+    ) {
+        Ok(res) => res,
+        Err(e) if e == Errno::EBADF as u64 => {
+            return syscall_error(Errno::EBADF, "select_syscall", "invalid file descriptor in set");
+        }
+        Err(_) => return syscall_error(Errno::EINVAL, "select_syscall", "nfds is too large"),
+    };
+
+    // Poll events to wait for and revents reporting readiness, for the read, write and except sets
+    let setevents = [POLLIN, POLLOUT, POLLPRI];
+    let readymasks = [POLLIN | POLLHUP | POLLERR, POLLOUT | POLLERR, POLLPRI];
+
+    let mut entries = Vec::new();
+    let mut entryset = Vec::new();
+    for (setidx, unparsed) in unparsedsets.iter().enumerate() {
+        for entry in unparsed.values().flatten() {
+            entries.push(PollEntry {
+                entry: *entry,
+                events: setevents[setidx],
+                revents: 0,
+            });
+            entryset.push(setidx);
+        }
+    }
+
+    let start = Instant::now();
+    let ret = _poll_virtual_fds(&mut entries, timeout);
+    if ret < 0 {
+        return ret;
+    }
+
+    // Gather the ready virtual fds of every set, per fd kind
+    let mut readyfds: [HashMap<u32, HashSet<u64>>; 3] = Default::default();
+    for (pe, &setidx) in entries.iter().zip(entryset.iter()) {
+        if pe.revents & (readymasks[setidx] | POLLNVAL) == 0 {
+            continue;
+        }
+        if let Some(virtfd) = fdtables::convert_poll_result_back_to_virtual(
+            pe.entry.fdkind,
+            pe.entry.underfd,
+            &mappingtable,
+        ) {
+            readyfds[setidx]
+                .entry(pe.entry.fdkind)
+                .or_default()
+                .insert(virtfd);
+        }
+    }
+
+    let mut total = 0;
+    for (setidx, &fdset) in fdsets.iter().enumerate() {
+        if inputsets[setidx].is_none() {
+            continue;
+        }
+        let mut resultset = fdtables::_init_fd_set();
+        for (fdkind, virtfds) in readyfds[setidx].drain() {
+            let (count, bits) = fdtables::get_one_virtual_bitmask_from_select_result(
+                fdkind,
+                nfds as u64,
+                None,
+                virtfds,
+                Some(resultset),
+                &mappingtable,
+            );
+            total += count as i32;
+            if let Some(bits) = bits {
+                resultset = bits;
+            }
+        }
+        _set_fdset(fdset, resultset);
+    }
+
+    if let Some(duration) = timeout {
+        unsafe { *tv = _duration_to_timeval(duration.saturating_sub(start.elapsed())) };
+    }
+
+    total
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/poll.2.html
+///
+/// The Linux `poll()` syscall waits for one of a set of file descriptors to become ready to perform
+/// I/O. The guest `pollfd` array holds virtual fds, which are translated with
+/// `fdtables::convert_virtualfds_for_poll` and waited on with the poll engine shared with `select`
+/// and `epoll_wait`. The `revents` of every guest entry are then filled in place. As in Linux,
+/// negative fds are ignored and fds that are not open are reported with `POLLNVAL`.
+///
+/// Input:
+///     - cageid: current cageid
+///     - fds_arg: pointer to the guest array of `pollfd` structures
+///     - nfds_arg: number of entries in the array
+///     - timeout_arg: timeout in milliseconds, negative to block indefinitely
+///
+/// Return:
+///     - On success: the number of entries with a non zero `revents`, 0 on timeout
+///     - On failure: a negative errno value indicating the syscall error
+pub fn poll_syscall(
+    cageid: u64,
+    fds_arg: u64,
+    fds_cageid: u64,
+    nfds_arg: u64,
+    nfds_cageid: u64,
+    timeout_arg: u64,
+    timeout_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let nfds = sc_convert_sysarg_to_usize(nfds_arg, nfds_cageid, cageid);
+    let timeout_ms = sc_convert_sysarg_to_i32(timeout_arg, timeout_cageid, cageid);
+
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "poll_syscall", "Invalide Cage ID");
+    }
+
+    if nfds > fdtables::FD_PER_PROCESS_MAX as usize {
+        return syscall_error(Errno::EINVAL, "poll_syscall", "nfds exceeds the fd limit");
+    }
+
+    let fds: &mut [pollfd] = if nfds == 0 {
+        &mut []
+    } else {
+        let size = nfds * std::mem::size_of::<pollfd>();
+        match sc_convert_addr_to_host_checked(
+            fds_arg,
+            fds_cageid,
+            cageid,
+            size,
+            PROT_READ | PROT_WRITE,
+        ) {
+            Ok(ptr) => unsafe { std::slice::from_raw_parts_mut(ptr as *mut pollfd, nfds) },
+            Err(e) => return syscall_error(e, "poll", "Invalid fds"),
+        }
+    };
+
+    // Fds beyond the table can't be open, let fdtables report them like closed ones
+    let virtualfds: HashSet<u64> = fds
+        .iter()
+        .filter(|pfd| pfd.fd >= 0 && (pfd.fd as u64) < fdtables::FD_PER_PROCESS_MAX)
+        .map(|pfd| pfd.fd as u64)
+        .collect();
+    let (entrymap, _) = fdtables::convert_virtualfds_for_poll(cageid, virtualfds);
+    let virtentries: HashMap<u64, fdtables::FDTableEntry> =
+        entrymap.into_values().flatten().collect();
+
+    let mut entries = Vec::new();
+    let mut entryidx = Vec::new();
+    for (idx, pfd) in fds.iter_mut().enumerate() {
+        pfd.revents = 0;
+        if pfd.fd < 0 {
+            continue;
+        }
+        let entry = match virtentries.get(&(pfd.fd as u64)) {
+            Some(entry) => *entry,
+            None => fdtables::FDTableEntry {
+                fdkind: fdtables::FDT_INVALID_FD,
+                underfd: pfd.fd as u64,
+                should_cloexec: false,
+                perfdinfo: u64::from(fdtables::FDT_INVALID_FD),
+            },
+        };
+        entries.push(PollEntry {
+            entry,
+            events: pfd.events,
+            revents: 0,
+        });
+        entryidx.push(idx);
+    }
+
+    let timeout = if timeout_ms < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout_ms as u64))
+    };
+    let ret = _poll_virtual_fds(&mut entries, timeout);
+    if ret < 0 {
+        return ret;
+    }
+
+    for (pe, &idx) in entries.iter().zip(entryidx.iter()) {
+        fds[idx].revents = pe.revents;
+    }
+    ret
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/epoll_create.2.html
+///
+/// The Linux `epoll_create()` syscall creates a new epoll instance. The epoll instance is entirely
+/// virtualized by `fdtables::epoll_create_empty`, which hands out a virtual fd of kind
+/// `FDT_KINDEPOLL`. No kernel epoll fd is created, since the interest list can hold fds of any kind.
+///
+/// Input:
+///     - cageid: current cageid
+///     - size_arg: ignored by Linux, but must be greater than zero
+///
+/// Return:
+///     - On success: the virtual file descriptor of the new epoll instance
+///     - On failure: a negative errno value indicating the syscall error
+pub fn epoll_create_syscall(
+    cageid: u64,
+    size_arg: u64,
+    size_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let size = sc_convert_sysarg_to_i32(size_arg, size_cageid, cageid);
+
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "epoll_create_syscall", "Invalide Cage ID");
+    }
+
+    if size <= 0 {
+        return syscall_error(Errno::EINVAL, "epoll_create_syscall", "size is not positive");
+    }
+
+    match fdtables::epoll_create_empty(cageid, false) {
+        Ok(epfd) => epfd as i32,
+        Err(e) => syscall_error(_fd_alloc_errno(e), "epoll_create_syscall", "too many open files"),
+    }
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/epoll_ctl.2.html
+///
+/// The Linux `epoll_ctl()` syscall adds, modifies or removes entries in the interest list of an
+/// epoll instance. The guest `epoll_event` is copied into the interest list kept by
+/// `fdtables::virtualize_epoll_ctl`, including its user data, which is handed back as is by
+/// `epoll_wait`.
+///
+/// Input:
+///     - cageid: current cageid
+///     - epfd_arg: virtual fd of the epoll instance
+///     - op_arg: EPOLL_CTL_ADD, EPOLL_CTL_MOD or EPOLL_CTL_DEL
+///     - fd_arg: virtual fd the operation applies to
+///     - event_arg: pointer to the guest `EpollEvent`, may be NULL for EPOLL_CTL_DEL
+///
+/// Return:
+///     - On success: 0
+///     - On failure: a negative errno value indicating the syscall error
+pub fn epoll_ctl_syscall(
+    cageid: u64,
+    epfd_arg: u64,
+    epfd_cageid: u64,
+    op_arg: u64,
+    op_cageid: u64,
+    fd_arg: u64,
+    fd_cageid: u64,
+    event_arg: u64,
+    event_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let epfd = sc_convert_sysarg_to_i32(epfd_arg, epfd_cageid, cageid);
+    let op = sc_convert_sysarg_to_i32(op_arg, op_cageid, cageid);
+    let fd = sc_convert_sysarg_to_i32(fd_arg, fd_cageid, cageid);
+
+    if !(sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "epoll_ctl_syscall", "Invalide Cage ID");
+    }
+
+    if epfd < 0 || epfd as u64 >= fdtables::FD_PER_PROCESS_MAX
+        || fd < 0 || fd as u64 >= fdtables::FD_PER_PROCESS_MAX
+    {
+        return syscall_error(Errno::EBADF, "epoll_ctl_syscall", "invalid file descriptor");
+    }
+
+    let event = if event_arg == 0 {
+        if op != EPOLL_CTL_DEL {
+            return syscall_error(Errno::EFAULT, "epoll_ctl_syscall", "event is NULL");
+        }
+        EpollEvent { events: 0, data: 0 }
+    } else {
+        let size = std::mem::size_of::<EpollEvent>();
+        match sc_convert_addr_to_host_checked(event_arg, event_cageid, cageid, size, PROT_READ) {
+            Ok(ptr) => unsafe { std::ptr::read_unaligned(ptr as *const EpollEvent) },
+            Err(e) => return syscall_error(e, "epoll_ctl", "Invalid event"),
+        }
+    };
+
+    let epevent = fdtables::epoll_event {
+        events: event.events,
+        u64: event.data,
+    };
+    match fdtables::virtualize_epoll_ctl(cageid, epfd as u64, op, fd as u64, epevent) {
+        Ok(()) => 0,
+        Err(e) => {
+            if e == Errno::EBADF as u64 {
+                syscall_error(Errno::EBADF, "epoll_ctl_syscall", "invalid file descriptor")
+            } else if e == Errno::EEXIST as u64 {
+                syscall_error(Errno::EEXIST, "epoll_ctl_syscall", "fd is already registered")
+            } else if e == Errno::ENOENT as u64 {
+                syscall_error(Errno::ENOENT, "epoll_ctl_syscall", "fd is not registered")
+            } else if e == Errno::ENOSYS as u64 {
+                syscall_error(Errno::ENOSYS, "epoll_ctl_syscall", "nested epoll fds are not supported")
+            } else {
+                syscall_error(Errno::EINVAL, "epoll_ctl_syscall", "invalid epoll fd, fd or op")
+            }
+        }
+    }
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/epoll_wait.2.html
+///
+/// The Linux `epoll_wait()` syscall waits for events on the interest list of an epoll instance.
+/// The interest list is read from `fdtables::get_virtual_epoll_wait_data`, and its fds are waited
+/// on with the poll engine shared with `select` and `poll`. Up to `maxevents` ready fds are reported
+/// to the guest with the user data given to `epoll_ctl`.
+///
+/// Edge-triggered entries (EPOLLET) are reported like level-triggered ones, which can only wake the
+/// guest more often. EPOLLONESHOT entries are disabled once reported, until re-armed with
+/// EPOLL_CTL_MOD.
+///
+/// Input:
+///     - cageid: current cageid
+///     - epfd_arg: virtual fd of the epoll instance
+///     - events_arg: pointer to the guest array of `EpollEvent` receiving the ready events
+///     - maxevents_arg: number of entries in the array, must be greater than zero
+///     - timeout_arg: timeout in milliseconds, negative to block indefinitely
+///
+/// Return:
+///     - On success: the number of events written to the array, 0 on timeout
+///     - On failure: a negative errno value indicating the syscall error
+pub fn epoll_wait_syscall(
+    cageid: u64,
+    epfd_arg: u64,
+    epfd_cageid: u64,
+    events_arg: u64,
+    events_cageid: u64,
+    maxevents_arg: u64,
+    maxevents_cageid: u64,
+    timeout_arg: u64,
+    timeout_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let epfd = sc_convert_sysarg_to_i32(epfd_arg, epfd_cageid, cageid);
+    let maxevents = sc_convert_sysarg_to_i32(maxevents_arg, maxevents_cageid, cageid);
+    let timeout_ms = sc_convert_sysarg_to_i32(timeout_arg, timeout_cageid, cageid);
+
+    if !(sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "epoll_wait_syscall", "Invalide Cage ID");
+    }
+
+    if maxevents <= 0 {
+        return syscall_error(Errno::EINVAL, "epoll_wait_syscall", "maxevents is not positive");
+    }
+    if epfd < 0 || epfd as u64 >= fdtables::FD_PER_PROCESS_MAX {
+        return syscall_error(Errno::EBADF, "epoll_wait_syscall", "invalid file descriptor");
+    }
+
+    // The events are checked before waiting, so that no event is consumed for nothing
+    let size = maxevents as usize * std::mem::size_of::<EpollEvent>();
+    let events = match sc_convert_addr_to_host_checked(
+        events_arg,
+        events_cageid,
+        cageid,
+        size,
+        PROT_WRITE,
+    ) {
+        Ok(ptr) => ptr as *mut EpollEvent,
+        Err(e) => return syscall_error(e, "epoll_wait", "Invalid events"),
+    };
+
+    let interestlist = match fdtables::get_virtual_epoll_wait_data(cageid, epfd as u64) {
+        Ok(interestlist) => interestlist,
+        Err(e) if e == Errno::EBADF as u64 => {
+            return syscall_error(Errno::EBADF, "epoll_wait_syscall", "invalid file descriptor");
+        }
+        Err(_) => return syscall_error(Errno::EINVAL, "epoll_wait_syscall", "not an epoll fd"),
+    };
+
+    let mut entries = Vec::new();
+    let mut registered = Vec::new();
+    for (virtfd, event) in interestlist.into_values().flatten() {
+        // disabled by EPOLLONESHOT until the guest re-arms it
+        if event.events & !(EPOLLONESHOT as u32) == 0 {
+            continue;
+        }
+        // the fd was closed without being removed from the interest list
+        let entry = match fdtables::translate_virtual_fd(cageid, virtfd) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        entries.push(PollEntry {
+            entry,
+            events: (event.events & 0xffff) as i16,
+            revents: 0,
+        });
+        registered.push((virtfd, event));
+    }
+
+    let timeout = if timeout_ms < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout_ms as u64))
+    };
+    let ret = _poll_virtual_fds(&mut entries, timeout);
+    if ret < 0 {
+        return ret;
+    }
+
+    let mut count = 0;
+    for (pe, (virtfd, event)) in entries.iter().zip(registered.iter()) {
+        if count == maxevents {
+            break;
+        }
+        if pe.revents == 0 {
+            continue;
+        }
+        let readyevent = EpollEvent {
+            events: pe.revents as u16 as u32,
+            data: event.u64,
+        };
+        unsafe { std::ptr::write_unaligned(events.add(count as usize), readyevent) };
+        count += 1;
+
+        if event.events & EPOLLONESHOT as u32 != 0 {
+            let disabled = fdtables::epoll_event {
+                events: EPOLLONESHOT as u32,
+                u64: event.u64,
+            };
+            let _ = fdtables::virtualize_epoll_ctl(cageid, epfd as u64, EPOLL_CTL_MOD, *virtfd, disabled);
+        }
+    }
+    count
+}
@@ -0,0 +1,452 @@
+//! In-memory pipes
+//!
+//! When in-memory pipes are enabled, `pipe()` and `pipe2()` don't create a host kernel pipe. The
+//! data goes through a ring buffer shared by every cage of the lind runtime, so a pipeline of cages
+//! neither holds host fds nor makes a kernel round trip per read and write.
+//!
+//! Both ends are registered in `fdtables` with `FDKIND_PIPE`. `underfd` is the id of the pipe in
+//! the table below, shifted left by one, with the lowest bit telling the write end from the read
+//! end. fdtables counts the references to each end separately, so its last-close handler tells us
+//! when the last reader or the last writer is gone.
+//!
+//! The ring buffer has a single producer and a single consumer: the reader only ever moves `head`
+//! and the writer only ever moves `tail`, so data flows without them taking a lock. Each end has
+//! a mutex which is only contended when several fds (e.g. after a `fork`) use the same end at
+//! once. Blocked calls sleep on a condition variable that is only signalled when someone waits.
+use crate::syscalls::fs_calls::_fd_alloc_errno;
+use crate::syscalls::locks;
+use cage::signal::{lind_send_signal, lind_signal_interrupts};
+use dashmap::DashMap;
+use fdtables;
+use once_cell::sync::Lazy;
+use parking_lot::{Condvar, Mutex};
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicU8, AtomicUsize, Ordering};
+use std::sync::Arc;
+use sysdefs::constants::err_const::{syscall_error, Errno};
+use sysdefs::constants::fs_const::{
+    FDKIND_PIPE, O_CLOEXEC, O_NONBLOCK, O_RDONLY, O_WRONLY, PIPE_BUF, PIPE_CAPACITY,
+};
+use sysdefs::constants::net_const::{POLLERR, POLLHUP, POLLIN, POLLNVAL, POLLOUT};
+use sysdefs::constants::sys_const::SIGPIPE;
+
+static INMEMORY_PIPES: AtomicBool = AtomicBool::new(false);
+
+/// Sets whether `pipe()` and `pipe2()` create in-memory pipes instead of host kernel pipes. Only
+/// pipes created afterwards are affected.
+pub fn set_inmemory_pipes(enabled: bool) {
+    INMEMORY_PIPES.store(enabled, Ordering::SeqCst);
+}
+
+pub fn inmemory_pipes() -> bool {
+    INMEMORY_PIPES.load(Ordering::SeqCst)
+}
+
+/// Lowest bit of `underfd`, set for the write end of a pipe
+const WRITE_END: u64 = 1;
+
+/// Bits of `RingPipe::closed`
+const READER_CLOSED: u8 = 1;
+const WRITER_CLOSED: u8 = 2;
+
+struct RingPipe {
+    buf: Box<[UnsafeCell<u8>]>,
+    /// Total number of bytes read so far, only moved by the reader
+    head: AtomicUsize,
+    /// Total number of bytes written so far, only moved by the writer
+    tail: AtomicUsize,
+    /// Serializes the fds of the read end, and those of the write end
+    reader: Mutex<()>,
+    writer: Mutex<()>,
+    /// `READER_CLOSED` and `WRITER_CLOSED` once the last fd of an end is closed
+    closed: AtomicU8,
+    /// `O_NONBLOCK` of each end, which every fd of the end shares like Linux' open file description
+    read_nonblock: AtomicBool,
+    write_nonblock: AtomicBool,
+    /// Number of calls sleeping on `wait_cv`
+    waiters: AtomicUsize,
+    wait_lock: Mutex<()>,
+    wait_cv: Condvar,
+}
+
+// The bytes between `head` and `tail` are only touched by the reader and the others by the writer,
+// see `read` and `write`
+unsafe impl Sync for RingPipe {}
+
+impl RingPipe {
+    fn new(nonblocking: bool) -> Self {
+        RingPipe {
+            buf: (0..PIPE_CAPACITY).map(|_| UnsafeCell::new(0)).collect(),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+            reader: Mutex::new(()),
+            writer: Mutex::new(()),
+            closed: AtomicU8::new(0),
+            read_nonblock: AtomicBool::new(nonblocking),
+            write_nonblock: AtomicBool::new(nonblocking),
+            waiters: AtomicUsize::new(0),
+            wait_lock: Mutex::new(()),
+            wait_cv: Condvar::new(),
+        }
+    }
+
+    fn is_closed(&self, end: u8) -> bool {
+        (self.closed.load(Ordering::SeqCst) & end) != 0
+    }
+
+    /// Bytes waiting to be read
+    fn len(&self) -> usize {
+        self.tail.load(Ordering::SeqCst) - self.head.load(Ordering::SeqCst)
+    }
+
+    /// Copies `len` bytes starting at ring position `pos` to `dst`
+    unsafe fn copy_out(&self, pos: usize, dst: *mut u8, len: usize) {
+        let start = pos % PIPE_CAPACITY;
+        let first = len.min(PIPE_CAPACITY - start);
+        let base = self.buf.as_ptr() as *mut u8;
+        std::ptr::copy_nonoverlapping(base.add(start), dst, first);
+        std::ptr::copy_nonoverlapping(base, dst.add(first), len - first);
+    }
+
+    /// Copies `len` bytes from `src` to the ring, starting at position `pos`
+    unsafe fn copy_in(&self, pos: usize, src: *const u8, len: usize) {
+        let start = pos % PIPE_CAPACITY;
+        let first = len.min(PIPE_CAPACITY - start);
+        let base = self.buf.as_ptr() as *mut u8;
+        std::ptr::copy_nonoverlapping(src, base.add(start), first);
+        std::ptr::copy_nonoverlapping(src.add(first), base, len - first);
+    }
+
+    /// Sleeps until `ready` holds, or until a signal interrupts the cage `cageid`. `ready` is
+    /// checked under `wait_lock` after registering as a waiter, and `wake` takes `wait_lock`
+    /// whenever it sees a waiter, so no wakeup is lost.
+    ///
+    /// Return:
+    ///     - false if the wait was interrupted by a signal
+    fn wait(&self, cageid: u64, ready: impl Fn(&RingPipe) -> bool) -> bool {
+        let mut guard = self.wait_lock.lock();
+        self.waiters.fetch_add(1, Ordering::SeqCst);
+        let mut interrupted = false;
+        while !ready(self) {
+            if lind_signal_interrupts(cageid) {
+                interrupted = true;
+                break;
+            }
+            self.wait_cv.wait(&mut guard);
+        }
+        self.waiters.fetch_sub(1, Ordering::SeqCst);
+        !interrupted
+    }
+
+    /// Wakes up the calls sleeping in `wait`, if any
+    fn wake(&self) {
+        if self.waiters.load(Ordering::SeqCst) > 0 {
+            let _guard = self.wait_lock.lock();
+            self.wait_cv.notify_all();
+        }
+    }
+}
+
+/// Every in-memory pipe with at least one open end, by id
+static PIPES: Lazy<DashMap<u64, Arc<RingPipe>>> = Lazy::new(DashMap::new);
+static NEXT_PIPEID: AtomicU64 = AtomicU64::new(0);
+
+/// Signal waker of the in-memory pipes, wakes up every blocked read and write so that they can
+/// check for an interrupting signal
+pub(crate) fn wake_all() {
+    for pipe in PIPES.iter() {
+        pipe.wake();
+    }
+}
+
+/// Returns the pipe behind `underfd`, and whether `underfd` is its write end
+fn _lookup(underfd: u64) -> Option<(Arc<RingPipe>, bool)> {
+    PIPES
+        .get(&(underfd >> 1))
+        .map(|pipe| (pipe.clone(), (underfd & WRITE_END) != 0))
+}
+
+/// Returns the `underfd` of the pipe end behind `virtual_fd`, None if the fd is of another kind
+pub(crate) fn pipe_end(virtual_fd: u64, vfd_cageid: u64) -> Option<u64> {
+    match fdtables::translate_virtual_fd(vfd_cageid, virtual_fd) {
+        Ok(entry) if entry.fdkind == FDKIND_PIPE => Some(entry.underfd),
+        _ => None,
+    }
+}
+
+/// Creates an in-memory pipe and allocates a virtual fd of `cageid` for each end. Only
+/// `O_NONBLOCK` and `O_CLOEXEC` are expected in `flags`.
+///
+/// Return:
+///     - On success: the virtual fds of the read end and the write end
+///     - On failure: a negative errno value
+pub(crate) fn pipe2(cageid: u64, flags: i32) -> Result<[i32; 2], i32> {
+    let id = NEXT_PIPEID.fetch_add(1, Ordering::SeqCst);
+    PIPES.insert(id, Arc::new(RingPipe::new((flags & O_NONBLOCK) != 0)));
+    let should_cloexec = (flags & O_CLOEXEC) != 0;
+
+    let read_vfd =
+        match fdtables::get_unused_virtual_fd(cageid, FDKIND_PIPE, id << 1, should_cloexec, 0) {
+            Ok(fd) => fd,
+            Err(e) => {
+                PIPES.remove(&id);
+                return Err(syscall_error(
+                    _fd_alloc_errno(e),
+                    "pipe2",
+                    "Too many files opened",
+                ));
+            }
+        };
+    let write_vfd = match fdtables::get_unused_virtual_fd(
+        cageid,
+        FDKIND_PIPE,
+        (id << 1) | WRITE_END,
+        should_cloexec,
+        0,
+    ) {
+        Ok(fd) => fd,
+        Err(e) => {
+            // closing the read end marks it closed, dropping the write end frees the pipe
+            let _ = fdtables::close_virtualfd(cageid, read_vfd);
+            _close_end(id, WRITER_CLOSED);
+            return Err(syscall_error(
+                _fd_alloc_errno(e),
+                "pipe2",
+                "Too many files opened",
+            ));
+        }
+    };
+    Ok([read_vfd as i32, write_vfd as i32])
+}
+
+/// Marks one end of pipe `id` closed, and drops the pipe once both are
+fn _close_end(id: u64, end: u8) {
+    let pipe = match PIPES.get(&id) {
+        Some(pipe) => pipe.clone(),
+        None => return,
+    };
+    let closed = pipe.closed.fetch_or(end, Ordering::SeqCst) | end;
+    // readers then see end of file, writers get EPIPE
+    pipe.wake();
+    if closed == READER_CLOSED | WRITER_CLOSED {
+        PIPES.remove(&id);
+    }
+}
+
+/// Close handler of `FDKIND_PIPE`, called by fdtables once the last fd of a pipe end is closed
+pub fn pipe_close(fdentry: fdtables::FDTableEntry, _count: u64) {
+    let end = if (fdentry.underfd & WRITE_END) != 0 {
+        WRITER_CLOSED
+    } else {
+        READER_CLOSED
+    };
+    locks::close_description(FDKIND_PIPE, fdentry.underfd);
+    _close_end(fdentry.underfd >> 1, end);
+}
+
+/// Reads up to `buf.len()` bytes from the pipe end `underfd`. Blocks while the pipe is empty and
+/// has a writer, unless the read end is non-blocking.
+///
+/// Return:
+///     - On success: the number of bytes read, 0 at end of file
+///     - On failure: a negative errno value, EINTR if a signal interrupted the cage `cageid`
+pub(crate) fn read(cageid: u64, underfd: u64, buf: &mut [u8]) -> i32 {
+    let (pipe, is_write_end) = match _lookup(underfd) {
+        Some(found) => found,
+        None => return syscall_error(Errno::EBADF, "read", "Bad File Descriptor"),
+    };
+    if is_write_end {
+        return syscall_error(Errno::EBADF, "read", "fd is the write end of a pipe");
+    }
+    if buf.is_empty() {
+        return 0;
+    }
+
+    let _reader = pipe.reader.lock();
+    loop {
+        // the writer closing after its last write, looking at `closed` first makes sure we see
+        // that write
+        let writer_closed = pipe.is_closed(WRITER_CLOSED);
+        let head = pipe.head.load(Ordering::Relaxed);
+        let avail = pipe.tail.load(Ordering::Acquire) - head;
+        if avail > 0 {
+            let len = avail.min(buf.len());
+            unsafe { pipe.copy_out(head, buf.as_mut_ptr(), len) };
+            pipe.head.store(head + len, Ordering::SeqCst);
+            pipe.wake();
+            return len as i32;
+        }
+        if writer_closed {
+            return 0;
+        }
+        if pipe.read_nonblock.load(Ordering::Relaxed) {
+            return syscall_error(Errno::EAGAIN, "read", "Resource temporarily unavailable");
+        }
+        if !pipe.wait(cageid, |pipe| {
+            pipe.len() > 0 || pipe.is_closed(WRITER_CLOSED)
+        }) {
+            return syscall_error(Errno::EINTR, "read", "Interrupted system call");
+        }
+    }
+}
+
+/// Writes `data` to the pipe end `underfd`. Writes of at most `PIPE_BUF` bytes go in one piece,
+/// larger ones are split as the reader makes room. Blocks while the pipe is full, unless the write
+/// end is non-blocking, in which case what fits is written.
+///
+/// Return:
+///     - On success: the number of bytes written
+///     - On failure: a negative errno value, EPIPE once no reader is left (the cage `cageid` is
+///       then sent SIGPIPE, as in Linux), EINTR if a signal interrupted the cage `cageid` before
+///       anything was written
+pub(crate) fn write(cageid: u64, underfd: u64, data: &[u8]) -> i32 {
+    let (pipe, is_write_end) = match _lookup(underfd) {
+        Some(found) => found,
+        None => return syscall_error(Errno::EBADF, "write", "Bad File Descriptor"),
+    };
+    if !is_write_end {
+        return syscall_error(Errno::EBADF, "write", "fd is the read end of a pipe");
+    }
+    if data.is_empty() {
+        return 0;
+    }
+
+    let _writer = pipe.writer.lock();
+    let mut written = 0;
+    loop {
+        if pipe.is_closed(READER_CLOSED) {
+            if written > 0 {
+                return written as i32;
+            }
+            lind_send_signal(cageid, SIGPIPE);
+            return syscall_error(Errno::EPIPE, "write", "Broken pipe");
+        }
+        let tail = pipe.tail.load(Ordering::Relaxed);
+        let space = PIPE_CAPACITY - (tail - pipe.head.load(Ordering::Acquire));
+        let remaining = data.len() - written;
+        let needed = if data.len() <= PIPE_BUF { remaining } else { 1 };
+        if space >= needed {
+            let len = space.min(remaining);
+            unsafe { pipe.copy_in(tail, data[written..].as_ptr(), len) };
+            pipe.tail.store(tail + len, Ordering::SeqCst);
+            pipe.wake();
+            written += len;
+            if written == data.len() {
+                return written as i32;
+            }
+            continue;
+        }
+        if pipe.write_nonblock.load(Ordering::Relaxed) {
+            if written > 0 {
+                return written as i32;
+            }
+            return syscall_error(Errno::EAGAIN, "write", "Resource temporarily unavailable");
+        }
+        if !pipe.wait(cageid, |pipe| {
+            PIPE_CAPACITY - pipe.len() >= needed || pipe.is_closed(READER_CLOSED)
+        }) {
+            if written > 0 {
+                return written as i32;
+            }
+            return syscall_error(Errno::EINTR, "write", "Interrupted system call");
+        }
+    }
+}
+
+/// Reads from the pipe end `underfd` into the host iovecs `iovs`, see `read`
+pub(crate) fn readv(cageid: u64, underfd: u64, iovs: &[libc::iovec]) -> i32 {
+    let mut data = vec![0u8; iovs.iter().map(|iov| iov.iov_len).sum()];
+    let ret = read(cageid, underfd, &mut data);
+    if ret <= 0 {
+        return ret;
+    }
+    let mut remaining = &data[..ret as usize];
+    for iov in iovs {
+        let copylen = iov.iov_len.min(remaining.len());
+        unsafe {
+            std::ptr::copy_nonoverlapping(remaining.as_ptr(), iov.iov_base as *mut u8, copylen)
+        };
+        remaining = &remaining[copylen..];
+    }
+    ret
+}
+
+/// Writes the host iovecs `iovs` to the pipe end `underfd` as a single write, see `write`
+pub(crate) fn writev(cageid: u64, underfd: u64, iovs: &[libc::iovec]) -> i32 {
+    let mut data = Vec::with_capacity(iovs.iter().map(|iov| iov.iov_len).sum());
+    for iov in iovs {
+        data.extend_from_slice(unsafe {
+            std::slice::from_raw_parts(iov.iov_base as *const u8, iov.iov_len)
+        });
+    }
+    write(cageid, underfd, &data)
+}
+
+/// Returns the file status flags of the pipe end `underfd`, as `F_GETFL` does
+pub(crate) fn get_status_flags(underfd: u64) -> i32 {
+    match _lookup(underfd) {
+        Some((pipe, true)) => {
+            O_WRONLY
+                | if pipe.write_nonblock.load(Ordering::Relaxed) {
+                    O_NONBLOCK
+                } else {
+                    0
+                }
+        }
+        Some((pipe, false)) => {
+            O_RDONLY
+                | if pipe.read_nonblock.load(Ordering::Relaxed) {
+                    O_NONBLOCK
+                } else {
+                    0
+                }
+        }
+        None => syscall_error(Errno::EBADF, "fcntl", "Bad File Descriptor"),
+    }
+}
+
+/// Updates `O_NONBLOCK` of the pipe end `underfd`, the other status flags are ignored
+pub(crate) fn set_status_flags(underfd: u64, flags: i32) {
+    if let Some((pipe, is_write_end)) = _lookup(underfd) {
+        let nonblock = if is_write_end {
+            &pipe.write_nonblock
+        } else {
+            &pipe.read_nonblock
+        };
+        nonblock.store((flags & O_NONBLOCK) != 0, Ordering::Relaxed);
+        // blocked calls of that end keep waiting, like on Linux
+    }
+}
+
+/// Returns the number of bytes waiting in the pipe of end `underfd`, as `ioctl(FIONREAD)` does
+pub(crate) fn bytes_available(underfd: u64) -> i32 {
+    match _lookup(underfd) {
+        Some((pipe, _)) => pipe.len() as i32,
+        None => syscall_error(Errno::EBADF, "ioctl", "Bad File Descriptor"),
+    }
+}
+
+/// Returns the poll events of the pipe end `underfd` among `events`, plus the error conditions
+/// that are always reported. As on Linux, the write end is writable once `PIPE_BUF` bytes fit.
+pub(crate) fn poll_events(underfd: u64, events: i16) -> i16 {
+    let (pipe, is_write_end) = match _lookup(underfd) {
+        Some(found) => found,
+        None => return POLLNVAL,
+    };
+    let mut revents = 0;
+    if is_write_end {
+        if pipe.is_closed(READER_CLOSED) {
+            revents |= POLLERR;
+        } else if PIPE_CAPACITY - pipe.len() >= PIPE_BUF {
+            revents |= POLLOUT;
+        }
+    } else {
+        if pipe.len() > 0 {
+            revents |= POLLIN;
+        }
+        if pipe.is_closed(WRITER_CLOSED) {
+            revents |= POLLHUP;
+        }
+    }
+    revents & (events | POLLERR | POLLHUP)
+}
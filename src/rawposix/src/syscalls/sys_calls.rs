@@ -2,20 +2,33 @@
 //!
 //! This module contains all system calls that are being emulated/faked in Lind.
 use crate::syscalls::fs_calls::kernel_close;
+use crate::syscalls::locks;
+use crate::syscalls::loopback::{self, loopback_close};
+use crate::syscalls::pipe::{self, pipe_close};
 use cage::memory::mem_helper::*;
+use cage::memory::memlimits::MemoryLimits;
+use cage::memory::shm::{shm_detach_all, shm_fork_attachments};
 use cage::memory::vmmap::{VmmapOps, *};
-use cage::{add_cage, cagetable_clear, get_cage, remove_cage, Cage, Zombie};
+use cage::signal::*;
+use cage::{
+    add_cage, cagetable_clear, get_cage, remove_cage_to_zombie, reparent_children, Cage, CAGE_MAP,
+    INIT_CAGEID,
+};
 use fdtables;
-use libc::sched_yield;
-use parking_lot::RwLock;
+use once_cell::sync::Lazy;
+use parking_lot::{Condvar, Mutex, RwLock};
+use std::collections::HashMap;
 use std::ffi::CString;
 use std::path::PathBuf;
 use std::sync::atomic::Ordering::*;
-use std::sync::atomic::{AtomicI32, AtomicU64};
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicU64};
 use std::sync::Arc;
+use std::time::Duration;
 use sysdefs::constants::err_const::{get_errno, handle_errno, syscall_error, Errno};
 use sysdefs::constants::fs_const::*;
+use sysdefs::constants::sys_const::*;
 use sysdefs::constants::{EXIT_SUCCESS, VERBOSE};
+use sysdefs::data::fs_struct::{ITimerVal, Rlimit, SigactionStruct, SigsetType, TimeVal};
 use typemap::syscall_conv::*;
 use typemap::syscall_conv::*;
 
@@ -50,27 +63,46 @@ pub fn fork_syscall(
         return syscall_error(Errno::EFAULT, "fork", "Invalide Arguments");
     }
 
-    // Modify the fdtable manually
-    fdtables::copy_fdtable_for_cage(child_arg_cageid, child_arg).unwrap();
+    // Modify the fdtable manually. This fails when the child's fds would go over the global limit
+    if fdtables::copy_fdtable_for_cage(child_arg_cageid, child_arg).is_err() {
+        return syscall_error(Errno::ENFILE, "fork", "Too many open files in the system");
+    }
 
     // Get the self cage
     let selfcage = get_cage(child_arg_cageid).unwrap();
 
     let parent_vmmap = selfcage.vmmap.read();
     let new_vmmap = parent_vmmap.clone();
+    // The child inherits the parent's shared memory attachments
+    shm_fork_attachments(&new_vmmap, child_arg);
+
+    // The child inherits the signal handlers and the signal mask of the calling thread, which becomes
+    // the mask of the child's main thread. Pending signals and interval timers are not inherited
+    let new_handlers = selfcage.signalhandler.read().clone();
+    let mut new_sigset = HashMap::new();
+    new_sigset.insert(0, get_sigmask(&selfcage, current_threadid()));
 
     let cageobj = Cage {
         cageid: child_arg,
         cwd: RwLock::new(selfcage.cwd.read().clone()),
-        parent: child_arg_cageid,
+        parent: AtomicU64::new(child_arg_cageid),
+        pgid: AtomicU64::new(selfcage.pgid.load(Relaxed)),
         gid: AtomicI32::new(selfcage.gid.load(Relaxed)),
         uid: AtomicI32::new(selfcage.uid.load(Relaxed)),
         egid: AtomicI32::new(selfcage.egid.load(Relaxed)),
         euid: AtomicI32::new(selfcage.euid.load(Relaxed)),
+        sgid: AtomicI32::new(selfcage.sgid.load(Relaxed)),
+        suid: AtomicI32::new(selfcage.suid.load(Relaxed)),
         main_threadid: AtomicU64::new(0),
-        zombies: RwLock::new(vec![]),
+        zombies: Mutex::new(vec![]),
+        zombie_cv: Condvar::new(),
         child_num: AtomicU64::new(0),
         vmmap: RwLock::new(new_vmmap),
+        signalhandler: RwLock::new(new_handlers),
+        sigset: RwLock::new(new_sigset),
+        pending_signals: AtomicU64::new(0),
+        interval_timer: IntervalTimer::new(),
+        term_signal: AtomicI32::new(0),
     };
 
     // increment child counter for parent
@@ -112,23 +144,21 @@ pub fn exit_syscall(
     }
 
     let _ = fdtables::remove_cage_from_fdtable(cageid);
+    // Record locks belong to the cage, unlike flock() locks which went with its last fds
+    locks::release_record_locks(cageid, None);
 
     // Get the self cage
     let selfcage = get_cage(cageid).unwrap();
-    let _ = remove_cage(cageid);
-    if selfcage.parent != cageid {
-        let parent_cage = get_cage(selfcage.parent);
-        if let Some(parent) = parent_cage {
-            parent.child_num.fetch_sub(1, SeqCst);
-            let mut zombie_vec = parent.zombies.write();
-            zombie_vec.push(Zombie {
-                cageid: status_cageid,
-                exit_code: status,
-            });
-        } else {
-            // if parent already exited
-            // BUG: we currently do not handle the situation where a parent has exited already
-        }
+    // Shared memory segments still attached are detached, as if by shmdt()
+    shm_detach_all(&selfcage.vmmap.read(), cageid);
+    // Children of the exiting cage are adopted by the init cage, which is then responsible for waiting
+    // for them. This must happen before the exit status is handed to our own parent
+    reparent_children(&selfcage);
+    remove_cage_to_zombie(&selfcage, status);
+    // Let the parent know one of its children changed state
+    let parentid = selfcage.parent.load(SeqCst);
+    if parentid != cageid {
+        lind_send_signal(parentid, SIGCHLD);
     }
 
     status
@@ -144,7 +174,9 @@ pub fn exit_syscall(
 /// (closing or inheriting them based on the `should_cloexec` flag in fdtable), resetting semaphores, and
 /// managing process attributes and threads (terminating unnecessary threads). This allows us to fully implement
 /// the exec functionality while aligning with POSIX standards. Cage fields remained in exec():
-/// cageid, cwd, parent, interval_timer
+/// cageid, cwd, parent, interval_timer, the memory limits, and the real and effective user and group ids.
+/// Like an exec of a binary without the set-user-ID and set-group-ID bits, the saved ids are set to the
+/// effective ids
 pub fn exec_syscall(
     cageid: u64,
     arg1: u64,
@@ -177,29 +209,54 @@ pub fn exec_syscall(
     // Copy necessary data from current cage
     let selfcage = get_cage(cageid).unwrap();
 
-    let zombies = selfcage.zombies.read();
-    let cloned_zombies = zombies.clone();
-    let child_num = selfcage.child_num.load(Relaxed);
-    drop(zombies);
+    // Shared memory segments are detached, the new program starts with empty memory
+    shm_detach_all(&selfcage.vmmap.read(), cageid);
+
+    // The zombie list stays locked until the new cage replaces this one in the cage table, so children
+    // exiting meanwhile insert their zombies into the new cage (see `remove_cage_to_zombie`)
+    let mut zombies = selfcage.zombies.lock();
+    let inherited_zombies = std::mem::take(&mut *zombies);
+    let child_num = selfcage.child_num.load(SeqCst);
+
+    // Memory is cleared after exec, but the limits on it are kept
+    let mut new_vmmap = Vmmap::new();
+    new_vmmap.limits = selfcage.vmmap.read().limits;
+
+    // Caught signals are reset to their default action since the handlers no longer exist in the new
+    // program, while ignored signals stay ignored. The signal mask, pending signals and interval timer
+    // are preserved
+    let mut new_handlers = selfcage.signalhandler.read().clone();
+    new_handlers.retain(|_, action| action.sa_handler == SIG_IGN);
+    let mut new_sigset = HashMap::new();
+    new_sigset.insert(0, get_sigmask(&selfcage, current_threadid()));
 
     let newcage = Cage {
         cageid: cageid,
         cwd: RwLock::new(selfcage.cwd.read().clone()),
-        parent: selfcage.parent,
-        gid: AtomicI32::new(-1),
-        uid: AtomicI32::new(-1),
-        egid: AtomicI32::new(-1),
-        euid: AtomicI32::new(-1),
+        parent: AtomicU64::new(selfcage.parent.load(SeqCst)),
+        pgid: AtomicU64::new(selfcage.pgid.load(Relaxed)),
+        gid: AtomicI32::new(selfcage.gid.load(Relaxed)),
+        uid: AtomicI32::new(selfcage.uid.load(Relaxed)),
+        egid: AtomicI32::new(selfcage.egid.load(Relaxed)),
+        euid: AtomicI32::new(selfcage.euid.load(Relaxed)),
+        // The saved set-user-ID and set-group-ID are copied from the effective ids
+        sgid: AtomicI32::new(selfcage.egid.load(Relaxed)),
+        suid: AtomicI32::new(selfcage.euid.load(Relaxed)),
         main_threadid: AtomicU64::new(0),
-        zombies: RwLock::new(cloned_zombies), // When a process exec-ed, its child relationship should be perserved
+        zombies: Mutex::new(inherited_zombies), // When a process exec-ed, its child relationship should be perserved
+        zombie_cv: Condvar::new(),
         child_num: AtomicU64::new(child_num),
-        vmmap: RwLock::new(Vmmap::new()), // Memory is cleared after exec
+        vmmap: RwLock::new(new_vmmap),
+        signalhandler: RwLock::new(new_handlers),
+        sigset: RwLock::new(new_sigset),
+        pending_signals: AtomicU64::new(selfcage.pending_signals.load(SeqCst)),
+        interval_timer: selfcage.interval_timer.clone_timer(),
+        term_signal: AtomicI32::new(0),
     };
 
-    // Remove the original cage
-    remove_cage(cageid);
-    // Insert the new cage with same cageid
+    // Replace the original cage with the new cage with same cageid
     add_cage(cageid, newcage);
+    drop(zombies);
     0
 }
 
@@ -208,7 +265,25 @@ pub fn exec_syscall(
 /// waitpid() will return the cageid of waited cage, or 0 when WNOHANG is set and there is no cage already exited
 /// waitpid_syscall utilizes the zombie list stored in cage struct. When a cage exited, a zombie entry will be inserted
 /// into the end of its parent's zombie list. Then when parent wants to wait for any of child, it could just check its
-/// zombie list and retrieve the first entry from it (first in, first out).
+/// zombie list and retrieve the first matching entry from it (first in, first out).
+///
+/// When no child has exited yet, the cage sleeps on its `zombie_cv` condition variable, which is notified by
+/// exiting children, instead of polling the zombie list.
+///
+/// Input:
+///     - cageid_arg: which children to wait for
+///         - `< -1`: any child whose process group id is `-cageid_arg`
+///         - `-1`: any child
+///         - `0`: any child in the same process group as the calling cage
+///         - `> 0`: the child with this cage id
+///     - status_arg: where the exit status of the child is stored, can be NULL
+///     - options_arg: WNOHANG returns immediately if no child has exited. WUNTRACED and WCONTINUED are
+///       accepted, but never report anything since stopping cages is not supported
+///
+/// Return:
+///     - the cage id of the waited child, 0 if WNOHANG is set and no matching child exited yet
+///     - -ECHILD if there is no child matching `cageid_arg`, -EINVAL if options are invalid
+///     - -EINTR if a signal interrupted the cage while waiting
 pub fn waitpid_syscall(
     cageid: u64,
     cageid_arg: u64,
@@ -224,7 +299,7 @@ pub fn waitpid_syscall(
     arg6: u64,
     arg6_cageid: u64,
 ) -> i32 {
-    let status = sc_convert_sysarg_to_i32_ref(status_arg, status_cageid, cageid);
+    let pid = sc_convert_sysarg_to_i32(cageid_arg, cageid_arg_cageid, cageid);
     let options = sc_convert_sysarg_to_i32(options_arg, options_cageid, cageid);
     // would sometimes check, sometimes be a no-op depending on the compiler settings
     if !(sc_unusedarg(arg4, arg4_cageid)
@@ -234,116 +309,70 @@ pub fn waitpid_syscall(
         return syscall_error(Errno::EFAULT, "waitpid", "Invalid Arguments");
     }
 
+    if options & !(libc::WNOHANG | libc::WUNTRACED | libc::WCONTINUED) != 0 {
+        return syscall_error(Errno::EINVAL, "waitpid", "Invalid options");
+    }
+
     // get the cage instance
     let cage = get_cage(cageid).unwrap();
+    let pgid = cage.pgid.load(SeqCst);
 
-    let mut zombies = cage.zombies.write();
-    let child_num = cage.child_num.load(Relaxed);
-
-    // if there is no pending zombies to wait, and there is no active child, return ECHILD
-    if zombies.len() == 0 && child_num == 0 {
-        return syscall_error(
-            Errno::ECHILD,
-            "waitpid",
-            "no existing unwaited-for child processes",
-        );
-    }
+    // whether a child with the given cage id and process group id is selected by `pid`
+    let selected = |childid: u64, childpgid: u64| match pid {
+        -1 => true,
+        0 => childpgid == pgid,
+        pid if pid < -1 => childpgid == -(pid as i64) as u64,
+        pid => childid == pid as u64,
+    };
 
-    let mut zombie_opt: Option<Zombie> = None;
-
-    // cageid <= 0 means wait for ANY child
-    // cageid < 0 actually refers to wait for any child process whose process group ID equals -pid
-    // but we do not have the concept of process group in lind, so let's just treat it as cageid == 0
-    if cageid_arg <= 0 {
-        loop {
-            if zombies.len() == 0 && (options & libc::WNOHANG > 0) {
-                // if there is no pending zombies and WNOHANG is set
-                // return immediately
-                return 0;
-            } else if zombies.len() == 0 {
-                // if there is no pending zombies and WNOHANG is not set
-                // then we need to wait for children to exit
-                // drop the zombies list before sleep to avoid deadlock
-                drop(zombies);
-                // TODO: replace busy waiting with more efficient mechanism
-                unsafe {
-                    sched_yield();
-                }
-                // after sleep, get the write access of zombies list back
-                zombies = cage.zombies.write();
-                continue;
-            } else {
-                // there are zombies avaliable
-                // let's retrieve the first zombie
-                zombie_opt = Some(zombies.remove(0));
-                break;
-            }
+    let mut zombies = cage.zombies.lock();
+    let zombie = loop {
+        if let Some(index) = zombies.iter().position(|z| selected(z.cageid, z.pgid)) {
+            break zombies.remove(index);
         }
-    }
-    // if cageid is specified, then we need to look up the zombie list for the id
-    else {
-        // first let's check if the cageid is in the zombie list
-        if let Some(index) = zombies
-            .iter()
-            .position(|zombie| zombie.cageid == cageid_arg as u64)
-        {
-            // find the cage in zombie list, remove it from the list and break
-            zombie_opt = Some(zombies.remove(index));
+
+        // No matching child exited yet, make sure there is a running child we can wait for. Exiting
+        // children leave the cage table and join the zombie list while holding `zombies`, so they are
+        // always found in one of the two places
+        let has_child = if pid == -1 {
+            cage.child_num.load(SeqCst) > 0
         } else {
-            // if the cageid is not in the zombie list, then we know either
-            // 1. the child is still running, or
-            // 2. the cage has exited, but it is not the child of this cage, or
-            // 3. the cage does not exist
-            // we need to make sure the child is still running, and it is the child of this cage
-            let child = get_cage(cageid_arg as u64);
-            if let Some(child_cage) = child {
-                // make sure the child's parent is correct
-                if child_cage.parent != cage.cageid {
-                    return syscall_error(
-                        Errno::ECHILD,
-                        "waitpid",
-                        "waited cage is not the child of the cage",
-                    );
-                }
-            } else {
-                // cage does not exist
-                return syscall_error(Errno::ECHILD, "waitpid", "cage does not exist");
-            }
+            CAGE_MAP.read().iter().flatten().any(|child| {
+                child.parent.load(SeqCst) == cageid
+                    && selected(child.cageid, child.pgid.load(SeqCst))
+            })
+        };
+        if !has_child {
+            return syscall_error(
+                Errno::ECHILD,
+                "waitpid",
+                "no existing unwaited-for child processes",
+            );
+        }
 
-            // now we have verified that the cage exists and is the child of the cage
-            loop {
-                // the cage is not in the zombie list
-                // we need to wait for the cage to actually exit
+        if options & libc::WNOHANG != 0 {
+            return 0;
+        }
+        // signals notify `zombie_cv` as well, after marking themselves pending
+        if lind_signal_interrupts(cageid) {
+            return syscall_error(Errno::EINTR, "waitpid", "Interrupted system call");
+        }
 
-                // drop the zombies list before sleep to avoid deadlock
-                drop(zombies);
-                // TODO: replace busy waiting with more efficient mechanism
-                unsafe {
-                    sched_yield();
-                }
-                // after sleep, get the write access of zombies list back
-                zombies = cage.zombies.write();
-
-                // let's check if the zombie list contains the cage
-                if let Some(index) = zombies
-                    .iter()
-                    .position(|zombie| zombie.cageid == cageid_arg as u64)
-                {
-                    // find the cage in zombie list, remove it from the list and break
-                    zombie_opt = Some(zombies.remove(index));
-                    break;
-                }
+        // the lock is released while sleeping, and notified each time a zombie is inserted
+        cage.zombie_cv.wait(&mut zombies);
+    };
+    drop(zombies);
 
-                continue;
-            }
-        }
+    // update the status, encoded the way Linux does: the terminating signal in the low 7 bits, or
+    // the exit code in the second byte for a cage that exited by itself (see WIFEXITED/WIFSIGNALED)
+    if status_arg != 0 {
+        let status = sc_convert_sysarg_to_i32_ref(status_arg, status_cageid, cageid);
+        *status = if zombie.term_signal != 0 {
+            zombie.term_signal & 0x7f
+        } else {
+            (zombie.exit_code & 0xff) << 8
+        };
     }
-
-    // reach here means we already found the desired exited child
-    let zombie = zombie_opt.unwrap();
-    // update the status
-    *status = zombie.exit_code;
-    println!("[rawposix|waitpid] cp-3");
     // return child's cageid
     zombie.cageid as i32
 }
@@ -376,10 +405,11 @@ pub fn wait_syscall(
         return syscall_error(Errno::EFAULT, "waitpid", "Invalid Arguments");
     }
     // left type conversion done inside waitpid_syscall
+    // wait() is equivalent to waitpid(-1, &status, 0)
     waitpid_syscall(
         cageid,
-        0,
-        0,
+        (-1i32) as u64,
+        cageid,
         status_arg,
         status_cageid,
         0,
@@ -452,7 +482,1167 @@ pub fn getppid_syscall(
 
     let cage = get_cage(cageid).unwrap();
 
-    return cage.parent as i32;
+    return cage.parent.load(SeqCst) as i32;
+}
+
+/// Credentials given to the first cage by `lindrustinit`
+static INITIAL_UID: AtomicU32 = AtomicU32::new(DEFAULT_UID);
+static INITIAL_GID: AtomicU32 = AtomicU32::new(DEFAULT_GID);
+
+/// Serializes the changes of credentials, so that the real, effective and saved ids of a cage are always
+/// updated together
+static CREDENTIALS_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// Value of an id argument that leaves the id unchanged, (uid_t) -1 in C
+const ID_UNCHANGED: u32 = u32::MAX;
+
+/// Sets the user and group ids of the first cage, which are used as its real, effective and saved ids.
+/// Must be called before `lindrustinit`, every other cage inherits the credentials of its parent.
+pub fn set_initial_credentials(uid: u32, gid: u32) {
+    INITIAL_UID.store(uid, SeqCst);
+    INITIAL_GID.store(gid, SeqCst);
+}
+
+/// Returns the user and group ids given to the first cage
+pub fn initial_credentials() -> (u32, u32) {
+    (INITIAL_UID.load(SeqCst), INITIAL_GID.load(SeqCst))
+}
+
+/// RLIMIT_AS and RLIMIT_DATA limits given to the first cage by `lindrustinit`, in bytes
+static INITIAL_AS_LIMIT: AtomicU64 = AtomicU64::new(RLIM_INFINITY);
+static INITIAL_DATA_LIMIT: AtomicU64 = AtomicU64::new(RLIM_INFINITY);
+
+/// Sets the RLIMIT_AS and RLIMIT_DATA limits of the first cage, in bytes, used as both its soft and hard
+/// limits. Must be called before `lindrustinit`, every other cage inherits the limits of its parent.
+pub fn set_initial_memory_limits(address_space: u64, data: u64) {
+    INITIAL_AS_LIMIT.store(address_space, SeqCst);
+    INITIAL_DATA_LIMIT.store(data, SeqCst);
+}
+
+/// Returns an empty vmmap with the memory limits given to the first cage
+fn _initial_vmmap() -> Vmmap {
+    let mut vmmap = Vmmap::new();
+    let address_space = INITIAL_AS_LIMIT.load(SeqCst);
+    let data = INITIAL_DATA_LIMIT.load(SeqCst);
+    vmmap.limits = MemoryLimits {
+        address_space: (address_space, address_space),
+        data: (data, data),
+    };
+    vmmap
+}
+
+/// Real, effective and saved user ids of a cage
+fn _user_ids(cage: &Cage) -> [&AtomicI32; 3] {
+    [&cage.uid, &cage.euid, &cage.suid]
+}
+
+/// Real, effective and saved group ids of a cage
+fn _group_ids(cage: &Cage) -> [&AtomicI32; 3] {
+    [&cage.gid, &cage.egid, &cage.sgid]
+}
+
+fn _load_ids(ids: &[&AtomicI32; 3]) -> [u32; 3] {
+    [
+        ids[0].load(SeqCst) as u32,
+        ids[1].load(SeqCst) as u32,
+        ids[2].load(SeqCst) as u32,
+    ]
+}
+
+fn _store_ids(ids: &[&AtomicI32; 3], new: [u32; 3]) {
+    for (id, value) in ids.iter().zip(new) {
+        id.store(value as i32, SeqCst);
+    }
+}
+
+/// Whether the cage may set its ids to any value, like a process with CAP_SETUID and CAP_SETGID. As on
+/// Linux without capabilities, this is the case when its effective user id is root.
+fn _privileged(cage: &Cage) -> bool {
+    cage.euid.load(SeqCst) == 0
+}
+
+/// Shared implementation of `setuid()` and `setgid()`. A privileged cage sets the real, effective and
+/// saved ids, any other cage may only set its effective id to its real or saved id.
+fn _setid(cage: &Cage, ids: [&AtomicI32; 3], id: u32, syscall_name: &str) -> i32 {
+    if id == ID_UNCHANGED {
+        return syscall_error(Errno::EINVAL, syscall_name, "Invalid id");
+    }
+    let _guard = CREDENTIALS_LOCK.lock();
+    let [real, _, saved] = _load_ids(&ids);
+    if _privileged(cage) {
+        _store_ids(&ids, [id, id, id]);
+    } else if id == real || id == saved {
+        _store_ids(&ids, [real, id, saved]);
+    } else {
+        return syscall_error(Errno::EPERM, syscall_name, "Operation not permitted");
+    }
+    0
+}
+
+/// Shared implementation of `setreuid()` and `setregid()`. An unprivileged cage may set its real id to
+/// its real or effective id, and its effective id to its real, effective or saved id. The saved id
+/// becomes the new effective id when the real id is set, or the effective id is set to a value other
+/// than the previous real id.
+fn _setreid(
+    cage: &Cage,
+    ids: [&AtomicI32; 3],
+    new_real: u32,
+    new_effective: u32,
+    syscall_name: &str,
+) -> i32 {
+    let _guard = CREDENTIALS_LOCK.lock();
+    let [real, effective, saved] = _load_ids(&ids);
+    if !_privileged(cage)
+        && ((new_real != ID_UNCHANGED && new_real != real && new_real != effective)
+            || (new_effective != ID_UNCHANGED
+                && ![real, effective, saved].contains(&new_effective)))
+    {
+        return syscall_error(Errno::EPERM, syscall_name, "Operation not permitted");
+    }
+
+    let next_real = if new_real == ID_UNCHANGED {
+        real
+    } else {
+        new_real
+    };
+    let next_effective = if new_effective == ID_UNCHANGED {
+        effective
+    } else {
+        new_effective
+    };
+    let next_saved =
+        if new_real != ID_UNCHANGED || (new_effective != ID_UNCHANGED && new_effective != real) {
+            next_effective
+        } else {
+            saved
+        };
+    _store_ids(&ids, [next_real, next_effective, next_saved]);
+    0
+}
+
+/// Shared implementation of `setresuid()` and `setresgid()`. An unprivileged cage may only set each
+/// id to one of its current real, effective or saved ids.
+fn _setresid(cage: &Cage, ids: [&AtomicI32; 3], new: [u32; 3], syscall_name: &str) -> i32 {
+    let _guard = CREDENTIALS_LOCK.lock();
+    let current = _load_ids(&ids);
+    if !_privileged(cage)
+        && new
+            .iter()
+            .any(|id| *id != ID_UNCHANGED && !current.contains(id))
+    {
+        return syscall_error(Errno::EPERM, syscall_name, "Operation not permitted");
+    }
+
+    let mut next = current;
+    for (id, value) in next.iter_mut().zip(new) {
+        if value != ID_UNCHANGED {
+            *id = value;
+        }
+    }
+    _store_ids(&ids, next);
+    0
+}
+
+/// Shared implementation of `getresuid()` and `getresgid()`, `ptrs` holds the pointers (and their cage
+/// ids) where the real, effective and saved ids are saved.
+fn _getresid(cageid: u64, ids: [&AtomicI32; 3], ptrs: [(u64, u64); 3], syscall_name: &str) -> i32 {
+    if ptrs.iter().any(|(ptr, _)| *ptr == 0) {
+        return syscall_error(Errno::EFAULT, syscall_name, "Invalid address");
+    }
+
+    // All the pointers are checked before any of them is written
+    let mut hostptrs = [std::ptr::null_mut::<u32>(); 3];
+    for (hostptr, (ptr, ptr_cageid)) in hostptrs.iter_mut().zip(ptrs) {
+        let size = std::mem::size_of::<u32>();
+        *hostptr = match sc_convert_addr_to_host_checked(ptr, ptr_cageid, cageid, size, PROT_WRITE)
+        {
+            Ok(addr) => addr as *mut u32,
+            Err(e) => return syscall_error(e, syscall_name, "Invalid address"),
+        };
+    }
+
+    let values = {
+        let _guard = CREDENTIALS_LOCK.lock();
+        _load_ids(&ids)
+    };
+    for (hostptr, value) in hostptrs.into_iter().zip(values) {
+        unsafe { *hostptr = value };
+    }
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getuid.2.html
+///
+/// `getuid_syscall` returns the real user id of the cage.
+///
+/// Return:
+///     - the real user id, this call never fails
+pub fn getuid_syscall(
+    cageid: u64,
+    arg1: u64,
+    arg1_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg1, arg1_cageid)
+        && sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getuid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    cage.uid.load(SeqCst)
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/geteuid.2.html
+///
+/// `geteuid_syscall` returns the effective user id of the cage.
+///
+/// Return:
+///     - the effective user id, this call never fails
+pub fn geteuid_syscall(
+    cageid: u64,
+    arg1: u64,
+    arg1_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg1, arg1_cageid)
+        && sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "geteuid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    cage.euid.load(SeqCst)
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getgid.2.html
+///
+/// `getgid_syscall` returns the real group id of the cage.
+///
+/// Return:
+///     - the real group id, this call never fails
+pub fn getgid_syscall(
+    cageid: u64,
+    arg1: u64,
+    arg1_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg1, arg1_cageid)
+        && sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getgid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    cage.gid.load(SeqCst)
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getegid.2.html
+///
+/// `getegid_syscall` returns the effective group id of the cage.
+///
+/// Return:
+///     - the effective group id, this call never fails
+pub fn getegid_syscall(
+    cageid: u64,
+    arg1: u64,
+    arg1_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg1, arg1_cageid)
+        && sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getegid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    cage.egid.load(SeqCst)
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setuid.2.html
+///
+/// `setuid_syscall` sets the effective user id of the cage. When the cage's effective user id is root,
+/// the real and saved user ids are set too, so the cage can't regain its privileges afterwards.
+///
+/// Input:
+///     - uid_arg: the new user id
+///
+/// Return:
+///     - 0 on success, -EINVAL if `uid` is -1, -EPERM if an unprivileged cage asks for a user id other
+///       than its real or saved user id
+pub fn setuid_syscall(
+    cageid: u64,
+    uid_arg: u64,
+    uid_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let uid = sc_convert_sysarg_to_u32(uid_arg, uid_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setuid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _setid(&cage, _user_ids(&cage), uid, "setuid")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setgid.2.html
+///
+/// `setgid_syscall` sets the effective group id of the cage. When the cage's effective user id is root,
+/// the real and saved group ids are set too.
+///
+/// Input:
+///     - gid_arg: the new group id
+///
+/// Return:
+///     - 0 on success, -EINVAL if `gid` is -1, -EPERM if an unprivileged cage asks for a group id other
+///       than its real or saved group id
+pub fn setgid_syscall(
+    cageid: u64,
+    gid_arg: u64,
+    gid_cageid: u64,
+    arg2: u64,
+    arg2_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let gid = sc_convert_sysarg_to_u32(gid_arg, gid_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg2, arg2_cageid)
+        && sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setgid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _setid(&cage, _group_ids(&cage), gid, "setgid")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setreuid.2.html
+///
+/// `setreuid_syscall` sets the real and effective user ids of the cage, an id of -1 is left unchanged.
+/// The saved user id follows the new effective user id when the real user id is set, or the effective
+/// user id is set to a value other than the previous real user id.
+///
+/// Input:
+///     - ruid_arg: the new real user id
+///     - euid_arg: the new effective user id
+///
+/// Return:
+///     - 0 on success, -EPERM if an unprivileged cage sets its real user id to something other than its
+///       real or effective user id, or its effective user id to something other than one of its ids
+pub fn setreuid_syscall(
+    cageid: u64,
+    ruid_arg: u64,
+    ruid_cageid: u64,
+    euid_arg: u64,
+    euid_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let ruid = sc_convert_sysarg_to_u32(ruid_arg, ruid_cageid, cageid);
+    let euid = sc_convert_sysarg_to_u32(euid_arg, euid_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setreuid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _setreid(&cage, _user_ids(&cage), ruid, euid, "setreuid")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setregid.2.html
+///
+/// `setregid_syscall` sets the real and effective group ids of the cage, following the same rules as
+/// `setreuid_syscall`.
+///
+/// Input:
+///     - rgid_arg: the new real group id
+///     - egid_arg: the new effective group id
+///
+/// Return:
+///     - 0 on success, -EPERM if an unprivileged cage sets its real group id to something other than its
+///       real or effective group id, or its effective group id to something other than one of its ids
+pub fn setregid_syscall(
+    cageid: u64,
+    rgid_arg: u64,
+    rgid_cageid: u64,
+    egid_arg: u64,
+    egid_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let rgid = sc_convert_sysarg_to_u32(rgid_arg, rgid_cageid, cageid);
+    let egid = sc_convert_sysarg_to_u32(egid_arg, egid_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setregid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _setreid(&cage, _group_ids(&cage), rgid, egid, "setregid")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setresuid.2.html
+///
+/// `setresuid_syscall` sets the real, effective and saved user ids of the cage, an id of -1 is left
+/// unchanged. glibc also implements `seteuid()` with this call.
+///
+/// Input:
+///     - ruid_arg: the new real user id
+///     - euid_arg: the new effective user id
+///     - suid_arg: the new saved user id
+///
+/// Return:
+///     - 0 on success, -EPERM if an unprivileged cage sets an id to something other than its current
+///       real, effective or saved user id
+pub fn setresuid_syscall(
+    cageid: u64,
+    ruid_arg: u64,
+    ruid_cageid: u64,
+    euid_arg: u64,
+    euid_cageid: u64,
+    suid_arg: u64,
+    suid_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let ruid = sc_convert_sysarg_to_u32(ruid_arg, ruid_cageid, cageid);
+    let euid = sc_convert_sysarg_to_u32(euid_arg, euid_cageid, cageid);
+    let suid = sc_convert_sysarg_to_u32(suid_arg, suid_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setresuid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _setresid(&cage, _user_ids(&cage), [ruid, euid, suid], "setresuid")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setresgid.2.html
+///
+/// `setresgid_syscall` sets the real, effective and saved group ids of the cage, an id of -1 is left
+/// unchanged. glibc also implements `setegid()` with this call.
+///
+/// Input:
+///     - rgid_arg: the new real group id
+///     - egid_arg: the new effective group id
+///     - sgid_arg: the new saved group id
+///
+/// Return:
+///     - 0 on success, -EPERM if an unprivileged cage sets an id to something other than its current
+///       real, effective or saved group id
+pub fn setresgid_syscall(
+    cageid: u64,
+    rgid_arg: u64,
+    rgid_cageid: u64,
+    egid_arg: u64,
+    egid_cageid: u64,
+    sgid_arg: u64,
+    sgid_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let rgid = sc_convert_sysarg_to_u32(rgid_arg, rgid_cageid, cageid);
+    let egid = sc_convert_sysarg_to_u32(egid_arg, egid_cageid, cageid);
+    let sgid = sc_convert_sysarg_to_u32(sgid_arg, sgid_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setresgid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _setresid(&cage, _group_ids(&cage), [rgid, egid, sgid], "setresgid")
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getresuid.2.html
+///
+/// `getresuid_syscall` saves the real, effective and saved user ids of the cage.
+///
+/// Input:
+///     - ruid_arg, euid_arg, suid_arg: pointers to the `uid_t` where each id is saved
+///
+/// Return:
+///     - 0 on success, -EFAULT if any pointer is NULL
+pub fn getresuid_syscall(
+    cageid: u64,
+    ruid_arg: u64,
+    ruid_cageid: u64,
+    euid_arg: u64,
+    euid_cageid: u64,
+    suid_arg: u64,
+    suid_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getresuid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _getresid(
+        cageid,
+        _user_ids(&cage),
+        [
+            (ruid_arg, ruid_cageid),
+            (euid_arg, euid_cageid),
+            (suid_arg, suid_cageid),
+        ],
+        "getresuid",
+    )
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getresgid.2.html
+///
+/// `getresgid_syscall` saves the real, effective and saved group ids of the cage.
+///
+/// Input:
+///     - rgid_arg, egid_arg, sgid_arg: pointers to the `gid_t` where each id is saved
+///
+/// Return:
+///     - 0 on success, -EFAULT if any pointer is NULL
+pub fn getresgid_syscall(
+    cageid: u64,
+    rgid_arg: u64,
+    rgid_cageid: u64,
+    egid_arg: u64,
+    egid_cageid: u64,
+    sgid_arg: u64,
+    sgid_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getresgid", "Invalid Arguments");
+    }
+
+    let cage = get_cage(cageid).unwrap();
+    _getresid(
+        cageid,
+        _group_ids(&cage),
+        [
+            (rgid_arg, rgid_cageid),
+            (egid_arg, egid_cageid),
+            (sgid_arg, sgid_cageid),
+        ],
+        "getresgid",
+    )
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/sigaction.2.html
+///
+/// `sigaction_syscall` examines and changes the action taken by the cage on receipt of a specific signal.
+/// Handlers are stored in the cage struct, as signals are emulated per cage in Lind (see `cage::signal`).
+/// `sa_handler` is the index of the handler in the guest's function table, or SIG_DFL / SIG_IGN.
+///
+/// Input:
+///     - sig_arg: the signal number, any valid signal except SIGKILL and SIGSTOP can be changed
+///     - act_arg: pointer to the new `SigactionStruct`, or NULL to only query the current action
+///     - oact_arg: pointer where the previous action is saved, can be NULL
+///
+/// Return:
+///     - 0 on success, -EINVAL if the signal is invalid or is SIGKILL / SIGSTOP
+pub fn sigaction_syscall(
+    cageid: u64,
+    sig_arg: u64,
+    sig_cageid: u64,
+    act_arg: u64,
+    act_cageid: u64,
+    oact_arg: u64,
+    oact_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let sig = sc_convert_sysarg_to_i32(sig_arg, sig_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "sigaction", "Invalid Arguments");
+    }
+
+    if !is_valid_signal(sig) {
+        return syscall_error(Errno::EINVAL, "sigaction", "Invalid signal number");
+    }
+    if act_arg != 0 && is_unblockable_signal(sig) {
+        return syscall_error(
+            Errno::EINVAL,
+            "sigaction",
+            "The action of SIGKILL and SIGSTOP cannot be changed",
+        );
+    }
+
+    let size = std::mem::size_of::<SigactionStruct>();
+    let oact = if oact_arg != 0 {
+        match sc_convert_addr_to_host_checked(oact_arg, oact_cageid, cageid, size, PROT_WRITE) {
+            Ok(addr) => addr as *mut SigactionStruct,
+            Err(e) => return syscall_error(e, "sigaction", "Invalid address"),
+        }
+    } else {
+        std::ptr::null_mut()
+    };
+    let act = if act_arg != 0 {
+        match sc_convert_addr_to_host_checked(act_arg, act_cageid, cageid, size, PROT_READ) {
+            Ok(addr) => addr as *const SigactionStruct,
+            Err(e) => return syscall_error(e, "sigaction", "Invalid address"),
+        }
+    } else {
+        std::ptr::null()
+    };
+
+    let cage = get_cage(cageid).unwrap();
+    let mut handlers = cage.signalhandler.write();
+
+    if !oact.is_null() {
+        unsafe {
+            *oact = handlers.get(&sig).copied().unwrap_or_default();
+        }
+    }
+
+    if !act.is_null() {
+        let mut newact = unsafe { *act };
+        // SIGKILL and SIGSTOP can never be blocked, even while running a handler
+        newact.sa_mask &= !(sigmask_bit(SIGKILL) | sigmask_bit(SIGSTOP));
+        handlers.insert(sig, newact);
+    }
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/sigprocmask.2.html
+///
+/// `sigprocmask_syscall` fetches and/or changes the signal mask of the calling thread. Signals that get
+/// unblocked while pending are delivered when the syscall returns to the guest.
+///
+/// Input:
+///     - how_arg: SIG_BLOCK, SIG_UNBLOCK or SIG_SETMASK
+///     - set_arg: pointer to the new `SigsetType`, or NULL to only query the current mask
+///     - oldset_arg: pointer where the previous mask is saved, can be NULL
+///
+/// Return:
+///     - 0 on success, -EINVAL if `how` is invalid
+pub fn sigprocmask_syscall(
+    cageid: u64,
+    how_arg: u64,
+    how_cageid: u64,
+    set_arg: u64,
+    set_cageid: u64,
+    oldset_arg: u64,
+    oldset_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let how = sc_convert_sysarg_to_i32(how_arg, how_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "sigprocmask", "Invalid Arguments");
+    }
+
+    let size = std::mem::size_of::<SigsetType>();
+    let oldset = if oldset_arg != 0 {
+        match sc_convert_addr_to_host_checked(oldset_arg, oldset_cageid, cageid, size, PROT_WRITE) {
+            Ok(addr) => addr as *mut SigsetType,
+            Err(e) => return syscall_error(e, "sigprocmask", "Invalid address"),
+        }
+    } else {
+        std::ptr::null_mut()
+    };
+
+    let cage = get_cage(cageid).unwrap();
+    let threadid = current_threadid();
+    let oldmask = get_sigmask(&cage, threadid);
+
+    if set_arg != 0 {
+        let set =
+            match sc_convert_addr_to_host_checked(set_arg, set_cageid, cageid, size, PROT_READ) {
+                Ok(addr) => unsafe { *(addr as *const SigsetType) },
+                Err(e) => return syscall_error(e, "sigprocmask", "Invalid address"),
+            };
+        let newmask = match how {
+            SIG_BLOCK => oldmask | set,
+            SIG_UNBLOCK => oldmask & !set,
+            SIG_SETMASK => set,
+            _ => {
+                return syscall_error(Errno::EINVAL, "sigprocmask", "Invalid value for how");
+            }
+        };
+        set_sigmask(&cage, threadid, newmask);
+    }
+
+    if !oldset.is_null() {
+        unsafe {
+            *oldset = oldmask;
+        }
+    }
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/kill.2.html
+///
+/// `kill_syscall` sends a signal to another cage or to a group of cages. The signal is only marked pending
+/// on the target cages, each of which delivers it to itself at its next safe point. Process groups are the
+/// `pgid` of the cages, which is inherited on fork.
+///
+/// Input:
+///     - pid_arg: the cage id of the target cage, 0 for the process group of the calling cage, -1 for every
+///       cage except init, or -pgid for the process group `pgid`
+///     - sig_arg: the signal to send, 0 only checks the target cages exist
+///
+/// Return:
+///     - 0 on success, -EINVAL if the signal is invalid, -ESRCH if no target cage exists
+pub fn kill_syscall(
+    cageid: u64,
+    pid_arg: u64,
+    pid_cageid: u64,
+    sig_arg: u64,
+    sig_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let pid = sc_convert_sysarg_to_i32(pid_arg, pid_cageid, cageid);
+    let sig = sc_convert_sysarg_to_i32(sig_arg, sig_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "kill", "Invalid Arguments");
+    }
+
+    if sig != 0 && !is_valid_signal(sig) {
+        return syscall_error(Errno::EINVAL, "kill", "Invalid signal number");
+    }
+
+    if pid > 0 {
+        let target = pid as u64;
+        if get_cage(target).is_none() {
+            return syscall_error(Errno::ESRCH, "kill", "Target cage does not exist");
+        }
+        if sig != 0 && !lind_send_signal(target, sig) {
+            return syscall_error(Errno::ESRCH, "kill", "Target cage does not exist");
+        }
+        return 0;
+    }
+
+    let found = match pid {
+        // Every cage but init, and the utility cage that only exists inside rawposix
+        -1 => lind_send_signal_to_cages(sig, |cage| cage.cageid > INIT_CAGEID),
+        _ => {
+            let pgid = if pid == 0 {
+                match get_cage(cageid) {
+                    Some(cage) => cage.pgid.load(SeqCst),
+                    None => {
+                        return syscall_error(Errno::ESRCH, "kill", "Calling cage does not exist")
+                    }
+                }
+            } else {
+                // -i32::MIN doesn't fit in an i32
+                -(pid as i64) as u64
+            };
+            lind_send_signal_to_cages(sig, |cage| cage.pgid.load(SeqCst) == pgid)
+        }
+    };
+    if !found {
+        return syscall_error(Errno::ESRCH, "kill", "No cage in the target process group");
+    }
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setitimer.2.html
+///
+/// `setitimer_syscall` arms or disarms the interval timer of the cage. Only ITIMER_REAL is supported:
+/// SIGALRM is raised on the cage when the timer expires, and checked for at every safe point. `alarm()`
+/// is implemented by glibc on top of this syscall.
+///
+/// Input:
+///     - which_arg: the timer to set, must be ITIMER_REAL
+///     - new_arg: pointer to the new `ITimerVal`, or NULL to only query the timer
+///     - old_arg: pointer where the previous timer value is saved, can be NULL
+///
+/// Return:
+///     - 0 on success, -EINVAL if `which` is not supported or the new value is out of range
+pub fn setitimer_syscall(
+    cageid: u64,
+    which_arg: u64,
+    which_cageid: u64,
+    new_arg: u64,
+    new_cageid: u64,
+    old_arg: u64,
+    old_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let which = sc_convert_sysarg_to_i32(which_arg, which_cageid, cageid);
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setitimer", "Invalid Arguments");
+    }
+
+    if which != ITIMER_REAL {
+        return syscall_error(Errno::EINVAL, "setitimer", "Only ITIMER_REAL is supported");
+    }
+
+    let size = std::mem::size_of::<ITimerVal>();
+    let oldval = if old_arg != 0 {
+        match sc_convert_addr_to_host_checked(old_arg, old_cageid, cageid, size, PROT_WRITE) {
+            Ok(addr) => Some(unsafe { &mut *(addr as *mut ITimerVal) }),
+            Err(e) => return syscall_error(e, "setitimer", "Invalid address"),
+        }
+    } else {
+        None
+    };
+
+    let cage = get_cage(cageid).unwrap();
+
+    let old = if new_arg != 0 {
+        let newval =
+            match sc_convert_addr_to_host_checked(new_arg, new_cageid, cageid, size, PROT_READ) {
+                Ok(addr) => unsafe { &*(addr as *const ITimerVal) },
+                Err(e) => return syscall_error(e, "setitimer", "Invalid address"),
+            };
+        let (Some(value), Some(interval)) = (
+            _timeval_to_duration(&newval.it_value),
+            _timeval_to_duration(&newval.it_interval),
+        ) else {
+            return syscall_error(Errno::EINVAL, "setitimer", "Invalid timer value");
+        };
+        cage.interval_timer.set_itimer(value, interval)
+    } else {
+        cage.interval_timer.get_itimer()
+    };
+
+    if let Some(oldval) = oldval {
+        oldval.it_value = _duration_to_timeval(old.0);
+        oldval.it_interval = _duration_to_timeval(old.1);
+    }
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/getrlimit.2.html
+///
+/// `getrlimit_syscall` returns the soft and hard limits of a resource of the cage. RLIMIT_NOFILE is kept
+/// per cage by `fdtables`, which also enforces it. RLIMIT_AS and RLIMIT_DATA are kept in the cage's
+/// `vmmap`, and enforced by `mmap`, `mremap`, `brk` and `sbrk`. RLIMIT_STACK is fixed when the module is
+/// instantiated, so it always reports the default limits.
+///
+/// Input:
+///     - resource_arg: RLIMIT_NOFILE, RLIMIT_AS, RLIMIT_DATA or RLIMIT_STACK
+///     - rlim_arg: pointer to the `Rlimit` where the limits are saved
+///
+/// Return:
+///     - 0 on success, -EFAULT if `rlim` is NULL, -EINVAL if the resource is not supported
+pub fn getrlimit_syscall(
+    cageid: u64,
+    resource_arg: u64,
+    resource_cageid: u64,
+    rlim_arg: u64,
+    rlim_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let resource = sc_convert_sysarg_to_u32(resource_arg, resource_cageid, cageid) as u64;
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "getrlimit", "Invalid Arguments");
+    }
+
+    if rlim_arg == 0 {
+        return syscall_error(Errno::EFAULT, "getrlimit", "rlim is NULL");
+    }
+
+    let (rlim_cur, rlim_max) = match resource {
+        RLIMIT_NOFILE => fdtables::get_fd_limits(cageid),
+        RLIMIT_AS => get_cage(cageid).unwrap().vmmap.read().limits.address_space,
+        RLIMIT_DATA => get_cage(cageid).unwrap().vmmap.read().limits.data,
+        RLIMIT_STACK => (STACK_CUR, STACK_MAX),
+        _ => {
+            return syscall_error(Errno::EINVAL, "getrlimit", "Unsupported resource");
+        }
+    };
+
+    let size = std::mem::size_of::<Rlimit>();
+    let rlim =
+        match sc_convert_addr_to_host_checked(rlim_arg, rlim_cageid, cageid, size, PROT_WRITE) {
+            Ok(addr) => unsafe { &mut *(addr as *mut Rlimit) },
+            Err(e) => return syscall_error(e, "getrlimit", "Invalid address"),
+        };
+    rlim.rlim_cur = rlim_cur;
+    rlim.rlim_max = rlim_max;
+
+    0
+}
+
+/// Reference to Linux: https://man7.org/linux/man-pages/man2/setrlimit.2.html
+///
+/// `setrlimit_syscall` sets the soft and hard limits of a resource of the cage. Like an unprivileged
+/// process, a cage may lower its RLIMIT_NOFILE, RLIMIT_AS and RLIMIT_DATA hard limits but never raise
+/// them. The new limits are inherited by the children the cage forks afterwards, and memory already
+/// mapped above a new soft limit stays mapped. RLIMIT_STACK can't be changed once the module is
+/// instantiated, so only setting it to its current limits is accepted.
+///
+/// Input:
+///     - resource_arg: RLIMIT_NOFILE, RLIMIT_AS, RLIMIT_DATA or RLIMIT_STACK
+///     - rlim_arg: pointer to the new `Rlimit`
+///
+/// Return:
+///     - 0 on success, -EFAULT if `rlim` is NULL, -EINVAL if the resource is not supported or the soft
+///       limit is above the hard limit, -EPERM if the hard limit would be raised
+pub fn setrlimit_syscall(
+    cageid: u64,
+    resource_arg: u64,
+    resource_cageid: u64,
+    rlim_arg: u64,
+    rlim_cageid: u64,
+    arg3: u64,
+    arg3_cageid: u64,
+    arg4: u64,
+    arg4_cageid: u64,
+    arg5: u64,
+    arg5_cageid: u64,
+    arg6: u64,
+    arg6_cageid: u64,
+) -> i32 {
+    let resource = sc_convert_sysarg_to_u32(resource_arg, resource_cageid, cageid) as u64;
+    // would sometimes check, sometimes be a no-op depending on the compiler settings
+    if !(sc_unusedarg(arg3, arg3_cageid)
+        && sc_unusedarg(arg4, arg4_cageid)
+        && sc_unusedarg(arg5, arg5_cageid)
+        && sc_unusedarg(arg6, arg6_cageid))
+    {
+        return syscall_error(Errno::EFAULT, "setrlimit", "Invalid Arguments");
+    }
+
+    if rlim_arg == 0 {
+        return syscall_error(Errno::EFAULT, "setrlimit", "rlim is NULL");
+    }
+    let size = std::mem::size_of::<Rlimit>();
+    let rlim = match sc_convert_addr_to_host_checked(rlim_arg, rlim_cageid, cageid, size, PROT_READ)
+    {
+        Ok(addr) => unsafe { &*(addr as *const Rlimit) },
+        Err(e) => return syscall_error(e, "setrlimit", "Invalid address"),
+    };
+    if rlim.rlim_cur > rlim.rlim_max {
+        return syscall_error(
+            Errno::EINVAL,
+            "setrlimit",
+            "Soft limit is above the hard limit",
+        );
+    }
+
+    match resource {
+        RLIMIT_NOFILE => match fdtables::set_fd_limits(cageid, rlim.rlim_cur, rlim.rlim_max) {
+            Ok(()) => 0,
+            Err(_) => syscall_error(Errno::EPERM, "setrlimit", "Cannot raise the hard limit"),
+        },
+        RLIMIT_AS | RLIMIT_DATA => {
+            let cage = get_cage(cageid).unwrap();
+            let mut vmmap = cage.vmmap.write();
+            let limits = if resource == RLIMIT_AS {
+                &mut vmmap.limits.address_space
+            } else {
+                &mut vmmap.limits.data
+            };
+            if rlim.rlim_max > limits.1 {
+                return syscall_error(Errno::EPERM, "setrlimit", "Cannot raise the hard limit");
+            }
+            *limits = (rlim.rlim_cur, rlim.rlim_max);
+            0
+        }
+        RLIMIT_STACK => {
+            if rlim.rlim_cur != STACK_CUR || rlim.rlim_max != STACK_MAX {
+                return syscall_error(
+                    Errno::EPERM,
+                    "setrlimit",
+                    "The stack size cannot be changed",
+                );
+            }
+            0
+        }
+        _ => syscall_error(Errno::EINVAL, "setrlimit", "Unsupported resource"),
+    }
+}
+
+/// Converts a guest `TimeVal` into a `Duration`, returns None if it is negative or `tv_usec` is out of range
+pub(crate) fn _timeval_to_duration(tv: &TimeVal) -> Option<Duration> {
+    if tv.tv_sec < 0 || tv.tv_usec < 0 || tv.tv_usec >= 1_000_000 {
+        return None;
+    }
+    Some(Duration::new(tv.tv_sec as u64, (tv.tv_usec * 1000) as u32))
+}
+
+pub(crate) fn _duration_to_timeval(duration: Duration) -> TimeVal {
+    TimeVal {
+        tv_sec: duration.as_secs() as i64,
+        tv_usec: duration.subsec_micros() as i64,
+    }
 }
 
 /// Those functions are required by wasmtime to create the first cage. `verbosity` indicates whether
@@ -461,19 +1651,34 @@ pub fn lindrustinit(verbosity: isize) {
     let _ = VERBOSE.set(verbosity); //assigned to suppress unused result warning
 
     fdtables::register_close_handlers(FDKIND_KERNEL, fdtables::NULL_FUNC, kernel_close);
+    fdtables::register_close_handlers(FDKIND_LOOPBACK, fdtables::NULL_FUNC, loopback_close);
+    fdtables::register_close_handlers(FDKIND_PIPE, fdtables::NULL_FUNC, pipe_close);
+    // pipes and in-memory sockets block on their own condition variables
+    lind_register_signal_waker(pipe::wake_all);
+    lind_register_signal_waker(loopback::wake_all);
 
+    let (uid, gid) = initial_credentials();
     let utilcage = Cage {
         cageid: 0,
         cwd: RwLock::new(Arc::new(PathBuf::from("/"))),
-        parent: 0,
-        gid: AtomicI32::new(-1),
-        uid: AtomicI32::new(-1),
-        egid: AtomicI32::new(-1),
-        euid: AtomicI32::new(-1),
+        parent: AtomicU64::new(0),
+        pgid: AtomicU64::new(0),
+        gid: AtomicI32::new(gid as i32),
+        uid: AtomicI32::new(uid as i32),
+        egid: AtomicI32::new(gid as i32),
+        euid: AtomicI32::new(uid as i32),
+        sgid: AtomicI32::new(gid as i32),
+        suid: AtomicI32::new(uid as i32),
         main_threadid: AtomicU64::new(0),
-        zombies: RwLock::new(vec![]),
+        zombies: Mutex::new(vec![]),
+        zombie_cv: Condvar::new(),
         child_num: AtomicU64::new(0),
-        vmmap: RwLock::new(Vmmap::new()),
+        vmmap: RwLock::new(_initial_vmmap()),
+        signalhandler: RwLock::new(HashMap::new()),
+        sigset: RwLock::new(HashMap::new()),
+        pending_signals: AtomicU64::new(0),
+        interval_timer: IntervalTimer::new(),
+        term_signal: AtomicI32::new(0),
     };
 
     add_cage(
@@ -532,15 +1737,24 @@ pub fn lindrustinit(verbosity: isize) {
     let initcage = Cage {
         cageid: 1,
         cwd: RwLock::new(Arc::new(PathBuf::from("/"))),
-        parent: 1,
-        gid: AtomicI32::new(-1),
-        uid: AtomicI32::new(-1),
-        egid: AtomicI32::new(-1),
-        euid: AtomicI32::new(-1),
+        parent: AtomicU64::new(1),
+        pgid: AtomicU64::new(1),
+        gid: AtomicI32::new(gid as i32),
+        uid: AtomicI32::new(uid as i32),
+        egid: AtomicI32::new(gid as i32),
+        euid: AtomicI32::new(uid as i32),
+        sgid: AtomicI32::new(gid as i32),
+        suid: AtomicI32::new(uid as i32),
         main_threadid: AtomicU64::new(0),
-        zombies: RwLock::new(vec![]),
+        zombies: Mutex::new(vec![]),
+        zombie_cv: Condvar::new(),
         child_num: AtomicU64::new(0),
-        vmmap: RwLock::new(Vmmap::new()),
+        vmmap: RwLock::new(_initial_vmmap()),
+        signalhandler: RwLock::new(HashMap::new()),
+        sigset: RwLock::new(HashMap::new()),
+        pending_signals: AtomicU64::new(0),
+        interval_timer: IntervalTimer::new(),
+        term_signal: AtomicI32::new(0),
     };
 
     // Add cage to cagetable
@@ -0,0 +1,163 @@
+mod common;
+
+use cage::memory::mem_helper::check_and_convert_addr_ext;
+use common::{fork_cage, init_memory, map_pages, page_addr, INIT_CAGEID};
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{MAX_CAGEID, PROT_READ, PROT_WRITE};
+use typemap::syscall_conv::{
+    sc_convert_addr_to_host_checked, sc_convert_buf_to_host_checked,
+    sc_convert_uaddr_to_host_checked,
+};
+
+/// Pages of linear memory reserved for every test cage, inaccessible until mapped
+const RESERVED_PAGES: u32 = 8;
+/// Pages at the start of the linear memory that are readable and writable
+const MEMORY_PAGES: u32 = 2;
+/// Page right after them, which is only readable. The pages after it are not mapped.
+const READONLY_PAGE: u32 = MEMORY_PAGES;
+
+/// Cage id that is never given to a cage, well above the ids `common` hands out
+const MISSING_CAGEID: u64 = MAX_CAGEID as u64 - 1;
+
+/// Forks a new cage from the init cage, whose linear memory is a host mapping of `RESERVED_PAGES`
+/// pages. The first `MEMORY_PAGES` pages are readable and writable, `READONLY_PAGE` is readable
+/// and the other pages are not mapped. Returns the cage id and the host base address of the cage
+/// memory.
+fn init_test_cage() -> (u64, *mut u8) {
+    let cageid = fork_cage(INIT_CAGEID);
+    let base = init_memory(cageid, RESERVED_PAGES, MEMORY_PAGES);
+    map_pages(cageid, base, READONLY_PAGE, 1, PROT_READ);
+    (cageid, base)
+}
+
+#[test]
+fn test_check_and_convert_addr_ext() {
+    let (cageid, base) = init_test_cage();
+    let host_addr = |addr: u64| base as u64 + addr;
+    let rw = PROT_READ | PROT_WRITE;
+
+    assert_eq!(
+        check_and_convert_addr_ext(cageid, 16, 32, rw),
+        Ok(host_addr(16))
+    );
+    // a region may span several mappings, as long as all of them allow the access
+    let across = page_addr(READONLY_PAGE) - 8;
+    assert_eq!(
+        check_and_convert_addr_ext(cageid, across, 16, PROT_READ),
+        Ok(host_addr(across))
+    );
+    assert_eq!(
+        check_and_convert_addr_ext(cageid, across, 16, PROT_WRITE),
+        Err(Errno::EFAULT)
+    );
+
+    // unmapped pages, and regions beyond the 32-bit linear memory
+    let unmapped = page_addr(READONLY_PAGE + 1);
+    assert_eq!(
+        check_and_convert_addr_ext(cageid, unmapped, 1, PROT_READ),
+        Err(Errno::EFAULT)
+    );
+    assert_eq!(
+        check_and_convert_addr_ext(cageid, u32::MAX as u64, 2, PROT_READ),
+        Err(Errno::EFAULT)
+    );
+    assert_eq!(
+        check_and_convert_addr_ext(cageid, u64::MAX, 1, PROT_READ),
+        Err(Errno::EFAULT)
+    );
+
+    // a cage that doesn't exist has no memory
+    assert_eq!(
+        check_and_convert_addr_ext(MISSING_CAGEID, 16, 32, PROT_READ),
+        Err(Errno::EFAULT)
+    );
+}
+
+#[test]
+fn test_checked_conversion() {
+    let (cageid, base) = init_test_cage();
+    let host_addr = |addr: u64| base as u64 + addr;
+
+    assert_eq!(
+        sc_convert_addr_to_host_checked(16, cageid, cageid, 32, PROT_WRITE),
+        Ok(host_addr(16) as *mut u8)
+    );
+    assert_eq!(
+        sc_convert_buf_to_host_checked(16, cageid, cageid, 32),
+        Ok(host_addr(16) as *const u8)
+    );
+    assert_eq!(
+        sc_convert_uaddr_to_host_checked(16, cageid, cageid, 4, PROT_READ),
+        Ok(host_addr(16))
+    );
+    // empty regions are never accessed
+    let unmapped = page_addr(READONLY_PAGE + 1);
+    assert_eq!(
+        sc_convert_addr_to_host_checked(unmapped, cageid, cageid, 0, PROT_WRITE),
+        Ok(host_addr(unmapped) as *mut u8)
+    );
+
+    assert_eq!(
+        sc_convert_addr_to_host_checked(16, MISSING_CAGEID, cageid, 32, PROT_WRITE),
+        Err(Errno::EFAULT)
+    );
+    assert_eq!(
+        sc_convert_buf_to_host_checked(16, MISSING_CAGEID, cageid, 32),
+        Err(Errno::EFAULT)
+    );
+    assert_eq!(
+        sc_convert_uaddr_to_host_checked(16, MISSING_CAGEID, cageid, 4, PROT_READ),
+        Err(Errno::EFAULT)
+    );
+}
+
+#[cfg(feature = "secure")]
+#[test]
+fn test_checked_conversion_secure() {
+    use common::load;
+    use rawposix::syscalls::sys_calls::{getrlimit_syscall, sigprocmask_syscall};
+    use sysdefs::constants::sys_const::RLIMIT_NOFILE;
+
+    let (cageid, base) = init_test_cage();
+    let efault = -(Errno::EFAULT as i32);
+    let readonly = page_addr(READONLY_PAGE);
+    let unmapped = page_addr(READONLY_PAGE + 1);
+
+    assert_eq!(
+        sc_convert_buf_to_host_checked(readonly, cageid, cageid, 16),
+        Ok((base as u64 + readonly) as *const u8)
+    );
+    assert_eq!(
+        sc_convert_addr_to_host_checked(readonly, cageid, cageid, 16, PROT_WRITE),
+        Err(Errno::EFAULT)
+    );
+    assert_eq!(
+        sc_convert_buf_to_host_checked(unmapped, cageid, cageid, 16),
+        Err(Errno::EFAULT)
+    );
+
+    // syscalls fail instead of writing to memory the cage can't write
+    assert_eq!(
+        getrlimit_syscall(
+            cageid,
+            RLIMIT_NOFILE,
+            cageid,
+            readonly,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        efault
+    );
+    assert_eq!(load::<u8>(base, readonly), 0);
+    assert_eq!(
+        sigprocmask_syscall(cageid, 0, cageid, 0, cageid, unmapped, cageid, 0, 0, 0, 0, 0, 0),
+        efault
+    );
+}
@@ -0,0 +1,151 @@
+//! Fixture shared by the rawposix integration tests and benchmarks.
+//!
+//! A test cage is forked from the init cage (or from another test cage) and is given a linear memory that
+//! is a host mapping registered in its vmmap, so that syscalls can be called with guest addresses the way
+//! wasmtime calls them. Every test binary runs in its own process, so each one counts its cage ids from
+//! `INIT_CAGEID + 1` and stays well below `MAX_CAGEID`.
+#![allow(dead_code)]
+
+use cage::get_cage;
+use cage::memory::mem_helper::init_vmmap_helper;
+use cage::memory::vmmap::{MemoryBackingType, VmmapOps};
+use rawposix::syscalls::sys_calls::{fork_syscall, lindrustinit};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Once;
+use sysdefs::constants::fs_const::{
+    MAP_ANONYMOUS, MAP_PRIVATE, PAGESHIFT, PROT_NONE, PROT_READ, PROT_WRITE,
+};
+use typemap::path_conv::LIND_ROOT;
+
+/// The cage `lindrustinit` creates, which test cages are forked from
+pub const INIT_CAGEID: u64 = 1;
+
+static INIT: Once = Once::new();
+static NEXT_CAGEID: AtomicU64 = AtomicU64::new(INIT_CAGEID + 1);
+static NEXT_DIR: AtomicU64 = AtomicU64::new(0);
+
+/// Starts rawposix, once per test binary
+pub fn init_rawposix() {
+    INIT.call_once(|| lindrustinit(0));
+}
+
+/// Forks `parentid` into a new cage that has no linear memory yet. Returns the cage id.
+pub fn fork_cage(parentid: u64) -> u64 {
+    init_rawposix();
+    let cageid = NEXT_CAGEID.fetch_add(1, Ordering::SeqCst);
+    assert_eq!(
+        fork_syscall(parentid, cageid, parentid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    cageid
+}
+
+/// Reserves `npages` inaccessible pages of host memory to serve as the linear memory of a cage. Returns
+/// their host base address.
+pub fn reserve_memory(npages: u32) -> *mut u8 {
+    let base = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            (npages as usize) << PAGESHIFT,
+            PROT_NONE,
+            (MAP_PRIVATE | MAP_ANONYMOUS) as i32,
+            -1,
+            0,
+        )
+    };
+    assert_ne!(base, libc::MAP_FAILED);
+    base as *mut u8
+}
+
+/// Maps `npages` pages at `page_num` of the cage memory starting at `base` with `prot`, both on the host
+/// and in the vmmap of the cage.
+pub fn map_pages(cageid: u64, base: *mut u8, page_num: u32, npages: u32, prot: i32) {
+    let ret = unsafe {
+        libc::mprotect(
+            base.add((page_num as usize) << PAGESHIFT) as *mut libc::c_void,
+            (npages as usize) << PAGESHIFT,
+            prot,
+        )
+    };
+    assert_eq!(ret, 0);
+    get_cage(cageid)
+        .unwrap()
+        .vmmap
+        .write()
+        .add_entry_with_overwrite(
+            page_num,
+            npages,
+            prot,
+            PROT_READ | PROT_WRITE,
+            (MAP_PRIVATE | MAP_ANONYMOUS) as i32,
+            MemoryBackingType::Anonymous,
+            0,
+            0,
+            cageid,
+        )
+        .unwrap();
+}
+
+/// Gives `cageid` a new linear memory of `reserved_pages` pages. Only the heap, the first `memory_pages`
+/// pages, is mapped readable and writable, and the cage can't map pages beyond the reservation. Returns
+/// the host base address of the cage memory.
+pub fn init_memory(cageid: u64, reserved_pages: u32, memory_pages: u32) -> *mut u8 {
+    let base = reserve_memory(reserved_pages);
+    init_vmmap_helper(cageid, base as usize, Some(memory_pages));
+    get_cage(cageid).unwrap().vmmap.write().end_address = reserved_pages;
+    map_pages(cageid, base, 0, memory_pages, PROT_READ | PROT_WRITE);
+    base
+}
+
+/// Forks a new cage from `parentid` whose linear memory is `memory_pages` pages, all readable and
+/// writable. Returns the cage id and the host base address of the cage memory.
+pub fn init_test_cage(parentid: u64, memory_pages: u32) -> (u64, *mut u8) {
+    let cageid = fork_cage(parentid);
+    (cageid, init_memory(cageid, memory_pages, memory_pages))
+}
+
+/// Returns the guest address of page `page_num`
+pub fn page_addr(page_num: u32) -> u64 {
+    (page_num as u64) << PAGESHIFT
+}
+
+/// Stores `val` at guest address `addr` of the cage memory starting at `base`
+pub fn store<T>(base: *mut u8, addr: u64, val: T) {
+    unsafe { (base.add(addr as usize) as *mut T).write_unaligned(val) }
+}
+
+/// Loads a value of type `T` from guest address `addr` of the cage memory starting at `base`
+pub fn load<T>(base: *mut u8, addr: u64) -> T {
+    unsafe { (base.add(addr as usize) as *const T).read_unaligned() }
+}
+
+/// Returns the `len` bytes at guest address `addr` of the cage memory starting at `base`
+pub fn guest_bytes<'a>(base: *mut u8, addr: u64, len: usize) -> &'a [u8] {
+    unsafe { std::slice::from_raw_parts(base.add(addr as usize), len) }
+}
+
+/// Stores `s` as a NUL terminated string at guest address `addr` of the cage memory starting at `base`
+pub fn store_str(base: *mut u8, addr: u64, s: &str) {
+    unsafe {
+        std::ptr::copy_nonoverlapping(s.as_ptr(), base.add(addr as usize), s.len());
+        *base.add(addr as usize + s.len()) = 0;
+    }
+}
+
+/// Creates a new, empty directory under `LIND_ROOT` for a test. Returns its path from the cage's
+/// perspective, the host path is `LIND_ROOT` followed by it.
+pub fn test_dir(name: &str) -> String {
+    let path = format!(
+        "/{}_{}_{}",
+        name,
+        std::process::id(),
+        NEXT_DIR.fetch_add(1, Ordering::SeqCst)
+    );
+    std::fs::create_dir_all(host_path(&path)).unwrap();
+    path
+}
+
+/// Returns the host path of `path`, a path from the cage's perspective
+pub fn host_path(path: &str) -> String {
+    format!("{}{}", LIND_ROOT, path)
+}
@@ -0,0 +1,246 @@
+mod common;
+
+use common::{init_memory, init_rawposix, load, INIT_CAGEID};
+use rawposix::syscalls::sys_calls::{
+    exec_syscall, getegid_syscall, geteuid_syscall, getgid_syscall, getresgid_syscall,
+    getresuid_syscall, getuid_syscall, set_initial_credentials, setgid_syscall, setregid_syscall,
+    setresgid_syscall, setresuid_syscall, setreuid_syscall, setuid_syscall,
+};
+use std::sync::Once;
+use sysdefs::constants::err_const::Errno;
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 4;
+
+/// Addresses in the cage memory where getresuid() and getresgid() save the real, effective and
+/// saved ids
+const IDS_ADDR: [u64; 3] = [64, 68, 72];
+
+/// Credentials of the first cage, root so that the tests can drop privileges
+const INITIAL_UID: u32 = 0;
+const INITIAL_GID: u32 = 0;
+
+/// Id argument that leaves an id unchanged, (uid_t) -1 in C
+const UNCHANGED: u64 = u32::MAX as u64;
+
+static INIT: Once = Once::new();
+
+/// Forks a new cage from `parentid` with `MEMORY_PAGES` pages of linear memory. Returns the cage id and
+/// the host base address of the cage memory.
+fn init_test_cage(parentid: u64) -> (u64, *mut u8) {
+    INIT.call_once(|| {
+        set_initial_credentials(INITIAL_UID, INITIAL_GID);
+        init_rawposix();
+    });
+    common::init_test_cage(parentid, MEMORY_PAGES)
+}
+
+/// Returns the (real, effective, saved) user ids of the cage
+fn getresuid(cageid: u64, base: *mut u8) -> (u32, u32, u32) {
+    assert_eq!(
+        getresuid_syscall(
+            cageid,
+            IDS_ADDR[0],
+            cageid,
+            IDS_ADDR[1],
+            cageid,
+            IDS_ADDR[2],
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    read_ids(base)
+}
+
+/// Returns the (real, effective, saved) group ids of the cage
+fn getresgid(cageid: u64, base: *mut u8) -> (u32, u32, u32) {
+    assert_eq!(
+        getresgid_syscall(
+            cageid,
+            IDS_ADDR[0],
+            cageid,
+            IDS_ADDR[1],
+            cageid,
+            IDS_ADDR[2],
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    read_ids(base)
+}
+
+fn read_ids(base: *mut u8) -> (u32, u32, u32) {
+    let id = |addr: u64| load::<u32>(base, addr);
+    (id(IDS_ADDR[0]), id(IDS_ADDR[1]), id(IDS_ADDR[2]))
+}
+
+fn setresuid(cageid: u64, ruid: u64, euid: u64, suid: u64) -> i32 {
+    setresuid_syscall(
+        cageid, ruid, cageid, euid, cageid, suid, cageid, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+fn setresgid(cageid: u64, rgid: u64, egid: u64, sgid: u64) -> i32 {
+    setresgid_syscall(
+        cageid, rgid, cageid, egid, cageid, sgid, cageid, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+#[test]
+fn test_credentials_inherited_by_fork() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    assert_eq!(
+        getuid_syscall(cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(
+        geteuid_syscall(cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(
+        getgid_syscall(cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(
+        getegid_syscall(cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(getresuid(cageid, base), (0, 0, 0));
+
+    assert_eq!(setresgid(cageid, 100, 200, 300), 0);
+    assert_eq!(setresuid(cageid, 1000, 2000, 3000), 0);
+
+    let (childid, childbase) = init_test_cage(cageid);
+    assert_eq!(
+        getuid_syscall(childid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        1000
+    );
+    assert_eq!(
+        geteuid_syscall(childid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        2000
+    );
+    assert_eq!(
+        getgid_syscall(childid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        100
+    );
+    assert_eq!(
+        getegid_syscall(childid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        200
+    );
+    assert_eq!(getresuid(childid, childbase), (1000, 2000, 3000));
+    assert_eq!(getresgid(childid, childbase), (100, 200, 300));
+}
+
+#[test]
+fn test_setuid_drops_privileges() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    assert_eq!(
+        setuid_syscall(cageid, UNCHANGED, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EINVAL as i32)
+    );
+
+    // root sets all three user ids
+    assert_eq!(
+        setuid_syscall(cageid, 1000, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(getresuid(cageid, base), (1000, 1000, 1000));
+    assert_eq!(
+        setuid_syscall(cageid, 0, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EPERM as i32)
+    );
+    // without privileges, the group ids can't be changed either
+    assert_eq!(
+        setgid_syscall(cageid, 1000, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EPERM as i32)
+    );
+    assert_eq!(getresgid(cageid, base), (0, 0, 0));
+}
+
+#[test]
+fn test_saved_uid_restores_privileges() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    assert_eq!(setresuid(cageid, 1000, 1000, 0), 0);
+
+    // seteuid(0) is allowed as 0 is the saved user id
+    assert_eq!(setresuid(cageid, UNCHANGED, 0, UNCHANGED), 0);
+    assert_eq!(getresuid(cageid, base), (1000, 0, 0));
+    assert_eq!(setresuid(cageid, UNCHANGED, 1000, UNCHANGED), 0);
+
+    // setuid() without privileges only changes the effective user id
+    assert_eq!(
+        setuid_syscall(cageid, 0, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(getresuid(cageid, base), (1000, 0, 0));
+    assert_eq!(setresuid(cageid, UNCHANGED, 1000, UNCHANGED), 0);
+
+    assert_eq!(
+        setresuid(cageid, 2000, UNCHANGED, UNCHANGED),
+        -(Errno::EPERM as i32)
+    );
+    assert_eq!(getresuid(cageid, base), (1000, 1000, 0));
+}
+
+#[test]
+fn test_setreuid_updates_saved_id() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    assert_eq!(setresuid(cageid, 1000, 2000, 0), 0);
+
+    // the effective user id is set to the real user id, the saved user id is kept
+    assert_eq!(
+        setreuid_syscall(cageid, UNCHANGED, cageid, 1000, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(getresuid(cageid, base), (1000, 1000, 0));
+
+    // any other effective user id is also saved
+    assert_eq!(
+        setreuid_syscall(cageid, UNCHANGED, cageid, 0, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(getresuid(cageid, base), (1000, 0, 0));
+
+    // the real and effective user ids are swapped, the saved user id follows the effective one
+    assert_eq!(
+        setreuid_syscall(cageid, 0, cageid, 1000, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(getresuid(cageid, base), (0, 1000, 1000));
+
+    // the real user id may only be set to the real or effective user id
+    assert_eq!(
+        setreuid_syscall(cageid, 3000, cageid, UNCHANGED, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EPERM as i32)
+    );
+    assert_eq!(
+        setregid_syscall(cageid, 3000, cageid, UNCHANGED, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EPERM as i32)
+    );
+}
+
+#[test]
+fn test_exec_saves_effective_ids() {
+    let (cageid, _) = init_test_cage(INIT_CAGEID);
+    assert_eq!(setresgid(cageid, 100, 200, 300), 0);
+    assert_eq!(setresuid(cageid, 1000, 2000, 3000), 0);
+
+    assert_eq!(exec_syscall(cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0), 0);
+    // exec() cleared the cage memory, map it again
+    let base = init_memory(cageid, MEMORY_PAGES, MEMORY_PAGES);
+
+    assert_eq!(getresuid(cageid, base), (1000, 2000, 2000));
+    assert_eq!(getresgid(cageid, base), (100, 200, 200));
+}
@@ -0,0 +1,224 @@
+mod common;
+
+use common::{fork_cage, guest_bytes, host_path, init_memory, store_str, test_dir, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{
+    chdir_syscall, getcwd_syscall, readlinkat_syscall, unlinkat_syscall,
+};
+use std::os::fd::IntoRawFd;
+use std::path::PathBuf;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::FDKIND_KERNEL;
+use typemap::path_conv::{strip_lind_root, LIND_ROOT};
+
+/// Pages of linear memory of every test cage
+const MEMORY_PAGES: u32 = 4;
+
+/// Guest address where the path of a call is kept
+const PATH_ADDR: u64 = 64;
+/// Guest address of the buffer a call fills in
+const BUF_ADDR: u64 = 1024;
+/// Size of the buffer at `BUF_ADDR`
+const BUF_SIZE: u64 = 1024;
+
+/// Forks a new cage from `parentid` and gives it its own linear memory. Returns the cage id and the host
+/// base address of the cage memory.
+fn new_cage(parentid: u64) -> (u64, *mut u8) {
+    let cageid = fork_cage(parentid);
+    (cageid, init_memory(cageid, MEMORY_PAGES, MEMORY_PAGES))
+}
+
+fn chdir(cageid: u64, base: *mut u8, path: &str) -> i32 {
+    store_str(base, PATH_ADDR, path);
+    chdir_syscall(cageid, PATH_ADDR, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0)
+}
+
+fn getcwd(cageid: u64, size: u64) -> i32 {
+    getcwd_syscall(
+        cageid, BUF_ADDR, cageid, size, cageid, 0, 0, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+/// Returns the working directory of `cageid` as reported by `getcwd`
+fn cwd(cageid: u64, base: *mut u8) -> String {
+    let len = getcwd(cageid, BUF_SIZE);
+    assert!(len > 0);
+    // the returned length counts the terminating null byte
+    let bytes = guest_bytes(base, BUF_ADDR, len as usize);
+    assert_eq!(bytes[len as usize - 1], 0);
+    String::from_utf8(bytes[..len as usize - 1].to_vec()).unwrap()
+}
+
+/// Opens the directory `path` on the host and gives `cageid` a virtual fd for it
+fn open_dir(cageid: u64, path: &str) -> u64 {
+    let dir = std::fs::File::open(host_path(path)).unwrap();
+    fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, dir.into_raw_fd() as u64, false, 0)
+        .unwrap()
+}
+
+fn unlinkat(cageid: u64, base: *mut u8, dirfd: u64, path: &str, flags: i32) -> i32 {
+    store_str(base, PATH_ADDR, path);
+    unlinkat_syscall(
+        cageid,
+        dirfd,
+        cageid,
+        PATH_ADDR,
+        cageid,
+        flags as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+/// Reads the symbolic link `path` relative to `dirfd`. Returns the link target, or the error.
+fn readlinkat(cageid: u64, base: *mut u8, dirfd: u64, path: &str) -> Result<String, i32> {
+    store_str(base, PATH_ADDR, path);
+    let ret = readlinkat_syscall(
+        cageid, dirfd, cageid, PATH_ADDR, cageid, BUF_ADDR, cageid, BUF_SIZE, cageid, 0, 0, 0, 0,
+    );
+    if ret < 0 {
+        return Err(ret);
+    }
+    let bytes = guest_bytes(base, BUF_ADDR, ret as usize);
+    Ok(String::from_utf8(bytes.to_vec()).unwrap())
+}
+
+#[test]
+fn test_cwd_is_per_cage_and_inherited_by_fork() {
+    let (parent, parent_base) = new_cage(INIT_CAGEID);
+    let parent_dir = test_dir("dir_test");
+    let child_dir = test_dir("dir_test");
+
+    assert_eq!(chdir(parent, parent_base, &parent_dir), 0);
+    assert_eq!(cwd(parent, parent_base), parent_dir);
+
+    // the child starts out in the working directory of its parent
+    let (child, child_base) = new_cage(parent);
+    assert_eq!(cwd(child, child_base), parent_dir);
+
+    // and changing it doesn't change the working directory of the parent
+    assert_eq!(chdir(child, child_base, &child_dir), 0);
+    assert_eq!(cwd(child, child_base), child_dir);
+    assert_eq!(cwd(parent, parent_base), parent_dir);
+
+    // relative paths are resolved against the working directory of the calling cage only
+    std::fs::create_dir(host_path(&format!("{}/sub", child_dir))).unwrap();
+    assert_eq!(chdir(parent, parent_base, "sub"), -(Errno::ENOENT as i32));
+    assert_eq!(chdir(child, child_base, "sub"), 0);
+    assert_eq!(cwd(child, child_base), format!("{}/sub", child_dir));
+    assert_eq!(chdir(child, child_base, ".."), 0);
+    assert_eq!(cwd(child, child_base), child_dir);
+
+    let file = format!("{}/file", child_dir);
+    std::fs::write(host_path(&file), b"").unwrap();
+    assert_eq!(chdir(child, child_base, &file), -(Errno::ENOTDIR as i32));
+    assert_eq!(cwd(child, child_base), child_dir);
+}
+
+#[test]
+fn test_at_calls_resolve_relative_paths() {
+    let (cageid, base) = new_cage(INIT_CAGEID);
+    let dir = test_dir("dir_test");
+    let other = test_dir("dir_test");
+    std::fs::write(host_path(&format!("{}/file", dir)), b"").unwrap();
+    std::fs::create_dir(host_path(&format!("{}/sub", dir))).unwrap();
+    std::os::unix::fs::symlink(
+        host_path(&format!("{}/file", dir)),
+        host_path(&format!("{}/link", dir)),
+    )
+    .unwrap();
+    std::os::unix::fs::symlink("/etc/passwd", host_path(&format!("{}/outside", dir))).unwrap();
+
+    // relative paths are resolved against dirfd, not against the working directory
+    assert_eq!(chdir(cageid, base, &other), 0);
+    let dirfd = open_dir(cageid, &dir);
+
+    // a target inside the lind root is reported from the cage's perspective
+    assert_eq!(
+        readlinkat(cageid, base, dirfd, "link"),
+        Ok(format!("{}/file", dir))
+    );
+    assert_eq!(
+        readlinkat(cageid, base, dirfd, "outside"),
+        Ok("/etc/passwd".to_string())
+    );
+    // absolute paths ignore dirfd
+    assert_eq!(
+        readlinkat(cageid, base, dirfd, &format!("{}/link", dir)),
+        Ok(format!("{}/file", dir))
+    );
+    assert_eq!(
+        readlinkat(cageid, base, dirfd, "missing"),
+        Err(-(Errno::ENOENT as i32))
+    );
+
+    assert_eq!(
+        unlinkat(cageid, base, dirfd, "sub", 0),
+        -(Errno::EISDIR as i32)
+    );
+    assert_eq!(unlinkat(cageid, base, dirfd, "sub", libc::AT_REMOVEDIR), 0);
+    assert_eq!(unlinkat(cageid, base, dirfd, "file", 0), 0);
+    assert!(!std::path::Path::new(&host_path(&format!("{}/file", dir))).exists());
+    assert!(!std::path::Path::new(&host_path(&format!("{}/sub", dir))).exists());
+
+    // AT_FDCWD resolves relative paths against the working directory of the cage
+    std::fs::write(host_path(&format!("{}/file", other)), b"").unwrap();
+    assert_eq!(unlinkat(cageid, base, libc::AT_FDCWD as u64, "file", 0), 0);
+    assert!(!std::path::Path::new(&host_path(&format!("{}/file", other))).exists());
+
+    assert_eq!(
+        unlinkat(cageid, base, 1000, "link", 0),
+        -(Errno::EBADF as i32)
+    );
+    assert_eq!(
+        unlinkat(cageid, base, dirfd, "link", 0x1000),
+        -(Errno::EINVAL as i32)
+    );
+}
+
+#[test]
+fn test_getcwd_buffer_size() {
+    let (cageid, base) = new_cage(INIT_CAGEID);
+    let dir = test_dir("dir_test");
+    assert_eq!(chdir(cageid, base, &dir), 0);
+
+    // the path doesn't fit without its terminating null byte
+    assert_eq!(getcwd(cageid, dir.len() as u64), -(Errno::ERANGE as i32));
+    assert_eq!(getcwd(cageid, 1), -(Errno::ERANGE as i32));
+    assert_eq!(getcwd(cageid, 0), -(Errno::EINVAL as i32));
+    assert_eq!(
+        getcwd_syscall(cageid, 0, cageid, BUF_SIZE, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EFAULT as i32)
+    );
+
+    assert_eq!(getcwd(cageid, dir.len() as u64 + 1), dir.len() as i32 + 1);
+    assert_eq!(
+        guest_bytes(base, BUF_ADDR, dir.len() + 1),
+        format!("{}\0", dir).as_bytes()
+    );
+}
+
+#[test]
+fn test_strip_lind_root() {
+    assert_eq!(strip_lind_root(LIND_ROOT), Some(PathBuf::from("/")));
+    assert_eq!(
+        strip_lind_root(&format!("{}/", LIND_ROOT)),
+        Some(PathBuf::from("/"))
+    );
+    assert_eq!(
+        strip_lind_root(&format!("{}/a/b", LIND_ROOT)),
+        Some(PathBuf::from("/a/b"))
+    );
+
+    // paths outside of the lind root aren't visible to cages
+    assert_eq!(strip_lind_root("/"), None);
+    assert_eq!(strip_lind_root("/etc/passwd"), None);
+    assert_eq!(strip_lind_root("relative/path"), None);
+    // a sibling that only shares a prefix with the lind root is outside of it
+    assert_eq!(strip_lind_root(&format!("{}2/a", LIND_ROOT)), None);
+    assert_eq!(strip_lind_root(&format!("{}-old", LIND_ROOT)), None);
+}
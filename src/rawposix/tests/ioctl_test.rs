@@ -0,0 +1,141 @@
+mod common;
+
+use common::{init_rawposix, load, page_addr, store, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{fcntl_syscall, ioctl_syscall, pipe2_syscall, write_syscall};
+use rawposix::syscalls::pipe::set_inmemory_pipes;
+use std::sync::Once;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    FIOASYNC, FIOCLEX, FIONBIO, FIONCLEX, FIONREAD, F_GETFD, F_GETFL, O_NONBLOCK, TIOCGWINSZ,
+};
+use sysdefs::data::fs_struct::PipeArray;
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 4;
+
+/// Address in the cage memory where the argument of an ioctl is kept
+const ARG_ADDR: u64 = 64;
+
+/// Address in the cage memory of the data written to pipes
+const BUF_ADDR: u64 = 128;
+
+static INIT: Once = Once::new();
+
+/// Forks a new cage from `parentid` with `MEMORY_PAGES` pages of linear memory. Returns the cage id and
+/// the host base address of the cage memory. Every pipe of the test binary is created in memory.
+fn init_test_cage(parentid: u64) -> (u64, *mut u8) {
+    INIT.call_once(|| {
+        init_rawposix();
+        set_inmemory_pipes(true);
+    });
+    common::init_test_cage(parentid, MEMORY_PAGES)
+}
+
+/// Creates an in-memory pipe and returns its read and write fds
+fn pipe(cageid: u64, base: *mut u8) -> (u64, u64) {
+    // pipe2() takes a host pointer to the fd array
+    let pipefd = base as *mut PipeArray;
+    assert_eq!(
+        pipe2_syscall(
+            cageid,
+            pipefd as u64,
+            cageid,
+            0,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    let fds = unsafe { &*pipefd };
+    (fds.readfd as u64, fds.writefd as u64)
+}
+
+fn ioctl(cageid: u64, fd: u64, request: u32, arg: u64) -> i32 {
+    ioctl_syscall(
+        cageid,
+        fd,
+        cageid,
+        request as u64,
+        cageid,
+        arg,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn fcntl(cageid: u64, fd: u64, cmd: i32) -> i32 {
+    fcntl_syscall(
+        cageid, fd, cageid, cmd as u64, cageid, 0, cageid, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+#[test]
+fn test_ioctl_cloexec() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let (readfd, _) = pipe(cageid, base);
+
+    assert_eq!(ioctl(cageid, readfd, FIOCLEX, 0), 0);
+    assert_eq!(fcntl(cageid, readfd, F_GETFD), 1);
+    assert_eq!(ioctl(cageid, readfd, FIONCLEX, 0), 0);
+    assert_eq!(fcntl(cageid, readfd, F_GETFD), 0);
+    assert_eq!(ioctl(cageid, 1000, FIOCLEX, 0), -(Errno::EBADF as i32));
+}
+
+#[test]
+fn test_ioctl_pipe_requests() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let (readfd, writefd) = pipe(cageid, base);
+
+    store(base, ARG_ADDR, 1i32);
+    assert_eq!(ioctl(cageid, readfd, FIONBIO, ARG_ADDR), 0);
+    assert_ne!(fcntl(cageid, readfd, F_GETFL) & O_NONBLOCK, 0);
+    store(base, ARG_ADDR, 0i32);
+    assert_eq!(ioctl(cageid, readfd, FIONBIO, ARG_ADDR), 0);
+    assert_eq!(fcntl(cageid, readfd, F_GETFL) & O_NONBLOCK, 0);
+
+    assert_eq!(ioctl(cageid, readfd, FIONREAD, ARG_ADDR), 0);
+    assert_eq!(load::<i32>(base, ARG_ADDR), 0);
+    assert_eq!(
+        write_syscall(cageid, writefd, cageid, BUF_ADDR, cageid, 5, cageid, 0, 0, 0, 0, 0, 0),
+        5
+    );
+    assert_eq!(ioctl(cageid, readfd, FIONREAD, ARG_ADDR), 0);
+    assert_eq!(load::<i32>(base, ARG_ADDR), 5);
+
+    // a pipe is not a terminal
+    assert_eq!(
+        ioctl(cageid, readfd, TIOCGWINSZ, ARG_ADDR),
+        -(Errno::ENOTTY as i32)
+    );
+}
+
+#[test]
+fn test_ioctl_rejects_unknown_requests_and_bad_pointers() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let (readfd, _) = pipe(cageid, base);
+
+    assert_eq!(
+        ioctl(cageid, readfd, FIOASYNC, ARG_ADDR),
+        -(Errno::ENOTTY as i32)
+    );
+    assert_eq!(ioctl(cageid, readfd, FIONREAD, 0), -(Errno::EFAULT as i32));
+    // the last bytes of the argument would be past the cage memory
+    let end = page_addr(MEMORY_PAGES);
+    assert_eq!(
+        ioctl(cageid, readfd, FIONREAD, end - 2),
+        -(Errno::EFAULT as i32)
+    );
+    assert_eq!(ioctl(cageid, readfd, FIONREAD, end - 4), 0);
+}
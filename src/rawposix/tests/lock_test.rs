@@ -0,0 +1,276 @@
+mod common;
+
+use common::{host_path, load, store, test_dir, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{close_syscall, dup_syscall, fcntl_syscall, flock_syscall};
+use std::os::fd::IntoRawFd;
+use std::thread;
+use std::time::Duration;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    FDKIND_KERNEL, F_DUPFD, F_DUPFD_CLOEXEC, F_GETFD, F_GETLK, F_GETOWN, F_RDLCK, F_SETLK,
+    F_SETLKW, F_SETOWN, F_UNLCK, F_WRLCK, LOCK_EX, LOCK_NB, LOCK_SH, LOCK_UN, SEEK_SET,
+};
+use sysdefs::data::fs_struct::FlockStruct;
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 4;
+
+/// Address in the cage memory where the `FlockStruct` of a call is kept
+const FLOCK_ADDR: u64 = 64;
+
+/// Forks a new cage from `parentid` with `MEMORY_PAGES` pages of linear memory. Returns the cage id and
+/// the host base address of the cage memory.
+fn init_test_cage(parentid: u64) -> (u64, *mut u8) {
+    common::init_test_cage(parentid, MEMORY_PAGES)
+}
+
+/// Returns the host path of a new, empty file for a test
+fn test_file() -> String {
+    let path = host_path(&format!("{}/file", test_dir("lock_test")));
+    std::fs::write(&path, b"").unwrap();
+    path
+}
+
+/// Opens `path` on the host for reading and writing, and gives `cageid` a virtual fd for it
+fn open_file(cageid: u64, path: &str) -> u64 {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .unwrap();
+    fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, file.into_raw_fd() as u64, false, 0)
+        .unwrap()
+}
+
+fn fcntl(cageid: u64, fd: u64, cmd: i32, arg: u64) -> i32 {
+    fcntl_syscall(
+        cageid, fd, cageid, cmd as u64, cageid, arg, cageid, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+/// Issues the record lock command `cmd` for the bytes `start..start + len`. Returns the result and
+/// the `FlockStruct` as the call left it.
+fn record_lock(
+    cageid: u64,
+    base: *mut u8,
+    fd: u64,
+    cmd: i32,
+    ltype: i16,
+    start: i64,
+    len: i64,
+) -> (i32, FlockStruct) {
+    store(
+        base,
+        FLOCK_ADDR,
+        FlockStruct {
+            l_type: ltype,
+            l_whence: SEEK_SET as i16,
+            l_start: start,
+            l_len: len,
+            l_pid: 0,
+        },
+    );
+    let ret = fcntl(cageid, fd, cmd, FLOCK_ADDR);
+    (ret, load::<FlockStruct>(base, FLOCK_ADDR))
+}
+
+fn flock(cageid: u64, fd: u64, operation: i32) -> i32 {
+    flock_syscall(
+        cageid,
+        fd,
+        cageid,
+        operation as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn close(cageid: u64, fd: u64) -> i32 {
+    close_syscall(cageid, fd, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0)
+}
+
+#[test]
+fn test_record_locks_conflict_between_cages() {
+    let path = test_file();
+    let (cage_a, base_a) = init_test_cage(INIT_CAGEID);
+    let (cage_b, base_b) = init_test_cage(INIT_CAGEID);
+    let fd_a = open_file(cage_a, &path);
+    let fd_b = open_file(cage_b, &path);
+
+    assert_eq!(
+        record_lock(cage_a, base_a, fd_a, F_SETLK, F_WRLCK, 0, 10).0,
+        0
+    );
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_WRLCK, 5, 10).0,
+        -(Errno::EAGAIN as i32)
+    );
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_RDLCK, 9, 1).0,
+        -(Errno::EAGAIN as i32)
+    );
+
+    // F_GETLK reports the lock in the way, and who holds it
+    let (ret, lock) = record_lock(cage_b, base_b, fd_b, F_GETLK, F_RDLCK, 5, 0);
+    assert_eq!(ret, 0);
+    assert_eq!(lock.l_type, F_WRLCK);
+    assert_eq!((lock.l_start, lock.l_len), (0, 10));
+    assert_eq!(lock.l_pid, cage_a as i32);
+    // the holder itself never conflicts with its own locks
+    let (_, lock) = record_lock(cage_a, base_a, fd_a, F_GETLK, F_WRLCK, 0, 0);
+    assert_eq!(lock.l_type, F_UNLCK);
+
+    // the bytes next to the lock are free, and unlocking the middle of it splits it
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_WRLCK, 10, 10).0,
+        0
+    );
+    assert_eq!(
+        record_lock(cage_a, base_a, fd_a, F_SETLK, F_UNLCK, 3, 4).0,
+        0
+    );
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_RDLCK, 3, 4).0,
+        0
+    );
+    let (_, lock) = record_lock(cage_b, base_b, fd_b, F_GETLK, F_RDLCK, 0, 0);
+    assert_eq!((lock.l_start, lock.l_len), (0, 3));
+
+    // a negative length locks the bytes before the start, which may not be before the file
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_RDLCK, 2, -3).0,
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_RDLCK, 3, -3).0,
+        -(Errno::EAGAIN as i32)
+    );
+
+    // the locks of a cage are gone when it closes any fd of the file
+    let dup_a = dup_syscall(cage_a, fd_a, cage_a, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    assert!(dup_a >= 0);
+    assert_eq!(close(cage_a, dup_a as u64), 0);
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_WRLCK, 0, 0).0,
+        0
+    );
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_record_locks_wait_and_deadlock() {
+    let path = test_file();
+    let (cage_a, base_a) = init_test_cage(INIT_CAGEID);
+    let (cage_b, base_b) = init_test_cage(INIT_CAGEID);
+    let fd_a = open_file(cage_a, &path);
+    let fd_b = open_file(cage_b, &path);
+
+    assert_eq!(
+        record_lock(cage_a, base_a, fd_a, F_SETLK, F_WRLCK, 0, 1).0,
+        0
+    );
+    assert_eq!(
+        record_lock(cage_b, base_b, fd_b, F_SETLK, F_WRLCK, 1, 1).0,
+        0
+    );
+
+    // cage B waits for the byte cage A holds...
+    let base_b = base_b as usize;
+    let waiter = thread::spawn(move || {
+        record_lock(cage_b, base_b as *mut u8, fd_b, F_SETLKW, F_WRLCK, 0, 1).0
+    });
+    thread::sleep(Duration::from_millis(100));
+    assert!(!waiter.is_finished());
+
+    // ...so cage A waiting for the byte of cage B would never end
+    assert_eq!(
+        record_lock(cage_a, base_a, fd_a, F_SETLKW, F_WRLCK, 1, 1).0,
+        -(Errno::EDEADLK as i32)
+    );
+    assert_eq!(
+        record_lock(cage_a, base_a, fd_a, F_SETLK, F_UNLCK, 0, 1).0,
+        0
+    );
+    assert_eq!(waiter.join().unwrap(), 0);
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_flock_between_cages() {
+    let path = test_file();
+    let (cage_a, _) = init_test_cage(INIT_CAGEID);
+    let fd_a = open_file(cage_a, &path);
+    // the child shares the open file description of its parent
+    let (child, _) = init_test_cage(cage_a);
+    let (cage_b, _) = init_test_cage(INIT_CAGEID);
+    let fd_b = open_file(cage_b, &path);
+
+    assert_eq!(flock(cage_a, fd_a, LOCK_EX), 0);
+    assert_eq!(
+        flock(cage_b, fd_b, LOCK_EX | LOCK_NB),
+        -(Errno::EAGAIN as i32)
+    );
+    assert_eq!(
+        flock(cage_b, fd_b, LOCK_SH | LOCK_NB),
+        -(Errno::EAGAIN as i32)
+    );
+    assert_eq!(flock(child, fd_a, LOCK_EX | LOCK_NB), 0);
+
+    // downgrading lets other shared locks in
+    assert_eq!(flock(cage_a, fd_a, LOCK_SH), 0);
+    assert_eq!(flock(cage_b, fd_b, LOCK_SH | LOCK_NB), 0);
+    assert_eq!(flock(cage_a, fd_a, LOCK_UN), 0);
+
+    // the lock goes away with the last fd of the description, not with the first
+    let dup_b = dup_syscall(cage_b, fd_b, cage_b, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    assert!(dup_b >= 0);
+    assert_eq!(close(cage_b, fd_b), 0);
+    assert_eq!(
+        flock(cage_a, fd_a, LOCK_EX | LOCK_NB),
+        -(Errno::EAGAIN as i32)
+    );
+    assert_eq!(close(cage_b, dup_b as u64), 0);
+    assert_eq!(flock(cage_a, fd_a, LOCK_EX | LOCK_NB), 0);
+
+    assert_eq!(
+        flock(cage_a, fd_a, LOCK_SH | LOCK_EX),
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(flock(cage_a, 1000, LOCK_SH), -(Errno::EBADF as i32));
+
+    let _ = std::fs::remove_file(&path);
+}
+
+#[test]
+fn test_fcntl_dupfd_and_owner() {
+    let path = test_file();
+    let (cageid, _) = init_test_cage(INIT_CAGEID);
+    let fd = open_file(cageid, &path);
+
+    assert_eq!(fcntl(cageid, fd, F_DUPFD, 20), 20);
+    assert_eq!(fcntl(cageid, fd, F_DUPFD, 20), 21);
+    assert_eq!(fcntl(cageid, fd, F_GETFD, 0), 0);
+    assert_eq!(fcntl(cageid, fd, F_DUPFD_CLOEXEC, 20), 22);
+    assert_eq!(fcntl(cageid, 22, F_GETFD, 0), 1);
+    assert_eq!(
+        fcntl(cageid, fd, F_DUPFD, u32::MAX as u64),
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(fcntl(cageid, 1000, F_DUPFD, 0), -(Errno::EBADF as i32));
+
+    // the owner belongs to the open file description, which the duplicates share
+    assert_eq!(fcntl(cageid, fd, F_GETOWN, 0), 0);
+    assert_eq!(fcntl(cageid, fd, F_SETOWN, cageid), 0);
+    assert_eq!(fcntl(cageid, 21, F_GETOWN, 0), cageid as i32);
+
+    let _ = std::fs::remove_file(&path);
+}
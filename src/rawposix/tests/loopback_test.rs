@@ -0,0 +1,365 @@
+mod common;
+
+use common::{guest_bytes, init_rawposix, load, store, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{close_syscall, dup_syscall, fcntl_syscall};
+use rawposix::syscalls::loopback::{set_loopback_policy, LoopbackPolicy};
+use rawposix::syscalls::net_calls::{
+    accept_syscall, bind_syscall, connect_syscall, listen_syscall, poll_syscall, recv_syscall,
+    recvfrom_syscall, recvmsg_syscall, send_syscall, sendmsg_syscall, sendto_syscall,
+    socket_syscall, socketpair_syscall,
+};
+use std::sync::Once;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{F_GETFL, F_SETFL, O_NONBLOCK};
+use sysdefs::constants::net_const::{MSG_CTRUNC, POLLHUP, POLLIN, POLLOUT, SCM_RIGHTS, SOL_SOCKET};
+use sysdefs::data::fs_struct::{SockPair, WasmCmsghdr, WasmIovec, WasmMsghdr};
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 4;
+
+static POLICY: Once = Once::new();
+
+/// Forks a new cage from `parentid` with `MEMORY_PAGES` pages of linear memory. Returns the cage id and
+/// the host base address of the cage memory. Every socket of the test binary is created in memory.
+fn init_test_cage(parentid: u64) -> (u64, *mut u8) {
+    POLICY.call_once(|| {
+        init_rawposix();
+        set_loopback_policy(LoopbackPolicy::All);
+    });
+    common::init_test_cage(parentid, MEMORY_PAGES)
+}
+
+fn socket(cageid: u64, domain: i32, socktype: i32) -> u64 {
+    let fd = socket_syscall(
+        cageid,
+        domain as u64,
+        cageid,
+        socktype as u64,
+        cageid,
+        0,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    assert!(fd >= 0);
+    fd as u64
+}
+
+fn socketpair(cageid: u64, base: *mut u8, addr: u64) -> (u64, u64) {
+    assert_eq!(
+        socketpair_syscall(
+            cageid,
+            libc::AF_UNIX as u64,
+            cageid,
+            libc::SOCK_STREAM as u64,
+            cageid,
+            0,
+            cageid,
+            addr,
+            cageid,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    let sv: SockPair = load(base, addr);
+    (sv.sock1 as u64, sv.sock2 as u64)
+}
+
+fn send(cageid: u64, fd: u64, buf: u64, len: usize) -> i32 {
+    send_syscall(
+        cageid, fd, cageid, buf, cageid, len as u64, cageid, 0, cageid, 0, 0, 0, 0,
+    )
+}
+
+fn recv(cageid: u64, fd: u64, buf: u64, len: usize) -> i32 {
+    recv_syscall(
+        cageid, fd, cageid, buf, cageid, len as u64, cageid, 0, cageid, 0, 0, 0, 0,
+    )
+}
+
+/// Polls `fd` without blocking and returns its revents
+fn poll_once(cageid: u64, base: *mut u8, fd: u64, events: i16) -> i16 {
+    store(
+        base,
+        2048,
+        libc::pollfd {
+            fd: fd as i32,
+            events,
+            revents: 0,
+        },
+    );
+    assert!(poll_syscall(cageid, 2048, cageid, 1, cageid, 0, cageid, 0, 0, 0, 0, 0, 0) >= 0);
+    load::<libc::pollfd>(base, 2048).revents
+}
+
+/// Lays out at guest address `addr` a message with one iovec over `buf` and a control buffer of
+/// `controllen` bytes right after the header. Returns the guest address of the control buffer.
+fn store_msghdr(base: *mut u8, addr: u64, buf: u32, buflen: u32, controllen: u32) -> u64 {
+    let iov = addr + std::mem::size_of::<WasmMsghdr>() as u64;
+    let control = iov + std::mem::size_of::<WasmIovec>() as u64;
+    store(
+        base,
+        iov,
+        WasmIovec {
+            iov_base: buf,
+            iov_len: buflen,
+        },
+    );
+    store(
+        base,
+        addr,
+        WasmMsghdr {
+            msg_iov: iov as u32,
+            msg_iovlen: 1,
+            msg_control: control as u32,
+            msg_controllen: controllen,
+            ..Default::default()
+        },
+    );
+    control
+}
+
+/// Stores a SCM_RIGHTS control message carrying `fd` at guest address `addr`
+fn store_rights(base: *mut u8, addr: u64, fd: i32) {
+    let hdrlen = std::mem::size_of::<WasmCmsghdr>() as u32;
+    store(
+        base,
+        addr,
+        WasmCmsghdr {
+            cmsg_len: hdrlen + 4,
+            cmsg_level: SOL_SOCKET,
+            cmsg_type: SCM_RIGHTS,
+        },
+    );
+    store(base, addr + hdrlen as u64, fd);
+}
+
+fn sendmsg(cageid: u64, fd: u64, msg: u64) -> i32 {
+    sendmsg_syscall(cageid, fd, cageid, msg, cageid, 0, cageid, 0, 0, 0, 0, 0, 0)
+}
+
+fn recvmsg(cageid: u64, fd: u64, msg: u64) -> i32 {
+    recvmsg_syscall(cageid, fd, cageid, msg, cageid, 0, cageid, 0, 0, 0, 0, 0, 0)
+}
+
+fn close(cageid: u64, fd: u64) {
+    assert_eq!(
+        close_syscall(cageid, fd, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+}
+
+#[test]
+fn test_stream_between_cages() {
+    let (server, server_base) = init_test_cage(INIT_CAGEID);
+    let (client, client_base) = init_test_cage(INIT_CAGEID);
+
+    let listener = socket(server, libc::AF_INET, libc::SOCK_STREAM);
+    let mut sin: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    sin.sin_family = libc::AF_INET as u16;
+    sin.sin_port = 8080u16.to_be();
+    sin.sin_addr.s_addr = u32::from_ne_bytes([127, 0, 0, 1]);
+    store(server_base, 32, sin);
+    store(client_base, 32, sin);
+    assert_eq!(
+        bind_syscall(server, listener, server, 32, server, 16, server, 0, 0, 0, 0, 0, 0),
+        0
+    );
+
+    // nobody listens yet
+    let conn = socket(client, libc::AF_INET, libc::SOCK_STREAM);
+    assert_eq!(
+        connect_syscall(client, conn, client, 32, client, 16, client, 0, 0, 0, 0, 0, 0),
+        -(Errno::ECONNREFUSED as i32)
+    );
+    assert_eq!(
+        listen_syscall(server, listener, server, 8, server, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(
+        connect_syscall(client, conn, client, 32, client, 16, client, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(poll_once(server, server_base, listener, POLLIN), POLLIN);
+
+    // the accepted socket reports the client's address
+    store(server_base, 128, 16u32);
+    let accepted = accept_syscall(
+        server, listener, server, 64, server, 128, server, 0, 0, 0, 0, 0, 0,
+    );
+    assert!(accepted >= 0);
+    let peer: libc::sockaddr_in = load(server_base, 64);
+    assert_eq!(peer.sin_addr.s_addr, u32::from_ne_bytes([127, 0, 0, 1]));
+    assert_ne!(peer.sin_port, 0);
+
+    store(client_base, 256, *b"hello");
+    assert_eq!(send(client, conn, 256, 5), 5);
+    assert_eq!(
+        poll_once(server, server_base, accepted as u64, POLLIN | POLLOUT),
+        POLLIN | POLLOUT
+    );
+    assert_eq!(recv(server, accepted as u64, 256, 16), 5);
+    assert_eq!(guest_bytes(server_base, 256, 5), b"hello");
+
+    // a duplicate refers to the same socket, which stays open until both are closed
+    let dupfd = dup_syscall(client, conn, client, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    assert!(dupfd >= 0);
+    close(client, conn);
+    assert_eq!(send(client, dupfd as u64, 256, 5), 5);
+    close(client, dupfd as u64);
+
+    // the server reads what was sent before the close, then end of file
+    assert_eq!(recv(server, accepted as u64, 512, 16), 5);
+    assert_eq!(recv(server, accepted as u64, 512, 16), 0);
+    assert_ne!(
+        poll_once(server, server_base, accepted as u64, POLLIN) & POLLHUP,
+        0
+    );
+    close(server, accepted as u64);
+    close(server, listener);
+}
+
+#[test]
+fn test_unix_datagrams_and_nonblocking() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let receiver = socket(cageid, libc::AF_UNIX, libc::SOCK_DGRAM);
+    let sender = socket(cageid, libc::AF_UNIX, libc::SOCK_DGRAM);
+
+    let mut sun: libc::sockaddr_un = unsafe { std::mem::zeroed() };
+    sun.sun_family = libc::AF_UNIX as u16;
+    for (dst, src) in sun.sun_path.iter_mut().zip(b"/tmp/receiver") {
+        *dst = *src as libc::c_char;
+    }
+    store(base, 128, sun);
+    let sunlen = std::mem::size_of::<libc::sockaddr_un>() as u64;
+    assert_eq!(
+        bind_syscall(cageid, receiver, cageid, 128, cageid, sunlen, cageid, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(
+        bind_syscall(cageid, sender, cageid, 128, cageid, sunlen, cageid, 0, 0, 0, 0, 0, 0),
+        -(Errno::EADDRINUSE as i32)
+    );
+
+    store(base, 1024, *b"ping");
+    assert_eq!(
+        sendto_syscall(
+            cageid, sender, cageid, 1024, cageid, 4, cageid, 0, cageid, 128, cageid, sunlen, cageid
+        ),
+        4
+    );
+
+    // the sender isn't bound, so its address is just the family
+    store(base, 512, sunlen as u32);
+    assert_eq!(
+        recvfrom_syscall(
+            cageid, receiver, cageid, 1536, cageid, 16, cageid, 0, cageid, 256, cageid, 512, cageid
+        ),
+        4
+    );
+    assert_eq!(guest_bytes(base, 1536, 4), b"ping");
+    assert_eq!(load::<u32>(base, 512), 2);
+    assert_eq!(load::<u16>(base, 256), libc::AF_UNIX as u16);
+
+    // once non-blocking, reading an empty socket fails instead of waiting
+    assert_eq!(
+        fcntl_syscall(
+            cageid,
+            receiver,
+            cageid,
+            F_SETFL as u64,
+            cageid,
+            O_NONBLOCK as u64,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    assert_eq!(
+        fcntl_syscall(
+            cageid,
+            receiver,
+            cageid,
+            F_GETFL as u64,
+            cageid,
+            0,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ) & O_NONBLOCK,
+        O_NONBLOCK
+    );
+    assert_eq!(recv(cageid, receiver, 1536, 16), -(Errno::EAGAIN as i32));
+    assert_eq!(poll_once(cageid, base, receiver, POLLIN | POLLOUT), POLLOUT);
+
+    close(cageid, sender);
+    close(cageid, receiver);
+}
+
+#[test]
+fn test_scm_rights_between_cages() {
+    let (sender, sender_base) = init_test_cage(INIT_CAGEID);
+    let (sock1, sock2) = socketpair(sender, sender_base, 8);
+    let (receiver, receiver_base) = init_test_cage(sender);
+    // the fd handed over is an in-memory socket too, whose peer stays in the sender
+    let (passed, peer) = socketpair(sender, sender_base, 16);
+
+    // two messages carrying fds, the sender closing its own fd before the receiver gets it
+    store(sender_base, 64, *b"hello");
+    let control = store_msghdr(sender_base, 128, 64, 5, 16);
+    store_rights(sender_base, control, passed as i32);
+    assert_eq!(sendmsg(sender, sock1, 128), 5);
+    assert_eq!(sendmsg(sender, sock1, 128), 5);
+    close(sender, passed);
+
+    // a read stops at the next message carrying fds
+    let control = store_msghdr(receiver_base, 128, 64, 16, 32);
+    assert_eq!(recvmsg(receiver, sock2, 128), 5);
+    assert_eq!(guest_bytes(receiver_base, 64, 5), b"hello");
+    let msg: WasmMsghdr = load(receiver_base, 128);
+    assert_eq!((msg.msg_controllen, msg.msg_flags), (16, 0));
+    let cmsg: WasmCmsghdr = load(receiver_base, control);
+    assert_eq!(
+        (cmsg.cmsg_len, cmsg.cmsg_level, cmsg.cmsg_type),
+        (16, SOL_SOCKET, SCM_RIGHTS)
+    );
+
+    // the receiver gets a virtual fd of its own that still reaches the sender's peer
+    let received: i32 = load(receiver_base, control + 12);
+    assert!(received >= 0);
+    assert_eq!(send(receiver, received as u64, 64, 5), 5);
+    assert_eq!(recv(sender, peer, 256, 16), 5);
+    assert_eq!(guest_bytes(sender_base, 256, 5), b"hello");
+
+    // descriptors that do not fit in the control buffer are dropped and reported as truncated
+    store_msghdr(receiver_base, 128, 64, 16, 12);
+    assert_eq!(recvmsg(receiver, sock2, 128), 5);
+    let msg: WasmMsghdr = load(receiver_base, 128);
+    assert_eq!(msg.msg_controllen, 0);
+    assert_eq!(msg.msg_flags & MSG_CTRUNC, MSG_CTRUNC);
+
+    // only open virtual fds of the sender can be passed
+    store_rights(sender_base, control, 900);
+    assert_eq!(sendmsg(sender, sock1, 128), -(Errno::EBADF as i32));
+
+    close(receiver, received as u64);
+    close(receiver, sock2);
+    close(sender, sock1);
+    close(sender, peer);
+}
@@ -0,0 +1,464 @@
+mod common;
+
+use cage::get_cage;
+use cage::memory::memlimits::{
+    get_total_committed_pages, get_total_memory_limit, set_total_memory_limit,
+};
+use common::{fork_cage, host_path, init_rawposix, load, store, test_dir, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{
+    brk_syscall, mmap_syscall, mremap_syscall, munmap_syscall, sbrk_syscall,
+};
+use rawposix::syscalls::sys_calls::{exec_syscall, getrlimit_syscall, setrlimit_syscall};
+use std::os::fd::IntoRawFd;
+use std::sync::Mutex;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    FDKIND_KERNEL, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MREMAP_MAYMOVE, PAGESHIFT, PAGESIZE,
+    PROT_READ, PROT_WRITE,
+};
+use sysdefs::constants::sys_const::{RLIMIT_AS, RLIMIT_DATA, RLIM_INFINITY};
+use sysdefs::data::fs_struct::Rlimit;
+
+/// Pages of linear memory reserved for every test cage, inaccessible until mapped
+const RESERVED_PAGES: u32 = 64;
+/// Pages of the heap at the start of the linear memory, readable and writable
+const MEMORY_PAGES: u32 = 4;
+
+/// Address in the cage memory where the `Rlimit` of a call is kept
+const RLIMIT_ADDR: u64 = 64;
+
+// The global memory limit and count are shared by all tests, so they must not run concurrently
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+    init_rawposix();
+    SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Forks a new cage from `parentid`, whose linear memory is a host mapping of `RESERVED_PAGES` pages.
+/// Only the heap, the first `MEMORY_PAGES` pages, is mapped, and the cage can't map pages beyond the
+/// reservation. Returns the cage id and the host base address of the cage memory.
+fn init_test_cage(parentid: u64) -> (u64, *mut u8) {
+    let cageid = fork_cage(parentid);
+    (cageid, init_memory(cageid))
+}
+
+/// Gives the cage new linear memory with only the heap mapped, returns its host base address
+fn init_memory(cageid: u64) -> *mut u8 {
+    common::init_memory(cageid, RESERVED_PAGES, MEMORY_PAGES)
+}
+
+/// Returns the host path of a new file of `npages` pages
+fn test_file(npages: u32) -> String {
+    let path = host_path(&format!("{}/file", test_dir("memlimit_test")));
+    std::fs::write(&path, vec![0u8; (npages * PAGESIZE) as usize]).unwrap();
+    path
+}
+
+/// Opens `path` on the host read-only, and gives `cageid` a virtual fd for it
+fn open_read_only(cageid: u64, path: &str) -> u64 {
+    let file = std::fs::File::open(path).unwrap();
+    fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, file.into_raw_fd() as u64, false, 0)
+        .unwrap()
+}
+
+/// Returns the (anonymous, file, shared) pages committed by the cage
+fn committed(cageid: u64) -> (u32, u32, u32) {
+    let cage = get_cage(cageid).unwrap();
+    let vmmap = cage.vmmap.read();
+    vmmap.check_consistency().unwrap();
+    (
+        vmmap.committed.anonymous(),
+        vmmap.committed.file(),
+        vmmap.committed.shared(),
+    )
+}
+
+fn page_addr(page_num: u32) -> u64 {
+    (page_num << PAGESHIFT) as u64
+}
+
+fn mmap(cageid: u64, addr: u64, npages: u32, prot: i32, flags: u32, fd: i64) -> i32 {
+    mmap_syscall(
+        cageid,
+        addr,
+        cageid,
+        page_addr(npages),
+        cageid,
+        prot as u64,
+        cageid,
+        flags as u64,
+        cageid,
+        fd as u64,
+        cageid,
+        0,
+        cageid,
+    )
+}
+
+fn mmap_anonymous(cageid: u64, npages: u32) -> i32 {
+    mmap(
+        cageid,
+        0,
+        npages,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS,
+        -1,
+    )
+}
+
+fn munmap(cageid: u64, addr: u64, npages: u32) -> i32 {
+    munmap_syscall(
+        cageid,
+        addr,
+        cageid,
+        page_addr(npages),
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn brk(cageid: u64, page_num: u32) -> i32 {
+    brk_syscall(
+        cageid,
+        page_addr(page_num),
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn sbrk(cageid: u64, increment: i32) -> i32 {
+    sbrk_syscall(
+        cageid,
+        increment as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn mremap(cageid: u64, old_addr: u64, old_npages: u32, new_npages: u32, flags: u32) -> i32 {
+    mremap_syscall(
+        cageid,
+        old_addr,
+        cageid,
+        page_addr(old_npages),
+        cageid,
+        page_addr(new_npages),
+        cageid,
+        flags as u64,
+        cageid,
+        0,
+        cageid,
+        0,
+        0,
+    )
+}
+
+/// Returns the (soft, hard) limits of `resource`, or the negative errno of the call
+fn getrlimit(cageid: u64, base: *mut u8, resource: u64) -> Result<(u64, u64), i32> {
+    let ret = getrlimit_syscall(
+        cageid,
+        resource,
+        cageid,
+        RLIMIT_ADDR,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    if ret < 0 {
+        return Err(ret);
+    }
+    let rlim = load::<Rlimit>(base, RLIMIT_ADDR);
+    Ok((rlim.rlim_cur, rlim.rlim_max))
+}
+
+fn setrlimit(cageid: u64, base: *mut u8, resource: u64, soft: u64, hard: u64) -> i32 {
+    store(
+        base,
+        RLIMIT_ADDR,
+        Rlimit {
+            rlim_cur: soft,
+            rlim_max: hard,
+        },
+    );
+    setrlimit_syscall(
+        cageid,
+        resource,
+        cageid,
+        RLIMIT_ADDR,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+#[test]
+fn test_committed_pages_by_backing() {
+    let _guard = setup();
+    let total = get_total_committed_pages();
+    let (cageid, _) = init_test_cage(INIT_CAGEID);
+    assert_eq!(committed(cageid), (MEMORY_PAGES, 0, 0));
+    assert_eq!(get_total_committed_pages(), total + MEMORY_PAGES as u64);
+
+    let anonymous = mmap_anonymous(cageid, 3);
+    assert!(anonymous > 0);
+    let path = test_file(2);
+    let fd = open_read_only(cageid, &path);
+    let file = mmap(cageid, 0, 2, PROT_READ, MAP_PRIVATE, fd as i64);
+    assert!(file > 0);
+    assert_eq!(committed(cageid), (MEMORY_PAGES + 3, 2, 0));
+
+    // mapping over existing pages replaces them
+    assert_eq!(
+        mmap(
+            cageid,
+            file as u64,
+            1,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        file
+    );
+    assert_eq!(committed(cageid), (MEMORY_PAGES + 4, 1, 0));
+
+    assert_eq!(munmap(cageid, anonymous as u64, 3), 0);
+    assert_eq!(brk(cageid, MEMORY_PAGES - 1), 0);
+    assert_eq!(committed(cageid), (MEMORY_PAGES, 1, 0));
+    assert_eq!(get_total_committed_pages(), total + MEMORY_PAGES as u64 + 1);
+
+    // a forked child commits the pages of its parent too, its heap is then mapped again
+    let total = get_total_committed_pages();
+    let (childid, _) = init_test_cage(cageid);
+    assert_eq!(committed(childid), (MEMORY_PAGES + 1, 1, 0));
+    assert_eq!(get_total_committed_pages(), total + MEMORY_PAGES as u64 + 2);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_address_space_limit() {
+    let _guard = setup();
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    assert_eq!(
+        getrlimit(cageid, base, RLIMIT_AS),
+        Ok((RLIM_INFINITY, RLIM_INFINITY))
+    );
+
+    let limit = page_addr(MEMORY_PAGES + 4);
+    assert_eq!(setrlimit(cageid, base, RLIMIT_AS, limit, limit), 0);
+    let addr = mmap_anonymous(cageid, 3);
+    assert!(addr > 0);
+    assert_eq!(mmap_anonymous(cageid, 2), -(Errno::ENOMEM as i32));
+
+    // file mappings count too
+    let path = test_file(2);
+    let fd = open_read_only(cageid, &path);
+    assert_eq!(
+        mmap(cageid, 0, 2, PROT_READ, MAP_PRIVATE, fd as i64),
+        -(Errno::ENOMEM as i32)
+    );
+    assert!(mmap(cageid, 0, 1, PROT_READ, MAP_PRIVATE, fd as i64) > 0);
+
+    // replacing pages commits no more of them
+    assert_eq!(
+        mmap(
+            cageid,
+            addr as u64,
+            3,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        addr
+    );
+
+    // the heap can't grow into the limit either
+    assert_eq!(brk(cageid, MEMORY_PAGES + 1), -(Errno::ENOMEM as i32));
+    assert_eq!(munmap(cageid, addr as u64, 1), 0);
+    assert_eq!(brk(cageid, MEMORY_PAGES + 1), 0);
+    assert_eq!(committed(cageid), (MEMORY_PAGES + 3, 1, 0));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_data_limit() {
+    let _guard = setup();
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+
+    let limit = page_addr(MEMORY_PAGES + 1);
+    assert_eq!(
+        setrlimit(cageid, base, RLIMIT_DATA, limit, RLIM_INFINITY),
+        0
+    );
+    assert_eq!(
+        sbrk(cageid, PAGESIZE as i32),
+        page_addr(MEMORY_PAGES) as i32
+    );
+    assert_eq!(sbrk(cageid, 1), -(Errno::ENOMEM as i32));
+    assert_eq!(brk(cageid, MEMORY_PAGES + 2), -(Errno::ENOMEM as i32));
+    assert_eq!(mmap_anonymous(cageid, 1), -(Errno::ENOMEM as i32));
+
+    // file mappings are not data
+    let path = test_file(2);
+    let fd = open_read_only(cageid, &path);
+    assert!(mmap(cageid, 0, 2, PROT_READ, MAP_PRIVATE, fd as i64) > 0);
+
+    // shrinking the heap makes room for an anonymous mapping
+    assert_eq!(
+        sbrk(cageid, -(PAGESIZE as i32)),
+        page_addr(MEMORY_PAGES + 1) as i32
+    );
+    assert!(mmap_anonymous(cageid, 1) > 0);
+    assert_eq!(committed(cageid), (MEMORY_PAGES + 1, 2, 0));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_mremap_within_limits() {
+    let _guard = setup();
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+
+    let limit = page_addr(MEMORY_PAGES + 2);
+    assert_eq!(setrlimit(cageid, base, RLIMIT_AS, limit, limit), 0);
+    let addr = mmap(
+        cageid,
+        page_addr(32),
+        1,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+        -1,
+    );
+    assert_eq!(addr, page_addr(32) as i32);
+
+    // grown in place
+    assert_eq!(mremap(cageid, addr as u64, 1, 2, 0), addr);
+    assert_eq!(
+        mremap(cageid, addr as u64, 2, 3, MREMAP_MAYMOVE),
+        -(Errno::ENOMEM as i32)
+    );
+    assert_eq!(
+        mmap(
+            cageid,
+            page_addr(34),
+            1,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        -(Errno::ENOMEM as i32)
+    );
+    assert_eq!(munmap(cageid, addr as u64, 2), 0);
+
+    // moved as it can't grow past the end of the memory, only the pages beyond the old length are new.
+    // A moved mapping is placed like a new one, at the top of the first free gap that is large
+    // enough, so it ends right below the old page.
+    let addr = mmap(
+        cageid,
+        page_addr(RESERVED_PAGES - 1),
+        1,
+        PROT_READ | PROT_WRITE,
+        MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+        -1,
+    );
+    assert_eq!(addr, page_addr(RESERVED_PAGES - 1) as i32);
+    assert_eq!(
+        mremap(cageid, addr as u64, 1, 2, MREMAP_MAYMOVE),
+        page_addr(RESERVED_PAGES - 3) as i32
+    );
+    assert_eq!(committed(cageid), (MEMORY_PAGES + 2, 0, 0));
+}
+
+#[test]
+fn test_memory_limits_inherited() {
+    let _guard = setup();
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+
+    assert_eq!(
+        setrlimit(cageid, base, RLIMIT_AS, 2 << 20, 1 << 20),
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(setrlimit(cageid, base, RLIMIT_AS, 1 << 20, 4 << 20), 0);
+    assert_eq!(setrlimit(cageid, base, RLIMIT_DATA, 1 << 20, 2 << 20), 0);
+    assert_eq!(
+        setrlimit(cageid, base, RLIMIT_DATA, 1 << 20, RLIM_INFINITY),
+        -(Errno::EPERM as i32)
+    );
+
+    // a forked child starts with the limits of its parent
+    let (childid, childbase) = init_test_cage(cageid);
+    assert_eq!(
+        getrlimit(childid, childbase, RLIMIT_AS),
+        Ok((1 << 20, 4 << 20))
+    );
+    assert_eq!(
+        getrlimit(childid, childbase, RLIMIT_DATA),
+        Ok((1 << 20, 2 << 20))
+    );
+
+    // and exec keeps them
+    assert_eq!(exec_syscall(childid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0), 0);
+    let childbase = init_memory(childid);
+    assert_eq!(
+        getrlimit(childid, childbase, RLIMIT_AS),
+        Ok((1 << 20, 4 << 20))
+    );
+    assert_eq!(committed(childid), (MEMORY_PAGES, 0, 0));
+}
+
+#[test]
+fn test_total_memory_limit() {
+    let _guard = setup();
+    let (cageid, _) = init_test_cage(INIT_CAGEID);
+    let (otherid, _) = init_test_cage(INIT_CAGEID);
+
+    let old_limit = get_total_memory_limit();
+    set_total_memory_limit(page_addr(2) + (get_total_committed_pages() << PAGESHIFT));
+    let addr = mmap_anonymous(cageid, 2);
+    assert!(addr > 0);
+    assert_eq!(mmap_anonymous(otherid, 1), -(Errno::ENOMEM as i32));
+    assert_eq!(brk(otherid, MEMORY_PAGES + 1), -(Errno::ENOMEM as i32));
+
+    // pages released by one cage can be mapped by another
+    assert_eq!(munmap(cageid, addr as u64, 1), 0);
+    assert!(mmap_anonymous(otherid, 1) > 0);
+    assert_eq!(mmap_anonymous(cageid, 1), -(Errno::ENOMEM as i32));
+    set_total_memory_limit(old_limit);
+    assert!(mmap_anonymous(cageid, 1) > 0);
+}
@@ -0,0 +1,722 @@
+mod common;
+
+use cage::get_cage;
+use cage::memory::mem_helper::{fork_vmmap_helper, init_vmmap_helper};
+use cage::memory::vmmap::{VmmapEntry, VmmapOps};
+use common::{
+    fork_cage, host_path, load, map_pages, page_addr, reserve_memory, store, test_dir, INIT_CAGEID,
+};
+use rawposix::syscalls::fs_calls::{
+    brk_syscall, mmap_syscall, mprotect_syscall, mremap_syscall, munmap_syscall,
+};
+use std::os::fd::IntoRawFd;
+use std::sync::Arc;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    FDKIND_KERNEL, MAP_ANONYMOUS, MAP_FIXED, MAP_PRIVATE, MAP_SHARED, MREMAP_FIXED, MREMAP_MAYMOVE,
+    PAGESHIFT, PAGESIZE, PROT_EXEC, PROT_NONE, PROT_READ, PROT_WRITE,
+};
+
+/// Pages of linear memory reserved for every test cage, inaccessible until mapped
+const RESERVED_PAGES: u32 = 64;
+/// Pages at the start of the linear memory that are readable and writable
+const MEMORY_PAGES: u32 = 4;
+
+/// Forks a new cage from the init cage, whose linear memory is a host mapping of `RESERVED_PAGES`
+/// pages. Only the first `MEMORY_PAGES` pages are mapped, and the cage can't map pages beyond the
+/// reservation. Unlike `common::init_memory`, no program break is set, so the tests are free to
+/// split the first pages with mmap and mprotect. Returns the cage id and the host base address of
+/// the cage memory.
+fn init_test_cage() -> (u64, *mut u8) {
+    let cageid = fork_cage(INIT_CAGEID);
+    let base = reserve_memory(RESERVED_PAGES);
+    init_vmmap_helper(cageid, base as usize, None);
+    get_cage(cageid).unwrap().vmmap.write().end_address = RESERVED_PAGES;
+    map_pages(cageid, base, 0, MEMORY_PAGES, PROT_READ | PROT_WRITE);
+    (cageid, base)
+}
+
+/// Forks `parentid` like wasmtime does: the child gets new linear memory, and the memory of the
+/// parent is copied into it. Returns the cage id and the host base address of the child memory.
+fn fork(parentid: u64) -> (u64, *mut u8) {
+    let childid = fork_cage(parentid);
+    let base = reserve_memory(RESERVED_PAGES);
+    init_vmmap_helper(childid, base as usize, None);
+    fork_vmmap_helper(parentid, childid);
+    (childid, base)
+}
+
+/// Returns the byte at user address `addr` of the cage memory at `base`
+fn read(base: *mut u8, addr: u64) -> u8 {
+    load(base, addr)
+}
+
+fn write(base: *mut u8, addr: u64, value: u8) {
+    store(base, addr, value)
+}
+
+/// Returns the path of a new host file of `npages` pages, whose page `i` is filled with byte `i`
+fn test_file(npages: u32) -> String {
+    let path = host_path(&format!("{}/file", test_dir("mm_test")));
+    let contents: Vec<u8> = (0..npages)
+        .flat_map(|i| std::iter::repeat_n(i as u8, PAGESIZE as usize))
+        .collect();
+    std::fs::write(&path, contents).unwrap();
+    path
+}
+
+/// Opens `path` on the host read-only, and gives `cageid` a virtual fd for it
+fn open_read_only(cageid: u64, path: &str) -> u64 {
+    let file = std::fs::File::open(path).unwrap();
+    fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, file.into_raw_fd() as u64, false, 0)
+        .unwrap()
+}
+
+/// Returns the entry mapping `page_num` in the vmmap of the cage
+fn find_page(cageid: u64, page_num: u32) -> Option<VmmapEntry> {
+    get_cage(cageid)
+        .unwrap()
+        .vmmap
+        .read()
+        .find_page(page_num)
+        .cloned()
+}
+
+/// Checks that the vmmap of the cage is consistent, printing it if not
+fn check_vmmap(cageid: u64) {
+    let cage = get_cage(cageid).unwrap();
+    let vmmap = cage.vmmap.read();
+    if let Err(error) = vmmap.check_consistency() {
+        vmmap.debug();
+        panic!("inconsistent vmmap: {}", error);
+    }
+}
+
+/// Returns whether each of the `npages` pages of the cage memory at `base` from page `page_num` on is
+/// resident in host memory
+fn resident_pages(base: *mut u8, page_num: u32, npages: u32) -> Vec<bool> {
+    let mut pages = vec![0u8; npages as usize];
+    let ret = unsafe {
+        libc::mincore(
+            base.add(page_addr(page_num) as usize) as *mut libc::c_void,
+            page_addr(npages) as usize,
+            pages.as_mut_ptr(),
+        )
+    };
+    assert_eq!(ret, 0);
+    pages.iter().map(|page| page & 1 != 0).collect()
+}
+
+fn mmap(cageid: u64, addr: u64, npages: u32, prot: i32, flags: u32, fd: i64) -> i32 {
+    let ret = mmap_syscall(
+        cageid,
+        addr,
+        cageid,
+        page_addr(npages),
+        cageid,
+        prot as u64,
+        cageid,
+        flags as u64,
+        cageid,
+        fd as u64,
+        cageid,
+        0,
+        cageid,
+    );
+    check_vmmap(cageid);
+    ret
+}
+
+fn munmap(cageid: u64, addr: u64, len: u64) -> i32 {
+    let ret = munmap_syscall(cageid, addr, cageid, len, cageid, 0, 0, 0, 0, 0, 0, 0, 0);
+    check_vmmap(cageid);
+    ret
+}
+
+fn brk(cageid: u64, brk: u64) -> i32 {
+    let ret = brk_syscall(cageid, brk, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    check_vmmap(cageid);
+    ret
+}
+
+fn mprotect(cageid: u64, addr: u64, len: u64, prot: i32) -> i32 {
+    let ret = mprotect_syscall(
+        cageid,
+        addr,
+        cageid,
+        len,
+        cageid,
+        prot as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    check_vmmap(cageid);
+    ret
+}
+
+fn mremap(
+    cageid: u64,
+    old_addr: u64,
+    old_len: u64,
+    new_len: u64,
+    flags: u32,
+    new_addr: u64,
+) -> i32 {
+    let ret = mremap_syscall(
+        cageid,
+        old_addr,
+        cageid,
+        old_len,
+        cageid,
+        new_len,
+        cageid,
+        flags as u64,
+        cageid,
+        new_addr,
+        cageid,
+        0,
+        0,
+    );
+    check_vmmap(cageid);
+    ret
+}
+
+#[test]
+fn test_mprotect_splits_entries() {
+    let (cageid, base) = init_test_cage();
+
+    assert_eq!(mprotect(cageid, page_addr(1), 1, PROT_READ), 0);
+    let entry = find_page(cageid, 0).unwrap();
+    assert_eq!(
+        (entry.page_num, entry.npages, entry.prot),
+        (0, 1, PROT_READ | PROT_WRITE)
+    );
+    let entry = find_page(cageid, 1).unwrap();
+    assert_eq!(
+        (entry.page_num, entry.npages, entry.prot),
+        (1, 1, PROT_READ)
+    );
+    let entry = find_page(cageid, 2).unwrap();
+    assert_eq!(
+        (entry.page_num, entry.npages, entry.prot),
+        (2, 2, PROT_READ | PROT_WRITE)
+    );
+    write(base, 0, 1);
+    write(base, page_addr(3), 1);
+
+    // a range over several entries changes all of them
+    assert_eq!(mprotect(cageid, 0, page_addr(3), PROT_NONE), 0);
+    for page_num in 0..3 {
+        assert_eq!(find_page(cageid, page_num).unwrap().prot, PROT_NONE);
+    }
+    assert_eq!(find_page(cageid, 3).unwrap().prot, PROT_READ | PROT_WRITE);
+    assert_eq!(find_page(cageid, 3).unwrap().page_num, 3);
+
+    assert_eq!(
+        mprotect(cageid, 0, page_addr(MEMORY_PAGES), PROT_READ | PROT_WRITE),
+        0
+    );
+    write(base, page_addr(1), 1);
+}
+
+#[test]
+fn test_mprotect_errors() {
+    let (cageid, _base) = init_test_cage();
+
+    assert_eq!(mprotect(cageid, 1, 1, PROT_READ), -(Errno::EINVAL as i32));
+    assert_eq!(
+        mprotect(cageid, 0, 1, PROT_READ | PROT_EXEC),
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(mprotect(cageid, 0, 1, 0x10), -(Errno::EINVAL as i32));
+    assert_eq!(mprotect(cageid, page_addr(MEMORY_PAGES), 0, PROT_READ), 0);
+
+    // the range must be fully mapped
+    assert_eq!(
+        mprotect(cageid, page_addr(MEMORY_PAGES), 1, PROT_READ),
+        -(Errno::ENOMEM as i32)
+    );
+    assert_eq!(
+        mprotect(cageid, page_addr(MEMORY_PAGES - 1), page_addr(2), PROT_READ),
+        -(Errno::ENOMEM as i32)
+    );
+    assert_eq!(
+        mprotect(cageid, page_addr(RESERVED_PAGES), 1, PROT_READ),
+        -(Errno::ENOMEM as i32)
+    );
+    assert_eq!(
+        find_page(cageid, MEMORY_PAGES - 1).unwrap().prot,
+        PROT_READ | PROT_WRITE
+    );
+}
+
+#[test]
+fn test_mprotect_maxprot() {
+    let (cageid, base) = init_test_cage();
+    let path = test_file(2);
+    let fd = open_read_only(cageid, &path);
+
+    // a shared mapping of a file opened read-only can't become writable
+    let addr = mmap(cageid, 0, 2, PROT_READ, MAP_SHARED, fd as i64);
+    assert!(addr > 0);
+    assert_eq!(
+        find_page(cageid, addr as u32 >> PAGESHIFT).unwrap().maxprot,
+        PROT_READ
+    );
+    assert_eq!(
+        mprotect(cageid, addr as u64, page_addr(2), PROT_READ | PROT_WRITE),
+        -(Errno::EACCES as i32)
+    );
+    assert_eq!(
+        find_page(cageid, addr as u32 >> PAGESHIFT).unwrap().prot,
+        PROT_READ
+    );
+
+    // a private mapping of the same file can
+    let addr = mmap(cageid, 0, 2, PROT_READ, MAP_PRIVATE, fd as i64);
+    assert!(addr > 0);
+    assert_eq!(
+        mprotect(cageid, addr as u64, page_addr(2), PROT_READ | PROT_WRITE),
+        0
+    );
+    let page = addr as u64 + PAGESIZE as u64;
+    assert_eq!(read(base, page), 1);
+    write(base, page, 7);
+    assert_eq!(std::fs::read(&path).unwrap()[PAGESIZE as usize], 1);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_mremap_resizes_in_place() {
+    let (cageid, base) = init_test_cage();
+    let addr = page_addr(16);
+    assert_eq!(
+        mmap(
+            cageid,
+            addr,
+            2,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        addr as i32
+    );
+    write(base, addr, 42);
+
+    assert_eq!(
+        mremap(cageid, addr, page_addr(2), page_addr(4), 0, 0),
+        addr as i32
+    );
+    let entry = find_page(cageid, 19).unwrap();
+    assert_eq!((entry.page_num, entry.npages), (16, 4));
+    assert_eq!(read(base, addr), 42);
+    write(base, page_addr(19), 1);
+
+    assert_eq!(mremap(cageid, addr, page_addr(4), 1, 0, 0), addr as i32);
+    assert_eq!(find_page(cageid, 16).unwrap().npages, 1);
+    assert!(find_page(cageid, 17).is_none());
+    assert_eq!(read(base, addr), 42);
+
+    // the pages after the mapping are taken, it can't grow without moving
+    assert_eq!(
+        mmap(
+            cageid,
+            page_addr(17),
+            1,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        page_addr(17) as i32
+    );
+    assert_eq!(
+        mremap(cageid, addr, page_addr(1), page_addr(2), 0, 0),
+        -(Errno::ENOMEM as i32)
+    );
+    assert_eq!(find_page(cageid, 16).unwrap().npages, 1);
+}
+
+#[test]
+fn test_mremap_grows_file_mapping() {
+    let (cageid, base) = init_test_cage();
+    let path = test_file(4);
+    let fd = open_read_only(cageid, &path);
+
+    let addr = page_addr(16);
+    assert_eq!(
+        mmap(
+            cageid,
+            addr,
+            2,
+            PROT_READ,
+            MAP_PRIVATE | MAP_FIXED,
+            fd as i64
+        ),
+        addr as i32
+    );
+    assert_eq!(
+        mremap(cageid, addr, page_addr(2), page_addr(4), 0, 0),
+        addr as i32
+    );
+
+    // the new pages continue the file where the mapping ended
+    for page_num in 0..4 {
+        assert_eq!(read(base, page_addr(16 + page_num)), page_num as u8);
+    }
+    let entry = find_page(cageid, 16).unwrap();
+    assert_eq!((entry.npages, entry.file_offset), (4, 0));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_mremap_moves_mapping() {
+    let (cageid, base) = init_test_cage();
+    let addr = page_addr(16);
+    assert_eq!(
+        mmap(
+            cageid,
+            addr,
+            2,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        addr as i32
+    );
+    assert_eq!(
+        mmap(
+            cageid,
+            page_addr(18),
+            1,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        page_addr(18) as i32
+    );
+    write(base, addr, 42);
+    write(base, page_addr(17), 43);
+
+    // the mapping can't grow in place, it moves to free space
+    let moved = mremap(cageid, addr, page_addr(2), page_addr(4), MREMAP_MAYMOVE, 0);
+    assert!(moved > 0 && moved as u64 != addr);
+    let moved_page = moved as u32 >> PAGESHIFT;
+    assert!(moved_page + 4 <= RESERVED_PAGES);
+    assert!(find_page(cageid, 16).is_none());
+    let entry = find_page(cageid, moved_page).unwrap();
+    assert_eq!((entry.page_num, entry.npages), (moved_page, 4));
+    assert_eq!(entry.prot, PROT_READ | PROT_WRITE);
+    assert_eq!(read(base, moved as u64), 42);
+    assert_eq!(read(base, moved as u64 + PAGESIZE as u64), 43);
+    write(base, moved as u64 + page_addr(3), 1);
+
+    // move and shrink it to a given address
+    let fixed = page_addr(40);
+    assert_eq!(
+        mremap(
+            cageid,
+            moved as u64,
+            page_addr(4),
+            page_addr(1),
+            MREMAP_MAYMOVE | MREMAP_FIXED,
+            fixed
+        ),
+        fixed as i32
+    );
+    assert!(find_page(cageid, moved_page).is_none());
+    assert_eq!(find_page(cageid, 40).unwrap().npages, 1);
+    assert!(find_page(cageid, 41).is_none());
+    assert_eq!(read(base, fixed), 42);
+}
+
+#[test]
+fn test_mremap_errors() {
+    let (cageid, _base) = init_test_cage();
+    let addr = page_addr(16);
+    assert_eq!(
+        mmap(
+            cageid,
+            addr,
+            2,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        addr as i32
+    );
+    assert_eq!(
+        mmap(
+            cageid,
+            page_addr(18),
+            1,
+            PROT_READ,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        page_addr(18) as i32
+    );
+
+    let einval = -(Errno::EINVAL as i32);
+    assert_eq!(
+        mremap(cageid, addr, page_addr(2), page_addr(4), MREMAP_FIXED, 0),
+        einval
+    );
+    assert_eq!(
+        mremap(cageid, addr, page_addr(2), page_addr(4), 0x4, 0),
+        einval
+    );
+    assert_eq!(
+        mremap(cageid, addr + 1, page_addr(1), page_addr(2), 0, 0),
+        einval
+    );
+    assert_eq!(mremap(cageid, addr, 0, page_addr(2), 0, 0), einval);
+    assert_eq!(mremap(cageid, addr, page_addr(2), 0, 0, 0), einval);
+    // the new range of a fixed move can't overlap the old one, nor leave the cage memory
+    assert_eq!(
+        mremap(
+            cageid,
+            addr,
+            page_addr(2),
+            page_addr(2),
+            MREMAP_MAYMOVE | MREMAP_FIXED,
+            page_addr(17)
+        ),
+        einval
+    );
+    assert_eq!(
+        mremap(
+            cageid,
+            addr,
+            page_addr(2),
+            page_addr(2),
+            MREMAP_MAYMOVE | MREMAP_FIXED,
+            page_addr(RESERVED_PAGES - 1)
+        ),
+        einval
+    );
+
+    let efault = -(Errno::EFAULT as i32);
+    // the new address of a fixed move must be in the address space of the cage being remapped
+    assert_eq!(
+        mremap_syscall(
+            cageid,
+            addr,
+            cageid,
+            page_addr(2),
+            cageid,
+            page_addr(2),
+            cageid,
+            (MREMAP_MAYMOVE | MREMAP_FIXED) as u64,
+            cageid,
+            page_addr(40),
+            INIT_CAGEID,
+            0,
+            0,
+        ),
+        efault
+    );
+    // the old range must lie within a single mapping
+    assert_eq!(
+        mremap(cageid, page_addr(30), page_addr(1), page_addr(2), 0, 0),
+        efault
+    );
+    assert_eq!(
+        mremap(cageid, addr, page_addr(3), page_addr(4), MREMAP_MAYMOVE, 0),
+        efault
+    );
+
+    assert_eq!(find_page(cageid, 16).unwrap().npages, 2);
+    assert_eq!(find_page(cageid, 18).unwrap().npages, 1);
+}
+
+#[test]
+fn test_fork_copies_private_memory() {
+    let (parentid, parent_base) = init_test_cage();
+    for page_num in 0..MEMORY_PAGES {
+        write(parent_base, page_addr(page_num), page_num as u8 + 1);
+    }
+
+    let (childid, child_base) = fork(parentid);
+    for page_num in 0..MEMORY_PAGES {
+        assert_eq!(read(child_base, page_addr(page_num)), page_num as u8 + 1);
+    }
+    // both map the memory from the same snapshot, and their writes stay private
+    let parent_snapshot = find_page(parentid, 0).unwrap().snapshot.unwrap();
+    let child_snapshot = find_page(childid, 0).unwrap().snapshot.unwrap();
+    assert!(Arc::ptr_eq(&parent_snapshot, &child_snapshot));
+    write(parent_base, 0, 10);
+    write(child_base, page_addr(1), 20);
+    assert_eq!(read(child_base, 0), 1);
+    assert_eq!(read(parent_base, page_addr(1)), 2);
+}
+
+#[test]
+fn test_fork_copies_pages_written_since_snapshot() {
+    let (parentid, parent_base) = init_test_cage();
+    write(parent_base, 0, 1);
+    write(parent_base, page_addr(1), 2);
+    let (firstid, first_base) = fork(parentid);
+    let snapshot = find_page(parentid, 0).unwrap().snapshot.unwrap();
+
+    // splitting the region keeps the snapshot
+    assert_eq!(mprotect(parentid, page_addr(3), 1, PROT_READ), 0);
+    write(parent_base, page_addr(1), 3);
+    write(parent_base, page_addr(2), 4);
+    let (secondid, second_base) = fork(parentid);
+    for page_num in 0..MEMORY_PAGES {
+        let entry = find_page(secondid, page_num).unwrap();
+        assert!(Arc::ptr_eq(entry.snapshot.as_ref().unwrap(), &snapshot));
+    }
+    assert_eq!(find_page(secondid, 3).unwrap().prot, PROT_READ);
+    assert_eq!(read(second_base, 0), 1);
+    assert_eq!(read(second_base, page_addr(1)), 3);
+    assert_eq!(read(second_base, page_addr(2)), 4);
+
+    // the first child doesn't see the writes of the parent made after it was forked
+    assert_eq!(read(first_base, page_addr(1)), 2);
+    assert_eq!(read(first_base, page_addr(2)), 0);
+
+    // and a child of the child sees its writes
+    write(first_base, page_addr(2), 5);
+    let (_, third_base) = fork(firstid);
+    assert_eq!(read(third_base, page_addr(1)), 2);
+    assert_eq!(read(third_base, page_addr(2)), 5);
+}
+
+#[test]
+fn test_fork_copies_heap_pages_outside_snapshot() {
+    let (parentid, parent_base) = init_test_cage();
+    get_cage(parentid)
+        .unwrap()
+        .vmmap
+        .write()
+        .set_program_break(MEMORY_PAGES);
+    write(parent_base, 0, 1);
+    let _ = fork(parentid);
+    let snapshot = find_page(parentid, 0).unwrap().snapshot.unwrap();
+
+    // the heap keeps its snapshot when it grows, the new pages are outside of it
+    assert_eq!(brk(parentid, page_addr(6)), 0);
+    write(parent_base, 0, 2);
+    write(parent_base, page_addr(5), 3);
+    let (childid, child_base) = fork(parentid);
+    let entry = find_page(childid, 0).unwrap();
+    assert_eq!(entry.npages, 6);
+    assert!(Arc::ptr_eq(entry.snapshot.as_ref().unwrap(), &snapshot));
+    assert_eq!(read(child_base, 0), 2);
+    assert_eq!(read(child_base, page_addr(4)), 0);
+    assert_eq!(read(child_base, page_addr(5)), 3);
+
+    // once the heap shrank, growing it again maps pages the snapshot holds, so it is dropped
+    write(parent_base, page_addr(3), 4);
+    assert_eq!(brk(parentid, page_addr(2)), 0);
+    assert_eq!(brk(parentid, page_addr(4)), 0);
+    assert!(find_page(parentid, 0).unwrap().snapshot.is_none());
+    let (_, child_base) = fork(parentid);
+    assert_eq!(read(child_base, 0), 2);
+    assert_eq!(read(child_base, page_addr(3)), 0);
+}
+
+#[test]
+fn test_munmap_releases_pages() {
+    let (cageid, base) = init_test_cage();
+    let addr = page_addr(16);
+    assert_eq!(
+        mmap(
+            cageid,
+            addr,
+            4,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        addr as i32
+    );
+    for page_num in 16..20 {
+        write(base, page_addr(page_num), 1);
+    }
+    assert_eq!(resident_pages(base, 16, 4), vec![true; 4]);
+
+    // the length is rounded up, the last page is unmapped as well
+    assert_eq!(munmap(cageid, page_addr(17), page_addr(2) + 1), 0);
+    assert_eq!(resident_pages(base, 16, 4), vec![true, false, false, false]);
+    let entry = find_page(cageid, 16).unwrap();
+    assert_eq!((entry.page_num, entry.npages), (16, 1));
+    for page_num in 17..20 {
+        assert!(find_page(cageid, page_num).is_none());
+    }
+
+    // pages mapped again after munmap are zeroed
+    assert_eq!(
+        mmap(
+            cageid,
+            page_addr(18),
+            1,
+            PROT_READ | PROT_WRITE,
+            MAP_PRIVATE | MAP_ANONYMOUS | MAP_FIXED,
+            -1
+        ),
+        page_addr(18) as i32
+    );
+    assert_eq!(read(base, page_addr(18)), 0);
+
+    assert_eq!(
+        munmap(cageid, page_addr(RESERVED_PAGES - 1), page_addr(2)),
+        -(Errno::EINVAL as i32)
+    );
+}
+
+#[test]
+fn test_munmap_file_mapping() {
+    let (cageid, base) = init_test_cage();
+    let path = test_file(4);
+    let fd = open_read_only(cageid, &path);
+    let addr = page_addr(16);
+    assert_eq!(
+        mmap(
+            cageid,
+            addr,
+            4,
+            PROT_READ,
+            MAP_PRIVATE | MAP_FIXED,
+            fd as i64
+        ),
+        addr as i32
+    );
+
+    // the pages around the unmapped ones keep their file offset
+    assert_eq!(munmap(cageid, page_addr(17), page_addr(2)), 0);
+    let entry = find_page(cageid, 19).unwrap();
+    assert_eq!((entry.page_num, entry.npages), (19, 1));
+    assert_eq!(entry.file_offset, page_addr(3) as i64);
+    assert_eq!(read(base, page_addr(19)), 3);
+    assert_eq!(resident_pages(base, 17, 2), vec![false; 2]);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_vmmap_check_consistency() {
+    let (cageid, _base) = init_test_cage();
+    let cage = get_cage(cageid).unwrap();
+    assert!(cage.vmmap.read().check_consistency().is_ok());
+
+    cage.vmmap.write().set_program_break(MEMORY_PAGES + 1);
+    assert!(cage.vmmap.read().check_consistency().is_err());
+    cage.vmmap.write().set_program_break(MEMORY_PAGES);
+    assert!(cage.vmmap.read().check_consistency().is_ok());
+
+    cage.vmmap.write().find_page_mut(0).unwrap().maxprot = PROT_READ;
+    assert!(cage.vmmap.read().check_consistency().is_err());
+    cage.vmmap.write().find_page_mut(0).unwrap().maxprot = PROT_READ | PROT_WRITE;
+
+    cage.vmmap.write().find_page_mut(0).unwrap().npages = MEMORY_PAGES - 1;
+    assert!(cage.vmmap.read().check_consistency().is_err());
+}
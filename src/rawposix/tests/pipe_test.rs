@@ -0,0 +1,297 @@
+mod common;
+
+use cage::get_cage;
+use cage::signal::sigmask_bit;
+use common::{init_rawposix, load, store, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{
+    close_syscall, fcntl_syscall, fstat_syscall, pipe2_syscall, read_syscall, write_syscall,
+};
+use rawposix::syscalls::net_calls::{poll_syscall, sendmsg_syscall, socketpair_syscall};
+use rawposix::syscalls::pipe::set_inmemory_pipes;
+use rawposix::syscalls::sys_calls::kill_syscall;
+use std::sync::atomic::Ordering::SeqCst;
+use std::sync::Once;
+use std::time::Duration;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    F_GETFL, O_CLOEXEC, O_NONBLOCK, O_WRONLY, PIPE_BUF, PIPE_CAPACITY, S_IFIFO,
+};
+use sysdefs::constants::net_const::{POLLHUP, POLLIN, POLLOUT, SCM_RIGHTS, SOL_SOCKET};
+use sysdefs::constants::sys_const::{SIGPIPE, SIGTERM};
+use sysdefs::data::fs_struct::{PipeArray, SockPair, StatData, WasmCmsghdr, WasmIovec, WasmMsghdr};
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 32;
+
+static INIT: Once = Once::new();
+
+/// Forks a new cage from `parentid` with `MEMORY_PAGES` pages of linear memory. Returns the cage id and
+/// the host base address of the cage memory. Every pipe of the test binary is created in memory.
+fn init_test_cage(parentid: u64) -> (u64, *mut u8) {
+    INIT.call_once(|| {
+        init_rawposix();
+        set_inmemory_pipes(true);
+    });
+    common::init_test_cage(parentid, MEMORY_PAGES)
+}
+
+/// Creates a pipe with `flags` and returns its read and write fds
+fn pipe2(cageid: u64, base: *mut u8, flags: i32) -> (u64, u64) {
+    // pipe2() takes a host pointer to the fd array
+    let pipefd = base as *mut PipeArray;
+    assert_eq!(
+        pipe2_syscall(
+            cageid,
+            pipefd as u64,
+            cageid,
+            flags as u64,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    let fds = unsafe { &*pipefd };
+    (fds.readfd as u64, fds.writefd as u64)
+}
+
+fn write(cageid: u64, fd: u64, buf: u64, len: usize) -> i32 {
+    write_syscall(
+        cageid, fd, cageid, buf, cageid, len as u64, cageid, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+fn read(cageid: u64, fd: u64, buf: u64, len: usize) -> i32 {
+    read_syscall(
+        cageid, fd, cageid, buf, cageid, len as u64, cageid, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+fn close(cageid: u64, fd: u64) {
+    assert_eq!(
+        close_syscall(cageid, fd, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+}
+
+/// Polls `fd` without blocking and returns its revents
+fn poll_once(cageid: u64, base: *mut u8, fd: u64, events: i16) -> i16 {
+    store(
+        base,
+        64,
+        libc::pollfd {
+            fd: fd as i32,
+            events,
+            revents: 0,
+        },
+    );
+    assert!(poll_syscall(cageid, 64, cageid, 1, cageid, 0, cageid, 0, 0, 0, 0, 0, 0) >= 0);
+    load::<libc::pollfd>(base, 64).revents
+}
+
+#[test]
+fn test_pipe_between_cages_reaches_eof() {
+    let (parent, parent_base) = init_test_cage(INIT_CAGEID);
+    let (readfd, writefd) = pipe2(parent, parent_base, 0);
+    // the child gets its own references to both ends
+    let (child, child_base) = init_test_cage(parent);
+    close(child, readfd);
+    close(parent, writefd);
+
+    unsafe { std::ptr::write_bytes(child_base.add(4096), b'x', 3 * PIPE_CAPACITY / 2) };
+    let writer = std::thread::spawn(move || {
+        // larger than the pipe, so the writer waits for the reader to make room
+        let ret = write(child, writefd, 4096, 3 * PIPE_CAPACITY / 2);
+        close(child, writefd);
+        ret
+    });
+
+    let mut total = 0;
+    loop {
+        let ret = read(parent, readfd, 4096, PIPE_BUF);
+        assert!(ret >= 0);
+        if ret == 0 {
+            break;
+        }
+        total += ret as usize;
+    }
+    assert_eq!(writer.join().unwrap(), (3 * PIPE_CAPACITY / 2) as i32);
+    assert_eq!(total, 3 * PIPE_CAPACITY / 2);
+    assert_eq!(poll_once(parent, parent_base, readfd, POLLIN), POLLHUP);
+
+    assert_eq!(
+        fstat_syscall(parent, readfd, parent, 128, parent, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    let statbuf = load::<StatData>(parent_base, 128);
+    assert_eq!(statbuf.st_mode & S_IFIFO as u32, S_IFIFO as u32);
+    close(parent, readfd);
+}
+
+#[test]
+fn test_nonblocking_pipe_and_broken_pipe() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let (readfd, writefd) = pipe2(cageid, base, O_NONBLOCK | O_CLOEXEC);
+    assert_eq!(
+        fcntl_syscall(
+            cageid,
+            writefd,
+            cageid,
+            F_GETFL as u64,
+            cageid,
+            0,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        O_WRONLY | O_NONBLOCK
+    );
+
+    assert_eq!(read(cageid, readfd, 4096, 16), -(Errno::EAGAIN as i32));
+    assert_eq!(poll_once(cageid, base, writefd, POLLOUT), POLLOUT);
+
+    // fill the pipe up to less than PIPE_BUF, a write of PIPE_BUF bytes then can't go in one piece
+    assert_eq!(
+        write(cageid, writefd, 4096, PIPE_CAPACITY - 16),
+        (PIPE_CAPACITY - 16) as i32
+    );
+    assert_eq!(poll_once(cageid, base, writefd, POLLOUT), 0);
+    assert_eq!(
+        write(cageid, writefd, 4096, PIPE_BUF),
+        -(Errno::EAGAIN as i32)
+    );
+
+    close(cageid, readfd);
+    assert_eq!(write(cageid, writefd, 4096, 1), -(Errno::EPIPE as i32));
+    // the writer is sent SIGPIPE as well
+    let pending = get_cage(cageid).unwrap().pending_signals.load(SeqCst);
+    assert_eq!(pending & sigmask_bit(SIGPIPE), sigmask_bit(SIGPIPE));
+    close(cageid, writefd);
+}
+
+#[test]
+fn test_signal_interrupts_blocked_read() {
+    let (parent, parent_base) = init_test_cage(INIT_CAGEID);
+    let (readfd, writefd) = pipe2(parent, parent_base, 0);
+    let (child, _) = init_test_cage(parent);
+    close(child, writefd);
+
+    // the pipe stays empty with a writer left, the read blocks until the signal comes
+    let reader = std::thread::spawn(move || read(child, readfd, 4096, 16));
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!reader.is_finished());
+    assert_eq!(
+        kill_syscall(
+            parent,
+            child,
+            parent,
+            SIGTERM as u64,
+            parent,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    assert_eq!(reader.join().unwrap(), -(Errno::EINTR as i32));
+
+    close(child, readfd);
+    close(parent, readfd);
+    close(parent, writefd);
+}
+
+#[test]
+fn test_pipe_cannot_pass_over_kernel_socket() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let (readfd, writefd) = pipe2(cageid, base, 0);
+    // sockets of this test binary are kernel sockets
+    assert_eq!(
+        socketpair_syscall(
+            cageid,
+            libc::AF_UNIX as u64,
+            cageid,
+            libc::SOCK_STREAM as u64,
+            cageid,
+            0,
+            cageid,
+            64,
+            cageid,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    let sv: SockPair = load(base, 64);
+
+    // a message over one byte at 4096, passing the read end of the pipe
+    let hdrlen = std::mem::size_of::<WasmCmsghdr>() as u32;
+    store(
+        base,
+        256,
+        WasmIovec {
+            iov_base: 4096,
+            iov_len: 1,
+        },
+    );
+    store(
+        base,
+        512,
+        WasmCmsghdr {
+            cmsg_len: hdrlen + 4,
+            cmsg_level: SOL_SOCKET,
+            cmsg_type: SCM_RIGHTS,
+        },
+    );
+    store(base, 512 + hdrlen as u64, readfd as i32);
+    store(
+        base,
+        128,
+        WasmMsghdr {
+            msg_iov: 256,
+            msg_iovlen: 1,
+            msg_control: 512,
+            msg_controllen: hdrlen + 4,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        sendmsg_syscall(
+            cageid,
+            sv.sock1 as u64,
+            cageid,
+            128,
+            cageid,
+            0,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        -(Errno::EOPNOTSUPP as i32)
+    );
+
+    close(cageid, sv.sock1 as u64);
+    close(cageid, sv.sock2 as u64);
+    close(cageid, readfd);
+    close(cageid, writefd);
+}
@@ -0,0 +1,435 @@
+mod common;
+
+use common::{init_test_cage, load, store, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{close_syscall, pipe_syscall, write_syscall};
+use rawposix::syscalls::net_calls::{
+    epoll_create_syscall, epoll_ctl_syscall, epoll_wait_syscall, poll_syscall, select_syscall,
+};
+use std::time::{Duration, Instant};
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::net_const::{
+    EPOLLIN, EPOLLONESHOT, EPOLLOUT, EPOLL_CTL_ADD, EPOLL_CTL_DEL, EPOLL_CTL_MOD, POLLIN, POLLNVAL,
+    POLLOUT,
+};
+use sysdefs::data::fs_struct::{EpollEvent, PipeArray, TimeVal};
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 4;
+
+/// Guest addresses where the arguments of the calls are kept
+const PIPEFD_ADDR: u64 = 64;
+const BUF_ADDR: u64 = 128;
+const READFDS_ADDR: u64 = 256;
+const WRITEFDS_ADDR: u64 = 512;
+const TIMEOUT_ADDR: u64 = 768;
+const POLLFDS_ADDR: u64 = 1024;
+const EVENT_ADDR: u64 = 1536;
+const EVENTS_ADDR: u64 = 2048;
+
+/// Forks a new cage from the init cage with `MEMORY_PAGES` pages of linear memory. Returns the cage id
+/// and the host base address of the cage memory.
+fn new_cage() -> (u64, *mut u8) {
+    init_test_cage(INIT_CAGEID, MEMORY_PAGES)
+}
+
+/// Stores `val`, if any, at `addr` in the cage memory. Returns the guest address to pass for it, 0 for
+/// none.
+fn store_arg<T>(base: *mut u8, addr: u64, val: &Option<&mut T>) -> u64 {
+    match val {
+        Some(val) => {
+            // the arguments are plain C structs, which TimeVal doesn't mark as Copy
+            store(base, addr, unsafe { std::ptr::read(&**val) });
+            addr
+        }
+        None => 0,
+    }
+}
+
+/// Copies the value at `addr` in the cage memory back into `val`, if any
+fn load_arg<T>(base: *mut u8, addr: u64, val: Option<&mut T>) {
+    if let Some(val) = val {
+        *val = load(base, addr);
+    }
+}
+
+fn pipe(cageid: u64, base: *mut u8) -> (u64, u64) {
+    // pipe() takes a host pointer to the fd array
+    let pipefd = base.wrapping_add(PIPEFD_ADDR as usize) as u64;
+    assert_eq!(
+        pipe_syscall(cageid, pipefd, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    let fds = load::<PipeArray>(base, PIPEFD_ADDR);
+    (fds.readfd as u64, fds.writefd as u64)
+}
+
+fn write_byte(cageid: u64, base: *mut u8, fd: u64) {
+    store(base, BUF_ADDR, 0u8);
+    assert_eq!(
+        write_syscall(cageid, fd, cageid, BUF_ADDR, cageid, 1, cageid, 0, 0, 0, 0, 0, 0),
+        1
+    );
+}
+
+fn fdset(fds: &[u64]) -> libc::fd_set {
+    let mut set = fdtables::_init_fd_set();
+    for &fd in fds {
+        fdtables::_fd_set(fd, &mut set);
+    }
+    set
+}
+
+/// Calls select with the sets and the timeout in the cage memory, and copies them back once it returns
+fn select(
+    cageid: u64,
+    base: *mut u8,
+    nfds: u64,
+    readfds: Option<&mut libc::fd_set>,
+    writefds: Option<&mut libc::fd_set>,
+    timeout: Option<&mut TimeVal>,
+) -> i32 {
+    let ret = select_syscall(
+        cageid,
+        nfds,
+        cageid,
+        store_arg(base, READFDS_ADDR, &readfds),
+        cageid,
+        store_arg(base, WRITEFDS_ADDR, &writefds),
+        cageid,
+        0,
+        cageid,
+        store_arg(base, TIMEOUT_ADDR, &timeout),
+        cageid,
+        0,
+        0,
+    );
+    load_arg(base, READFDS_ADDR, readfds);
+    load_arg(base, WRITEFDS_ADDR, writefds);
+    load_arg(base, TIMEOUT_ADDR, timeout);
+    ret
+}
+
+/// Calls poll with `fds` in the cage memory, and copies them back once it returns
+fn poll(cageid: u64, base: *mut u8, fds: &mut [libc::pollfd], timeout_ms: i32) -> i32 {
+    let size = std::mem::size_of::<libc::pollfd>() as u64;
+    for (i, fd) in fds.iter().enumerate() {
+        store(base, POLLFDS_ADDR + i as u64 * size, *fd);
+    }
+    let ret = poll_syscall(
+        cageid,
+        POLLFDS_ADDR,
+        cageid,
+        fds.len() as u64,
+        cageid,
+        timeout_ms as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    for (i, fd) in fds.iter_mut().enumerate() {
+        *fd = load(base, POLLFDS_ADDR + i as u64 * size);
+    }
+    ret
+}
+
+fn epoll_ctl(
+    cageid: u64,
+    base: *mut u8,
+    epfd: u64,
+    op: i32,
+    fd: u64,
+    event: Option<&mut EpollEvent>,
+) -> i32 {
+    epoll_ctl_syscall(
+        cageid,
+        epfd,
+        cageid,
+        op as u64,
+        cageid,
+        fd,
+        cageid,
+        store_arg(base, EVENT_ADDR, &event),
+        cageid,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+/// Calls epoll_wait with room for `events.len()` events in the cage memory, and copies them back once
+/// it returns
+fn epoll_wait(
+    cageid: u64,
+    base: *mut u8,
+    epfd: u64,
+    events: &mut [EpollEvent],
+    timeout_ms: i32,
+) -> i32 {
+    let ret = epoll_wait_syscall(
+        cageid,
+        epfd,
+        cageid,
+        EVENTS_ADDR,
+        cageid,
+        events.len() as u64,
+        cageid,
+        timeout_ms as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+    );
+    let size = std::mem::size_of::<EpollEvent>() as u64;
+    for (i, event) in events.iter_mut().enumerate() {
+        *event = load(base, EVENTS_ADDR + i as u64 * size);
+    }
+    ret
+}
+
+#[test]
+fn test_select_reports_ready_virtual_fds() {
+    let (cageid, base) = new_cage();
+    let (readfd, writefd) = pipe(cageid, base);
+
+    let mut readfds = fdset(&[readfd]);
+    let mut writefds = fdset(&[writefd]);
+    let mut timeout = TimeVal {
+        tv_sec: 1,
+        tv_usec: 0,
+    };
+    let nfds = readfd.max(writefd) + 1;
+    assert_eq!(
+        select(
+            cageid,
+            base,
+            nfds,
+            Some(&mut readfds),
+            Some(&mut writefds),
+            Some(&mut timeout)
+        ),
+        1
+    );
+    assert!(!fdtables::_fd_isset(readfd, &readfds));
+    assert!(fdtables::_fd_isset(writefd, &writefds));
+
+    write_byte(cageid, base, writefd);
+    let mut readfds = fdset(&[readfd]);
+    let mut writefds = fdset(&[writefd]);
+    assert_eq!(
+        select(
+            cageid,
+            base,
+            nfds,
+            Some(&mut readfds),
+            Some(&mut writefds),
+            None
+        ),
+        2
+    );
+    assert!(fdtables::_fd_isset(readfd, &readfds));
+    assert!(fdtables::_fd_isset(writefd, &writefds));
+}
+
+#[test]
+fn test_select_timeout_and_errors() {
+    let (cageid, base) = new_cage();
+    let (readfd, _writefd) = pipe(cageid, base);
+
+    // the timeout is updated with the time left, none when select timed out
+    let mut readfds = fdset(&[readfd]);
+    let mut timeout = TimeVal {
+        tv_sec: 0,
+        tv_usec: 20_000,
+    };
+    let start = Instant::now();
+    assert_eq!(
+        select(
+            cageid,
+            base,
+            readfd + 1,
+            Some(&mut readfds),
+            None,
+            Some(&mut timeout)
+        ),
+        0
+    );
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert!(!fdtables::_fd_isset(readfd, &readfds));
+    assert_eq!((timeout.tv_sec, timeout.tv_usec), (0, 0));
+
+    let mut timeout = TimeVal {
+        tv_sec: 0,
+        tv_usec: 1_000_000,
+    };
+    assert_eq!(
+        select(cageid, base, readfd + 1, None, None, Some(&mut timeout)),
+        -(Errno::EINVAL as i32)
+    );
+
+    let mut readfds = fdset(&[readfd + 10]);
+    assert_eq!(
+        select(cageid, base, readfd + 11, Some(&mut readfds), None, None),
+        -(Errno::EBADF as i32)
+    );
+}
+
+#[test]
+fn test_poll_reports_ready_and_invalid_fds() {
+    let (cageid, base) = new_cage();
+    let (readfd, writefd) = pipe(cageid, base);
+    write_byte(cageid, base, writefd);
+    close_syscall(cageid, writefd, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+
+    let mut fds = [
+        libc::pollfd {
+            fd: readfd as i32,
+            events: POLLIN,
+            revents: 0,
+        },
+        libc::pollfd {
+            fd: writefd as i32,
+            events: POLLOUT,
+            revents: 0,
+        },
+        // negative fds are ignored
+        libc::pollfd {
+            fd: -1,
+            events: POLLIN,
+            revents: POLLIN,
+        },
+    ];
+    assert_eq!(poll(cageid, base, &mut fds, -1), 2);
+    assert_ne!(fds[0].revents & POLLIN, 0);
+    assert_eq!(fds[1].revents, POLLNVAL);
+    assert_eq!(fds[2].revents, 0);
+
+    let (readfd, _writefd) = pipe(cageid, base);
+    let mut fds = [libc::pollfd {
+        fd: readfd as i32,
+        events: POLLIN,
+        revents: 0,
+    }];
+    let start = Instant::now();
+    assert_eq!(poll(cageid, base, &mut fds, 20), 0);
+    assert!(start.elapsed() >= Duration::from_millis(20));
+    assert_eq!(fds[0].revents, 0);
+}
+
+#[test]
+fn test_epoll_interest_list() {
+    let (cageid, base) = new_cage();
+    let (readfd, writefd) = pipe(cageid, base);
+
+    let epfd = epoll_create_syscall(cageid, 1, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    assert!(epfd >= 0);
+    let epfd = epfd as u64;
+    assert_eq!(
+        epoll_create_syscall(cageid, 0, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EINVAL as i32)
+    );
+
+    let mut readevent = EpollEvent {
+        events: EPOLLIN as u32,
+        data: 0x1234_5678_9abc_def0,
+    };
+    let mut writeevent = EpollEvent {
+        events: EPOLLOUT as u32,
+        data: writefd,
+    };
+    assert_eq!(
+        epoll_ctl(
+            cageid,
+            base,
+            epfd,
+            EPOLL_CTL_ADD,
+            readfd,
+            Some(&mut readevent)
+        ),
+        0
+    );
+    assert_eq!(
+        epoll_ctl(
+            cageid,
+            base,
+            epfd,
+            EPOLL_CTL_ADD,
+            readfd,
+            Some(&mut readevent)
+        ),
+        -(Errno::EEXIST as i32)
+    );
+    assert_eq!(
+        epoll_ctl(
+            cageid,
+            base,
+            epfd,
+            EPOLL_CTL_ADD,
+            writefd,
+            Some(&mut writeevent)
+        ),
+        0
+    );
+
+    let mut events = [EpollEvent { events: 0, data: 0 }; 4];
+    assert_eq!(epoll_wait(cageid, base, epfd, &mut events, 1000), 1);
+    let (ready, data) = (events[0].events, events[0].data);
+    assert_eq!((ready, data), (EPOLLOUT as u32, writefd));
+
+    // the user data is handed back as is, and maxevents bounds the events reported
+    write_byte(cageid, base, writefd);
+    assert_eq!(epoll_wait(cageid, base, epfd, &mut events, 1000), 2);
+    assert_eq!(epoll_wait(cageid, base, epfd, &mut events[..1], 1000), 1);
+    assert_eq!(
+        epoll_ctl(cageid, base, epfd, EPOLL_CTL_DEL, writefd, None),
+        0
+    );
+    assert_eq!(epoll_wait(cageid, base, epfd, &mut events, 1000), 1);
+    let (ready, data) = (events[0].events, events[0].data);
+    assert_eq!((ready, data), (EPOLLIN as u32, 0x1234_5678_9abc_def0));
+
+    assert_eq!(
+        epoll_ctl(cageid, base, epfd, EPOLL_CTL_DEL, writefd, None),
+        -(Errno::ENOENT as i32)
+    );
+    assert_eq!(
+        epoll_wait(cageid, base, epfd, &mut events[..0], 0),
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(
+        epoll_wait(cageid, base, readfd, &mut events, 0),
+        -(Errno::EINVAL as i32)
+    );
+}
+
+#[test]
+fn test_epoll_oneshot_until_rearmed() {
+    let (cageid, base) = new_cage();
+    let (readfd, writefd) = pipe(cageid, base);
+    write_byte(cageid, base, writefd);
+
+    let epfd = epoll_create_syscall(cageid, 1, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0) as u64;
+    let mut event = EpollEvent {
+        events: (EPOLLIN | EPOLLONESHOT) as u32,
+        data: readfd,
+    };
+    assert_eq!(
+        epoll_ctl(cageid, base, epfd, EPOLL_CTL_ADD, readfd, Some(&mut event)),
+        0
+    );
+
+    let mut events = [EpollEvent { events: 0, data: 0 }; 1];
+    assert_eq!(epoll_wait(cageid, base, epfd, &mut events, 1000), 1);
+    // the fd is still readable, but disabled until re-armed
+    assert_eq!(epoll_wait(cageid, base, epfd, &mut events, 10), 0);
+    assert_eq!(
+        epoll_ctl(cageid, base, epfd, EPOLL_CTL_MOD, readfd, Some(&mut event)),
+        0
+    );
+    assert_eq!(epoll_wait(cageid, base, epfd, &mut events, 1000), 1);
+}
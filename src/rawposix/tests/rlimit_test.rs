@@ -0,0 +1,152 @@
+mod common;
+
+use common::{load, store, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{close_syscall, dup_syscall};
+use rawposix::syscalls::sys_calls::{getrlimit_syscall, setrlimit_syscall};
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::sys_const::{RLIMIT_NOFILE, RLIMIT_STACK, STACK_CUR, STACK_MAX};
+use sysdefs::data::fs_struct::Rlimit;
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 4;
+
+/// Address in the cage memory where the `Rlimit` of a call is kept
+const RLIMIT_ADDR: u64 = 64;
+
+/// Forks a new cage from `parentid` with `MEMORY_PAGES` pages of linear memory. Returns the cage id and
+/// the host base address of the cage memory.
+fn init_test_cage(parentid: u64) -> (u64, *mut u8) {
+    common::init_test_cage(parentid, MEMORY_PAGES)
+}
+
+/// Returns the (soft, hard) limits of `resource`, or the negative errno of the call
+fn getrlimit(cageid: u64, base: *mut u8, resource: u64) -> Result<(u64, u64), i32> {
+    let ret = getrlimit_syscall(
+        cageid,
+        resource,
+        cageid,
+        RLIMIT_ADDR,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    if ret < 0 {
+        return Err(ret);
+    }
+    let rlim = load::<Rlimit>(base, RLIMIT_ADDR);
+    Ok((rlim.rlim_cur, rlim.rlim_max))
+}
+
+fn setrlimit(cageid: u64, base: *mut u8, resource: u64, soft: u64, hard: u64) -> i32 {
+    store(
+        base,
+        RLIMIT_ADDR,
+        Rlimit {
+            rlim_cur: soft,
+            rlim_max: hard,
+        },
+    );
+    setrlimit_syscall(
+        cageid,
+        resource,
+        cageid,
+        RLIMIT_ADDR,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn dup(cageid: u64, fd: u64) -> i32 {
+    dup_syscall(cageid, fd, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0)
+}
+
+#[test]
+fn test_nofile_limit_bounds_new_fds() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let (soft, hard) = getrlimit(cageid, base, RLIMIT_NOFILE).unwrap();
+    assert!(soft <= hard);
+
+    // stdin, stdout and stderr are inherited, so only fd 3 is left
+    assert_eq!(setrlimit(cageid, base, RLIMIT_NOFILE, 4, hard), 0);
+    assert_eq!(getrlimit(cageid, base, RLIMIT_NOFILE), Ok((4, hard)));
+    assert_eq!(dup(cageid, 0), 3);
+    assert_eq!(dup(cageid, 0), -(Errno::EMFILE as i32));
+
+    assert_eq!(
+        close_syscall(cageid, 3, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(dup(cageid, 0), 3);
+}
+
+#[test]
+fn test_nofile_hard_limit_cannot_be_raised() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    let (_, hard) = getrlimit(cageid, base, RLIMIT_NOFILE).unwrap();
+
+    assert_eq!(
+        setrlimit(cageid, base, RLIMIT_NOFILE, 20, 10),
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(setrlimit(cageid, base, RLIMIT_NOFILE, 10, 100), 0);
+    assert_eq!(
+        setrlimit(cageid, base, RLIMIT_NOFILE, 10, hard),
+        -(Errno::EPERM as i32)
+    );
+    assert_eq!(getrlimit(cageid, base, RLIMIT_NOFILE), Ok((10, 100)));
+
+    // a forked child starts with the limits of its parent
+    let (childid, childbase) = init_test_cage(cageid);
+    assert_eq!(getrlimit(childid, childbase, RLIMIT_NOFILE), Ok((10, 100)));
+}
+
+#[test]
+fn test_stack_and_unsupported_limits() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID);
+    assert_eq!(
+        getrlimit(cageid, base, RLIMIT_STACK),
+        Ok((STACK_CUR, STACK_MAX))
+    );
+    assert_eq!(
+        setrlimit(cageid, base, RLIMIT_STACK, STACK_CUR, STACK_MAX),
+        0
+    );
+    assert_eq!(
+        setrlimit(cageid, base, RLIMIT_STACK, STACK_CUR / 2, STACK_MAX),
+        -(Errno::EPERM as i32)
+    );
+
+    // RLIMIT_CPU
+    assert_eq!(getrlimit(cageid, base, 0), Err(-(Errno::EINVAL as i32)));
+    assert_eq!(
+        getrlimit_syscall(
+            cageid,
+            RLIMIT_NOFILE,
+            cageid,
+            0,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        -(Errno::EFAULT as i32)
+    );
+}
@@ -0,0 +1,173 @@
+mod common;
+
+use common::{
+    fork_cage, guest_bytes, host_path, init_memory, page_addr, store, test_dir, INIT_CAGEID,
+};
+use rawposix::syscalls::fs_calls::{pread_syscall, pwrite_syscall, readv_syscall, writev_syscall};
+use std::os::fd::IntoRawFd;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::FDKIND_KERNEL;
+use sysdefs::data::fs_struct::WasmIovec;
+
+/// Pages of linear memory reserved for every test cage
+const RESERVED_PAGES: u32 = 8;
+/// Pages at the start of the linear memory that are readable and writable
+const MEMORY_PAGES: u32 = 4;
+
+/// Guest address of the iovec array
+const IOV_ADDR: u64 = 64;
+/// Guest address of the data buffer
+const BUF_ADDR: u64 = 1024;
+
+/// Contents every test file starts with
+const CONTENTS: &[u8] = b"0123456789";
+
+/// Creates a new cage and a file holding `CONTENTS`, opened for reading and writing. Returns the cage id,
+/// the host base address of the cage memory, the virtual fd of the file and its path.
+fn setup() -> (u64, *mut u8, u64, String) {
+    let cageid = fork_cage(INIT_CAGEID);
+    let base = init_memory(cageid, RESERVED_PAGES, MEMORY_PAGES);
+    let path = format!("{}/file", test_dir("rw_test"));
+    std::fs::write(host_path(&path), CONTENTS).unwrap();
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(host_path(&path))
+        .unwrap();
+    let fd =
+        fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, file.into_raw_fd() as u64, false, 0)
+            .unwrap();
+    (cageid, base, fd, path)
+}
+
+fn pread(cageid: u64, fd: u64, buf: u64, count: usize, offset: i64) -> i32 {
+    pread_syscall(
+        cageid,
+        fd,
+        cageid,
+        buf,
+        cageid,
+        count as u64,
+        cageid,
+        offset as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn pwrite(cageid: u64, fd: u64, buf: u64, count: usize, offset: i64) -> i32 {
+    pwrite_syscall(
+        cageid,
+        fd,
+        cageid,
+        buf,
+        cageid,
+        count as u64,
+        cageid,
+        offset as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+/// Stores `iovs` at `IOV_ADDR`
+fn store_iovecs(base: *mut u8, iovs: &[(u64, usize)]) {
+    for (i, (iov_base, iov_len)) in iovs.iter().enumerate() {
+        store(
+            base,
+            IOV_ADDR + (i * std::mem::size_of::<WasmIovec>()) as u64,
+            WasmIovec {
+                iov_base: *iov_base as u32,
+                iov_len: *iov_len as u32,
+            },
+        );
+    }
+}
+
+#[test]
+fn test_pread_and_pwrite() {
+    let (cageid, base, fd, path) = setup();
+
+    assert_eq!(pread(cageid, fd, BUF_ADDR, 4, 3), 4);
+    assert_eq!(guest_bytes(base, BUF_ADDR, 4), b"3456");
+    // reading past the end of the file
+    assert_eq!(pread(cageid, fd, BUF_ADDR, 4, 8), 2);
+    assert_eq!(pread(cageid, fd, BUF_ADDR, 4, 100), 0);
+
+    assert_eq!(pwrite(cageid, fd, BUF_ADDR, 2, 0), 2);
+    assert_eq!(std::fs::read(host_path(&path)).unwrap(), b"8923456789");
+
+    assert_eq!(pread(cageid, 1000, BUF_ADDR, 4, 0), -(Errno::EBADF as i32));
+    assert_eq!(pwrite(cageid, fd, BUF_ADDR, 4, -1), -(Errno::EINVAL as i32));
+    // a zero-length buffer is never dereferenced, so it may be anywhere
+    assert_eq!(pread(cageid, fd, page_addr(MEMORY_PAGES + 1), 0, 0), 0);
+}
+
+#[test]
+fn test_pread_and_pwrite_buffers_must_be_mapped() {
+    let (cageid, _base, fd, path) = setup();
+
+    assert_eq!(
+        pread(cageid, fd, page_addr(MEMORY_PAGES + 1), 4, 0),
+        -(Errno::EFAULT as i32)
+    );
+    assert_eq!(
+        pwrite(cageid, fd, page_addr(MEMORY_PAGES + 1), 4, 0),
+        -(Errno::EFAULT as i32)
+    );
+    // the buffer runs past the end of the mapped memory
+    assert_eq!(
+        pread(cageid, fd, page_addr(MEMORY_PAGES) - 2, 4, 0),
+        -(Errno::EFAULT as i32)
+    );
+    assert_eq!(
+        pwrite(cageid, fd, page_addr(MEMORY_PAGES) - 2, 4, 0),
+        -(Errno::EFAULT as i32)
+    );
+    // past the end of the 32-bit linear memory
+    assert_eq!(
+        pread(cageid, fd, u32::MAX as u64 - 1, 4, 0),
+        -(Errno::EFAULT as i32)
+    );
+
+    // nothing was written to the file
+    assert_eq!(std::fs::read(host_path(&path)).unwrap(), CONTENTS);
+}
+
+#[test]
+fn test_readv_and_writev_buffers_must_be_mapped() {
+    let (cageid, base, fd, path) = setup();
+    let readv = |iovcnt: u64| {
+        readv_syscall(
+            cageid, fd, cageid, IOV_ADDR, cageid, iovcnt, cageid, 0, 0, 0, 0, 0, 0,
+        )
+    };
+    let writev = |iov: u64, iovcnt: u64| {
+        writev_syscall(
+            cageid, fd, cageid, iov, cageid, iovcnt, cageid, 0, 0, 0, 0, 0, 0,
+        )
+    };
+
+    store_iovecs(base, &[(BUF_ADDR, 4), (BUF_ADDR + 100, 6)]);
+    assert_eq!(readv(2), CONTENTS.len() as i32);
+    assert_eq!(guest_bytes(base, BUF_ADDR, 4), b"0123");
+    assert_eq!(guest_bytes(base, BUF_ADDR + 100, 6), b"456789");
+
+    // the second buffer isn't mapped
+    store_iovecs(base, &[(BUF_ADDR, 4), (page_addr(MEMORY_PAGES + 1), 4)]);
+    assert_eq!(readv(2), -(Errno::EFAULT as i32));
+    assert_eq!(writev(IOV_ADDR, 2), -(Errno::EFAULT as i32));
+    // the iovec array itself isn't mapped
+    assert_eq!(
+        writev(page_addr(MEMORY_PAGES) - 8, 2),
+        -(Errno::EFAULT as i32)
+    );
+
+    assert_eq!(std::fs::read(host_path(&path)).unwrap(), CONTENTS);
+}
@@ -0,0 +1,172 @@
+mod common;
+
+use cage::memory::mem_helper::{fork_vmmap_helper, init_vmmap_helper};
+use common::{fork_cage, load, reserve_memory, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{shmat_syscall, shmctl_syscall, shmdt_syscall, shmget_syscall};
+use rawposix::syscalls::sys_calls::exit_syscall;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    IPC_CREAT, IPC_EXCL, IPC_PRIVATE, IPC_RMID, IPC_STAT, PAGESIZE, SHM_DEST,
+};
+use sysdefs::data::fs_struct::ShmidsStruct;
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 32;
+/// Guest address segments are attached at, well inside the cage memory
+const SHM_ADDR: u32 = 16 * PAGESIZE;
+
+/// Forks a new cage from the init cage with `MEMORY_PAGES` pages of linear memory. Returns the cage id
+/// and the host base address of the cage memory.
+fn init_test_cage() -> (u64, *mut u8) {
+    common::init_test_cage(INIT_CAGEID, MEMORY_PAGES)
+}
+
+fn shmget(cageid: u64, key: i32, size: usize, shmflg: i32) -> i32 {
+    shmget_syscall(
+        cageid,
+        key as u64,
+        cageid,
+        size as u64,
+        cageid,
+        shmflg as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn shmat(cageid: u64, shmid: i32, shmaddr: u32) -> i32 {
+    shmat_syscall(
+        cageid,
+        shmid as u64,
+        cageid,
+        shmaddr as u64,
+        cageid,
+        0,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn shmdt(cageid: u64, shmaddr: u32) -> i32 {
+    shmdt_syscall(cageid, shmaddr as u64, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0)
+}
+
+fn shmctl(cageid: u64, shmid: i32, cmd: i32, buf: u64) -> i32 {
+    shmctl_syscall(
+        cageid,
+        shmid as u64,
+        cageid,
+        cmd as u64,
+        cageid,
+        buf,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+/// Returns the segment's `shmid_ds`, read through IPC_STAT into the first page of the cage memory
+fn shm_stat(cageid: u64, base: *mut u8, shmid: i32) -> ShmidsStruct {
+    assert_eq!(shmctl(cageid, shmid, IPC_STAT, 64), 0);
+    load::<ShmidsStruct>(base, 64)
+}
+
+#[test]
+fn test_shm_shared_between_cages() {
+    let (cage_a, base_a) = init_test_cage();
+    let (cage_b, base_b) = init_test_cage();
+    let key = 0x5eed;
+
+    assert_eq!(
+        shmget(cage_b, key, 2 * PAGESIZE as usize, 0),
+        -(Errno::ENOENT as i32)
+    );
+    let shmid = shmget(cage_a, key, 2 * PAGESIZE as usize, IPC_CREAT | 0o600);
+    assert!(shmid > 0);
+    // the other cage finds the same segment by its key
+    assert_eq!(shmget(cage_b, key, 0, 0), shmid);
+    assert_eq!(
+        shmget(
+            cage_b,
+            key,
+            2 * PAGESIZE as usize,
+            IPC_CREAT | IPC_EXCL | 0o600
+        ),
+        -(Errno::EEXIST as i32)
+    );
+    assert_eq!(
+        shmget(cage_b, key, 3 * PAGESIZE as usize, 0),
+        -(Errno::EINVAL as i32)
+    );
+
+    assert_eq!(shmat(cage_a, shmid, SHM_ADDR), SHM_ADDR as i32);
+    assert_eq!(
+        shmat(cage_b, shmid, SHM_ADDR + PAGESIZE),
+        (SHM_ADDR + PAGESIZE) as i32
+    );
+    assert_eq!(shmat(cage_b, shmid, SHM_ADDR + 1), -(Errno::EINVAL as i32));
+
+    // both cages see the same pages
+    unsafe {
+        *base_a.add(SHM_ADDR as usize + PAGESIZE as usize) = 42;
+        assert_eq!(*base_b.add((SHM_ADDR + 2 * PAGESIZE) as usize), 42);
+    }
+    let info = shm_stat(cage_a, base_a, shmid);
+    assert_eq!(info.shm_nattch, 2);
+    assert_eq!(info.shm_segsz, 2 * PAGESIZE);
+
+    // after IPC_RMID the key is released, but the segment lives on while attached
+    assert_eq!(shmctl(cage_a, shmid, IPC_RMID, 0), 0);
+    assert_eq!(shmget(cage_b, key, 0, 0), -(Errno::ENOENT as i32));
+    let info = shm_stat(cage_b, base_b, shmid);
+    assert_eq!(info.shm_perm.mode as i32 & SHM_DEST, SHM_DEST);
+
+    assert_eq!(shmdt(cage_a, SHM_ADDR), 0);
+    assert_eq!(shmdt(cage_a, SHM_ADDR), -(Errno::EINVAL as i32));
+    assert_eq!(shm_stat(cage_b, base_b, shmid).shm_nattch, 1);
+    assert_eq!(shmdt(cage_b, SHM_ADDR + PAGESIZE), 0);
+    // the last detach destroyed the segment
+    assert_eq!(shmctl(cage_b, shmid, IPC_STAT, 0), -(Errno::EINVAL as i32));
+}
+
+#[test]
+fn test_fork_shares_and_exit_detaches() {
+    let (parent, parent_base) = init_test_cage();
+    let shmid = shmget(parent, IPC_PRIVATE, PAGESIZE as usize, 0o600);
+    assert!(shmid > 0);
+    assert_eq!(shmat(parent, shmid, SHM_ADDR), SHM_ADDR as i32);
+
+    // fork the way wasmtime does: the child's memory is filled in from the parent's vmmap
+    let child = fork_cage(parent);
+    let child_base = reserve_memory(MEMORY_PAGES);
+    init_vmmap_helper(child, child_base as usize, None);
+    fork_vmmap_helper(parent, child);
+    assert_eq!(shm_stat(parent, parent_base, shmid).shm_nattch, 2);
+
+    // the segment is shared with the child, not copied
+    unsafe {
+        *child_base.add(SHM_ADDR as usize) = 7;
+        assert_eq!(*parent_base.add(SHM_ADDR as usize), 7);
+    }
+
+    exit_syscall(child, 0, child, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+    assert_eq!(shm_stat(parent, parent_base, shmid).shm_nattch, 1);
+
+    assert_eq!(shmctl(parent, shmid, IPC_RMID, 0), 0);
+    assert_eq!(shmdt(parent, SHM_ADDR), 0);
+    assert_eq!(shmctl(parent, shmid, IPC_STAT, 0), -(Errno::EINVAL as i32));
+}
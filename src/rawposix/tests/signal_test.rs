@@ -0,0 +1,335 @@
+mod common;
+
+use cage::get_cage;
+use cage::signal::{
+    lind_check_no_pending_signal, lind_get_deliverable_signal, lind_signal_handler_return,
+    sigmask_bit, SignalDelivery,
+};
+use common::{fork_cage, init_test_cage, load, store, INIT_CAGEID};
+use rawposix::syscalls::sys_calls::{
+    exit_syscall, kill_syscall, setitimer_syscall, sigaction_syscall, sigprocmask_syscall,
+    waitpid_syscall,
+};
+use std::sync::atomic::Ordering::SeqCst;
+use std::time::Duration;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::sys_const::{
+    ITIMER_REAL, SA_RESETHAND, SIGALRM, SIGCHLD, SIGKILL, SIGTERM, SIGUSR1, SIGUSR2, SIG_BLOCK,
+    SIG_DFL, SIG_SETMASK, SIG_UNBLOCK,
+};
+use sysdefs::data::fs_struct::{ITimerVal, SigactionStruct, SigsetType, TimeVal};
+
+/// Pages of linear memory of every test cage
+const MEMORY_PAGES: u32 = 1;
+
+/// Guest address of the new value passed to a call
+const NEW_ADDR: u64 = 64;
+/// Guest address where a call saves the old value
+const OLD_ADDR: u64 = 128;
+
+fn sigaction(cageid: u64, sig: i32, act: u64, oact: u64) -> i32 {
+    sigaction_syscall(
+        cageid, sig as u64, cageid, act, cageid, oact, cageid, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+fn sigprocmask(cageid: u64, base: *mut u8, how: i32, set: SigsetType) -> SigsetType {
+    store(base, NEW_ADDR, set);
+    assert_eq!(
+        sigprocmask_syscall(
+            cageid, how as u64, cageid, NEW_ADDR, cageid, OLD_ADDR, cageid, 0, 0, 0, 0, 0, 0,
+        ),
+        0
+    );
+    load(base, OLD_ADDR)
+}
+
+fn kill(cageid: u64, pid: i32, sig: i32) -> i32 {
+    kill_syscall(
+        cageid, pid as u64, cageid, sig as u64, cageid, 0, 0, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+/// Arms the interval timer of `cageid`. Returns the previous value of the timer.
+fn setitimer(cageid: u64, base: *mut u8, value: Duration, interval: Duration) -> ITimerVal {
+    let timeval = |d: Duration| TimeVal {
+        tv_sec: d.as_secs() as i64,
+        tv_usec: d.subsec_micros() as i64,
+    };
+    store(
+        base,
+        NEW_ADDR,
+        ITimerVal {
+            it_interval: timeval(interval),
+            it_value: timeval(value),
+        },
+    );
+    assert_eq!(
+        setitimer_syscall(
+            cageid,
+            ITIMER_REAL as u64,
+            cageid,
+            NEW_ADDR,
+            cageid,
+            OLD_ADDR,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ),
+        0
+    );
+    load(base, OLD_ADDR)
+}
+
+fn pending(cageid: u64) -> SigsetType {
+    get_cage(cageid).unwrap().pending_signals.load(SeqCst)
+}
+
+/// Installs `handler` for `sig` on `cageid`
+fn set_handler(cageid: u64, base: *mut u8, sig: i32, handler: u32) {
+    store(
+        base,
+        NEW_ADDR,
+        SigactionStruct {
+            sa_handler: handler,
+            sa_mask: 0,
+            sa_flags: 0,
+        },
+    );
+    assert_eq!(sigaction(cageid, sig, NEW_ADDR, 0), 0);
+}
+
+/// Gives the cages a process group of their own, the way `setpgid` would
+fn new_process_group(cageids: &[u64]) -> u64 {
+    let pgid = cageids[0];
+    for cageid in cageids {
+        get_cage(*cageid).unwrap().pgid.store(pgid, SeqCst);
+    }
+    pgid
+}
+
+#[test]
+fn test_sigaction_round_trip() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+
+    // signals start with the default action
+    assert_eq!(sigaction(cageid, SIGUSR1, 0, OLD_ADDR), 0);
+    let old: SigactionStruct = load(base, OLD_ADDR);
+    assert_eq!(old.sa_handler, SIG_DFL);
+
+    let mask = sigmask_bit(SIGUSR2) | sigmask_bit(SIGKILL);
+    store(
+        base,
+        NEW_ADDR,
+        SigactionStruct {
+            sa_handler: 5,
+            sa_mask: mask,
+            sa_flags: SA_RESETHAND,
+        },
+    );
+    assert_eq!(sigaction(cageid, SIGUSR1, NEW_ADDR, OLD_ADDR), 0);
+    assert_eq!(load::<SigactionStruct>(base, OLD_ADDR).sa_handler, SIG_DFL);
+
+    assert_eq!(sigaction(cageid, SIGUSR1, 0, OLD_ADDR), 0);
+    let old: SigactionStruct = load(base, OLD_ADDR);
+    assert_eq!(old.sa_handler, 5);
+    // SIGKILL can never be blocked, so it is dropped from the mask
+    assert_eq!(old.sa_mask, sigmask_bit(SIGUSR2));
+    assert_eq!(old.sa_flags, SA_RESETHAND);
+
+    // SA_RESETHAND restores the default action once the signal is delivered
+    assert_eq!(kill(cageid, cageid as i32, SIGUSR1), 0);
+    match lind_get_deliverable_signal(cageid) {
+        Some(SignalDelivery::Handler {
+            signo,
+            handler,
+            oldmask,
+        }) => {
+            assert_eq!((signo, handler), (SIGUSR1, 5));
+            lind_signal_handler_return(cageid, oldmask);
+        }
+        other => panic!("unexpected delivery {:?}", other),
+    }
+    assert_eq!(sigaction(cageid, SIGUSR1, 0, OLD_ADDR), 0);
+    assert_eq!(load::<SigactionStruct>(base, OLD_ADDR).sa_handler, SIG_DFL);
+
+    assert_eq!(
+        sigaction(cageid, SIGKILL, NEW_ADDR, 0),
+        -(Errno::EINVAL as i32)
+    );
+    assert_eq!(sigaction(cageid, 0, 0, OLD_ADDR), -(Errno::EINVAL as i32));
+    assert_eq!(sigaction(cageid, 65, 0, OLD_ADDR), -(Errno::EINVAL as i32));
+}
+
+#[test]
+fn test_blocked_signals_stay_pending() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+    set_handler(cageid, base, SIGUSR1, 7);
+    set_handler(cageid, base, SIGUSR2, 8);
+
+    let old = sigprocmask(cageid, base, SIG_BLOCK, sigmask_bit(SIGUSR1));
+    assert_eq!(old, 0);
+    assert_eq!(kill(cageid, cageid as i32, SIGUSR1), 0);
+    assert_eq!(pending(cageid), sigmask_bit(SIGUSR1));
+    assert!(!lind_check_no_pending_signal(cageid));
+    // the signal is pending, but blocked
+    assert!(lind_get_deliverable_signal(cageid).is_none());
+
+    // an unblocked signal is delivered past the blocked one
+    assert_eq!(kill(cageid, cageid as i32, SIGUSR2), 0);
+    match lind_get_deliverable_signal(cageid) {
+        Some(SignalDelivery::Handler { signo, oldmask, .. }) => {
+            assert_eq!(signo, SIGUSR2);
+            lind_signal_handler_return(cageid, oldmask);
+        }
+        other => panic!("unexpected delivery {:?}", other),
+    }
+    assert_eq!(pending(cageid), sigmask_bit(SIGUSR1));
+
+    let old = sigprocmask(cageid, base, SIG_UNBLOCK, sigmask_bit(SIGUSR1));
+    assert_eq!(old, sigmask_bit(SIGUSR1));
+    match lind_get_deliverable_signal(cageid) {
+        Some(SignalDelivery::Handler {
+            signo,
+            handler,
+            oldmask,
+        }) => {
+            assert_eq!((signo, handler), (SIGUSR1, 7));
+            lind_signal_handler_return(cageid, oldmask);
+        }
+        other => panic!("unexpected delivery {:?}", other),
+    }
+    assert_eq!(pending(cageid), 0);
+    assert!(lind_check_no_pending_signal(cageid));
+
+    let set = sigmask_bit(SIGUSR1) | sigmask_bit(SIGUSR2);
+    assert_eq!(sigprocmask(cageid, base, SIG_SETMASK, set), 0);
+    assert_eq!(sigprocmask(cageid, base, SIG_SETMASK, 0), set);
+    store(base, NEW_ADDR, set);
+    assert_eq!(
+        sigprocmask_syscall(cageid, 3, cageid, NEW_ADDR, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EINVAL as i32)
+    );
+}
+
+#[test]
+fn test_kill_self_and_child() {
+    let (parent, _) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+    let (child, _) = init_test_cage(parent, MEMORY_PAGES);
+
+    assert_eq!(kill(parent, parent as i32, SIGUSR1), 0);
+    assert_eq!(pending(parent), sigmask_bit(SIGUSR1));
+    assert_eq!(pending(child), 0);
+
+    assert_eq!(kill(parent, child as i32, SIGUSR2), 0);
+    assert_eq!(pending(child), sigmask_bit(SIGUSR2));
+    assert_eq!(pending(parent), sigmask_bit(SIGUSR1));
+
+    // signal 0 only checks that the target exists
+    assert_eq!(kill(parent, child as i32, 0), 0);
+    assert_eq!(pending(child), sigmask_bit(SIGUSR2));
+
+    assert_eq!(kill(parent, 1000, SIGUSR1), -(Errno::ESRCH as i32));
+    assert_eq!(kill(parent, child as i32, 100), -(Errno::EINVAL as i32));
+}
+
+#[test]
+fn test_kill_process_group() {
+    let parent = fork_cage(INIT_CAGEID);
+    let child = fork_cage(parent);
+    let grandchild = fork_cage(child);
+    let other = fork_cage(INIT_CAGEID);
+    let pgid = new_process_group(&[parent, child, grandchild]);
+
+    // -pgid signals every cage of the group
+    assert_eq!(kill(other, -(pgid as i32), SIGUSR1), 0);
+    for cageid in [parent, child, grandchild] {
+        assert_eq!(pending(cageid), sigmask_bit(SIGUSR1));
+    }
+    assert_eq!(pending(other), 0);
+
+    // 0 signals the group of the calling cage
+    assert_eq!(kill(grandchild, 0, SIGUSR2), 0);
+    for cageid in [parent, child, grandchild] {
+        assert_eq!(pending(cageid), sigmask_bit(SIGUSR1) | sigmask_bit(SIGUSR2));
+    }
+    assert_eq!(pending(other), 0);
+
+    // no cage is in the group
+    assert_eq!(kill(other, -999, 0), -(Errno::ESRCH as i32));
+    assert_eq!(kill(other, -(pgid as i32), 0), 0);
+    // -1 reaches every cage but init
+    assert_eq!(kill(other, -1, 0), 0);
+}
+
+#[test]
+fn test_signal_interrupts_blocked_waitpid() {
+    let parent = fork_cage(INIT_CAGEID);
+    let child = fork_cage(parent);
+    let waiter = std::thread::spawn(move || {
+        waitpid_syscall(parent, child, parent, 0, 0, 0, parent, 0, 0, 0, 0, 0, 0)
+    });
+
+    // SIGCHLD is ignored by default, it doesn't interrupt the wait
+    assert_eq!(kill(INIT_CAGEID, parent as i32, SIGCHLD), 0);
+    std::thread::sleep(Duration::from_millis(50));
+    assert!(!waiter.is_finished());
+
+    assert_eq!(kill(INIT_CAGEID, parent as i32, SIGTERM), 0);
+    assert_eq!(waiter.join().unwrap(), -(Errno::EINTR as i32));
+    // the signal stays pending, to be delivered on return from the syscall
+    assert!(matches!(
+        lind_get_deliverable_signal(parent),
+        Some(SignalDelivery::Terminate { signo: SIGTERM })
+    ));
+
+    exit_syscall(child, 0, child, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+}
+
+#[test]
+fn test_setitimer_expiry() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+
+    let old = setitimer(cageid, base, Duration::from_millis(20), Duration::ZERO);
+    assert_eq!((old.it_value.tv_sec, old.it_value.tv_usec), (0, 0));
+    assert!(lind_check_no_pending_signal(cageid));
+
+    std::thread::sleep(Duration::from_millis(40));
+    // the timer is checked at the next safe point
+    assert!(!lind_check_no_pending_signal(cageid));
+    assert_eq!(pending(cageid), sigmask_bit(SIGALRM));
+    get_cage(cageid).unwrap().pending_signals.store(0, SeqCst);
+
+    // a one-shot timer is disarmed once it expired
+    std::thread::sleep(Duration::from_millis(40));
+    assert!(lind_check_no_pending_signal(cageid));
+
+    // a periodic timer is re-armed
+    setitimer(
+        cageid,
+        base,
+        Duration::from_millis(10),
+        Duration::from_millis(10),
+    );
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!lind_check_no_pending_signal(cageid));
+    get_cage(cageid).unwrap().pending_signals.store(0, SeqCst);
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(!lind_check_no_pending_signal(cageid));
+
+    // disarming returns the remaining time and the interval of the timer
+    let old = setitimer(cageid, base, Duration::ZERO, Duration::ZERO);
+    assert_eq!(old.it_value.tv_sec, 0);
+    assert!(old.it_value.tv_usec <= 10_000);
+    assert_eq!(
+        (old.it_interval.tv_sec, old.it_interval.tv_usec),
+        (0, 10_000)
+    );
+    get_cage(cageid).unwrap().pending_signals.store(0, SeqCst);
+    std::thread::sleep(Duration::from_millis(20));
+    assert!(lind_check_no_pending_signal(cageid));
+}
@@ -0,0 +1,270 @@
+mod common;
+
+use common::{guest_bytes, init_test_cage, load, store, INIT_CAGEID};
+use rawposix::syscalls::net_calls::{
+    bind_syscall, getpeername_syscall, getsockname_syscall, getsockopt_syscall, recv_syscall,
+    recvfrom_syscall, recvmsg_syscall, send_syscall, sendmsg_syscall, sendto_syscall,
+    shutdown_syscall, socket_syscall, socketpair_syscall,
+};
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::net_const::{MSG_CTRUNC, SCM_RIGHTS, SOL_SOCKET};
+use sysdefs::data::fs_struct::{SockPair, WasmCmsghdr, WasmIovec, WasmMsghdr};
+
+/// Pages of linear memory, all readable and writable, given to every test cage
+const MEMORY_PAGES: u32 = 4;
+
+fn socketpair(cageid: u64, base: *mut u8, addr: u64) -> (u64, u64) {
+    assert_eq!(
+        socketpair_syscall(
+            cageid,
+            libc::AF_UNIX as u64,
+            cageid,
+            libc::SOCK_STREAM as u64,
+            cageid,
+            0,
+            cageid,
+            addr,
+            cageid,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    let sv: SockPair = load(base, addr);
+    (sv.sock1 as u64, sv.sock2 as u64)
+}
+
+fn udp_socket(cageid: u64) -> u64 {
+    let fd = socket_syscall(
+        cageid,
+        libc::AF_INET as u64,
+        cageid,
+        libc::SOCK_DGRAM as u64,
+        cageid,
+        0,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    assert!(fd >= 0);
+    fd as u64
+}
+
+/// Lays out at guest address `addr` a message with one iovec over `buf` and, if `controllen` is
+/// not zero, a control buffer right after the header. Returns the guest address of the control
+/// buffer.
+fn store_msghdr(base: *mut u8, addr: u64, buf: u32, buflen: u32, controllen: u32) -> u64 {
+    let iov = addr + std::mem::size_of::<WasmMsghdr>() as u64;
+    let control = iov + std::mem::size_of::<WasmIovec>() as u64;
+    store(
+        base,
+        iov,
+        WasmIovec {
+            iov_base: buf,
+            iov_len: buflen,
+        },
+    );
+    store(
+        base,
+        addr,
+        WasmMsghdr {
+            msg_iov: iov as u32,
+            msg_iovlen: 1,
+            msg_control: if controllen == 0 { 0 } else { control as u32 },
+            msg_controllen: controllen,
+            ..Default::default()
+        },
+    );
+    control
+}
+
+/// Stores a SCM_RIGHTS control message carrying `fd` at guest address `addr`
+fn store_rights(base: *mut u8, addr: u64, fd: i32) {
+    let hdrlen = std::mem::size_of::<WasmCmsghdr>() as u32;
+    store(
+        base,
+        addr,
+        WasmCmsghdr {
+            cmsg_len: hdrlen + 4,
+            cmsg_level: SOL_SOCKET,
+            cmsg_type: SCM_RIGHTS,
+        },
+    );
+    store(base, addr + hdrlen as u64, fd);
+}
+
+fn sendmsg(cageid: u64, fd: u64, msg: u64) -> i32 {
+    sendmsg_syscall(cageid, fd, cageid, msg, cageid, 0, cageid, 0, 0, 0, 0, 0, 0)
+}
+
+fn recvmsg(cageid: u64, fd: u64, msg: u64) -> i32 {
+    recvmsg_syscall(cageid, fd, cageid, msg, cageid, 0, cageid, 0, 0, 0, 0, 0, 0)
+}
+
+#[test]
+fn test_sendmsg_passes_fds_between_cages() {
+    let (sender, sender_base) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+    let (sock1, sock2) = socketpair(sender, sender_base, 8);
+    // the fd handed over, whose peer stays in the sender
+    let (passed, peer) = socketpair(sender, sender_base, 16);
+    let (receiver, receiver_base) = init_test_cage(sender, MEMORY_PAGES);
+
+    store(sender_base, 64, *b"hello");
+    let control = store_msghdr(sender_base, 128, 64, 5, 16);
+    store_rights(sender_base, control, passed as i32);
+    assert_eq!(sendmsg(sender, sock1, 128), 5);
+
+    let control = store_msghdr(receiver_base, 128, 64, 16, 32);
+    assert_eq!(recvmsg(receiver, sock2, 128), 5);
+    assert_eq!(guest_bytes(receiver_base, 64, 5), b"hello");
+    let msg: WasmMsghdr = load(receiver_base, 128);
+    assert_eq!((msg.msg_controllen, msg.msg_flags), (16, 0));
+    let cmsg: WasmCmsghdr = load(receiver_base, control);
+    assert_eq!(
+        (cmsg.cmsg_len, cmsg.cmsg_level, cmsg.cmsg_type),
+        (16, SOL_SOCKET, SCM_RIGHTS)
+    );
+
+    // the receiver gets a virtual fd of its own that reaches the sender's peer
+    let received: i32 = load(receiver_base, control + 12);
+    assert!(received >= 0);
+    assert_ne!(received as u64, passed);
+    assert_eq!(
+        send_syscall(
+            receiver,
+            received as u64,
+            receiver,
+            64,
+            receiver,
+            5,
+            receiver,
+            0,
+            receiver,
+            0,
+            0,
+            0,
+            0
+        ),
+        5
+    );
+    assert_eq!(
+        recv_syscall(sender, peer, sender, 256, sender, 16, sender, 0, sender, 0, 0, 0, 0),
+        5
+    );
+    assert_eq!(guest_bytes(sender_base, 256, 5), b"hello");
+
+    // descriptors that do not fit in the control buffer are dropped and reported as truncated
+    assert_eq!(sendmsg(sender, sock1, 128), 5);
+    store_msghdr(receiver_base, 128, 64, 16, 12);
+    assert_eq!(recvmsg(receiver, sock2, 128), 5);
+    let msg: WasmMsghdr = load(receiver_base, 128);
+    assert_eq!(msg.msg_controllen, 0);
+    assert_eq!(msg.msg_flags & MSG_CTRUNC, MSG_CTRUNC);
+
+    // only open virtual fds of the sender can be passed
+    store_rights(sender_base, control, 900);
+    assert_eq!(sendmsg(sender, sock1, 128), -(Errno::EBADF as i32));
+
+    // once the sender shuts down writing, the receiver reads end of file
+    assert_eq!(
+        shutdown_syscall(
+            sender,
+            sock1,
+            sender,
+            libc::SHUT_WR as u64,
+            sender,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        0
+    );
+    assert_eq!(
+        recv_syscall(
+            receiver, sock2, receiver, 64, receiver, 16, receiver, 0, receiver, 0, 0, 0, 0
+        ),
+        0
+    );
+}
+
+#[test]
+fn test_datagram_addresses_and_options() {
+    let (cageid, base) = init_test_cage(INIT_CAGEID, MEMORY_PAGES);
+    let receiver = udp_socket(cageid);
+    let sender = udp_socket(cageid);
+
+    let mut sin: libc::sockaddr_in = unsafe { std::mem::zeroed() };
+    sin.sin_family = libc::AF_INET as u16;
+    sin.sin_addr.s_addr = u32::from_ne_bytes([127, 0, 0, 1]);
+    store(base, 32, sin);
+    assert_eq!(
+        bind_syscall(cageid, receiver, cageid, 32, cageid, 16, cageid, 0, 0, 0, 0, 0, 0),
+        0
+    );
+
+    // the bound address, with the port picked by the kernel
+    store(base, 128, 128u32);
+    assert_eq!(
+        getsockname_syscall(cageid, receiver, cageid, 32, cageid, 128, cageid, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(load::<u32>(base, 128), 16);
+    let bound: libc::sockaddr_in = load(base, 32);
+    assert_ne!(bound.sin_port, 0);
+    assert_eq!(
+        getpeername_syscall(cageid, receiver, cageid, 32, cageid, 128, cageid, 0, 0, 0, 0, 0, 0),
+        -(Errno::ENOTCONN as i32)
+    );
+
+    store(base, 256, *b"ping");
+    assert_eq!(
+        sendto_syscall(
+            cageid, sender, cageid, 256, cageid, 4, cageid, 0, cageid, 32, cageid, 16, cageid
+        ),
+        4
+    );
+
+    // the source address is truncated to the buffer, but its full length is reported
+    store(base, 128, 8u32);
+    assert_eq!(
+        recvfrom_syscall(
+            cageid, receiver, cageid, 512, cageid, 16, cageid, 0, cageid, 64, cageid, 128, cageid
+        ),
+        4
+    );
+    assert_eq!(guest_bytes(base, 512, 4), b"ping");
+    assert_eq!(load::<u32>(base, 128), 16);
+    assert_eq!(load::<u16>(base, 64), libc::AF_INET as u16);
+
+    store(base, 128, 4u32);
+    assert_eq!(
+        getsockopt_syscall(
+            cageid,
+            receiver,
+            cageid,
+            libc::SOL_SOCKET as u64,
+            cageid,
+            libc::SO_TYPE as u64,
+            cageid,
+            64,
+            cageid,
+            128,
+            cageid,
+            0,
+            0
+        ),
+        0
+    );
+    assert_eq!(load::<i32>(base, 64), libc::SOCK_DGRAM);
+}
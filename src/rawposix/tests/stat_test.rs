@@ -0,0 +1,264 @@
+mod common;
+
+use common::{fork_cage, host_path, init_memory, load, store_str, test_dir, INIT_CAGEID};
+use rawposix::syscalls::fs_calls::{
+    access_syscall, fstat_syscall, fstatfs_syscall, ftruncate_syscall, lseek_syscall, stat_syscall,
+    statfs_syscall, truncate_syscall,
+};
+use std::os::fd::IntoRawFd;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{FDKIND_KERNEL, F_OK, R_OK, SEEK_CUR, SEEK_END, SEEK_SET, X_OK};
+use sysdefs::data::fs_struct::{FSData, StatData};
+
+/// Pages of linear memory reserved for every test cage
+const RESERVED_PAGES: u32 = 8;
+/// Pages at the start of the linear memory that are readable and writable
+const MEMORY_PAGES: u32 = 4;
+
+/// Guest address where the path of a call is kept
+const PATH_ADDR: u64 = 64;
+/// Guest address of the buffer a call fills in
+const BUF_ADDR: u64 = 1024;
+
+/// Contents every test file starts with
+const CONTENTS: &[u8] = b"0123456789";
+
+/// Creates a new cage and a file holding `CONTENTS` in a new test directory. Returns the cage id, the
+/// host base address of the cage memory and the path of the file from the cage's perspective.
+fn setup() -> (u64, *mut u8, String) {
+    let cageid = fork_cage(INIT_CAGEID);
+    let base = init_memory(cageid, RESERVED_PAGES, MEMORY_PAGES);
+    let path = format!("{}/file", test_dir("stat_test"));
+    std::fs::write(host_path(&path), CONTENTS).unwrap();
+    (cageid, base, path)
+}
+
+/// Opens `path` on the host for reading and writing, and gives `cageid` a virtual fd for it
+fn open_file(cageid: u64, path: &str) -> u64 {
+    let file = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(host_path(path))
+        .unwrap();
+    fdtables::get_unused_virtual_fd(cageid, FDKIND_KERNEL, file.into_raw_fd() as u64, false, 0)
+        .unwrap()
+}
+
+fn stat(cageid: u64, base: *mut u8, path: &str, buf: u64) -> i32 {
+    store_str(base, PATH_ADDR, path);
+    stat_syscall(
+        cageid, PATH_ADDR, cageid, buf, cageid, 0, 0, 0, 0, 0, 0, 0, 0,
+    )
+}
+
+fn fstat(cageid: u64, fd: u64, buf: u64) -> i32 {
+    fstat_syscall(cageid, fd, cageid, buf, cageid, 0, 0, 0, 0, 0, 0, 0, 0)
+}
+
+fn lseek(cageid: u64, fd: u64, offset: i64, whence: i32) -> i32 {
+    lseek_syscall(
+        cageid,
+        fd,
+        cageid,
+        offset as u64,
+        cageid,
+        whence as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+fn file_size(cageid: u64, base: *mut u8, path: &str) -> usize {
+    assert_eq!(stat(cageid, base, path, BUF_ADDR), 0);
+    load::<StatData>(base, BUF_ADDR).st_size
+}
+
+#[test]
+fn test_stat_and_fstat() {
+    let (cageid, base, path) = setup();
+
+    assert_eq!(stat(cageid, base, &path, BUF_ADDR), 0);
+    let statdata: StatData = load(base, BUF_ADDR);
+    assert_eq!(statdata.st_size, CONTENTS.len());
+    assert_eq!(statdata.st_mode & libc::S_IFMT, libc::S_IFREG);
+
+    let fd = open_file(cageid, &path);
+    assert_eq!(fstat(cageid, fd, BUF_ADDR), 0);
+    let fstatdata: StatData = load(base, BUF_ADDR);
+    assert_eq!(fstatdata.st_ino, statdata.st_ino);
+    assert_eq!(fstatdata.st_size, CONTENTS.len());
+
+    let missing = format!("{}.missing", path);
+    assert_eq!(
+        stat(cageid, base, &missing, BUF_ADDR),
+        -(Errno::ENOENT as i32)
+    );
+    assert_eq!(fstat(cageid, 1000, BUF_ADDR), -(Errno::EBADF as i32));
+    assert_eq!(stat(cageid, base, &path, 0), -(Errno::EFAULT as i32));
+    assert_eq!(fstat(cageid, fd, 0), -(Errno::EFAULT as i32));
+}
+
+#[test]
+fn test_statfs_and_fstatfs() {
+    let (cageid, base, path) = setup();
+
+    store_str(base, PATH_ADDR, &path);
+    assert_eq!(
+        statfs_syscall(cageid, PATH_ADDR, cageid, BUF_ADDR, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    let fsdata: FSData = load(base, BUF_ADDR);
+    assert!(fsdata.f_bsize > 0);
+
+    let fd = open_file(cageid, &path);
+    assert_eq!(
+        fstatfs_syscall(cageid, fd, cageid, BUF_ADDR, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    let ffsdata: FSData = load(base, BUF_ADDR);
+    assert_eq!(ffsdata.f_type, fsdata.f_type);
+    assert_eq!(ffsdata.f_fsid, fsdata.f_fsid);
+
+    assert_eq!(
+        fstatfs_syscall(cageid, 1000, cageid, BUF_ADDR, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::EBADF as i32)
+    );
+}
+
+#[test]
+fn test_access() {
+    let (cageid, base, path) = setup();
+    let access = |path: &str, amode: u32| {
+        store_str(base, PATH_ADDR, path);
+        access_syscall(
+            cageid,
+            PATH_ADDR,
+            cageid,
+            amode as u64,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        )
+    };
+
+    assert_eq!(access(&path, F_OK), 0);
+    assert_eq!(access(&path, R_OK), 0);
+    // no execute bit is set, so even root may not execute it
+    assert_eq!(access(&path, X_OK), -(Errno::EACCES as i32));
+    assert_eq!(
+        access(&format!("{}.missing", path), F_OK),
+        -(Errno::ENOENT as i32)
+    );
+}
+
+#[test]
+fn test_lseek() {
+    let (cageid, _base, path) = setup();
+    let fd = open_file(cageid, &path);
+
+    assert_eq!(lseek(cageid, fd, 4, SEEK_SET), 4);
+    assert_eq!(lseek(cageid, fd, 2, SEEK_CUR), 6);
+    assert_eq!(lseek(cageid, fd, 0, SEEK_END), CONTENTS.len() as i32);
+    assert_eq!(lseek(cageid, fd, -100, SEEK_CUR), -(Errno::EINVAL as i32));
+    assert_eq!(lseek(cageid, 1000, 0, SEEK_SET), -(Errno::EBADF as i32));
+
+    // the offset is valid for the file, but doesn't fit in the return value
+    assert_eq!(
+        lseek(cageid, fd, i32::MAX as i64 + 1, SEEK_SET),
+        -(Errno::EOVERFLOW as i32)
+    );
+    assert_eq!(lseek(cageid, fd, i32::MAX as i64, SEEK_SET), i32::MAX);
+}
+
+#[test]
+fn test_truncate_and_ftruncate() {
+    let (cageid, base, path) = setup();
+
+    store_str(base, PATH_ADDR, &path);
+    assert_eq!(
+        truncate_syscall(cageid, PATH_ADDR, cageid, 4, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(file_size(cageid, base, &path), 4);
+    assert_eq!(std::fs::read(host_path(&path)).unwrap(), &CONTENTS[..4]);
+
+    let fd = open_file(cageid, &path);
+    assert_eq!(
+        ftruncate_syscall(cageid, fd, cageid, 20, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        0
+    );
+    assert_eq!(file_size(cageid, base, &path), 20);
+
+    assert_eq!(
+        ftruncate_syscall(
+            cageid,
+            fd,
+            cageid,
+            -1i64 as u64,
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        -(Errno::EINVAL as i32)
+    );
+    store_str(base, PATH_ADDR, &format!("{}.missing", path));
+    assert_eq!(
+        truncate_syscall(cageid, PATH_ADDR, cageid, 4, cageid, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::ENOENT as i32)
+    );
+}
+
+// Only the secure build checks buffers against the vmmap of the cage
+#[cfg(feature = "secure")]
+#[test]
+fn test_buffers_must_be_mapped() {
+    use common::page_addr;
+
+    let (cageid, base, path) = setup();
+    let fd = open_file(cageid, &path);
+
+    assert_eq!(
+        stat(cageid, base, &path, page_addr(MEMORY_PAGES + 1)),
+        -(Errno::EFAULT as i32)
+    );
+    // the buffer runs past the end of the mapped memory
+    assert_eq!(
+        fstat(cageid, fd, page_addr(MEMORY_PAGES) - 8),
+        -(Errno::EFAULT as i32)
+    );
+    assert_eq!(
+        fstatfs_syscall(
+            cageid,
+            fd,
+            cageid,
+            page_addr(MEMORY_PAGES + 1),
+            cageid,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0
+        ),
+        -(Errno::EFAULT as i32)
+    );
+}
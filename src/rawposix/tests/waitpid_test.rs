@@ -0,0 +1,198 @@
+mod common;
+
+use cage::signal::lind_set_termination_signal;
+use common::{fork_cage, init_rawposix, init_test_cage, load, INIT_CAGEID};
+use rawposix::syscalls::sys_calls::{exit_syscall, getppid_syscall, waitpid_syscall};
+use std::collections::HashSet;
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::sys_const::SIGKILL;
+
+// All tests share the init cage, which also reaps orphans, so they must not run concurrently
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn setup() -> std::sync::MutexGuard<'static, ()> {
+    init_rawposix();
+    SERIAL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+fn exit(cageid: u64, status: i32) {
+    exit_syscall(cageid, status as u64, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
+}
+
+fn waitpid(cageid: u64, pid: i32, options: i32) -> i32 {
+    waitpid_syscall(
+        cageid,
+        pid as u64,
+        cageid,
+        0,
+        0,
+        options as u64,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+#[test]
+fn test_waitpid_stress_many_children() {
+    let _guard = setup();
+    const CHILDREN: usize = 300;
+
+    let children: Vec<u64> = (0..CHILDREN).map(|_| fork_cage(INIT_CAGEID)).collect();
+    let handles: Vec<_> = children
+        .iter()
+        .map(|&child| {
+            thread::spawn(move || {
+                thread::sleep(Duration::from_micros(child % 7 * 100));
+                exit(child, 0);
+            })
+        })
+        .collect();
+
+    let mut reaped = HashSet::new();
+    for _ in 0..CHILDREN {
+        let ret = waitpid(INIT_CAGEID, -1, 0);
+        assert!(ret > 0, "waitpid failed with {}", ret);
+        assert!(reaped.insert(ret as u64), "cage {} reaped twice", ret);
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    assert_eq!(reaped, children.into_iter().collect());
+    assert_eq!(waitpid(INIT_CAGEID, -1, 0), -(Errno::ECHILD as i32));
+}
+
+#[test]
+fn test_waitpid_specific_child_and_wnohang() {
+    let _guard = setup();
+
+    let first = fork_cage(INIT_CAGEID);
+    let second = fork_cage(INIT_CAGEID);
+
+    // no child has exited yet
+    assert_eq!(waitpid(INIT_CAGEID, -1, libc::WNOHANG), 0);
+    assert_eq!(waitpid(INIT_CAGEID, second as i32, libc::WNOHANG), 0);
+
+    let handle = thread::spawn(move || {
+        exit(first, 0);
+        thread::sleep(Duration::from_millis(20));
+        exit(second, 0);
+    });
+
+    // blocks until `second` exits, even though `first` exited before
+    assert_eq!(waitpid(INIT_CAGEID, second as i32, 0), second as i32);
+    assert_eq!(waitpid(INIT_CAGEID, 0, 0), first as i32);
+    handle.join().unwrap();
+
+    // not our children anymore
+    assert_eq!(
+        waitpid(INIT_CAGEID, first as i32, 0),
+        -(Errno::ECHILD as i32)
+    );
+    assert_eq!(
+        waitpid(INIT_CAGEID, -1, libc::WNOHANG),
+        -(Errno::ECHILD as i32)
+    );
+    // no cage in process group 12345
+    assert_eq!(waitpid(INIT_CAGEID, -12345, 0), -(Errno::ECHILD as i32));
+    assert_eq!(
+        waitpid(INIT_CAGEID, -1, 0x4000_0000),
+        -(Errno::EINVAL as i32)
+    );
+}
+
+#[test]
+fn test_wait_status_encoding() {
+    let _guard = setup();
+    const STATUS_ADDR: u64 = 64;
+
+    let (parent, base) = init_test_cage(INIT_CAGEID, 1);
+    let waitstatus = |child: u64| {
+        assert_eq!(
+            waitpid_syscall(
+                parent,
+                child,
+                parent,
+                STATUS_ADDR,
+                parent,
+                0,
+                parent,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            ),
+            child as i32
+        );
+        load::<i32>(base, STATUS_ADDR)
+    };
+
+    let exited = fork_cage(parent);
+    exit(exited, 3);
+    let status = waitstatus(exited);
+    assert!(libc::WIFEXITED(status));
+    assert_eq!(libc::WEXITSTATUS(status), 3);
+
+    // a cage killed by a signal exits with the signal number as its code
+    let killed = fork_cage(parent);
+    lind_set_termination_signal(killed, SIGKILL);
+    exit(killed, SIGKILL);
+    let status = waitstatus(killed);
+    assert!(!libc::WIFEXITED(status));
+    assert!(libc::WIFSIGNALED(status));
+    assert_eq!(libc::WTERMSIG(status), SIGKILL);
+
+    exit(parent, 0);
+    assert_eq!(waitpid(INIT_CAGEID, parent as i32, 0), parent as i32);
+}
+
+#[test]
+fn test_orphans_are_reaped_by_init() {
+    let _guard = setup();
+    const GRANDCHILDREN: usize = 100;
+
+    let parent = fork_cage(INIT_CAGEID);
+    let grandchildren: Vec<u64> = (0..GRANDCHILDREN).map(|_| fork_cage(parent)).collect();
+
+    // half of the grandchildren become zombies of `parent`, the others are still running when it exits
+    for &grandchild in &grandchildren[..GRANDCHILDREN / 2] {
+        exit(grandchild, 0);
+    }
+    exit(parent, 0);
+
+    for &grandchild in &grandchildren[GRANDCHILDREN / 2..] {
+        assert_eq!(
+            getppid_syscall(grandchild, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+            INIT_CAGEID as i32
+        );
+    }
+    let handles: Vec<_> = grandchildren[GRANDCHILDREN / 2..]
+        .iter()
+        .map(|&grandchild| thread::spawn(move || exit(grandchild, 0)))
+        .collect();
+
+    let mut reaped = HashSet::new();
+    for _ in 0..=GRANDCHILDREN {
+        let ret = waitpid(INIT_CAGEID, -1, 0);
+        assert!(ret > 0, "waitpid failed with {}", ret);
+        reaped.insert(ret as u64);
+    }
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    let mut expected: HashSet<u64> = grandchildren.into_iter().collect();
+    expected.insert(parent);
+    assert_eq!(reaped, expected);
+    assert_eq!(waitpid(INIT_CAGEID, -1, 0), -(Errno::ECHILD as i32));
+}
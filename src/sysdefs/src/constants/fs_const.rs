@@ -11,6 +11,8 @@ pub const PATH_MAX: usize = 4096;
 
 // ===== Lind specific
 pub const FDKIND_KERNEL: u32 = 0;
+pub const FDKIND_LOOPBACK: u32 = 1; // In-memory socket, `underfd` is its id in rawposix's loopback table
+pub const FDKIND_PIPE: u32 = 2; // In-memory pipe end, `underfd` is its id in rawposix's pipe table
 /// Maximum cage id determines how many processes can exist simultaneously in the RawPOSIX
 /// `Vec` in Rust is indexed using `usize` not `u64`
 pub const MAX_CAGEID: usize = 1024;
@@ -27,6 +29,7 @@ pub const STARTINGFD: i32 = 0; // Starting file descriptor number
 pub const MAXFD: i32 = 1024; // Maximum number of file descriptors
 pub const STARTINGPIPE: i32 = 0; // Starting pipe descriptor number
 pub const MAXPIPE: i32 = 1024; // Maximum number of pipes
+pub const IOV_MAX: i32 = 1024; // Maximum number of iovec entries in readv/writev
 
 // ===== Inode Constants =====
 pub const ROOTDIRECTORYINODE: usize = 1; // Root directory inode number
@@ -34,6 +37,7 @@ pub const STREAMINODE: usize = 2; // Stream inode number
 
 // ===== Pipe Constants =====
 pub const PIPE_CAPACITY: usize = 65536; // Maximum pipe buffer size
+pub const PIPE_BUF: usize = 4096; // Writes up to this size are atomic
 
 // ===== File Access Permission Flags =====
 pub const F_OK: u32 = 0; // Test for existence
@@ -96,10 +100,20 @@ pub const F_GETSIG: i32 = 11;
 pub const F_SETLEASE: i32 = 1024;
 pub const F_GETLEASE: i32 = 1025;
 pub const F_NOTIFY: i32 = 1026;
+pub const F_DUPFD_CLOEXEC: i32 = 1030;
+
+//Lock types for fcntl record locks
+pub const F_RDLCK: i16 = 0;
+pub const F_WRLCK: i16 = 1;
+pub const F_UNLCK: i16 = 2;
 
 //Commands for IOCTL
 pub const FIONBIO: u32 = 21537;
 pub const FIOASYNC: u32 = 21586;
+pub const FIONREAD: u32 = 21531;
+pub const TIOCGWINSZ: u32 = 21523;
+pub const FIONCLEX: u32 = 21584;
+pub const FIOCLEX: u32 = 21585;
 
 //File types for open/stat etc.
 pub const S_IFBLK: i32 = 0o60000;
@@ -2,10 +2,12 @@ pub mod err_const;
 pub mod fs_const;
 pub mod net_const;
 pub mod sys_const;
+pub mod syscall_const;
 pub mod threei_const;
 
 pub use err_const::*;
 pub use fs_const::*;
 pub use net_const::*;
 pub use sys_const::*;
+pub use syscall_const::*;
 pub use threei_const::*;
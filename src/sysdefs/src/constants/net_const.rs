@@ -263,6 +263,13 @@ pub const MSG_BATCH: i32 = 0x40000; // sendmmsg(): more messages coming
 pub const MSG_EOF: i32 = MSG_FIN; // Alias for MSG_FIN
 pub const MSG_NO_SHARED_FRAGS: i32 = 0x80000; // sendpage() internal: no shared frags
 pub const MSG_SENDPAGE_DECRYPTED: i32 = 0x100000; // sendpage() internal: page needs encryption
+pub const MSG_CMSG_CLOEXEC: i32 = 0x40000000; // Set close-on-exec on fds received via SCM_RIGHTS
+
+// ===== Ancillary Data Types =====
+// Source: include/linux/socket.h
+pub const SCM_RIGHTS: i32 = 0x01; // Pass file descriptors
+pub const SCM_CREDENTIALS: i32 = 0x02; // Pass credentials
+pub const SCM_MAX_FD: usize = 253; // Most fds one SCM_RIGHTS message can pass (include/net/scm.h)
 
 // ===== Shutdown Constants =====
 // Source: include/linux/socket.h
@@ -298,6 +305,9 @@ pub const SO_RCVTIMEO_OLD: i32 = 20; // Receive timeout (old)
 pub const SO_SNDTIMEO_OLD: i32 = 21; // Send timeout (old)
 pub const SO_PEERNAME: i32 = 28; // Name of connected peer
 pub const SO_ACCEPTCONN: i32 = 30; // Socket has had listen()
+pub const SO_PROTOCOL: i32 = 38; // Get socket protocol
+pub const SO_DOMAIN: i32 = 39; // Get socket domain
+pub const SOMAXCONN: i32 = 4096; // Maximum listen() backlog
 
 // ===== TCP Options =====
 // Source: include/uapi/linux/tcp.h
@@ -24,8 +24,12 @@ pub const STACK_CUR: u64 = 8192 * 1024; // Soft limit for stack size (8MB)
 pub const STACK_MAX: u64 = 1 << 32; // Hard limit for stack size (4GB)
 
 // Resource identifiers
-pub const RLIMIT_STACK: u64 = 0; // Limit type for stack size
-pub const RLIMIT_NOFILE: u64 = 1; // Limit type for number of files
+pub const RLIMIT_DATA: u64 = 2; // Limit type for data segment size
+pub const RLIMIT_STACK: u64 = 3; // Limit type for stack size
+pub const RLIMIT_NOFILE: u64 = 7; // Limit type for number of files
+pub const RLIMIT_AS: u64 = 9; // Limit type for address space size
+
+pub const RLIM_INFINITY: u64 = u64::MAX; // Value of a limit that is not enforced
 
 // ===== Process Exit Status =====
 // Source: <stdlib.h> and POSIX standard
@@ -50,6 +54,7 @@ pub const SIGABRT: i32 = 6; // Abort program
 pub const SIGIOT: i32 = 6; // Alias for SIGABRT
 pub const SIGBUS: i32 = 7; // Bus error (bad memory access)
 pub const SIGFPE: i32 = 8; // Floating point exception
+pub const SIGKILL: i32 = 9; // Kill (cannot be caught or ignored)
 pub const SIGSEGV: i32 = 11; // Segmentation violation
 pub const SIGSYS: i32 = 31; // Bad system call
 pub const SIGUNUSED: i32 = 31; // Alias for SIGSYS
@@ -88,5 +93,16 @@ pub const SIG_BLOCK: i32 = 0; // Block signals in signal mask
 pub const SIG_UNBLOCK: i32 = 1; // Unblock signals in signal mask
 pub const SIG_SETMASK: i32 = 2; // Set the signal mask
 
+// Signal handler values
+// Source: include/uapi/asm-generic/signal-defs.h
+pub const SIG_DFL: u32 = 0; // Default signal handling
+pub const SIG_IGN: u32 = 1; // Ignore signal
+
+// Sigaction flags
+pub const SA_SIGINFO: i32 = 0x00000004; // Handler takes three arguments
+pub const SA_RESTART: i32 = 0x10000000; // Restart interrupted syscalls
+pub const SA_NODEFER: i32 = 0x40000000; // Don't block the signal while its handler runs
+pub const SA_RESETHAND: i32 = 0x80000000u32 as i32; // Reset to SIG_DFL on entry to the handler
+
 // Timer types
 pub const ITIMER_REAL: i32 = 0; // Real-time timer
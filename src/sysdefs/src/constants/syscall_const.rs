@@ -0,0 +1,135 @@
+//! Lind Syscall Number Constants
+//!
+//! Rust mirror of `glibc/lind_syscall/lind_syscall_num.h`. glibc issues every call through `MAKE_SYSCALL`
+//! with one of these numbers, and threei uses them to index its dispatch table. The two files must stay
+//! in sync; `threei/tests/syscall_table_test.rs` fails if they drift apart.
+#![allow(dead_code)]
+
+pub const ACCESS_SYSCALL: u64 = 2;
+pub const UNLINKAT_SYSCALL: u64 = 3;
+pub const UNLINK_SYSCALL: u64 = 4;
+pub const LINK_SYSCALL: u64 = 5;
+pub const RENAME_SYSCALL: u64 = 6;
+
+pub const XSTAT_SYSCALL: u64 = 9;
+pub const OPEN_SYSCALL: u64 = 10;
+pub const CLOSE_SYSCALL: u64 = 11;
+pub const READ_SYSCALL: u64 = 12;
+pub const WRITE_SYSCALL: u64 = 13;
+pub const LSEEK_SYSCALL: u64 = 14;
+pub const IOCTL_SYSCALL: u64 = 15;
+pub const TRUNCATE_SYSCALL: u64 = 16;
+pub const FXSTAT_SYSCALL: u64 = 17;
+pub const FTRUNCATE_SYSCALL: u64 = 18;
+pub const FSTATFS_SYSCALL: u64 = 19;
+pub const MREMAP_SYSCALL: u64 = 20;
+pub const MMAP_SYSCALL: u64 = 21;
+pub const MUNMAP_SYSCALL: u64 = 22;
+pub const GETDENTS_SYSCALL: u64 = 23;
+pub const DUP_SYSCALL: u64 = 24;
+pub const DUP2_SYSCALL: u64 = 25;
+pub const STATFS_SYSCALL: u64 = 26;
+pub const MPROTECT_SYSCALL: u64 = 27;
+pub const FCNTL_SYSCALL: u64 = 28;
+
+pub const GETPPID_SYSCALL: u64 = 29;
+pub const EXIT_SYSCALL: u64 = 30;
+pub const GETPID_SYSCALL: u64 = 31;
+
+pub const BIND_SYSCALL: u64 = 33;
+pub const SEND_SYSCALL: u64 = 34;
+pub const SENDTO_SYSCALL: u64 = 35;
+pub const RECV_SYSCALL: u64 = 36;
+pub const RECVFROM_SYSCALL: u64 = 37;
+pub const CONNECT_SYSCALL: u64 = 38;
+pub const LISTEN_SYSCALL: u64 = 39;
+pub const ACCEPT_SYSCALL: u64 = 40;
+pub const SENDMSG_SYSCALL: u64 = 41;
+pub const RECVMSG_SYSCALL: u64 = 42;
+
+pub const GETSOCKOPT_SYSCALL: u64 = 43;
+pub const SETSOCKOPT_SYSCALL: u64 = 44;
+pub const SHUTDOWN_SYSCALL: u64 = 45;
+pub const SELECT_SYSCALL: u64 = 46;
+pub const GETCWD_SYSCALL: u64 = 47;
+pub const POLL_SYSCALL: u64 = 48;
+pub const SOCKETPAIR_SYSCALL: u64 = 49;
+pub const GETUID_SYSCALL: u64 = 50;
+pub const GETEUID_SYSCALL: u64 = 51;
+pub const GETGID_SYSCALL: u64 = 52;
+pub const GETEGID_SYSCALL: u64 = 53;
+pub const FLOCK_SYSCALL: u64 = 54;
+pub const EPOLL_CREATE_SYSCALL: u64 = 56;
+pub const EPOLL_CTL_SYSCALL: u64 = 57;
+pub const EPOLL_WAIT_SYSCALL: u64 = 58;
+
+pub const SHMGET_SYSCALL: u64 = 62;
+pub const SHMAT_SYSCALL: u64 = 63;
+pub const SHMDT_SYSCALL: u64 = 64;
+pub const SHMCTL_SYSCALL: u64 = 65;
+
+pub const PIPE_SYSCALL: u64 = 66;
+pub const PIPE2_SYSCALL: u64 = 67;
+pub const FORK_SYSCALL: u64 = 68;
+pub const EXEC_SYSCALL: u64 = 69;
+
+pub const MUTEX_CREATE_SYSCALL: u64 = 70;
+pub const COND_CREATE_SYSCALL: u64 = 75;
+pub const COND_TIMEDWAIT_SYSCALL: u64 = 80;
+
+pub const SEM_TIMEDWAIT_SYSCALL: u64 = 94;
+pub const GETRLIMIT_SYSCALL: u64 = 97;
+pub const FUTEX_SYSCALL: u64 = 98;
+
+pub const SETUID_SYSCALL: u64 = 100;
+pub const SETGID_SYSCALL: u64 = 101;
+pub const SETREUID_SYSCALL: u64 = 102;
+pub const SETREGID_SYSCALL: u64 = 103;
+pub const SETRESUID_SYSCALL: u64 = 104;
+pub const SETRESGID_SYSCALL: u64 = 105;
+pub const GETRESUID_SYSCALL: u64 = 106;
+pub const GETRESGID_SYSCALL: u64 = 107;
+
+pub const GETHOSTNAME_SYSCALL: u64 = 125;
+pub const PREAD_SYSCALL: u64 = 126;
+pub const PWRITE_SYSCALL: u64 = 127;
+pub const CHDIR_SYSCALL: u64 = 130;
+pub const MKDIR_SYSCALL: u64 = 131;
+pub const RMDIR_SYSCALL: u64 = 132;
+pub const CHMOD_SYSCALL: u64 = 133;
+pub const FCHMOD_SYSCALL: u64 = 134;
+
+pub const SOCKET_SYSCALL: u64 = 136;
+
+pub const GETSOCKNAME_SYSCALL: u64 = 144;
+pub const GETPEERNAME_SYSCALL: u64 = 145;
+
+pub const SIGACTION_SYSCALL: u64 = 147;
+pub const KILL_SYSCALL: u64 = 148;
+pub const SIGPROCMASK_SYSCALL: u64 = 149;
+pub const SETITIMER_SYSCALL: u64 = 150;
+
+pub const SETRLIMIT_SYSCALL: u64 = 160;
+pub const FCHDIR_SYSCALL: u64 = 161;
+pub const FSYNC_SYSCALL: u64 = 162;
+pub const FDATASYNC_SYSCALL: u64 = 163;
+pub const SYNC_FILE_RANGE: u64 = 164;
+
+pub const READLINK_SYSCALL: u64 = 165;
+pub const READLINKAT_SYSCALL: u64 = 166;
+
+pub const WRITEV_SYSCALL: u64 = 170;
+
+pub const CLONE_SYSCALL: u64 = 171;
+pub const WAIT_SYSCALL: u64 = 172;
+pub const WAITPID_SYSCALL: u64 = 173;
+pub const BRK_SYSCALL: u64 = 175;
+pub const SBRK_SYSCALL: u64 = 176;
+
+pub const NANOSLEEP_TIME64_SYSCALL: u64 = 181;
+pub const CLOCK_GETTIME_SYSCALL: u64 = 191;
+
+// 3i calls, made by grates through the syscall table so they can be interposed
+pub const COPY_DATA_BETWEEN_CAGES_SYSCALL: u64 = 252;
+pub const COPY_HANDLER_TABLE_TO_CAGE_SYSCALL: u64 = 253;
+pub const HARSH_CAGE_EXIT_SYSCALL: u64 = 254;
@@ -1,6 +1,20 @@
 /// Need to replace by either linux number or purposed more appropriate error num
 /// Deregister/matchall should be large enough to avoid confusion
-pub const THREEI_DEREGISTER: u64 = 500;
+/// Deregister is passed in place of a cage id, so it must never be a valid one (see `MAX_CAGEID`)
+pub const THREEI_DEREGISTER: u64 = u64::MAX;
 pub const THREEI_MATCHALL: u64 = 501;
 pub const ELINDAPIABORTED: u64 = 0xFFFFFFFF;
 pub const ELINDESRCH: u64 = 0xFFFFFFFF;
+
+/// Exit types of `trigger_harsh_cage_exit`, used as the exit status of the cage. They are the signal
+/// that would have terminated the process on Linux
+/// The cage trapped (out of bounds memory access, unreachable, ...)
+pub const THREEI_EXIT_FAULT: u64 = 11;
+/// The cage was killed
+pub const THREEI_EXIT_KILLED: u64 = 9;
+
+/// Copy types of `copy_data_between_cages`
+/// Copy exactly `len` bytes
+pub const THREEI_COPYTYPE_RAW: u64 = 0;
+/// Copy a NUL-terminated string (terminator included) of at most `len` bytes
+pub const THREEI_COPYTYPE_STRING: u64 = 1;
@@ -51,6 +51,27 @@ pub struct StatData {
     pub st_ctim: (u64, u64),
 }
 
+//Record lock for fcntl F_GETLK, F_SETLK and F_SETLKW, laid out like glibc's struct flock64
+#[derive(Eq, PartialEq, Default, Copy, Clone, Debug)]
+#[repr(C)]
+pub struct FlockStruct {
+    pub l_type: i16,
+    pub l_whence: i16,
+    pub l_start: i64,
+    pub l_len: i64,
+    pub l_pid: i32,
+}
+
+//Terminal window size for ioctl TIOCGWINSZ
+#[derive(Eq, PartialEq, Default, Copy, Clone, Debug)]
+#[repr(C)]
+pub struct WinSize {
+    pub ws_row: u16,
+    pub ws_col: u16,
+    pub ws_xpixel: u16,
+    pub ws_ypixel: u16,
+}
+
 //R Limit for getrlimit system call
 #[repr(C)]
 pub struct Rlimit {
@@ -73,12 +94,13 @@ pub struct SockPair {
 }
 
 //EPOLL
+// glibc packs epoll_event on x86, so the guest struct is 12 bytes without padding
 #[derive(Copy, Clone, Debug)]
-#[repr(C)]
+#[repr(C, packed)]
 pub struct EpollEvent {
     pub events: u32,
-    pub fd: i32, //in native this is a union which could be one of a number of things
-                 //however, we only support EPOLL_CTL subcommands which take the fd
+    pub data: u64, //in native this is a union which could be one of a number of things,
+                   //we store it as is and hand it back to the guest in epoll_wait
 }
 
 #[repr(C)]
@@ -139,6 +161,38 @@ pub type SigsetType = u64;
 
 pub type IovecStruct = libc::iovec;
 
+/// `struct iovec` as laid out in the 32-bit wasm guest: both the base pointer and the length are
+/// 32 bits wide, so guest iovec arrays cannot be read as `IovecStruct` directly
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct WasmIovec {
+    pub iov_base: u32,
+    pub iov_len: u32,
+}
+
+/// `struct msghdr` as laid out in the 32-bit wasm guest, where pointers and `size_t` are 32 bits wide
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct WasmMsghdr {
+    pub msg_name: u32,
+    pub msg_namelen: u32,
+    pub msg_iov: u32,
+    pub msg_iovlen: u32,
+    pub msg_control: u32,
+    pub msg_controllen: u32,
+    pub msg_flags: i32,
+}
+
+/// `struct cmsghdr` as laid out in the 32-bit wasm guest. The data follows the header, and every
+/// control message is aligned to the 4 bytes of the guest `size_t`
+#[derive(Copy, Clone, Debug, Default)]
+#[repr(C)]
+pub struct WasmCmsghdr {
+    pub cmsg_len: u32,
+    pub cmsg_level: i32,
+    pub cmsg_type: i32,
+}
+
 #[derive(Copy, Clone, Debug, Default)]
 #[repr(C)]
 pub struct SigactionStruct {
@@ -1,44 +1,200 @@
-use super::threei::Raw_CallFunc;
+use super::threei::{
+    copy_data_between_cages, copy_handler_table_to_cage, harsh_cage_exit, Raw_CallFunc,
+};
 use rawposix::syscalls::fs_calls::{
-    brk_syscall, clock_gettime_syscall, close_syscall, dup2_syscall, dup_syscall, fcntl_syscall,
-    mkdir_syscall, mmap_syscall, munmap_syscall, nanosleep_time64_syscall, open_syscall,
-    pipe2_syscall, pipe_syscall, sbrk_syscall, write_syscall, futex_syscall,
+    access_syscall, brk_syscall, chdir_syscall, clock_gettime_syscall, close_syscall, dup2_syscall,
+    dup_syscall, fchdir_syscall, fcntl_syscall, flock_syscall, fstat_syscall, fstatfs_syscall,
+    ftruncate_syscall, futex_syscall, getcwd_syscall, getdents_syscall, ioctl_syscall,
+    link_syscall, lseek_syscall, mkdir_syscall, mmap_syscall, mprotect_syscall, mremap_syscall,
+    munmap_syscall, nanosleep_time64_syscall, open_syscall, pipe2_syscall, pipe_syscall,
+    pread_syscall, pwrite_syscall, read_syscall, readlink_syscall, readlinkat_syscall,
+    rename_syscall, rmdir_syscall, sbrk_syscall, shmat_syscall, shmctl_syscall, shmdt_syscall,
+    shmget_syscall, stat_syscall, statfs_syscall, truncate_syscall, unlink_syscall,
+    unlinkat_syscall, write_syscall, writev_syscall,
 };
 use rawposix::syscalls::sys_calls::{
-    exec_syscall, exit_syscall, fork_syscall, getpid_syscall, wait_syscall, waitpid_syscall,
+    exec_syscall, exit_syscall, fork_syscall, getegid_syscall, geteuid_syscall, getgid_syscall,
+    getpid_syscall, getppid_syscall, getresgid_syscall, getresuid_syscall, getrlimit_syscall,
+    getuid_syscall, kill_syscall, setgid_syscall, setitimer_syscall, setregid_syscall,
+    setresgid_syscall, setresuid_syscall, setreuid_syscall, setrlimit_syscall, setuid_syscall,
+    sigaction_syscall, sigprocmask_syscall, wait_syscall, waitpid_syscall,
+};
+use rawposix::syscalls::net_calls::{
+    accept_syscall, bind_syscall, connect_syscall, epoll_create_syscall, epoll_ctl_syscall,
+    epoll_wait_syscall, getpeername_syscall, getsockname_syscall, getsockopt_syscall,
+    listen_syscall, poll_syscall, recv_syscall, recvfrom_syscall, recvmsg_syscall,
+    select_syscall, send_syscall, sendmsg_syscall, sendto_syscall, setsockopt_syscall,
+    shutdown_syscall, socket_syscall, socketpair_syscall,
 };
-use rawposix::syscalls::net_calls::{socket_syscall,accept_syscall,bind_syscall,connect_syscall,listen_syscall,setsockopt_syscall,send_syscall,recv_syscall};
+use sysdefs::constants::err_const::{syscall_error, Errno};
+use sysdefs::constants::syscall_const::*;
+
+/// Size of the dense dispatch table. Every syscall number defined in `lind_syscall_num.h` must be
+/// strictly smaller than this value.
+pub const MAX_SYSCALLNUM: usize = 256;
 
-/// Will replace syscall number with Linux Standard after confirming the refactoring details
-pub const SYSCALL_TABLE: &[(u64, Raw_CallFunc)] = &[
-    (10, open_syscall),
-    (11, close_syscall),
-    (13, write_syscall),
-    (21, mmap_syscall),
-    (22, munmap_syscall),
-    (24, dup_syscall),
-    (25, dup2_syscall),
-    (28, fcntl_syscall),
-    (30, exit_syscall),
-    (31, getpid_syscall),
-    (33, bind_syscall),
-    (34, send_syscall),
-    (36, recv_syscall),
-    (38, connect_syscall),
-    (39, listen_syscall),
-    (40, accept_syscall),
-    (44, setsockopt_syscall),
-    (66, pipe_syscall),
-    (67, pipe2_syscall),
-    (68, fork_syscall),
-    (69, exec_syscall),
-    (98, futex_syscall),
-    (131, mkdir_syscall),
-    (136, socket_syscall),
-    (172, wait_syscall),
-    (173, waitpid_syscall),
-    (175, brk_syscall),
-    (176, sbrk_syscall),
-    (181, nanosleep_time64_syscall),
-    (191, clock_gettime_syscall),
+/// Every syscall number glibc can issue, as defined in `glibc/lind_syscall/lind_syscall_num.h`.
+///
+/// Each entry is (name in the glibc header, syscall number, rawposix implementation). Numbers
+/// without a rawposix implementation are `None` and return `ENOSYS` to the caller. This list is
+/// checked against the glibc header by `tests/syscall_table_test.rs`, so a new number added on
+/// either side must be added on the other.
+pub const LIND_SYSCALLS: &[(&str, u64, Option<Raw_CallFunc>)] = &[
+    ("ACCESS_SYSCALL", ACCESS_SYSCALL, Some(access_syscall)),
+    ("UNLINKAT_SYSCALL", UNLINKAT_SYSCALL, Some(unlinkat_syscall)),
+    ("UNLINK_SYSCALL", UNLINK_SYSCALL, Some(unlink_syscall)),
+    ("LINK_SYSCALL", LINK_SYSCALL, Some(link_syscall)),
+    ("RENAME_SYSCALL", RENAME_SYSCALL, Some(rename_syscall)),
+    ("XSTAT_SYSCALL", XSTAT_SYSCALL, Some(stat_syscall)),
+    ("OPEN_SYSCALL", OPEN_SYSCALL, Some(open_syscall)),
+    ("CLOSE_SYSCALL", CLOSE_SYSCALL, Some(close_syscall)),
+    ("READ_SYSCALL", READ_SYSCALL, Some(read_syscall)),
+    ("WRITE_SYSCALL", WRITE_SYSCALL, Some(write_syscall)),
+    ("LSEEK_SYSCALL", LSEEK_SYSCALL, Some(lseek_syscall)),
+    ("IOCTL_SYSCALL", IOCTL_SYSCALL, Some(ioctl_syscall)),
+    ("TRUNCATE_SYSCALL", TRUNCATE_SYSCALL, Some(truncate_syscall)),
+    ("FXSTAT_SYSCALL", FXSTAT_SYSCALL, Some(fstat_syscall)),
+    ("FTRUNCATE_SYSCALL", FTRUNCATE_SYSCALL, Some(ftruncate_syscall)),
+    ("FSTATFS_SYSCALL", FSTATFS_SYSCALL, Some(fstatfs_syscall)),
+    ("MREMAP_SYSCALL", MREMAP_SYSCALL, Some(mremap_syscall)),
+    ("MMAP_SYSCALL", MMAP_SYSCALL, Some(mmap_syscall)),
+    ("MUNMAP_SYSCALL", MUNMAP_SYSCALL, Some(munmap_syscall)),
+    ("GETDENTS_SYSCALL", GETDENTS_SYSCALL, Some(getdents_syscall)),
+    ("DUP_SYSCALL", DUP_SYSCALL, Some(dup_syscall)),
+    ("DUP2_SYSCALL", DUP2_SYSCALL, Some(dup2_syscall)),
+    ("STATFS_SYSCALL", STATFS_SYSCALL, Some(statfs_syscall)),
+    ("MPROTECT_SYSCALL", MPROTECT_SYSCALL, Some(mprotect_syscall)),
+    ("FCNTL_SYSCALL", FCNTL_SYSCALL, Some(fcntl_syscall)),
+    ("GETPPID_SYSCALL", GETPPID_SYSCALL, Some(getppid_syscall)),
+    ("EXIT_SYSCALL", EXIT_SYSCALL, Some(exit_syscall)),
+    ("GETPID_SYSCALL", GETPID_SYSCALL, Some(getpid_syscall)),
+    ("BIND_SYSCALL", BIND_SYSCALL, Some(bind_syscall)),
+    ("SEND_SYSCALL", SEND_SYSCALL, Some(send_syscall)),
+    ("SENDTO_SYSCALL", SENDTO_SYSCALL, Some(sendto_syscall)),
+    ("RECV_SYSCALL", RECV_SYSCALL, Some(recv_syscall)),
+    ("RECVFROM_SYSCALL", RECVFROM_SYSCALL, Some(recvfrom_syscall)),
+    ("CONNECT_SYSCALL", CONNECT_SYSCALL, Some(connect_syscall)),
+    ("LISTEN_SYSCALL", LISTEN_SYSCALL, Some(listen_syscall)),
+    ("ACCEPT_SYSCALL", ACCEPT_SYSCALL, Some(accept_syscall)),
+    ("SENDMSG_SYSCALL", SENDMSG_SYSCALL, Some(sendmsg_syscall)),
+    ("RECVMSG_SYSCALL", RECVMSG_SYSCALL, Some(recvmsg_syscall)),
+    ("GETSOCKOPT_SYSCALL", GETSOCKOPT_SYSCALL, Some(getsockopt_syscall)),
+    ("SETSOCKOPT_SYSCALL", SETSOCKOPT_SYSCALL, Some(setsockopt_syscall)),
+    ("SHUTDOWN_SYSCALL", SHUTDOWN_SYSCALL, Some(shutdown_syscall)),
+    ("SELECT_SYSCALL", SELECT_SYSCALL, Some(select_syscall)),
+    ("GETCWD_SYSCALL", GETCWD_SYSCALL, Some(getcwd_syscall)),
+    ("POLL_SYSCALL", POLL_SYSCALL, Some(poll_syscall)),
+    ("SOCKETPAIR_SYSCALL", SOCKETPAIR_SYSCALL, Some(socketpair_syscall)),
+    ("GETUID_SYSCALL", GETUID_SYSCALL, Some(getuid_syscall)),
+    ("GETEUID_SYSCALL", GETEUID_SYSCALL, Some(geteuid_syscall)),
+    ("GETGID_SYSCALL", GETGID_SYSCALL, Some(getgid_syscall)),
+    ("GETEGID_SYSCALL", GETEGID_SYSCALL, Some(getegid_syscall)),
+    ("FLOCK_SYSCALL", FLOCK_SYSCALL, Some(flock_syscall)),
+    ("EPOLL_CREATE_SYSCALL", EPOLL_CREATE_SYSCALL, Some(epoll_create_syscall)),
+    ("EPOLL_CTL_SYSCALL", EPOLL_CTL_SYSCALL, Some(epoll_ctl_syscall)),
+    ("EPOLL_WAIT_SYSCALL", EPOLL_WAIT_SYSCALL, Some(epoll_wait_syscall)),
+    ("SHMGET_SYSCALL", SHMGET_SYSCALL, Some(shmget_syscall)),
+    ("SHMAT_SYSCALL", SHMAT_SYSCALL, Some(shmat_syscall)),
+    ("SHMDT_SYSCALL", SHMDT_SYSCALL, Some(shmdt_syscall)),
+    ("SHMCTL_SYSCALL", SHMCTL_SYSCALL, Some(shmctl_syscall)),
+    ("PIPE_SYSCALL", PIPE_SYSCALL, Some(pipe_syscall)),
+    ("PIPE2_SYSCALL", PIPE2_SYSCALL, Some(pipe2_syscall)),
+    ("FORK_SYSCALL", FORK_SYSCALL, Some(fork_syscall)),
+    ("EXEC_SYSCALL", EXEC_SYSCALL, Some(exec_syscall)),
+    ("MUTEX_CREATE_SYSCALL", MUTEX_CREATE_SYSCALL, None),
+    ("COND_CREATE_SYSCALL", COND_CREATE_SYSCALL, None),
+    ("COND_TIMEDWAIT_SYSCALL", COND_TIMEDWAIT_SYSCALL, None),
+    ("SEM_TIMEDWAIT_SYSCALL", SEM_TIMEDWAIT_SYSCALL, None),
+    ("GETRLIMIT_SYSCALL", GETRLIMIT_SYSCALL, Some(getrlimit_syscall)),
+    ("FUTEX_SYSCALL", FUTEX_SYSCALL, Some(futex_syscall)),
+    ("SETUID_SYSCALL", SETUID_SYSCALL, Some(setuid_syscall)),
+    ("SETGID_SYSCALL", SETGID_SYSCALL, Some(setgid_syscall)),
+    ("SETREUID_SYSCALL", SETREUID_SYSCALL, Some(setreuid_syscall)),
+    ("SETREGID_SYSCALL", SETREGID_SYSCALL, Some(setregid_syscall)),
+    ("SETRESUID_SYSCALL", SETRESUID_SYSCALL, Some(setresuid_syscall)),
+    ("SETRESGID_SYSCALL", SETRESGID_SYSCALL, Some(setresgid_syscall)),
+    ("GETRESUID_SYSCALL", GETRESUID_SYSCALL, Some(getresuid_syscall)),
+    ("GETRESGID_SYSCALL", GETRESGID_SYSCALL, Some(getresgid_syscall)),
+    ("GETHOSTNAME_SYSCALL", GETHOSTNAME_SYSCALL, None),
+    ("PREAD_SYSCALL", PREAD_SYSCALL, Some(pread_syscall)),
+    ("PWRITE_SYSCALL", PWRITE_SYSCALL, Some(pwrite_syscall)),
+    ("CHDIR_SYSCALL", CHDIR_SYSCALL, Some(chdir_syscall)),
+    ("MKDIR_SYSCALL", MKDIR_SYSCALL, Some(mkdir_syscall)),
+    ("RMDIR_SYSCALL", RMDIR_SYSCALL, Some(rmdir_syscall)),
+    ("CHMOD_SYSCALL", CHMOD_SYSCALL, None),
+    ("FCHMOD_SYSCALL", FCHMOD_SYSCALL, None),
+    ("SOCKET_SYSCALL", SOCKET_SYSCALL, Some(socket_syscall)),
+    ("GETSOCKNAME_SYSCALL", GETSOCKNAME_SYSCALL, Some(getsockname_syscall)),
+    ("GETPEERNAME_SYSCALL", GETPEERNAME_SYSCALL, Some(getpeername_syscall)),
+    ("SIGACTION_SYSCALL", SIGACTION_SYSCALL, Some(sigaction_syscall)),
+    ("KILL_SYSCALL", KILL_SYSCALL, Some(kill_syscall)),
+    ("SIGPROCMASK_SYSCALL", SIGPROCMASK_SYSCALL, Some(sigprocmask_syscall)),
+    ("SETITIMER_SYSCALL", SETITIMER_SYSCALL, Some(setitimer_syscall)),
+    ("SETRLIMIT_SYSCALL", SETRLIMIT_SYSCALL, Some(setrlimit_syscall)),
+    ("FCHDIR_SYSCALL", FCHDIR_SYSCALL, Some(fchdir_syscall)),
+    ("FSYNC_SYSCALL", FSYNC_SYSCALL, None),
+    ("FDATASYNC_SYSCALL", FDATASYNC_SYSCALL, None),
+    ("SYNC_FILE_RANGE", SYNC_FILE_RANGE, None),
+    ("READLINK_SYSCALL", READLINK_SYSCALL, Some(readlink_syscall)),
+    ("READLINKAT_SYSCALL", READLINKAT_SYSCALL, Some(readlinkat_syscall)),
+    ("WRITEV_SYSCALL", WRITEV_SYSCALL, Some(writev_syscall)),
+    // clone is handled inside wasmtime (lind-common) and never reaches rawposix
+    ("CLONE_SYSCALL", CLONE_SYSCALL, None),
+    ("WAIT_SYSCALL", WAIT_SYSCALL, Some(wait_syscall)),
+    ("WAITPID_SYSCALL", WAITPID_SYSCALL, Some(waitpid_syscall)),
+    ("BRK_SYSCALL", BRK_SYSCALL, Some(brk_syscall)),
+    ("SBRK_SYSCALL", SBRK_SYSCALL, Some(sbrk_syscall)),
+    ("NANOSLEEP_TIME64_SYSCALL", NANOSLEEP_TIME64_SYSCALL, Some(nanosleep_time64_syscall)),
+    ("CLOCK_GETTIME_SYSCALL", CLOCK_GETTIME_SYSCALL, Some(clock_gettime_syscall)),
+    ("COPY_DATA_BETWEEN_CAGES_SYSCALL", COPY_DATA_BETWEEN_CAGES_SYSCALL, Some(copy_data_between_cages)),
+    ("COPY_HANDLER_TABLE_TO_CAGE_SYSCALL", COPY_HANDLER_TABLE_TO_CAGE_SYSCALL, Some(copy_handler_table_to_cage)),
+    ("HARSH_CAGE_EXIT_SYSCALL", HARSH_CAGE_EXIT_SYSCALL, Some(harsh_cage_exit)),
 ];
+
+/// Dense dispatch table indexed directly by syscall number, so `make_syscall` finds the handler
+/// in O(1).
+///
+/// - `None`: the number is not defined in `lind_syscall_num.h`
+/// - `Some(enosys_syscall)`: the number is defined by glibc but rawposix does not implement it yet
+/// - `Some(f)`: the rawposix implementation
+pub static SYSCALL_TABLE: [Option<Raw_CallFunc>; MAX_SYSCALLNUM] = build_syscall_table();
+
+/// Builds `SYSCALL_TABLE` from `LIND_SYSCALLS` at compile time. Out of range or duplicated syscall
+/// numbers fail the build.
+const fn build_syscall_table() -> [Option<Raw_CallFunc>; MAX_SYSCALLNUM] {
+    let mut table: [Option<Raw_CallFunc>; MAX_SYSCALLNUM] = [None; MAX_SYSCALLNUM];
+    let mut i = 0;
+    while i < LIND_SYSCALLS.len() {
+        let (_, num, handler) = LIND_SYSCALLS[i];
+        assert!((num as usize) < MAX_SYSCALLNUM, "syscall number exceeds MAX_SYSCALLNUM");
+        assert!(table[num as usize].is_none(), "duplicated syscall number in LIND_SYSCALLS");
+        table[num as usize] = match handler {
+            Some(func) => Some(func),
+            None => Some(enosys_syscall as Raw_CallFunc),
+        };
+        i += 1;
+    }
+    table
+}
+
+/// Placeholder for syscalls that glibc defines but rawposix has not implemented yet.
+///
+/// Return:
+///     - always fails with ENOSYS
+pub fn enosys_syscall(
+    _cageid: u64,
+    _arg1: u64,
+    _arg1_cageid: u64,
+    _arg2: u64,
+    _arg2_cageid: u64,
+    _arg3: u64,
+    _arg3_cageid: u64,
+    _arg4: u64,
+    _arg4_cageid: u64,
+    _arg5: u64,
+    _arg5_cageid: u64,
+    _arg6: u64,
+    _arg6_cageid: u64,
+) -> i32 {
+    syscall_error(Errno::ENOSYS, "syscall", "Function not implemented")
+}
@@ -3,7 +3,7 @@ use crate::syscall_table::SYSCALL_TABLE;
 use core::panic;
 use dashmap::DashSet;
 use once_cell::sync::Lazy;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::sync::{Arc, Mutex};
 
 /// ------------------------------------------------------------
@@ -42,10 +42,15 @@ pub fn threei_test_func(grateid: u64, mut callback: Box<dyn FnMut(
 }
 /// ------------------------------------------------------------
 
-// use cage::cage::get_cage;
-// use cage::memory::mem_helper::*;
+use cage::get_cage;
+use cage::memory::mem_helper::*;
+use sysdefs::constants::err_const::{syscall_error, Errno};
+use rawposix::syscalls::sys_calls::exit_syscall;
+use sysdefs::constants::syscall_const::{
+    COPY_HANDLER_TABLE_TO_CAGE_SYSCALL, FORK_SYSCALL, HARSH_CAGE_EXIT_SYSCALL,
+};
 use sysdefs::constants::threei_const;
-// use sysdefs::constants::{PROT_READ, PROT_WRITE}; // might be used on memcp, so keep them for now
+use sysdefs::constants::{PAGESIZE, PROT_READ, PROT_WRITE};
 
 const exit_syscallnum: u64 = 30; // Develop purpose only
 
@@ -196,19 +201,34 @@ fn check_cage_handler_exist(cageid: u64) -> bool {
 }
 
 /// Return value: <call_index_inside_grate, grateid>
+///
+/// A handler registered for `syscall_num` itself takes precedence over a match-all handler of the cage.
+/// `exit` and `harsh_cage_exit` are never caught by a match-all handler: the teardown of a cage must
+/// reach 3i even when its grate doesn't forward unknown calls, they are only interposed by a handler
+/// registered for them explicitly.
 fn get_handler(self_cageid: u64, syscall_num: u64) -> Option<(u64, u64)> {
     let handler_table = HANDLERTABLE.lock().unwrap();
-    
-    handler_table
-        .get(&self_cageid) // Get the first HashMap<u64, HashMap<u64, u64>>
-        .and_then(|sub_table| sub_table.get(&syscall_num)) // Get the second HashMap<u64, u64>
-        .and_then(|map| map.iter().next()) // Extract the first (key, value) pair
+    let sub_table = handler_table.get(&self_cageid)?; // Get the first HashMap<u64, HashMap<u64, u64>>
+
+    // Get the second HashMap<u64, u64> and extract the first (key, value) pair
+    let lookup = |callnum: u64| sub_table.get(&callnum).and_then(|map| map.iter().next());
+    let matchall_exempt = syscall_num == exit_syscallnum || syscall_num == HARSH_CAGE_EXIT_SYSCALL;
+    lookup(syscall_num)
+        .or_else(|| {
+            if matchall_exempt {
+                None
+            } else {
+                lookup(threei_const::THREEI_MATCHALL)
+            }
+        })
         .map(|(&call_index, &grateid)| (call_index, grateid)) // Convert to (u64, u64)
 }
 
-/// Remove all entries point to grate
+/// Remove all entries point to grate, and the handlers registered for the grate/cage itself, so a
+/// cage reusing the id doesn't inherit them
 fn rm_grate_from_handler(grateid: u64) {
     let mut table = HANDLERTABLE.lock().unwrap();
+    table.remove(&grateid);
     for (_, callmap) in table.iter_mut() {
         for (_, target_map) in callmap.iter_mut() {
             target_map.retain(|_, &mut dest_grateid| dest_grateid != grateid);
@@ -237,9 +257,20 @@ static EXITING_TABLE: Lazy<DashSet<u64>> = Lazy::new(|| DashSet::new());
 ///    ...)
 /// ```
 /// 
-/// TODO: 
-/// 1. match-all / deregister cases 
-/// 2. handle treat as function ptr not index (data structure will change)
+/// Passing `THREEI_MATCHALL` as `targetcallnum` registers `handlefunc` for every syscall of the target
+/// cage, except `exit` and `harsh_cage_exit`. Handlers registered for a specific syscall still take
+/// precedence over the match-all handler.
+///
+/// Passing `THREEI_DEREGISTER` as `handlefunccage` removes the handler of `targetcallnum` (or the
+/// match-all handler) for the target cage, so the call goes to rawposix again.
+///
+/// Return:
+///     - 0 on success, or if the exact same handler is already registered
+///     - EEXIST if the syscall is already handled by another function or grate, it has to be deregistered first
+///     - ELINDESRCH if either cage is exiting
+///
+/// TODO:
+/// 1. handle treat as function ptr not index (data structure will change)
 pub fn register_handler(
     _callnum: u64,
     targetcage: u64,    // Cage to modify
@@ -257,24 +288,45 @@ pub fn register_handler(
     _arg6cage: u64,
 ) -> i32 {
     // Make sure that both the cage that registers the handler and the cage being registered are valid (not in exited state)
-    if EXITING_TABLE.contains(&targetcage) && EXITING_TABLE.contains(&handlefunccage) {
+    if EXITING_TABLE.contains(&targetcage) || EXITING_TABLE.contains(&handlefunccage) {
         return threei_const::ELINDESRCH as i32;
     }
 
     let mut handler_table = HANDLERTABLE.lock().unwrap();
 
+    if handlefunccage == threei_const::THREEI_DEREGISTER {
+        if let Some(cage_entry) = handler_table.get_mut(&targetcage) {
+            cage_entry.remove(&targetcallnum);
+            if cage_entry.is_empty() {
+                // Cages without handlers skip the handler lookup in make_syscall
+                handler_table.remove(&targetcage);
+            }
+        }
+        return 0;
+    }
+
     if let Some(cage_entry) = handler_table.get(&targetcage) {
         // Check if targetcallnum exists
         if let Some(callnum_entry) = cage_entry.get(&targetcallnum) {
-            // Check if handlefunc exists
-            match callnum_entry.get(&handlefunc) {
-                Some(existing_dest_grateid) if *existing_dest_grateid == handlefunccage => return 0, // Do nothing
-                Some(_) => panic!("Already exists"),
-                None => {} // If `handlefunc` not exists, execute insertion
+            // A syscall is dispatched to a single handler, so any other registration is a conflict
+            match callnum_entry.iter().next() {
+                Some((&existing_handlefunc, &existing_dest_grateid))
+                    if existing_handlefunc == handlefunc && existing_dest_grateid == handlefunccage =>
+                {
+                    return 0 // Do nothing
+                }
+                Some(_) => {
+                    return syscall_error(
+                        Errno::EEXIST,
+                        "register_handler",
+                        "syscall already has a handler registered",
+                    )
+                }
+                None => {} // If no handler is left for the syscall, execute insertion
             }
         }
     }
-    
+
     handler_table
         .entry(targetcage)
         .or_insert_with(HashMap::new)
@@ -282,6 +334,11 @@ pub fn register_handler(
         .or_insert_with(HashMap::new)
         .insert(handlefunc, handlefunccage);
     // println!("[3i|register_handler] handler_table: {:?}", handler_table);
+    drop(handler_table);
+
+    // The grate handling the call needs to read the arguments from the cage and write results back
+    grant_copy_permission(targetcage, handlefunccage);
+    grant_copy_permission(handlefunccage, targetcage);
     0
 }
 
@@ -290,60 +347,73 @@ pub fn register_handler(
 /// add or remove entries.
 ///
 /// Note that this call is itself made through a syscall and is thus
-/// interposable.
-// pub fn copy_handler_table_to_cage(
-//     _callnum: u64,
-//     targetcage: u64,
-//     srccage: u64,
-//     _arg1cage: u64,
-//     _arg2: u64,
-//     _arg2cage: u64,
-//     _arg3: u64,
-//     _arg3cage: u64,
-//     _arg4: u64,
-//     _arg4cage: u64,
-//     _arg5: u64,
-//     _arg5cage: u64,
-//     _arg6: u64,
-//     _arg6cage: u64,
-// ) -> u64 {
-//     let mut handler_table = HANDLERTABLE.lock().unwrap();
-
-//     if let Some(srccage_entries) = handler_table.get(&srccage) {
-//         // Create new HashMap for target case
-//         let mut new_entries = HashMap::new();
-
-//         for (callnum, cage_call_table) in srccage_entries {
-//             let new_cage_call_table = Arc::new(Mutex::new({
-//                 // Deep copy CageCallTable
-//                 if let Ok(src_cage_call_table) = cage_call_table.lock() {
-//                     CageCallTable {
-//                         defaultcallfunc: src_cage_call_table
-//                             .defaultcallfunc
-//                             .as_ref()
-//                             .map(|funcs| funcs.clone()),
-//                         thiscalltable: src_cage_call_table.thiscalltable.clone(),
-//                     }
-//                 } else {
-//                     continue;
-//                 }
-//             }));
-
-//             new_entries.insert(*callnum, new_cage_call_table);
-//         }
+/// interposable. `make_syscall` issues it on behalf of the parent after every successful fork, so a
+/// grate registering a handler for `COPY_HANDLER_TABLE_TO_CAGE_SYSCALL` can veto or modify what the
+/// child inherits.
+///
+/// Any handler previously registered for `targetcage` is replaced. The grates handling the calls of
+/// `targetcage` are granted the same copy permissions as `register_handler` grants them.
+///
+/// Return:
+///     - 0 on success, including when `srccage` has no handler to copy
+///     - ELINDESRCH if either cage is exiting
+pub fn copy_handler_table_to_cage(
+    _cageid: u64,
+    targetcage: u64,
+    _targetcage_cageid: u64,
+    srccage: u64,
+    _srccage_cageid: u64,
+    _arg3: u64,
+    _arg3cage: u64,
+    _arg4: u64,
+    _arg4cage: u64,
+    _arg5: u64,
+    _arg5cage: u64,
+    _arg6: u64,
+    _arg6cage: u64,
+) -> i32 {
+    if EXITING_TABLE.contains(&targetcage) || EXITING_TABLE.contains(&srccage) {
+        return threei_const::ELINDESRCH as i32;
+    }
 
-//         handler_table.insert(targetcage, new_entries);
+    let mut handler_table = HANDLERTABLE.lock().unwrap();
 
-//         println!(
-//             "Successfully copied handler table entries from cage {} to cage {}",
-//             srccage, targetcage
-//         );
-//     } else {
-//         println!("No entries found for srccage {} in HANDLERTABLE", srccage);
-//         return threei_const::ELINDAPIABORTED;
-//     }
-//     0
-// }
+    let new_entries = match handler_table.get(&srccage) {
+        Some(srccage_entries) => srccage_entries.clone(),
+        None => {
+            // Nothing to inherit, the target cage calls rawposix directly
+            handler_table.remove(&targetcage);
+            return 0;
+        }
+    };
+    let grates: HashSet<u64> = new_entries
+        .values()
+        .flat_map(|target_map| target_map.values().copied())
+        .collect();
+    handler_table.insert(targetcage, new_entries);
+    drop(handler_table);
+
+    for grateid in grates {
+        grant_copy_permission(targetcage, grateid);
+        grant_copy_permission(grateid, targetcage);
+    }
+    0
+}
+
+/// Copies the handlers of `parent` to its new `child`. This goes through make_syscall on behalf of the
+/// parent so that its grates can interpose on the inheritance
+fn _inherit_handlers_on_fork(parent: u64, child: u64) {
+    make_syscall(
+        parent,
+        COPY_HANDLER_TABLE_TO_CAGE_SYSCALL,
+        parent,
+        child,
+        parent,
+        parent,
+        parent,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    );
+}
 
 /// `make_syscall` is simpler, which is to directly execute the system call that grate/cage wants to execute.
 /// But there are several special cases that need to be treated differently:
@@ -388,7 +458,10 @@ pub fn make_syscall(
     println!("[3i|make_syscall] syscallnum: {}, self_cageid: {}, target_cageid: {}", syscall_num, self_cageid, target_cageid);
     // Return error if the target cage/grate is exiting. We need to add this check beforehead, because make_syscall will also
     // contain cases that can directly redirect a syscall when self_cageid == target_id, which will bypass the handlertable check
-    if EXITING_TABLE.contains(&target_cageid) && syscall_num != exit_syscallnum {
+    if EXITING_TABLE.contains(&target_cageid)
+        && syscall_num != exit_syscallnum
+        && syscall_num != HARSH_CAGE_EXIT_SYSCALL
+    {
         return threei_const::ELINDESRCH as i32;
     }
 
@@ -399,6 +472,10 @@ pub fn make_syscall(
         if let Some((call_index, grateid)) = get_handler(self_cageid, syscall_num) {
             // <targetcage, targetcallnum, handlefunc_index_in_this_grate, this_grate_id>
             println!("[3i|make_syscall] grate call -- selfcageid: {}, syscallnum: {}, callindex: {}, grateid: {}", self_cageid, syscall_num, call_index, grateid);
+            // The grate is being torn down, its handlers are about to be removed
+            if EXITING_TABLE.contains(&grateid) {
+                return threei_const::ELINDESRCH as i32;
+            }
             // Theoretically, the complexity is O(1), shouldn't affect performance a lot
             if let Some(ret) = call_grate_func(
                 grateid,
@@ -411,11 +488,20 @@ pub fn make_syscall(
                 arg5, arg5_cageid,
                 arg6, arg6_cageid,
             ) {
+                // The grate may have handled the fork without forwarding it to 3i, so the child
+                // inherits the handlers here too
+                if syscall_num == FORK_SYSCALL && ret == 0 {
+                    _inherit_handlers_on_fork(self_cageid, arg1);
+                }
                 return ret;
             } else {
                 // syscall has been registered to register_handler but grate's entry function
                 // doesn't provide
-                panic!("[3i|make_syscall] grate call not found! grateid: {}", grateid);
+                return syscall_error(
+                    Errno::ENOSYS,
+                    "make_syscall",
+                    "grate entry function not found",
+                );
             }
         }
         
@@ -432,10 +518,14 @@ pub fn make_syscall(
         // currently all cages/grates will store closures in global_grate table, so we need to 
         // cleanup whatever its actually a cage/grate
         rm_from_global_grate(self_cageid);
+        rm_cage_from_permission(self_cageid);
     }
 
     // Regular case (call from cage/grate to rawposix)
-    if let Some(&(_, syscall_func)) = SYSCALL_TABLE.iter().find(|&&(num, _)| num == syscall_num) {
+    // SYSCALL_TABLE is indexed by syscall number; numbers glibc defines but rawposix doesn't
+    // implement yet resolve to a stub returning ENOSYS
+    let syscall_entry = SYSCALL_TABLE.get(syscall_num as usize).copied().flatten();
+    if let Some(syscall_func) = syscall_entry {
         let ret = syscall_func(
             target_cageid,
             arg1,
@@ -452,6 +542,17 @@ pub fn make_syscall(
             arg6_cageid,
         );
         // println!("[3i|make_syscall] regular syscallnum: {}, ret: {}, self_cageid: {}, target_cageid: {}", syscall_num, ret, self_cageid, target_cageid);
+
+        // The child (arg1) inherits the handlers of the forked cage. A fork forwarded by the grate
+        // interposing on it is skipped, the child inherits when the grate returns
+        if syscall_num == FORK_SYSCALL && ret == 0 {
+            let forwarded = self_cageid != target_cageid
+                && get_handler(target_cageid, FORK_SYSCALL)
+                    .is_some_and(|(_, grateid)| grateid == self_cageid);
+            if !forwarded {
+                _inherit_handlers_on_fork(target_cageid, arg1);
+            }
+        }
         return ret;
     } else {
         println!("[3i|make_syscall] Syscall number {} not found!", syscall_num);
@@ -466,189 +567,250 @@ pub fn make_syscall(
 /// new calls by adding to EXITING_TABLE and clean up resources. The call is only called from trusted modules
 /// or system kernel so we don't need selfcageid to check (we will remove from cage table directly)
 ///
+/// This is used when the memory state of the cage cannot be relied upon anymore, e.g. when the cage traps
+/// in wasmtime or is killed, so the cage doesn't go through its own `exit()`. `exittype` is used as the exit
+/// status of the cage (see `THREEI_EXIT_*`).
+///
+/// `harsh_cage_exit` is dispatched through `make_syscall` on behalf of the exiting cage, so the grates
+/// interposing on it are notified before the cage is torn down. Calls targeting the cage fail with
+/// ELINDESRCH from now on.
+///
 /// TODO:
 /// We want: This function cannot be called directly by user mode to ensure that it is only triggered by the
 /// system kernel or trusted modules
 /// Question: How we check the call is only called from trusted mode..?
-// pub fn trigger_harsh_cage_exit(targetcage: u64, exittype: u64) {
-//     // Use {} to specific the lock usage to avoid dead lock
-//     {
-//         let mut handler_table = HANDLERTABLE.lock().unwrap();
-//         // Remove exited cage entry from syscall handler
-//         if handler_table.remove(&targetcage).is_none() {
-//             panic!(
-//                 "targetcage {:?} entry not found in HANDLERTABLE when triggering harsh cage exit.",
-//                 targetcage
-//             );
-//         }
-//     }
+pub fn trigger_harsh_cage_exit(targetcage: u64, exittype: u64) {
+    // The cage is already being torn down (e.g. it trapped while being killed)
+    if !EXITING_TABLE.insert(targetcage) {
+        return;
+    }
 
-//     {
-//         EXITING_TABLE.insert(targetcage);
-//         // println!("Added targetcage {} to EXITING_TABLE", targetcage);
-//     }
+    make_syscall(
+        targetcage,
+        HARSH_CAGE_EXIT_SYSCALL,
+        targetcage,
+        targetcage,
+        targetcage,
+        exittype,
+        targetcage,
+        0, 0, 0, 0, 0, 0, 0, 0,
+    );
+}
 
-//     // TODO: replace call num with real exit_syscall num
-//     harsh_cage_exit(
-//         exit_syscallnum, // exit_syscall
-//         targetcage,
-//         exittype,
-//         0,
-//         0,
-//         0,
-//         0,
-//         0,
-//         0,
-//         0,
-//         0,
-//         0,
-//         0,
-//         0,
-//     );
-
-//     // The block of code is enclosed within curly braces to explicitly scope the lock on the `HANDLERTABLE`,
-//     // which ensures that the lock is released as soon as the operation within the block is completed.
-//     {
-//         let mut handler_table = HANDLERTABLE.lock().unwrap();
-//         // Update syscall handler to remove all access to exited cage
-//         handler_table.retain(|_self_cageid, callmap| {
-//             callmap.retain(|_callnum, cage_calltable| {
-//                 if let Ok(mut cage_calltable) = cage_calltable.lock() {
-//                     // Remove entries in `thiscalltable` where the destination cage ID matches `targetcage`
-//                     cage_calltable
-//                         .thiscalltable
-//                         .retain(|&dest_cageid, _| dest_cageid != targetcage);
-
-//                     // Check if both `thiscalltable` and `defaultcallfunc` are empty
-//                     !(cage_calltable.thiscalltable.is_empty()
-//                         && cage_calltable.defaultcallfunc.is_none())
-//                 } else {
-//                     // If we can't acquire the lock, keep the entry
-//                     true
-//                 }
-//             });
-
-//             // Retain the `callmap` only if it still contains entries
-//             !callmap.is_empty()
-//         });
-//     }
-// }
+/// 3i's version of the harsh exit, reached once every grate interposing on the call has been notified.
+/// It runs the `exit_syscall` cleanup of rawposix (fdtable, vmmap and zombie notification to the parent)
+/// without going through the handlers of the cage, then removes the cage from every 3i table.
+///
+/// Return:
+///     - 0 on success
+///     - ELINDESRCH if the cage doesn't exist or is not marked as exiting by `trigger_harsh_cage_exit`
+pub fn harsh_cage_exit(
+    _cageid: u64,
+    targetcage: u64, // Cage to cleanup
+    _targetcage_cageid: u64,
+    exittype: u64, // Exit type (e.g., fault, killed)
+    _exittype_cageid: u64,
+    _arg3: u64,
+    _arg3cage: u64,
+    _arg4: u64,
+    _arg4cage: u64,
+    _arg5: u64,
+    _arg5cage: u64,
+    _arg6: u64,
+    _arg6cage: u64,
+) -> i32 {
+    if !EXITING_TABLE.contains(&targetcage) || get_cage(targetcage).is_none() {
+        return threei_const::ELINDESRCH as i32;
+    }
 
-// pub fn harsh_cage_exit(
-//     callnum: u64,    // System call number (can be used if called as syscall)
-//     targetcage: u64, // Cage to cleanup
-//     exittype: u64,   // Exit type (e.g., fault, manual exit)
-//     _arg1cage: u64,
-//     _arg2: u64,
-//     _arg2cage: u64,
-//     _arg3: u64,
-//     _arg3cage: u64,
-//     _arg4: u64,
-//     _arg4cage: u64,
-//     _arg5: u64,
-//     _arg5cage: u64,
-//     _arg6: u64,
-//     _arg6cage: u64,
-// ) -> u64 {
-//     // Directly execute
-//     let result = make_syscall(targetcage, callnum, targetcage, exittype, targetcage, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0);
-
-//     // TODO:
-//     // This should align with specific exit type. Does different exit type mean different things..?
-//     // aka do we need to handle different situations here?
-//     if result != exittype as i32 {
-//         panic!("Error on exit() {}", result);
-//     }
+    // Remove the cage from the handler tables first, so no call is routed to or from it anymore
+    rm_grate_from_handler(targetcage);
+    rm_from_global_grate(targetcage);
+    rm_cage_from_permission(targetcage);
 
-//     {
-//         EXITING_TABLE.remove(&targetcage);
-//         // println!("Added targetcage {} to EXITING_TABLE", targetcage);
-//     }
+    exit_syscall(
+        targetcage,
+        exittype,
+        targetcage,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    );
 
-//     0
-// }
+    EXITING_TABLE.remove(&targetcage);
+    0
+}
 
 /***************************** copy_data_between_cages *****************************/
-// Validate the memory range for both source (`srcaddr -> srcaddr + srclen`) and destination (`destaddr -> destaddr + destlen`)
-// using the corresponding `vmmap` functions in RawPOSIX.
-//
-// First, check if the source range is valid and properly mapped.
-// Then, check if the destination range is valid:
-//  - If any part of the destination range is unmapped, attempt to map it using the appropriate `vmmap` function.
-//  - If the destination range becomes valid and satisfies the required permissions after mapping, proceed to
-//      perform the copy operation.
-// Otherwise, abort the operation if the mapping fails or permissions are insufficient.
-// pub fn copy_data_between_cages(
-//     callnum: u64,
-//     targetcage: u64,
-//     srcaddr: u64,
-//     srccage: u64,
-//     destaddr: u64,
-//     destcage: u64,
-//     len: u64,
-//     _arg3cage: u64,
-//     copytype: u64,
-//     _arg4cage: u64,
-//     _arg5: u64,
-//     _arg5cage: u64,
-//     _arg6: u64,
-//     _arg6cage: u64,
-// ) -> u64 {
-//     // Check address validity and permissions
-//     // Validate source address
-//     if !check_addr(srccage, srcaddr, len as usize, PROT_READ as i32).unwrap_or(false) {
-//         eprintln!("Source address is invalid.");
-//         return threei_const::ELINDAPIABORTED; // Error: Invalid address
-//     }
+/// PERMISSION_TABLE:
+/// <srccage, {destcage}>
+/// A cage can always copy within its own memory. Copying data out of `srccage` into another cage is only
+/// allowed if `destcage` is in the set of `srccage`. Entries are added when a grate registers a handler
+/// for a cage (both directions, so the grate can read arguments and write back results), or explicitly
+/// with `grant_copy_permission`.
+static PERMISSION_TABLE: Lazy<Mutex<HashMap<u64, HashSet<u64>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Allows data to be copied from `srccage` into `destcage`
+pub fn grant_copy_permission(srccage: u64, destcage: u64) {
+    let mut permission_table = PERMISSION_TABLE.lock().unwrap();
+    permission_table
+        .entry(srccage)
+        .or_insert_with(HashSet::new)
+        .insert(destcage);
+}
 
-//     // Validate destination address, and we will try to map if we don't the memory region
-//     // unmapping
-//     if !check_addr(destcage, destaddr, len as usize, PROT_WRITE as i32).unwrap_or(false) {
-//         eprintln!("Dest address is invalid.");
-//         return threei_const::ELINDAPIABORTED; // Error: Invalid address
-//     }
+/// Revokes the permission to copy data from `srccage` into `destcage`
+pub fn revoke_copy_permission(srccage: u64, destcage: u64) {
+    let mut permission_table = PERMISSION_TABLE.lock().unwrap();
+    if let Some(allowed_destinations) = permission_table.get_mut(&srccage) {
+        allowed_destinations.remove(&destcage);
+    }
+}
 
-//     // TODO:
-//     //  - Do we need to consider the permission relationship between cages..?
-//     //      ie: only parent cage can perfrom copy..?
-//     // if !_has_permission(srccage, destcage) {
-//     //     eprintln!("Permission denied between cages.");
-//     //     return threei_const::ELINDAPIABORTED; // Error: Permission denied
-//     // }
+/// Removes every permission from or to `cageid`, used when the cage exits
+fn rm_cage_from_permission(cageid: u64) {
+    let mut permission_table = PERMISSION_TABLE.lock().unwrap();
+    permission_table.remove(&cageid);
+    for (_, allowed_destinations) in permission_table.iter_mut() {
+        allowed_destinations.remove(&cageid);
+    }
+}
 
-//     // Perform the data copy
-//     unsafe {
-//         let src_ptr = srcaddr as *const u8;
-//         let dest_ptr = destaddr as *mut u8;
-//         std::ptr::copy_nonoverlapping(src_ptr, dest_ptr, len as usize);
-//     }
+/// Check if permissions allow data copying from `srccage` to `destcage`
+fn _has_permission(srccage: u64, destcage: u64) -> bool {
+    if srccage == destcage {
+        return true;
+    }
+    let permission_table = PERMISSION_TABLE.lock().unwrap();
+    permission_table
+        .get(&srccage)
+        .is_some_and(|allowed_destinations| allowed_destinations.contains(&destcage))
+}
 
-//     0
-// }
+/// Validates that `[addr, addr + len)` is mapped with `prot` in the vmmap of `cageid`, and translates it
+/// into a host address.
+fn _check_and_translate(cageid: u64, addr: u64, len: u64, prot: i32) -> Result<u64, Errno> {
+    // The region must stay inside the 32-bit linear memory of the cage
+    match addr.checked_add(len) {
+        Some(end) if end <= 1 << 32 => {}
+        _ => return Err(Errno::EFAULT),
+    }
+    let cage = get_cage(cageid).ok_or(Errno::ESRCH)?;
+    check_addr(cageid, addr, len as usize, prot)?;
+    translate_vmmap_addr(&cage, addr)
+}
 
-// -- Check if permissions allow data copying between cages
-// TODO:
-// How we handle permission relationship...?
-// fn _has_permission(srccage: u64, destcage: u64) -> bool {
-//     lazy_static::lazy_static! {
-//         static ref PERMISSION_TABLE: Mutex<HashMap<u64, HashSet<u64>>> = Mutex::new(HashMap::new());
-//     }
+/// Returns the length (terminator included) of the NUL-terminated string at `srcaddr` in `srccage`,
+/// reading at most `maxlen` bytes. Pages are validated one at a time while scanning, so a short string
+/// at the end of a mapping can be copied with a large `maxlen`.
+fn _strlen_in_cage(srccage: u64, srcaddr: u64, maxlen: u64) -> Result<u64, Errno> {
+    let mut scanned = 0;
+    while scanned < maxlen {
+        let addr = srcaddr + scanned;
+        let chunk = (PAGESIZE as u64 - addr % PAGESIZE as u64).min(maxlen - scanned);
+        let host_addr = _check_and_translate(srccage, addr, chunk, PROT_READ)?;
+        let bytes = unsafe { std::slice::from_raw_parts(host_addr as *const u8, chunk as usize) };
+        if let Some(pos) = bytes.iter().position(|&b| b == 0) {
+            return Ok(scanned + pos as u64 + 1);
+        }
+        scanned += chunk;
+    }
+    Err(Errno::ENAMETOOLONG)
+}
 
-//     // Check permission
-//     let permission_table = PERMISSION_TABLE.lock().unwrap();
-//     if let Some(allowed_destinations) = permission_table.get(&srccage) {
-//         if allowed_destinations.contains(&destcage) {
-//             return true;
-//         } else {
-//             eprintln!(
-//                 "Permission denied: Cage {} cannot access Cage {}.",
-//                 srccage, destcage
-//             );
-//             return false;
-//         }
-//     }
-//     false
-// }
+/// Copies data from the memory of `srccage` into the memory of `destcage`. Grates use it to move
+/// buffers (e.g. read results) between a cage's linear memory and their own. Addresses are user
+/// addresses in the linear memory of their respective cage.
+///
+/// The calling cage must be either `srccage` or `destcage`, so a cage can't move data between two other
+/// cages. The source range must be mapped readable and the destination range mapped writable in the
+/// vmmap of the corresponding cage, and `srccage` must have granted `destcage` the permission to copy its
+/// data (see `PERMISSION_TABLE`).
+///
+/// This call is registered in the syscall table, so it can be interposed like any other syscall.
+///
+/// Input:
+///     - cageid: the calling cage
+///     - srcaddr / srccage: source address and the cage it belongs to
+///     - destaddr / destcage: destination address and the cage it belongs to
+///     - len: number of bytes to copy, or the maximum length (terminator included) of a string
+///     - copytype: `THREEI_COPYTYPE_RAW` or `THREEI_COPYTYPE_STRING`
+///
+/// Return:
+///     - 0 on success
+///     - -EFAULT if a range is not mapped with the required permission, -EPERM if the caller is neither
+///       cage or the copy is not allowed, -ESRCH if a cage doesn't exist, -EINVAL for an unknown copy type, -ENAMETOOLONG if
+///       no NUL terminator is found within `len` bytes
+pub fn copy_data_between_cages(
+    cageid: u64,
+    srcaddr: u64,
+    srccage: u64,
+    destaddr: u64,
+    destcage: u64,
+    len: u64,
+    _len_cageid: u64,
+    copytype: u64,
+    _copytype_cageid: u64,
+    _arg5: u64,
+    _arg5_cageid: u64,
+    _arg6: u64,
+    _arg6_cageid: u64,
+) -> i32 {
+    if get_cage(srccage).is_none() || get_cage(destcage).is_none() {
+        return syscall_error(Errno::ESRCH, "copy_data_between_cages", "Cage does not exist");
+    }
+    if cageid != srccage && cageid != destcage {
+        return syscall_error(
+            Errno::EPERM,
+            "copy_data_between_cages",
+            "Caller is neither the source nor the destination cage",
+        );
+    }
+    if !_has_permission(srccage, destcage) {
+        return syscall_error(
+            Errno::EPERM,
+            "copy_data_between_cages",
+            "Permission denied between cages",
+        );
+    }
+
+    // Number of bytes to copy
+    let copylen = match copytype {
+        threei_const::THREEI_COPYTYPE_RAW => len,
+        threei_const::THREEI_COPYTYPE_STRING => match _strlen_in_cage(srccage, srcaddr, len) {
+            Ok(strlen) => strlen,
+            Err(e) => {
+                return syscall_error(e, "copy_data_between_cages", "Invalid source string");
+            }
+        },
+        _ => {
+            return syscall_error(Errno::EINVAL, "copy_data_between_cages", "Invalid copy type");
+        }
+    };
+    if copylen == 0 {
+        return 0;
+    }
+
+    // Check address validity and permissions
+    let src_ptr = match _check_and_translate(srccage, srcaddr, copylen, PROT_READ) {
+        Ok(addr) => addr as *const u8,
+        Err(e) => {
+            return syscall_error(e, "copy_data_between_cages", "Source address is invalid");
+        }
+    };
+    let dest_ptr = match _check_and_translate(destcage, destaddr, copylen, PROT_WRITE) {
+        Ok(addr) => addr as *mut u8,
+        Err(e) => {
+            return syscall_error(e, "copy_data_between_cages", "Dest address is invalid");
+        }
+    };
+
+    // Perform the data copy. Ranges may overlap when copying inside the same cage
+    unsafe {
+        std::ptr::copy(src_ptr, dest_ptr, copylen as usize);
+    }
+
+    0
+}
 
 // ---- CODE BELOW IS HELPER FUNCTIONS FOR TESTING ----
 pub fn testing_remove_cage_entry(target_cageid: u64) -> i32 {
@@ -0,0 +1,248 @@
+use cage::get_cage;
+use cage::memory::mem_helper::init_vmmap_helper;
+use cage::memory::vmmap::{MemoryBackingType, VmmapOps};
+use rawposix::syscalls::sys_calls::{fork_syscall, lindrustinit};
+use std::sync::Once;
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::{
+    MAP_ANONYMOUS, MAP_PRIVATE, PAGESHIFT, PAGESIZE, PROT_READ, PROT_WRITE,
+};
+use sysdefs::constants::syscall_const::COPY_DATA_BETWEEN_CAGES_SYSCALL;
+use sysdefs::constants::threei_const::{THREEI_COPYTYPE_RAW, THREEI_COPYTYPE_STRING};
+use threei::threei::{copy_data_between_cages, grant_copy_permission, make_syscall};
+
+/// Pages backed by host memory for every test cage
+const MEMORY_PAGES: u32 = 8;
+/// Pages registered in vmmap, the remaining pages are unmapped from the cage's point of view
+const MAPPED_PAGES: u32 = 4;
+/// Page mapped read-only in every test cage
+const READONLY_PAGE: u32 = 3;
+
+static INIT: Once = Once::new();
+
+/// Forks a new cage from the init cage, whose linear memory is a host anonymous mapping. Returns the
+/// host base address of the cage memory.
+fn init_test_cage(cageid: u64) -> *mut u8 {
+    INIT.call_once(|| lindrustinit(0));
+    assert_eq!(fork_syscall(1, cageid, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0), 0);
+
+    let memory_size = (MEMORY_PAGES as usize) << PAGESHIFT;
+    let base = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            memory_size,
+            PROT_READ | PROT_WRITE,
+            (MAP_PRIVATE | MAP_ANONYMOUS) as i32,
+            -1,
+            0,
+        )
+    };
+    assert_ne!(base, libc::MAP_FAILED);
+    init_vmmap_helper(cageid, base as usize, None);
+
+    let cage = get_cage(cageid).unwrap();
+    let mut vmmap = cage.vmmap.write();
+    for (page, prot) in [(0, PROT_READ | PROT_WRITE), (READONLY_PAGE, PROT_READ)] {
+        let npages = if page == 0 { READONLY_PAGE } else { 1 };
+        vmmap
+            .add_entry_with_overwrite(
+                page,
+                npages,
+                prot,
+                PROT_READ | PROT_WRITE,
+                (MAP_PRIVATE | MAP_ANONYMOUS) as i32,
+                MemoryBackingType::Anonymous,
+                0,
+                0,
+                cageid,
+            )
+            .unwrap();
+    }
+
+    base as *mut u8
+}
+
+fn copy(srcaddr: u64, srccage: u64, destaddr: u64, destcage: u64, len: u64, copytype: u64) -> i32 {
+    copy_as(srccage, srcaddr, srccage, destaddr, destcage, len, copytype)
+}
+
+/// Copies on behalf of the cage `cageid`
+fn copy_as(
+    cageid: u64,
+    srcaddr: u64,
+    srccage: u64,
+    destaddr: u64,
+    destcage: u64,
+    len: u64,
+    copytype: u64,
+) -> i32 {
+    copy_data_between_cages(
+        cageid, srcaddr, srccage, destaddr, destcage, len, 0, copytype, 0, 0, 0, 0, 0,
+    )
+}
+
+#[test]
+fn test_copy_raw_bytes_between_cages() {
+    let (src, dest) = (100, 101);
+    let src_base = init_test_cage(src);
+    let dest_base = init_test_cage(dest);
+
+    let data: Vec<u8> = (0..=255).collect();
+    unsafe { std::ptr::copy_nonoverlapping(data.as_ptr(), src_base.add(16), data.len()) };
+
+    // dest was never allowed to receive data from src
+    assert_eq!(
+        copy(16, src, 32, dest, data.len() as u64, THREEI_COPYTYPE_RAW),
+        -(Errno::EPERM as i32)
+    );
+
+    grant_copy_permission(src, dest);
+    assert_eq!(
+        copy(16, src, 32, dest, data.len() as u64, THREEI_COPYTYPE_RAW),
+        0
+    );
+    let copied = unsafe { std::slice::from_raw_parts(dest_base.add(32), data.len()) };
+    assert_eq!(copied, &data[..]);
+
+    // the permission is one-way
+    assert_eq!(
+        copy(32, dest, 16, src, 1, THREEI_COPYTYPE_RAW),
+        -(Errno::EPERM as i32)
+    );
+    // copying inside the same cage is always allowed, even with overlapping ranges
+    assert_eq!(copy(16, src, 17, src, 255, THREEI_COPYTYPE_RAW), 0);
+    assert_eq!(unsafe { *src_base.add(17 + 254) }, 254);
+}
+
+#[test]
+fn test_copy_requires_caller_to_be_source_or_destination() {
+    let (src, dest, third) = (108, 109, 110);
+    let src_base = init_test_cage(src);
+    let dest_base = init_test_cage(dest);
+    init_test_cage(third);
+    grant_copy_permission(src, dest);
+    grant_copy_permission(src, third);
+    grant_copy_permission(third, dest);
+
+    unsafe { std::ptr::copy_nonoverlapping(b"3i".as_ptr(), src_base, 2) };
+    // a third cage can't move data between two other cages, even if both allowed it to copy
+    assert_eq!(
+        copy_as(third, 0, src, 8, dest, 2, THREEI_COPYTYPE_RAW),
+        -(Errno::EPERM as i32)
+    );
+    assert_eq!(
+        unsafe { std::slice::from_raw_parts(dest_base.add(8), 2) },
+        b"\0\0"
+    );
+
+    // the source and the destination can both start the copy
+    assert_eq!(copy_as(src, 0, src, 8, dest, 1, THREEI_COPYTYPE_RAW), 0);
+    assert_eq!(copy_as(dest, 1, src, 9, dest, 1, THREEI_COPYTYPE_RAW), 0);
+    assert_eq!(
+        unsafe { std::slice::from_raw_parts(dest_base.add(8), 2) },
+        b"3i"
+    );
+}
+
+#[test]
+fn test_copy_checks_vmmap_permissions() {
+    let (src, dest) = (102, 103);
+    init_test_cage(src);
+    init_test_cage(dest);
+    grant_copy_permission(src, dest);
+
+    let readonly = (READONLY_PAGE * PAGESIZE) as u64;
+    let unmapped = (MAPPED_PAGES * PAGESIZE) as u64;
+
+    // read-only pages can be a source but not a destination
+    assert_eq!(copy(readonly, src, 0, dest, 64, THREEI_COPYTYPE_RAW), 0);
+    assert_eq!(
+        copy(0, src, readonly, dest, 64, THREEI_COPYTYPE_RAW),
+        -(Errno::EFAULT as i32)
+    );
+    // ranges crossing into unmapped pages
+    assert_eq!(
+        copy(unmapped - 8, src, 0, dest, 16, THREEI_COPYTYPE_RAW),
+        -(Errno::EFAULT as i32)
+    );
+    assert_eq!(
+        copy(0, src, u32::MAX as u64, dest, 16, THREEI_COPYTYPE_RAW),
+        -(Errno::EFAULT as i32)
+    );
+    // nonexistent cage and unknown copy type
+    assert_eq!(
+        copy(0, src, 0, 999, 16, THREEI_COPYTYPE_RAW),
+        -(Errno::ESRCH as i32)
+    );
+    assert_eq!(copy(0, src, 0, dest, 16, 42), -(Errno::EINVAL as i32));
+}
+
+#[test]
+fn test_copy_strings_with_max_length() {
+    let (src, dest) = (104, 105);
+    let src_base = init_test_cage(src);
+    let dest_base = init_test_cage(dest);
+    grant_copy_permission(src, dest);
+
+    let hello = b"hello\0garbage";
+    unsafe { std::ptr::copy_nonoverlapping(hello.as_ptr(), src_base, hello.len()) };
+    unsafe { std::ptr::write_bytes(dest_base, 0xff, 64) };
+
+    // only the string and its terminator are copied
+    assert_eq!(copy(0, src, 0, dest, 64, THREEI_COPYTYPE_STRING), 0);
+    let copied = unsafe { std::slice::from_raw_parts(dest_base, 7) };
+    assert_eq!(copied, b"hello\0\xff");
+
+    // the terminator must be found within the max length
+    assert_eq!(
+        copy(0, src, 0, dest, 5, THREEI_COPYTYPE_STRING),
+        -(Errno::ENAMETOOLONG as i32)
+    );
+
+    // a string ending right before unmapped memory can be copied with a larger max length
+    let last = (MAPPED_PAGES * PAGESIZE) as u64 - 3;
+    unsafe { std::ptr::copy_nonoverlapping(b"ab\0".as_ptr(), src_base.add(last as usize), 3) };
+    assert_eq!(copy(last, src, 128, dest, 4096, THREEI_COPYTYPE_STRING), 0);
+    assert_eq!(
+        unsafe { std::slice::from_raw_parts(dest_base.add(128), 3) },
+        b"ab\0"
+    );
+    // but not if it is not terminated before that
+    unsafe { *src_base.add(last as usize + 2) = b'c' };
+    assert_eq!(
+        copy(last, src, 128, dest, 4096, THREEI_COPYTYPE_STRING),
+        -(Errno::EFAULT as i32)
+    );
+}
+
+#[test]
+fn test_copy_is_dispatched_through_syscall_table() {
+    let (src, dest) = (106, 107);
+    let src_base = init_test_cage(src);
+    let dest_base = init_test_cage(dest);
+    grant_copy_permission(src, dest);
+
+    unsafe { std::ptr::copy_nonoverlapping(b"3i".as_ptr(), src_base, 2) };
+    let ret = make_syscall(
+        dest,
+        COPY_DATA_BETWEEN_CAGES_SYSCALL,
+        dest,
+        0,
+        src,
+        8,
+        dest,
+        2,
+        0,
+        THREEI_COPYTYPE_RAW,
+        0,
+        0,
+        0,
+        0,
+        0,
+    );
+    assert_eq!(ret, 0);
+    assert_eq!(
+        unsafe { std::slice::from_raw_parts(dest_base.add(8), 2) },
+        b"3i"
+    );
+}
@@ -0,0 +1,454 @@
+use rawposix::syscalls::sys_calls::{fork_syscall, lindrustinit, waitpid_syscall};
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Mutex, Once};
+use sysdefs::constants::err_const::Errno;
+use sysdefs::constants::fs_const::MAX_CAGEID;
+use sysdefs::constants::syscall_const::{
+    COPY_HANDLER_TABLE_TO_CAGE_SYSCALL, EXIT_SYSCALL, FORK_SYSCALL, GETPID_SYSCALL,
+    GETPPID_SYSCALL, HARSH_CAGE_EXIT_SYSCALL,
+};
+use sysdefs::constants::threei_const::{
+    ELINDESRCH, THREEI_DEREGISTER, THREEI_EXIT_FAULT, THREEI_EXIT_KILLED, THREEI_MATCHALL,
+};
+use threei::threei::{
+    make_syscall, register_handler, testing_remove_all, threei_test_func, trigger_harsh_cage_exit,
+    HANDLERTABLE,
+};
+
+/// Id of the grate handling the interposed calls, it only needs an entry function in 3i
+const GRATEID: u64 = 5;
+/// Grate functions return this plus their index, to tell them apart from rawposix results
+const GRATE_RET: i32 = 10000;
+
+static INIT: Once = Once::new();
+// The handler table and grate entry functions are global, so tests must not run concurrently
+static SERIAL: Mutex<()> = Mutex::new(());
+
+fn setup(cageid: u64) -> std::sync::MutexGuard<'static, ()> {
+    INIT.call_once(|| {
+        lindrustinit(0);
+        threei_test_func(
+            GRATEID,
+            Box::new(|call_index, _, _, _, _, _, _, _, _, _, _, _, _, _| {
+                GRATE_RET + call_index as i32
+            }),
+        );
+    });
+    let guard = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+    testing_remove_all();
+    assert_eq!(fork_syscall(1, cageid, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0), 0);
+    guard
+}
+
+fn register(targetcage: u64, targetcallnum: u64, handlefunc: u64, handlefunccage: u64) -> i32 {
+    register_handler(
+        0,
+        targetcage,
+        targetcallnum,
+        0,
+        handlefunc,
+        handlefunccage,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+/// Returns the (function, grate) handling `callnum` for `cageid`
+fn handler_of(cageid: u64, callnum: u64) -> Option<(u64, u64)> {
+    let handler_table = HANDLERTABLE.lock().unwrap();
+    handler_table
+        .get(&cageid)?
+        .get(&callnum)?
+        .iter()
+        .next()
+        .map(|(&handlefunc, &grateid)| (handlefunc, grateid))
+}
+
+fn syscall(cageid: u64, syscall_num: u64) -> i32 {
+    make_syscall(
+        cageid,
+        syscall_num,
+        cageid,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+#[test]
+fn test_register_conflict_returns_error() {
+    let cageid = 200;
+    let _guard = setup(cageid);
+
+    assert_eq!(register(cageid, GETPID_SYSCALL, 1, GRATEID), 0);
+    // registering the same handler again is a no-op
+    assert_eq!(register(cageid, GETPID_SYSCALL, 1, GRATEID), 0);
+    // another function or another grate cannot take over the call
+    assert_eq!(
+        register(cageid, GETPID_SYSCALL, 2, GRATEID),
+        -(Errno::EEXIST as i32)
+    );
+    assert_eq!(
+        register(cageid, GETPID_SYSCALL, 1, GRATEID + 1),
+        -(Errno::EEXIST as i32)
+    );
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), GRATE_RET + 1);
+
+    // once deregistered, the call can be handled by another function
+    assert_eq!(register(cageid, GETPID_SYSCALL, 0, THREEI_DEREGISTER), 0);
+    assert_eq!(register(cageid, GETPID_SYSCALL, 2, GRATEID), 0);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), GRATE_RET + 2);
+}
+
+#[test]
+fn test_deregister_single_call() {
+    let cageid = 201;
+    let _guard = setup(cageid);
+
+    assert_eq!(register(cageid, GETPID_SYSCALL, 1, GRATEID), 0);
+    assert_eq!(register(cageid, GETPPID_SYSCALL, 2, GRATEID), 0);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), GRATE_RET + 1);
+
+    // only the deregistered call goes back to rawposix
+    assert_eq!(register(cageid, GETPID_SYSCALL, 0, THREEI_DEREGISTER), 0);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), cageid as i32);
+    assert_eq!(syscall(cageid, GETPPID_SYSCALL), GRATE_RET + 2);
+
+    assert_eq!(register(cageid, GETPPID_SYSCALL, 0, THREEI_DEREGISTER), 0);
+    assert_eq!(syscall(cageid, GETPPID_SYSCALL), 1);
+    // deregistering a call that has no handler does nothing
+    assert_eq!(register(cageid, GETPPID_SYSCALL, 0, THREEI_DEREGISTER), 0);
+}
+
+#[test]
+fn test_deregister_is_not_a_cage_id() {
+    let cageid = 210;
+    let _guard = setup(cageid);
+    // any cage id below MAX_CAGEID can handle calls, including 500 which used to mean deregister
+    let grateid = 500;
+    assert!(THREEI_DEREGISTER >= MAX_CAGEID as u64);
+
+    assert_eq!(register(cageid, GETPID_SYSCALL, 1, grateid), 0);
+    assert_eq!(handler_of(cageid, GETPID_SYSCALL), Some((1, grateid)));
+    // cage 500 has no entry function in 3i
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), -(Errno::ENOSYS as i32));
+    // the handler is only replaced after deregistering it
+    assert_eq!(
+        register(cageid, GETPID_SYSCALL, 1, GRATEID),
+        -(Errno::EEXIST as i32)
+    );
+    assert_eq!(register(cageid, GETPID_SYSCALL, 0, THREEI_DEREGISTER), 0);
+    assert_eq!(handler_of(cageid, GETPID_SYSCALL), None);
+    // the cage has no handler left, so its table is dropped
+    assert!(!HANDLERTABLE.lock().unwrap().contains_key(&cageid));
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), cageid as i32);
+}
+
+#[test]
+fn test_matchall_interposes_every_call() {
+    let cageid = 202;
+    let _guard = setup(cageid);
+
+    assert_eq!(register(cageid, THREEI_MATCHALL, 3, GRATEID), 0);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), GRATE_RET + 3);
+    assert_eq!(syscall(cageid, GETPPID_SYSCALL), GRATE_RET + 3);
+
+    // a handler for a specific call takes precedence over the match-all one
+    assert_eq!(register(cageid, GETPID_SYSCALL, 1, GRATEID), 0);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), GRATE_RET + 1);
+    assert_eq!(syscall(cageid, GETPPID_SYSCALL), GRATE_RET + 3);
+
+    // deregistering match-all keeps the specific handlers
+    assert_eq!(register(cageid, THREEI_MATCHALL, 0, THREEI_DEREGISTER), 0);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), GRATE_RET + 1);
+    assert_eq!(syscall(cageid, GETPPID_SYSCALL), 1);
+}
+
+fn fork(parent: u64, child: u64) -> i32 {
+    make_syscall(
+        parent,
+        FORK_SYSCALL,
+        parent,
+        child,
+        parent,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+        0,
+    )
+}
+
+#[test]
+fn test_fork_inherits_handlers() {
+    let (parent, child, grandchild) = (203, 204, 205);
+    let _guard = setup(parent);
+
+    assert_eq!(register(parent, GETPID_SYSCALL, 1, GRATEID), 0);
+    assert_eq!(fork(parent, child), 0);
+    assert_eq!(syscall(child, GETPID_SYSCALL), GRATE_RET + 1);
+    assert_eq!(syscall(child, GETPPID_SYSCALL), parent as i32);
+
+    // the inherited table belongs to the child, and follows the whole process tree
+    assert_eq!(register(child, GETPID_SYSCALL, 0, THREEI_DEREGISTER), 0);
+    assert_eq!(register(child, GETPPID_SYSCALL, 2, GRATEID), 0);
+    assert_eq!(syscall(parent, GETPID_SYSCALL), GRATE_RET + 1);
+    assert_eq!(syscall(parent, GETPPID_SYSCALL), 1);
+    assert_eq!(fork(child, grandchild), 0);
+    assert_eq!(syscall(grandchild, GETPID_SYSCALL), grandchild as i32);
+    assert_eq!(syscall(grandchild, GETPPID_SYSCALL), GRATE_RET + 2);
+}
+
+#[test]
+fn test_fork_inheritance_can_be_interposed() {
+    let (parent, child) = (206, 207);
+    let _guard = setup(parent);
+
+    // the grate handles the copy itself and doesn't forward it, so the child escapes interposition
+    assert_eq!(register(parent, GETPID_SYSCALL, 1, GRATEID), 0);
+    assert_eq!(
+        register(parent, COPY_HANDLER_TABLE_TO_CAGE_SYSCALL, 4, GRATEID),
+        0
+    );
+    assert_eq!(fork(parent, child), 0);
+    assert_eq!(syscall(parent, GETPID_SYSCALL), GRATE_RET + 1);
+    assert_eq!(syscall(child, GETPID_SYSCALL), child as i32);
+}
+
+#[test]
+fn test_interposed_fork_inherits_handlers() {
+    let (parent, child) = (211, 212);
+    let _guard = setup(parent);
+    // grate that forks the cage itself, without forwarding the call to 3i
+    let forkgrate = 8;
+    threei_test_func(
+        forkgrate,
+        Box::new(|_, cageid, child, _, _, _, _, _, _, _, _, _, _, _| {
+            fork_syscall(cageid, child, cageid, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0)
+        }),
+    );
+
+    assert_eq!(register(parent, FORK_SYSCALL, 0, forkgrate), 0);
+    assert_eq!(register(parent, GETPID_SYSCALL, 1, GRATEID), 0);
+    assert_eq!(fork(parent, child), 0);
+    assert_eq!(handler_of(child, FORK_SYSCALL), Some((0, forkgrate)));
+    assert_eq!(syscall(child, GETPID_SYSCALL), GRATE_RET + 1);
+}
+
+#[test]
+fn test_forwarded_fork_inherits_handlers_once() {
+    let (parent, child) = (213, 214);
+    let _guard = setup(parent);
+    // Number of times the inheritance of the handlers was requested
+    static COPIES: AtomicI32 = AtomicI32::new(0);
+    COPIES.store(0, Ordering::SeqCst);
+
+    // grate forwarding the fork to 3i
+    let forkgrate = 8;
+    threei_test_func(
+        forkgrate,
+        Box::new(move |_, cageid, child, _, _, _, _, _, _, _, _, _, _, _| {
+            make_syscall(
+                forkgrate,
+                FORK_SYSCALL,
+                cageid,
+                child,
+                cageid,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+                0,
+            )
+        }),
+    );
+    // grate counting the copies of the handler table, which it forwards to 3i
+    let copygrate = 9;
+    threei_test_func(
+        copygrate,
+        Box::new(
+            move |_, cageid, child, _, srccage, _, _, _, _, _, _, _, _, _| {
+                COPIES.fetch_add(1, Ordering::SeqCst);
+                make_syscall(
+                    copygrate,
+                    COPY_HANDLER_TABLE_TO_CAGE_SYSCALL,
+                    cageid,
+                    child,
+                    cageid,
+                    srccage,
+                    cageid,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                )
+            },
+        ),
+    );
+
+    assert_eq!(register(parent, FORK_SYSCALL, 0, forkgrate), 0);
+    assert_eq!(
+        register(parent, COPY_HANDLER_TABLE_TO_CAGE_SYSCALL, 0, copygrate),
+        0
+    );
+    assert_eq!(register(parent, GETPID_SYSCALL, 1, GRATEID), 0);
+    assert_eq!(fork(parent, child), 0);
+    assert_eq!(COPIES.load(Ordering::SeqCst), 1);
+    assert_eq!(handler_of(child, FORK_SYSCALL), Some((0, forkgrate)));
+    assert_eq!(syscall(child, GETPID_SYSCALL), GRATE_RET + 1);
+}
+
+#[test]
+fn test_harsh_exit_notifies_grates_and_parent() {
+    let cageid = 208;
+    let _guard = setup(cageid);
+    // Result of a call made to the exiting cage while the grate is notified of the harsh exit
+    static INFLIGHT_RET: AtomicI32 = AtomicI32::new(0);
+
+    let observer = 6;
+    threei_test_func(
+        observer,
+        Box::new(
+            move |_, _, targetcage, _, exittype, _, _, _, _, _, _, _, _, _| {
+                let ret = make_syscall(
+                    observer,
+                    GETPID_SYSCALL,
+                    targetcage,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                );
+                INFLIGHT_RET.store(ret, Ordering::SeqCst);
+                // forward the call to 3i
+                make_syscall(
+                    observer,
+                    HARSH_CAGE_EXIT_SYSCALL,
+                    targetcage,
+                    targetcage,
+                    targetcage,
+                    exittype,
+                    targetcage,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                    0,
+                )
+            },
+        ),
+    );
+    assert_eq!(register(cageid, HARSH_CAGE_EXIT_SYSCALL, 1, observer), 0);
+
+    trigger_harsh_cage_exit(cageid, THREEI_EXIT_FAULT);
+    assert_eq!(INFLIGHT_RET.load(Ordering::SeqCst), ELINDESRCH as i32);
+
+    // the parent can reap the cage
+    assert_eq!(
+        waitpid_syscall(1, cageid, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0),
+        cageid as i32
+    );
+}
+
+#[test]
+fn test_harsh_exit_of_grate_removes_its_handlers() {
+    let (cageid, grateid) = (209, 7);
+    let _guard = setup(cageid);
+    assert_eq!(fork_syscall(1, grateid, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0), 0);
+    threei_test_func(
+        grateid,
+        Box::new(|_, _, _, _, _, _, _, _, _, _, _, _, _, _| GRATE_RET),
+    );
+
+    assert_eq!(register(cageid, GETPID_SYSCALL, 1, grateid), 0);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), GRATE_RET);
+
+    // the cage is not interposed anymore once its grate is gone
+    trigger_harsh_cage_exit(grateid, THREEI_EXIT_KILLED);
+    assert_eq!(syscall(cageid, GETPID_SYSCALL), cageid as i32);
+    assert_eq!(
+        waitpid_syscall(1, grateid, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0),
+        grateid as i32
+    );
+}
+
+#[test]
+fn test_matchall_does_not_catch_exits() {
+    let (cageid, exiting) = (215, 216);
+    let _guard = setup(cageid);
+    assert_eq!(fork_syscall(1, exiting, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0), 0);
+
+    // the grate handles every call itself, without forwarding any to 3i
+    for cage in [cageid, exiting] {
+        assert_eq!(register(cage, THREEI_MATCHALL, 3, GRATEID), 0);
+        assert_eq!(syscall(cage, GETPID_SYSCALL), GRATE_RET + 3);
+    }
+
+    // the harsh exit and exit still tear the cages down, the parent can reap them right away
+    let reap =
+        |child: u64| waitpid_syscall(1, child, 1, 0, 0, libc::WNOHANG as u64, 1, 0, 0, 0, 0, 0, 0);
+    trigger_harsh_cage_exit(cageid, THREEI_EXIT_FAULT);
+    assert_eq!(reap(cageid), cageid as i32);
+    assert_eq!(
+        make_syscall(
+            exiting,
+            EXIT_SYSCALL,
+            exiting,
+            0,
+            exiting,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+            0,
+        ),
+        0
+    );
+    assert_eq!(reap(exiting), exiting as i32);
+    assert_eq!(handler_of(exiting, THREEI_MATCHALL), None);
+}
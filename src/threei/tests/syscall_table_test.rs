@@ -0,0 +1,103 @@
+use std::collections::BTreeMap;
+
+use sysdefs::constants::err_const::{Errno, VERBOSE};
+use threei::syscall_table::{enosys_syscall, LIND_SYSCALLS, MAX_SYSCALLNUM, SYSCALL_TABLE};
+
+const LIND_SYSCALL_NUM_H: &str = concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/../glibc/lind_syscall/lind_syscall_num.h"
+);
+
+/// Collects every `#define NAME <number>` in glibc's `lind_syscall_num.h`.
+fn parse_glibc_syscall_numbers() -> BTreeMap<String, u64> {
+    let header = std::fs::read_to_string(LIND_SYSCALL_NUM_H)
+        .unwrap_or_else(|e| panic!("failed to read {}: {}", LIND_SYSCALL_NUM_H, e));
+
+    let mut defines = BTreeMap::new();
+    for line in header.lines() {
+        let mut tokens = line.split_whitespace();
+        if tokens.next() != Some("#define") {
+            continue;
+        }
+        let (Some(name), Some(value)) = (tokens.next(), tokens.next()) else {
+            continue;
+        };
+        if let Ok(num) = value.parse::<u64>() {
+            assert!(
+                defines.insert(name.to_string(), num).is_none(),
+                "{} defined twice in lind_syscall_num.h",
+                name
+            );
+        }
+    }
+    defines
+}
+
+#[test]
+fn test_syscall_table_matches_glibc_header() {
+    let glibc = parse_glibc_syscall_numbers();
+    assert!(!glibc.is_empty(), "no syscall numbers parsed from lind_syscall_num.h");
+
+    let rust: BTreeMap<String, u64> = LIND_SYSCALLS
+        .iter()
+        .map(|&(name, num, _)| (name.to_string(), num))
+        .collect();
+    assert_eq!(rust.len(), LIND_SYSCALLS.len(), "duplicated name in LIND_SYSCALLS");
+
+    for (name, num) in &glibc {
+        assert_eq!(
+            rust.get(name),
+            Some(num),
+            "{} = {} in lind_syscall_num.h is missing or different in LIND_SYSCALLS",
+            name,
+            num
+        );
+    }
+    for (name, num) in &rust {
+        assert_eq!(
+            glibc.get(name),
+            Some(num),
+            "{} = {} in LIND_SYSCALLS is not defined in lind_syscall_num.h",
+            name,
+            num
+        );
+    }
+}
+
+#[test]
+fn test_syscall_table_is_dense() {
+    let glibc = parse_glibc_syscall_numbers();
+
+    for num in 0..MAX_SYSCALLNUM as u64 {
+        let defined = glibc.values().any(|&n| n == num);
+        assert_eq!(
+            SYSCALL_TABLE[num as usize].is_some(),
+            defined,
+            "slot {} does not match lind_syscall_num.h",
+            num
+        );
+    }
+}
+
+#[test]
+fn test_unimplemented_syscall_returns_enosys() {
+    let _ = VERBOSE.set(0);
+
+    for &(name, num, handler) in LIND_SYSCALLS {
+        if handler.is_some() {
+            continue;
+        }
+        let slot = SYSCALL_TABLE[num as usize].expect("defined syscall has an empty slot");
+        assert_eq!(
+            slot as usize,
+            enosys_syscall as usize,
+            "{} has no implementation but is not routed to enosys_syscall",
+            name
+        );
+    }
+
+    assert_eq!(
+        enosys_syscall(1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0),
+        -(Errno::ENOSYS as i32)
+    );
+}
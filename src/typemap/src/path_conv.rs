@@ -73,3 +73,23 @@ pub fn add_lind_root(cageid: u64, path: &str) -> CString {
     let c_path = CString::new(full_path).unwrap();
     c_path
 }
+
+/// Reverse of `add_lind_root`: converts a path from host's perspective back to the path seen by the cage.
+/// This is used whenever the kernel hands back a host path (eg: `/proc/self/fd/<fd>` or a symlink target)
+/// so that `LIND_ROOT` is never exposed to the cage.
+///
+/// Input:
+///     - host_path: path location from host's perspective
+///
+/// Output:
+///     - the path from user's perspective, or `None` if `host_path` is not located under `LIND_ROOT`
+pub fn strip_lind_root(host_path: &str) -> Option<PathBuf> {
+    let rest = host_path.strip_prefix(LIND_ROOT)?;
+    if rest.is_empty() {
+        return Some(PathBuf::from("/"));
+    }
+    if !rest.starts_with('/') {
+        return None;
+    }
+    Some(PathBuf::from(rest))
+}
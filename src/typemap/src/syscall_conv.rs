@@ -8,9 +8,10 @@ use cage::get_cage;
 use cage::memory::mem_helper::*;
 use fdtables;
 use std::error::Error;
+use std::path::PathBuf;
 use std::str::Utf8Error;
 use sysdefs::constants::err_const::{syscall_error, Errno};
-use sysdefs::constants::fs_const::{MAX_CAGEID, PATH_MAX};
+use sysdefs::constants::fs_const::{FDKIND_KERNEL, MAX_CAGEID, PATH_MAX, PROT_READ};
 
 /// Translate a received virtual file descriptor (`virtual_fd`) to real kernel file descriptor.
 /// This function is not for security purpose. Always using arg_cageid to translate.
@@ -20,7 +21,7 @@ use sysdefs::constants::fs_const::{MAX_CAGEID, PATH_MAX};
 pub fn convert_fd_to_host(virtual_fd: u64, arg_cageid: u64, cageid: u64) -> i32 {
     #[cfg(feature = "secure")]
     {
-        if !validate_cageid(path_arg_cageid, cageid) {
+        if !validate_cageid(arg_cageid, cageid) {
             return -1;
         }
     }
@@ -30,6 +31,11 @@ pub fn convert_fd_to_host(virtual_fd: u64, arg_cageid: u64, cageid: u64) -> i32
         return -9;
     }
     let vfd = wrappedvfd.unwrap();
+    // Fds of other kinds (e.g. in-memory sockets) have no kernel fd behind them, their `underfd`
+    // must never reach the host
+    if vfd.fdkind != FDKIND_KERNEL {
+        return -9;
+    }
     // Actual kernel fd mapped with provided virtual fd
     vfd.underfd as i32
 }
@@ -51,20 +57,8 @@ pub fn convert_fd_to_host(virtual_fd: u64, arg_cageid: u64, cageid: u64) -> i32
 ///     - will return error if total length exceed the MAX_PATH (which is 4096). We use `Box<dyn Error>` here to
 ///      let upper functions do error handling. (ie: we want to )
 pub fn sc_convert_path_to_host(path_arg: u64, path_arg_cageid: u64, cageid: u64) -> CString {
-    #[cfg(feature = "secure")]
-    {
-        if !validate_cageid(path_arg_cageid, cageid) {
-            panic!("Invalide Cage ID");
-        }
-    }
-    let cage = get_cage(path_arg_cageid).unwrap();
-    let addr = translate_vmmap_addr(&cage, path_arg).unwrap();
-    let path = match get_cstr(addr) {
-        Ok(path) => path,
-        Err(e) => panic!("{:?}", e),
-    };
     // We will create a new variable in host process to handle the path value
-    let relpath = normpath(convpath(path), path_arg_cageid);
+    let relpath = sc_convert_path_to_cage(path_arg, path_arg_cageid, cageid);
     let relative_path = relpath.to_str().unwrap();
 
     #[cfg(feature = "secure")]
@@ -85,6 +79,34 @@ pub fn sc_convert_path_to_host(path_arg: u64, path_arg_cageid: u64, cageid: u64)
     }
 }
 
+/// This function translates the path pointer address from WASM environment to kernel system address, and
+/// normalizes the path against the cage's current working directory. Unlike `sc_convert_path_to_host`, the
+/// result is the path from user's perspective (without `LIND_ROOT`), which is what syscalls such as `chdir`
+/// need to record in the cage.
+///
+/// Input:
+///     - path_arg: the path pointer with address and contents from user's perspective
+///     - path_arg_cageid: the cage ID that owns the path pointer
+///     - cageid: the current running cage's ID
+///
+/// Output:
+///     - the normalized absolute path from user's perspective
+pub fn sc_convert_path_to_cage(path_arg: u64, path_arg_cageid: u64, cageid: u64) -> PathBuf {
+    #[cfg(feature = "secure")]
+    {
+        if !validate_cageid(path_arg_cageid, cageid) {
+            panic!("Invalide Cage ID");
+        }
+    }
+    let cage = get_cage(path_arg_cageid).unwrap();
+    let addr = translate_vmmap_addr(&cage, path_arg).unwrap();
+    let path = match get_cstr(addr) {
+        Ok(path) => path,
+        Err(e) => panic!("{:?}", e),
+    };
+    normpath(convpath(path), path_arg_cageid)
+}
+
 /// This function translates a memory address from the WASM environment (user space)
 /// to the corresponding host system address (kernel space). It is typically used when
 /// the guest application passes a pointer argument to a syscall, and we need to dereference
@@ -98,6 +120,9 @@ pub fn sc_convert_path_to_host(path_arg: u64, path_arg_cageid: u64, cageid: u64)
 /// Output:
 ///     - Returns a mutable pointer to host memory corresponding to the given address
 ///       from the guest. The pointer can be used for direct read/write operations.
+///
+/// The address is not checked against the vmmap of the cage, syscalls use
+/// `sc_convert_addr_to_host_checked` instead.
 pub fn sc_convert_addr_to_host(addr_arg: u64, addr_arg_cageid: u64, cageid: u64) -> *mut u8 {
     let cage = get_cage(addr_arg_cageid).unwrap();
     let addr = translate_vmmap_addr(&cage, addr_arg).unwrap() as *mut u8;
@@ -115,6 +140,9 @@ pub fn sc_convert_addr_to_host(addr_arg: u64, addr_arg_cageid: u64, cageid: u64)
 /// Output:
 ///     - Returns a constant (read-only) host pointer to the translated buffer.
 ///       Suitable for syscalls that only read from the buffer.
+///
+/// The buffer is not checked against the vmmap of the cage, syscalls use
+/// `sc_convert_buf_to_host_checked` instead.
 pub fn sc_convert_buf_to_host(buf_arg: u64, buf_arg_cageid: u64, cageid: u64) -> *const u8 {
     let cage = get_cage(buf_arg_cageid).unwrap();
     let addr = translate_vmmap_addr(&cage, buf_arg).unwrap() as *mut u8;
@@ -132,12 +160,87 @@ pub fn sc_convert_buf_to_host(buf_arg: u64, buf_arg_cageid: u64, cageid: u64) ->
 ///
 /// Output:
 ///     - Returns the translated 64-bit address in host space as a u64.
+///
+/// The address is not checked against the vmmap of the cage, syscalls use
+/// `sc_convert_uaddr_to_host_checked` instead.
 pub fn sc_convert_uaddr_to_host(uaddr_arg: u64, uaddr_arg_cageid: u64, cageid: u64) -> u64{
     let cage = get_cage(uaddr_arg_cageid).unwrap();
     let uaddr = translate_vmmap_addr(&cage, uaddr_arg).unwrap();
     return uaddr;
 }
 
+/// Translates the address of `len` bytes of guest memory that a syscall accesses into a host address,
+/// like `sc_convert_addr_to_host`, without ever panicking. With the `secure` feature, the whole region
+/// is checked against the vmmap of the cage before it is translated, so that the host never touches
+/// memory the guest hasn't mapped with `prot`.
+///
+/// Input:
+///     - addr_arg: the raw address from the user
+///     - addr_arg_cageid: the cage ID where the address belongs to
+///     - cageid: the current running cage's ID (used for checking context)
+///     - len: the number of bytes the syscall accesses, a region of 0 bytes is never checked
+///     - prot: PROT_READ if the syscall reads the region, PROT_WRITE if it writes it, or both
+///
+/// Output:
+///     - Ok(ptr): a mutable pointer to host memory corresponding to the given address
+///     - Err(Errno::EFAULT): the cage doesn't exist, or (with `secure`) the region isn't mapped in it
+///       with `prot`
+pub fn sc_convert_addr_to_host_checked(
+    addr_arg: u64,
+    addr_arg_cageid: u64,
+    cageid: u64,
+    len: usize,
+    prot: i32,
+) -> Result<*mut u8, Errno> {
+    sc_convert_uaddr_to_host_checked(addr_arg, addr_arg_cageid, cageid, len, prot)
+        .map(|addr| addr as *mut u8)
+}
+
+/// Translates a buffer of `len` bytes that a syscall only reads, like `sc_convert_buf_to_host`, checked
+/// as `sc_convert_addr_to_host_checked` does with PROT_READ.
+///
+/// Output:
+///     - Ok(ptr): a constant (read-only) host pointer to the translated buffer
+///     - Err(Errno::EFAULT): the buffer can't be read by the cage
+pub fn sc_convert_buf_to_host_checked(
+    buf_arg: u64,
+    buf_arg_cageid: u64,
+    cageid: u64,
+    len: usize,
+) -> Result<*const u8, Errno> {
+    sc_convert_uaddr_to_host_checked(buf_arg, buf_arg_cageid, cageid, len, PROT_READ)
+        .map(|addr| addr as *const u8)
+}
+
+/// Translates the address of `len` bytes of guest memory into a host address returned as a `u64`, like
+/// `sc_convert_uaddr_to_host`, checked as `sc_convert_addr_to_host_checked` does.
+///
+/// Output:
+///     - Ok(addr): the translated 64-bit address in host space
+///     - Err(Errno::EFAULT): the cage doesn't exist, or (with `secure`) the region isn't mapped in it
+///       with `prot`
+#[cfg_attr(not(feature = "secure"), allow(unused_variables))]
+pub fn sc_convert_uaddr_to_host_checked(
+    uaddr_arg: u64,
+    uaddr_arg_cageid: u64,
+    cageid: u64,
+    len: usize,
+    prot: i32,
+) -> Result<u64, Errno> {
+    #[cfg(feature = "secure")]
+    {
+        if !validate_cageid(uaddr_arg_cageid, cageid) {
+            return Err(Errno::EFAULT);
+        }
+        if len > 0 {
+            return check_and_convert_addr_ext(uaddr_arg_cageid, uaddr_arg, len, prot);
+        }
+    }
+
+    let cage = get_cage(uaddr_arg_cageid).ok_or(Errno::EFAULT)?;
+    translate_vmmap_addr(&cage, uaddr_arg)
+}
+
 pub unsafe fn charstar_to_ruststr<'a>(cstr: *const i8) -> Result<&'a str, Utf8Error> {
     std::ffi::CStr::from_ptr(cstr as *const _).to_str() //returns a result to be unwrapped later
 }
@@ -267,7 +370,7 @@ pub fn sc_unusedarg(arg: u64, arg_cageid: u64) -> bool {
     return true;
 
     #[cfg(feature = "secure")]
-    return !(arg | arg_cageid);
+    return (arg | arg_cageid) == 0;
 }
 
 /// This function translates the buffer pointer from user buffer address to system address, because we are
@@ -7,9 +7,9 @@
 // //! promotes secure, reliable access to memory and resources in a low-level systems environment.
 // use sysdefs::data::fs_struct;
 // use sysdefs::data::net_struct;
-use sysdefs::data::fs_struct::PipeArray;
+use sysdefs::data::fs_struct::{PipeArray, SockPair};
 use sysdefs::constants::err_const::{syscall_error, Errno};
-use crate::path_conv::LIND_ROOT;
+use crate::path_conv::{strip_lind_root, LIND_ROOT};
 use libc::*;
 use std::ptr;
 use sysdefs::*;
@@ -78,6 +78,44 @@ pub fn get_sockaddr(addr: *mut u8) -> (*mut libc::sockaddr, u32) {
     (finalsockaddr, addrlen)
 }
 
+/// copies a sockaddr returned by the kernel back to the user buffer, the reverse of `get_sockaddr`.
+/// If the socket is UNIX, the LIND_ROOT prefix is stripped from the path. As in Linux, the address
+/// is truncated to the size of the user buffer given in `addrlen`, and `addrlen` is updated with
+/// the actual length of the address.
+pub fn copy_out_sockaddr(addr: *mut u8, addrlen: *mut u32, sockaddr: &sockaddr_storage, len: u32) {
+    let mut hostaddr = *sockaddr;
+    let mut len = len as usize;
+
+    if hostaddr.ss_family as i32 == AF_UNIX {
+        let sockaddr_un_ptr = &mut hostaddr as *mut sockaddr_storage as *mut sockaddr_un;
+        let sun_path = unsafe { &mut (*sockaddr_un_ptr).sun_path };
+        let path_offset = size_of::<sa_family_t>();
+        let path_len = len.saturating_sub(path_offset).min(sun_path.len());
+        // unnamed and abstract sockets have no path to strip
+        let path: Vec<u8> = sun_path[..path_len]
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as u8)
+            .collect();
+        let stripped = std::str::from_utf8(&path).ok().and_then(strip_lind_root);
+        if let Some(stripped) = stripped {
+            let stripped = stripped.into_os_string().into_string().unwrap();
+            sun_path.fill(0);
+            for (dst, src) in sun_path.iter_mut().zip(stripped.bytes()) {
+                *dst = src as c_char;
+            }
+            // the path is followed by its null terminator, as the kernel does
+            len = path_offset + stripped.len() + 1;
+        }
+    }
+
+    unsafe {
+        let buflen = (*addrlen as usize).min(len);
+        ptr::copy_nonoverlapping(&hostaddr as *const sockaddr_storage as *const u8, addr, buflen);
+        *addrlen = len as u32;
+    }
+}
+
 
 
 // pub unsafe fn charstar_to_ruststr<'a>(cstr: *const i8) -> Result<&'a str, Utf8Error> {
@@ -266,17 +304,17 @@ pub fn get_pipearray<'a>(generic_argument: u64) -> Result<&'a mut PipeArray, i32
     ));
 }
 
-// pub fn get_sockpair<'a>(generic_argument: u64) -> Result<&'a mut SockPair, i32> {
-//     let pointer = generic_argument as *mut SockPair;
-//     if !pointer.is_null() {
-//         return Ok(unsafe { &mut *pointer });
-//     }
-//     return Err(syscall_error(
-//         Errno::EFAULT,
-//         "dispatcher",
-//         "input data not valid",
-//     ));
-// }
+pub fn get_sockpair<'a>(generic_argument: u64) -> Result<&'a mut SockPair, i32> {
+    let pointer = generic_argument as *mut SockPair;
+    if !pointer.is_null() {
+        return Ok(unsafe { &mut *pointer });
+    }
+    return Err(syscall_error(
+        Errno::EFAULT,
+        "dispatcher",
+        "input data not valid",
+    ));
+}
 
 // pub fn get_constsockaddr<'a>(generic_argument: u64) -> Result<&'a SockaddrDummy, i32> {
 //     let pointer = generic_argument as *const SockaddrDummy;
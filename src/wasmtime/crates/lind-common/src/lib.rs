@@ -1,10 +1,14 @@
 #![allow(dead_code)]
 
 use anyhow::Result;
+use cage::signal::{
+    lind_check_no_pending_signal, lind_get_deliverable_signal, lind_set_termination_signal,
+    lind_signal_handler_return, SignalDelivery,
+};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 use threei::threei::make_syscall;
-use wasmtime::Caller;
+use wasmtime::{Caller, Extern};
 use wasmtime_lind_multi_process::{clone_constants::CloneArgStruct, get_memory_base, LindHost};
 
 // lind-common serves as the main entry point when lind_syscall. Any syscalls made in glibc would reach here first,
@@ -69,7 +73,7 @@ impl LindCommonCtx {
             30 => wasmtime_lind_multi_process::exit_syscall(caller, arg1 as i32),
             // other syscalls goes into rawposix
             _ => {
-                make_syscall(
+                let ret = make_syscall(
                     self.pid as u64,
                     call_number as u64,
                     self.pid as u64, // Set target_cageid same with self_cageid by defualt
@@ -85,9 +89,73 @@ impl LindCommonCtx {
                     self.pid as u64,
                     arg6,
                     self.pid as u64,
-                )
+                );
+
+                // returning from a syscall is a safe point to run signal handlers, as the guest is not
+                // in the middle of any instruction and all host locks of the syscall are released.
+                // Syscalls blocked in waitpid, pipes or in-memory sockets are woken up by signals and
+                // return EINTR, the signal is then delivered here. Epoch interruption is another
+                // candidate safe point to deliver signals to cages that never make syscalls.
+                if !lind_check_no_pending_signal(self.pid as u64) && self.deliver_signals(caller) {
+                    // the cage is terminated by a signal, the return value will be discarded
+                    return 0;
+                }
+
+                ret
+            }
+        }
+    }
+
+    // deliver all pending signals that are not blocked by the calling thread. Returns true if the cage
+    // has been terminated by a signal, in which case nothing else should run in the guest
+    fn deliver_signals<
+        T: LindHost<T, U> + Clone + Send + 'static + std::marker::Sync,
+        U: Clone + Send + 'static + std::marker::Sync,
+    >(
+        &self,
+        caller: &mut Caller<'_, T>,
+    ) -> bool {
+        while let Some(delivery) = lind_get_deliverable_signal(self.pid as u64) {
+            match delivery {
+                SignalDelivery::Terminate { signo } => {
+                    // the signal is recorded apart from the exit code, waitpid() of the parent then
+                    // reports the cage as killed by it (see WIFSIGNALED)
+                    lind_set_termination_signal(self.pid as u64, signo);
+                    wasmtime_lind_multi_process::exit_syscall(caller, signo);
+                    return true;
+                }
+                SignalDelivery::Handler {
+                    signo,
+                    handler,
+                    oldmask,
+                } => {
+                    // sa_handler is the index of the handler in the guest's function table, which
+                    // requires the module to export its table (linked with --export-table)
+                    let func = match caller.get_export("__indirect_function_table") {
+                        Some(Extern::Table(table)) => table
+                            .get(&mut *caller, handler)
+                            .and_then(|r| r.as_func().flatten().copied()),
+                        _ => None,
+                    };
+                    match func.map(|f| f.typed::<i32, ()>(&*caller)) {
+                        Some(Ok(f)) => {
+                            if let Err(e) = f.call(&mut *caller, signo) {
+                                log::error!("signal handler for signal {} failed: {:?}", signo, e);
+                            }
+                        }
+                        _ => {
+                            log::error!(
+                                "invalid signal handler {} for signal {}, signal dropped",
+                                handler,
+                                signo
+                            );
+                        }
+                    }
+                    lind_signal_handler_return(self.pid as u64, oldmask);
+                }
             }
         }
+        false
     }
 
     // setjmp call. This function needs to be handled within wasmtime, but it is not an actual syscall so we use a different routine from lind_syscall
@@ -1,7 +1,8 @@
 #![allow(dead_code)]
 
 use anyhow::{anyhow, Result};
-use threei::threei::make_syscall;
+use sysdefs::constants::threei_const::THREEI_EXIT_FAULT;
+use threei::threei::{make_syscall, trigger_harsh_cage_exit};
 use wasmtime_lind_utils::lind_syscall_numbers::{EXEC_SYSCALL, EXIT_SYSCALL, FORK_SYSCALL};
 use wasmtime_lind_utils::{parse_env_var, LindCageManager};
 
@@ -13,7 +14,7 @@ use std::sync::{Arc, Barrier};
 use std::thread;
 use wasmtime::{
     AsContext, AsContextMut, Caller, ExternType, InstanceId, InstantiateType, Linker, Module,
-    OnCalledAction, RewindingReturn, SharedMemory, Store, StoreOpaque, Val,
+    OnCalledAction, RewindingReturn, SharedMemory, Store, StoreOpaque, Trap, Val,
 };
 
 use wasmtime_environ::MemoryIndex;
@@ -462,6 +463,13 @@ impl<
 
                         // print errors if any when running the child process
                         if let Err(err) = invoke_res {
+                            // a trap only takes down the faulting cage, the other cages keep running
+                            if err.is::<Trap>() {
+                                eprintln!("Error: {:?}", err);
+                                trigger_harsh_cage_exit(child_cageid, THREEI_EXIT_FAULT);
+                                lind_manager.decrement();
+                                return 0;
+                            }
                             let e = wasi_common::maybe_exit_on_error(err);
                             eprintln!("Error: {:?}", e);
                             return 0;
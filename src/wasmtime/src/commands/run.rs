@@ -9,12 +9,15 @@ use crate::common::{Profile, RunCommon, RunTarget};
 
 use anyhow::{anyhow, bail, Context as _, Error, Result};
 use clap::Parser;
+use rawposix::LoopbackPolicy;
 use std::ffi::OsString;
 use std::path::{Path, PathBuf};
 use std::sync::atomic::AtomicU64;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use threei::threei::make_syscall;
+use sysdefs::constants::sys_const::{DEFAULT_GID, DEFAULT_UID, RLIM_INFINITY};
+use sysdefs::constants::threei_const::THREEI_EXIT_FAULT;
+use threei::threei::{make_syscall, trigger_harsh_cage_exit};
 use wasi_common::sync::{ambient_authority, Dir, TcpListener, WasiCtxBuilder};
 use wasmtime::{
     AsContextMut, Engine, Func, InstantiateType, Module, Store, StoreLimits, Val,
@@ -44,6 +47,15 @@ fn parse_preloads(s: &str) -> Result<(String, PathBuf)> {
     Ok((parts[0].into(), parts[1].into()))
 }
 
+fn parse_loopback_policy(s: &str) -> Result<LoopbackPolicy> {
+    match s {
+        "kernel" => Ok(LoopbackPolicy::Kernel),
+        "unix" => Ok(LoopbackPolicy::Unix),
+        "all" => Ok(LoopbackPolicy::All),
+        _ => bail!("must be one of `kernel`, `unix` or `all`"),
+    }
+}
+
 /// Runs a WebAssembly module
 #[derive(Parser, PartialEq, Clone)]
 pub struct RunCommand {
@@ -64,6 +76,42 @@ pub struct RunCommand {
     )]
     pub preloads: Vec<(String, PathBuf)>,
 
+    /// User id the first cage runs as, used as its real, effective and saved user id
+    #[arg(long, value_name = "UID", default_value_t = DEFAULT_UID)]
+    pub uid: u32,
+
+    /// Group id the first cage runs as, used as its real, effective and saved group id
+    #[arg(long, value_name = "GID", default_value_t = DEFAULT_GID)]
+    pub gid: u32,
+
+    /// Most bytes of memory the first cage may map (RLIMIT_AS), inherited by the cages it forks.
+    /// Unlimited if not set
+    #[arg(long, value_name = "BYTES")]
+    pub rlimit_as: Option<u64>,
+
+    /// Most bytes of anonymous memory, the heap included, the first cage may map (RLIMIT_DATA),
+    /// inherited by the cages it forks. Unlimited if not set
+    #[arg(long, value_name = "BYTES")]
+    pub rlimit_data: Option<u64>,
+
+    /// Most bytes of memory all cages may map together. Unlimited if not set
+    #[arg(long, value_name = "BYTES")]
+    pub total_memory_limit: Option<u64>,
+
+    /// Which sockets are created in memory instead of on the host kernel: `kernel` for none, `unix`
+    /// for AF_UNIX sockets, `all` for AF_UNIX and AF_INET sockets (the latter only reach 127.0.0.0/8)
+    #[arg(
+        long,
+        value_name = "POLICY",
+        default_value = "kernel",
+        value_parser = parse_loopback_policy,
+    )]
+    pub loopback: LoopbackPolicy,
+
+    /// Create the pipes of `pipe()` and `pipe2()` in memory instead of on the host kernel
+    #[arg(long)]
+    pub inmemory_pipes: bool,
+
     /// The WebAssembly module to run and arguments to pass to it.
     ///
     /// Arguments passed to the wasm module will be configured as WASI CLI
@@ -190,6 +238,14 @@ impl RunCommand {
         }
 
         // Initialize Lind here
+        rawposix::set_initial_credentials(self.uid, self.gid);
+        rawposix::set_initial_memory_limits(
+            self.rlimit_as.unwrap_or(RLIM_INFINITY),
+            self.rlimit_data.unwrap_or(RLIM_INFINITY),
+        );
+        cage::memory::set_total_memory_limit(self.total_memory_limit.unwrap_or(RLIM_INFINITY));
+        rawposix::set_loopback_policy(self.loopback);
+        rawposix::set_inmemory_pipes(self.inmemory_pipes);
         rawposix::lindrustinit(0);
         // new cage is created
         lind_manager.increment();
@@ -247,6 +303,10 @@ impl RunCommand {
                 rawposix::lindrustfinalize();
             }
             Err(e) => {
+                // The main cage trapped, tear it down so its parent, children and grates are notified
+                if e.is::<wasmtime::Trap>() {
+                    trigger_harsh_cage_exit(1, THREEI_EXIT_FAULT);
+                }
                 // Exit the process if Wasmtime understands the error;
                 // otherwise, fall back on Rust's default error printing/return
                 // code.